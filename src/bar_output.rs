@@ -0,0 +1,159 @@
+//! The output (monitor) that the bar is shown on, so that blocks can be limited to some outputs,
+//! e.g. the battery to the bar on the laptop panel.
+//!
+//! i3 and sway run a status command for each bar, so each output needs a bar of its own. The
+//! output is given on the command line or found by asking i3 or sway over their IPC socket which
+//! outputs the bar that started us is on.
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use serde_json::Value as JsonValue;
+
+use crate::errors::*;
+
+lazy_static! {
+    /// Name of the output of the bar, if known
+    static ref OUTPUT: Mutex<Option<String>> = Mutex::new(None);
+}
+
+const MAGIC: &[u8] = b"i3-ipc";
+const GET_BAR_CONFIG: u32 = 6;
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sets the output of the bar to `name`, or else to the one that i3 or sway configured the bar for
+pub fn init(name: Option<&str>) {
+    let output = match name {
+        Some(name) => Some(name.to_string()),
+        None => match discover() {
+            Ok(output) => output,
+            Err(error) => {
+                log::debug!("couldn't find out the output of the bar: {:?}", error);
+                None
+            }
+        },
+    };
+    log::debug!("output of the bar: {:?}", output);
+    *OUTPUT.lock().unwrap() = output;
+}
+
+/// Whether a block with the `outputs` and `outputs_exclude` options is shown on this bar. Blocks
+/// are shown on bars whose output is unknown.
+pub fn shown(outputs: Option<&[String]>, outputs_exclude: Option<&[String]>) -> bool {
+    let output = OUTPUT.lock().unwrap();
+    let output = match &*output {
+        Some(output) => output,
+        None => return true,
+    };
+    outputs.is_none_or(|outputs| outputs.contains(output))
+        && !outputs_exclude.is_some_and(|exclude| exclude.contains(output))
+}
+
+/// The bar id in the command line of i3bar (`--bar_id=bar-0`) or swaybar (`-b bar-0`)
+fn bar_id(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(id) = arg.strip_prefix("--bar_id=") {
+            return Some(id.to_string());
+        }
+        if arg == "--bar_id" || arg == "-b" {
+            return args.next().cloned();
+        }
+    }
+    None
+}
+
+/// The bar id of the closest ancestor process that is a bar, as the status command may be run by
+/// a shell
+fn ancestor_bar_id() -> Option<String> {
+    let mut pid = std::os::unix::process::parent_id();
+    for _ in 0..3 {
+        let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+        let args: Vec<String> = cmdline
+            .split(|&byte| byte == 0)
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        let is_bar = args
+            .first()
+            .is_some_and(|program| program.ends_with("i3bar") || program.ends_with("swaybar"));
+        if is_bar {
+            return bar_id(&args);
+        }
+        // The fourth field of stat is the parent, after the name in parentheses
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        pid = stat
+            .rsplit(')')
+            .next()?
+            .split_whitespace()
+            .nth(1)?
+            .parse()
+            .ok()?;
+    }
+    None
+}
+
+/// Sends a message over the IPC of i3 or sway and returns the JSON of the reply
+fn ipc(message_type: u32, payload: &str) -> Result<JsonValue> {
+    let path = env::var("I3SOCK")
+        .or_else(|_| env::var("SWAYSOCK"))
+        .internal_error("bar_output", "neither I3SOCK nor SWAYSOCK is set")?;
+    let mut stream =
+        UnixStream::connect(path).internal_error("bar_output", "failed to connect to the IPC")?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .internal_error("bar_output", "failed to set a timeout")?;
+
+    let mut message = MAGIC.to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(message_type.to_ne_bytes());
+    message.extend(payload.as_bytes());
+    stream
+        .write_all(&message)
+        .internal_error("bar_output", "failed to send to the IPC")?;
+
+    let mut header = [0; 14];
+    stream
+        .read_exact(&mut header)
+        .internal_error("bar_output", "failed to read from the IPC")?;
+    let mut length = [0; 4];
+    length.copy_from_slice(&header[6..10]);
+    let mut reply = vec![0; u32::from_ne_bytes(length) as usize];
+    stream
+        .read_exact(&mut reply)
+        .internal_error("bar_output", "failed to read from the IPC")?;
+    serde_json::from_slice(&reply).internal_error("bar_output", "invalid reply of the IPC")
+}
+
+/// The output of the bar that started us, if it is configured for exactly one output
+fn discover() -> Result<Option<String>> {
+    let id = match ancestor_bar_id() {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let config = ipc(GET_BAR_CONFIG, &id)?;
+    Ok(match config["outputs"].as_array().map(Vec::as_slice) {
+        Some([JsonValue::String(output)]) if output != "*" => Some(output.clone()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_id() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        assert_eq!(
+            bar_id(&args("i3bar --bar_id=bar-1 --socket=/run/user/1000/i3/ipc")),
+            Some("bar-1".to_string())
+        );
+        assert_eq!(bar_id(&args("swaybar -b bar-0")), Some("bar-0".to_string()));
+        assert_eq!(bar_id(&args("swaybar")), None);
+    }
+}
@@ -1,90 +1,272 @@
+pub mod adb;
+#[cfg(feature = "http")]
+pub mod air_quality;
 pub mod apt;
 pub mod backlight;
+pub mod backup;
 pub mod base_block;
 pub mod battery;
+#[cfg(feature = "dbus")]
 pub mod bluetooth;
+#[cfg(feature = "dbus")]
+pub mod bluetooth_devices;
+pub mod bmc;
+pub mod breaks;
+pub mod calendar;
+pub mod cloud_instances;
+pub mod cloud_spend;
+pub mod countdown;
+pub mod counter;
 pub mod cpu;
 pub mod custom;
+#[cfg(feature = "dbus")]
 pub mod custom_dbus;
+pub mod daily_events;
+pub mod devenv;
+pub mod disk_cleanup;
 pub mod disk_space;
 pub mod dnf;
+#[cfg(feature = "http")]
 pub mod docker;
+pub mod domain_expiry;
+pub mod downloads;
+pub mod dyndns;
+pub mod focus;
+#[cfg(feature = "sway")]
 pub mod focused_window;
+#[cfg(feature = "http")]
 pub mod github;
+#[cfg(feature = "http")]
+pub mod gitlab;
+pub mod gpu;
+pub mod group;
+pub mod hdd_spin;
+#[cfg(feature = "http")]
+pub mod healthchecks;
+pub mod hearing;
 pub mod hueshift;
+#[cfg(feature = "dbus")]
 pub mod ibus;
+#[cfg(feature = "dbus")]
+pub mod idle_inhibitor;
+#[cfg(feature = "http")]
+pub mod karma;
+#[cfg(feature = "dbus")]
 pub mod kdeconnect;
+pub mod key_agents;
 pub mod keyboard_layout;
+pub mod kubernetes;
+pub mod libvirt;
+#[cfg(feature = "http")]
+pub mod live_streams;
 pub mod load;
+pub mod mail;
+pub mod mail_queue;
 #[cfg(feature = "maildir")]
 pub mod maildir;
+#[cfg(feature = "http")]
+pub mod mastodon;
 pub mod memory;
+pub mod mic_level;
+pub mod mqtt;
+#[cfg(feature = "dbus")]
 pub mod music;
 pub mod net;
+#[cfg(feature = "dbus")]
 pub mod networkmanager;
+#[cfg(feature = "dbus")]
 pub mod notify;
 #[cfg(feature = "notmuch")]
 pub mod notmuch;
 pub mod nvidia_gpu;
 pub mod pacman;
+#[cfg(feature = "dbus")]
+pub mod password_manager;
+pub mod ping;
+pub mod pipewire;
+#[cfg(feature = "http")]
+pub mod podcasts;
 pub mod pomodoro;
+pub mod privacy;
+pub mod process;
+#[cfg(feature = "http")]
+pub mod prometheus;
+pub mod remote;
 pub mod rofication;
+#[cfg(feature = "http")]
+pub mod rss;
+pub mod s3_backup;
+#[cfg(feature = "http")]
+pub mod scores;
+pub mod security;
+pub mod serial;
+pub mod sessions;
+pub mod slider;
+pub mod snmp;
 pub mod sound;
 pub mod speedtest;
+pub mod ssd_wear;
+pub mod stopwatch;
+pub mod sun_moon;
 pub mod supertoggle;
+#[cfg(feature = "dbus")]
+pub mod systemd;
+pub mod tailscale;
 pub mod taskwarrior;
 pub mod temperature;
 pub mod template;
+#[cfg(feature = "http")]
+pub mod ticker;
 pub mod time;
+pub mod timer;
 pub mod toggle;
+pub mod trash;
+pub mod typing;
+pub mod updates;
+pub mod ups;
 pub mod uptime;
+pub mod vpn;
 pub mod watson;
+#[cfg(feature = "http")]
 pub mod weather;
+pub mod worldclock;
 pub mod xrandr;
+pub mod yubikey;
 
+use self::adb::*;
+#[cfg(feature = "http")]
+use self::air_quality::*;
 use self::apt::*;
 use self::backlight::*;
+use self::backup::*;
 use self::base_block::*;
 use self::battery::*;
+#[cfg(feature = "dbus")]
 use self::bluetooth::*;
+#[cfg(feature = "dbus")]
+use self::bluetooth_devices::*;
+use self::bmc::*;
+use self::breaks::*;
+use self::calendar::*;
+use self::cloud_instances::*;
+use self::cloud_spend::*;
+use self::countdown::*;
+use self::counter::*;
 use self::cpu::*;
 use self::custom::*;
+#[cfg(feature = "dbus")]
 use self::custom_dbus::*;
+use self::daily_events::*;
+use self::devenv::*;
+use self::disk_cleanup::*;
 use self::disk_space::*;
 use self::dnf::*;
+#[cfg(feature = "http")]
 use self::docker::*;
+use self::domain_expiry::*;
+use self::downloads::*;
+use self::dyndns::*;
+use self::focus::*;
+#[cfg(feature = "sway")]
 use self::focused_window::*;
+#[cfg(feature = "http")]
 use self::github::*;
+#[cfg(feature = "http")]
+use self::gitlab::*;
+use self::gpu::*;
+use self::group::*;
+use self::hdd_spin::*;
+#[cfg(feature = "http")]
+use self::healthchecks::*;
+use self::hearing::*;
 use self::hueshift::*;
+#[cfg(feature = "dbus")]
 use self::ibus::*;
+#[cfg(feature = "dbus")]
+use self::idle_inhibitor::*;
+#[cfg(feature = "http")]
+use self::karma::*;
+#[cfg(feature = "dbus")]
 use self::kdeconnect::*;
+use self::key_agents::*;
 use self::keyboard_layout::*;
+use self::kubernetes::*;
+use self::libvirt::*;
+#[cfg(feature = "http")]
+use self::live_streams::*;
 use self::load::*;
+use self::mail::*;
+use self::mail_queue::*;
 #[cfg(feature = "maildir")]
 use self::maildir::*;
+#[cfg(feature = "http")]
+use self::mastodon::*;
 use self::memory::*;
+use self::mic_level::*;
+use self::mqtt::*;
+#[cfg(feature = "dbus")]
 use self::music::*;
 use self::net::*;
+#[cfg(feature = "dbus")]
 use self::networkmanager::*;
+#[cfg(feature = "dbus")]
 use self::notify::*;
 #[cfg(feature = "notmuch")]
 use self::notmuch::*;
 use self::nvidia_gpu::*;
 use self::pacman::*;
+#[cfg(feature = "dbus")]
+use self::password_manager::*;
+use self::ping::*;
+use self::pipewire::*;
+#[cfg(feature = "http")]
+use self::podcasts::*;
 use self::pomodoro::*;
+use self::privacy::*;
+use self::process::*;
+#[cfg(feature = "http")]
+use self::prometheus::*;
+use self::remote::*;
 use self::rofication::*;
+#[cfg(feature = "http")]
+use self::rss::*;
+use self::s3_backup::*;
+#[cfg(feature = "http")]
+use self::scores::*;
+use self::security::*;
+use self::serial::*;
+use self::sessions::*;
+use self::slider::*;
+use self::snmp::*;
 use self::sound::*;
 use self::speedtest::*;
+use self::ssd_wear::*;
+use self::stopwatch::*;
+use self::sun_moon::*;
 use self::supertoggle::*;
+#[cfg(feature = "dbus")]
+use self::systemd::*;
+use self::tailscale::*;
 use self::taskwarrior::*;
 use self::temperature::*;
 use self::template::*;
+#[cfg(feature = "http")]
+use self::ticker::*;
 use self::time::*;
+use self::timer::*;
 use self::toggle::*;
+use self::trash::*;
+use self::typing::*;
+use self::updates::*;
+use self::ups::*;
 use self::uptime::*;
+use self::vpn::*;
 use self::watson::*;
+#[cfg(feature = "http")]
 use self::weather::*;
+use self::worldclock::*;
 use self::xrandr::*;
+use self::yubikey::*;
 
 use std::time::Duration;
 
@@ -94,6 +276,7 @@ use toml::value::Value;
 
 use crate::config::SharedConfig;
 use crate::errors::*;
+use crate::menu::{Launcher, MenuEntry};
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
 use crate::widgets::I3BarWidget;
@@ -183,6 +366,43 @@ pub trait Block {
     fn click(&mut self, _event: &I3BarEvent) -> Result<()> {
         Ok(())
     }
+
+    /// Called once when the bar is terminated or the block is replaced, e.g. by a config reload,
+    /// to undo what the block changed outside of the bar, e.g. to release an inhibitor or to stop
+    /// time tracking. The block is dropped right after, so threads and subprocesses that only
+    /// serve it are better ended by `Drop`, or by noticing that the state they share is gone.
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the block must only be shown on the bar and not be published with `--headless`
+    fn private(&self) -> bool {
+        false
+    }
+
+    /// Entries of a menu that a right click shows in the `menu_launcher` instead of calling
+    /// `click`, if the block has one
+    fn menu(&mut self) -> Result<Option<Vec<MenuEntry>>> {
+        Ok(None)
+    }
+
+    /// Marks the block as clicked, to show that a click was received while it is handled
+    fn press(&mut self) {}
+
+    /// Whether the block was clicked so recently that it is still shown as such
+    fn pressed(&self) -> bool {
+        false
+    }
+
+    /// Whether the block hasn't updated for longer than its `watchdog` allows, see `BaseBlock`
+    fn stale(&self) -> bool {
+        false
+    }
+
+    /// Whether any of the block's widgets has to be redrawn regularly, e.g. to scroll its text
+    fn animated(&self) -> bool {
+        self.view().iter().any(|widget| widget.animated())
+    }
 }
 
 macro_rules! block {
@@ -192,6 +412,23 @@ macro_rules! block {
         let mut common_config = BaseBlockConfig::deserialize(common_config)
             .configuration_error("Failed to deserialize common block config.")?;
 
+        // Leave out blocks that don't apply to this machine before they can fail
+        if let Some(ref requires) = common_config.requires {
+            if !crate::capabilities::satisfied(requires)? {
+                return Ok(Box::new(Unavailable { id: $id }));
+            }
+        }
+        if !crate::bar_output::shown(
+            common_config.outputs.as_deref(),
+            common_config.outputs_exclude.as_deref(),
+        ) {
+            return Ok(Box::new(Unavailable { id: $id }));
+        }
+
+        common_config.apply_short_format(&mut $block_config)?;
+        let on_click_modifiers = common_config.modifier_commands();
+        let on_buttons = common_config.button_commands();
+
         // Apply theme overrides if presented
         if let Some(ref overrides) = common_config.theme_overrides {
             $shared_config.theme_override(overrides)?;
@@ -199,12 +436,42 @@ macro_rules! block {
         if let Some(overrides) = common_config.icons_format {
             $shared_config.icons_format_override(overrides);
         }
+        if let Some(ref overrides) = common_config.icons_overrides {
+            $shared_config.icons_override(overrides);
+        }
+        $shared_config.if_empty_override(common_config.if_empty, common_config.if_empty_text);
+        $shared_config.overflow_override(common_config.max_text_width, common_config.overflow);
+        $shared_config.markup_override(common_config.markup);
+        $shared_config.layout_override(
+            common_config.font,
+            common_config.min_width,
+            common_config.align,
+        );
+        $shared_config.gradient_override(common_config.gradient);
 
         // Extract block-specific config
         let block_config = <$block_type as ConfigBlock>::Config::deserialize($block_config)
             .configuration_error("Failed to deserialize block config.")?;
 
-        let mut block = $block_type::new($id, block_config, $shared_config, $update_request)?;
+        let file_watch = match common_config.watch_files {
+            Some(ref paths) => Some(watch_files($id, paths, $update_request.clone())?),
+            None => None,
+        };
+        let condition = common_config
+            .condition_command
+            .map(|command| Condition::new(command, $id, $update_request.clone()));
+        let min_interval = $shared_config
+            .monochrome
+            .as_ref()
+            .map(|monochrome| monochrome.min_interval);
+        let jitter = Some(jitter_offset($id, $shared_config.jitter));
+        let launcher = Launcher::new(
+            $shared_config.menu_launcher.to_string(),
+            $update_request.clone(),
+        );
+        let mut block = crate::logging::in_block(stringify!($block_type), $id, || {
+            $block_type::new($id, block_config, $shared_config, $update_request)
+        })?;
         if let Some(overrided) = block.override_on_click() {
             *overrided = common_config.on_click.take();
         }
@@ -213,6 +480,27 @@ macro_rules! block {
             name: stringify!($block_type).to_string(),
             inner: block,
             on_click: common_config.on_click,
+            on_double_click: common_config.on_double_click,
+            on_click_modifiers,
+            on_buttons,
+            last_values: std::collections::HashMap::new(),
+            launcher,
+            notify_on: common_config.notify_on,
+            last_state: crate::widgets::State::Idle,
+            group: common_config.group,
+            if_empty: common_config.if_empty,
+            visible: condition.is_none(),
+            condition,
+            private: common_config.private,
+            profiles: common_config.profiles,
+            watchdog: common_config.watchdog,
+            last_update: std::time::Instant::now(),
+            interval: None,
+            pressed: None,
+            min_interval,
+            interval_align: common_config.interval_align,
+            jitter,
+            file_watch,
         }) as Box<dyn Block>)
     }};
 }
@@ -226,16 +514,60 @@ pub fn create_block(
 ) -> Result<Box<dyn Block>> {
     match name {
         // Please keep these in alphabetical order.
+        "adb" => block!(Adb, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "air_quality" => block!(AirQuality, id, block_config, shared_config, update_request),
         "apt" => block!(Apt, id, block_config, shared_config, update_request),
         "backlight" => block!(Backlight, id, block_config, shared_config, update_request),
+        "backup" => block!(Backup, id, block_config, shared_config, update_request),
         "battery" => block!(Battery, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "bluetooth" => block!(Bluetooth, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
+        "bluetooth_devices" => block!(
+            BluetoothDevices,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "bmc" => block!(Bmc, id, block_config, shared_config, update_request),
+        "breaks" => block!(Breaks, id, block_config, shared_config, update_request),
+        "calendar" => block!(Calendar, id, block_config, shared_config, update_request),
+        "cloud_instances" => block!(
+            CloudInstances,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "cloud_spend" => block!(CloudSpend, id, block_config, shared_config, update_request),
+        "countdown" => block!(Countdown, id, block_config, shared_config, update_request),
+        "counter" => block!(Counter, id, block_config, shared_config, update_request),
         "cpu" => block!(Cpu, id, block_config, shared_config, update_request),
         "custom" => block!(Custom, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "custom_dbus" => block!(CustomDBus, id, block_config, shared_config, update_request),
+        "daily_events" => block!(DailyEvents, id, block_config, shared_config, update_request),
+        "devenv" => block!(Devenv, id, block_config, shared_config, update_request),
+        "disk_cleanup" => block!(DiskCleanup, id, block_config, shared_config, update_request),
         "disk_space" => block!(DiskSpace, id, block_config, shared_config, update_request),
         "dnf" => block!(Dnf, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
         "docker" => block!(Docker, id, block_config, shared_config, update_request), ///////
+        "domain_expiry" => {
+            block!(
+                DomainExpiry,
+                id,
+                block_config,
+                shared_config,
+                update_request
+            )
+        }
+        "downloads" => block!(Downloads, id, block_config, shared_config, update_request),
+        "dyndns" => block!(Dyndns, id, block_config, shared_config, update_request),
+        "focus" => block!(Focus, id, block_config, shared_config, update_request),
+        #[cfg(feature = "sway")]
         "focused_window" => block!(
             FocusedWindow,
             id,
@@ -243,10 +575,38 @@ pub fn create_block(
             shared_config,
             update_request
         ),
+        #[cfg(feature = "http")]
         "github" => block!(Github, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "gitlab" => block!(Gitlab, id, block_config, shared_config, update_request),
+        "gpu" => block!(Gpu, id, block_config, shared_config, update_request),
+        "group" => block!(Group, id, block_config, shared_config, update_request),
+        "hdd_spin" => block!(HddSpin, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "healthchecks" => block!(
+            Healthchecks,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "hearing" => block!(Hearing, id, block_config, shared_config, update_request),
         "hueshift" => block!(Hueshift, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "ibus" => block!(IBus, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
+        "idle_inhibitor" => block!(
+            IdleInhibitor,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        #[cfg(feature = "http")]
+        "karma" => block!(Karma, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "kdeconnect" => block!(KDEConnect, id, block_config, shared_config, update_request),
+        "key_agents" => block!(KeyAgents, id, block_config, shared_config, update_request),
         "keyboard_layout" => block!(
             KeyboardLayout,
             id,
@@ -254,12 +614,24 @@ pub fn create_block(
             shared_config,
             update_request
         ),
+        "kubernetes" => block!(Kubernetes, id, block_config, shared_config, update_request),
+        "libvirt" => block!(Libvirt, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "live_streams" => block!(LiveStreams, id, block_config, shared_config, update_request),
         "load" => block!(Load, id, block_config, shared_config, update_request),
+        "mail" => block!(Mail, id, block_config, shared_config, update_request),
+        "mail_queue" => block!(MailQueue, id, block_config, shared_config, update_request),
         #[cfg(feature = "maildir")]
         "maildir" => block!(Maildir, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "mastodon" => block!(Mastodon, id, block_config, shared_config, update_request),
         "memory" => block!(Memory, id, block_config, shared_config, update_request),
+        "mic_level" => block!(MicLevel, id, block_config, shared_config, update_request),
+        "mqtt" => block!(Mqtt, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "music" => block!(Music, id, block_config, shared_config, update_request),
         "net" => block!(Net, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
         "networkmanager" => block!(
             NetworkManager,
             id,
@@ -267,25 +639,98 @@ pub fn create_block(
             shared_config,
             update_request
         ),
+        #[cfg(feature = "dbus")]
         "notify" => block!(Notify, id, block_config, shared_config, update_request),
         #[cfg(feature = "notmuch")]
         "notmuch" => block!(Notmuch, id, block_config, shared_config, update_request),
         "nvidia_gpu" => block!(NvidiaGpu, id, block_config, shared_config, update_request),
         "pacman" => block!(Pacman, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
+        "password_manager" => {
+            block!(
+                PasswordManager,
+                id,
+                block_config,
+                shared_config,
+                update_request
+            )
+        }
+        "ping" => block!(Ping, id, block_config, shared_config, update_request),
+        "pipewire" => block!(Pipewire, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "podcasts" => block!(Podcasts, id, block_config, shared_config, update_request),
         "pomodoro" => block!(Pomodoro, id, block_config, shared_config, update_request),
+        "privacy" => block!(Privacy, id, block_config, shared_config, update_request),
+        "process" => block!(Process, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "prometheus" => block!(Prometheus, id, block_config, shared_config, update_request),
+        "remote" => block!(Remote, id, block_config, shared_config, update_request),
         "rofication" => block!(Rofication, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "rss" => block!(Rss, id, block_config, shared_config, update_request),
+        "s3_backup" => block!(S3Backup, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "scores" => block!(Scores, id, block_config, shared_config, update_request),
+        "security" => block!(Security, id, block_config, shared_config, update_request),
+        "serial" => block!(Serial, id, block_config, shared_config, update_request),
+        "sessions" => block!(Sessions, id, block_config, shared_config, update_request),
+        "slider" => block!(Slider, id, block_config, shared_config, update_request),
+        "snmp" => block!(Snmp, id, block_config, shared_config, update_request),
         "sound" => block!(Sound, id, block_config, shared_config, update_request),
         "speedtest" => block!(SpeedTest, id, block_config, shared_config, update_request),
+        "ssd_wear" => block!(SsdWear, id, block_config, shared_config, update_request),
+        "stopwatch" => block!(Stopwatch, id, block_config, shared_config, update_request),
+        "sun_moon" => block!(SunMoon, id, block_config, shared_config, update_request),
+        #[cfg(feature = "dbus")]
+        "systemd" => block!(Systemd, id, block_config, shared_config, update_request),
+        "tailscale" => block!(Tailscale, id, block_config, shared_config, update_request),
         "taskwarrior" => block!(Taskwarrior, id, block_config, shared_config, update_request),
         "temperature" => block!(Temperature, id, block_config, shared_config, update_request),
         "template" => block!(Template, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
+        "ticker" => block!(Ticker, id, block_config, shared_config, update_request),
         "time" => block!(Time, id, block_config, shared_config, update_request), /////////
+        "timer" => block!(Timer, id, block_config, shared_config, update_request),
         "supertoggle" => block!(SuperToggle, id, block_config, shared_config, update_request),
         "toggle" => block!(Toggle, id, block_config, shared_config, update_request),
+        "trash" => block!(Trash, id, block_config, shared_config, update_request),
+        "typing" => block!(Typing, id, block_config, shared_config, update_request),
+        "updates" => block!(Updates, id, block_config, shared_config, update_request),
+        "ups" => block!(Ups, id, block_config, shared_config, update_request),
         "uptime" => block!(Uptime, id, block_config, shared_config, update_request),
+        "vpn" => block!(Vpn, id, block_config, shared_config, update_request),
         "watson" => block!(Watson, id, block_config, shared_config, update_request),
+        #[cfg(feature = "http")]
         "weather" => block!(Weather, id, block_config, shared_config, update_request),
+        "worldclock" => block!(Worldclock, id, block_config, shared_config, update_request),
         "xrandr" => block!(Xrandr, id, block_config, shared_config, update_request),
-        other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
+        "yubikey" => block!(Yubikey, id, block_config, shared_config, update_request),
+        other => Err(BlockError(
+            other.to_string(),
+            match required_feature(other) {
+                Some(feature) => format!(
+                    "This build lacks the '{}' feature the block requires.",
+                    feature
+                ),
+                None => "Unknown block!".to_string(),
+            },
+        )),
     }
 }
+
+/// The cargo feature a block is compiled with, for blocks that are left out without it
+fn required_feature(block: &str) -> Option<&'static str> {
+    Some(match block {
+        "bluetooth" | "bluetooth_devices" | "custom_dbus" | "ibus" | "idle_inhibitor"
+        | "kdeconnect" | "music" | "networkmanager" | "notify" | "password_manager" | "systemd" => {
+            "dbus"
+        }
+        "air_quality" | "docker" | "github" | "gitlab" | "healthchecks" | "karma"
+        | "live_streams" | "mastodon" | "podcasts" | "prometheus" | "rss" | "scores" | "ticker"
+        | "weather" => "http",
+        "focused_window" => "sway",
+        "maildir" => "maildir",
+        "notmuch" => "notmuch",
+        _ => return None,
+    })
+}
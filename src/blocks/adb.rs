@@ -0,0 +1,275 @@
+//! Shows the Android devices connected over ADB with their battery level and temperature. A click
+//! mirrors the screen of a device with scrcpy.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::{spawn_child_async, CommandRunner};
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Device {
+    serial: String,
+    /// "device" once it's ready, or "unauthorized", "offline" and so on
+    state: String,
+    model: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Battery {
+    level: Option<i64>,
+    /// In degrees Celsius
+    temperature: Option<f64>,
+    charging: bool,
+}
+
+pub struct Adb {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    devices: Vec<Device>,
+    format: FormatTemplate,
+    command: String,
+    battery_warning: i64,
+    hide_if_none: bool,
+    interval: Duration,
+    shared_config: SharedConfig,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct AdbConfig {
+    /// Shell command run on left click, with `{serial}` replaced by the serial of the device
+    pub command: String,
+
+    /// Battery level in percent below which a device is shown as warning
+    pub battery_warning: i64,
+
+    /// Hide the block while no device is connected
+    pub hide_if_none: bool,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for AdbConfig {
+    fn default() -> Self {
+        Self {
+            command: "scrcpy -s '{serial}'".to_string(),
+            battery_warning: 20,
+            hide_if_none: false,
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Adb {
+    type Config = AdbConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Adb {
+            id,
+            widgets: Vec::new(),
+            devices: Vec::new(),
+            format: block_config.format.with_default("{model} {battery}")?,
+            command: block_config.command,
+            battery_warning: block_config.battery_warning,
+            hide_if_none: block_config.hide_if_none,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner.clone(),
+            shared_config,
+        })
+    }
+}
+
+/// The devices in the output of `adb devices -l`, with lines like
+/// "R58M12ABCDE  device usb:1-1 product:beyond1 model:SM_G973F device:beyond1 transport_id:1"
+fn parse_devices(output: &str) -> Vec<Device> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with("List of devices") && !line.starts_with('*'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let serial = fields.next()?.to_string();
+            let state = fields.next()?.to_string();
+            let model = fields
+                .find_map(|field| field.strip_prefix("model:"))
+                .map_or_else(|| serial.clone(), |model| model.replace('_', " "));
+            Some(Device {
+                serial,
+                state,
+                model,
+            })
+        })
+        .collect()
+}
+
+/// The battery in the output of `dumpsys battery`, with lines like "  level: 87" and
+/// "  temperature: 285" in tenths of degrees
+fn parse_battery(output: &str) -> Battery {
+    let mut battery = Battery::default();
+    for line in output.lines() {
+        let (key, value) = match line.trim().split_once(": ") {
+            Some(field) => field,
+            None => continue,
+        };
+        match key {
+            "level" => battery.level = value.parse().ok(),
+            "temperature" => {
+                battery.temperature = value.parse::<f64>().ok().map(|tenths| tenths / 10.0)
+            }
+            // 2 is charging and 5 is full
+            "status" => battery.charging = value == "2" || value == "5",
+            _ => (),
+        }
+    }
+    battery
+}
+
+impl Block for Adb {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let output = self.command_runner.run("adb devices -l")?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "adb".to_string(),
+                "failed to list the devices, is adb installed?".to_string(),
+            ));
+        }
+        self.devices = parse_devices(&output.stdout);
+
+        self.widgets = Vec::with_capacity(self.devices.len());
+        for (instance, device) in self.devices.iter().enumerate() {
+            let battery = if device.state == "device" {
+                let command = format!("adb -s '{}' shell dumpsys battery", device.serial);
+                parse_battery(&self.command_runner.run(&command)?.stdout)
+            } else {
+                Battery::default()
+            };
+            let values = map!(
+                "serial" => Value::from_string(device.serial.clone()),
+                "model" => Value::from_string(device.model.clone()),
+                "state" => Value::from_string(device.state.clone()),
+                "battery" => match battery.level {
+                    Some(level) => Value::from_integer(level).percents(),
+                    None => Value::from_string("-".to_string()),
+                },
+                "temperature" => match battery.temperature {
+                    Some(temperature) => Value::from_float(temperature).degrees(),
+                    None => Value::from_string("-".to_string()),
+                },
+            );
+            let mut widget = TextWidget::new(self.id, instance, self.shared_config.clone());
+            if instance == 0 {
+                widget = widget.with_icon("phone")?;
+            }
+            widget.set_texts(self.format.render(&values)?);
+            widget.set_state(if device.state != "device" {
+                // Waiting for the debugging authorization to be accepted on the device
+                State::Warning
+            } else if battery.charging {
+                State::Good
+            } else if battery
+                .level
+                .is_some_and(|level| level < self.battery_warning)
+            {
+                State::Warning
+            } else {
+                State::Idle
+            });
+            self.widgets.push(widget);
+        }
+
+        if self.widgets.is_empty() && !self.hide_if_none {
+            let mut widget = TextWidget::new(self.id, 0, self.shared_config.clone())
+                .with_icon("phone_disconnected")?;
+            widget.set_text("0".to_string());
+            self.widgets.push(widget);
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let device = event
+            .instance
+            .and_then(|instance| self.devices.get(instance));
+        if let Some(device) = device {
+            let command = self.command.replace("{serial}", &device.serial);
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("adb", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let devices = "List of devices attached\n\
+            R58M12ABCDE            device usb:1-1 product:beyond1 model:SM_G973F device:beyond1 transport_id:1\n\
+            192.168.1.5:5555       unauthorized transport_id:3\n\n";
+        assert_eq!(
+            parse_devices(devices),
+            vec![
+                Device {
+                    serial: "R58M12ABCDE".to_string(),
+                    state: "device".to_string(),
+                    model: "SM G973F".to_string(),
+                },
+                Device {
+                    serial: "192.168.1.5:5555".to_string(),
+                    state: "unauthorized".to_string(),
+                    model: "192.168.1.5:5555".to_string(),
+                },
+            ]
+        );
+
+        let battery =
+            "Current Battery Service state:\n  AC powered: false\n  USB powered: true\n  \
+                       status: 2\n  health: 2\n  present: true\n  level: 87\n  scale: 100\n  \
+                       temperature: 285\n";
+        assert_eq!(
+            parse_battery(battery),
+            Battery {
+                level: Some(87),
+                temperature: Some(28.5),
+                charging: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Shows the air quality index of a monitoring station, as reported by the World Air Quality Index
+//! project or computed from the measurements on OpenAQ
+
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum AirQualityService {
+    Waqi {
+        #[serde(default = "AirQualityService::getenv_waqi_token")]
+        token: Option<String>,
+        /// A city, "@" and the ID of a station, "geo:<lat>;<lon>" or "here" for the station next
+        /// to the IP address
+        #[serde(default = "AirQualityService::default_station")]
+        station: String,
+    },
+    OpenAq {
+        #[serde(default = "AirQualityService::getenv_openaq_api_key")]
+        api_key: Option<String>,
+        location_id: u64,
+    },
+}
+
+impl AirQualityService {
+    fn getenv_waqi_token() -> Option<String> {
+        env::var("WAQI_TOKEN").ok()
+    }
+
+    fn getenv_openaq_api_key() -> Option<String> {
+        env::var("OPENAQ_API_KEY").ok()
+    }
+
+    fn default_station() -> String {
+        "here".to_string()
+    }
+}
+
+/// The AQI of a station and the sub-indices of particulate matter
+#[derive(Debug, Clone, PartialEq)]
+struct Reading {
+    station: String,
+    aqi: u32,
+    pm25: Option<u32>,
+    pm10: Option<u32>,
+}
+
+/// Concentrations and the index they start at, by the US EPA's 2024 breakpoints
+const PM25_BREAKPOINTS: &[(f64, u32)] = &[
+    (0.0, 0),
+    (9.1, 51),
+    (35.5, 101),
+    (55.5, 151),
+    (125.5, 201),
+    (225.5, 301),
+    (325.5, 501),
+];
+const PM10_BREAKPOINTS: &[(f64, u32)] = &[
+    (0.0, 0),
+    (55.0, 51),
+    (155.0, 101),
+    (255.0, 151),
+    (355.0, 201),
+    (425.0, 301),
+    (605.0, 501),
+];
+
+/// The sub-index of a concentration in µg/m³, interpolated between the breakpoints
+fn sub_index(concentration: f64, breakpoints: &[(f64, u32)], step: f64) -> u32 {
+    // Concentrations are truncated to the precision of the breakpoints
+    let concentration = (concentration / step + 1e-6).floor() * step;
+    for band in breakpoints.windows(2) {
+        let ((low, index_low), (next, index_next)) = (band[0], band[1]);
+        if concentration < next - step / 2.0 {
+            let high = next - step;
+            let index_high = index_next - 1;
+            return (f64::from(index_high - index_low) / (high - low) * (concentration - low)
+                + f64::from(index_low))
+            .round() as u32;
+        }
+    }
+    500
+}
+
+/// The category of an AQI, as the EPA names it
+fn category(aqi: u32) -> &'static str {
+    match aqi {
+        0..=50 => "good",
+        51..=100 => "moderate",
+        101..=150 => "unhealthy for sensitive groups",
+        151..=200 => "unhealthy",
+        201..=300 => "very unhealthy",
+        _ => "hazardous",
+    }
+}
+
+fn get(url: &str, headers: Vec<(&str, &str)>) -> Result<JsonValue> {
+    let response = http::http_get_json(url, Some(Duration::from_secs(10)), headers)?;
+    if response.code != 200 {
+        return Err(BlockError(
+            "air_quality".to_string(),
+            format!("the service answered with HTTP {}", response.code),
+        ));
+    }
+    Ok(response.content)
+}
+
+fn parse_waqi(json: &JsonValue) -> Result<Reading> {
+    if json["status"] != "ok" {
+        return Err(BlockError(
+            "air_quality".to_string(),
+            json["data"]
+                .as_str()
+                .unwrap_or("unexpected answer of WAQI")
+                .to_string(),
+        ));
+    }
+    let data = &json["data"];
+    let sub_index = |pollutant: &str| data["iaqi"][pollutant]["v"].as_f64().map(|v| v as u32);
+    Ok(Reading {
+        station: data["city"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        aqi: data["aqi"]
+            .as_u64()
+            .block_error("air_quality", "the station has no current AQI")? as u32,
+        pm25: sub_index("pm25"),
+        pm10: sub_index("pm10"),
+    })
+}
+
+pub struct AirQuality {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    service: AirQualityService,
+    /// Names of the parameters the sensors of the OpenAQ location measure, by sensor ID, and the
+    /// name of the location
+    openaq_sensors: Option<(String, HashMap<u64, String>)>,
+    warning: u32,
+    critical: u32,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AirQualityConfig {
+    pub service: AirQualityService,
+
+    /// AQI from which on the block is shown as warning
+    #[serde(default = "AirQualityConfig::default_warning")]
+    pub warning: u32,
+
+    /// AQI from which on the block is shown as critical
+    #[serde(default = "AirQualityConfig::default_critical")]
+    pub critical: u32,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "AirQualityConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl AirQualityConfig {
+    fn default_warning() -> u32 {
+        101
+    }
+
+    fn default_critical() -> u32 {
+        151
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(1800)
+    }
+}
+
+impl ConfigBlock for AirQuality {
+    type Config = AirQualityConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(AirQuality {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("AQI {aqi}")?,
+            service: block_config.service,
+            openaq_sensors: None,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl AirQuality {
+    fn read(&mut self) -> Result<Reading> {
+        match &self.service {
+            AirQualityService::Waqi { token, station } => {
+                let token = token
+                    .as_ref()
+                    .block_error("air_quality", "missing token, set token or WAQI_TOKEN")?;
+                let url = format!("https://api.waqi.info/feed/{}/?token={}", station, token);
+                parse_waqi(&get(&url, vec![])?)
+            }
+            AirQualityService::OpenAq {
+                api_key,
+                location_id,
+            } => {
+                let api_key = api_key.as_ref().block_error(
+                    "air_quality",
+                    "missing API key, set api_key or OPENAQ_API_KEY",
+                )?;
+                let headers = vec![("X-API-Key", api_key.as_str())];
+                // Which sensor measures what only changes when the station does
+                if self.openaq_sensors.is_none() {
+                    let url = format!("https://api.openaq.org/v3/locations/{}", location_id);
+                    let location = get(&url, headers.clone())?;
+                    let location = &location["results"][0];
+                    let sensors = location["sensors"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|sensor| {
+                            Some((
+                                sensor["id"].as_u64()?,
+                                sensor["parameter"]["name"].as_str()?.to_string(),
+                            ))
+                        })
+                        .collect();
+                    let name = location["name"].as_str().unwrap_or_default().to_string();
+                    self.openaq_sensors = Some((name, sensors));
+                }
+                let (name, sensors) = self.openaq_sensors.as_ref().unwrap();
+
+                let url = format!("https://api.openaq.org/v3/locations/{}/latest", location_id);
+                let latest = get(&url, headers)?;
+                let concentration = |parameter: &str| {
+                    latest["results"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .find(|result| {
+                            result["sensorsId"]
+                                .as_u64()
+                                .and_then(|id| sensors.get(&id))
+                                .map(String::as_str)
+                                == Some(parameter)
+                        })
+                        .and_then(|result| result["value"].as_f64())
+                };
+                let pm25 = concentration("pm25").map(|c| sub_index(c, PM25_BREAKPOINTS, 0.1));
+                let pm10 = concentration("pm10").map(|c| sub_index(c, PM10_BREAKPOINTS, 1.0));
+                Ok(Reading {
+                    station: name.clone(),
+                    aqi: pm25.max(pm10).block_error(
+                        "air_quality",
+                        "the location measures neither PM2.5 nor PM10",
+                    )?,
+                    pm25,
+                    pm10,
+                })
+            }
+        }
+    }
+}
+
+impl Block for AirQuality {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let reading = match self.read() {
+            Ok(reading) => reading,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, the air is checked again at the next update
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(self.interval.into()));
+            }
+            Err(error) => {
+                self.text.set_text(format!("air_quality error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+        let values = map!(
+            "aqi" => Value::from_integer(reading.aqi as i64),
+            "pm25" => Value::from_integer(reading.pm25.unwrap_or(0) as i64),
+            "pm10" => Value::from_integer(reading.pm10.unwrap_or(0) as i64),
+            "category" => Value::from_string(category(reading.aqi).to_string()),
+            "station" => Value::from_string(reading.station),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if reading.aqi >= self.critical {
+            State::Critical
+        } else if reading.aqi >= self.warning {
+            State::Warning
+        } else if reading.aqi <= 50 {
+            State::Good
+        } else {
+            State::Idle
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aqi() {
+        assert_eq!(sub_index(9.04, PM25_BREAKPOINTS, 0.1), 50);
+        assert_eq!(sub_index(35.4, PM25_BREAKPOINTS, 0.1), 100);
+        assert_eq!(sub_index(40.0, PM25_BREAKPOINTS, 0.1), 112);
+        assert_eq!(sub_index(80.0, PM10_BREAKPOINTS, 1.0), 63);
+        assert_eq!(sub_index(900.0, PM10_BREAKPOINTS, 1.0), 500);
+        assert_eq!(category(112), "unhealthy for sensitive groups");
+
+        let json = serde_json::json!({
+            "status": "ok",
+            "data": {
+                "aqi": 57,
+                "city": {"name": "Berlin Mitte, Germany"},
+                "iaqi": {"pm25": {"v": 57}, "pm10": {"v": 21}},
+            },
+        });
+        assert_eq!(
+            parse_waqi(&json).unwrap(),
+            Reading {
+                station: "Berlin Mitte, Germany".to_string(),
+                aqi: 57,
+                pm25: Some(57),
+                pm10: Some(21),
+            }
+        );
+    }
+}
@@ -3,17 +3,19 @@
 //! This module contains the [`Backlight`](./struct.Backlight.html) block, which
 //! can display the brightness level of physical backlit devices. Brightness
 //! levels are read from and written to the `sysfs` filesystem, so this block
-//! does not depend on `xrandr` (and thus it works on Wayland). To set
+//! does not depend on `xrandr` (and thus it works on Wayland). External
+//! monitors are controlled over DDC/CI with `ddcutil`. To set
 //! brightness levels using `xrandr`, see the
 //! [`Xrandr`](../xrandr/struct.Xrandr.html) block.
 
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{unbounded, Sender};
 use inotify::{EventMask, Inotify, WatchMask};
 use serde_derive::Deserialize;
 
@@ -80,6 +82,21 @@ impl BacklitDevice {
         })
     }
 
+    /// All backlit devices in the `/sys/class/backlight` directory.
+    pub fn all(root_scaling: f64) -> Result<Vec<Self>> {
+        Path::new("/sys/class/backlight")
+            .read_dir()
+            .block_error("backlight", "Failed to read backlight device directory")?
+            .map(|entry| {
+                let entry = entry.block_error("backlight", "Failed to read device file")?;
+                BacklitDevice::from_device(
+                    entry.file_name().to_string_lossy().into_owned(),
+                    root_scaling,
+                )
+            })
+            .collect()
+    }
+
     /// Use the backlit device `device`. Returns an error if a directory for
     /// that device is not found.
     pub fn from_device(device: String, root_scaling: f64) -> Result<Self> {
@@ -141,6 +158,16 @@ impl BacklitDevice {
             .block_error("backlight", "Failed to write into brightness file")
     }
 
+    #[cfg(not(feature = "dbus"))]
+    fn set_brightness_via_dbus(&self, _raw_value: u64) -> Result<()> {
+        Err(BlockError(
+            "backlight".to_string(),
+            "Failed to open brightness file for writing, and logind requires the dbus feature"
+                .to_string(),
+        ))
+    }
+
+    #[cfg(feature = "dbus")]
     fn set_brightness_via_dbus(&self, raw_value: u64) -> Result<()> {
         let device_name = self
             .device_path
@@ -177,16 +204,182 @@ impl BacklitDevice {
     }
 }
 
+/// A display whose brightness the block shows or changes.
+trait Dimmable {
+    /// The brightness, as a percent.
+    fn brightness(&self) -> Result<u64>;
+    fn set_brightness(&mut self, value: u64) -> Result<()>;
+}
+
+impl Dimmable for BacklitDevice {
+    fn brightness(&self) -> Result<u64> {
+        BacklitDevice::brightness(self)
+    }
+
+    fn set_brightness(&mut self, value: u64) -> Result<()> {
+        BacklitDevice::set_brightness(self, value)
+    }
+}
+
+/// The I2C buses and models of the displays in the output of `ddcutil detect --brief`, which has
+/// lines like "I2C bus:  /dev/i2c-4" and "Monitor:  DEL:DELL U2415:7MT0167".
+fn ddc_displays(output: &str) -> Vec<(u32, String)> {
+    let mut displays: Vec<(u32, String)> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if let Some(bus) = line.strip_prefix("I2C bus:") {
+            if let Some(bus) = bus.trim().strip_prefix("/dev/i2c-") {
+                if let Ok(bus) = bus.parse() {
+                    displays.push((bus, String::new()));
+                }
+            }
+        } else if let Some(monitor) = line.strip_prefix("Monitor:") {
+            if let Some(display) = displays.last_mut() {
+                display.1 = monitor.trim().to_string();
+            }
+        }
+    }
+    displays
+}
+
+/// The current and maximum value in the output of `ddcutil getvcp 10 --brief`, like
+/// "VCP 10 C 60 100".
+fn parse_vcp(output: &str) -> Option<(u64, u64)> {
+    let mut fields = output.split_whitespace().skip(3);
+    let current = fields.next()?.parse().ok()?;
+    let max = fields.next()?.parse().ok()?;
+    Some((current, max))
+}
+
+/// An external monitor whose brightness is set over DDC/CI with `ddcutil`. Monitors take their
+/// time to answer, so the brightness is only read once and the monitor is set in the background,
+/// skipping the values that were overtaken while it was busy.
+struct DdcDisplay {
+    bus: u32,
+    monitor: String,
+    max_brightness: u64,
+    brightness: u64,
+    requests: Sender<u64>,
+}
+
+impl DdcDisplay {
+    fn ddcutil(args: &[&str]) -> Result<String> {
+        let output = Command::new("ddcutil")
+            .args(args)
+            .output()
+            .block_error("backlight", "Failed to run ddcutil")?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// The monitors that ddcutil finds and that report their brightness.
+    fn detect() -> Result<Vec<Self>> {
+        let mut displays = Vec::new();
+        for (bus, monitor) in ddc_displays(&Self::ddcutil(&["detect", "--brief"])?) {
+            let output = Self::ddcutil(&["--bus", &bus.to_string(), "getvcp", "10", "--brief"])?;
+            let (raw, max_brightness) = match parse_vcp(&output) {
+                Some((raw, max_brightness)) if max_brightness > 0 => (raw, max_brightness),
+                _ => continue,
+            };
+            let (requests, received) = unbounded::<u64>();
+            thread::Builder::new()
+                .name("backlight".into())
+                .spawn(move || {
+                    // Ends when the display is dropped
+                    while let Ok(raw) = received.recv() {
+                        let raw = received.try_iter().last().unwrap_or(raw);
+                        let _ = Command::new("ddcutil")
+                            .args(["--bus", &bus.to_string(), "setvcp", "10", &raw.to_string()])
+                            .output();
+                    }
+                })
+                .internal_error("backlight", "failed to start a thread")?;
+            displays.push(DdcDisplay {
+                bus,
+                monitor,
+                max_brightness,
+                brightness: raw * 100 / max_brightness,
+                requests,
+            });
+        }
+        Ok(displays)
+    }
+
+    /// Whether this is the display that the user chose, by its I2C bus number or a part of its
+    /// model, like "U2415"
+    fn is(&self, display: &str) -> bool {
+        display == self.bus.to_string() || self.monitor.contains(display)
+    }
+}
+
+impl Dimmable for DdcDisplay {
+    fn brightness(&self) -> Result<u64> {
+        Ok(self.brightness)
+    }
+
+    fn set_brightness(&mut self, value: u64) -> Result<()> {
+        let value = value.min(100);
+        self.requests
+            .send((value * self.max_brightness + 50) / 100)
+            .internal_error("backlight", "ddcutil thread is gone")?;
+        self.brightness = value;
+        Ok(())
+    }
+}
+
+/// A change of brightness that is spread over some time.
+struct Transition {
+    from: u64,
+    to: u64,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Transition {
+    /// The brightness at `now`, and whether the transition is over.
+    fn at(&self, now: Instant) -> (u64, bool) {
+        let progress =
+            now.saturating_duration_since(self.start).as_secs_f64() / self.duration.as_secs_f64();
+        if progress.is_finite() && progress < 1.0 {
+            let value = self.from as f64 + (self.to as f64 - self.from as f64) * progress;
+            (value.round() as u64, false)
+        } else {
+            (self.to, true)
+        }
+    }
+}
+
+/// How often the brightness is set during a transition
+const TRANSITION_TICK: Duration = Duration::from_millis(25);
+
+/// The brightness of a synced display after the brightness of the block's device changed from
+/// `old` to `new`, in proportion.
+fn synced_brightness(synced: f64, old: u64, new: u64) -> f64 {
+    if old == 0 {
+        new as f64
+    } else {
+        (synced * new as f64 / old as f64).min(100.0)
+    }
+}
+
+/// A synced display, along with the brightness it was last set to before rounding.
+struct Synced {
+    display: Box<dyn Dimmable>,
+    exact: Option<f64>,
+}
+
 /// A block for displaying the brightness of a backlit device.
 pub struct Backlight {
     id: usize,
     output: TextWidget,
-    device: BacklitDevice,
+    device: Box<dyn Dimmable>,
     step_width: u64,
     minimum: u64,
     maximum: u64,
     cycle: Vec<u64>,
     cycle_index: usize,
+    synced: Vec<Synced>,
+    transition_duration: Duration,
+    transition: Option<Transition>,
+    tx_update_request: Sender<Task>,
     scrolling: Scrolling,
     invert_icons: bool,
     on_click: Option<String>,
@@ -200,6 +393,10 @@ pub struct BacklightConfig {
     /// The backlight device in `/sys/class/backlight/` to read brightness from.
     pub device: Option<String>,
 
+    /// An external monitor to control over DDC/CI instead, by its I2C bus number or a part of
+    /// its model.
+    pub ddc_display: Option<String>,
+
     /// The steps brightness is in/decreased for the selected screen (When greater than 50 it gets limited to 50)
     pub step_width: u64,
 
@@ -226,12 +423,22 @@ pub struct BacklightConfig {
     pub invert_icons: bool,
 
     pub on_click: Option<String>,
+
+    /// Apply brightness changes to all other backlit devices as well, in proportion
+    pub sync: bool,
+
+    /// With `sync`, also apply them to the external monitors that `ddcutil` finds
+    pub sync_ddc: bool,
+
+    /// Milliseconds over which brightness changes are spread, or 0 to change it at once
+    pub transition: u64,
 }
 
 impl Default for BacklightConfig {
     fn default() -> Self {
         Self {
             device: None,
+            ddc_display: None,
             step_width: 5,
             root_scaling: 1f64,
             invert_icons: false,
@@ -240,6 +447,9 @@ impl Default for BacklightConfig {
             minimum: 5,
             maximum: 100,
             cycle: None,
+            sync: false,
+            sync_ddc: false,
+            transition: 0,
         }
     }
 }
@@ -275,7 +485,62 @@ impl Backlight {
                 .0
         };
         self.cycle_index = (nearest + 1) % self.cycle.len();
-        self.device.set_brightness(self.cycle[self.cycle_index])
+        self.set_brightness(self.cycle[self.cycle_index])
+    }
+
+    /// The brightness that the device has, or is about to have after a transition.
+    fn target_brightness(&self) -> Result<u64> {
+        match &self.transition {
+            Some(transition) => Ok(transition.to),
+            None => self.device.brightness(),
+        }
+    }
+
+    /// Change the brightness, at once or in a transition.
+    fn set_brightness(&mut self, value: u64) -> Result<()> {
+        if self.transition_duration.is_zero() {
+            return self.apply_brightness(value);
+        }
+        self.transition = Some(Transition {
+            from: self.device.brightness()?,
+            to: value,
+            start: Instant::now(),
+            duration: self.transition_duration,
+        });
+        self.tx_update_request.send(Task {
+            id: self.id,
+            update_time: Instant::now(),
+        })?;
+        Ok(())
+    }
+
+    /// Set the brightness of the device, and of the synced displays in proportion.
+    fn apply_brightness(&mut self, value: u64) -> Result<()> {
+        if !self.synced.is_empty() {
+            let old = self.device.brightness()?;
+            for synced in &mut self.synced {
+                // A display that went out of reach shouldn't keep the others from following
+                let current = match synced.display.brightness() {
+                    Ok(current) => current,
+                    Err(error) => {
+                        log::warn!("failed to read synced brightness: {}", error);
+                        continue;
+                    }
+                };
+                // Small brightnesses would get stuck if the rounded value was scaled, so the
+                // exact one is kept unless the brightness was changed elsewhere since
+                let base = match synced.exact {
+                    Some(exact) if exact.round() as u64 == current => exact,
+                    _ => current as f64,
+                };
+                let exact = synced_brightness(base, old, value);
+                match synced.display.set_brightness(exact.round() as u64) {
+                    Ok(()) => synced.exact = Some(exact),
+                    Err(error) => log::warn!("failed to set synced brightness: {}", error),
+                }
+            }
+        }
+        self.device.set_brightness(value)
     }
 }
 
@@ -288,11 +553,46 @@ impl ConfigBlock for Backlight {
         shared_config: SharedConfig,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let device = match block_config.device {
-            Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
-            None => BacklitDevice::default(block_config.root_scaling),
-        }?;
-        let brightness_file = device.brightness_file();
+        let mut ddc_displays = if block_config.ddc_display.is_some() || block_config.sync_ddc {
+            DdcDisplay::detect()?
+        } else {
+            Vec::new()
+        };
+        let (device, backlit_path): (Box<dyn Dimmable>, _) = match block_config.ddc_display {
+            Some(ref chosen) => {
+                let index = ddc_displays
+                    .iter()
+                    .position(|display| display.is(chosen))
+                    .block_error(
+                        "backlight",
+                        &format!("ddcutil found no display '{}'", chosen),
+                    )?;
+                (Box::new(ddc_displays.remove(index)), None)
+            }
+            None => {
+                let device = match block_config.device {
+                    Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
+                    None => BacklitDevice::default(block_config.root_scaling),
+                }?;
+                let path = device.device_path.clone();
+                let brightness_file = device.brightness_file();
+                (Box::new(device), Some((path, brightness_file)))
+            }
+        };
+
+        let mut synced = Vec::new();
+        if block_config.sync {
+            for other in BacklitDevice::all(block_config.root_scaling)? {
+                if backlit_path.as_ref().map(|(path, _)| path) != Some(&other.device_path) {
+                    synced.push(Box::new(other) as Box<dyn Dimmable>);
+                }
+            }
+            if block_config.sync_ddc {
+                for display in ddc_displays {
+                    synced.push(Box::new(display));
+                }
+            }
+        }
 
         let (minimum, maximum) = if block_config.minimum <= block_config.maximum {
             (block_config.minimum, block_config.maximum)
@@ -308,6 +608,16 @@ impl ConfigBlock for Backlight {
             maximum,
             cycle: block_config.cycle.unwrap_or_else(|| vec![minimum, maximum]),
             cycle_index: 0,
+            transition_duration: Duration::from_millis(block_config.transition),
+            transition: None,
+            tx_update_request: tx_update_request.clone(),
+            synced: synced
+                .into_iter()
+                .map(|display| Synced {
+                    display,
+                    exact: None,
+                })
+                .collect(),
             on_click: block_config.on_click,
             scrolling: shared_config.scrolling,
             output: TextWidget::new(id, 0, shared_config),
@@ -316,7 +626,12 @@ impl ConfigBlock for Backlight {
         };
 
         // Spin up a thread to watch for changes to the brightness file for the
-        // device, and schedule an update if needed.
+        // device, and schedule an update if needed. Monitors don't tell about
+        // changes.
+        let brightness_file = match backlit_path {
+            Some((_, brightness_file)) => brightness_file,
+            None => return Ok(backlight),
+        };
         thread::Builder::new()
             .name("backlight".into())
             .spawn(move || {
@@ -356,6 +671,18 @@ impl ConfigBlock for Backlight {
 
 impl Block for Backlight {
     fn update(&mut self) -> Result<Option<Update>> {
+        let mut next_update = None;
+        if let Some((value, done)) = self.transition.as_ref().map(|t| t.at(Instant::now())) {
+            if done {
+                self.transition = None;
+            } else {
+                next_update = Some(TRANSITION_TICK.into());
+            }
+            if value != self.device.brightness()? {
+                self.apply_brightness(value)?;
+            }
+        }
+
         let mut brightness = self.device.brightness()?;
         let values = map!(
             "brightness" => Value::from_integer(brightness as i64).percents(),
@@ -383,7 +710,7 @@ impl Block for Backlight {
             _ => "backlight_full",
         })?;
 
-        Ok(None)
+        Ok(next_update)
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -402,7 +729,7 @@ impl Block for Backlight {
                 }
             }
             _ => {
-                let brightness = self.device.brightness()? as i64;
+                let brightness = self.target_brightness()? as i64;
                 let step_width = self.step_width as i64;
                 if let Some(direction) = self.scrolling.to_logical_direction(event.button) {
                     use LogicalDirection::*;
@@ -410,7 +737,7 @@ impl Block for Backlight {
                         Up => 1,
                         Down => -1,
                     };
-                    self.device.set_brightness(
+                    self.set_brightness(
                         (brightness + sign * step_width)
                             .clamp(self.minimum as i64, self.maximum as i64)
                             as u64,
@@ -425,3 +752,53 @@ impl Block for Backlight {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ddcutil() {
+        let detect = "Display 1\n   I2C bus:  /dev/i2c-4\n   Monitor:  DEL:DELL U2415:7MT0167\n\n\
+                      Invalid display\n   I2C bus:  /dev/i2c-7\n";
+        assert_eq!(
+            ddc_displays(detect),
+            vec![
+                (4, "DEL:DELL U2415:7MT0167".to_string()),
+                (7, String::new())
+            ]
+        );
+        assert_eq!(parse_vcp("VCP 10 C 60 100\n"), Some((60, 100)));
+        assert_eq!(parse_vcp("VCP 10 ERR\n"), None);
+    }
+
+    #[test]
+    fn test_synced_brightness() {
+        assert_eq!(synced_brightness(40.0, 50, 60), 48.0);
+        assert_eq!(synced_brightness(90.0, 50, 60), 100.0);
+        assert_eq!(synced_brightness(0.0, 0, 20), 20.0);
+        // The exact value keeps small brightnesses moving
+        let up = synced_brightness(3.0, 50, 55);
+        assert_eq!(synced_brightness(up, 55, 60).round(), 4.0);
+    }
+
+    #[test]
+    fn test_transition() {
+        let start = Instant::now();
+        let transition = Transition {
+            from: 20,
+            to: 60,
+            start,
+            duration: Duration::from_millis(200),
+        };
+        assert_eq!(transition.at(start), (20, false));
+        assert_eq!(
+            transition.at(start + Duration::from_millis(50)),
+            (30, false)
+        );
+        assert_eq!(
+            transition.at(start + Duration::from_millis(200)),
+            (60, true)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Shows the age of the newest snapshot of a restic or borg repository, to notice when backups
+//! stop. Both tools are asked through their JSON output.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupTool {
+    Restic,
+    Borg,
+}
+
+/// The newest snapshot of the repository
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    /// The short ID of restic or the archive name of borg
+    name: String,
+    hostname: String,
+    time: DateTime<Utc>,
+}
+
+impl BackupTool {
+    /// The shell command that lists the newest snapshots as JSON
+    fn command(self, repository: Option<&str>, password_command: Option<&str>) -> String {
+        let (password_var, list) = match self {
+            // One snapshot per host and paths, the newest of which wins
+            BackupTool::Restic => (
+                "RESTIC_PASSWORD_COMMAND",
+                "restic snapshots --json --latest 1 --no-lock",
+            ),
+            BackupTool::Borg => (
+                "BORG_PASSCOMMAND",
+                "borg list --json --last 1 --bypass-lock",
+            ),
+        };
+        let mut command = String::new();
+        if let Some(password_command) = password_command {
+            command.push_str(&format!("{}='{}' ", password_var, password_command));
+        }
+        command.push_str(list);
+        match (self, repository) {
+            (BackupTool::Restic, Some(repository)) => {
+                command.push_str(&format!(" --repo '{}'", repository))
+            }
+            (BackupTool::Borg, Some(repository)) => command.push_str(&format!(" '{}'", repository)),
+            // The tools fall back to RESTIC_REPOSITORY and BORG_REPO
+            (_, None) => {}
+        }
+        command
+    }
+
+    fn parse(self, output: &str) -> Result<Option<Snapshot>> {
+        let json: JsonValue = serde_json::from_str(output)
+            .block_error("backup", "unexpected output of the backup tool")?;
+        let snapshots = match self {
+            BackupTool::Restic => json.as_array(),
+            BackupTool::Borg => json["archives"].as_array(),
+        };
+        let mut newest: Option<Snapshot> = None;
+        for snapshot in snapshots.into_iter().flatten() {
+            let time = match self {
+                BackupTool::Restic => snapshot["time"]
+                    .as_str()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                    .map(|time| time.with_timezone(&Utc)),
+                // borg lists local times without a time zone
+                BackupTool::Borg => snapshot["time"]
+                    .as_str()
+                    .and_then(|time| {
+                        NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f").ok()
+                    })
+                    .and_then(|time| Local.from_local_datetime(&time).earliest())
+                    .map(|time| time.with_timezone(&Utc)),
+            }
+            .block_error("backup", "unexpected time of a snapshot")?;
+            if newest
+                .as_ref()
+                .map(|newest| newest.time < time)
+                .unwrap_or(true)
+            {
+                let field = |key: &str| snapshot[key].as_str().unwrap_or_default().to_string();
+                newest = Some(Snapshot {
+                    name: field(match self {
+                        BackupTool::Restic => "short_id",
+                        BackupTool::Borg => "name",
+                    }),
+                    // borg only knows the host from the archive name
+                    hostname: field("hostname"),
+                    time,
+                });
+            }
+        }
+        Ok(newest)
+    }
+}
+
+pub struct Backup {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    tool: BackupTool,
+    command: String,
+    warning: i64,
+    critical: i64,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct BackupConfig {
+    pub tool: BackupTool,
+
+    /// The repository, which otherwise comes from `RESTIC_REPOSITORY` or `BORG_REPO`
+    pub repository: Option<String>,
+
+    /// Command that prints the password of the repository
+    pub password_command: Option<String>,
+
+    /// Hours since the newest snapshot from which on the block is shown as warning
+    pub warning: i64,
+
+    /// Hours since the newest snapshot from which on the block is shown as critical
+    pub critical: i64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            tool: BackupTool::Restic,
+            repository: None,
+            password_command: None,
+            warning: 26,
+            critical: 50,
+            interval: Duration::from_secs(3600),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Backup {
+    type Config = BackupConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Backup {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("disk_drive")?,
+            format: block_config.format.with_default("{age:dur}")?,
+            tool: block_config.tool,
+            command: block_config.tool.command(
+                block_config.repository.as_deref(),
+                block_config.password_command.as_deref(),
+            ),
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+impl Block for Backup {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let output = self.command_runner.run(&self.command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "backup".to_string(),
+                "failed to list the snapshots".to_string(),
+            ));
+        }
+
+        match self.tool.parse(&output.stdout)? {
+            Some(snapshot) => {
+                let age = (Utc::now() - snapshot.time).num_seconds().max(0);
+                let values = map!(
+                    "age" => Value::from_integer(age).seconds(),
+                    "name" => Value::from_string(snapshot.name),
+                    "hostname" => Value::from_string(snapshot.hostname),
+                );
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(if age >= self.critical * 3600 {
+                    State::Critical
+                } else if age >= self.warning * 3600 {
+                    State::Warning
+                } else {
+                    State::Idle
+                });
+            }
+            None => {
+                self.text.set_text("none".to_string());
+                self.text.set_state(State::Critical);
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let restic = r#"[
+            {"time": "2021-10-14T03:00:12.5+02:00", "hostname": "laptop", "short_id": "1a2b3c4d"},
+            {"time": "2021-10-15T03:00:40.25+02:00", "hostname": "desktop", "short_id": "5e6f7a8b"}
+        ]"#;
+        assert_eq!(
+            BackupTool::Restic.parse(restic).unwrap(),
+            Some(Snapshot {
+                name: "5e6f7a8b".to_string(),
+                hostname: "desktop".to_string(),
+                time: Utc.ymd(2021, 10, 15).and_hms_milli(1, 0, 40, 250),
+            })
+        );
+        assert_eq!(BackupTool::Restic.parse("[]").unwrap(), None);
+
+        let borg = r#"{"archives": [{"name": "laptop-2021-10-15", "time": "2021-10-15T03:00:40.000000"}]}"#;
+        assert_eq!(
+            BackupTool::Borg
+                .parse(borg)
+                .unwrap()
+                .map(|snapshot| snapshot.name),
+            Some("laptop-2021-10-15".to_string())
+        );
+        assert_eq!(
+            BackupTool::Borg.command(Some("/mnt/borg"), Some("pass borg")),
+            "BORG_PASSCOMMAND='pass borg' borg list --json --last 1 --bypass-lock '/mnt/borg'"
+        );
+    }
+}
@@ -1,10 +1,34 @@
 //! A Base block for common behavior for all blocks
 
 use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, Timelike};
+use crossbeam_channel::Sender;
+use inotify::{Event, Inotify, WatchMask};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::{pipe, Pid};
 
 use crate::errors::*;
+use crate::formatting::record_values;
+use crate::menu::Launcher;
+use crate::protocol::i3bar_block::{I3BarBlockAlign, I3BarBlockMinWidth};
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
-use crate::{blocks::Update, subprocess::spawn_child_async, widgets::I3BarWidget, Block};
+use crate::protocol::plain::strip_markup;
+use crate::scheduler::Task;
+use crate::themes::Gradient;
+use crate::widgets::{EmptyPolicy, I3BarWidget, Markup, Overflow, State};
+use crate::{blocks::Update, subprocess::spawn_child_async, Block};
 
 use serde_derive::Deserialize;
 use toml::{value::Table, Value};
@@ -13,37 +37,307 @@ pub(super) struct BaseBlock<T: Block> {
     pub name: String,
     pub inner: T,
     pub on_click: Option<String>,
+    pub on_double_click: Option<String>,
+    /// Commands that left clicks run instead while a modifier is held, by the name of the modifier
+    pub on_click_modifiers: Vec<(&'static str, String)>,
+    /// Commands that clicks with other buttons than the left one run instead
+    pub on_buttons: Vec<(MouseButton, String)>,
+    /// The values of the placeholders that the block rendered last, for the click commands
+    pub last_values: HashMap<String, String>,
+    /// Shows the menu of the block
+    pub launcher: Launcher,
+    /// States that a desktop notification is sent for when the block enters them
+    pub notify_on: Vec<State>,
+    /// The most alarming state of the widgets after the last update
+    pub last_state: State,
+    pub group: Option<String>,
+    pub if_empty: Option<EmptyPolicy>,
+    pub condition: Option<Condition>,
+    pub visible: bool,
+    pub private: bool,
+    pub profiles: Option<Vec<String>>,
+    /// Multiple of the update interval after which the block counts as stale
+    pub watchdog: Option<u32>,
+    pub last_update: Instant,
+    /// The interval the block asked for with its last update
+    pub interval: Option<Duration>,
+    /// When the block was last clicked
+    pub pressed: Option<Instant>,
+    /// Shortest interval the block is updated at, see `monochrome`
+    pub min_interval: Option<Duration>,
+    pub interval_align: Option<IntervalAlign>,
+    /// Delay of the second update, which staggers blocks with the same interval, see `jitter`
+    pub jitter: Option<Duration>,
+    /// Watches the files of `watch_files`, see `watch_files`
+    pub file_watch: Option<FileWatch>,
+}
+
+/// What the updates of a block are aligned to
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum IntervalAlign {
+    Minute,
+    Hour,
+}
+
+impl IntervalAlign {
+    fn unit(self) -> f64 {
+        match self {
+            IntervalAlign::Minute => 60.,
+            IntervalAlign::Hour => 3600.,
+        }
+    }
+
+    /// The time from `now`, in seconds since midnight, to the next multiple of `interval` counted
+    /// from the start of the minute or hour. Intervals longer than the unit are rounded to whole
+    /// minutes or hours.
+    fn delay(self, now: f64, interval: Duration) -> Duration {
+        let unit = self.unit();
+        let interval = interval.as_secs_f64();
+        let step = if interval < unit {
+            interval
+        } else {
+            (interval / unit).round() * unit
+        };
+        // The scheduler may wake up a bit early, which must not count as not yet at the boundary
+        let early = now + step / 100.;
+        let start = (early / unit).floor() * unit;
+        let next = start + ((early - start) / step).floor() * step + step;
+        Duration::from_secs_f64(next - now)
+    }
 }
 
+/// A share of `jitter` for the block with the id `id`. Consecutive ids are spread evenly, by the
+/// golden ratio.
+pub(super) fn jitter_offset(id: usize, jitter: Duration) -> Duration {
+    jitter.mul_f64((id as f64 * 0.618_033_988_75).fract())
+}
+
+/// How long a clicked block is shown as such, at least
+pub const CLICK_FEEDBACK: Duration = Duration::from_millis(150);
+
 impl<T: Block> Block for BaseBlock<T> {
     fn id(&self) -> usize {
         self.inner.id()
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        self.inner.view()
+        if !self.visible {
+            return Vec::new();
+        }
+        if let Some(profiles) = &self.profiles {
+            if !crate::profiles::shown(profiles) {
+                return Vec::new();
+            }
+        }
+        match &self.group {
+            Some(group) if super::group::is_collapsed(group) => Vec::new(),
+            _ => match self.if_empty {
+                // Widgets render to an empty text when the policy hides them
+                Some(EmptyPolicy::Hide) | Some(EmptyPolicy::IconOnly) => self
+                    .inner
+                    .view()
+                    .into_iter()
+                    .filter(|widget| !widget.get_data().full_text.is_empty())
+                    .collect(),
+                _ => self.inner.view(),
+            },
+        }
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        self.inner.update()
+        if let Some(condition) = &self.condition {
+            self.visible = condition.check();
+        }
+        let (name, inner) = (&self.name, &mut self.inner);
+        let (update, values) =
+            record_values(|| crate::logging::in_block(name, inner.id(), || inner.update()));
+        self.remember_values(values);
+        let update = update?;
+        self.notify_state();
+        self.last_update = Instant::now();
+        let update = match (update, self.min_interval) {
+            (Some(Update::Every(interval)), Some(min_interval)) => {
+                Some(Update::Every(interval.max(min_interval)))
+            }
+            (update, _) => update,
+        };
+        // Blocks that wait for events have no interval to be late by
+        self.interval = match update {
+            Some(Update::Every(interval)) => Some(interval),
+            _ => None,
+        };
+        let update = match (update, self.interval_align) {
+            (Some(Update::Every(interval)), Some(align)) => {
+                let now = Local::now();
+                let seconds = now.num_seconds_from_midnight() as f64
+                    + now.nanosecond() as f64 / 1_000_000_000.;
+                Some(Update::Every(align.delay(seconds, interval)))
+            }
+            (Some(Update::Every(interval)), None) => Some(Update::Every(
+                interval + self.jitter.take().unwrap_or_default(),
+            )),
+            (update, _) => update,
+        };
+        Ok(update)
     }
 
     fn signal(&mut self, signal: i32) -> Result<()> {
-        self.inner.signal(signal)
+        let inner = &mut self.inner;
+        crate::logging::in_block(&self.name, inner.id(), || inner.signal(signal))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.file_watch = None;
+        let inner = &mut self.inner;
+        crate::logging::in_block(&self.name, inner.id(), || inner.stop())
+    }
+
+    fn private(&self) -> bool {
+        self.private
+    }
+
+    fn press(&mut self) {
+        self.pressed = Some(Instant::now());
+    }
+
+    fn pressed(&self) -> bool {
+        self.pressed
+            .map(|pressed| pressed.elapsed() < CLICK_FEEDBACK)
+            .unwrap_or(false)
+    }
+
+    fn stale(&self) -> bool {
+        match (self.watchdog, self.interval) {
+            (Some(watchdog), Some(interval)) => self.last_update.elapsed() > interval * watchdog,
+            _ => false,
+        }
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        match &self.on_click {
-            Some(cmd) => {
-                if let MouseButton::Left = e.button {
-                    spawn_child_async("sh", &["-c", cmd])
-                        .block_error(&self.name, "could not spawn child")?;
-                }
-                Ok(())
+        if let Some(cmd) = self.click_command(e) {
+            let cmd = fill_placeholders(cmd, &self.last_values);
+            return spawn_child_async("sh", &["-c", &cmd])
+                .block_error(&self.name, "could not spawn child");
+        }
+        let (name, inner) = (&self.name, &mut self.inner);
+        if e.button == MouseButton::Right {
+            let menu = crate::logging::in_block(name, inner.id(), || inner.menu())?;
+            if let Some(entries) = menu {
+                return self.launcher.show(self.inner.id(), entries);
+            }
+        }
+        if self.on_click.is_some() {
+            return Ok(());
+        }
+        let (result, values) =
+            record_values(|| crate::logging::in_block(name, inner.id(), || inner.click(e)));
+        self.remember_values(values);
+        result
+    }
+}
+
+impl<T: Block> BaseBlock<T> {
+    /// The command that `e` runs instead of the click handling of the block, if any
+    fn click_command(&self, e: &I3BarEvent) -> Option<&String> {
+        if e.button == MouseButton::Left {
+            let modifier = self
+                .on_click_modifiers
+                .iter()
+                .find(|(modifier, _)| e.has_modifier(modifier));
+            if let Some((_, cmd)) = modifier {
+                return Some(cmd);
             }
-            None => self.inner.click(e),
+            // Takes the place of the second click, so that it doesn't undo the first one
+            if e.double_click && self.on_double_click.is_some() {
+                return self.on_double_click.as_ref();
+            }
+            return self.on_click.as_ref();
+        }
+        self.on_buttons
+            .iter()
+            .find(|(button, _)| *button == e.button)
+            .map(|(_, cmd)| cmd)
+    }
+
+    /// Sends a desktop notification with the text of the block if it entered one of the states
+    /// of `notify_on`
+    fn notify_state(&mut self) {
+        let widgets = self.inner.view();
+        let state = widgets
+            .iter()
+            .map(|widget| widget.state())
+            .max_by_key(|state| state.severity())
+            .unwrap_or(State::Idle);
+        if state == self.last_state {
+            return;
+        }
+        self.last_state = state;
+        if !self.notify_on.contains(&state) {
+            return;
+        }
+        let text: Vec<String> = widgets
+            .iter()
+            .map(|widget| {
+                strip_markup(&widget.get_data().full_text)
+                    .trim()
+                    .to_string()
+            })
+            .filter(|text| !text.is_empty())
+            .collect();
+        let urgency = match state {
+            State::Critical => "critical",
+            State::Warning => "normal",
+            _ => "low",
+        };
+        // A missing notify-send only costs the notification
+        if let Err(error) = spawn_child_async(
+            "notify-send",
+            &["-u", urgency, &self.name.to_lowercase(), &text.join(" ")],
+        ) {
+            log::warn!("failed to run notify-send: {}", error);
         }
     }
+
+    /// Keeps the values of an update or click, unless it rendered nothing
+    fn remember_values(&mut self, values: HashMap<String, String>) {
+        if !values.is_empty() {
+            self.last_values = values;
+        }
+    }
+}
+
+/// Replaces the placeholders in `cmd` by the values, quoted for the shell. Unknown placeholders are
+/// left as they are.
+fn fill_placeholders(cmd: &str, values: &HashMap<String, String>) -> String {
+    let mut filled = cmd.to_string();
+    for (name, value) in values {
+        let placeholder = format!("{{{}}}", name);
+        if filled.contains(&placeholder) {
+            filled = filled.replace(&placeholder, &quote(value));
+        }
+    }
+    filled
+}
+
+/// Quotes a string for the shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Stands in for a block whose requirements aren't met, keeping the ids of the following blocks
+pub(super) struct Unavailable {
+    pub id: usize,
+}
+
+impl Block for Unavailable {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        Vec::new()
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -51,12 +345,122 @@ pub(super) struct BaseBlockConfig {
     /// Command to execute when the button is clicked
     pub on_click: Option<String>,
 
+    /// Command to execute on a double click with the left button
+    pub on_double_click: Option<String>,
+
+    /// Commands to execute on clicks with other buttons
+    pub on_right_click: Option<String>,
+    pub on_middle_click: Option<String>,
+    pub on_scroll_up: Option<String>,
+    pub on_scroll_down: Option<String>,
+
+    /// Commands to execute when the button is clicked while a modifier key is held
+    pub on_click_shift: Option<String>,
+    pub on_click_ctrl: Option<String>,
+    pub on_click_alt: Option<String>,
+    pub on_click_super: Option<String>,
+
     pub theme_overrides: Option<HashMap<String, String>>,
     pub icons_format: Option<String>,
+    pub icons_overrides: Option<HashMap<String, String>>,
+
+    /// Name of a `group` block that shows and hides this block
+    pub group: Option<String>,
+
+    /// What to show in place of widgets without text
+    pub if_empty: Option<EmptyPolicy>,
+    pub if_empty_text: Option<String>,
+
+    /// Capabilities the machine needs for the block, which is left out otherwise
+    pub requires: Option<Vec<String>>,
+
+    /// Shell command that decides whether the block is shown, checked on every update
+    pub condition_command: Option<String>,
+
+    /// Number of characters after which the text of widgets overflows
+    pub max_text_width: Option<usize>,
+    pub overflow: Option<Overflow>,
+
+    /// Whether the text of widgets is pango markup
+    pub markup: Option<Markup>,
+
+    /// Pango font description of the text of widgets, like "Symbols Nerd Font 13"
+    pub font: Option<String>,
+
+    /// Width that i3bar reserves for widgets, and where it puts the text within it
+    pub min_width: Option<I3BarBlockMinWidth>,
+    pub align: Option<I3BarBlockAlign>,
+
+    /// Colors the block by its value instead of its state
+    pub gradient: Option<Gradient>,
+
+    /// Format of the text shown when the bar runs out of space
+    pub short_format: Option<String>,
+
+    /// Keeps the block out of everything but the bar, e.g. because it shows where the user is
+    #[serde(default)]
+    pub private: bool,
+
+    /// Profiles the block is shown in, see `profiles`
+    pub profiles: Option<Vec<String>>,
+
+    /// Multiple of the update interval after which a block that stopped updating is shown as
+    /// stale and restarted
+    pub watchdog: Option<u32>,
+
+    /// Files and directories whose changes update the block right away
+    pub watch_files: Option<Vec<String>>,
+
+    /// Moves the updates to multiples of the interval counted from the start of a minute or hour
+    pub interval_align: Option<IntervalAlign>,
+
+    /// Outputs whose bar shows the block, all by default
+    pub outputs: Option<Vec<String>>,
+    /// Outputs whose bar doesn't show the block
+    pub outputs_exclude: Option<Vec<String>>,
+
+    /// States that a desktop notification is sent for when the block enters them
+    #[serde(default)]
+    pub notify_on: Vec<State>,
 }
 
 impl BaseBlockConfig {
-    const FIELDS: &'static [&'static str] = &["on_click", "theme_overrides", "icons_format"];
+    const FIELDS: &'static [&'static str] = &[
+        "on_click",
+        "on_double_click",
+        "on_click_shift",
+        "on_click_ctrl",
+        "on_click_alt",
+        "on_click_super",
+        "on_right_click",
+        "on_middle_click",
+        "on_scroll_up",
+        "on_scroll_down",
+        "theme_overrides",
+        "icons_format",
+        "icons_overrides",
+        "group",
+        "if_empty",
+        "if_empty_text",
+        "requires",
+        "condition_command",
+        "max_text_width",
+        "overflow",
+        "markup",
+        "font",
+        "min_width",
+        "align",
+        "gradient",
+        "short_format",
+        "private",
+        "profiles",
+        "watchdog",
+        "watch_files",
+        "interval_align",
+        "outputs",
+        "outputs_exclude",
+        "notify_on",
+    ];
 
     // FIXME: this function is to paper over https://github.com/serde-rs/serde/issues/1957
     pub(super) fn extract(config: &mut Value) -> Value {
@@ -70,4 +474,371 @@ impl BaseBlockConfig {
         }
         common_table.into()
     }
+
+    /// The `on_click_*` commands by the names that i3bar and swaybar give the modifiers
+    pub(super) fn modifier_commands(&mut self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Shift", self.on_click_shift.take()),
+            ("Control", self.on_click_ctrl.take()),
+            ("Mod1", self.on_click_alt.take()),
+            ("Mod4", self.on_click_super.take()),
+        ]
+        .into_iter()
+        .filter_map(|(modifier, cmd)| Some((modifier, cmd?)))
+        .collect()
+    }
+
+    /// The commands of the other buttons than the left one
+    pub(super) fn button_commands(&mut self) -> Vec<(MouseButton, String)> {
+        vec![
+            (MouseButton::Right, self.on_right_click.take()),
+            (MouseButton::Middle, self.on_middle_click.take()),
+            (MouseButton::WheelUp, self.on_scroll_up.take()),
+            (MouseButton::WheelDown, self.on_scroll_down.take()),
+        ]
+        .into_iter()
+        .filter_map(|(button, cmd)| Some((button, cmd?)))
+        .collect()
+    }
+
+    /// Moves `short_format` into the `format` of the block, where it works like `short` in a
+    /// `format` table
+    pub(super) fn apply_short_format(&mut self, config: &mut Value) -> Result<()> {
+        let short = match self.short_format.take() {
+            Some(short) => Value::String(short),
+            None => return Ok(()),
+        };
+        let table = match config.as_table_mut() {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let mut format = match table.remove("format") {
+            None => Table::new(),
+            Some(Value::String(full)) => {
+                let mut format = Table::new();
+                format.insert("full".to_string(), Value::String(full));
+                format
+            }
+            Some(Value::Table(format)) if format.contains_key("short") => {
+                return Err(ConfigurationError(
+                    "short_format".to_string(),
+                    "format already has a short variant".to_string(),
+                ))
+            }
+            Some(Value::Table(format)) => format,
+            Some(_) => {
+                return Err(ConfigurationError(
+                    "format".to_string(),
+                    "expected a string or a table".to_string(),
+                ))
+            }
+        };
+        format.insert("short".to_string(), short);
+        table.insert("format".to_string(), Value::Table(format));
+        Ok(())
+    }
+}
+
+/// Keeps the files of `watch_files` watched until it is dropped, which ends the thread that
+/// watches them
+pub(super) struct FileWatch {
+    /// Closing the pipe wakes the thread up
+    _stop: File,
+}
+
+/// Updates the block with the id `id` whenever one of the files or directories at `paths` changes
+pub(super) fn watch_files(
+    id: usize,
+    paths: &[String],
+    tx_update_request: Sender<Task>,
+) -> Result<FileWatch> {
+    let mut inotify = Inotify::init().internal_error("watch_files", "failed to start inotify")?;
+    let mask = WatchMask::MODIFY
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO;
+    // The names of the files that are watched in each directory, `None` for all of them
+    let mut watches = Vec::new();
+    for path in paths {
+        let expanded = shellexpand::full(path)
+            .configuration_error(&format!("failed to expand file path {}", path))?;
+        let path = Path::new(&*expanded);
+        // Files are watched through their directory, as a watch on the file itself ends when the
+        // file is replaced, which is how most programs save
+        let (dir, name) = match (path.is_dir(), path.parent(), path.file_name()) {
+            (false, Some(dir), Some(name)) => (dir, Some(name.to_os_string())),
+            _ => (path, None),
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let wd = inotify
+            .add_watch(dir, mask)
+            .configuration_error(&format!("failed to watch file {}", path.display()))?;
+        watches.push((wd, name));
+    }
+
+    follow_inotify(inotify, id, tx_update_request, move |event| {
+        watches
+            .iter()
+            .any(|(wd, name)| *wd == event.wd && (name.is_none() || name.as_deref() == event.name))
+    })
+}
+
+/// Updates the block with the id `id` whenever `inotify` has an event that is `relevant`, until
+/// the returned `FileWatch` is dropped
+pub(super) fn follow_inotify<F>(
+    mut inotify: Inotify,
+    id: usize,
+    tx_update_request: Sender<Task>,
+    relevant: F,
+) -> Result<FileWatch>
+where
+    F: Fn(&Event<&OsStr>) -> bool + Send + 'static,
+{
+    let (stop_read, stop_write) =
+        pipe().internal_error("watch_files", "failed to create a pipe")?;
+    // Owned, so that they are closed
+    let (stop_read, stop_write) =
+        unsafe { (File::from_raw_fd(stop_read), File::from_raw_fd(stop_write)) };
+    thread::Builder::new()
+        .name("watch_files".into())
+        .spawn(move || {
+            let mut buffer = [0; 1024];
+            let mut fds = [
+                PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN),
+                PollFd::new(stop_read.as_raw_fd(), PollFlags::POLLIN),
+            ];
+            while let Ok(_) | Err(Errno::EINTR) = poll(&mut fds, -1) {
+                // The block was stopped
+                if fds[1].revents().is_some_and(|revents| !revents.is_empty()) {
+                    break;
+                }
+                let changed = match inotify.read_events(&mut buffer) {
+                    Ok(mut events) => events.any(|event| relevant(&event)),
+                    Err(_) => break,
+                };
+                if !changed {
+                    continue;
+                }
+                let task = Task {
+                    id,
+                    update_time: Instant::now(),
+                };
+                if tx_update_request.send(task).is_err() {
+                    break;
+                }
+                // Writing a file often takes several events
+                thread::sleep(Duration::from_millis(250));
+                while let Ok(mut events) = inotify.read_events(&mut buffer) {
+                    if events.next().is_none() {
+                        break;
+                    }
+                }
+            }
+        })
+        .internal_error("watch_files", "failed to start a thread")?;
+    Ok(FileWatch { _stop: stop_write })
+}
+
+/// How long `condition_command` may take before it is stopped and counts as failed
+const CONDITION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct Check {
+    /// Whether the command succeeded the last time it ran
+    met: bool,
+    running: bool,
+}
+
+/// Runs `condition_command` in the background, so that a slow command doesn't hold up the bar
+pub(super) struct Condition {
+    command: String,
+    id: usize,
+    tx_update_request: Sender<Task>,
+    check: Arc<Mutex<Check>>,
+}
+
+impl Condition {
+    pub fn new(command: String, id: usize, tx_update_request: Sender<Task>) -> Self {
+        Condition {
+            command,
+            id,
+            tx_update_request,
+            check: Arc::new(Mutex::new(Check::default())),
+        }
+    }
+
+    /// Whether the command succeeded the last time it ran, and runs it again unless it still
+    /// runs. The block is updated again when the outcome changes.
+    pub fn check(&self) -> bool {
+        let mut check = self.check.lock().unwrap();
+        if !check.running {
+            check.running = true;
+            let shared = self.check.clone();
+            let (command, id) = (self.command.clone(), self.id);
+            let tx_update_request = self.tx_update_request.clone();
+            let spawned = thread::Builder::new()
+                .name("condition".into())
+                .spawn(move || {
+                    let met = run_condition(&command);
+                    let mut check = shared.lock().unwrap();
+                    check.running = false;
+                    if check.met != met {
+                        check.met = met;
+                        let _ = tx_update_request.send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        });
+                    }
+                });
+            if spawned.is_err() {
+                log::warn!("failed to start a thread");
+                check.running = false;
+            }
+        }
+        check.met
+    }
+}
+
+/// Whether `command` succeeds within `CONDITION_TIMEOUT`
+fn run_condition(command: &str) -> bool {
+    // The output must not end up in the bar's stdout
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Its own process group, so that what the shell started can be stopped along with it
+        .process_group(0)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if started.elapsed() < CONDITION_TIMEOUT => {
+                thread::sleep(Duration::from_millis(50))
+            }
+            Ok(None) => {
+                log::warn!("condition_command `{}` timed out", command);
+                let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+                let _ = child.wait();
+                return false;
+            }
+            Err(_) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale() {
+        let mut block = BaseBlock {
+            name: "unavailable".to_string(),
+            inner: Unavailable { id: 0 },
+            on_click: None,
+            on_double_click: None,
+            on_click_modifiers: Vec::new(),
+            on_buttons: Vec::new(),
+            last_values: HashMap::new(),
+            launcher: Launcher::new(String::new(), crossbeam_channel::unbounded().0),
+            notify_on: Vec::new(),
+            last_state: State::Idle,
+            group: None,
+            if_empty: None,
+            condition: None,
+            visible: true,
+            private: false,
+            profiles: None,
+            watchdog: Some(3),
+            last_update: Instant::now() - Duration::from_secs(5),
+            interval: Some(Duration::from_secs(2)),
+            pressed: None,
+            min_interval: None,
+            interval_align: None,
+            jitter: None,
+            file_watch: None,
+        };
+        assert!(!block.stale());
+
+        block.last_update -= Duration::from_secs(2);
+        assert!(block.stale());
+
+        // Blocks that wait for events are never late
+        block.update().unwrap();
+        block.last_update -= Duration::from_secs(60);
+        assert!(!block.stale());
+    }
+
+    #[test]
+    fn test_interval_align() {
+        let delay = |align: IntervalAlign, now: f64, interval: u64| {
+            align
+                .delay(now, Duration::from_secs(interval))
+                .as_secs_f64()
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        // 12:00:55
+        let now = 12. * 3600. + 55.;
+        assert!(close(delay(IntervalAlign::Minute, now, 60), 5.));
+        assert!(close(delay(IntervalAlign::Minute, now, 10), 5.));
+        assert!(close(delay(IntervalAlign::Hour, now, 300), 245.));
+        assert!(close(delay(IntervalAlign::Hour, now, 3600), 3545.));
+        // Updated a moment before the boundary
+        assert!(close(delay(IntervalAlign::Minute, 59.99, 10), 10.01));
+
+        let jitter = Duration::from_secs(10);
+        assert_eq!(jitter_offset(0, jitter), Duration::from_secs(0));
+        assert!(jitter_offset(1, jitter) != jitter_offset(2, jitter));
+    }
+
+    #[test]
+    fn test_watch_files() {
+        let dir = std::env::temp_dir().join(format!("i3status-rs-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("status");
+        std::fs::write(&path, "1").unwrap();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let watch = watch_files(7, &[path.display().to_string()], tx).unwrap();
+        let timeout = Duration::from_secs(2);
+
+        std::fs::write(dir.join("other"), "1").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        // Replaced the way editors save, which must not end the watch
+        for content in &["2", "3"] {
+            std::fs::write(dir.join("status.tmp"), content).unwrap();
+            std::fs::rename(dir.join("status.tmp"), &path).unwrap();
+            assert_eq!(rx.recv_timeout(timeout).unwrap().id, 7);
+            thread::sleep(Duration::from_millis(300));
+            assert!(rx.try_recv().is_err());
+        }
+
+        // The thread ends with the watch
+        drop(watch);
+        assert_eq!(
+            rx.recv_timeout(timeout),
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected)
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fill_placeholders() {
+        let values = map_to_owned!("volume" => "50", "title" => "Don't Stop");
+        assert_eq!(
+            fill_placeholders("notify-send {title} {volume}% {unknown}", &values),
+            r"notify-send 'Don'\''t Stop' '50'% {unknown}"
+        );
+    }
 }
@@ -4,16 +4,27 @@
 //! display the status, capacity, and time remaining for (dis)charge for an
 //! internal power supply.
 
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
-use std::thread;
+#[cfg(feature = "dbus")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "dbus")]
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
-use dbus::arg::Array;
-use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+#[cfg(feature = "dbus")]
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+#[cfg(feature = "dbus")]
+use dbus::blocking::SyncConnection;
+#[cfg(feature = "dbus")]
+use dbus::message::MatchRule;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
+#[cfg(feature = "dbus")]
+use crate::buses::{Bus, Watch};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
@@ -21,6 +32,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
 use crate::util::{battery_level_to_icon, read_file};
+use crate::widgets::progress::{ProgressConfig, ProgressWidget};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, Spacing, State};
 
@@ -78,6 +90,198 @@ impl PowerSupplyDevice {
 
         Ok(device)
     }
+
+    /// The energy stored now and when full, in µWh. Devices that only report their charge are
+    /// converted with their voltage.
+    fn energy(&self) -> Option<(f64, f64)> {
+        let read = |name: &str| -> Option<f64> {
+            read_file("battery", &self.device_path.join(name))
+                .ok()?
+                .parse()
+                .ok()
+        };
+        if let (Some(now), Some(full)) = (read("energy_now"), self.energy_full) {
+            return Some((now, full as f64));
+        }
+        let voltage = read("voltage_min_design").or_else(|| read("voltage_now"))? * 1e-6;
+        Some((
+            read("charge_now")? * voltage,
+            self.charge_full? as f64 * voltage,
+        ))
+    }
+}
+
+/// What one battery reports, for combining several
+#[derive(Debug, Clone)]
+struct Reading {
+    status: String,
+    capacity: Option<u64>,
+    /// Now and when full, in µWh
+    energy: Option<(f64, f64)>,
+    /// In µW
+    power: Option<u64>,
+}
+
+/// Several batteries combined into one
+#[derive(Debug, Clone, PartialEq)]
+struct Combined {
+    status: String,
+    capacity: Option<u64>,
+    power: Option<u64>,
+    /// Minutes until empty or full
+    time_remaining: Option<u64>,
+}
+
+fn combine(readings: &[Reading]) -> Combined {
+    let any = |status: &str| readings.iter().any(|reading| reading.status == status);
+    let status = if any("Charging") {
+        "Charging"
+    } else if any("Discharging") {
+        "Discharging"
+    } else if readings
+        .iter()
+        .all(|reading| reading.status == "Full" || reading.status == "Not charging")
+    {
+        "Full"
+    } else {
+        "Unknown"
+    };
+
+    // Weighted by the size of the batteries, if they are all known
+    let energies: Option<Vec<(f64, f64)>> = readings.iter().map(|reading| reading.energy).collect();
+    let (now, full) = energies
+        .unwrap_or_default()
+        .iter()
+        .fold((0., 0.), |(now, full), energy| {
+            (now + energy.0, full + energy.1)
+        });
+    let capacities: Option<Vec<u64>> = readings.iter().map(|reading| reading.capacity).collect();
+    let capacity = if full > 0. {
+        Some(((now / full * 100.) as u64).min(100))
+    } else {
+        capacities
+            .filter(|capacities| !capacities.is_empty())
+            .map(|capacities| capacities.iter().sum::<u64>() / capacities.len() as u64)
+    };
+
+    let power = readings
+        .iter()
+        .filter_map(|reading| reading.power)
+        .sum::<u64>();
+    let time_remaining = match status {
+        _ if full <= 0. || power == 0 => None,
+        "Discharging" => Some((now / power as f64 * 60.) as u64),
+        "Charging" => Some(((full - now) / power as f64 * 60.) as u64),
+        _ => Some(0),
+    };
+
+    Combined {
+        status: status.to_string(),
+        capacity,
+        power: readings
+            .iter()
+            .any(|reading| reading.power.is_some())
+            .then_some(power),
+        time_remaining,
+    }
+}
+
+/// All batteries in `/sys/class/power_supply` combined, as if they were one. Batteries that are
+/// plugged in or out count from the next update on.
+pub struct CombinedDevice {
+    allow_missing: bool,
+    devices: Vec<PowerSupplyDevice>,
+}
+
+impl CombinedDevice {
+    pub fn new(allow_missing: bool) -> Self {
+        CombinedDevice {
+            allow_missing,
+            devices: Vec::new(),
+        }
+    }
+
+    /// The names of the batteries, like "BAT0"
+    fn names() -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir("/sys/class/power_supply")
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| name.starts_with("BAT"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn combined(&self) -> Result<Combined> {
+        if self.devices.is_empty() {
+            return Err(BlockError(
+                "battery".to_string(),
+                "No batteries found".to_string(),
+            ));
+        }
+        let readings = self
+            .devices
+            .iter()
+            .map(|device| {
+                Ok(Reading {
+                    status: device.status()?,
+                    capacity: device.capacity().ok(),
+                    energy: device.energy(),
+                    power: device.power_consumption().ok(),
+                })
+            })
+            .collect::<Result<Vec<Reading>>>()?;
+        Ok(combine(&readings))
+    }
+}
+
+impl BatteryDevice for CombinedDevice {
+    fn is_available(&self) -> bool {
+        !CombinedDevice::names().is_empty()
+    }
+
+    fn refresh_device_info(&mut self) -> Result<()> {
+        let names = CombinedDevice::names();
+        if names.is_empty() && !self.allow_missing {
+            return Err(BlockError(
+                "battery".to_string(),
+                "No batteries found".to_string(),
+            ));
+        }
+        self.devices = names
+            .iter()
+            .map(|name| PowerSupplyDevice::from_device(name, false))
+            .collect::<Result<_>>()?;
+        for device in &mut self.devices {
+            device.refresh_device_info()?;
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<String> {
+        Ok(self.combined()?.status)
+    }
+
+    fn capacity(&self) -> Result<u64> {
+        self.combined()?
+            .capacity
+            .block_error("battery", "Devices do not support reading capacity")
+    }
+
+    fn time_remaining(&self) -> Result<u64> {
+        self.combined()?
+            .time_remaining
+            .block_error("battery", "Devices do not support calculating the time")
+    }
+
+    fn power_consumption(&self) -> Result<u64> {
+        self.combined()?
+            .power
+            .block_error("battery", "Devices do not support power consumption")
+    }
 }
 
 impl BatteryDevice for PowerSupplyDevice {
@@ -305,54 +509,186 @@ impl BatteryDevice for PowerSupplyDevice {
     }
 }
 
+/// Represents the battery of a FreeBSD or OpenBSD machine, as known to `sysctl` or `apm`.
+pub struct SysctlDevice {
+    allow_missing: bool,
+    info: Option<bsd::BatteryInfo>,
+}
+
+impl SysctlDevice {
+    pub fn new(allow_missing: bool) -> Self {
+        SysctlDevice {
+            allow_missing,
+            info: None,
+        }
+    }
+
+    fn info(&self) -> Result<&bsd::BatteryInfo> {
+        self.info
+            .as_ref()
+            .block_error("battery", "Battery does not exist")
+    }
+}
+
+impl BatteryDevice for SysctlDevice {
+    fn is_available(&self) -> bool {
+        matches!(bsd::battery(), Ok(Some(_)))
+    }
+
+    fn refresh_device_info(&mut self) -> Result<()> {
+        self.info = bsd::battery()?;
+        if self.info.is_none() && !self.allow_missing {
+            return Err(BlockError(
+                "battery".into(),
+                "Battery does not exist".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<String> {
+        Ok(self.info()?.status.to_string())
+    }
+
+    fn capacity(&self) -> Result<u64> {
+        Ok(self.info()?.capacity.min(100))
+    }
+
+    fn time_remaining(&self) -> Result<u64> {
+        self.info()?
+            .time_remaining
+            .block_error("battery", "Device does not report the time remaining")
+    }
+
+    fn power_consumption(&self) -> Result<u64> {
+        Err(BlockError(
+            "battery".to_string(),
+            "Device does not support reading power consumption".to_string(),
+        ))
+    }
+}
+
+#[cfg(feature = "dbus")]
+const UPOWER: &str = "org.freedesktop.UPower";
+#[cfg(feature = "dbus")]
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+#[cfg(feature = "dbus")]
+const UPOWER_DEVICE: &str = "org.freedesktop.UPower.Device";
+#[cfg(feature = "dbus")]
+const UPOWER_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Represents a battery known to UPower.
+///
+/// The block is updated when UPower signals that the properties of the device changed. When
+/// devices are added or removed, the device is looked up again, so that a battery that is
+/// plugged in later is found.
+#[cfg(feature = "dbus")]
 pub struct UpowerDevice {
-    device_path: String,
-    con: dbus::ffidisp::Connection,
+    device: String,
+    /// The object of the device, `None` while it is missing
+    device_path: Arc<Mutex<Option<String>>>,
+    /// Set when UPower added or removed a device
+    rescan: Arc<AtomicBool>,
+    con: Arc<SyncConnection>,
+    _watches: Vec<Watch>,
 }
 
+#[cfg(feature = "dbus")]
 impl UpowerDevice {
-    /// Create the UPower device from the `device` string, which is converted to
-    /// the path `"/org/freedesktop/UPower/devices/battery_<device>"`, except if
-    /// `device` equals `"DisplayDevice"`, in which case it is converted to the
-    /// path `"/org/freedesktop/UPower/devices/DisplayDevice"`. Raises an error
-    /// if D-Bus cannot connect to this device, or if the device is not a
-    /// battery.
-    pub fn from_device(device: &str) -> Result<Self> {
-        let device_path;
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("battery", "Failed to establish D-Bus connection.")?;
-
-        if device == "DisplayDevice" {
-            device_path = String::from("/org/freedesktop/UPower/devices/DisplayDevice");
-        } else {
-            let msg = dbus::Message::new_method_call(
-                "org.freedesktop.UPower",
-                "/org/freedesktop/UPower",
-                "org.freedesktop.UPower",
-                "EnumerateDevices",
-            )
-            .block_error("battery", "Failed to create DBus message")?;
-
-            let dbus_reply = con
-                .send_with_reply_and_block(msg, 2000)
-                .block_error("battery", "Failed to retrieve DBus reply")?;
+    /// Create the UPower device from the `device` string, which is looked up
+    /// among the paths of the devices UPower knows, except if `device` equals
+    /// `"DisplayDevice"`, in which case the path
+    /// `"/org/freedesktop/UPower/devices/DisplayDevice"` is used. Raises an
+    /// error if D-Bus cannot connect to this device, if the device is not a
+    /// battery or if it is missing and `allow_missing` is `false`.
+    pub fn from_device(
+        id: usize,
+        device: &str,
+        allow_missing: bool,
+        shared_config: &SharedConfig,
+        update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let con = shared_config.buses.get(Bus::System)?;
+        let device_path = UpowerDevice::find(&con, device)?;
+        if device_path.is_none() && !allow_missing {
+            return Err(BlockError(
+                "battery".into(),
+                "UPower device could not be found.".into(),
+            ));
+        }
+        let device_path = Arc::new(Mutex::new(device_path));
+        let rescan = Arc::new(AtomicBool::new(false));
+
+        let update = move |update_request: &Sender<Task>| {
+            update_request
+                .send(Task {
+                    id,
+                    update_time: Instant::now(),
+                })
+                .is_ok()
+        };
+        let mut watches = Vec::new();
+        let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+            .with_sender(UPOWER)
+            .with_namespaced_path("/org/freedesktop/UPower/devices");
+        let changed_path = device_path.clone();
+        let changed_request = update_request.clone();
+        watches.push(
+            shared_config
+                .buses
+                .watch(Bus::System, rule, move |_: (), _, message| {
+                    let path = changed_path.lock().unwrap();
+                    match (message.path(), &*path) {
+                        (Some(changed), Some(path)) if &*changed == path => {
+                            update(&changed_request)
+                        }
+                        _ => true,
+                    }
+                })?,
+        );
+        for member in &["DeviceAdded", "DeviceRemoved"] {
+            let rule = MatchRule::new_signal(UPOWER, *member)
+                .with_sender(UPOWER)
+                .with_path(UPOWER_PATH);
+            let rescan = rescan.clone();
+            let update_request = update_request.clone();
+            watches.push(
+                shared_config
+                    .buses
+                    .watch(Bus::System, rule, move |_: (), _, _| {
+                        rescan.store(true, Ordering::SeqCst);
+                        update(&update_request)
+                    })?,
+            );
+        }
 
-            // EnumerateDevices returns one argument, which is an array of ObjectPaths (not dbus::tree:ObjectPath).
-            let mut paths: Array<dbus::Path, _> = dbus_reply
-                .get1()
-                .block_error("battery", "Failed to read DBus reply")?;
+        Ok(UpowerDevice {
+            device: device.to_string(),
+            device_path,
+            rescan,
+            con,
+            _watches: watches,
+        })
+    }
 
-            device_path = paths
-                .find(|entry| entry.ends_with(device))
-                .block_error("battery", "UPower device could not be found.")?
-                .to_string();
+    /// The path of the battery `device`, `None` if UPower doesn't know it
+    fn find(con: &SyncConnection, device: &str) -> Result<Option<String>> {
+        if device == "DisplayDevice" {
+            return Ok(Some(format!("{}/devices/DisplayDevice", UPOWER_PATH)));
         }
+        let upower = con.with_proxy(UPOWER, UPOWER_PATH, UPOWER_TIMEOUT);
+        let (paths,): (Vec<dbus::Path>,) = upower
+            .method_call(UPOWER, "EnumerateDevices", ())
+            .block_error("battery", "Failed to retrieve DBus reply")?;
+        let device_path = match paths.iter().find(|path| path.ends_with(device)) {
+            Some(path) => path.to_string(),
+            None => return Ok(None),
+        };
+
         let upower_type: u32 = con
-            .with_path("org.freedesktop.UPower", &device_path, 1000)
-            .get("org.freedesktop.UPower.Device", "Type")
+            .with_proxy(UPOWER, &device_path, UPOWER_TIMEOUT)
+            .get(UPOWER_DEVICE, "Type")
             .block_error("battery", "Failed to read UPower Type property.")?;
-
         // https://upower.freedesktop.org/docs/Device.html#Device:Type
         // consider any peripheral, UPS and internal battery
         if upower_type == 1 {
@@ -361,53 +697,42 @@ impl UpowerDevice {
                 "UPower device is not a battery.".into(),
             ));
         }
-        Ok(UpowerDevice { device_path, con })
-    }
-
-    /// Monitor UPower property changes in a separate thread and send updates
-    /// via the `update_request` channel.
-    pub fn monitor(&self, id: usize, update_request: Sender<Task>) {
-        let path = self.device_path.clone();
-        thread::Builder::new()
-            .name("battery".into())
-            .spawn(move || {
-                let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-                    .expect("Failed to establish D-Bus connection.");
-                let rule = format!(
-                    "type='signal',\
-                 path='{}',\
-                 interface='org.freedesktop.DBus.Properties',\
-                 member='PropertiesChanged'",
-                    path
-                );
-
-                // First we're going to get an (irrelevant) NameAcquired event.
-                con.incoming(10_000).next();
-
-                con.add_match(&rule)
-                    .expect("Failed to add D-Bus match rule.");
-
-                loop {
-                    if con.incoming(10_000).next().is_some() {
-                        update_request
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                        // Avoid update spam.
-                        // TODO: Is this necessary?
-                        thread::sleep(Duration::from_millis(1000))
-                    }
-                }
-            })
-            .unwrap();
+        Ok(Some(device_path))
+    }
+
+    /// The path of the device, which is looked up again after devices were added or removed
+    fn path(&self) -> Option<String> {
+        let mut path = self.device_path.lock().unwrap();
+        if self.rescan.swap(false, Ordering::SeqCst) {
+            *path = UpowerDevice::find(&self.con, &self.device).unwrap_or_else(|error| {
+                log::debug!("failed to look up the battery: {:?}", error);
+                None
+            });
+        }
+        path.clone()
+    }
+
+    fn get<T>(&self, property: &str) -> Result<T>
+    where
+        T: for<'b> dbus::arg::Get<'b> + 'static,
+    {
+        let path = self
+            .path()
+            .block_error("battery", "UPower device could not be found.")?;
+        self.con
+            .with_proxy(UPOWER, path, UPOWER_TIMEOUT)
+            .get(UPOWER_DEVICE, property)
+            .block_error(
+                "battery",
+                &format!("Failed to read UPower {} property.", property),
+            )
     }
 }
 
+#[cfg(feature = "dbus")]
 impl BatteryDevice for UpowerDevice {
     fn is_available(&self) -> bool {
-        true // TODO: has to be implemented for UPower
+        self.path().is_some()
     }
 
     fn refresh_device_info(&mut self) -> Result<()> {
@@ -415,11 +740,7 @@ impl BatteryDevice for UpowerDevice {
     }
 
     fn status(&self) -> Result<String> {
-        let status: u32 = self
-            .con
-            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
-            .get("org.freedesktop.UPower.Device", "State")
-            .block_error("battery", "Failed to read UPower State property.")?;
+        let status: u32 = self.get("State")?;
 
         // https://upower.freedesktop.org/docs/Device.html#Device:State
         match status {
@@ -434,11 +755,7 @@ impl BatteryDevice for UpowerDevice {
     }
 
     fn capacity(&self) -> Result<u64> {
-        let capacity: f64 = self
-            .con
-            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
-            .get("org.freedesktop.UPower.Device", "Percentage")
-            .block_error("battery", "Failed to read UPower Percentage property.")?;
+        let capacity: f64 = self.get("Percentage")?;
 
         if capacity > 100.0 {
             Ok(100)
@@ -453,30 +770,93 @@ impl BatteryDevice for UpowerDevice {
         } else {
             "TimeToEmpty"
         };
-        let time_to_empty: i64 = self
-            .con
-            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
-            .get("org.freedesktop.UPower.Device", property)
-            .block_error(
-                "battery",
-                &format!("Failed to read UPower {} property.", property),
-            )?;
+        let time_to_empty: i64 = self.get(property)?;
         Ok((time_to_empty / 60) as u64)
     }
 
     fn power_consumption(&self) -> Result<u64> {
-        let energy_rate: f64 = self
-            .con
-            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
-            .get("org.freedesktop.UPower.Device", "EnergyRate")
-            .block_error("battery", "Failed to read UPower EnergyRate property.")?;
+        let energy_rate: f64 = self.get("EnergyRate")?;
         // FIXME: Might want to make the interface send Watts instead.
         Ok((energy_rate * 1_000_000.0) as u64)
     }
 }
 
+/// The charge and power draw over the last minutes, which smooth out the jumps of the rates that
+/// the battery reports.
+struct History {
+    window: Duration,
+    /// When, the charge in percent and the power in W
+    samples: VecDeque<(Instant, f64, Option<f64>)>,
+}
+
+impl History {
+    fn push(&mut self, now: Instant, capacity: f64, power: Option<f64>) {
+        self.samples.push_back((now, capacity, power));
+        while let Some(&(time, ..)) = self.samples.front() {
+            if now.saturating_duration_since(time) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn mean_power<'a>(
+        samples: impl Iterator<Item = &'a (Instant, f64, Option<f64>)>,
+    ) -> Option<f64> {
+        let powers: Vec<f64> = samples.filter_map(|(_, _, power)| *power).collect();
+        (!powers.is_empty()).then(|| powers.iter().sum::<f64>() / powers.len() as f64)
+    }
+
+    /// The mean power draw
+    fn power(&self) -> Option<f64> {
+        Self::mean_power(self.samples.iter())
+    }
+
+    /// Whether the power draw of the newer half of the samples is above (1), below (-1) or within
+    /// a tenth (0) of the older half
+    fn power_trend(&self) -> Option<i8> {
+        let half = self.samples.len() / 2;
+        let older = Self::mean_power(self.samples.iter().take(half))?;
+        let newer = Self::mean_power(self.samples.iter().skip(half))?;
+        Some(if newer > older * 1.1 {
+            1
+        } else if newer < older * 0.9 {
+            -1
+        } else {
+            0
+        })
+    }
+
+    /// The seconds until the charge reaches `threshold` percent, from the least squares fit of
+    /// the charge over time. None if it isn't heading there, or the samples span under a minute.
+    fn time_until(&self, threshold: f64) -> Option<f64> {
+        let &(start, ..) = self.samples.front()?;
+        let &(end, capacity, _) = self.samples.back()?;
+        if end.duration_since(start) < Duration::from_secs(60) {
+            return None;
+        }
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|&(time, capacity, _)| (time.duration_since(start).as_secs_f64(), capacity))
+            .collect();
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_c = points.iter().map(|(_, c)| c).sum::<f64>() / n;
+        let covariance: f64 = points
+            .iter()
+            .map(|(t, c)| (t - mean_t) * (c - mean_c))
+            .sum();
+        let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+        let slope = covariance / variance;
+        let seconds = (threshold - capacity) / slope;
+        (seconds.is_finite() && seconds >= 0.).then_some(seconds)
+    }
+}
+
 /// A block for displaying information about an internal power supply.
 pub struct Battery {
+    progress: Option<ProgressWidget>,
     id: usize,
     output: TextWidget,
     update_interval: Duration,
@@ -493,6 +873,10 @@ pub struct Battery {
     warning: u64,
     critical: u64,
     fallback_icons: bool,
+    history: History,
+    /// The status of the samples in the history
+    history_status: String,
+    threshold: u64,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -516,7 +900,8 @@ pub struct BatteryConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
 
-    /// The internal power supply device in `/sys/class/power_supply/` to read from.
+    /// The internal power supply device in `/sys/class/power_supply/` to read from, or "all" to
+    /// combine all batteries.
     pub device: String,
 
     /// Format string for displaying battery information.
@@ -549,11 +934,21 @@ pub struct BatteryConfig {
     /// The threshold below which the remaining capacity is shown as critical
     pub critical: u64,
 
-    /// If the battery device cannot be found, do not fail and show the block anyway (sysfs only).
+    /// If the battery device cannot be found, do not fail and show the block anyway.
     pub allow_missing: bool,
 
     /// If the battery device cannot be found, completely hide this block.
     pub hide_missing: bool,
+
+    /// Show the charge as a progress bar
+    pub progress: Option<ProgressConfig>,
+
+    /// Seconds of history for {power_avg}, {power_trend} and {time_threshold}
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub trend_window: Duration,
+
+    /// The charge in percent that {time_threshold} estimates the time until
+    pub threshold: u64,
 }
 
 fn default_device() -> String {
@@ -588,6 +983,9 @@ impl Default for BatteryConfig {
             critical: 15,
             allow_missing: false,
             hide_missing: false,
+            progress: None,
+            trend_window: Duration::from_secs(600),
+            threshold: 20,
         }
     }
 }
@@ -595,6 +993,7 @@ impl Default for BatteryConfig {
 impl ConfigBlock for Battery {
     type Config = BatteryConfig;
 
+    #[cfg_attr(not(feature = "dbus"), allow(unused_variables))]
     fn new(
         id: usize,
         block_config: Self::Config,
@@ -602,10 +1001,34 @@ impl ConfigBlock for Battery {
         update_request: Sender<Task>,
     ) -> Result<Self> {
         let device: Box<dyn BatteryDevice> = match block_config.driver {
+            #[cfg(feature = "dbus")]
+            BatteryDriver::Upower => {
+                // UPower combines the batteries itself
+                let device = match block_config.device.as_str() {
+                    "all" => "DisplayDevice",
+                    device => device,
+                };
+                Box::new(UpowerDevice::from_device(
+                    id,
+                    device,
+                    block_config.allow_missing,
+                    &shared_config,
+                    update_request,
+                )?)
+            }
+            #[cfg(not(feature = "dbus"))]
             BatteryDriver::Upower => {
-                let out = UpowerDevice::from_device(&block_config.device)?;
-                out.monitor(id, update_request);
-                Box::new(out)
+                return Err(BlockError(
+                    "battery".to_string(),
+                    "The upower driver requires the dbus feature.".to_string(),
+                ))
+            }
+            // There is no sysfs on the BSDs, but the same can be read with sysctl
+            BatteryDriver::Sysfs if bsd::bsd().is_some() => {
+                Box::new(SysctlDevice::new(block_config.allow_missing))
+            }
+            BatteryDriver::Sysfs if block_config.device == "all" => {
+                Box::new(CombinedDevice::new(block_config.allow_missing))
             }
             BatteryDriver::Sysfs => Box::new(PowerSupplyDevice::from_device(
                 &block_config.device,
@@ -616,13 +1039,16 @@ impl ConfigBlock for Battery {
         let fallback = match shared_config.get_icon("bat_10") {
             Ok(_) => false,
             Err(_) => {
-                eprintln!("Icon bat_10 not found in your icons file. Please check NEWS.md");
+                log::warn!("Icon bat_10 not found in your icons file. Please check NEWS.md");
                 true
             }
         };
 
         Ok(Battery {
             id,
+            progress: block_config
+                .progress
+                .map(|progress| ProgressWidget::new(id, 1, shared_config.clone(), progress)),
             update_interval: block_config.interval,
             output: TextWidget::new(id, 0, shared_config),
             device,
@@ -639,6 +1065,12 @@ impl ConfigBlock for Battery {
             critical: block_config.critical,
             // TODO remove on next release
             fallback_icons: fallback,
+            history: History {
+                window: block_config.trend_window,
+                samples: VecDeque::new(),
+            },
+            history_status: String::new(),
+            threshold: block_config.threshold,
         })
     }
 }
@@ -656,6 +1088,9 @@ impl Block for Battery {
                 "percentage" => Value::from_string("X".to_string()),
                 "time" => Value::from_string("xx:xx".to_string()),
                 "power" => Value::from_string("N/A".to_string()),
+                "power_avg" => Value::from_string("N/A".to_string()),
+                "power_trend" => Value::from_string("".to_string()),
+                "time_threshold" => Value::from_string("xx:xx".to_string()),
             );
 
             self.output.set_icon("bat_not_available")?;
@@ -674,6 +1109,18 @@ impl Block for Battery {
 
         let status = self.device.status()?;
         let capacity = self.device.capacity();
+        let power = self.device.power_consumption();
+
+        // Rates from before plugging in or out don't tell anything about now
+        if status != self.history_status {
+            self.history.samples.clear();
+            self.history_status = status.clone();
+        }
+        if let Ok(capacity) = capacity {
+            let power = power.as_ref().ok().map(|&power| power as f64 * 1e-6);
+            self.history.push(Instant::now(), capacity as f64, power);
+        }
+
         let values = map!(
             "percentage" => match capacity {
                 Ok(capacity) => Value::from_integer(capacity as i64).percents(),
@@ -685,12 +1132,34 @@ impl Block for Battery {
                 _ => Value::from_string("×".into()),
             },
             // convert µW to W for display
-            "power" => match self.device.power_consumption() {
+            "power" => match power {
                 Ok(power) => Value::from_float(power as f64 * 1e-6).watts(),
                 _ => Value::from_string("×".into()),
             },
+            "power_avg" => match self.history.power() {
+                Some(power) => Value::from_float(power).watts(),
+                None => Value::from_string("×".into()),
+            },
+            "power_trend" => Value::from_string(match self.history.power_trend() {
+                Some(1) => "↗",
+                Some(-1) => "↘",
+                Some(_) => "→",
+                None => "",
+            }.to_string()),
+            "time_threshold" => match self.history.time_until(self.threshold as f64) {
+                Some(seconds) => {
+                    let time = (seconds / 60.).round() as u64;
+                    Value::from_string(format!("{}:{:02}", std::cmp::min(time / 60, 99), time % 60))
+                }
+                None => Value::from_string("".into()),
+            },
         );
 
+        if let Ok(capacity) = capacity {
+            self.output.set_gradient_value(capacity as f64);
+        }
+        let percentage = capacity.as_ref().ok().copied();
+
         let capacity_is_above_full_threshold = match capacity {
             Ok(capacity) => (capacity >= self.full_threshold),
             _ => false,
@@ -738,6 +1207,10 @@ impl Block for Battery {
             self.output.set_spacing(Spacing::Normal);
         }
 
+        if let (Some(progress), Some(capacity)) = (&mut self.progress, percentage) {
+            progress.set_value(capacity as f64, self.output.state());
+        }
+
         match self.driver {
             BatteryDriver::Sysfs => Ok(Some(self.update_interval.into())),
             BatteryDriver::Upower => Ok(None),
@@ -750,10 +1223,75 @@ impl Block for Battery {
             return Vec::new();
         }
 
-        vec![&self.output]
+        match &self.progress {
+            Some(progress) => vec![&self.output, progress],
+            None => vec![&self.output],
+        }
     }
 
     fn id(&self) -> usize {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let start = Instant::now();
+        let mut history = History {
+            window: Duration::from_secs(600),
+            samples: VecDeque::new(),
+        };
+        // Discharging by 1% a minute, while the draw goes up
+        for minute in 0..5 {
+            let power = if minute < 2 { 10. } else { 14. };
+            history.push(
+                start + Duration::from_secs(minute * 60),
+                50. - minute as f64,
+                Some(power),
+            );
+        }
+        assert_eq!(history.time_until(20.).map(f64::round), Some(26. * 60.));
+        assert_eq!(history.time_until(80.), None);
+        assert_eq!(history.power_trend(), Some(1));
+
+        // Old samples are forgotten
+        history.push(start + Duration::from_secs(800), 30., None);
+        assert_eq!(history.samples.len(), 2);
+    }
+
+    #[test]
+    fn test_combine() {
+        let reading = |status: &str, now: f64, full: f64, power: u64| Reading {
+            status: status.to_string(),
+            capacity: Some((now / full * 100.) as u64),
+            energy: Some((now, full)),
+            power: Some(power),
+        };
+        // A full internal battery and a half empty external one that is being drained
+        let combined = combine(&[
+            reading("Not charging", 20e6, 20e6, 0),
+            reading("Discharging", 30e6, 60e6, 10e6 as u64),
+        ]);
+        assert_eq!(
+            combined,
+            Combined {
+                status: "Discharging".to_string(),
+                capacity: Some(62),
+                power: Some(10e6 as u64),
+                time_remaining: Some(300),
+            }
+        );
+
+        // Without the sizes, the charges count the same
+        let mut unknown = reading("Full", 20e6, 20e6, 0);
+        unknown.energy = None;
+        let combined = combine(&[unknown, reading("Full", 30e6, 60e6, 0)]);
+        assert_eq!(combined.status, "Full");
+        assert_eq!(combined.capacity, Some(75));
+        assert_eq!(combined.time_remaining, None);
+    }
+}
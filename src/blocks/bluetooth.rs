@@ -21,6 +21,17 @@ use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
+/// The icon for a device by the icon name that BlueZ gives it
+pub fn device_icon(icon: Option<&str>) -> &'static str {
+    match icon {
+        Some("audio-card") | Some("audio-headset") | Some("audio-headphones") => "headphones",
+        Some("input-gaming") => "joystick",
+        Some("input-keyboard") => "keyboard",
+        Some("input-mouse") => "mouse",
+        _ => "bluetooth",
+    }
+}
+
 pub struct BluetoothDevice {
     pub path: String,
     pub icon: Option<String>,
@@ -249,13 +260,8 @@ impl ConfigBlock for Bluetooth {
 
         Ok(Bluetooth {
             id,
-            output: TextWidget::new(id, 0, shared_config).with_icon(match device.icon {
-                Some(ref icon) if icon == "audio-card" => "headphones",
-                Some(ref icon) if icon == "input-gaming" => "joystick",
-                Some(ref icon) if icon == "input-keyboard" => "keyboard",
-                Some(ref icon) if icon == "input-mouse" => "mouse",
-                _ => "bluetooth",
-            })?,
+            output: TextWidget::new(id, 0, shared_config)
+                .with_icon(device_icon(device.icon.as_deref()))?,
             device,
             hide_disconnected: block_config.hide_disconnected,
             format: block_config.format.with_default("{label} {percentage}")?,
@@ -280,13 +286,8 @@ impl Block for Bluetooth {
             self.output
                 .set_state(if connected { State::Good } else { State::Idle });
 
-            self.output.set_icon(match self.device.icon() {
-                Some(ref icon) if icon == "audio-card" => "headphones",
-                Some(ref icon) if icon == "input-gaming" => "joystick",
-                Some(ref icon) if icon == "input-keyboard" => "keyboard",
-                Some(ref icon) if icon == "input-mouse" => "mouse",
-                _ => "bluetooth",
-            })?;
+            self.output
+                .set_icon(device_icon(self.device.icon().as_deref()))?;
 
             // Use battery info, when available.
             if let Some(value) = self.device.battery() {
@@ -0,0 +1,334 @@
+//! Lists the paired Bluetooth devices from BlueZ, one at a time, with their battery level where
+//! the device reports it. Scrolling switches between the devices and a left click connects or
+//! disconnects the shown one. BlueZ's D-Bus signals trigger the updates, so nothing is polled.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    ObjectManager, ObjectManagerInterfacesAdded as IA, ObjectManagerInterfacesRemoved as IR,
+    PropertiesPropertiesChanged as PPC,
+};
+use dbus::blocking::SyncConnection;
+use dbus::channel::Sender as _;
+use dbus::message::SignalArgs;
+use serde_derive::Deserialize;
+
+use crate::blocks::bluetooth::device_icon;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::{Bus, Watch};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLUEZ: &str = "org.bluez";
+const DEVICE: &str = "org.bluez.Device1";
+const BATTERY: &str = "org.bluez.Battery1";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Device {
+    path: dbus::Path<'static>,
+    address: String,
+    name: String,
+    icon: Option<String>,
+    connected: bool,
+    /// In percent
+    battery: Option<u8>,
+}
+
+/// The paired or connected devices among the objects of BlueZ, the connected ones first
+fn devices(objects: HashMap<dbus::Path<'static>, HashMap<String, PropMap>>) -> Vec<Device> {
+    let mut devices: Vec<Device> = objects
+        .into_iter()
+        .filter_map(|(path, interfaces)| {
+            let device = interfaces.get(DEVICE)?;
+            let flag = |name| prop_cast::<bool>(device, name).copied().unwrap_or(false);
+            if !flag("Paired") && !flag("Connected") {
+                return None;
+            }
+            let address = prop_cast::<String>(device, "Address")?.clone();
+            Some(Device {
+                name: prop_cast::<String>(device, "Alias")
+                    .cloned()
+                    .unwrap_or_else(|| address.clone()),
+                address,
+                icon: prop_cast::<String>(device, "Icon").cloned(),
+                connected: flag("Connected"),
+                battery: interfaces
+                    .get(BATTERY)
+                    .and_then(|battery| prop_cast::<u8>(battery, "Percentage").copied()),
+                path,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| {
+        b.connected
+            .cmp(&a.connected)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    devices
+}
+
+/// Updates the block whenever devices appear, disappear or change
+fn watch(
+    id: usize,
+    shared_config: &SharedConfig,
+    tx_update_request: Sender<Task>,
+) -> Result<Vec<Watch>> {
+    let update = move || {
+        tx_update_request
+            .send(Task {
+                id,
+                update_time: Instant::now(),
+            })
+            .is_ok()
+    };
+    let sender = Some(BLUEZ.into());
+    let added = {
+        let update = update.clone();
+        shared_config.buses.watch(
+            Bus::System,
+            IA::match_rule(sender.as_ref(), None).static_clone(),
+            move |_: IA, _, _| update(),
+        )?
+    };
+    let removed = {
+        let update = update.clone();
+        shared_config.buses.watch(
+            Bus::System,
+            IR::match_rule(sender.as_ref(), None).static_clone(),
+            move |_: IR, _, _| update(),
+        )?
+    };
+    let changed = shared_config.buses.watch(
+        Bus::System,
+        PPC::match_rule(sender.as_ref(), None).static_clone(),
+        move |_: PPC, _, _| update(),
+    )?;
+    Ok(vec![added, removed, changed])
+}
+
+pub struct BluetoothDevices {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_none: FormatTemplate,
+    con: Arc<SyncConnection>,
+    _watches: Vec<Watch>,
+    devices: Vec<Device>,
+    /// The address of the shown device, which stays shown while others come and go
+    focused: Option<String>,
+    scrolling: Scrolling,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct BluetoothDevicesConfig {
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Format override if there are no paired devices
+    pub format_none: FormatTemplate,
+}
+
+impl ConfigBlock for BluetoothDevices {
+    type Config = BluetoothDevicesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let con = shared_config.buses.get(Bus::System)?;
+        let watches = watch(id, &shared_config, tx_update_request)?;
+
+        Ok(BluetoothDevices {
+            id,
+            format: block_config.format.with_default("{name} {battery}")?,
+            format_none: block_config.format_none.with_default("none")?,
+            con,
+            _watches: watches,
+            devices: Vec::new(),
+            focused: None,
+            scrolling: shared_config.scrolling,
+            text: TextWidget::new(id, 0, shared_config).with_icon("bluetooth")?,
+        })
+    }
+}
+
+impl BluetoothDevices {
+    fn focused(&self) -> Option<&Device> {
+        let address = self.focused.as_ref()?;
+        self.devices
+            .iter()
+            .find(|device| &device.address == address)
+    }
+}
+
+impl Block for BluetoothDevices {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let objects = self
+            .con
+            .with_proxy(BLUEZ, "/", TIMEOUT)
+            .get_managed_objects()
+            .block_error("bluetooth_devices", "failed to get the devices from BlueZ")?;
+        self.devices = devices(objects);
+        if self.focused().is_none() {
+            self.focused = self.devices.first().map(|device| device.address.clone());
+        }
+
+        let connected = self
+            .devices
+            .iter()
+            .filter(|device| device.connected)
+            .count();
+        let mut values = map!(
+            "connected" => Value::from_integer(connected as i64),
+            "devices" => Value::from_integer(self.devices.len() as i64),
+        );
+        match self.focused().cloned() {
+            Some(device) => {
+                values.insert("name", Value::from_string(device.name.clone()));
+                values.insert("address", Value::from_string(device.address.clone()));
+                values.insert(
+                    "battery",
+                    match device.battery {
+                        Some(battery) => Value::from_integer(battery as i64).percents(),
+                        None => Value::from_string("-".to_string()),
+                    },
+                );
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_icon(device_icon(device.icon.as_deref()))?;
+                self.text
+                    .set_state(match (device.connected, device.battery) {
+                        (false, _) => State::Idle,
+                        (true, Some(0..=15)) => State::Critical,
+                        (true, Some(16..=30)) => State::Warning,
+                        (true, _) => State::Good,
+                    });
+            }
+            None => {
+                self.text.set_texts(self.format_none.render(&values)?);
+                self.text.set_icon("bluetooth")?;
+                self.text.set_state(State::Idle);
+            }
+        }
+        Ok(None)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => {
+                if let Some(device) = self.focused() {
+                    let method = if device.connected {
+                        "Disconnect"
+                    } else {
+                        "Connect"
+                    };
+                    let message =
+                        dbus::Message::new_method_call(BLUEZ, &device.path, DEVICE, method)
+                            .block_error("bluetooth_devices", "failed to build D-Bus method")?;
+                    // Connecting takes a while, and the signals tell when it's done
+                    self.con.send(message).map_err(|_| {
+                        BlockError(
+                            "bluetooth_devices".to_string(),
+                            format!("failed to {} {}", method.to_lowercase(), device.name),
+                        )
+                    })?;
+                }
+            }
+            _ => {
+                let position = self
+                    .focused
+                    .as_ref()
+                    .and_then(|address| {
+                        self.devices
+                            .iter()
+                            .position(|device| &device.address == address)
+                    })
+                    .unwrap_or(0);
+                let count = self.devices.len();
+                let position = match self.scrolling.to_logical_direction(event.button) {
+                    Some(LogicalDirection::Up) if count > 0 => (position + 1) % count,
+                    Some(LogicalDirection::Down) if count > 0 => (position + count - 1) % count,
+                    _ => return Ok(()),
+                };
+                self.focused = Some(self.devices[position].address.clone());
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{RefArg, Variant};
+
+    fn props(props: Vec<(&str, Box<dyn RefArg>)>) -> PropMap {
+        props
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Variant(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_devices() {
+        let device = |address: &str, alias: &str, paired: bool, connected: bool| {
+            props(vec![
+                ("Address", Box::new(address.to_string())),
+                ("Alias", Box::new(alias.to_string())),
+                ("Paired", Box::new(paired)),
+                ("Connected", Box::new(connected)),
+            ])
+        };
+        let mut objects = HashMap::new();
+        objects.insert(
+            dbus::Path::from("/org/bluez/hci0"),
+            map!("org.bluez.Adapter1".to_string() => PropMap::new()),
+        );
+        objects.insert(
+            dbus::Path::from("/org/bluez/hci0/dev_A"),
+            map!(DEVICE.to_string() => device("A", "Keyboard", true, false)),
+        );
+        objects.insert(
+            dbus::Path::from("/org/bluez/hci0/dev_B"),
+            map!(
+                DEVICE.to_string() => device("B", "Headphones", true, true),
+                BATTERY.to_string() => props(vec![("Percentage", Box::new(80u8))]),
+            ),
+        );
+        objects.insert(
+            dbus::Path::from("/org/bluez/hci0/dev_C"),
+            map!(DEVICE.to_string() => device("C", "Phone nearby", false, false)),
+        );
+
+        let devices = devices(objects);
+        assert_eq!(
+            devices
+                .iter()
+                .map(|device| (device.name.as_str(), device.connected, device.battery))
+                .collect::<Vec<_>>(),
+            vec![("Headphones", true, Some(80)), ("Keyboard", false, None)]
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Shows the health of a server as its baseboard management controller (BMC) reports it: whether
+//! the server is powered on, the health of its temperature and fan sensors, and how many entries
+//! its system event log (SEL) holds. The BMC is asked over Redfish or with `ipmitool`.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum BmcInterface {
+    #[cfg(feature = "http")]
+    Redfish(RedfishConfig),
+    Ipmitool(IpmitoolConfig),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RedfishConfig {
+    /// Host name or address of the BMC
+    pub host: String,
+
+    pub username: String,
+
+    pub password: String,
+
+    /// BMCs mostly come with self-signed certificates, so these aren't checked by default
+    #[serde(default)]
+    pub verify_tls: bool,
+
+    /// Path of the entries of the SEL, for BMCs on which it isn't found
+    #[serde(default)]
+    pub sel: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IpmitoolConfig {
+    /// Host name or address of the BMC. Without it, the BMC of this machine is asked.
+    #[serde(default)]
+    pub host: Option<String>,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// File that holds the password. Without it, ipmitool reads it from `IPMI_PASSWORD`.
+    #[serde(default)]
+    pub password_file: Option<String>,
+}
+
+/// What the BMC reports
+#[derive(Debug, Clone, PartialEq)]
+struct Health {
+    power_on: bool,
+    /// Highest reading of the temperature sensors, in °C
+    temperature: Option<f64>,
+    /// Number of sensors that aren't OK
+    failing: u64,
+    /// The worst health of the server and its sensors
+    state: State,
+    /// Number of entries of the SEL
+    events: Option<u64>,
+}
+
+impl Health {
+    fn new(power_on: bool) -> Self {
+        Health {
+            power_on,
+            temperature: None,
+            failing: 0,
+            state: State::Idle,
+            events: None,
+        }
+    }
+
+    fn worsen(&mut self, state: State) {
+        if state.severity() > self.state.severity() {
+            self.state = state;
+        }
+    }
+
+    fn add_sensor(&mut self, state: State, celsius: Option<f64>) {
+        if state != State::Idle {
+            self.failing += 1;
+        }
+        self.worsen(state);
+        if let Some(celsius) = celsius {
+            self.temperature = Some(self.temperature.map_or(celsius, |t| t.max(celsius)));
+        }
+    }
+}
+
+pub struct Bmc {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    interface: BmcInterface,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    #[cfg(feature = "http")]
+    paths: Option<redfish::Paths>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BmcConfig {
+    pub interface: BmcInterface,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "BmcConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl BmcConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+impl ConfigBlock for Bmc {
+    type Config = BmcConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Bmc {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("thermometer")?,
+            format: block_config.format.with_default("{health} {temperature}")?,
+            interface: block_config.interface,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            #[cfg(feature = "http")]
+            paths: None,
+        })
+    }
+}
+
+impl IpmitoolConfig {
+    fn command(&self, arguments: &str) -> String {
+        let mut command = "ipmitool".to_string();
+        if let Some(host) = &self.host {
+            command += &format!(" -I lanplus -H '{}'", host);
+            if let Some(username) = &self.username {
+                command += &format!(" -U '{}'", username);
+            }
+            match &self.password_file {
+                Some(file) => command += &format!(" -f '{}'", file),
+                None => command += " -E",
+            }
+        }
+        format!("{} {}", command, arguments)
+    }
+
+    fn health(&self, command_runner: &dyn CommandRunner) -> Result<Health> {
+        let run = |arguments: &str| -> Result<String> {
+            let output = command_runner.run(&self.command(arguments))?;
+            if output.status != Some(0) {
+                return Err(BlockError(
+                    "bmc".to_string(),
+                    format!("ipmitool {} failed", arguments),
+                ));
+            }
+            Ok(output.stdout)
+        };
+
+        // "Chassis Power is on"
+        let mut health = Health::new(run("chassis power status")?.trim_end().ends_with("on"));
+        // "CPU Temp | 45 degrees C | ok"
+        for line in run("sdr list")?.lines() {
+            let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+            let (name, reading, status) = match fields[..] {
+                [name, reading, status] => (name, reading, status),
+                _ => continue,
+            };
+            let celsius = reading
+                .strip_suffix("degrees C")
+                .and_then(|number| number.trim().parse().ok());
+            if celsius.is_none() && !name.to_lowercase().contains("fan") {
+                continue;
+            }
+            let state = match status {
+                "ok" => State::Idle,
+                "nc" => State::Warning,
+                "cr" | "nr" => State::Critical,
+                // No reading, e.g. of an empty fan slot
+                _ => continue,
+            };
+            health.add_sensor(state, celsius);
+        }
+        // "Entries          : 12"
+        health.events = run("sel info")?.lines().find_map(|line| {
+            line.strip_prefix("Entries")?
+                .trim_start_matches([' ', ':'])
+                .parse()
+                .ok()
+        });
+        Ok(health)
+    }
+}
+
+#[cfg(feature = "http")]
+mod redfish {
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use super::{Health, RedfishConfig};
+    use crate::errors::*;
+    use crate::widgets::State;
+
+    /// Where the resources of interest are, which differs between vendors
+    #[derive(Debug, Clone)]
+    pub(super) struct Paths {
+        system: String,
+        thermal: Option<String>,
+        sel: Option<String>,
+    }
+
+    /// The first member of a collection
+    fn member(collection: &Value) -> Option<String> {
+        collection["Members"][0]["@odata.id"]
+            .as_str()
+            .map(String::from)
+    }
+
+    fn link(resource: &Value, key: &str) -> Option<String> {
+        resource[key]["@odata.id"].as_str().map(String::from)
+    }
+
+    fn state(health: &Value) -> Option<State> {
+        match health.as_str()? {
+            "OK" => Some(State::Idle),
+            "Warning" => Some(State::Warning),
+            "Critical" => Some(State::Critical),
+            _ => None,
+        }
+    }
+
+    impl RedfishConfig {
+        fn get(&self, path: &str) -> Result<Value> {
+            let response = crate::http::http_get_with_credentials(
+                &format!("https://{}{}", self.host, path),
+                Some(Duration::from_secs(10)),
+                &self.username,
+                &self.password,
+                self.verify_tls,
+            )?;
+            if response.code != 200 {
+                return Err(BlockError(
+                    "bmc".to_string(),
+                    format!("failed to get {} (HTTP {})", path, response.code),
+                ));
+            }
+            serde_json::from_slice(&response.content)
+                .block_error("bmc", &format!("{} is not JSON", path))
+        }
+
+        pub(super) fn discover(&self) -> Result<Paths> {
+            let system = member(&self.get("/redfish/v1/Systems")?)
+                .block_error("bmc", "the BMC knows no systems")?;
+            let thermal = match member(&self.get("/redfish/v1/Chassis")?) {
+                Some(chassis) => link(&self.get(&chassis)?, "Thermal"),
+                None => None,
+            };
+            let sel = match &self.sel {
+                Some(sel) => Some(sel.clone()),
+                None => self.find_sel(&system)?,
+            };
+            Ok(Paths {
+                system,
+                thermal,
+                sel,
+            })
+        }
+
+        /// Looks for a log service called "SEL" of the system or of the BMC itself
+        fn find_sel(&self, system: &str) -> Result<Option<String>> {
+            let mut owners = vec![system.to_string()];
+            owners.extend(member(&self.get("/redfish/v1/Managers")?));
+            for owner in owners {
+                let services = match link(&self.get(&owner)?, "LogServices") {
+                    Some(services) => self.get(&services)?,
+                    None => continue,
+                };
+                let sel = services["Members"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|service| service["@odata.id"].as_str())
+                    .find(|path| {
+                        path.trim_end_matches('/')
+                            .rsplit('/')
+                            .next()
+                            .is_some_and(|name| name.eq_ignore_ascii_case("sel"))
+                    });
+                if let Some(sel) = sel {
+                    return Ok(link(&self.get(sel)?, "Entries"));
+                }
+            }
+            Ok(None)
+        }
+
+        pub(super) fn health(&self, paths: &Paths) -> Result<Health> {
+            let system = self.get(&paths.system)?;
+            let mut health = Health::new(system["PowerState"] == "On");
+            if let Some(state) = state(&system["Status"]["HealthRollup"])
+                .or_else(|| state(&system["Status"]["Health"]))
+            {
+                health.worsen(state);
+            }
+            if let Some(thermal) = &paths.thermal {
+                let thermal = self.get(thermal)?;
+                let sensors = ["Temperatures", "Fans"]
+                    .iter()
+                    .filter_map(|key| thermal[*key].as_array())
+                    .flatten();
+                for sensor in sensors {
+                    if let Some(state) = state(&sensor["Status"]["Health"]) {
+                        health.add_sensor(state, sensor["ReadingCelsius"].as_f64());
+                    }
+                }
+            }
+            if let Some(sel) = &paths.sel {
+                health.events = self.get(sel)?["Members@odata.count"].as_u64();
+            }
+            Ok(health)
+        }
+    }
+}
+
+impl Bmc {
+    fn health(&mut self) -> Result<Health> {
+        match &self.interface {
+            #[cfg(feature = "http")]
+            BmcInterface::Redfish(redfish) => {
+                let paths = match &self.paths {
+                    Some(paths) => paths.clone(),
+                    None => redfish.discover()?,
+                };
+                let health = redfish.health(&paths);
+                // Look for the resources again after errors, in case the BMC was updated
+                self.paths = health.is_ok().then_some(paths);
+                health
+            }
+            BmcInterface::Ipmitool(ipmitool) => ipmitool.health(self.command_runner.as_ref()),
+        }
+    }
+}
+
+impl Block for Bmc {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut health = match self.health() {
+            Ok(health) => health,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // The BMC can't be reached, e.g. from outside of its network
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(self.interval.into()));
+            }
+            Err(error) => {
+                self.text.set_text(format!("bmc error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+        if !health.power_on {
+            health.worsen(State::Warning);
+        }
+        let values = map!(
+            "power" => Value::from_string(if health.power_on { "on" } else { "off" }.to_string()),
+            "health" => Value::from_string(
+                match health.state {
+                    State::Critical => "critical",
+                    State::Warning => "warning",
+                    _ => "ok",
+                }
+                .to_string()
+            ),
+            "temperature" => Value::from_integer(health.temperature.unwrap_or(0.).round() as i64).degrees(),
+            "failing" => Value::from_integer(health.failing as i64),
+            "events" => Value::from_integer(health.events.unwrap_or(0) as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(health.state);
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_ipmitool_health() {
+        let runner = MockRunner::default();
+        let config = IpmitoolConfig {
+            host: Some("bmc".to_string()),
+            username: Some("admin".to_string()),
+            password_file: None,
+        };
+        let command = "ipmitool -I lanplus -H 'bmc' -U 'admin' -E";
+        runner.set(
+            &format!("{} chassis power status", command),
+            "Chassis Power is on\n",
+        );
+        runner.set(
+            &format!("{} sdr list", command),
+            "CPU Temp         | 45 degrees C      | ok\n\
+             System Temp      | 71 degrees C      | nc\n\
+             FAN1             | 5400 RPM          | ok\n\
+             FAN2             | disabled          | ns\n\
+             12V              | 12.10 Volts       | cr\n",
+        );
+        runner.set(
+            &format!("{} sel info", command),
+            "SEL Information\nVersion          : 1.5 (v1.5, v2 compliant)\nEntries          : 12\n",
+        );
+        assert_eq!(
+            config.health(&runner).unwrap(),
+            Health {
+                power_on: true,
+                temperature: Some(71.),
+                failing: 1,
+                state: State::Warning,
+                events: Some(12),
+            }
+        );
+    }
+}
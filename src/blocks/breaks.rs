@@ -0,0 +1,238 @@
+//! Reminds to take breaks, e.g. every 20 minutes to look at something 20 feet away for 20
+//! seconds, and counts the breaks that were taken and skipped each day.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+enum Phase {
+    /// Working until the next break is due
+    Working(Instant),
+    Due,
+    /// On a break until the given time
+    OnBreak(Instant),
+}
+
+/// The breaks of a day
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+struct Tally {
+    day: i32,
+    taken: u64,
+    skipped: u64,
+}
+
+impl Tally {
+    /// The tally of `day`, which starts over if it is a new day
+    fn on(self, day: i32) -> Tally {
+        if self.day == day {
+            self
+        } else {
+            Tally {
+                day,
+                ..Tally::default()
+            }
+        }
+    }
+}
+
+pub struct Breaks {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    interval: Duration,
+    duration: Duration,
+    message: String,
+    notify: bool,
+    phase: Phase,
+    tally: Tally,
+    state_file: PathBuf,
+    /// Alternates while a break is due to make the block flash
+    flash: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct BreaksConfig {
+    /// Minutes between breaks
+    pub interval: u64,
+
+    /// Length of a break in seconds
+    pub duration: u64,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Message of the notification when a break is due
+    pub message: String,
+
+    /// Whether to send a notification when a break is due
+    pub notify: bool,
+}
+
+impl Default for BreaksConfig {
+    fn default() -> Self {
+        Self {
+            interval: 20,
+            duration: 20,
+            format: FormatTemplate::default(),
+            message: "Time for a break! Look at something 20 feet away for 20 seconds.".to_string(),
+            notify: true,
+        }
+    }
+}
+
+impl ConfigBlock for Breaks {
+    type Config = BreaksConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let interval = Duration::from_secs(block_config.interval * 60);
+        let state_file = xdg_state_home().join("i3status-rust/breaks.toml");
+        let tally = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(Breaks {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("pomodoro_break")?,
+            format: block_config.format.with_default("{next}")?,
+            interval,
+            duration: Duration::from_secs(block_config.duration),
+            message: block_config.message,
+            notify: block_config.notify,
+            phase: Phase::Working(Instant::now() + interval),
+            tally,
+            state_file,
+            flash: false,
+        })
+    }
+}
+
+impl Breaks {
+    /// Counts a break as taken or skipped and starts working towards the next one
+    fn finish_break(&mut self, taken: bool) {
+        let mut tally = self.tally.on(Local::today().num_days_from_ce());
+        if taken {
+            tally.taken += 1;
+        } else {
+            tally.skipped += 1;
+        }
+        self.tally = tally;
+        self.phase = Phase::Working(Instant::now() + self.interval);
+
+        // Failing to save only means that today's counts start over after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&tally) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let next = match self.phase {
+            Phase::Working(until) | Phase::OnBreak(until) => {
+                until.saturating_duration_since(now).as_secs()
+            }
+            Phase::Due => 0,
+        };
+        let tally = self.tally.on(Local::today().num_days_from_ce());
+        let values = map!(
+            "next" => Value::from_string(format!("{}:{:02}", next / 60, next % 60)),
+            "taken" => Value::from_integer(tally.taken as i64),
+            "skipped" => Value::from_integer(tally.skipped as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.phase {
+            Phase::Working(_) => State::Idle,
+            Phase::Due if self.flash => State::Critical,
+            Phase::Due => State::Warning,
+            Phase::OnBreak(_) => State::Good,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Breaks {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Instant::now();
+        match self.phase {
+            Phase::Working(until) if now >= until => {
+                self.phase = Phase::Due;
+                if self.notify {
+                    // The block flashes anyway, so a missing notify-send doesn't matter
+                    let _ = spawn_child_async("notify-send", &[&self.message]);
+                }
+            }
+            Phase::Due => self.flash = !self.flash,
+            Phase::OnBreak(until) if now >= until => self.finish_break(true),
+            _ => {}
+        }
+        self.set_text()?;
+        Ok(Some(Duration::from_secs(1).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match (event.button, &self.phase) {
+            (MouseButton::Left, Phase::Working(_)) | (MouseButton::Left, Phase::Due) => {
+                self.phase = Phase::OnBreak(Instant::now() + self.duration)
+            }
+            (MouseButton::Right, _) => self.finish_break(false),
+            _ => return Ok(()),
+        }
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally_starts_over_each_day() {
+        let tally = Tally {
+            day: 10,
+            taken: 3,
+            skipped: 1,
+        };
+        assert_eq!(tally.on(10), tally);
+        assert_eq!(
+            tally.on(11),
+            Tally {
+                day: 11,
+                taken: 0,
+                skipped: 0,
+            }
+        );
+    }
+}
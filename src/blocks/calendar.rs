@@ -0,0 +1,264 @@
+//! Shows the next event of iCalendar calendars, read from local `.ics` files or from a calendar
+//! server such as a CalDAV collection
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::ics;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::format_countdown;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Calendar {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    calendars: Vec<String>,
+    username: Option<String>,
+    password: Option<String>,
+    all_day: bool,
+    days: i64,
+    warning: chrono::Duration,
+    interval: Duration,
+    events: Vec<ics::Event>,
+    fetched: Option<Instant>,
+    /// What to show instead of the events when the calendars couldn't be read last time
+    error: Option<String>,
+    visible: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct CalendarConfig {
+    /// Paths of `.ics` files or of directories of them, or http(s) URLs of calendars
+    pub calendars: Vec<String>,
+
+    /// User name to log in to calendar servers with
+    pub username: Option<String>,
+
+    /// Password to log in to calendar servers with
+    pub password: Option<String>,
+
+    /// Whether events that last whole days count
+    pub all_day: bool,
+
+    /// How many days ahead to look for events
+    pub days: i64,
+
+    /// Minutes before an event from which on the block is shown as warning
+    pub warning: i64,
+
+    /// How often the calendars are read again
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            calendars: Vec::new(),
+            username: None,
+            password: None,
+            all_day: false,
+            days: 7,
+            warning: 10,
+            interval: Duration::from_secs(300),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Calendar {
+    type Config = CalendarConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.calendars.is_empty() {
+            return Err(ConfigurationError(
+                "calendar".to_string(),
+                "calendars must be set".to_string(),
+            ));
+        }
+
+        Ok(Calendar {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("calendar")?,
+            format: block_config.format.with_default("{summary} {starts_in}")?,
+            calendars: block_config.calendars,
+            username: block_config.username,
+            password: block_config.password,
+            all_day: block_config.all_day,
+            days: block_config.days,
+            warning: chrono::Duration::minutes(block_config.warning),
+            interval: block_config.interval,
+            events: Vec::new(),
+            fetched: None,
+            error: None,
+            visible: false,
+        })
+    }
+}
+
+impl Calendar {
+    fn fetch(&self) -> Result<Vec<ics::Event>> {
+        let credentials = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Some(ics::Credentials { username, password }),
+            _ => None,
+        };
+        let mut events = Vec::new();
+        for calendar in &self.calendars {
+            events.extend(ics::fetch("calendar", calendar, credentials.as_ref())?);
+        }
+        Ok(events)
+    }
+
+    /// The first event that starts after `now`
+    fn next_event(&self, now: DateTime<Utc>) -> Option<&ics::Event> {
+        self.events
+            .iter()
+            .filter(|event| event.start > now && (self.all_day || !event.all_day))
+            .min_by_key(|event| event.start)
+    }
+}
+
+impl Block for Calendar {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if self
+            .fetched
+            .is_none_or(|fetched| fetched.elapsed() >= self.interval)
+        {
+            // The events read last are kept when the calendars can't be read, and the calendars
+            // are tried again after the interval
+            self.error = match self.fetch() {
+                Ok(events) => {
+                    self.events = events;
+                    None
+                }
+                Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                    Some("×".to_string())
+                }
+                Err(error) => Some(format!("calendar error {}", error)),
+            };
+            self.fetched = Some(Instant::now());
+        }
+
+        let now = Utc::now();
+        let event = match self.next_event(now) {
+            Some(event) if event.start - now <= chrono::Duration::days(self.days) => event,
+            _ => {
+                match &self.error {
+                    Some(error) => {
+                        self.text.set_text(error.clone());
+                        self.text.set_state(State::Critical);
+                        self.visible = true;
+                    }
+                    None => self.visible = false,
+                }
+                return Ok(Some(Duration::from_secs(60).into()));
+            }
+        };
+        let start = event.start.with_timezone(&Local);
+        let values = map!(
+            "summary" => Value::from_string(event.summary.clone()),
+            "location" => Value::from_string(event.location.clone()),
+            "start" => Value::from_string(if start.date() == Local::now().date() {
+                start.format("%H:%M").to_string()
+            } else {
+                start.format("%a %H:%M").to_string()
+            }),
+            "starts_in" => Value::from_string(format_countdown(event.start - now)),
+        );
+        let imminent = event.start - now <= self.warning;
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if self.error.is_some() {
+            State::Critical
+        } else if imminent {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        self.visible = true;
+
+        Ok(Some(Duration::from_secs(60).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.fetched = None;
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.visible {
+            vec![&self.text]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_next_event() {
+        let config = CalendarConfig {
+            calendars: vec!["/dev/null".to_string()],
+            ..CalendarConfig::default()
+        };
+        let mut calendar = Calendar::new(
+            0,
+            config,
+            SharedConfig::default(),
+            crossbeam_channel::unbounded().0,
+        )
+        .unwrap();
+        let event = |summary: &str, hour, all_day| {
+            let start = Utc.ymd(2021, 10, 5).and_hms(hour, 0, 0);
+            ics::Event {
+                summary: summary.to_string(),
+                location: String::new(),
+                start,
+                end: start,
+                all_day,
+            }
+        };
+        calendar.events = vec![
+            event("Lunch", 12, false),
+            event("Holiday", 0, true),
+            event("Standup", 9, false),
+        ];
+        let now = Utc.ymd(2021, 10, 4).and_hms(18, 0, 0);
+        assert_eq!(calendar.next_event(now).unwrap().summary, "Standup");
+        calendar.all_day = true;
+        assert_eq!(calendar.next_event(now).unwrap().summary, "Holiday");
+        let now = Utc.ymd(2021, 10, 5).and_hms(10, 0, 0);
+        assert_eq!(calendar.next_event(now).unwrap().summary, "Lunch");
+    }
+}
@@ -0,0 +1,329 @@
+//! Counts the running instances of cloud accounts, as a guard against forgetting to shut down
+//! test machines
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum Account {
+    /// EC2 instances of a region, through the AWS CLI
+    Aws {
+        region: String,
+        #[serde(default)]
+        profile: Option<String>,
+    },
+    /// Compute Engine instances, through the gcloud CLI
+    Gcp {
+        /// Defaults to the project gcloud is configured with
+        #[serde(default)]
+        project: Option<String>,
+    },
+    /// Droplets of a DigitalOcean team
+    #[cfg(feature = "http")]
+    DigitalOcean {
+        #[serde(default = "Account::getenv_digitalocean_token")]
+        token: Option<String>,
+    },
+    /// Servers of a Hetzner Cloud project
+    #[cfg(feature = "http")]
+    Hetzner {
+        #[serde(default = "Account::getenv_hcloud_token")]
+        token: Option<String>,
+    },
+}
+
+impl Account {
+    #[cfg(feature = "http")]
+    fn getenv_digitalocean_token() -> Option<String> {
+        std::env::var("DIGITALOCEAN_TOKEN").ok()
+    }
+
+    #[cfg(feature = "http")]
+    fn getenv_hcloud_token() -> Option<String> {
+        std::env::var("HCLOUD_TOKEN").ok()
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Account::Aws { region, .. } => region.clone(),
+            Account::Gcp { project } => project.clone().unwrap_or_else(|| "gcp".to_string()),
+            #[cfg(feature = "http")]
+            Account::DigitalOcean { .. } => "digitalocean".to_string(),
+            #[cfg(feature = "http")]
+            Account::Hetzner { .. } => "hetzner".to_string(),
+        }
+    }
+
+    fn run(command_runner: &dyn CommandRunner, command: &str) -> Result<String> {
+        let output = command_runner.run(command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "cloud_instances".to_string(),
+                format!("{} failed", command.split(' ').next().unwrap_or(command)),
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    fn running(&self, command_runner: &dyn CommandRunner) -> Result<u64> {
+        match self {
+            Account::Aws { region, profile } => {
+                let command = format!(
+                    "aws ec2 describe-instances --region '{}' \
+                     --filters Name=instance-state-name,Values=running \
+                     --query 'length(Reservations[].Instances[])' --output text{}",
+                    region,
+                    profile
+                        .as_ref()
+                        .map_or_else(String::new, |p| format!(" --profile '{}'", p))
+                );
+                Self::run(command_runner, &command)?
+                    .trim()
+                    .parse()
+                    .block_error("cloud_instances", "unexpected output of aws")
+            }
+            Account::Gcp { project } => {
+                let command = format!(
+                    "gcloud compute instances list --filter=status=RUNNING --format='value(name)'{}",
+                    project
+                        .as_ref()
+                        .map_or_else(String::new, |p| format!(" --project '{}'", p))
+                );
+                Ok(Self::run(command_runner, &command)?
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .count() as u64)
+            }
+            #[cfg(feature = "http")]
+            Account::DigitalOcean { token } => api::digitalocean(token.as_deref()),
+            #[cfg(feature = "http")]
+            Account::Hetzner { token } => api::hetzner(token.as_deref()),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+mod api {
+    use std::time::Duration;
+
+    use serde_json::Value as JsonValue;
+
+    use crate::errors::*;
+    use crate::http;
+
+    fn get(url: &str, token: Option<&str>, variable: &str) -> Result<JsonValue> {
+        let token = token.block_error(
+            "cloud_instances",
+            &format!("missing token, set token or {}", variable),
+        )?;
+        let header_value = format!("Bearer {}", token);
+        let response = http::http_get_json(
+            url,
+            Some(Duration::from_secs(10)),
+            vec![("Authorization", &header_value)],
+        )?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "cloud_instances".to_string(),
+                format!("failed to get {} (HTTP {})", url, response.code),
+            ));
+        }
+        Ok(response.content)
+    }
+
+    /// Droplets can't be filtered by status, so all of them are listed
+    pub fn digitalocean(token: Option<&str>) -> Result<u64> {
+        let mut running = 0;
+        let mut url = Some("https://api.digitalocean.com/v2/droplets?per_page=200".to_string());
+        while let Some(page) = url {
+            let content = get(&page, token, "DIGITALOCEAN_TOKEN")?;
+            running += content["droplets"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|droplet| droplet["status"] == "active")
+                .count() as u64;
+            url = content["links"]["pages"]["next"].as_str().map(String::from);
+        }
+        Ok(running)
+    }
+
+    pub fn hetzner(token: Option<&str>) -> Result<u64> {
+        let content = get(
+            "https://api.hetzner.cloud/v1/servers?status=running&per_page=1",
+            token,
+            "HCLOUD_TOKEN",
+        )?;
+        content["meta"]["pagination"]["total_entries"]
+            .as_u64()
+            .block_error("cloud_instances", "unexpected answer of Hetzner Cloud")
+    }
+}
+
+pub struct CloudInstances {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    accounts: Vec<Account>,
+    warning: u64,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct CloudInstancesConfig {
+    pub accounts: Vec<Account>,
+
+    /// Number of running instances above which the block is shown as warning
+    pub warning: u64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for CloudInstancesConfig {
+    fn default() -> Self {
+        Self {
+            accounts: Vec::new(),
+            warning: 0,
+            interval: Duration::from_secs(300),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for CloudInstances {
+    type Config = CloudInstancesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.accounts.is_empty() {
+            return Err(ConfigurationError(
+                "cloud_instances".to_string(),
+                "accounts must be set".to_string(),
+            ));
+        }
+
+        Ok(CloudInstances {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("cpu")?,
+            format: block_config.format.with_default("{count}")?,
+            accounts: block_config.accounts,
+            warning: block_config.warning,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+impl CloudInstances {
+    /// The number of running instances of every account
+    fn count(&self) -> Result<Vec<(String, u64)>> {
+        self.accounts
+            .iter()
+            .map(|account| {
+                let running = account.running(self.command_runner.as_ref())?;
+                Ok((account.label(), running))
+            })
+            .collect()
+    }
+}
+
+impl Block for CloudInstances {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let counts = match self.count() {
+            Ok(counts) => counts,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, the accounts are asked again at the next update
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(self.interval.into()));
+            }
+            Err(error) => {
+                self.text
+                    .set_text(format!("cloud_instances error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+        let total: u64 = counts.iter().map(|(_, running)| running).sum();
+        let details: Vec<String> = counts
+            .iter()
+            .filter(|(_, running)| *running > 0)
+            .map(|(label, running)| format!("{}: {}", label, running))
+            .collect();
+
+        let values = map!(
+            "count" => Value::from_integer(total as i64),
+            "details" => Value::from_string(details.join(", ")),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if total > self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_running() {
+        let runner = MockRunner::default();
+        runner.set(
+            "aws ec2 describe-instances --region 'eu-west-1' \
+             --filters Name=instance-state-name,Values=running \
+             --query 'length(Reservations[].Instances[])' --output text --profile 'lab'",
+            "2\n",
+        );
+        runner.set(
+            "gcloud compute instances list --filter=status=RUNNING --format='value(name)'",
+            "test-1\ntest-2\ntest-3\n",
+        );
+        let aws = Account::Aws {
+            region: "eu-west-1".to_string(),
+            profile: Some("lab".to_string()),
+        };
+        let gcp = Account::Gcp { project: None };
+        assert_eq!(aws.running(&runner).unwrap(), 2);
+        assert_eq!(gcp.running(&runner).unwrap(), 3);
+        assert_eq!(gcp.label(), "gcp");
+    }
+}
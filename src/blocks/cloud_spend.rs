@@ -0,0 +1,420 @@
+//! Shows how much has been spent on a cloud provider this month, compared to a budget. Billing
+//! data is updated only a few times a day, so the block asks rarely and keeps the last amount
+//! across restarts.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long to wait before asking again after asking failed
+const RETRY_INTERVAL: Duration = Duration::from_secs(1800);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum SpendService {
+    /// Cost Explorer, through the AWS CLI
+    Aws {
+        #[serde(default)]
+        profile: Option<String>,
+    },
+    /// The BigQuery export of Cloud Billing, through the `bq` CLI
+    Gcp {
+        /// The export table, e.g. "project.billing.gcp_billing_export_v1_XXXXXX"
+        table: String,
+    },
+    /// Estimated from the prices of the servers of a Hetzner Cloud project
+    #[cfg(feature = "http")]
+    Hetzner {
+        #[serde(default = "SpendService::getenv_hcloud_token")]
+        token: Option<String>,
+    },
+}
+
+/// The spend of a month, which survives restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Spend {
+    amount: f64,
+    currency: String,
+    /// When the amount was asked for, as a Unix timestamp
+    fetched: i64,
+}
+
+impl Spend {
+    fn is_of_month(&self, now: DateTime<Utc>) -> bool {
+        let fetched = Utc.timestamp(self.fetched, 0);
+        (fetched.year(), fetched.month()) == (now.year(), now.month())
+    }
+}
+
+/// A number that JSON APIs send either as a number or as a string
+fn amount(value: &JsonValue) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|amount| amount.parse().ok()))
+}
+
+impl SpendService {
+    #[cfg(feature = "http")]
+    fn getenv_hcloud_token() -> Option<String> {
+        std::env::var("HCLOUD_TOKEN").ok()
+    }
+
+    /// Under which name the spend is kept
+    fn cache_key(&self) -> String {
+        match self {
+            SpendService::Aws { profile } => {
+                format!("aws:{}", profile.as_deref().unwrap_or("default"))
+            }
+            SpendService::Gcp { table } => format!("gcp:{}", table),
+            #[cfg(feature = "http")]
+            SpendService::Hetzner { .. } => "hetzner".to_string(),
+        }
+    }
+
+    fn run(command_runner: &dyn CommandRunner, command: &str) -> Result<JsonValue> {
+        let output = command_runner.run(command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "cloud_spend".to_string(),
+                format!("{} failed", command.split(' ').next().unwrap_or(command)),
+            ));
+        }
+        serde_json::from_str(&output.stdout).block_error("cloud_spend", "unexpected output")
+    }
+
+    fn fetch(&self, command_runner: &dyn CommandRunner, now: DateTime<Utc>) -> Result<Spend> {
+        let (amount, currency) = match self {
+            SpendService::Aws { profile } => {
+                // The end of the period is exclusive
+                let command = format!(
+                    "aws ce get-cost-and-usage --time-period Start={},End={} \
+                     --granularity MONTHLY --metrics UnblendedCost --output json{}",
+                    now.format("%Y-%m-01"),
+                    (now + chrono::Duration::days(1)).format("%Y-%m-%d"),
+                    profile
+                        .as_ref()
+                        .map_or_else(String::new, |p| format!(" --profile '{}'", p))
+                );
+                let output = Self::run(command_runner, &command)?;
+                let cost = &output["ResultsByTime"][0]["Total"]["UnblendedCost"];
+                (
+                    amount(&cost["Amount"]),
+                    cost["Unit"].as_str().map(String::from),
+                )
+            }
+            SpendService::Gcp { table } => {
+                let query = format!(
+                    "SELECT SUM(cost) + SUM(IFNULL((SELECT SUM(c.amount) FROM UNNEST(credits) c), 0)) \
+                     AS total, ANY_VALUE(currency) AS currency FROM `{}` WHERE invoice.month = \"{}\"",
+                    table,
+                    now.format("%Y%m")
+                );
+                let command = format!("bq query --format=json --nouse_legacy_sql '{}'", query);
+                let output = Self::run(command_runner, &command)?;
+                // Nothing was billed yet if the total is null
+                (
+                    amount(&output[0]["total"]).or(Some(0.)),
+                    output[0]["currency"].as_str().map(String::from),
+                )
+            }
+            #[cfg(feature = "http")]
+            SpendService::Hetzner { token } => {
+                let token = token.as_ref().block_error(
+                    "cloud_spend",
+                    "missing Hetzner Cloud token, set it or HCLOUD_TOKEN",
+                )?;
+                (
+                    Some(hetzner::estimate(token, now)?),
+                    Some("EUR".to_string()),
+                )
+            }
+        };
+        Ok(Spend {
+            amount: amount.block_error("cloud_spend", "no amount in the billing data")?,
+            currency: currency.unwrap_or_default(),
+            fetched: now.timestamp(),
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+mod hetzner {
+    use std::time::Duration;
+
+    use chrono::{DateTime, Datelike, TimeZone, Utc};
+    use serde_json::Value as JsonValue;
+
+    use super::amount;
+    use crate::errors::*;
+    use crate::http;
+
+    /// What the servers of a page of `GET /servers` cost this month so far. Servers cost their
+    /// hourly price for every started hour, but no more than their monthly price.
+    pub fn servers_cost(servers: &JsonValue, now: DateTime<Utc>) -> f64 {
+        let month_start = Utc.ymd(now.year(), now.month(), 1).and_hms(0, 0, 0);
+        servers
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|server| {
+                let created = DateTime::parse_from_rfc3339(server["created"].as_str()?).ok()?;
+                let since = created.with_timezone(&Utc).max(month_start);
+                let hours = ((now - since).num_minutes() as f64 / 60.).ceil();
+                let location = &server["datacenter"]["location"]["name"];
+                let prices = server["server_type"]["prices"]
+                    .as_array()?
+                    .iter()
+                    .find(|prices| prices["location"] == *location)?;
+                let hourly = amount(&prices["price_hourly"]["gross"])?;
+                let monthly = amount(&prices["price_monthly"]["gross"])?;
+                Some((hours * hourly).min(monthly))
+            })
+            .sum()
+    }
+
+    pub fn estimate(token: &str, now: DateTime<Utc>) -> Result<f64> {
+        let header_value = format!("Bearer {}", token);
+        let mut total = 0.;
+        let mut page = Some(1);
+        while let Some(number) = page {
+            let url = format!(
+                "https://api.hetzner.cloud/v1/servers?per_page=50&page={}",
+                number
+            );
+            let response = http::http_get_json(
+                &url,
+                Some(Duration::from_secs(10)),
+                vec![("Authorization", &header_value)],
+            )?;
+            if response.code != 200 {
+                return Err(BlockError(
+                    "cloud_spend".to_string(),
+                    format!("Hetzner Cloud answered HTTP {}", response.code),
+                ));
+            }
+            total += servers_cost(&response.content["servers"], now);
+            page = response.content["meta"]["pagination"]["next_page"].as_u64();
+        }
+        Ok(total)
+    }
+}
+
+pub struct CloudSpend {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    service: SpendService,
+    budget: Option<f64>,
+    warning: f64,
+    interval: Duration,
+    spend: Option<Spend>,
+    state_file: PathBuf,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CloudSpendConfig {
+    pub service: SpendService,
+
+    /// Monthly budget, in the currency of the bill
+    #[serde(default)]
+    pub budget: Option<f64>,
+
+    /// Percentage of the budget from which on the block is shown as warning
+    #[serde(default = "CloudSpendConfig::default_warning")]
+    pub warning: f64,
+
+    /// How often to ask for the spend
+    #[serde(
+        default = "CloudSpendConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl CloudSpendConfig {
+    fn default_warning() -> f64 {
+        80.
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(6 * 3600)
+    }
+}
+
+impl ConfigBlock for CloudSpend {
+    type Config = CloudSpendConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let state_file = xdg_state_home().join("i3status-rust/cloud_spend.toml");
+        let spend = load(&state_file).remove(&block_config.service.cache_key());
+
+        Ok(CloudSpend {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()),
+            format: block_config.format.with_default("{spend} {currency}")?,
+            service: block_config.service,
+            budget: block_config.budget,
+            warning: block_config.warning,
+            interval: block_config.interval,
+            spend,
+            state_file,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+fn load(state_file: &std::path::Path) -> BTreeMap<String, Spend> {
+    fs::read_to_string(state_file)
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+impl CloudSpend {
+    fn save(&self, spend: &Spend) {
+        // Failing to save only means that the spend is asked for again after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        // Other blocks keep the spend of other accounts in the same file
+        let mut spends = load(&self.state_file);
+        spends.insert(self.service.cache_key(), spend.clone());
+        if let Ok(contents) = toml::to_string(&spends) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+
+    /// Asks for the spend unless the last amount is recent enough, and returns when to ask again
+    fn refresh(&mut self, now: DateTime<Utc>) -> Result<Duration> {
+        let interval = self.interval.as_secs() as i64;
+        if let Some(spend) = &self.spend {
+            let age = now.timestamp() - spend.fetched;
+            if spend.is_of_month(now) && age < interval {
+                return Ok(Duration::from_secs((interval - age) as u64));
+            }
+        }
+        match self.service.fetch(self.command_runner.as_ref(), now) {
+            Ok(spend) => {
+                self.save(&spend);
+                self.spend = Some(spend);
+                Ok(self.interval)
+            }
+            // Keep showing the last amount
+            Err(error) if self.spend.as_ref().is_some_and(|s| s.is_of_month(now)) => {
+                log::warn!("{}", error);
+                Ok(RETRY_INTERVAL)
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl Block for CloudSpend {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let next_update = match self.refresh(Utc::now()) {
+            Ok(next_update) => next_update,
+            // There is no amount of this month to show instead
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(RETRY_INTERVAL.into()));
+            }
+            Err(error) => {
+                self.text.set_text(format!("cloud_spend error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(RETRY_INTERVAL.into()));
+            }
+        };
+        let spend = self
+            .spend
+            .as_ref()
+            .internal_error("cloud_spend", "no spend after refreshing")?;
+
+        let percent = self
+            .budget
+            .map_or(0., |budget| spend.amount / budget * 100.);
+        let values = map!(
+            "spend" => Value::from_string(format!("{:.2}", spend.amount)),
+            "currency" => Value::from_string(spend.currency.clone()),
+            "budget" => Value::from_string(self.budget.map_or_else(String::new, |b| format!("{:.2}", b))),
+            "percent" => Value::from_integer(percent.round() as i64).percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.budget {
+            Some(_) if percent >= 100. => State::Critical,
+            Some(_) if percent >= self.warning => State::Warning,
+            _ => State::Idle,
+        });
+
+        Ok(Some(next_update.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_aws_spend() {
+        let runner = MockRunner::default();
+        runner.set(
+            "aws ce get-cost-and-usage --time-period Start=2021-10-01,End=2021-10-16 \
+             --granularity MONTHLY --metrics UnblendedCost --output json --profile 'work'",
+            r#"{"ResultsByTime": [{"Total": {"UnblendedCost": {"Amount": "123.4567", "Unit": "USD"}}}]}"#,
+        );
+        let service = SpendService::Aws {
+            profile: Some("work".to_string()),
+        };
+        let now = Utc.ymd(2021, 10, 15).and_hms(12, 0, 0);
+        let spend = service.fetch(&runner, now).unwrap();
+        assert_eq!(
+            spend,
+            Spend {
+                amount: 123.4567,
+                currency: "USD".to_string(),
+                fetched: now.timestamp(),
+            }
+        );
+        assert!(spend.is_of_month(now));
+        assert!(!spend.is_of_month(Utc.ymd(2021, 11, 1).and_hms(0, 0, 0)));
+    }
+}
@@ -0,0 +1,196 @@
+//! Counts down to the next session of a followed calendar, such as the race weekends of a motor
+//! sport or the matches of an esports league, as published in iCalendar format.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use crossbeam_channel::Sender;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::ics;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::format_countdown;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Countdown {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    calendar: String,
+    filter: Option<Regex>,
+    hide_days: i64,
+    interval: Duration,
+    events: Vec<ics::Event>,
+    fetched: Option<Instant>,
+    /// What to show instead of the sessions when the calendar couldn't be read last time
+    error: Option<String>,
+    visible: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct CountdownConfig {
+    /// Path or http(s) URL of an iCalendar (`.ics`) file
+    pub calendar: String,
+
+    /// Only count down to sessions whose summary matches
+    #[serde(with = "serde_regex")]
+    pub filter: Option<Regex>,
+
+    /// Hide the block while the next session is more days away
+    pub hide_days: i64,
+
+    /// How often the calendar is read again
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        Self {
+            calendar: String::new(),
+            filter: None,
+            hide_days: 7,
+            interval: Duration::from_secs(3600),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Countdown {
+    type Config = CountdownConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.calendar.is_empty() {
+            return Err(ConfigurationError(
+                "countdown".to_string(),
+                "calendar must be set".to_string(),
+            ));
+        }
+
+        Ok(Countdown {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("time")?,
+            format: block_config.format.with_default("{summary} {countdown}")?,
+            calendar: block_config.calendar,
+            filter: block_config.filter,
+            hide_days: block_config.hide_days,
+            interval: block_config.interval,
+            events: Vec::new(),
+            fetched: None,
+            error: None,
+            visible: false,
+        })
+    }
+}
+
+impl Countdown {
+    /// The session that is running or else the next one
+    fn next_session(&self, now: DateTime<Utc>) -> Option<&ics::Event> {
+        self.events
+            .iter()
+            .filter(|event| event.end > now || event.start > now)
+            .filter(|event| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.is_match(&event.summary))
+            })
+            .min_by_key(|event| event.start)
+    }
+}
+
+impl Block for Countdown {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if self
+            .fetched
+            .is_none_or(|fetched| fetched.elapsed() >= self.interval)
+        {
+            // The sessions read last are kept when the calendar can't be read, and the calendar is
+            // tried again after the interval
+            self.error = match ics::fetch("countdown", &self.calendar, None) {
+                Ok(events) => {
+                    self.events = events;
+                    None
+                }
+                Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                    Some("×".to_string())
+                }
+                Err(error) => Some(format!("countdown error {}", error)),
+            };
+            self.fetched = Some(Instant::now());
+        }
+
+        let now = Utc::now();
+        let session = match self.next_session(now) {
+            Some(session) if session.start - now <= chrono::Duration::days(self.hide_days) => {
+                session
+            }
+            _ => {
+                match &self.error {
+                    Some(error) => {
+                        self.text.set_text(error.clone());
+                        self.text.set_state(State::Critical);
+                        self.visible = true;
+                    }
+                    None => self.visible = false,
+                }
+                return Ok(Some(Duration::from_secs(60).into()));
+            }
+        };
+        let values = map!(
+            "summary" => Value::from_string(session.summary.clone()),
+            "location" => Value::from_string(session.location.clone()),
+            "start" => Value::from_string(session.start.with_timezone(&Local).format("%a %H:%M").to_string()),
+            "countdown" => Value::from_string(format_countdown(session.start - now)),
+        );
+        let running = session.start <= now;
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if self.error.is_some() {
+            State::Critical
+        } else if running {
+            State::Info
+        } else {
+            State::Idle
+        });
+        self.visible = true;
+
+        Ok(Some(Duration::from_secs(60).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.fetched = None;
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.visible {
+            vec![&self.text]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -0,0 +1,195 @@
+//! Counts something over the day, e.g. glasses of water or cups of coffee, towards an optional
+//! goal. The count survives restarts and starts over every day.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, TimeZone};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// The count of a day
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+struct Tally {
+    day: i32,
+    count: u64,
+}
+
+pub struct Counter {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    goal: Option<u64>,
+    reset_hour: u32,
+    tally: Tally,
+    state_file: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct CounterConfig {
+    /// Name of the counter, which tells the counts of several counter blocks apart
+    pub counter: String,
+
+    /// The count to reach every day
+    pub goal: Option<u64>,
+
+    /// Hour of the day (0 to 23) at which the count starts over
+    pub reset_hour: u32,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for CounterConfig {
+    fn default() -> Self {
+        Self {
+            counter: "counter".to_string(),
+            goal: None,
+            reset_hour: 0,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The day that `time` counts towards if days start at `reset_hour`
+fn day_of<Tz: TimeZone>(time: DateTime<Tz>, reset_hour: u32) -> i32 {
+    (time - chrono::Duration::hours(reset_hour as i64))
+        .date()
+        .num_days_from_ce()
+}
+
+impl ConfigBlock for Counter {
+    type Config = CounterConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.reset_hour > 23 {
+            return Err(ConfigurationError(
+                "counter".to_string(),
+                "reset_hour must be between 0 and 23".to_string(),
+            ));
+        }
+        let state_file = xdg_state_home().join(format!(
+            "i3status-rust/counter/{}.toml",
+            block_config.counter
+        ));
+        let tally = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(Counter {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default(match block_config.goal {
+                Some(_) => "{count}/{goal}",
+                None => "{count}",
+            })?,
+            goal: block_config.goal,
+            reset_hour: block_config.reset_hour,
+            tally,
+            state_file,
+        })
+    }
+}
+
+impl Counter {
+    /// The tally of today, which starts over if the day changed
+    fn today(&self) -> Tally {
+        let day = day_of(Local::now(), self.reset_hour);
+        if self.tally.day == day {
+            self.tally
+        } else {
+            Tally { day, count: 0 }
+        }
+    }
+
+    fn save(&self) {
+        // Failing to save only means that the count starts over after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&self.tally) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+}
+
+impl Block for Counter {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.tally = self.today();
+        let count = self.tally.count;
+        let goal = self.goal.unwrap_or(0);
+        let progress = if goal > 0 {
+            count as f64 / goal as f64 * 100.
+        } else {
+            0.
+        };
+        let values = map!(
+            "count" => Value::from_integer(count as i64),
+            "goal" => Value::from_integer(goal as i64),
+            "progress" => Value::from_float(progress).percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.goal {
+            Some(goal) if count >= goal => State::Good,
+            _ => State::Idle,
+        });
+
+        // Often enough to start over close to the reset hour
+        Ok(Some(Duration::from_secs(60).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let mut tally = self.today();
+        match event.button {
+            MouseButton::Left => tally.count += 1,
+            MouseButton::Right => tally.count = tally.count.saturating_sub(1),
+            _ => return Ok(()),
+        }
+        self.tally = tally;
+        self.save();
+        self.update()?;
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_of() {
+        let evening = Local.ymd(2021, 6, 1).and_hms(23, 0, 0);
+        let night = Local.ymd(2021, 6, 2).and_hms(3, 0, 0);
+        let morning = Local.ymd(2021, 6, 2).and_hms(5, 0, 0);
+        assert_eq!(day_of(night, 0), day_of(evening, 0) + 1);
+        assert_eq!(day_of(night, 4), day_of(evening, 4));
+        assert_eq!(day_of(morning, 4), day_of(evening, 4) + 1);
+    }
+}
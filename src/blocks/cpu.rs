@@ -7,23 +7,30 @@ use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_smoothing};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::util::{Hysteresis, Smoother, Smoothing};
+use crate::widgets::graph::{GraphConfig, GraphWidget};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
 pub struct Cpu {
     id: usize,
     output: TextWidget,
+    graph: Option<GraphWidget>,
     prev_util: Vec<(u64, u64)>,
     update_interval: Duration,
     minimum_info: u64,
     minimum_warning: u64,
     minimum_critical: u64,
+    hysteresis: Hysteresis,
+    smoothing: Option<Smoothing>,
+    smoothers: Vec<Smoother>,
     format: FormatTemplate,
     boost_icon_on: String,
     boost_icon_off: String,
@@ -45,8 +52,18 @@ pub struct CpuConfig {
     /// Minimum usage, where state is set to critical
     pub critical: u64,
 
+    /// Margin by which the usage has to drop below a threshold before the state is lowered
+    pub hysteresis: f64,
+
+    /// Smoothing applied to the utilization values
+    #[serde(deserialize_with = "deserialize_opt_smoothing")]
+    pub smoothing: Option<Smoothing>,
+
     /// Format override
     pub format: FormatTemplate,
+
+    /// Show the history of the average utilization as a graph
+    pub graph: Option<GraphConfig>,
 }
 
 impl Default for CpuConfig {
@@ -56,7 +73,10 @@ impl Default for CpuConfig {
             info: 30,
             warning: 60,
             critical: 90,
+            hysteresis: 0.,
+            smoothing: None,
             format: FormatTemplate::default(),
+            graph: None,
         }
     }
 }
@@ -70,13 +90,23 @@ impl ConfigBlock for Cpu {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let graph = block_config.graph.map(|mut graph| {
+            graph.min.get_or_insert(0.);
+            graph.max.get_or_insert(100.);
+            GraphWidget::new(id, 1, shared_config.clone(), graph)
+        });
+
         Ok(Cpu {
             id,
+            graph,
             update_interval: block_config.interval,
             prev_util: Vec::with_capacity(32),
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
+            hysteresis: Hysteresis::new(block_config.hysteresis),
+            smoothing: block_config.smoothing,
+            smoothers: Vec::new(),
             boost_icon_on: shared_config.get_icon("cpu_boost_on")?,
             boost_icon_off: shared_config.get_icon("cpu_boost_off")?,
             output: TextWidget::new(id, 0, shared_config).with_icon("cpu")?,
@@ -87,91 +117,75 @@ impl ConfigBlock for Cpu {
 
 impl Block for Cpu {
     fn update(&mut self) -> Result<Option<Update>> {
-        // Read frequencies (read in MHz, store in Hz)
-        let mut freqs = Vec::with_capacity(32);
-        let mut freqs_avg = 0.0;
-        let freqs_f =
-            File::open("/proc/cpuinfo").block_error("cpu", "failed to read /proc/cpuinfo")?;
-        for line in BufReader::new(freqs_f).lines().scan((), |_, x| x.ok()) {
-            if line.starts_with("cpu MHz") {
-                let words = line.split(' ');
-                let last = words
-                    .last()
-                    .expect("failed to get last word of line while getting cpu frequency");
-                let numb = last
-                    .parse::<f64>()
-                    .expect("failed to parse String to f64 while getting cpu frequency")
-                    * 1e6; // convert to Hz
-                freqs.push(numb);
-                freqs_avg += numb;
-            }
+        let (freqs, times) = if bsd::bsd().is_some() {
+            (bsd::cpu_frequencies(), bsd::cpu_times()?)
+        } else {
+            (read_frequencies()?, read_times()?)
+        };
+        let freqs_avg = freqs.iter().sum::<f64>() / freqs.len() as f64;
+
+        let mut utilizations = Vec::with_capacity(times.len());
+        for (i, (idle, non_idle)) in times.into_iter().enumerate() {
+            let (prev_idles, prev_non_idles) = if self.prev_util.len() <= i {
+                self.prev_util.push((0, 0));
+                (0, 0)
+            } else {
+                self.prev_util[i]
+            };
+
+            let prev_total = prev_idles + prev_non_idles;
+            let total = idle + non_idle;
+
+            // This check is needed because the new values may be reset, for
+            // example after hibernation.
+            let (total_delta, idle_delta) = if prev_total < total && prev_idles <= idle {
+                (total - prev_total, idle - prev_idles)
+            } else {
+                (1, 1)
+            };
+
+            utilizations
+                .push(((total_delta - idle_delta) as f64 / total_delta as f64).clamp(0., 1.));
+
+            self.prev_util[i] = (idle, non_idle);
         }
-        freqs_avg /= freqs.len() as f64;
-
-        // Read utilizations
-        let mut utilizations = Vec::with_capacity(32);
-        let utilizations_f = File::open("/proc/stat")
-            .block_error("cpu", "Your system doesn't support /proc/stat")?;
-        for (i, line) in BufReader::new(utilizations_f)
-            .lines()
-            .scan((), |_, x| x.ok())
-            .enumerate()
-        {
-            if line.starts_with("cpu") {
-                let data: Vec<u64> = line
-                    .split_whitespace()
-                    .filter_map(|x| x.parse::<u64>().ok())
-                    .collect();
-
-                // idle = idle + iowait
-                let idle = data[3] + data[4];
-                let non_idle = data[0] + // user
-                                data[1] + // nice
-                                data[2] + // system
-                                data[5] + // irq
-                                data[6] + // softirq
-                                data[7]; // steal
-
-                let (prev_idles, prev_non_idles) = if self.prev_util.len() <= i {
-                    self.prev_util.push((0, 0));
-                    (0, 0)
-                } else {
-                    self.prev_util[i]
-                };
-
-                let prev_total = prev_idles + prev_non_idles;
-                let total = idle + non_idle;
-
-                // This check is needed because the new values may be reset, for
-                // example after hibernation.
-                let (total_delta, idle_delta) = if prev_total < total && prev_idles <= idle {
-                    (total - prev_total, idle - prev_idles)
-                } else {
-                    (1, 1)
-                };
-
-                utilizations
-                    .push(((total_delta - idle_delta) as f64 / total_delta as f64).clamp(0., 1.));
-
-                self.prev_util[i] = (idle, non_idle);
-            }
+
+        let smoothing = self.smoothing;
+        self.smoothers
+            .resize_with(utilizations.len(), || Smoother::new(smoothing));
+        for (utilization, smoother) in utilizations.iter_mut().zip(&mut self.smoothers) {
+            *utilization = smoother.push(*utilization);
         }
 
         let (avg, utilizations) = utilizations.split_first().unwrap();
         let avg_utilization = avg * 100.;
 
-        self.output.set_state(match avg_utilization as u64 {
-            x if x > self.minimum_critical => State::Critical,
-            x if x > self.minimum_warning => State::Warning,
-            x if x > self.minimum_info => State::Info,
-            _ => State::Idle,
-        });
+        let (info, warning, critical) = (
+            self.minimum_info,
+            self.minimum_warning,
+            self.minimum_critical,
+        );
+        let state =
+            self.hysteresis
+                .apply(avg_utilization, |utilization| match utilization as u64 {
+                    x if x > critical => State::Critical,
+                    x if x > warning => State::Warning,
+                    x if x > info => State::Info,
+                    _ => State::Idle,
+                });
+        self.output.set_gradient_value(avg_utilization);
+        self.output.set_state(state);
+        if let Some(graph) = &mut self.graph {
+            graph.push(avg_utilization);
+            graph.set_state(state);
+        }
 
         let mut barchart = String::new();
         const BOXCHARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
         for utilization in utilizations {
             barchart.push(BOXCHARS[(7.5 * utilization) as usize]);
         }
+        let heatmap = heatmap(utilizations);
 
         let boost = match boost_status() {
             Some(true) => self.boost_icon_on.clone(),
@@ -182,6 +196,7 @@ impl Block for Cpu {
         let mut values = map_to_owned!(
             "frequency" => Value::from_float(freqs_avg).hertz(),
             "barchart" => Value::from_string(barchart),
+            "heatmap" => Value::from_string(heatmap),
             "utilization" => Value::from_integer(avg_utilization as i64).percents(),
             "boost" => Value::from_string(boost),
         );
@@ -204,7 +219,10 @@ impl Block for Cpu {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+        match &self.graph {
+            Some(graph) => vec![&self.output, graph],
+            None => vec![&self.output],
+        }
     }
 
     fn id(&self) -> usize {
@@ -212,6 +230,64 @@ impl Block for Cpu {
     }
 }
 
+/// Read the frequency of each core from /proc/cpuinfo (read in MHz, return in Hz)
+fn read_frequencies() -> Result<Vec<f64>> {
+    let mut freqs = Vec::with_capacity(32);
+    let freqs_f = File::open("/proc/cpuinfo").block_error("cpu", "failed to read /proc/cpuinfo")?;
+    for line in BufReader::new(freqs_f).lines().scan((), |_, x| x.ok()) {
+        if line.starts_with("cpu MHz") {
+            let words = line.split(' ');
+            let last = words
+                .last()
+                .expect("failed to get last word of line while getting cpu frequency");
+            let numb = last
+                .parse::<f64>()
+                .expect("failed to parse String to f64 while getting cpu frequency")
+                * 1e6; // convert to Hz
+            freqs.push(numb);
+        }
+    }
+    Ok(freqs)
+}
+
+/// Read the idle and non-idle time of all cpus together and of each core from /proc/stat
+fn read_times() -> Result<Vec<(u64, u64)>> {
+    let mut times = Vec::with_capacity(32);
+    let utilizations_f =
+        File::open("/proc/stat").block_error("cpu", "Your system doesn't support /proc/stat")?;
+    for line in BufReader::new(utilizations_f)
+        .lines()
+        .scan((), |_, x| x.ok())
+    {
+        if line.starts_with("cpu") {
+            let data: Vec<u64> = line
+                .split_whitespace()
+                .filter_map(|x| x.parse::<u64>().ok())
+                .collect();
+
+            // idle = idle + iowait
+            let idle = data[3] + data[4];
+            let non_idle = data[0] + // user
+                            data[1] + // nice
+                            data[2] + // system
+                            data[5] + // irq
+                            data[6] + // softirq
+                            data[7]; // steal
+            times.push((idle, non_idle));
+        }
+    }
+    Ok(times)
+}
+
+/// One glyph per core in four coarse steps, which stay readable at a glance even for many cores
+fn heatmap(utilizations: &[f64]) -> String {
+    const GLYPHS: &[char] = &['▁', '▃', '▅', '█'];
+    utilizations
+        .iter()
+        .map(|utilization| GLYPHS[((4. * utilization) as usize).min(GLYPHS.len() - 1)])
+        .collect()
+}
+
 /// Read the cpu turbo boost status from kernel sys interface
 /// or intel pstate interface
 fn boost_status() -> Option<bool> {
@@ -222,3 +298,13 @@ fn boost_status() -> Option<bool> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap() {
+        assert_eq!(heatmap(&[0., 0.3, 0.6, 0.8, 1.]), "▁▃▅██");
+    }
+}
@@ -1,6 +1,9 @@
 use std::env;
+use std::io::{BufRead, BufReader};
 use std::iter::{Cycle, Peekable};
-use std::process::Command;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use std::vec;
@@ -13,16 +16,19 @@ use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
 use crate::signals::convert_to_valid_signal;
 use crate::subprocess::spawn_child_async;
+use crate::widgets::graph::{GraphConfig, GraphWidget};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 use crossbeam_channel::Sender;
-use inotify::{EventMask, Inotify, WatchMask};
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
 use serde_derive::Deserialize;
 
 pub struct Custom {
     id: usize,
     update_interval: Update,
     output: TextWidget,
+    graph: Option<GraphWidget>,
     command: Option<String>,
     on_click: Option<String>,
     cycle: Option<Peekable<Cycle<vec::IntoIter<String>>>>,
@@ -32,6 +38,16 @@ pub struct Custom {
     hide_when_empty: bool,
     is_empty: bool,
     shell: String,
+    persistent: Option<Arc<Mutex<Persistent>>>,
+}
+
+/// The command of a persistent block, which keeps running
+#[derive(Debug, Default)]
+struct Persistent {
+    /// The process group of the command while it runs
+    pid: Option<u32>,
+    /// The line that the command printed last, until it is shown
+    line: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -50,14 +66,18 @@ pub struct CustomConfig {
     /// Signal to update upon reception
     pub signal: Option<i32>,
 
-    /// Files to watch for modifications and trigger update
-    pub watch_files: Option<Vec<String>>,
-
     /// Parse command output if it contains valid bar JSON
     pub json: bool,
 
     pub hide_when_empty: bool,
 
+    /// Show the history of the output as a graph, which is the `value` in JSON output and
+    /// otherwise the whole output if it is a number
+    pub graph: Option<GraphConfig>,
+
+    /// Keep the command running and show each line it prints as soon as it does
+    pub persistent: bool,
+
     // TODO make a global config option
     pub shell: String,
 }
@@ -69,9 +89,10 @@ impl Default for CustomConfig {
             command: None,
             cycle: None,
             signal: None,
-            watch_files: None,
             json: false,
             hide_when_empty: false,
+            graph: None,
+            persistent: false,
             shell: env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()),
         }
     }
@@ -89,6 +110,9 @@ impl ConfigBlock for Custom {
         let mut custom = Custom {
             id,
             update_interval: block_config.interval,
+            graph: block_config
+                .graph
+                .map(|graph| GraphWidget::new(id, 1, shared_config.clone(), graph)),
             output: TextWidget::new(id, 0, shared_config),
             command: None,
             on_click: None,
@@ -99,6 +123,7 @@ impl ConfigBlock for Custom {
             hide_when_empty: block_config.hide_when_empty,
             is_empty: true,
             shell: block_config.shell,
+            persistent: None,
         };
 
         if let Some(signal) = block_config.signal {
@@ -106,50 +131,6 @@ impl ConfigBlock for Custom {
             custom.signal = Some(convert_to_valid_signal(signal)?);
         };
 
-        if let Some(paths) = block_config.watch_files {
-            let tx_inotify = custom.tx_update_request.clone();
-            let mut notify = Inotify::init().expect("Failed to start inotify");
-            for path in paths {
-                let path_expanded = shellexpand::full(&path).map_err(|e| {
-                    ConfigurationError(
-                        "custom".to_string(),
-                        format!("Failed to expand file path {}: {}", &path, e),
-                    )
-                })?;
-                notify
-                    .add_watch(&*path_expanded, WatchMask::MODIFY)
-                    .map_err(|e| {
-                        ConfigurationError(
-                            "custom".to_string(),
-                            format!("Failed to watch file {}: {}", &path, e),
-                        )
-                    })?;
-            }
-            thread::Builder::new()
-                .name("custom".into())
-                .spawn(move || {
-                    let mut buffer = [0; 1024];
-                    loop {
-                        let mut events = notify
-                            .read_events_blocking(&mut buffer)
-                            .expect("Error while reading inotify events");
-
-                        if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
-                            tx_inotify
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-                        }
-
-                        // Avoid update spam.
-                        thread::sleep(Duration::from_millis(250))
-                    }
-                })
-                .unwrap();
-        };
-
         if block_config.cycle.is_some() && block_config.command.is_some() {
             return Err(BlockError(
                 "custom".to_string(),
@@ -163,9 +144,34 @@ impl ConfigBlock for Custom {
         };
 
         if let Some(command) = block_config.command {
+            if block_config.persistent {
+                let persistent = Arc::new(Mutex::new(Persistent::default()));
+                let thread_persistent = persistent.clone();
+                let shell = custom.shell.clone();
+                let restart = match custom.update_interval {
+                    Update::Every(interval) => Some(interval),
+                    Update::Once => None,
+                };
+                let tx = custom.tx_update_request.clone();
+                thread::Builder::new()
+                    .name("custom".into())
+                    .spawn(move || {
+                        run_persistent(id, shell, command, restart, thread_persistent, tx)
+                    })
+                    .internal_error("custom", "failed to start a thread")?;
+                custom.persistent = Some(persistent);
+                return Ok(custom);
+            }
             custom.command = Some(command)
         };
 
+        if block_config.persistent {
+            return Err(ConfigurationError(
+                "custom".to_string(),
+                "`persistent` needs a `command`".to_string(),
+            ));
+        }
+
         Ok(custom)
     }
 
@@ -174,6 +180,59 @@ impl ConfigBlock for Custom {
     }
 }
 
+fn update_now(id: usize, tx_update_request: &Sender<Task>) {
+    let _ = tx_update_request.send(Task {
+        id,
+        update_time: Instant::now(),
+    });
+}
+
+/// Runs the command of a persistent block and keeps the line it printed last, until the block is
+/// dropped. The command is run again `restart` after it exits, or never if that is `None`.
+fn run_persistent(
+    id: usize,
+    shell: String,
+    command: String,
+    restart: Option<Duration>,
+    persistent: Arc<Mutex<Persistent>>,
+    tx_update_request: Sender<Task>,
+) {
+    // The block holds the other reference, until a reload of the configuration drops it
+    while Arc::strong_count(&persistent) > 1 {
+        // In a group of its own, so that the pipelines of the command end with it
+        let child = Command::new(&shell)
+            .args(["-c", &command])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                persistent.lock().unwrap().pid = Some(child.id());
+                if let Some(stdout) = child.stdout.take() {
+                    for line in BufReader::new(stdout).lines() {
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(_) => break,
+                        };
+                        log::debug!("{:?} printed {:?}", command, line);
+                        persistent.lock().unwrap().line = Some(line);
+                        update_now(id, &tx_update_request);
+                    }
+                }
+                let exit = child.wait();
+                persistent.lock().unwrap().pid = None;
+                log::warn!("{:?} exited: {:?}", command, exit);
+            }
+            Err(error) => log::warn!("failed to run {:?}: {}", command, error),
+        }
+        match restart {
+            Some(restart) => thread::sleep(restart),
+            None => return,
+        }
+    }
+}
+
 fn default_icon() -> String {
     String::from("")
 }
@@ -189,10 +248,66 @@ struct Output {
     #[serde(default = "default_state")]
     state: State,
     text: String,
+    /// Shown instead of `text` when the bar runs out of space
+    short_text: Option<String>,
+    /// Picks the colors if the block is configured with a `gradient`
+    value: Option<f64>,
+}
+
+impl Drop for Custom {
+    // Ends the thread of a persistent block, which waits for the command
+    fn drop(&mut self) {
+        if let Some(persistent) = &self.persistent {
+            if let Some(pid) = persistent.lock().unwrap().pid {
+                let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM);
+            }
+        }
+    }
+}
+
+impl Custom {
+    fn show(&mut self, raw_output: String) -> Result<()> {
+        if self.json {
+            let output: Output = serde_json::from_str(&*raw_output).map_err(|e| {
+                BlockError("custom".to_string(), format!("Error parsing JSON: {}", e))
+            })?;
+            if output.icon.is_empty() {
+                self.output.unset_icon();
+            } else {
+                self.output.set_icon(&output.icon)?;
+            }
+            self.output.set_state(output.state);
+            if let Some(value) = output.value {
+                self.output.set_gradient_value(value);
+            }
+            if let (Some(graph), Some(value)) = (&mut self.graph, output.value) {
+                graph.push(value);
+                graph.set_state(output.state);
+            }
+            self.is_empty = output.text.is_empty();
+            self.output.set_texts((output.text, output.short_text));
+        } else {
+            if let (Some(graph), Ok(value)) = (&mut self.graph, raw_output.parse()) {
+                graph.push(value);
+            }
+            self.is_empty = raw_output.is_empty();
+            self.output.set_text(raw_output);
+        }
+        Ok(())
+    }
 }
 
 impl Block for Custom {
     fn update(&mut self) -> Result<Option<Update>> {
+        if let Some(persistent) = &self.persistent {
+            // Updates without a new line, e.g. after clicks, keep what is shown
+            let line = persistent.lock().unwrap().line.take();
+            if let Some(line) = line {
+                self.show(line.trim().to_owned())?;
+            }
+            return Ok(None);
+        }
+
         let command_str = self
             .cycle
             .as_mut()
@@ -208,23 +323,8 @@ impl Block for Custom {
             Ok(output) => output,
             Err(e) => return Err(BlockError("custom".to_string(), e.to_string())),
         };
-
-        if self.json {
-            let output: Output = serde_json::from_str(&*raw_output).map_err(|e| {
-                BlockError("custom".to_string(), format!("Error parsing JSON: {}", e))
-            })?;
-            if output.icon.is_empty() {
-                self.output.unset_icon();
-            } else {
-                self.output.set_icon(&output.icon)?;
-            }
-            self.output.set_state(output.state);
-            self.is_empty = output.text.is_empty();
-            self.output.set_text(output.text);
-        } else {
-            self.is_empty = raw_output.is_empty();
-            self.output.set_text(raw_output);
-        }
+        log::debug!("{:?} printed {:?}", command_str, raw_output);
+        self.show(raw_output)?;
 
         Ok(Some(self.update_interval.clone()))
     }
@@ -232,6 +332,8 @@ impl Block for Custom {
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         if self.is_empty && self.hide_when_empty {
             vec![]
+        } else if let Some(graph) = &self.graph {
+            vec![&self.output, graph]
         } else {
             vec![&self.output]
         }
@@ -64,12 +64,13 @@ impl ConfigBlock for CustomDBus {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let status_original = Arc::new(Mutex::new(CustomDBusStatus {
+        let status = Arc::new(Mutex::new(CustomDBusStatus {
             content: block_config.initial_text,
             icon: String::from(""),
             state: State::Idle,
         }));
-        let status = status_original.clone();
+        // Weak, so that the thread notices when the block is gone
+        let status_original = Arc::downgrade(&status);
         let name = block_config.name;
         thread::Builder::new()
             .name("custom_dbus".into())
@@ -79,6 +80,8 @@ impl ConfigBlock for CustomDBus {
                 c.request_name("i3.status.rs", false, true, false)
                     .expect("Failed to request bus name");
 
+                let alive = status_original.clone();
+
                 // TODO: better to rewrite this to use a property?
                 let f = Factory::new_fn::<()>();
                 let tree = f
@@ -94,6 +97,10 @@ impl ConfigBlock for CustomDBus {
                                         // messages to send back.
 
                                         let args = m.msg.get3::<&str, &str, &str>();
+                                        let status_original = match status_original.upgrade() {
+                                            Some(status) => status,
+                                            None => return Ok(vec![m.msg.method_return()]),
+                                        };
                                         let mut status = status_original.lock().unwrap();
 
                                         if let Some(new_content) = args.0 {
@@ -132,8 +139,8 @@ impl ConfigBlock for CustomDBus {
                 // We add the tree to the connection so that incoming method calls will be handled.
                 tree.start_receive(&c);
 
-                // Serve clients forever.
-                loop {
+                // Serve clients until the block is gone.
+                while alive.strong_count() > 0 {
                     c.process(Duration::from_millis(1000)).unwrap();
                 }
             })
@@ -0,0 +1,339 @@
+//! Counts down to the next of a set of daily events, which are prayer times computed for a place
+//! and/or fixed times of the day.
+//!
+//! Prayer times are computed like [PrayTimes.org](http://praytimes.org/calculation) does: dhuhr
+//! is at solar noon, sunrise and maghrib are when the sun crosses the horizon, fajr and isha
+//! when it is a certain angle below it, and asr when shadows have grown by the length of their
+//! object (twice that with the Hanafi method).
+
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Conventions for the angle of the sun below the horizon at fajr and isha
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// Muslim World League
+    #[default]
+    Mwl,
+    /// Islamic Society of North America
+    Isna,
+    /// Egyptian General Authority of Survey
+    Egypt,
+    /// Umm al-Qura University, Makkah
+    Makkah,
+    /// University of Islamic Sciences, Karachi
+    Karachi,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AsrMethod {
+    #[default]
+    Standard,
+    Hanafi,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PrayerTimesConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub method: Method,
+    #[serde(default)]
+    pub asr: AsrMethod,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EventConfig {
+    pub name: String,
+    /// Time of the day like "09:30"
+    pub time: String,
+}
+
+pub struct DailyEvents {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    warning: chrono::Duration,
+    prayer_times: Option<PrayerTimesConfig>,
+    events: Vec<(String, NaiveTime)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DailyEventsConfig {
+    /// Compute prayer times for this place
+    pub prayer_times: Option<PrayerTimesConfig>,
+
+    /// Events at fixed times of the day
+    pub events: Vec<EventConfig>,
+
+    /// Minutes before an event from which on the block is shown as warning
+    pub warning: i64,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for DailyEventsConfig {
+    fn default() -> Self {
+        Self {
+            prayer_times: None,
+            events: Vec::new(),
+            warning: 15,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for DailyEvents {
+    type Config = DailyEventsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.prayer_times.is_none() && block_config.events.is_empty() {
+            return Err(ConfigurationError(
+                "daily_events".to_string(),
+                "either prayer_times or events must be set".to_string(),
+            ));
+        }
+        let events = block_config
+            .events
+            .into_iter()
+            .map(|event| {
+                NaiveTime::parse_from_str(&event.time, "%H:%M")
+                    .configuration_error(&format!("'{}' is not a time like \"19:30\"", event.time))
+                    .map(|time| (event.name, time))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DailyEvents {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{name} {countdown}")?,
+            warning: chrono::Duration::minutes(block_config.warning),
+            prayer_times: block_config.prayer_times,
+            events,
+        })
+    }
+}
+
+fn sin(degrees: f64) -> f64 {
+    (degrees * PI / 180.).sin()
+}
+
+fn cos(degrees: f64) -> f64 {
+    (degrees * PI / 180.).cos()
+}
+
+fn tan(degrees: f64) -> f64 {
+    (degrees * PI / 180.).tan()
+}
+
+fn arcsin(x: f64) -> f64 {
+    x.asin() * 180. / PI
+}
+
+fn arccos(x: f64) -> f64 {
+    x.acos() * 180. / PI
+}
+
+fn arccot(x: f64) -> f64 {
+    (1. / x).atan() * 180. / PI
+}
+
+fn arctan2(y: f64, x: f64) -> f64 {
+    y.atan2(x) * 180. / PI
+}
+
+fn julian_day(date: NaiveDate) -> f64 {
+    let (mut year, mut month) = (date.year() as f64, date.month() as f64);
+    if month <= 2. {
+        year -= 1.;
+        month += 12.;
+    }
+    let a = (year / 100.).floor();
+    let b = 2. - a + (a / 4.).floor();
+    (365.25 * (year + 4716.)).floor() + (30.6001 * (month + 1.)).floor() + date.day() as f64 + b
+        - 1524.5
+}
+
+/// Declination of the sun and equation of time (in hours) at the julian day `jd`
+fn sun_position(jd: f64) -> (f64, f64) {
+    let d = jd - 2451545.;
+    let g = 357.529 + 0.98560028 * d;
+    let q = 280.459 + 0.98564736 * d;
+    let l = q + 1.915 * sin(g) + 0.020 * sin(2. * g);
+    let e = 23.439 - 0.00000036 * d;
+    let ra = arctan2(cos(e) * sin(l), cos(l)) / 15.;
+    let equation_of_time = q / 15. - ra.rem_euclid(24.);
+    let declination = arcsin(sin(e) * sin(l));
+    (declination, (equation_of_time + 12.).rem_euclid(24.) - 12.)
+}
+
+/// Prayer times of `date` in UTC. Times that don't occur, such as isha during the white nights of
+/// high latitudes, are left out.
+fn prayer_times(config: &PrayerTimesConfig, date: NaiveDate) -> Vec<(&'static str, DateTime<Utc>)> {
+    let jd = julian_day(date) - config.longitude / (15. * 24.);
+    let latitude = config.latitude;
+
+    // Hours of local solar time, refined for the time of day by computing the sun's position then
+    let noon = |hour: f64| 12. - sun_position(jd + hour / 24.).1;
+    let sun_angle_time = |angle: f64, hour: f64, before_noon: bool| {
+        let (declination, _) = sun_position(jd + hour / 24.);
+        let t = arccos(
+            (-sin(angle) - sin(declination) * sin(latitude)) / (cos(declination) * cos(latitude)),
+        ) / 15.;
+        noon(hour) + if before_noon { -t } else { t }
+    };
+    let asr_time = |factor: f64, hour: f64| {
+        let (declination, _) = sun_position(jd + hour / 24.);
+        let angle = -arccot(factor + tan((latitude - declination).abs()));
+        sun_angle_time(angle, hour, false)
+    };
+
+    let fajr_angle = match config.method {
+        Method::Mwl | Method::Karachi => 18.,
+        Method::Isna => 15.,
+        Method::Egypt => 19.5,
+        Method::Makkah => 18.5,
+    };
+    let maghrib = sun_angle_time(0.833, 18., false);
+    let isha = match config.method {
+        Method::Mwl => sun_angle_time(17., 18., false),
+        Method::Isna => sun_angle_time(15., 18., false),
+        Method::Egypt => sun_angle_time(17.5, 18., false),
+        Method::Karachi => sun_angle_time(18., 18., false),
+        Method::Makkah => maghrib + 1.5,
+    };
+    let asr_factor = match config.asr {
+        AsrMethod::Standard => 1.,
+        AsrMethod::Hanafi => 2.,
+    };
+
+    let midnight = Utc.from_utc_date(&date).and_hms(0, 0, 0);
+    vec![
+        ("Fajr", sun_angle_time(fajr_angle, 5., true)),
+        ("Sunrise", sun_angle_time(0.833, 6., true)),
+        ("Dhuhr", noon(12.)),
+        ("Asr", asr_time(asr_factor, 13.)),
+        ("Maghrib", maghrib),
+        ("Isha", isha),
+    ]
+    .into_iter()
+    .filter(|(_, hours)| hours.is_finite())
+    .map(|(name, hours)| {
+        let seconds = ((hours - config.longitude / 15.) * 3600.).round() as i64;
+        (name, midnight + chrono::Duration::seconds(seconds))
+    })
+    .collect()
+}
+
+impl DailyEvents {
+    /// The events of `date` in local time
+    fn events_on(&self, date: NaiveDate) -> Vec<(String, DateTime<Local>)> {
+        let mut events: Vec<(String, DateTime<Local>)> = self
+            .events
+            .iter()
+            .filter_map(|(name, time)| {
+                Local
+                    .from_local_datetime(&date.and_time(*time))
+                    .earliest()
+                    .map(|time| (name.clone(), time))
+            })
+            .collect();
+        if let Some(config) = &self.prayer_times {
+            events.extend(
+                prayer_times(config, date)
+                    .into_iter()
+                    .map(|(name, time)| (name.to_string(), time.with_timezone(&Local))),
+            );
+        }
+        events
+    }
+}
+
+impl Block for DailyEvents {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Local::now();
+        let today = now.date().naive_local();
+        let (name, time) = self
+            .events_on(today)
+            .into_iter()
+            .chain(self.events_on(today.succ()))
+            .filter(|(_, time)| *time > now)
+            .min_by_key(|(_, time)| *time)
+            .block_error("daily_events", "no event in the next two days")?;
+
+        let remaining = (time - now).num_minutes() + 1;
+        let values = map!(
+            "name" => Value::from_string(name),
+            "time" => Value::from_string(time.format("%H:%M").to_string()),
+            "countdown" => Value::from_string(format!("{}:{:02}", remaining / 60, remaining % 60)),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if time - now <= self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(Duration::from_secs(30).into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    #[test]
+    fn test_prayer_times() {
+        // London on the summer solstice: sunrise 3:43, solar noon 12:02, sunset 20:21 UTC
+        let config = PrayerTimesConfig {
+            latitude: 51.5,
+            longitude: -0.13,
+            method: Method::Mwl,
+            asr: AsrMethod::Standard,
+        };
+        let times = prayer_times(&config, NaiveDate::from_ymd(2021, 6, 21));
+        let minutes = |name| {
+            let (_, time) = times.iter().find(|(n, _)| *n == name).unwrap();
+            time.hour() as i64 * 60 + time.minute() as i64
+        };
+        assert!((minutes("Sunrise") - (3 * 60 + 43)).abs() <= 3);
+        assert!((minutes("Dhuhr") - (12 * 60 + 2)).abs() <= 3);
+        assert!((minutes("Maghrib") - (20 * 60 + 21)).abs() <= 3);
+        // The sun doesn't get 18° below the horizon in London in June
+        assert!(times.iter().all(|(name, _)| *name != "Fajr"));
+        assert!(minutes("Dhuhr") < minutes("Asr") && minutes("Asr") < minutes("Maghrib"));
+    }
+}
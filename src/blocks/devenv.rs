@@ -0,0 +1,295 @@
+//! Shows whether the development environments of projects are up: the Vagrant machines of
+//! projects with a `Vagrantfile`, or the containers that the devcontainer CLI and VS Code start
+//! for projects with a `devcontainer.json`. A click brings an environment up or down in a
+//! terminal.
+
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::{spawn_child_async, CommandRunner};
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Directory of the project
+    pub path: String,
+
+    /// Defaults to the name of the directory
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Vagrant,
+    Devcontainer,
+}
+
+struct Project {
+    path: String,
+    name: String,
+    kind: Kind,
+    running: bool,
+}
+
+pub struct Devenv {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    projects: Vec<Project>,
+    format: FormatTemplate,
+    terminal_command: String,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DevenvConfig {
+    pub projects: Vec<ProjectConfig>,
+
+    /// Command that runs the rest of the command line in a terminal
+    pub terminal_command: String,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for DevenvConfig {
+    fn default() -> Self {
+        Self {
+            projects: Vec::new(),
+            terminal_command: "xterm -e".to_string(),
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Devenv {
+    type Config = DevenvConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.projects.is_empty() {
+            return Err(ConfigurationError(
+                "devenv".to_string(),
+                "projects must be set".to_string(),
+            ));
+        }
+        let projects = block_config
+            .projects
+            .into_iter()
+            .map(|project| {
+                let path = shellexpand::tilde(&project.path).to_string();
+                let dir = Path::new(&path);
+                let kind = if dir.join("Vagrantfile").exists() {
+                    Kind::Vagrant
+                } else if dir.join(".devcontainer/devcontainer.json").exists()
+                    || dir.join(".devcontainer.json").exists()
+                {
+                    Kind::Devcontainer
+                } else {
+                    return Err(ConfigurationError(
+                        "devenv".to_string(),
+                        format!("{} has neither a Vagrantfile nor a devcontainer.json", path),
+                    ));
+                };
+                let name = project.name.unwrap_or_else(|| {
+                    dir.file_name()
+                        .map_or_else(|| path.clone(), |name| name.to_string_lossy().into())
+                });
+                Ok(Project {
+                    path,
+                    name,
+                    kind,
+                    running: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let widgets = (0..projects.len())
+            .map(|instance| TextWidget::new(id, instance, shared_config.clone()))
+            .collect();
+
+        Ok(Devenv {
+            id,
+            widgets,
+            projects,
+            format: block_config.format.with_default("{name} {status}")?,
+            terminal_command: block_config.terminal_command,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+/// Quotes a string for the shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The states of the machines in the output of `vagrant status --machine-readable`, whose lines
+/// look like "1634567890,default,state,running"
+fn vagrant_states(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            match fields[..] {
+                [_, _, "state", state] => Some(state),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+impl Project {
+    fn devcontainer_ps(&self) -> String {
+        format!(
+            "docker ps -q --filter {}",
+            quote(&format!("label=devcontainer.local_folder={}", self.path))
+        )
+    }
+
+    /// Returns whether the environment is running and a description of its status
+    fn status(&self, command_runner: &dyn CommandRunner) -> Result<(bool, String)> {
+        let command = match self.kind {
+            Kind::Vagrant => format!(
+                "cd {} && vagrant status --machine-readable",
+                quote(&self.path)
+            ),
+            Kind::Devcontainer => self.devcontainer_ps(),
+        };
+        let output = command_runner.run(&command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "devenv".to_string(),
+                format!("failed to get the status of {}", self.name),
+            ));
+        }
+
+        Ok(match self.kind {
+            Kind::Vagrant => {
+                let states = vagrant_states(&output.stdout);
+                let running = states.iter().filter(|state| **state == "running").count();
+                match running {
+                    0 => (false, states.first().unwrap_or(&"unknown").to_string()),
+                    _ if running == states.len() => (true, "running".to_string()),
+                    _ => (true, format!("{}/{} running", running, states.len())),
+                }
+            }
+            Kind::Devcontainer if output.stdout.trim().is_empty() => (false, "stopped".to_string()),
+            Kind::Devcontainer => (true, "running".to_string()),
+        })
+    }
+
+    /// The command that brings the environment up, or down if it is running
+    fn toggle_command(&self) -> String {
+        match (self.kind, self.running) {
+            (Kind::Vagrant, false) => format!("cd {} && vagrant up", quote(&self.path)),
+            (Kind::Vagrant, true) => format!("cd {} && vagrant halt", quote(&self.path)),
+            (Kind::Devcontainer, false) => {
+                format!("devcontainer up --workspace-folder {}", quote(&self.path))
+            }
+            (Kind::Devcontainer, true) => format!("docker stop $({})", self.devcontainer_ps()),
+        }
+    }
+}
+
+impl Block for Devenv {
+    fn update(&mut self) -> Result<Option<Update>> {
+        for (widget, project) in self.widgets.iter_mut().zip(&mut self.projects) {
+            let (running, status) = project.status(self.command_runner.as_ref())?;
+            project.running = running;
+            let values = map!(
+                "name" => Value::from_string(project.name.clone()),
+                "status" => Value::from_string(status),
+                "kind" => Value::from_string(match project.kind {
+                    Kind::Vagrant => "vagrant".to_string(),
+                    Kind::Devcontainer => "devcontainer".to_string(),
+                }),
+            );
+            widget.set_texts(self.format.render(&values)?);
+            widget.set_state(if running { State::Good } else { State::Idle });
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let Some(project) = event
+            .instance
+            .and_then(|instance| self.projects.get(instance))
+        {
+            // Keep the terminal open so that the output can be read
+            let script = format!("{}; echo; read -r _", project.toggle_command());
+            let command = format!("{} sh -c {}", self.terminal_command, quote(&script));
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("devenv", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_vagrant_status() {
+        let runner = MockRunner::default();
+        runner.set(
+            "cd '/src/it'\\''s' && vagrant status --machine-readable",
+            "1634567890,web,metadata,provider,virtualbox\n\
+             1634567890,web,state,running\n\
+             1634567890,web,state-human-short,running\n\
+             1634567890,db,state,poweroff\n",
+        );
+        let mut project = Project {
+            path: "/src/it's".to_string(),
+            name: "it's".to_string(),
+            kind: Kind::Vagrant,
+            running: false,
+        };
+        assert_eq!(
+            project.status(&runner).unwrap(),
+            (true, "1/2 running".to_string())
+        );
+        project.running = true;
+        assert_eq!(
+            project.toggle_command(),
+            r"cd '/src/it'\''s' && vagrant halt"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Shows how much space caches that are safe to clean up take, like the package cache of pacman or
+//! apt, ~/.cache, the journal and unused Docker images. A click runs a cleanup command.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::util::disk_usage;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    Pacman,
+    Apt,
+    Dnf,
+    Cache,
+    Journal,
+    Docker,
+}
+
+impl Location {
+    fn key(self) -> &'static str {
+        match self {
+            Location::Pacman => "pacman",
+            Location::Apt => "apt",
+            Location::Dnf => "dnf",
+            Location::Cache => "cache",
+            Location::Journal => "journal",
+            Location::Docker => "docker",
+        }
+    }
+}
+
+pub struct DiskCleanup {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    locations: Vec<Location>,
+    paths: Vec<PathBuf>,
+    warning: Option<u64>,
+    cleanup_command: Option<String>,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DiskCleanupConfig {
+    /// Known caches to measure
+    pub locations: Vec<Location>,
+
+    /// More directories to measure
+    pub paths: Vec<String>,
+
+    /// Reclaimable space in MB from which on the block is shown as warning
+    pub warning: Option<u64>,
+
+    /// Shell command that cleans up, run on click
+    pub cleanup_command: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for DiskCleanupConfig {
+    fn default() -> Self {
+        Self {
+            locations: vec![
+                Location::Pacman,
+                Location::Apt,
+                Location::Dnf,
+                Location::Cache,
+                Location::Journal,
+                Location::Docker,
+            ],
+            paths: Vec::new(),
+            warning: None,
+            cleanup_command: None,
+            interval: Duration::from_secs(3600),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for DiskCleanup {
+    type Config = DiskCleanupConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(DiskCleanup {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("disk_drive")?,
+            format: block_config.format.with_default("{total}")?,
+            locations: block_config.locations,
+            paths: block_config
+                .paths
+                .iter()
+                .map(|path| PathBuf::from(shellexpand::tilde(path).to_string()))
+                .collect(),
+            warning: block_config.warning.map(|warning| warning * 1024 * 1024),
+            cleanup_command: block_config.cleanup_command,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+/// A size as Docker prints it, like "1.234GB" or "512kB"
+fn parse_docker_size(size: &str) -> Option<u64> {
+    let split = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = size.split_at(split);
+    let factor = match unit.to_ascii_uppercase().as_str() {
+        "B" => 1.0,
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+    Some((number.parse::<f64>().ok()? * factor) as u64)
+}
+
+/// The space that removing unused images and the build cache frees, from the output of
+/// `docker system df --format '{{.Type}}\t{{.Reclaimable}}'`, with lines like
+/// "Images\t1.234GB (52%)"
+fn parse_docker_df(output: &str) -> u64 {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(kind, _)| *kind == "Images" || *kind == "Build Cache")
+        .filter_map(|(_, reclaimable)| parse_docker_size(reclaimable.split(' ').next()?))
+        .sum()
+}
+
+impl DiskCleanup {
+    fn measure(&self, location: Location) -> Result<u64> {
+        let dir = match location {
+            Location::Pacman => PathBuf::from("/var/cache/pacman/pkg"),
+            Location::Apt => PathBuf::from("/var/cache/apt/archives"),
+            Location::Dnf => PathBuf::from("/var/cache/dnf"),
+            Location::Cache => PathBuf::from(std::env::var("XDG_CACHE_HOME").unwrap_or(format!(
+                "{}/.cache",
+                std::env::var("HOME").unwrap_or_default()
+            ))),
+            Location::Journal => PathBuf::from("/var/log/journal"),
+            Location::Docker => {
+                let output = self
+                    .command_runner
+                    .run("docker system df --format '{{.Type}}\t{{.Reclaimable}}'")?;
+                // Without Docker or access to it there is nothing to clean up
+                return Ok(if output.status == Some(0) {
+                    parse_docker_df(&output.stdout)
+                } else {
+                    0
+                });
+            }
+        };
+        // Directories that don't exist, e.g. of other package managers, take no space
+        Ok(disk_usage(&dir))
+    }
+}
+
+impl Block for DiskCleanup {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut values = HashMap::new();
+        let mut total = 0;
+        for location in self.locations.clone() {
+            let size = self.measure(location)?;
+            total += size;
+            values.insert(location.key(), Value::from_float(size as f64).bytes());
+        }
+        total += self.paths.iter().map(|path| disk_usage(path)).sum::<u64>();
+        values.insert("total", Value::from_float(total as f64).bytes());
+
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.warning {
+            Some(warning) if total >= warning => State::Warning,
+            _ => State::Idle,
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let Some(command) = self.cleanup_command.clone() {
+            let id = self.id;
+            let tx_update_request = self.tx_update_request.clone();
+            // Measure again once the cleanup is done
+            thread::Builder::new()
+                .name("disk_cleanup".into())
+                .spawn(move || {
+                    let _ = Command::new("sh").args(["-c", &command]).status();
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                })
+                .internal_error("disk_cleanup", "failed to start a thread")?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_docker_df() {
+        let output = "Images\t1.234GB (52%)\n\
+                      Containers\t12kB (100%)\n\
+                      Local Volumes\t3.5GB (80%)\n\
+                      Build Cache\t512MB\n";
+        assert_eq!(parse_docker_df(output), 1_746_000_000);
+    }
+}
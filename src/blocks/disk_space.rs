@@ -12,6 +12,8 @@ use crate::errors::*;
 use crate::formatting::FormatTemplate;
 use crate::formatting::{prefix::Prefix, value::Value};
 use crate::scheduler::Task;
+use crate::util::Hysteresis;
+use crate::widgets::progress::{ProgressConfig, ProgressWidget};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -23,17 +25,40 @@ pub enum InfoType {
     Used,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PathConfig {
+    pub path: String,
+
+    /// Warning level override for this path
+    pub warning: Option<f64>,
+
+    /// Alert level override for this path
+    pub alert: Option<f64>,
+
+    /// Format override for this path
+    #[serde(default)]
+    pub format: Option<FormatTemplate>,
+}
+
+struct Mount {
+    path: String,
+    warning: f64,
+    alert: f64,
+    hysteresis: Hysteresis,
+    format: FormatTemplate,
+}
+
 pub struct DiskSpace {
     id: usize,
     disk_space: TextWidget,
+    progress: Option<ProgressWidget>,
     update_interval: Duration,
-    path: String,
+    mounts: Vec<Mount>,
+    separator: String,
     unit: Prefix,
     info_type: InfoType,
-    warning: f64,
-    alert: f64,
     alert_absolute: bool,
-    format: FormatTemplate,
     icon: String,
 
     // DEPRECATED
@@ -47,6 +72,12 @@ pub struct DiskSpaceConfig {
     /// Path to collect information from
     pub path: String,
 
+    /// Several paths to show in the block instead of `path`
+    pub paths: Vec<PathConfig>,
+
+    /// Put between the texts of the paths
+    pub separator: String,
+
     /// Currently supported options are available, free, total and used
     /// Sets value used for {percentage} calculation
     /// total is the same as used, use format to set format string for output
@@ -71,6 +102,12 @@ pub struct DiskSpaceConfig {
     /// use absolute (unit) values for disk space alerts
     pub alert_absolute: bool,
 
+    /// Margin by which the value has to move past a threshold before the state is lowered
+    pub hysteresis: f64,
+
+    /// Show the percentage as a progress bar
+    pub progress: Option<ProgressConfig>,
+
     /// Alias that is displayed for path
     // DEPRECATED
     // TODO remove
@@ -81,6 +118,8 @@ impl Default for DiskSpaceConfig {
     fn default() -> Self {
         Self {
             path: "/".to_string(),
+            paths: Vec::new(),
+            separator: " ".to_string(),
             info_type: InfoType::Available,
             format: FormatTemplate::default(),
             unit: "GB".to_string(),
@@ -88,6 +127,8 @@ impl Default for DiskSpaceConfig {
             warning: 20.,
             alert: 10.,
             alert_absolute: false,
+            hysteresis: 0.,
+            progress: None,
             alias: "/".to_string(),
         }
     }
@@ -99,7 +140,7 @@ enum AlertType {
 }
 
 impl DiskSpace {
-    fn compute_state(&self, value: f64, warning: f64, alert: f64, alert_type: AlertType) -> State {
+    fn compute_state(value: f64, warning: f64, alert: f64, alert_type: &AlertType) -> State {
         match alert_type {
             AlertType::Above => {
                 if value > alert {
@@ -133,13 +174,42 @@ impl ConfigBlock for DiskSpace {
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let icon = shared_config.get_icon("disk_drive")?;
+        let format = block_config.format.with_default("{available}")?;
+        let paths = if block_config.paths.is_empty() {
+            vec![PathConfig {
+                path: block_config.path,
+                warning: None,
+                alert: None,
+                format: None,
+            }]
+        } else {
+            block_config.paths
+        };
+        let (warning, alert, hysteresis) = (
+            block_config.warning,
+            block_config.alert,
+            block_config.hysteresis,
+        );
+        let mounts = paths
+            .into_iter()
+            .map(|path| Mount {
+                path: path.path,
+                warning: path.warning.unwrap_or(warning),
+                alert: path.alert.unwrap_or(alert),
+                hysteresis: Hysteresis::new(hysteresis),
+                format: path.format.unwrap_or_else(|| format.clone()),
+            })
+            .collect();
 
         Ok(DiskSpace {
             id,
             update_interval: block_config.interval,
+            progress: block_config
+                .progress
+                .map(|progress| ProgressWidget::new(id, 1, shared_config.clone(), progress)),
             disk_space: TextWidget::new(id, 0, shared_config),
-            path: block_config.path,
-            format: block_config.format.with_default("{available}")?,
+            mounts,
+            separator: block_config.separator,
             info_type: block_config.info_type,
             unit: match block_config.unit.as_str() {
                 "TB" => Prefix::Tera,
@@ -154,8 +224,6 @@ impl ConfigBlock for DiskSpace {
                     ))
                 }
             },
-            warning: block_config.warning,
-            alert: block_config.alert,
             alert_absolute: block_config.alert_absolute,
             icon: icon.trim().to_string(),
             alias: block_config.alias,
@@ -165,69 +233,99 @@ impl ConfigBlock for DiskSpace {
 
 impl Block for DiskSpace {
     fn update(&mut self) -> Result<Option<Update>> {
-        let statvfs = statvfs(Path::new(self.path.as_str()))
-            .block_error("disk_space", "failed to retrieve statvfs")?;
-
-        let total = (statvfs.blocks() as u64) * (statvfs.fragment_size() as u64);
-        let used = ((statvfs.blocks() as u64) - (statvfs.blocks_free() as u64))
-            * (statvfs.fragment_size() as u64);
-        let available = (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64);
-        let free = (statvfs.blocks_free() as u64) * (statvfs.block_size() as u64);
-
-        let result;
-        let alert_type;
-        match self.info_type {
-            InfoType::Available => {
-                result = available as f64;
-                alert_type = AlertType::Below;
-            }
-            InfoType::Free => {
-                result = free as f64;
-                alert_type = AlertType::Below;
+        let mut texts = Vec::with_capacity(self.mounts.len());
+        let mut short_texts = Vec::with_capacity(self.mounts.len());
+        // The most alarming state of the paths, and the percentage of that path
+        let mut worst: Option<(State, f64)> = None;
+        for mount in &mut self.mounts {
+            let statvfs = statvfs(Path::new(mount.path.as_str())).block_error(
+                "disk_space",
+                &format!("failed to retrieve statvfs of {}", mount.path),
+            )?;
+
+            let total = (statvfs.blocks() as u64) * (statvfs.fragment_size() as u64);
+            let used = ((statvfs.blocks() as u64) - (statvfs.blocks_free() as u64))
+                * (statvfs.fragment_size() as u64);
+            let available = (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64);
+            let free = (statvfs.blocks_free() as u64) * (statvfs.block_size() as u64);
+
+            let result;
+            let alert_type;
+            match self.info_type {
+                InfoType::Available => {
+                    result = available as f64;
+                    alert_type = AlertType::Below;
+                }
+                InfoType::Free => {
+                    result = free as f64;
+                    alert_type = AlertType::Below;
+                }
+                InfoType::Used => {
+                    result = used as f64;
+                    alert_type = AlertType::Above;
+                }
             }
-            InfoType::Used => {
-                result = used as f64;
-                alert_type = AlertType::Above;
+
+            let percentage = result / (total as f64) * 100.;
+            let values = map!(
+                "percentage" => Value::from_float(percentage).percents(),
+                "path" => Value::from_string(mount.path.clone()),
+                "total" => Value::from_float(total as f64).bytes(),
+                "used" => Value::from_float(used as f64).bytes(),
+                "available" => Value::from_float(available as f64).bytes(),
+                "free" => Value::from_float(free as f64).bytes(),
+                "icon" => Value::from_string(self.icon.to_string()),
+                //TODO remove
+                "alias" => Value::from_string(self.alias.clone()),
+            );
+            let (text, short_text) = mount.format.render(&values)?;
+            short_texts.push(short_text.clone().unwrap_or_else(|| text.clone()));
+            texts.push(text);
+
+            // Send percentage to alert check if we don't want absolute alerts
+            let alert_val = if self.alert_absolute {
+                result
+                    / match self.unit {
+                        Prefix::Tera => 1u64 << 40,
+                        Prefix::Giga => 1u64 << 30,
+                        Prefix::Mega => 1u64 << 20,
+                        Prefix::Kilo => 1u64 << 10,
+                        Prefix::One => 1u64,
+                        _ => unreachable!(),
+                    } as f64
+            } else {
+                percentage
+            };
+
+            let (warning, alert) = (mount.warning, mount.alert);
+            let state = mount.hysteresis.apply(alert_val, |value| {
+                Self::compute_state(value, warning, alert, &alert_type)
+            });
+            match worst {
+                Some((worst, _)) if worst.severity() >= state.severity() => {}
+                _ => worst = Some((state, percentage)),
             }
         }
 
-        let percentage = result / (total as f64) * 100.;
-        let values = map!(
-            "percentage" => Value::from_float(percentage).percents(),
-            "path" => Value::from_string(self.path.clone()),
-            "total" => Value::from_float(total as f64).bytes(),
-            "used" => Value::from_float(used as f64).bytes(),
-            "available" => Value::from_float(available as f64).bytes(),
-            "free" => Value::from_float(free as f64).bytes(),
-            "icon" => Value::from_string(self.icon.to_string()),
-            //TODO remove
-            "alias" => Value::from_string(self.alias.clone()),
-        );
-        self.disk_space.set_texts(self.format.render(&values)?);
-
-        // Send percentage to alert check if we don't want absolute alerts
-        let alert_val = if self.alert_absolute {
-            result
-                / match self.unit {
-                    Prefix::Tera => 1u64 << 40,
-                    Prefix::Giga => 1u64 << 30,
-                    Prefix::Mega => 1u64 << 20,
-                    Prefix::Kilo => 1u64 << 10,
-                    Prefix::One => 1u64,
-                    _ => unreachable!(),
-                } as f64
-        } else {
-            percentage
-        };
-
-        let state = self.compute_state(alert_val, self.warning, self.alert, alert_type);
-        self.disk_space.set_state(state);
+        let short_text = short_texts.join(&self.separator);
+        let text = texts.join(&self.separator);
+        let short_text = (short_text != text).then_some(short_text);
+        self.disk_space.set_texts((text, short_text));
+        if let Some((state, percentage)) = worst {
+            self.disk_space.set_state(state);
+            if let Some(progress) = &mut self.progress {
+                progress.set_value(percentage, state);
+            }
+        }
 
         Ok(Some(self.update_interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.disk_space]
+        match &self.progress {
+            Some(progress) => vec![&self.disk_space, progress],
+            None => vec![&self.disk_space],
+        }
     }
 
     fn id(&self) -> usize {
@@ -1,7 +1,10 @@
-use std::time::Duration;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
@@ -10,6 +13,7 @@ use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
@@ -21,6 +25,10 @@ pub struct Docker {
     format: FormatTemplate,
     update_interval: Duration,
     socket_path: String,
+    containers: Vec<String>,
+    /// Watched containers that aren't running or are unhealthy
+    down: Vec<String>,
+    tx_update_request: Sender<Task>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -53,6 +61,9 @@ pub struct DockerConfig {
 
     /// Absolute path to docker socket
     pub socket_path: String,
+
+    /// Names of containers that have to be running and healthy, restarted on click when not
+    pub containers: Vec<String>,
 }
 
 impl Default for DockerConfig {
@@ -61,6 +72,7 @@ impl Default for DockerConfig {
             interval: Duration::from_secs(5),
             format: FormatTemplate::default(),
             socket_path: "/var/run/docker.sock".to_string(),
+            containers: Vec::new(),
         }
     }
 }
@@ -72,7 +84,7 @@ impl ConfigBlock for Docker {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let text = TextWidget::new(id, 0, shared_config)
             .with_text("N/A")
@@ -92,10 +104,55 @@ impl ConfigBlock for Docker {
             format: block_config.format.with_default("{running}")?,
             update_interval: block_config.interval,
             socket_path: path_expanded.to_string(),
+            containers: block_config.containers,
+            down: Vec::new(),
+            tx_update_request,
         })
     }
 }
 
+/// The health of a container if it has a health check, or else its state, and whether it's up,
+/// from the inspection of the container by the API
+fn container_status(inspection: &JsonValue) -> (String, bool) {
+    let state = &inspection["State"];
+    match (state["Status"].as_str(), state["Health"]["Status"].as_str()) {
+        (Some("running"), Some(health)) => (health.to_string(), health != "unhealthy"),
+        (Some(status), _) => (status.to_string(), status == "running"),
+        // The API answers with a message when there is no such container
+        (None, _) => ("missing".to_string(), false),
+    }
+}
+
+impl Docker {
+    /// The status of every watched container, like "web:healthy db:exited", or "error" for the
+    /// ones that couldn't be inspected
+    fn watch(&mut self) -> String {
+        let mut statuses = Vec::new();
+        self.down.clear();
+        for name in &self.containers {
+            let inspection = http::http_get_socket_json(
+                PathBuf::from(&self.socket_path),
+                &format!(
+                    "http://localhost/containers/{}/json",
+                    http::url_encode(name)
+                ),
+            );
+            let (status, up) = match inspection {
+                Ok(inspection) => container_status(&inspection.content),
+                Err(error) => {
+                    log::warn!("failed to inspect {}: {}", name, error);
+                    ("error".to_string(), false)
+                }
+            };
+            if !up {
+                self.down.push(name.clone());
+            }
+            statuses.push(format!("{}:{}", name, status));
+        }
+        statuses.join(" ")
+    }
+}
+
 impl Block for Docker {
     fn update(&mut self) -> Result<Option<Update>> {
         let socket_path = std::path::PathBuf::from(self.socket_path.as_str());
@@ -117,7 +174,10 @@ impl Block for Docker {
             }
         };
 
+        let health = self.watch();
         let values = map!(
+            "health" =>  Value::from_string(health),
+            "down" =>    Value::from_integer(self.down.len() as i64),
             "total" =>   Value::from_integer(status.total),
             "running" => Value::from_integer(status.running),
             "paused" =>  Value::from_integer(status.paused),
@@ -126,11 +186,47 @@ impl Block for Docker {
         );
 
         self.text.set_texts(self.format.render(&values)?);
-        self.text.set_state(State::Idle);
+        self.text.set_state(if self.down.is_empty() {
+            State::Idle
+        } else {
+            State::Critical
+        });
 
         Ok(Some(self.update_interval.into()))
     }
 
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left || self.down.is_empty() {
+            return Ok(());
+        }
+        let socket_path = PathBuf::from(&self.socket_path);
+        let down = self.down.clone();
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        // Restarting waits for the containers to stop, which can take a while
+        thread::Builder::new()
+            .name("docker".into())
+            .spawn(move || {
+                for name in down {
+                    let url = format!(
+                        "http://localhost/containers/{}/restart",
+                        http::url_encode(&name)
+                    );
+                    match http::http_post_socket(socket_path.clone(), &url) {
+                        Ok(204) => (),
+                        Ok(code) => log::warn!("failed to restart {}: HTTP {}", name, code),
+                        Err(error) => log::warn!("failed to restart {}: {}", name, error),
+                    }
+                }
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("docker", "failed to start a thread")?;
+        Ok(())
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.text]
     }
@@ -139,3 +235,19 @@ impl Block for Docker {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_status() {
+        let healthy =
+            serde_json::json!({"State": {"Status": "running", "Health": {"Status": "healthy"}}});
+        let exited = serde_json::json!({"State": {"Status": "exited", "ExitCode": 137}});
+        let missing = serde_json::json!({"message": "No such container: db"});
+        assert_eq!(container_status(&healthy), ("healthy".to_string(), true));
+        assert_eq!(container_status(&exited), ("exited".to_string(), false));
+        assert_eq!(container_status(&missing), ("missing".to_string(), false));
+    }
+}
@@ -0,0 +1,275 @@
+//! Shows how many days are left until the nearest expiry of domain registrations, as registries
+//! publish them over RDAP or, for registries without RDAP, WHOIS
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long to wait after a failed lookup at most, as lookups fail e.g. before the network is up
+const RETRY_INTERVAL: Duration = Duration::from_secs(3600);
+
+pub struct DomainExpiry {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    domains: Vec<String>,
+    /// The base URLs of the RDAP servers of TLDs, by TLD
+    #[cfg(feature = "http")]
+    rdap_servers: Option<std::collections::HashMap<String, String>>,
+    warning: i64,
+    critical: i64,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DomainExpiryConfig {
+    pub domains: Vec<String>,
+
+    /// Days before an expiry from which on the block is shown as warning
+    pub warning: i64,
+
+    /// Days before an expiry from which on the block is shown as critical
+    pub critical: i64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for DomainExpiryConfig {
+    fn default() -> Self {
+        Self {
+            domains: Vec::new(),
+            warning: 30,
+            critical: 7,
+            interval: Duration::from_secs(86400),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for DomainExpiry {
+    type Config = DomainExpiryConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.domains.is_empty() {
+            return Err(ConfigurationError(
+                "domain_expiry".to_string(),
+                "domains must be set".to_string(),
+            ));
+        }
+
+        Ok(DomainExpiry {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("calendar")?,
+            format: block_config.format.with_default("{domain} {days}d")?,
+            domains: block_config.domains,
+            #[cfg(feature = "http")]
+            rdap_servers: None,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+/// The expiry date in the answer of a WHOIS server. Every registry names and formats it
+/// differently, e.g. "Registry Expiry Date: 2025-08-13T04:00:00Z" or "Expiry date:  03-Jan-2026".
+fn parse_whois(output: &str) -> Option<NaiveDate> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let key = key.trim().to_lowercase();
+        if !(key.contains("expir") || key == "paid-till" || key == "renewal date") {
+            return None;
+        }
+        let value = value.trim();
+        let date = value
+            .split(|c: char| c == 'T' || c.is_whitespace())
+            .next()?;
+        ["%Y-%m-%d", "%Y.%m.%d", "%d-%b-%Y", "%d.%m.%Y", "%Y/%m/%d"]
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+    })
+}
+
+#[cfg(feature = "http")]
+mod rdap {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use chrono::{DateTime, NaiveDate};
+    use serde_json::Value as JsonValue;
+
+    use crate::errors::*;
+    use crate::http;
+
+    /// The base URLs of the RDAP servers of TLDs, from IANA's bootstrap file
+    pub(super) fn servers() -> Result<HashMap<String, String>> {
+        let response = http::http_get_json(
+            "https://data.iana.org/rdap/dns.json",
+            Some(Duration::from_secs(10)),
+            vec![],
+        )?;
+        let mut servers = HashMap::new();
+        for service in response.content["services"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let url = match service[1][0].as_str() {
+                Some(url) => url.trim_end_matches('/'),
+                None => continue,
+            };
+            for tld in service[0].as_array().into_iter().flatten() {
+                if let Some(tld) = tld.as_str() {
+                    servers.insert(tld.to_lowercase(), url.to_string());
+                }
+            }
+        }
+        Ok(servers)
+    }
+
+    pub(super) fn expiry(server: &str, domain: &str) -> Result<Option<NaiveDate>> {
+        let url = format!("{}/domain/{}", server, domain);
+        let response = http::http_get_json(&url, Some(Duration::from_secs(10)), vec![])?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "domain_expiry".to_string(),
+                format!("could not look up {} (HTTP {})", domain, response.code),
+            ));
+        }
+        Ok(parse_expiry(&response.content))
+    }
+
+    fn parse_expiry(domain: &JsonValue) -> Option<NaiveDate> {
+        domain["events"]
+            .as_array()?
+            .iter()
+            .find(|event| event["eventAction"] == "expiration")
+            .and_then(|event| event["eventDate"].as_str())
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|date| date.naive_utc().date())
+    }
+}
+
+impl DomainExpiry {
+    fn expiry(&mut self, domain: &str) -> Result<NaiveDate> {
+        #[cfg(feature = "http")]
+        {
+            if self.rdap_servers.is_none() {
+                self.rdap_servers = Some(rdap::servers()?);
+            }
+            let tld = domain.rsplit('.').next().unwrap_or(domain).to_lowercase();
+            let server = self.rdap_servers.as_ref().and_then(|s| s.get(&tld));
+            // Some registries don't publish the expiry over RDAP, but still do over WHOIS
+            if let Some(date) = server
+                .map(|s| rdap::expiry(s, domain))
+                .transpose()?
+                .flatten()
+            {
+                return Ok(date);
+            }
+        }
+
+        let output = self.command_runner.run(&format!("whois '{}'", domain))?;
+        parse_whois(&output.stdout).block_error(
+            "domain_expiry",
+            &format!("could not find when {} expires", domain),
+        )
+    }
+}
+
+impl Block for DomainExpiry {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut expiries = Vec::with_capacity(self.domains.len());
+        for domain in self.domains.clone() {
+            match self.expiry(&domain) {
+                Ok(date) => expiries.push((domain, date)),
+                Err(error) => {
+                    match error {
+                        BlockError(block, _) | InternalError(block, _, _) if block == "curl" => {
+                            self.text.set_text("×".to_string());
+                            self.text.set_state(State::Warning);
+                        }
+                        error => {
+                            self.text.set_text(format!("domain_expiry error {}", error));
+                            self.text.set_state(State::Critical);
+                        }
+                    }
+                    return Ok(Some(self.interval.min(RETRY_INTERVAL).into()));
+                }
+            }
+        }
+        let nearest = expiries.into_iter().min_by_key(|(_, date)| *date);
+        let (domain, date) = nearest.internal_error("domain_expiry", "no domains")?;
+        let days = (date - Local::today().naive_local()).num_days();
+
+        let values = map!(
+            "domain" => Value::from_string(domain),
+            "days" => Value::from_integer(days),
+            "date" => Value::from_string(date.format("%Y-%m-%d").to_string()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if days <= self.critical {
+            State::Critical
+        } else if days <= self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_whois() {
+        let verisign =
+            "   Domain Name: EXAMPLE.COM\n   Registry Expiry Date: 2025-08-13T04:00:00Z\n";
+        let nominet = "    Registered on: 26-Aug-2001\n    Expiry date:  03-Jan-2026\n";
+        let tcinet = "domain:        EXAMPLE.RU\npaid-till:     2025.11.30\n";
+        assert_eq!(
+            parse_whois(verisign),
+            Some(NaiveDate::from_ymd(2025, 8, 13))
+        );
+        assert_eq!(parse_whois(nominet), Some(NaiveDate::from_ymd(2026, 1, 3)));
+        assert_eq!(parse_whois(tcinet), Some(NaiveDate::from_ymd(2025, 11, 30)));
+        assert_eq!(parse_whois("Domain not found.\n"), None);
+    }
+}
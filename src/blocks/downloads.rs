@@ -0,0 +1,283 @@
+//! Shows the most recent finished download in the XDG download directory for a short while, and how
+//! many downloads are still in progress
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
+use serde_derive::Deserialize;
+
+use crate::blocks::base_block::{follow_inotify, FileWatch};
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{escape_pango_text, xdg_config_home};
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Extensions of the files that browsers download into before renaming them: Firefox, Chromium,
+/// Epiphany and Safari
+const PARTIAL: &[&str] = &["part", "crdownload", "partial", "download"];
+
+/// How often the directory is read when it can't be watched
+const POLL: Duration = Duration::from_secs(5);
+
+pub struct Downloads {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_progress: FormatTemplate,
+    path: PathBuf,
+    show_for: Duration,
+    open_command: String,
+    /// The finished download that is shown
+    latest: Option<PathBuf>,
+    downloading: usize,
+    /// Set while the directory is watched, so that it doesn't need to be polled
+    watch: Option<FileWatch>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DownloadsConfig {
+    /// Directory to watch, by default the download directory of `xdg-user-dirs` or ~/Downloads
+    pub path: Option<String>,
+
+    /// Time in seconds for which a finished download is shown
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub show_for: Duration,
+
+    /// Command that opens the download or the directory on click
+    pub open_command: String,
+
+    /// Format override for a finished download
+    pub format: FormatTemplate,
+
+    /// Format override while downloads are in progress
+    pub format_progress: FormatTemplate,
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            show_for: Duration::from_secs(30),
+            open_command: "xdg-open".to_string(),
+            format: FormatTemplate::default(),
+            format_progress: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The download directory in the contents of `user-dirs.dirs`, which has lines like
+/// `XDG_DOWNLOAD_DIR="$HOME/Downloads"`
+fn parse_user_dirs(contents: &str, home: &str) -> Option<PathBuf> {
+    let line = contents
+        .lines()
+        .find(|line| line.trim_start().starts_with("XDG_DOWNLOAD_DIR="))?;
+    let dir = line.split_once('=')?.1.trim().trim_matches('"');
+    Some(PathBuf::from(dir.replace("$HOME", home)))
+}
+
+fn download_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_default();
+    fs::read_to_string(xdg_config_home().join("user-dirs.dirs"))
+        .ok()
+        .and_then(|contents| parse_user_dirs(&contents, &home))
+        .unwrap_or_else(|| PathBuf::from(home).join("Downloads"))
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| PARTIAL.iter().any(|partial| extension == *partial))
+}
+
+fn watch(id: usize, dir: &Path, tx_update_request: Sender<Task>) -> Option<FileWatch> {
+    let mut notify = match Inotify::init() {
+        Ok(notify) => notify,
+        Err(error) => {
+            log::warn!("failed to start inotify: {}", error);
+            return None;
+        }
+    };
+    // Downloads are written to a partial file that is renamed once finished
+    let mask = WatchMask::CREATE
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO;
+    if let Err(error) = notify.add_watch(dir, mask) {
+        log::warn!("failed to watch {}: {}", dir.display(), error);
+        return None;
+    }
+
+    match follow_inotify(notify, id, tx_update_request, |_| true) {
+        Ok(watch) => Some(watch),
+        Err(error) => {
+            log::warn!("failed to watch for changes: {:?}", error);
+            None
+        }
+    }
+}
+
+impl ConfigBlock for Downloads {
+    type Config = DownloadsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let path = match block_config.path {
+            Some(path) => PathBuf::from(shellexpand::tilde(&path).to_string()),
+            None => download_dir(),
+        };
+
+        Ok(Downloads {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("net_down")?,
+            format: block_config.format.with_default("{name}")?,
+            format_progress: block_config.format_progress.with_default("{downloading}")?,
+            show_for: block_config.show_for,
+            open_command: block_config.open_command,
+            latest: None,
+            downloading: 0,
+            watch: watch(id, &path, tx_update_request),
+            path,
+        })
+    }
+}
+
+impl Downloads {
+    /// The newest finished download with the time it finished, and the number of downloads in
+    /// progress
+    fn scan(&self) -> Result<(Option<(PathBuf, SystemTime)>, usize)> {
+        let entries = fs::read_dir(&self.path).block_error(
+            "downloads",
+            &format!("failed to read {}", self.path.display()),
+        )?;
+        let files: Vec<(PathBuf, fs::Metadata)> = entries
+            .flatten()
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+            .collect();
+
+        let downloading = files.iter().filter(|(path, _)| is_partial(path)).count();
+        let latest = files
+            .into_iter()
+            .filter(|(path, metadata)| metadata.is_file() && !is_partial(path))
+            // Firefox creates an empty file under the final name next to the partial one
+            .filter(|(path, _)| {
+                let partial = format!("{}.part", path.display());
+                !Path::new(&partial).exists()
+            })
+            // Browsers keep the modification time of the server, but renaming a finished
+            // download changes the status change time
+            .map(|(path, metadata)| {
+                let changed = UNIX_EPOCH + Duration::from_secs(metadata.ctime().max(0) as u64);
+                let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+                (path, changed.max(modified))
+            })
+            .max_by_key(|(_, time)| *time);
+        Ok((latest, downloading))
+    }
+
+    fn open(&self, path: &Path) -> Result<()> {
+        let command = format!("{} '{}'", self.open_command, path.display());
+        spawn_child_async("sh", &["-c", &command])
+            .block_error("downloads", &format!("failed to run {}", command))
+    }
+}
+
+impl Block for Downloads {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (latest, downloading) = self.scan()?;
+        let now = SystemTime::now();
+        let remaining = latest.as_ref().and_then(|(_, finished)| {
+            let age = now.duration_since(*finished).unwrap_or_default();
+            self.show_for
+                .checked_sub(age)
+                .filter(|left| !left.is_zero())
+        });
+        self.latest = remaining.and(latest.map(|(path, _)| path));
+        self.downloading = downloading;
+
+        let name = self
+            .latest
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| escape_pango_text(&name.to_string_lossy()))
+            .unwrap_or_default();
+        let values = map!(
+            "name" => Value::from_string(name),
+            "downloading" => Value::from_integer(downloading as i64),
+        );
+        if downloading > 0 {
+            self.text.set_texts(self.format_progress.render(&values)?);
+            self.text.set_state(State::Info);
+        } else {
+            self.text.set_texts(self.format.render(&values)?);
+            self.text.set_state(State::Good);
+        }
+
+        // Hide the download once it was shown long enough
+        Ok(match (remaining, self.watch.is_some()) {
+            (Some(left), true) => Some(left.into()),
+            (Some(left), false) => Some(left.min(POLL).into()),
+            (None, true) => None,
+            (None, false) => Some(POLL.into()),
+        })
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match (event.button, &self.latest) {
+            (MouseButton::Left, Some(latest)) => self.open(latest),
+            (MouseButton::Left, None) | (MouseButton::Right, _) => self.open(&self.path),
+            _ => Ok(()),
+        }
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.latest.is_none() && self.downloading == 0 {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_user_dirs() {
+        let contents = "# This file is written by xdg-user-dirs-update\n\
+                        XDG_DESKTOP_DIR=\"$HOME/Desktop\"\n\
+                        XDG_DOWNLOAD_DIR=\"$HOME/Téléchargements\"\n";
+        assert_eq!(
+            parse_user_dirs(contents, "/home/me"),
+            Some(PathBuf::from("/home/me/Téléchargements"))
+        );
+        assert_eq!(
+            parse_user_dirs("XDG_MUSIC_DIR=\"$HOME/Music\"", "/home/me"),
+            None
+        );
+        assert!(is_partial(Path::new("/tmp/x.iso.crdownload")));
+        assert!(!is_partial(Path::new("/tmp/x.iso")));
+    }
+}
@@ -0,0 +1,271 @@
+//! Checks that hostnames resolve to the public IP address of the machine, to notice when a dynamic
+//! DNS record is stale. A click runs a script that updates the records.
+
+use std::net::{IpAddr, ToSocketAddrs};
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+#[cfg(feature = "http")]
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Dyndns {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    hostnames: Vec<String>,
+    expected: Option<IpAddr>,
+    ip_url: String,
+    resolver: Option<String>,
+    update_command: Option<String>,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DyndnsConfig {
+    pub hostnames: Vec<String>,
+
+    /// The address the hostnames should resolve to, instead of the public one
+    pub expected: Option<String>,
+
+    /// URL that answers with the public IP address of the machine
+    pub ip_url: String,
+
+    /// DNS server to ask with `dig` instead of the system resolver, so that caches don't hide
+    /// changes
+    pub resolver: Option<String>,
+
+    /// Shell command that updates the records, run on click
+    pub update_command: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for DyndnsConfig {
+    fn default() -> Self {
+        Self {
+            hostnames: Vec::new(),
+            expected: None,
+            ip_url: "https://api.ipify.org".to_string(),
+            resolver: None,
+            update_command: None,
+            interval: Duration::from_secs(300),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Dyndns {
+    type Config = DyndnsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.hostnames.is_empty() {
+            return Err(ConfigurationError(
+                "dyndns".to_string(),
+                "hostnames must be set".to_string(),
+            ));
+        }
+        let expected = block_config
+            .expected
+            .map(|ip| ip.parse())
+            .transpose()
+            .configuration_error("expected must be an IP address")?;
+
+        Ok(Dyndns {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()),
+            format: block_config.format.with_default("{ip}")?,
+            hostnames: block_config.hostnames,
+            expected,
+            ip_url: block_config.ip_url,
+            resolver: block_config.resolver,
+            update_command: block_config.update_command,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+/// The addresses in the output of `dig +short`, which also lists the targets of CNAMEs
+fn parse_dig(output: &str) -> Vec<IpAddr> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+/// The public IP address of the machine, as a service at `url` sees it
+#[cfg(feature = "http")]
+fn fetch_public_ip(url: &str) -> Result<IpAddr> {
+    let response = http::http_get(url, Some(Duration::from_secs(10)), vec![])?;
+    if response.code != 200 {
+        return Err(BlockError(
+            "dyndns".to_string(),
+            format!("could not get {} (HTTP {})", url, response.code),
+        ));
+    }
+    String::from_utf8_lossy(&response.content)
+        .trim()
+        .parse()
+        .block_error("dyndns", &format!("{} didn't answer an IP address", url))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_public_ip(_url: &str) -> Result<IpAddr> {
+    Err(ConfigurationError(
+        "dyndns".to_string(),
+        "expected must be set without the http feature".to_string(),
+    ))
+}
+
+impl Dyndns {
+    /// The addresses of a hostname, none if it doesn't resolve
+    fn resolve(&self, hostname: &str, ipv6: bool) -> Result<Vec<IpAddr>> {
+        match &self.resolver {
+            Some(resolver) => {
+                let command = format!(
+                    "dig +short '@{}' '{}' {}",
+                    resolver,
+                    hostname,
+                    if ipv6 { "AAAA" } else { "A" }
+                );
+                let output = self.command_runner.run(&command)?;
+                if output.status != Some(0) {
+                    return Err(BlockError(
+                        "dyndns".to_string(),
+                        format!("could not ask {}", resolver),
+                    ));
+                }
+                Ok(parse_dig(&output.stdout))
+            }
+            None => Ok((hostname, 0)
+                .to_socket_addrs()
+                .map(|addresses| addresses.map(|address| address.ip()).collect())
+                .unwrap_or_default()),
+        }
+    }
+
+    /// The address the hostnames should resolve to and the hostnames that don't
+    fn check(&self) -> Result<(IpAddr, Vec<&str>)> {
+        let ip = match self.expected {
+            Some(expected) => expected,
+            None => fetch_public_ip(&self.ip_url)?,
+        };
+        let mut stale = Vec::new();
+        for hostname in &self.hostnames {
+            if !self.resolve(hostname, ip.is_ipv6())?.contains(&ip) {
+                stale.push(hostname.as_str());
+            }
+        }
+        Ok((ip, stale))
+    }
+}
+
+impl Block for Dyndns {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.check() {
+            Ok((ip, stale)) => {
+                let values = map!(
+                    "ip" => Value::from_string(ip.to_string()),
+                    "stale" => Value::from_integer(stale.len() as i64),
+                    "hostnames" => Value::from_string(stale.join(", ")),
+                );
+                let state = if stale.is_empty() {
+                    State::Idle
+                } else {
+                    State::Warning
+                };
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(state);
+            }
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, so the public address is unknown
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+            }
+            Err(error) => {
+                self.text.set_text(format!("dyndns error {}", error));
+                self.text.set_state(State::Critical);
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let Some(command) = self.update_command.clone() {
+            let id = self.id;
+            let tx_update_request = self.tx_update_request.clone();
+            // Check again once the records are updated
+            thread::Builder::new()
+                .name("dyndns".into())
+                .spawn(move || {
+                    // The check that follows shows whether it worked
+                    match Command::new("sh").args(["-c", &command]).status() {
+                        Ok(status) if status.success() => {}
+                        Ok(status) => log::warn!("{} failed with {}", command, status),
+                        Err(error) => log::warn!("failed to run {}: {}", command, error),
+                    }
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                })
+                .internal_error("dyndns", "failed to start a thread")?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dig() {
+        let output = "home.dyndns.example.\n203.0.113.7\n";
+        assert_eq!(
+            parse_dig(output),
+            vec!["203.0.113.7".parse::<IpAddr>().unwrap()]
+        );
+    }
+}
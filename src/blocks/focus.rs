@@ -0,0 +1,152 @@
+//! Focus mode: shuts out distractions for a set time, e.g. by turning on do not disturb and hiding
+//! blocks, and shows how much of that time is left.
+
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Focus {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    duration: Duration,
+    /// When the current focus time ends
+    until: Option<Instant>,
+    start_commands: Vec<String>,
+    stop_commands: Vec<String>,
+    hide_groups: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct FocusConfig {
+    /// Length of the focus time in minutes
+    pub duration: u64,
+
+    /// Format while focusing
+    pub format: FormatTemplate,
+
+    /// Shell commands run when the focus time starts
+    pub start_commands: Vec<String>,
+
+    /// Shell commands run when the focus time ends or is stopped
+    pub stop_commands: Vec<String>,
+
+    /// Groups (see the `group` option of blocks) that are hidden while focusing
+    pub hide_groups: Vec<String>,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            duration: 25,
+            format: FormatTemplate::default(),
+            start_commands: Vec::new(),
+            stop_commands: Vec::new(),
+            hide_groups: Vec::new(),
+        }
+    }
+}
+
+impl ConfigBlock for Focus {
+    type Config = FocusConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Focus {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("focus")?,
+            format: block_config.format.with_default("{remaining}")?,
+            duration: Duration::from_secs(block_config.duration * 60),
+            until: None,
+            start_commands: block_config.start_commands,
+            stop_commands: block_config.stop_commands,
+            hide_groups: block_config.hide_groups,
+        })
+    }
+}
+
+impl Focus {
+    fn set_focus(&mut self, focus: bool) -> Result<()> {
+        self.until = if focus {
+            Some(Instant::now() + self.duration)
+        } else {
+            None
+        };
+        for group in &self.hide_groups {
+            super::group::set_collapsed(group, focus);
+        }
+        let commands = if focus {
+            &self.start_commands
+        } else {
+            &self.stop_commands
+        };
+        for command in commands {
+            spawn_child_async("sh", &["-c", command])
+                .block_error("focus", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        match self.until {
+            Some(until) => {
+                let remaining = until.saturating_duration_since(Instant::now()).as_secs();
+                let values = map!(
+                    "remaining" => Value::from_string(format!("{}:{:02}", remaining / 60, remaining % 60)),
+                );
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(State::Info);
+            }
+            None => {
+                self.text.set_text(String::new());
+                self.text.set_state(State::Idle);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Block for Focus {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if let Some(until) = self.until {
+            if Instant::now() >= until {
+                self.set_focus(false)?;
+            }
+        }
+        self.set_text()?;
+        Ok(Some(Duration::from_secs(1).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.set_focus(self.until.is_none())?;
+            self.set_text()?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
@@ -13,7 +13,9 @@ use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
 use crate::widgets::{text::TextWidget, I3BarWidget, State};
 
 const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
@@ -27,6 +29,9 @@ pub struct Github {
     format: FormatTemplate,
     total_notifications: u64,
     hide_if_total_is_zero: bool,
+    /// Whether the open pull requests that await a review are counted
+    count_reviews: bool,
+    open_command: String,
     good: Option<Vec<String>>,
     info: Option<Vec<String>>,
     warning: Option<Vec<String>>,
@@ -42,11 +47,17 @@ pub struct GithubConfig {
 
     pub api_server: String,
 
+    /// File that holds the token, instead of the `I3RS_GITHUB_TOKEN` environment variable
+    pub token_file: Option<String>,
+
     /// Format override
     pub format: FormatTemplate,
 
     pub hide_if_total_is_zero: bool,
 
+    /// Command that opens the notifications page on click
+    pub open_command: String,
+
     /// good state list
     pub good: Option<Vec<String>>,
 
@@ -66,7 +77,9 @@ impl Default for GithubConfig {
             interval: Duration::from_secs(30),
             api_server: "https://api.github.com".to_string(),
             format: FormatTemplate::default(),
+            token_file: None,
             hide_if_total_is_zero: false,
+            open_command: "xdg-open".to_string(),
             good: None,
             info: None,
             warning: None,
@@ -84,8 +97,24 @@ impl ConfigBlock for Github {
         shared_config: SharedConfig,
         _: Sender<Task>,
     ) -> Result<Self> {
-        let token = std::env::var(GITHUB_TOKEN_ENV)
-            .block_error("github", "missing I3RS_GITHUB_TOKEN environment variable")?;
+        let token = match &block_config.token_file {
+            Some(file) => std::fs::read_to_string(shellexpand::tilde(file).as_ref())
+                .block_error("github", &format!("failed to read {}", file))?
+                .trim()
+                .to_string(),
+            None => std::env::var(GITHUB_TOKEN_ENV)
+                .block_error("github", "missing I3RS_GITHUB_TOKEN environment variable")?,
+        };
+        let format = block_config.format.with_default("{total:1}")?;
+        let count_reviews = format.contains("reviews")
+            || [
+                &block_config.good,
+                &block_config.info,
+                &block_config.warning,
+                &block_config.critical,
+            ]
+            .iter()
+            .any(|list| list.iter().flatten().any(|key| key == "reviews"));
 
         let text = TextWidget::new(id, 0, shared_config)
             .with_text("x")
@@ -96,9 +125,11 @@ impl ConfigBlock for Github {
             text,
             api_server: block_config.api_server,
             token,
-            format: block_config.format.with_default("{total:1}")?,
+            format,
             total_notifications: 0,
             hide_if_total_is_zero: block_config.hide_if_total_is_zero,
+            count_reviews,
+            open_command: block_config.open_command,
             good: block_config.good,
             info: block_config.info,
             warning: block_config.warning,
@@ -107,9 +138,36 @@ impl ConfigBlock for Github {
     }
 }
 
+impl Github {
+    /// Counts the open pull requests that await a review by the user
+    fn reviews(&self) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let url = format!(
+            "{}/search/issues?q=is:open+is:pr+review-requested:%40me&per_page=1",
+            self.api_server
+        );
+        let header_value = format!("Bearer {}", self.token);
+        let headers = vec![("Authorization", header_value.as_str())];
+        let result = http::http_get_json(&url, Some(Duration::from_secs(3)), headers)?;
+        result.content["total_count"]
+            .as_u64()
+            .ok_or_else(|| "the search failed".into())
+    }
+
+    /// The page that lists the notifications
+    fn notifications_page(&self) -> String {
+        let server = self.api_server.trim_end_matches('/');
+        match server {
+            "https://api.github.com" => "https://github.com/notifications".to_string(),
+            // GitHub Enterprise serves the API under /api/v3
+            _ => format!("{}/notifications", server.trim_end_matches("/api/v3")),
+        }
+    }
+}
+
 impl Block for Github {
     fn update(&mut self) -> Result<Option<Update>> {
-        let aggregations = match Notifications::new(&self.api_server, &self.token).try_fold(
+        let mut notifications = Notifications::new(&self.api_server, &self.token);
+        let aggregations = notifications.by_ref().try_fold(
             map!("total".to_owned() => 0),
             |mut acc,
              notif|
@@ -119,14 +177,33 @@ impl Block for Github {
                 acc.entry("total".to_owned()).and_modify(|v| *v += 1);
                 Ok(acc)
             },
-        ) {
+        );
+        let interval = notifications
+            .wait
+            .map_or(self.update_interval, |wait| wait.max(self.update_interval));
+        let unauthorized = notifications.unauthorized;
+        let mut aggregations = match aggregations {
             Ok(v) => v,
             Err(_) => {
                 // If there is a error reported, set the value to x
                 self.text.set_text("x".to_owned());
-                return Ok(Some(self.update_interval.into()));
+                if unauthorized {
+                    self.text.set_state(State::Critical);
+                }
+                return Ok(Some(interval.into()));
             }
         };
+        if self.count_reviews {
+            match self.reviews() {
+                Ok(reviews) => {
+                    aggregations.insert("reviews".to_owned(), reviews);
+                }
+                Err(_) => {
+                    self.text.set_text("x".to_owned());
+                    return Ok(Some(interval.into()));
+                }
+            }
+        }
 
         let default: u64 = 0;
         self.total_notifications = *aggregations.get("total").unwrap_or(&default);
@@ -145,6 +222,7 @@ impl Block for Github {
             "state_change" =>     Value::from_integer(*aggregations.get("state_change").unwrap_or(&default) as i64),
             "subscribed" =>       Value::from_integer(*aggregations.get("subscribed").unwrap_or(&default) as i64),
             "team_mention" =>     Value::from_integer(*aggregations.get("team_mention").unwrap_or(&default) as i64),
+            "reviews" =>          Value::from_integer(*aggregations.get("reviews").unwrap_or(&default) as i64),
         );
 
         self.text.set_texts(self.format.render(&values)?);
@@ -157,7 +235,16 @@ impl Block for Github {
             &aggregations,
         ));
 
-        Ok(Some(self.update_interval.into()))
+        Ok(Some(interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            let command = format!("{} '{}'", self.open_command, self.notifications_page());
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("github", &format!("failed to run {}", command))?;
+        }
+        Ok(())
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -182,6 +269,10 @@ struct Notifications<'a> {
     notifications: <Vec<Notification> as IntoIterator>::IntoIter,
     token: &'a str,
     next_page_url: String,
+    /// Set once GitHub rejected the token
+    unauthorized: bool,
+    /// How long GitHub asked to wait before polling again
+    wait: Option<Duration>,
 }
 
 impl<'a> Iterator for Notifications<'a> {
@@ -202,6 +293,8 @@ impl<'a> Notifications<'a> {
             next_page_url: format!("{}/notifications", api_server),
             token,
             notifications: vec![].into_iter(),
+            unauthorized: false,
+            wait: None,
         }
     }
 
@@ -221,16 +314,17 @@ impl<'a> Notifications<'a> {
         let result =
             http::http_get_json(&self.next_page_url, Some(Duration::from_secs(3)), headers)?;
 
-        self.next_page_url = result
-            .headers
-            .iter()
-            .find_map(|header| {
-                if header.starts_with("Link:") {
-                    parse_links_header(header).get("next").cloned()
-                } else {
-                    None
-                }
-            })
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        self.wait = requested_wait(&result.headers, now);
+        if result.code == 401 {
+            self.unauthorized = true;
+            return Err("the token was rejected".into());
+        }
+
+        self.next_page_url = header(&result.headers, "Link")
+            .and_then(|links| parse_links_header(links).get("next").cloned())
             .unwrap_or("")
             .to_string();
 
@@ -266,6 +360,35 @@ fn get_state(
     State::Idle
 }
 
+/// The value of a response header. HTTP/2 servers send the names in lower case.
+fn header<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+    headers.iter().find_map(|header| {
+        let (key, value) = header.split_once(':')?;
+        if key.eq_ignore_ascii_case(name) {
+            Some(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// How long to wait before polling again, as GitHub asks with `X-Poll-Interval` and, once the rate
+/// limit is used up, with the time at which the limit is reset
+fn requested_wait(headers: &[String], now: u64) -> Option<Duration> {
+    let poll_interval = header(headers, "X-Poll-Interval").and_then(|secs| secs.parse().ok());
+    let reset = match header(headers, "X-RateLimit-Remaining") {
+        Some("0") => header(headers, "X-RateLimit-Reset")
+            .and_then(|reset| reset.parse::<u64>().ok())
+            .map(|reset| reset.saturating_sub(now)),
+        _ => None,
+    };
+    poll_interval
+        .into_iter()
+        .chain(reset)
+        .max()
+        .map(Duration::from_secs)
+}
+
 fn parse_links_header(raw_links: &str) -> HashMap<&str, &str> {
     lazy_static! {
         static ref LINKS_REGEX: Regex =
@@ -304,4 +427,25 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_requested_wait() {
+        let headers = |remaining: &str| {
+            vec![
+                "HTTP/2 200\r\n".to_string(),
+                "x-poll-interval: 60\r\n".to_string(),
+                format!("x-ratelimit-remaining: {}\r\n", remaining),
+                "x-ratelimit-reset: 1000300\r\n".to_string(),
+            ]
+        };
+        assert_eq!(
+            requested_wait(&headers("4999"), 1_000_000),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            requested_wait(&headers("0"), 1_000_000),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(requested_wait(&[], 1_000_000), None);
+    }
 }
@@ -0,0 +1,293 @@
+//! Shows the status of the latest pipeline of GitLab projects, optionally of specific branches
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const GITLAB_TOKEN_ENV: &str = "I3RS_GITLAB_TOKEN";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Path of the project, e.g. "gitlab-org/gitlab", or its numeric ID
+    pub project: String,
+
+    /// Defaults to the latest pipeline of any branch
+    #[serde(default)]
+    pub branch: Option<String>,
+
+    /// Defaults to the name of the project
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pipeline {
+    status: String,
+    /// In seconds, so far if the pipeline is still running
+    duration: Option<i64>,
+    web_url: String,
+}
+
+/// The color of a pipeline status
+fn status_state(status: &str) -> State {
+    match status {
+        "success" => State::Good,
+        "failed" => State::Critical,
+        "created" | "waiting_for_resource" | "preparing" | "pending" | "running" | "scheduled" => {
+            State::Warning
+        }
+        // Canceled, skipped or waiting for a manual action
+        _ => State::Idle,
+    }
+}
+
+/// The duration of a pipeline as GitLab returns it from `GET /projects/:id/pipelines/:id`
+fn pipeline_duration(pipeline: &JsonValue, now: DateTime<Utc>) -> Option<i64> {
+    pipeline["duration"].as_i64().or_else(|| {
+        let started = DateTime::parse_from_rfc3339(pipeline["started_at"].as_str()?).ok()?;
+        Some((now - started.with_timezone(&Utc)).num_seconds())
+    })
+}
+
+pub struct Gitlab {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    format: FormatTemplate,
+    server: String,
+    token: Option<String>,
+    projects: Vec<ProjectConfig>,
+    pipelines: Vec<Option<Pipeline>>,
+    open_command: String,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct GitlabConfig {
+    /// URL of the GitLab instance
+    pub server: String,
+
+    /// Personal access token with the read_api scope, needed for private projects. Defaults to
+    /// the `I3RS_GITLAB_TOKEN` environment variable.
+    pub token: Option<String>,
+
+    pub projects: Vec<ProjectConfig>,
+
+    /// Command that opens the pipeline on click
+    pub open_command: String,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for GitlabConfig {
+    fn default() -> Self {
+        Self {
+            server: "https://gitlab.com".to_string(),
+            token: None,
+            projects: Vec::new(),
+            open_command: "xdg-open".to_string(),
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Gitlab {
+    type Config = GitlabConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.projects.is_empty() {
+            return Err(ConfigurationError(
+                "gitlab".to_string(),
+                "projects must be set".to_string(),
+            ));
+        }
+        let widgets = (0..block_config.projects.len())
+            .map(|instance| {
+                let widget = TextWidget::new(id, instance, shared_config.clone());
+                if instance == 0 {
+                    widget.with_icon("tasks")
+                } else {
+                    Ok(widget)
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Gitlab {
+            id,
+            widgets,
+            format: block_config.format.with_default("{project} {status}")?,
+            server: block_config.server.trim_end_matches('/').to_string(),
+            token: block_config
+                .token
+                .or_else(|| std::env::var(GITLAB_TOKEN_ENV).ok()),
+            pipelines: vec![None; block_config.projects.len()],
+            projects: block_config.projects,
+            open_command: block_config.open_command,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Gitlab {
+    fn get(&self, path: &str) -> Result<JsonValue> {
+        let url = format!("{}/api/v4{}", self.server, path);
+        let headers = match &self.token {
+            Some(token) => vec![("PRIVATE-TOKEN", token.as_str())],
+            None => vec![],
+        };
+        let response = http::http_get_json(&url, Some(Duration::from_secs(10)), headers)?;
+        match response.code {
+            200 => Ok(response.content),
+            401 => Err(BlockError(
+                "gitlab".to_string(),
+                "the token was rejected".to_string(),
+            )),
+            code => Err(BlockError(
+                "gitlab".to_string(),
+                format!("failed to get {} (HTTP {})", path, code),
+            )),
+        }
+    }
+
+    /// The latest pipeline of a project, if it has any
+    fn latest_pipeline(&self, project: &ProjectConfig) -> Result<Option<Pipeline>> {
+        let path = format!("/projects/{}/pipelines", http::url_encode(&project.project));
+        let query = match &project.branch {
+            Some(branch) => format!("?per_page=1&ref={}", http::url_encode(branch)),
+            None => "?per_page=1".to_string(),
+        };
+        let id = match self.get(&format!("{}{}", path, query))?[0]["id"].as_u64() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        // Only single pipelines come with their duration
+        let pipeline = self.get(&format!("{}/{}", path, id))?;
+        Ok(Some(Pipeline {
+            status: pipeline["status"].as_str().unwrap_or("unknown").to_string(),
+            duration: pipeline_duration(&pipeline, Utc::now()),
+            web_url: pipeline["web_url"].as_str().unwrap_or_default().to_string(),
+        }))
+    }
+}
+
+impl Block for Gitlab {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let results: Vec<Result<Option<Pipeline>>> = self
+            .projects
+            .iter()
+            .map(|project| self.latest_pipeline(project))
+            .collect();
+
+        self.pipelines.clear();
+        for ((widget, project), result) in self.widgets.iter_mut().zip(&self.projects).zip(results)
+        {
+            // A project that can't be checked doesn't hide the others
+            let pipeline = match result {
+                Ok(pipeline) => pipeline,
+                Err(error) => {
+                    match error {
+                        BlockError(block, _) | InternalError(block, _, _) if block == "curl" => {
+                            widget.set_text("×".to_string());
+                            widget.set_state(State::Warning);
+                        }
+                        error => {
+                            widget.set_text(format!("gitlab error {}", error));
+                            widget.set_state(State::Critical);
+                        }
+                    }
+                    self.pipelines.push(None);
+                    continue;
+                }
+            };
+            let name = project.project.rsplit('/').next().unwrap_or_default();
+            let status = pipeline.as_ref().map_or("none", |p| p.status.as_str());
+            let values = map!(
+                "project" => Value::from_string(project.label.clone().unwrap_or_else(|| name.to_string())),
+                "branch" => Value::from_string(project.branch.clone().unwrap_or_default()),
+                "status" => Value::from_string(status.to_string()),
+                "duration" => Value::from_integer(pipeline.as_ref().and_then(|p| p.duration).unwrap_or(0)).seconds(),
+            );
+            widget.set_texts(self.format.render(&values)?);
+            widget.set_state(status_state(status));
+            self.pipelines.push(pipeline);
+        }
+
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let pipeline = event
+            .instance
+            .and_then(|instance| self.pipelines.get(instance))
+            .and_then(Option::as_ref);
+        if let Some(pipeline) = pipeline {
+            let command = format!("{} '{}'", self.open_command, pipeline.web_url);
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("gitlab", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_pipeline_duration() {
+        let now = Utc.ymd(2021, 10, 15).and_hms(12, 0, 0);
+        let finished = serde_json::json!({
+            "status": "success",
+            "started_at": "2021-10-15T11:00:00.000Z",
+            "duration": 272,
+        });
+        let running = serde_json::json!({
+            "status": "running",
+            "started_at": "2021-10-15T11:55:30.000Z",
+            "duration": null,
+        });
+        assert_eq!(pipeline_duration(&finished, now), Some(272));
+        assert_eq!(pipeline_duration(&running, now), Some(270));
+        assert_eq!(status_state("running"), State::Warning);
+    }
+}
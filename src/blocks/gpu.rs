@@ -0,0 +1,516 @@
+//! Shows the utilization, VRAM usage, temperature and power draw of a GPU, from NVML for NVIDIA
+//! GPUs and from the amdgpu driver in sysfs for AMD GPUs
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// What the driver reports, which not every GPU supports all of
+#[derive(Debug, Default, Clone, PartialEq)]
+struct GpuInfo {
+    name: String,
+    /// In percent
+    utilization: Option<f64>,
+    /// VRAM used and total, in bytes
+    memory: Option<(u64, u64)>,
+    /// In °C
+    temperature: Option<f64>,
+    /// In watts
+    power: Option<f64>,
+}
+
+trait GpuBackend {
+    fn info(&mut self) -> Result<GpuInfo>;
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuDriver {
+    Nvidia,
+    Amd,
+}
+
+/// The NVIDIA Management Library, which comes with the driver and is loaded at runtime so that
+/// the block works without it for AMD GPUs
+mod nvml {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_uint, c_ulonglong, c_void};
+    use std::ptr;
+
+    use nix::libc;
+
+    use super::{GpuBackend, GpuInfo};
+    use crate::errors::*;
+
+    type Device = *mut c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Utilization {
+        gpu: c_uint,
+        memory: c_uint,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Memory {
+        total: c_ulonglong,
+        free: c_ulonglong,
+        used: c_ulonglong,
+    }
+
+    const NVML_TEMPERATURE_GPU: c_int = 0;
+
+    pub struct Nvml {
+        library: *mut c_void,
+        initialized: bool,
+        device: Device,
+    }
+
+    fn check(code: c_int, function: &str) -> Result<()> {
+        match code {
+            0 => Ok(()),
+            code => Err(BlockError(
+                "gpu".to_string(),
+                format!("{} failed with NVML error {}", function, code),
+            )),
+        }
+    }
+
+    impl Nvml {
+        pub fn new(index: u32) -> Result<Self> {
+            let library = unsafe {
+                libc::dlopen(
+                    b"libnvidia-ml.so.1\0".as_ptr() as *const c_char,
+                    libc::RTLD_NOW,
+                )
+            };
+            if library.is_null() {
+                return Err(BlockError(
+                    "gpu".to_string(),
+                    "failed to load libnvidia-ml.so.1, is the NVIDIA driver installed?".to_string(),
+                ));
+            }
+            let mut nvml = Nvml {
+                library,
+                initialized: false,
+                device: ptr::null_mut(),
+            };
+
+            let init: unsafe extern "C" fn() -> c_int = nvml.symbol("nvmlInit_v2")?;
+            check(unsafe { init() }, "nvmlInit_v2")?;
+            nvml.initialized = true;
+
+            let handle_by_index: unsafe extern "C" fn(c_uint, *mut Device) -> c_int =
+                nvml.symbol("nvmlDeviceGetHandleByIndex_v2")?;
+            let mut device = ptr::null_mut();
+            check(
+                unsafe { handle_by_index(index, &mut device) },
+                "nvmlDeviceGetHandleByIndex_v2",
+            )?;
+            nvml.device = device;
+            Ok(nvml)
+        }
+
+        /// The function `name` of the library, whose type `T` must match its signature
+        fn symbol<T: Copy>(&self, name: &str) -> Result<T> {
+            let c_name = CString::new(name).internal_error("gpu", "invalid symbol name")?;
+            let symbol = unsafe { libc::dlsym(self.library, c_name.as_ptr()) };
+            if symbol.is_null() {
+                return Err(BlockError(
+                    "gpu".to_string(),
+                    format!("libnvidia-ml.so.1 has no {}", name),
+                ));
+            }
+            // `T` is an `extern "C" fn`, which has the size of a pointer
+            Ok(unsafe { std::mem::transmute_copy(&symbol) })
+        }
+
+        fn name(&self) -> Result<String> {
+            let get_name: unsafe extern "C" fn(Device, *mut c_char, c_uint) -> c_int =
+                self.symbol("nvmlDeviceGetName")?;
+            let mut name = [0u8; 96];
+            check(
+                unsafe { get_name(self.device, name.as_mut_ptr() as *mut c_char, 96) },
+                "nvmlDeviceGetName",
+            )?;
+            let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            Ok(String::from_utf8_lossy(&name[..end]).into_owned())
+        }
+
+        fn utilization(&self) -> Result<f64> {
+            let get: unsafe extern "C" fn(Device, *mut Utilization) -> c_int =
+                self.symbol("nvmlDeviceGetUtilizationRates")?;
+            let mut utilization = Utilization::default();
+            check(
+                unsafe { get(self.device, &mut utilization) },
+                "nvmlDeviceGetUtilizationRates",
+            )?;
+            Ok(f64::from(utilization.gpu))
+        }
+
+        fn memory(&self) -> Result<(u64, u64)> {
+            let get: unsafe extern "C" fn(Device, *mut Memory) -> c_int =
+                self.symbol("nvmlDeviceGetMemoryInfo")?;
+            let mut memory = Memory::default();
+            check(
+                unsafe { get(self.device, &mut memory) },
+                "nvmlDeviceGetMemoryInfo",
+            )?;
+            Ok((memory.used, memory.total))
+        }
+
+        fn temperature(&self) -> Result<f64> {
+            let get: unsafe extern "C" fn(Device, c_int, *mut c_uint) -> c_int =
+                self.symbol("nvmlDeviceGetTemperature")?;
+            let mut temperature = 0;
+            check(
+                unsafe { get(self.device, NVML_TEMPERATURE_GPU, &mut temperature) },
+                "nvmlDeviceGetTemperature",
+            )?;
+            Ok(f64::from(temperature))
+        }
+
+        fn power(&self) -> Result<f64> {
+            let get: unsafe extern "C" fn(Device, *mut c_uint) -> c_int =
+                self.symbol("nvmlDeviceGetPowerUsage")?;
+            let mut milliwatts = 0;
+            check(
+                unsafe { get(self.device, &mut milliwatts) },
+                "nvmlDeviceGetPowerUsage",
+            )?;
+            Ok(f64::from(milliwatts) / 1000.)
+        }
+    }
+
+    impl GpuBackend for Nvml {
+        fn info(&mut self) -> Result<GpuInfo> {
+            // Not every GPU supports every query, e.g. laptop GPUs often don't report power
+            Ok(GpuInfo {
+                name: self.name()?,
+                utilization: self.utilization().ok(),
+                memory: self.memory().ok(),
+                temperature: self.temperature().ok(),
+                power: self.power().ok(),
+            })
+        }
+    }
+
+    impl Drop for Nvml {
+        fn drop(&mut self) {
+            if self.initialized {
+                if let Ok(shutdown) = self.symbol::<unsafe extern "C" fn() -> c_int>("nvmlShutdown")
+                {
+                    unsafe { shutdown() };
+                }
+            }
+            unsafe { libc::dlclose(self.library) };
+        }
+    }
+}
+
+/// The `device` directories of the AMD GPUs in /sys/class/drm, like /sys/class/drm/card0/device
+fn amd_gpus() -> Vec<PathBuf> {
+    let mut cards: Vec<PathBuf> = fs::read_dir("/sys/class/drm")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                // Skips the connectors, like card0-DP-1
+                .filter(|entry| {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+                    name.strip_prefix("card")
+                        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+                })
+                .map(|entry| entry.path().join("device"))
+                .filter(|device| {
+                    fs::read_to_string(device.join("vendor"))
+                        .is_ok_and(|vendor| vendor.trim() == "0x1002")
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    cards.sort();
+    cards
+}
+
+/// A GPU of the amdgpu driver, by its device directory in sysfs
+struct Amdgpu {
+    device: PathBuf,
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl Amdgpu {
+    fn read(&self) -> GpuInfo {
+        let device = &self.device;
+        let hwmon = fs::read_dir(device.join("hwmon"))
+            .ok()
+            .and_then(|mut entries| entries.next()?.ok())
+            .map(|entry| entry.path());
+        let memory = read_number(&device.join("mem_info_vram_used"))
+            .zip(read_number(&device.join("mem_info_vram_total")));
+        GpuInfo {
+            // Only some GPUs have a product name
+            name: fs::read_to_string(device.join("product_name"))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_else(|_| "AMD GPU".to_string()),
+            utilization: read_number(&device.join("gpu_busy_percent")),
+            memory: memory.map(|(used, total)| (used as u64, total as u64)),
+            temperature: hwmon
+                .as_ref()
+                .and_then(|hwmon| read_number(&hwmon.join("temp1_input")))
+                .map(|millidegrees| millidegrees / 1000.),
+            power: hwmon
+                .as_ref()
+                .and_then(|hwmon| {
+                    read_number(&hwmon.join("power1_average"))
+                        .or_else(|| read_number(&hwmon.join("power1_input")))
+                })
+                .map(|microwatts| microwatts / 1_000_000.),
+        }
+    }
+}
+
+impl GpuBackend for Amdgpu {
+    fn info(&mut self) -> Result<GpuInfo> {
+        if !self.device.exists() {
+            return Err(BlockError(
+                "gpu".to_string(),
+                format!("{} is gone", self.device.display()),
+            ));
+        }
+        Ok(self.read())
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(deny_unknown_fields, default)]
+pub struct Thresholds {
+    /// Value from which on the block is shown as warning
+    pub warning: Option<f64>,
+
+    /// Value from which on the block is shown as critical
+    pub critical: Option<f64>,
+}
+
+impl Thresholds {
+    fn state(&self, value: Option<f64>) -> State {
+        match (value, self.warning, self.critical) {
+            (Some(value), _, Some(critical)) if value >= critical => State::Critical,
+            (Some(value), Some(warning), _) if value >= warning => State::Warning,
+            _ => State::Idle,
+        }
+    }
+}
+
+pub struct Gpu {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    backend: Box<dyn GpuBackend>,
+    interval: Duration,
+    utilization: Thresholds,
+    memory: Thresholds,
+    temperature: Thresholds,
+    power: Thresholds,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct GpuConfig {
+    /// Which driver to ask, by default AMD if there is an AMD GPU and NVIDIA otherwise
+    pub driver: Option<GpuDriver>,
+
+    /// Index of the GPU among those of the driver
+    pub gpu: u32,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Thresholds for the utilization, in percent
+    pub utilization: Thresholds,
+
+    /// Thresholds for the VRAM usage, in percent
+    pub memory: Thresholds,
+
+    /// Thresholds for the temperature, in °C
+    pub temperature: Thresholds,
+
+    /// Thresholds for the power draw, in watts
+    pub power: Thresholds,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            driver: None,
+            gpu: 0,
+            interval: Duration::from_secs(3),
+            utilization: Thresholds::default(),
+            memory: Thresholds::default(),
+            temperature: Thresholds {
+                warning: Some(80.),
+                critical: Some(90.),
+            },
+            power: Thresholds::default(),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Gpu {
+    type Config = GpuConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let amd_gpus = amd_gpus();
+        let driver = block_config.driver.unwrap_or(if amd_gpus.is_empty() {
+            GpuDriver::Nvidia
+        } else {
+            GpuDriver::Amd
+        });
+        let backend: Box<dyn GpuBackend> = match driver {
+            GpuDriver::Nvidia => Box::new(nvml::Nvml::new(block_config.gpu)?),
+            GpuDriver::Amd => Box::new(Amdgpu {
+                device: amd_gpus
+                    .into_iter()
+                    .nth(block_config.gpu as usize)
+                    .block_error("gpu", &format!("found no AMD GPU {}", block_config.gpu))?,
+            }),
+        };
+
+        Ok(Gpu {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("gpu")?,
+            format: block_config
+                .format
+                .with_default("{utilization} {memory} {temperature}")?,
+            backend,
+            interval: block_config.interval,
+            utilization: block_config.utilization,
+            memory: block_config.memory,
+            temperature: block_config.temperature,
+            power: block_config.power,
+        })
+    }
+}
+
+impl Block for Gpu {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let info = self.backend.info()?;
+
+        let unknown = || Value::from_string("-".to_string());
+        let memory_percent = info
+            .memory
+            .filter(|(_, total)| *total > 0)
+            .map(|(used, total)| used as f64 / total as f64 * 100.);
+        let values = map!(
+            "name" => Value::from_string(info.name.clone()),
+            "utilization" => info.utilization.map_or_else(unknown, |utilization| {
+                Value::from_integer(utilization.round() as i64).percents()
+            }),
+            "memory" => info.memory.map_or_else(unknown, |(used, _)| {
+                Value::from_integer(used as i64).bytes()
+            }),
+            "memory_total" => info.memory.map_or_else(unknown, |(_, total)| {
+                Value::from_integer(total as i64).bytes()
+            }),
+            "memory_percent" => memory_percent.map_or_else(unknown, |percent| {
+                Value::from_integer(percent.round() as i64).percents()
+            }),
+            "temperature" => info.temperature.map_or_else(unknown, |temperature| {
+                Value::from_integer(temperature.round() as i64).degrees()
+            }),
+            "power" => info.power.map_or_else(unknown, |power| Value::from_float(power).watts()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+
+        // The worst of the values decides
+        let state = [
+            self.utilization.state(info.utilization),
+            self.memory.state(memory_percent),
+            self.temperature.state(info.temperature),
+            self.power.state(info.power),
+        ]
+        .iter()
+        .copied()
+        .max_by_key(|state| state.severity())
+        .unwrap_or(State::Idle);
+        self.text.set_state(state);
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_amdgpu() {
+        let device = TempDir::new().unwrap();
+        device.child("gpu_busy_percent").write_str("37\n").unwrap();
+        device
+            .child("mem_info_vram_used")
+            .write_str("1073741824\n")
+            .unwrap();
+        device
+            .child("mem_info_vram_total")
+            .write_str("8589934592\n")
+            .unwrap();
+        device
+            .child("hwmon/hwmon3/temp1_input")
+            .write_str("54000\n")
+            .unwrap();
+        device
+            .child("hwmon/hwmon3/power1_average")
+            .write_str("18250000\n")
+            .unwrap();
+        let gpu = Amdgpu {
+            device: device.path().to_path_buf(),
+        };
+        assert_eq!(
+            gpu.read(),
+            GpuInfo {
+                name: "AMD GPU".to_string(),
+                utilization: Some(37.),
+                memory: Some((1_073_741_824, 8_589_934_592)),
+                temperature: Some(54.),
+                power: Some(18.25),
+            }
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::state_store::StateStore;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+lazy_static! {
+    /// Whether each group is collapsed, shared by the group blocks and the blocks they contain
+    static ref COLLAPSED: Mutex<HashMap<String, bool>> = Mutex::new(HashMap::new());
+}
+
+/// Whether the blocks belonging to `group` are currently hidden
+pub fn is_collapsed(group: &str) -> bool {
+    COLLAPSED
+        .lock()
+        .map(|groups| groups.get(group).copied().unwrap_or(false))
+        .unwrap_or(false)
+}
+
+pub fn set_collapsed(group: &str, collapsed: bool) {
+    if let Ok(mut groups) = COLLAPSED.lock() {
+        groups.insert(group.to_string(), collapsed);
+    }
+}
+
+pub struct Group {
+    id: usize,
+    text: TextWidget,
+    name: String,
+    icon_collapsed: String,
+    icon_expanded: String,
+    state_store: Rc<StateStore>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    /// Name of the group, as used in the `group` option of its blocks
+    pub group_name: String,
+
+    /// Text to display next to the icon (default is the group name)
+    pub text: Option<String>,
+
+    /// Whether the group starts collapsed the first time, afterwards it starts as it was left
+    #[serde(default = "GroupConfig::default_collapsed")]
+    pub collapsed: bool,
+
+    /// Icon ID while the group is collapsed
+    #[serde(default = "GroupConfig::default_icon_collapsed")]
+    pub icon_collapsed: String,
+
+    /// Icon ID while the group is expanded
+    #[serde(default = "GroupConfig::default_icon_expanded")]
+    pub icon_expanded: String,
+}
+
+impl GroupConfig {
+    fn default_collapsed() -> bool {
+        true
+    }
+
+    fn default_icon_collapsed() -> String {
+        "toggle_off".to_owned()
+    }
+
+    fn default_icon_expanded() -> String {
+        "toggle_on".to_owned()
+    }
+}
+
+impl ConfigBlock for Group {
+    type Config = GroupConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let state_store = shared_config.state_store();
+        let collapsed = state_store
+            .get(&state_key(&block_config.group_name))
+            .unwrap_or(block_config.collapsed);
+        set_collapsed(&block_config.group_name, collapsed);
+
+        let name = block_config.group_name;
+        let text = block_config.text.unwrap_or_else(|| name.clone());
+
+        let mut group = Group {
+            id,
+            text: TextWidget::new(id, 0, shared_config)
+                .with_text(&text)
+                .with_state(State::Idle),
+            name,
+            icon_collapsed: block_config.icon_collapsed,
+            icon_expanded: block_config.icon_expanded,
+            state_store,
+        };
+        group.set_icon()?;
+        Ok(group)
+    }
+}
+
+/// Groups are saved by name, which stays the same when blocks are added or moved
+fn state_key(group: &str) -> String {
+    format!("group/{}", group)
+}
+
+impl Group {
+    fn set_icon(&mut self) -> Result<()> {
+        self.text.set_icon(if is_collapsed(&self.name) {
+            &self.icon_collapsed
+        } else {
+            &self.icon_expanded
+        })
+    }
+}
+
+impl Block for Group {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.set_icon()?;
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        if let MouseButton::Left = e.button {
+            let collapsed = !is_collapsed(&self.name);
+            set_collapsed(&self.name, collapsed);
+            self.state_store.set(&state_key(&self.name), &collapsed);
+            self.set_icon()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct HddSpin {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    devices: Vec<String>,
+    use_sudo: bool,
+    format: FormatTemplate,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HddSpinConfig {
+    /// The disks to watch, all rotational disks if empty
+    pub devices: Vec<String>,
+
+    /// Run `hdparm` with `sudo -n`
+    pub use_sudo: bool,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for HddSpinConfig {
+    fn default() -> Self {
+        Self {
+            devices: Vec::new(),
+            use_sudo: false,
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// All disks that the kernel reports as rotational
+fn rotational_disks() -> Vec<String> {
+    let mut disks: Vec<String> = fs::read_dir("/sys/block")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("queue/rotational"))
+                .map(|rotational| rotational.trim() == "1")
+                .unwrap_or(false)
+        })
+        .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        .collect();
+    disks.sort();
+    disks
+}
+
+/// Reads the output of `hdparm -C`, returning the devices and whether they are spun up
+fn parse_hdparm(output: &str) -> Vec<(String, bool)> {
+    let mut states = Vec::new();
+    let mut device = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_suffix(':') {
+            device = Some(name.trim().to_string());
+        } else if let Some(state) = line.trim().strip_prefix("drive state is:") {
+            if let Some(device) = device.take() {
+                let state = state.trim();
+                states.push((device, state != "standby" && state != "sleeping"));
+            }
+        }
+    }
+    states
+}
+
+impl ConfigBlock for HddSpin {
+    type Config = HddSpinConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let devices = if block_config.devices.is_empty() {
+            rotational_disks()
+        } else {
+            block_config.devices
+        };
+        if devices.is_empty() {
+            return Err(BlockError(
+                "hdd_spin".to_string(),
+                "no rotational disks found".to_string(),
+            ));
+        }
+
+        Ok(HddSpin {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("disk_drive")?,
+            update_interval: block_config.interval,
+            devices,
+            use_sudo: block_config.use_sudo,
+            format: block_config.format.with_default("{active}/{total}")?,
+        })
+    }
+}
+
+impl Block for HddSpin {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut command = if self.use_sudo {
+            let mut command = Command::new("sudo");
+            command.arg("-n").arg("hdparm");
+            command
+        } else {
+            Command::new("hdparm")
+        };
+        // CHECK POWER MODE is answered by the drive without spinning up
+        let output = command
+            .arg("-C")
+            .args(&self.devices)
+            .output()
+            .block_error("hdd_spin", "could not run hdparm")?;
+        let states = parse_hdparm(&String::from_utf8_lossy(&output.stdout));
+        if states.is_empty() {
+            return Err(BlockError(
+                "hdd_spin".to_string(),
+                format!(
+                    "could not read the power mode: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+
+        let active: Vec<&str> = states
+            .iter()
+            .filter(|(_, active)| *active)
+            .map(|(device, _)| device.trim_start_matches("/dev/"))
+            .collect();
+        let values = map!(
+            "active" => Value::from_integer(active.len() as i64),
+            "standby" => Value::from_integer((states.len() - active.len()) as i64),
+            "total" => Value::from_integer(states.len() as i64),
+            "disks" => Value::from_string(active.join(" ")),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if active.is_empty() {
+            State::Good
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hdparm() {
+        let output = concat!(
+            "\n",
+            "/dev/sda:\n",
+            " drive state is:  standby\n",
+            "\n",
+            "/dev/sdb:\n",
+            " drive state is:  active/idle\n",
+            "\n",
+            "/dev/sdc:\n",
+            " drive state is:  sleeping\n",
+        );
+        assert_eq!(
+            parse_hdparm(output),
+            vec![
+                ("/dev/sda".to_string(), false),
+                ("/dev/sdb".to_string(), true),
+                ("/dev/sdc".to_string(), false),
+            ]
+        );
+        assert_eq!(parse_hdparm(""), vec![]);
+    }
+}
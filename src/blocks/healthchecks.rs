@@ -0,0 +1,244 @@
+//! Makes the bar part of the monitoring with [Healthchecks](https://healthchecks.io/): every update
+//! pings a check, so that a bar that stops running is noticed, and shows how the other checks of
+//! the project are doing. Self-hosted instances work the same.
+
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const API_KEY_ENV: &str = "HEALTHCHECKS_API_KEY";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many of the checks are in which state
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    total: usize,
+    up: usize,
+    /// Late, but within the grace time
+    grace: usize,
+    down: Vec<String>,
+}
+
+/// Sums up the checks that the management API lists, leaving out those that aren't in `names`
+/// unless it's empty. Paused and new checks only count towards the total.
+fn summarize(checks: &JsonValue, names: &[String]) -> Summary {
+    let mut summary = Summary::default();
+    for check in checks["checks"].as_array().into_iter().flatten() {
+        let name = check["name"].as_str().unwrap_or_default();
+        if !names.is_empty() && !names.iter().any(|n| n == name) {
+            continue;
+        }
+        summary.total += 1;
+        match check["status"].as_str() {
+            Some("up") | Some("started") => summary.up += 1,
+            Some("grace") => summary.grace += 1,
+            Some("down") => summary.down.push(name.to_string()),
+            _ => {}
+        }
+    }
+    summary
+}
+
+pub struct Healthchecks {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    ping_url: Option<String>,
+    api_url: String,
+    api_key: Option<String>,
+    checks: Vec<String>,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HealthchecksConfig {
+    /// URL to ping on every update, like "https://hc-ping.com/<uuid>"
+    pub ping_url: Option<String>,
+
+    /// URL of the management API
+    pub api_url: String,
+
+    /// Read-only API key of the project, to show the state of its checks
+    #[serde(default = "HealthchecksConfig::default_api_key")]
+    pub api_key: Option<String>,
+
+    /// Names of the checks to show, all by default
+    pub checks: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl HealthchecksConfig {
+    fn default_api_key() -> Option<String> {
+        std::env::var(API_KEY_ENV).ok()
+    }
+}
+
+impl Default for HealthchecksConfig {
+    fn default() -> Self {
+        Self {
+            ping_url: None,
+            api_url: "https://healthchecks.io/api/v3/".to_string(),
+            api_key: Self::default_api_key(),
+            checks: Vec::new(),
+            interval: Duration::from_secs(300),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Healthchecks {
+    type Config = HealthchecksConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.ping_url.is_none() && block_config.api_key.is_none() {
+            return Err(ConfigurationError(
+                "healthchecks".to_string(),
+                format!("ping_url or api_key (or {}) must be set", API_KEY_ENV),
+            ));
+        }
+
+        Ok(Healthchecks {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("healthchecks")?,
+            format: block_config.format.with_default("{up}/{total}")?,
+            ping_url: block_config.ping_url,
+            api_url: block_config.api_url,
+            api_key: block_config.api_key,
+            checks: block_config.checks,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Healthchecks {
+    /// Whether the check-in went through
+    fn ping(&self) -> bool {
+        match &self.ping_url {
+            Some(url) => match http::http_get(url, Some(TIMEOUT), vec![]) {
+                Ok(response) if response.code == 200 => true,
+                Ok(response) => {
+                    log::warn!("pinging {} failed with HTTP {}", url, response.code);
+                    false
+                }
+                Err(error) => {
+                    log::warn!("pinging {} failed: {}", url, error);
+                    false
+                }
+            },
+            None => true,
+        }
+    }
+
+    fn summary(&self) -> Result<Summary> {
+        let api_key = match &self.api_key {
+            Some(api_key) => api_key,
+            None => return Ok(Summary::default()),
+        };
+        let url = format!("{}/checks/", self.api_url.trim_end_matches('/'));
+        let response = http::http_get_json(&url, Some(TIMEOUT), vec![("X-Api-Key", api_key)])?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "healthchecks".to_string(),
+                format!("could not list the checks (HTTP {})", response.code),
+            ));
+        }
+        Ok(summarize(&response.content, &self.checks))
+    }
+}
+
+impl Block for Healthchecks {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let pinged = self.ping();
+        let summary = match self.summary() {
+            Ok(summary) => summary,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, the checks are listed again at the next update
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(self.interval.into()));
+            }
+            Err(error) => {
+                self.text.set_text(format!("healthchecks error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+
+        let values = map!(
+            "total" => Value::from_integer(summary.total as i64),
+            "up" => Value::from_integer(summary.up as i64),
+            "grace" => Value::from_integer(summary.grace as i64),
+            "down" => Value::from_integer(summary.down.len() as i64),
+            "down_names" => Value::from_string(summary.down.join(", ")),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if !summary.down.is_empty() {
+            State::Critical
+        } else if summary.grace > 0 || !pinged {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_summarize() {
+        let checks = json!({ "checks": [
+            { "name": "backup", "status": "up" },
+            { "name": "cron", "status": "grace" },
+            { "name": "nas", "status": "down" },
+            { "name": "old", "status": "paused" },
+        ]});
+        assert_eq!(
+            summarize(&checks, &[]),
+            Summary {
+                total: 4,
+                up: 1,
+                grace: 1,
+                down: vec!["nas".to_string()],
+            }
+        );
+        assert_eq!(summarize(&checks, &["backup".to_string()]).total, 1);
+    }
+}
@@ -0,0 +1,229 @@
+//! Counts how long audio has played loudly today, that is while the output volume was above a
+//! threshold, and warns once that exceeds a daily limit, to protect the user's hearing
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::sound::{sound_device, DeviceKind, SoundDevice, SoundDriver};
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// The loud listening of a day
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+struct Exposure {
+    day: i32,
+    seconds: u64,
+}
+
+impl Exposure {
+    /// The exposure of `day`, which starts over if it is a new day
+    fn on(self, day: i32) -> Exposure {
+        if self.day == day {
+            self
+        } else {
+            Exposure {
+                day,
+                ..Exposure::default()
+            }
+        }
+    }
+}
+
+pub struct Hearing {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    device: Box<dyn SoundDevice>,
+    threshold: u32,
+    limit: u64,
+    notify: bool,
+    exposure: Exposure,
+    state_file: PathBuf,
+    /// When the volume was last checked, and whether audio played loudly then
+    last: Option<(Instant, bool)>,
+    /// Loud listening of less than a second that isn't counted yet
+    carry: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HearingConfig {
+    /// Sound driver, like in the sound block
+    pub driver: SoundDriver,
+
+    /// PulseAudio sink name, or ALSA control name
+    pub name: Option<String>,
+
+    /// ALSA device name
+    pub device: Option<String>,
+
+    /// Output volume in percent from which on listening counts as loud
+    pub threshold: u32,
+
+    /// Minutes of loud listening per day after which the block warns
+    pub limit: u64,
+
+    /// Whether to send a notification when the limit is reached
+    pub notify: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for HearingConfig {
+    fn default() -> Self {
+        Self {
+            driver: SoundDriver::default(),
+            name: None,
+            device: None,
+            threshold: 60,
+            limit: 60,
+            notify: true,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Hearing {
+    type Config = HearingConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let mut device = sound_device(
+            block_config.driver,
+            block_config.name,
+            block_config.device,
+            DeviceKind::Sink,
+            false,
+        )?;
+        device.monitor(id, tx_update_request)?;
+
+        let state_file = xdg_state_home().join("i3status-rust/hearing.toml");
+        let exposure = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(Hearing {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("headphones")?,
+            format: block_config.format.with_default("{time}")?,
+            device,
+            threshold: block_config.threshold,
+            limit: block_config.limit * 60,
+            notify: block_config.notify,
+            exposure,
+            state_file,
+            last: None,
+            carry: Duration::ZERO,
+        })
+    }
+}
+
+impl Hearing {
+    fn save(&self) {
+        // Failing to save only means that today's exposure starts over after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&self.exposure) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+}
+
+impl Block for Hearing {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.device.get_info()?;
+        let now = Instant::now();
+        let loud =
+            self.device.playing() && !self.device.muted() && self.device.volume() >= self.threshold;
+
+        let before = self.exposure.on(Local::today().num_days_from_ce());
+        let mut exposure = before;
+        // Updates happen both on a timer and when the volume changes, so count the time since
+        // the last one at the loudness of back then
+        if let Some((last, was_loud)) = self.last {
+            if was_loud {
+                let loud_for = self.carry + now.duration_since(last);
+                exposure.seconds += loud_for.as_secs();
+                self.carry = loud_for - Duration::from_secs(loud_for.as_secs());
+            }
+        }
+        self.last = Some((now, loud));
+        self.exposure = exposure;
+        if exposure.seconds / 60 != before.seconds / 60 {
+            self.save();
+        }
+        if self.notify && before.seconds < self.limit && exposure.seconds >= self.limit {
+            let message = format!(
+                "You listened loudly for {} minutes today, consider turning the volume down.",
+                self.limit / 60
+            );
+            // The block warns anyway, so a missing notify-send doesn't matter
+            let _ = spawn_child_async("notify-send", &[&message]);
+        }
+
+        let minutes = exposure.seconds / 60;
+        let values = map!(
+            "time" => Value::from_string(format!("{}:{:02}", minutes / 60, minutes % 60)),
+            "minutes" => Value::from_integer(minutes as i64),
+            "volume" => Value::from_integer(self.device.volume() as i64).percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text
+            .set_state(match (exposure.seconds >= self.limit, loud) {
+                (true, true) => State::Critical,
+                (true, false) => State::Warning,
+                (false, true) => State::Info,
+                (false, false) => State::Idle,
+            });
+        Ok(Some(Duration::from_secs(10).into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposure_on() {
+        let exposure = Exposure {
+            day: 738_000,
+            seconds: 1800,
+        };
+        assert_eq!(exposure.on(738_000), exposure);
+        assert_eq!(
+            exposure.on(738_001),
+            Exposure {
+                day: 738_001,
+                seconds: 0
+            }
+        );
+    }
+}
@@ -114,7 +114,7 @@ impl ConfigBlock for IBus {
                             let mut engine = engine_copy.lock().unwrap();
                             // see comment in the ibus-engine-monitor thread
                             // TODO: way to restart the other thread with the new IBus address
-                            eprintln!("ibus block: ibus-daemon was restarted, so the block will no longer update");
+                            log::warn!("ibus block: ibus-daemon was restarted, so the block will no longer update");
                             *engine = "ibus restarted so i3status-rs must be restarted!".to_string();
 							send2.send(Task {
 								id,
@@ -125,7 +125,7 @@ impl ConfigBlock for IBus {
 							let mut available = lock.lock().unwrap();
 							*available = true;
 							cvar.notify_one();
-                            eprintln!("ibus block: ibus-daemon has started!");
+                            log::info!("ibus block: ibus-daemon has started!");
 							send2.send(Task {
 						   		id,
 						   		update_time: Instant::now(),
@@ -154,7 +154,7 @@ impl ConfigBlock for IBus {
             let value = &info.0;
             match value.arg_type() {
                 arg::ArgType::String => {
-                    eprintln!("ibus block: global engine not set");
+                    log::info!("ibus block: global engine not set");
                     init_text
                 }
                 arg::ArgType::Struct => {
@@ -305,7 +305,7 @@ fn parse_msg(ci: &ConnectionItem) -> Option<&str> {
 // ```
 fn get_ibus_address() -> Result<String> {
     if let Ok(address) = env::var("IBUS_ADDRESS") {
-        eprintln!("ibus block: using address from $IBUS_ADDRESS ({})", address);
+        log::debug!("ibus block: using address from $IBUS_ADDRESS ({})", address);
         return Ok(address);
     }
 
@@ -315,7 +315,7 @@ fn get_ibus_address() -> Result<String> {
         .output()
         .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
     {
-        eprintln!(
+        log::debug!(
             "ibus block: using address from `ibus address` ({})",
             address
         );
@@ -388,7 +388,7 @@ fn get_ibus_address() -> Result<String> {
     )?;
 
     let address = cap[1].to_string();
-    eprintln!(
+    log::debug!(
         "ibus block: using address from {} ({})",
         socket_path.into_os_string().into_string().unwrap(),
         address
@@ -0,0 +1,279 @@
+//! A toggle that keeps the screen from blanking or locking, e.g. during presentations. The
+//! inhibitor is held with systemd-logind, which passes a file descriptor that inhibits for as long
+//! as it's open. Sway doesn't ask logind, so there the window that has the focus when the block is
+//! clicked is told to inhibit idling instead, which ends by itself when the window is closed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use dbus::arg::OwnedFd;
+use dbus::blocking::SyncConnection;
+use serde_derive::Deserialize;
+#[cfg(feature = "sway")]
+use swayipc::reply::Node;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::Bus;
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleInhibitorDriver {
+    /// Sway if it's running, or else logind
+    Auto,
+    Logind,
+    #[cfg(feature = "sway")]
+    Sway,
+}
+
+enum Inhibitor {
+    /// Inhibits until the file descriptor is closed
+    Logind(Arc<SyncConnection>, Option<OwnedFd>),
+    /// The container ID and name of the window that inhibits
+    #[cfg(feature = "sway")]
+    Sway(Option<(i64, String)>),
+}
+
+#[cfg(feature = "sway")]
+fn sway_command(command: &str) -> Result<()> {
+    let outcomes = swayipc::Connection::new()
+        .block_error("idle_inhibitor", "Failed to connect to sway.")?
+        .run_command(command)
+        .block_error("idle_inhibitor", "Failed to run a sway command.")?;
+    match outcomes.into_iter().find(|outcome| !outcome.success) {
+        Some(outcome) => Err(BlockError(
+            "idle_inhibitor".to_string(),
+            outcome
+                .error
+                .unwrap_or_else(|| "Sway refused to inhibit idling.".to_string()),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The window with the container ID `id`, if it's still open
+#[cfg(feature = "sway")]
+fn find_window(node: &Node, id: i64) -> Option<&Node> {
+    if node.id == id {
+        return Some(node);
+    }
+    node.nodes
+        .iter()
+        .chain(&node.floating_nodes)
+        .find_map(|child| find_window(child, id))
+}
+
+impl Inhibitor {
+    fn held(&self) -> bool {
+        match self {
+            Inhibitor::Logind(_, fd) => fd.is_some(),
+            #[cfg(feature = "sway")]
+            Inhibitor::Sway(window) => window.is_some(),
+        }
+    }
+
+    /// What inhibits idling, for the `{window}` key
+    fn holder(&self) -> String {
+        match self {
+            Inhibitor::Logind(..) => String::new(),
+            #[cfg(feature = "sway")]
+            Inhibitor::Sway(window) => window
+                .as_ref()
+                .map(|(_, name)| name.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn hold(&mut self) -> Result<()> {
+        match self {
+            Inhibitor::Logind(con, fd) => {
+                let (inhibitor,): (OwnedFd,) = con
+                    .with_proxy(
+                        "org.freedesktop.login1",
+                        "/org/freedesktop/login1",
+                        Duration::from_secs(5),
+                    )
+                    .method_call(
+                        "org.freedesktop.login1.Manager",
+                        "Inhibit",
+                        ("idle", "i3status-rust", "Idle inhibitor block", "block"),
+                    )
+                    .block_error("idle_inhibitor", "logind refused to inhibit idling")?;
+                *fd = Some(inhibitor);
+            }
+            #[cfg(feature = "sway")]
+            Inhibitor::Sway(window) => {
+                let tree = swayipc::Connection::new()
+                    .block_error("idle_inhibitor", "Failed to connect to sway.")?
+                    .get_tree()
+                    .block_error("idle_inhibitor", "Failed to get the windows from sway.")?;
+                let focused = tree
+                    .find_as_ref(|node| node.focused)
+                    .filter(|node| node.pid.is_some())
+                    .block_error("idle_inhibitor", "No window has the focus.")?;
+                sway_command(&format!("[con_id={}] inhibit_idle open", focused.id))?;
+                *window = Some((focused.id, focused.name.clone().unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<()> {
+        match self {
+            // Closing the file descriptor ends the inhibitor
+            Inhibitor::Logind(_, fd) => *fd = None,
+            #[cfg(feature = "sway")]
+            Inhibitor::Sway(window) => {
+                if let Some((id, _)) = window.take() {
+                    // The window may be gone already
+                    let _ = sway_command(&format!("[con_id={}] inhibit_idle none", id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the window if it was closed, since that ended the inhibitor
+    fn refresh(&mut self) -> Result<()> {
+        #[cfg(feature = "sway")]
+        if let Inhibitor::Sway(Some((id, _))) = self {
+            let tree = swayipc::Connection::new()
+                .block_error("idle_inhibitor", "Failed to connect to sway.")?
+                .get_tree()
+                .block_error("idle_inhibitor", "Failed to get the windows from sway.")?;
+            if find_window(&tree, *id).is_none() {
+                *self = Inhibitor::Sway(None);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct IdleInhibitor {
+    id: usize,
+    text: TextWidget,
+    format_on: FormatTemplate,
+    format_off: FormatTemplate,
+    inhibitor: Inhibitor,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct IdleInhibitorConfig {
+    pub driver: IdleInhibitorDriver,
+
+    /// Format override while idling is inhibited
+    pub format_on: FormatTemplate,
+
+    /// Format override while it isn't
+    pub format_off: FormatTemplate,
+
+    /// How often to check on the inhibitor while it's held, e.g. whether the window is still open
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for IdleInhibitorConfig {
+    fn default() -> Self {
+        Self {
+            driver: IdleInhibitorDriver::Auto,
+            format_on: FormatTemplate::default(),
+            format_off: FormatTemplate::default(),
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ConfigBlock for IdleInhibitor {
+    type Config = IdleInhibitorConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let driver = match block_config.driver {
+            #[cfg(feature = "sway")]
+            IdleInhibitorDriver::Auto if std::env::var_os("SWAYSOCK").is_some() => {
+                IdleInhibitorDriver::Sway
+            }
+            IdleInhibitorDriver::Auto => IdleInhibitorDriver::Logind,
+            driver => driver,
+        };
+        let inhibitor = match driver {
+            #[cfg(feature = "sway")]
+            IdleInhibitorDriver::Sway => Inhibitor::Sway(None),
+            _ => Inhibitor::Logind(shared_config.buses.get(Bus::System)?, None),
+        };
+
+        Ok(IdleInhibitor {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("idle_inhibitor_off")?,
+            format_on: block_config.format_on.with_default("")?,
+            format_off: block_config.format_off.with_default("")?,
+            inhibitor,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Block for IdleInhibitor {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.inhibitor.refresh()?;
+        let held = self.inhibitor.held();
+        let values = map!(
+            "window" => Value::from_string(self.inhibitor.holder()),
+        );
+        let format = if held {
+            &self.format_on
+        } else {
+            &self.format_off
+        };
+        self.text.set_texts(format.render(&values)?);
+        self.text.set_icon(if held {
+            "idle_inhibitor_on"
+        } else {
+            "idle_inhibitor_off"
+        })?;
+        self.text
+            .set_state(if held { State::Warning } else { State::Idle });
+
+        Ok(held.then_some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            if self.inhibitor.held() {
+                self.inhibitor.release()?;
+            } else {
+                self.inhibitor.hold()?;
+            }
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        // Sway would keep inhibiting for the window
+        self.inhibitor.release()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
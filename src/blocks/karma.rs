@@ -0,0 +1,345 @@
+//! Shows the karma of a Hacker News or Lobsters account and how it changed, and on Hacker News the
+//! replies to recent comments that weren't opened yet. The karma of the last click and the
+//! opened replies are kept in a state file.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Utc;
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const HN_API: &str = "https://hacker-news.firebaseio.com/v0";
+
+/// Hacker News closes comments to replies after two weeks
+const REPLY_WINDOW: i64 = 14 * 86400;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Site {
+    HackerNews,
+    Lobsters,
+}
+
+/// What the block remembers of an account across restarts
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+struct Seen {
+    /// The karma at the last click
+    karma: Option<i64>,
+    /// Replies that were opened or existed before
+    replies: BTreeSet<u64>,
+}
+
+/// What is known of an item of Hacker News that doesn't change anymore
+#[derive(Debug, Clone, Copy)]
+enum Cached {
+    /// A story, or a comment too old to be replied to
+    Closed,
+    Comment {
+        time: i64,
+    },
+}
+
+pub struct Karma {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    site: Site,
+    username: String,
+    comments: usize,
+    open_command: String,
+    interval: Duration,
+    karma: i64,
+    seen: Seen,
+    state_file: PathBuf,
+    items: HashMap<u64, Cached>,
+    /// Replies that weren't opened, newest first, with the comment they reply to
+    unread: Vec<(u64, u64)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KarmaConfig {
+    pub site: Site,
+
+    pub username: String,
+
+    /// How many of the newest submissions are checked for replies
+    #[serde(default = "KarmaConfig::default_comments")]
+    pub comments: usize,
+
+    /// Command that opens the thread of a reply on click
+    #[serde(default = "KarmaConfig::default_open_command")]
+    pub open_command: String,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "KarmaConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl KarmaConfig {
+    fn default_comments() -> usize {
+        10
+    }
+
+    fn default_open_command() -> String {
+        "xdg-open".to_string()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(600)
+    }
+}
+
+impl ConfigBlock for Karma {
+    type Config = KarmaConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let state_file = xdg_state_home().join("i3status-rust/karma.toml");
+        let mut karma = Karma {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{karma} {replies}")?,
+            site: block_config.site,
+            username: block_config.username,
+            comments: block_config.comments,
+            open_command: block_config.open_command,
+            interval: block_config.interval,
+            karma: 0,
+            seen: Seen::default(),
+            state_file,
+            items: HashMap::new(),
+            unread: Vec::new(),
+        };
+        karma.seen = karma.load().remove(&karma.account()).unwrap_or_default();
+        Ok(karma)
+    }
+}
+
+fn get(url: &str) -> Result<JsonValue> {
+    let response = http::http_get_json(url, Some(Duration::from_secs(10)), vec![])?;
+    if response.code != 200 {
+        return Err(BlockError(
+            "karma".to_string(),
+            format!("could not get {} (HTTP {})", url, response.code),
+        ));
+    }
+    Ok(response.content)
+}
+
+/// The replies that weren't seen, newest first and with the comment they reply to. Seen replies
+/// to comments that aren't checked anymore are forgotten, so that the state doesn't grow forever.
+fn unread_replies(replies: &[(u64, Vec<u64>)], seen: &mut BTreeSet<u64>) -> Vec<(u64, u64)> {
+    seen.retain(|reply| replies.iter().any(|(_, kids)| kids.contains(reply)));
+    let mut unread: Vec<(u64, u64)> = replies
+        .iter()
+        .flat_map(|(comment, kids)| kids.iter().map(move |&kid| (kid, *comment)))
+        .filter(|(kid, _)| !seen.contains(kid))
+        .collect();
+    // IDs on Hacker News grow with time
+    unread.sort_by(|a, b| b.cmp(a));
+    unread
+}
+
+impl Karma {
+    fn account(&self) -> String {
+        let site = match self.site {
+            Site::HackerNews => "hackernews",
+            Site::Lobsters => "lobsters",
+        };
+        format!("{}/{}", site, self.username)
+    }
+
+    fn load(&self) -> BTreeMap<String, Seen> {
+        fs::read_to_string(&self.state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        // Failing to save only means that replies show up again after a restart. Other blocks
+        // may share the file, so only this account is replaced.
+        let mut accounts = self.load();
+        accounts.insert(self.account(), self.seen.clone());
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&accounts) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+
+    /// The replies to the recent comments of a Hacker News user, by comment
+    fn hn_replies(&mut self, user: &JsonValue) -> Result<Vec<(u64, Vec<u64>)>> {
+        let now = Utc::now().timestamp();
+        let mut replies = Vec::new();
+        let submitted = user["submitted"].as_array().into_iter().flatten();
+        for id in submitted.filter_map(JsonValue::as_u64).take(self.comments) {
+            match self.items.get(&id) {
+                Some(Cached::Closed) => continue,
+                Some(Cached::Comment { time }) if now - time > REPLY_WINDOW => {
+                    self.items.insert(id, Cached::Closed);
+                    continue;
+                }
+                _ => (),
+            }
+            let item = get(&format!("{}/item/{}.json", HN_API, id))?;
+            let time = item["time"].as_i64().unwrap_or_default();
+            if item["type"] != "comment" || now - time > REPLY_WINDOW {
+                self.items.insert(id, Cached::Closed);
+                continue;
+            }
+            self.items.insert(id, Cached::Comment { time });
+            let kids = item["kids"].as_array().into_iter().flatten();
+            replies.push((id, kids.filter_map(JsonValue::as_u64).collect()));
+        }
+        Ok(replies)
+    }
+
+    /// Gets the karma and the replies
+    fn refresh(&mut self) -> Result<()> {
+        let before = self.seen.clone();
+        match self.site {
+            Site::HackerNews => {
+                let user = get(&format!("{}/user/{}.json", HN_API, self.username))?;
+                if user.is_null() {
+                    return Err(BlockError(
+                        "karma".to_string(),
+                        format!("there is no user {}", self.username),
+                    ));
+                }
+                self.karma = user["karma"].as_i64().unwrap_or_default();
+                let replies = self.hn_replies(&user)?;
+                let first_sight = self.seen.karma.is_none();
+                self.unread = unread_replies(&replies, &mut self.seen.replies);
+                // Replies from before the block was set up don't count as new
+                if first_sight {
+                    self.seen
+                        .replies
+                        .extend(self.unread.drain(..).map(|(reply, _)| reply));
+                }
+            }
+            Site::Lobsters => {
+                let user = get(&format!("https://lobste.rs/~{}.json", self.username))?;
+                self.karma = user["karma"].as_i64().unwrap_or_default();
+            }
+        }
+        self.seen.karma.get_or_insert(self.karma);
+        if self.seen != before {
+            self.save();
+        }
+
+        Ok(())
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let change = self.seen.karma.map_or(0, |karma| self.karma - karma);
+        let values = map!(
+            "username" => Value::from_string(self.username.clone()),
+            "karma" => Value::from_integer(self.karma),
+            "change" => Value::from_string(format!("{:+}", change)),
+            "replies" => Value::from_integer(self.unread.len() as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if self.unread.is_empty() {
+            State::Idle
+        } else {
+            State::Info
+        });
+        Ok(())
+    }
+}
+
+impl Block for Karma {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.refresh() {
+            Ok(()) => self.set_text()?,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, the karma is checked again at the next update
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+            }
+            Err(error) => {
+                self.text.set_text(format!("karma error {}", error));
+                self.text.set_state(State::Critical);
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let url = match (self.site, self.unread.first()) {
+            (Site::HackerNews, Some((_, comment))) => {
+                format!("https://news.ycombinator.com/item?id={}", comment)
+            }
+            (Site::HackerNews, None) => {
+                format!("https://news.ycombinator.com/threads?id={}", self.username)
+            }
+            (Site::Lobsters, _) => format!("https://lobste.rs/threads/{}", self.username),
+        };
+        let command = format!("{} '{}'", self.open_command, url);
+        spawn_child_async("sh", &["-c", &command])
+            .block_error("karma", &format!("failed to run {}", command))?;
+
+        self.seen
+            .replies
+            .extend(self.unread.drain(..).map(|(reply, _)| reply));
+        self.seen.karma = Some(self.karma);
+        self.save();
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unread_replies() {
+        let mut seen: BTreeSet<u64> = [10, 11, 3].iter().copied().collect();
+        let replies = vec![(1, vec![10, 12]), (2, vec![11, 13])];
+        assert_eq!(unread_replies(&replies, &mut seen), vec![(13, 2), (12, 1)]);
+        assert_eq!(seen, [10, 11].iter().copied().collect());
+    }
+}
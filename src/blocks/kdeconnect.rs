@@ -1,19 +1,20 @@
 use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use dbus::arg;
-use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
-use dbus::Message;
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, SyncConnection};
+use dbus::message::{MatchRule, SignalArgs};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::{Bus, Watch};
 use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::util::battery_level_to_icon;
 use crate::widgets::text::TextWidget;
@@ -35,8 +36,13 @@ pub struct KDEConnect {
     bat_critical: i32,
     format: FormatTemplate,
     format_disconnected: FormatTemplate,
+    hide_disconnected: bool,
+    /// Whether the block is hidden because the phone is unreachable
+    hidden: bool,
     output: TextWidget,
     shared_config: SharedConfig,
+    con: Arc<SyncConnection>,
+    _watches: Vec<Watch>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -61,6 +67,9 @@ pub struct KDEConnectConfig {
 
     /// Format string for displaying phone information when it is disconnected.
     pub format_disconnected: FormatTemplate,
+
+    /// Hide the block while the phone is unreachable
+    pub hide_disconnected: bool,
 }
 
 impl Default for KDEConnectConfig {
@@ -73,6 +82,7 @@ impl Default for KDEConnectConfig {
             bat_critical: 15,
             format: FormatTemplate::default(),
             format_disconnected: FormatTemplate::default(),
+            hide_disconnected: false,
         }
     }
 }
@@ -86,17 +96,7 @@ impl ConfigBlock for KDEConnect {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let send2 = send.clone();
-        let send3 = send.clone();
-        let send4 = send.clone();
-        let send5 = send.clone();
-        let send6 = send.clone();
-        let send7 = send.clone();
-
-        let c = Connection::new_session().block_error(
-            "kdeconnect",
-            &"Failed to establish D-Bus connection".to_string(),
-        )?;
+        let c = shared_config.buses.get(Bus::Session)?;
 
         let device_id = if block_config.device_id.is_none() {
             // If none specified in block config, just grab the first device found.
@@ -214,24 +214,15 @@ impl ConfigBlock for KDEConnect {
             notifications
         };
 
-        let device_id_copy = device_id.clone();
         let device_name = Arc::new(Mutex::new(initial_name));
-        let device_name_copy = device_name.clone();
         let charge = Arc::new(Mutex::new(initial_charge));
-        let charge_copy = charge.clone();
         // TODO: revisit this lint
         #[allow(clippy::mutex_atomic)]
         let charging = Arc::new(Mutex::new(initial_charging));
-        let charging_copy = charging.clone();
         let notif_count = Arc::new(Mutex::new(initial_notifications.len() as i32));
-        let notif_count_copy1 = notif_count.clone();
-        let notif_count_copy2 = notif_count.clone();
-        let notif_count_copy3 = notif_count.clone();
         // TODO: revisit this lint
         #[allow(clippy::mutex_atomic)]
         let reachable = Arc::new(Mutex::new(initial_reachable));
-        let reachable_copy1 = reachable.clone();
-        let reachable_copy2 = reachable.clone();
 
         // TODO: See if can reliably get the text and/or app of the most recent notification.
         // Will need to see if the order of notifications is guaranteed or not.
@@ -243,280 +234,156 @@ impl ConfigBlock for KDEConnect {
         //    Arc::new(Mutex::new(initial_notifications.get(0).unwrap().to_string()))
         //};
 
-        thread::Builder::new()
-            .name("kdeconnect".into())
-            .spawn(move || {
-                let c = Connection::new_session()
-                    .expect("Failed to establish D-Bus connection in thread");
-
-                let p1 = c.with_proxy(
-                    "org.kde.kdeconnect",
-                    format!("/modules/kdeconnect/devices/{}", device_id_copy),
-                    Duration::from_millis(5000),
-                );
-
-                let _device_name_handler = p1.match_signal(
-                    move |s: OrgKdeKdeconnectDeviceNameChanged, _: &Connection, _: &Message| {
-                        let mut name = device_name_copy.lock().unwrap();
-                        *name = s.name;
-
-                        // Tell block to update now.
-                        send2
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                        true
+        // Tell block to update now.
+        let update = move || {
+            send.send(Task {
+                id,
+                update_time: Instant::now(),
+            })
+            .is_ok()
+        };
+        let device_path = format!("/modules/kdeconnect/devices/{}", device_id);
+        let battery_path = if old_kdeconnect {
+            device_path.clone()
+        } else {
+            format!("{}/battery", device_path)
+        };
+        let notifications_path = if old_kdeconnect {
+            device_path.clone()
+        } else {
+            format!("{}/notifications", device_path)
+        };
+        let mut watches = Vec::new();
+
+        watches.push({
+            let device_name = device_name.clone();
+            let update = update.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDeviceNameChanged>(&device_path),
+                move |s: OrgKdeKdeconnectDeviceNameChanged, _, _| {
+                    *device_name.lock().unwrap() = s.name;
+                    update()
+                },
+            )?
+        });
+
+        watches.push({
+            let reachable = reachable.clone();
+            let update = update.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDeviceReachableChanged>(&device_path),
+                move |s: OrgKdeKdeconnectDeviceReachableChanged, _, _| {
+                    *reachable.lock().unwrap() = s.reachable;
+                    update()
+                },
+            )?
+        });
+
+        if old_kdeconnect {
+            watches.push({
+                let charging = charging.clone();
+                let update = update.clone();
+                shared_config.buses.watch(
+                    Bus::Session,
+                    rule::<OrgKdeKdeconnectDeviceBatteryStateChanged>(&battery_path),
+                    move |s: OrgKdeKdeconnectDeviceBatteryStateChanged, _, _| {
+                        *charging.lock().unwrap() = s.charging;
+                        // The older KDEConnect emits both stateChanged and chargeChanged
+                        // whenever there is an update regardless of whether or
+                        // not they both changed. So we only need to send updates
+                        // in one of the two battery signal handlers.
+                        update()
                     },
-                );
-
-                let _phone_reachable_handler = p1.match_signal(
-                    move |s: OrgKdeKdeconnectDeviceReachableChanged,
-                          _: &Connection,
-                          _: &Message| {
-                        let mut reachable = reachable_copy1.lock().unwrap();
-                        *reachable = s.reachable;
-
-                        send6
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
+                )?
+            });
 
+            watches.push({
+                let charge = charge.clone();
+                shared_config.buses.watch(
+                    Bus::Session,
+                    rule::<OrgKdeKdeconnectDeviceBatteryChargeChanged>(&battery_path),
+                    move |s: OrgKdeKdeconnectDeviceBatteryChargeChanged, _, _| {
+                        *charge.lock().unwrap() = s.charge;
                         true
                     },
-                );
-
-                if old_kdeconnect {
-                    let _battery_state_handler = p1.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryStateChanged,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charging = charging_copy.lock().unwrap();
-                            *charging = s.charging;
-
-                            // Tell block to update now.
-                            // The older KDEConnect emits both stateChanged and chargeChanged
-                            // whenever there is an update regardless of whether or
-                            // not they both changed. So we only need to send updates
-                            // in one of the two battery signal handlers.
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _battery_charge_handler = p1.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryChargeChanged,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charge = charge_copy.lock().unwrap();
-                            *charge = s.charge;
-
-                            true
-                        },
-                    );
-                } else {
-                    let p2 = c.with_proxy(
-                        "org.kde.kdeconnect",
-                        format!("/modules/kdeconnect/devices/{}/battery", device_id_copy),
-                        Duration::from_millis(5000),
-                    );
-                    let _battery_state_handler = p2.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryRefreshed,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charging = charging_copy.lock().unwrap();
-                            *charging = s.is_charging;
-
-                            let mut charge = charge_copy.lock().unwrap();
-                            *charge = s.charge;
-
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                            true
-                        },
-                    );
-                };
-
-                if old_kdeconnect {
-                    let _notification_added_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationPosted,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy1.lock().unwrap();
-                            *notif_count += 1;
-
-                            // Tell block to update now.
-                            send3
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_removed_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy2.lock().unwrap();
-                            *notif_count = if *notif_count - 1 < 0 {
-                                0
-                            } else {
-                                *notif_count - 1
-                            };
-
-                            // Tell block to update now.
-                            send4
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_all_removed_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy3.lock().unwrap();
-                            *notif_count = 0;
-
-                            // Tell block to update now.
-                            send5
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-                } else {
-                    let p3 = c.with_proxy(
-                        "org.kde.kdeconnect",
-                        format!(
-                            "/modules/kdeconnect/devices/{}/notifications",
-                            device_id_copy
-                        ),
-                        Duration::from_millis(5000),
-                    );
-
-                    let _notification_added_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationPosted,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy1.lock().unwrap();
-                            *notif_count += 1;
-
-                            // Tell block to update now.
-                            send3
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_removed_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy2.lock().unwrap();
-                            *notif_count = if *notif_count - 1 < 0 {
-                                0
-                            } else {
-                                *notif_count - 1
-                            };
-
-                            // Tell block to update now.
-                            send4
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_all_removed_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy3.lock().unwrap();
-                            *notif_count = 0;
-
-                            // Tell block to update now.
-                            send5
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    //if notif_text is ever implemented this may be handy
-                    //OrgKdeKdeconnectDeviceNotificationsNotificationUpdated
-                };
-
-                let p4 = c.with_proxy(
-                    "org.kde.kdeconnect",
-                    "/modules/kdeconnect",
-                    Duration::from_millis(5000),
-                );
-
-                let _phone_visible_handler = p4.match_signal(
-                    move |s: OrgKdeKdeconnectDaemonDeviceVisibilityChanged,
-                          _: &Connection,
-                          _: &Message| {
-                        // TODO: check if s.id matches our device? Is visible same as reachable?
-                        let mut reachable = reachable_copy2.lock().unwrap();
-                        *reachable = s.is_visible;
-
-                        // Tell block to update now.
-                        send7
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                        true
+                )?
+            });
+        } else {
+            watches.push({
+                let charging = charging.clone();
+                let charge = charge.clone();
+                let update = update.clone();
+                shared_config.buses.watch(
+                    Bus::Session,
+                    rule::<OrgKdeKdeconnectDeviceBatteryRefreshed>(&battery_path),
+                    move |s: OrgKdeKdeconnectDeviceBatteryRefreshed, _, _| {
+                        *charging.lock().unwrap() = s.is_charging;
+                        *charge.lock().unwrap() = s.charge;
+                        update()
                     },
-                );
+                )?
+            });
+        }
 
-                loop {
-                    c.process(Duration::from_millis(1000)).unwrap();
-                }
-            })
-            .unwrap();
+        watches.push({
+            let notif_count = notif_count.clone();
+            let update = update.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDeviceNotificationsNotificationPosted>(&notifications_path),
+                move |_: OrgKdeKdeconnectDeviceNotificationsNotificationPosted, _, _| {
+                    *notif_count.lock().unwrap() += 1;
+                    update()
+                },
+            )?
+        });
+
+        watches.push({
+            let notif_count = notif_count.clone();
+            let update = update.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDeviceNotificationsNotificationRemoved>(&notifications_path),
+                move |_: OrgKdeKdeconnectDeviceNotificationsNotificationRemoved, _, _| {
+                    let mut notif_count = notif_count.lock().unwrap();
+                    *notif_count = (*notif_count - 1).max(0);
+                    update()
+                },
+            )?
+        });
+
+        watches.push({
+            let notif_count = notif_count.clone();
+            let update = update.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved>(
+                    &notifications_path,
+                ),
+                move |_: OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved, _, _| {
+                    *notif_count.lock().unwrap() = 0;
+                    update()
+                },
+            )?
+        });
+
+        //if notif_text is ever implemented this may be handy
+        //OrgKdeKdeconnectDeviceNotificationsNotificationUpdated
+
+        watches.push({
+            let reachable = reachable.clone();
+            shared_config.buses.watch(
+                Bus::Session,
+                rule::<OrgKdeKdeconnectDaemonDeviceVisibilityChanged>("/modules/kdeconnect"),
+                move |s: OrgKdeKdeconnectDaemonDeviceVisibilityChanged, _, _| {
+                    // TODO: check if s.id matches our device? Is visible same as reachable?
+                    *reachable.lock().unwrap() = s.is_visible;
+                    update()
+                },
+            )?
+        });
 
         Ok(KDEConnect {
             id,
@@ -536,12 +403,22 @@ impl ConfigBlock for KDEConnect {
                 .format
                 .with_default("{name} {bat_icon}{bat_charge} {notif_icon}{notif_count}")?,
             format_disconnected: block_config.format_disconnected.with_default("{name}")?,
+            hide_disconnected: block_config.hide_disconnected,
+            hidden: false,
             output: TextWidget::new(id, 0, shared_config.clone()).with_icon("phone")?,
             shared_config,
+            con: c,
+            _watches: watches,
         })
     }
 }
 
+/// Matches a signal of the device by its object path, as signals come from the unique name of the
+/// daemon
+fn rule<S: SignalArgs>(path: &str) -> MatchRule<'static> {
+    S::match_rule(None, Some(&path.into())).static_clone()
+}
+
 impl Block for KDEConnect {
     fn id(&self) -> usize {
         self.id
@@ -596,7 +473,7 @@ impl Block for KDEConnect {
                     match self.shared_config.get_icon("bat_10") {
                         Ok(_) => false,
                         Err(_) => {
-                            eprintln!(
+                            log::warn!(
                                 "Icon bat_10 not found in your icons file. Please check NEWS.md"
                             );
                             true
@@ -645,6 +522,7 @@ impl Block for KDEConnect {
             });
         }
 
+        self.hidden = self.hide_disconnected && !phone_reachable;
         if !phone_reachable {
             self.output.set_state(State::Critical);
             self.output.set_icon("phone_disconnected")?;
@@ -658,9 +536,32 @@ impl Block for KDEConnect {
         Ok(None)
     }
 
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        // Ring the phone to find it
+        let p = self.con.with_proxy(
+            "org.kde.kdeconnect",
+            format!("/modules/kdeconnect/devices/{}/findmyphone", self.device_id),
+            Duration::from_millis(5000),
+        );
+        let _: () = p
+            .method_call("org.kde.kdeconnect.device.findmyphone", "ring", ())
+            .block_error(
+                "kdeconnect",
+                "Couldn't ring the phone, is Find My Phone enabled?",
+            )?;
+        Ok(())
+    }
+
     // Returns the view of the block, comprised of widgets.
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+        if self.hidden {
+            vec![]
+        } else {
+            vec![&self.output]
+        }
     }
 }
 
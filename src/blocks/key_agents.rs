@@ -0,0 +1,156 @@
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct KeyAgents {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct KeyAgentsConfig {
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for KeyAgentsConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Counts the keys with a cached passphrase in the output of `KEYINFO --list`
+fn count_cached_gpg_keys(keyinfo: &str) -> usize {
+    // S KEYINFO <keygrip> <type> <serialno> <idstr> <cached> <protection> ...
+    keyinfo
+        .lines()
+        .filter(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            fields.get(..2) == Some(&["S", "KEYINFO"]) && fields.get(6) == Some(&"1")
+        })
+        .count()
+}
+
+fn cached_gpg_keys() -> usize {
+    Command::new("gpg-connect-agent")
+        .arg("--no-autostart")
+        .arg("KEYINFO --list")
+        .arg("/bye")
+        .output()
+        .map(|output| count_cached_gpg_keys(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(0)
+}
+
+/// Counts the identities of the ssh-agent, which exits with 1 if it has none and 2 if it isn't
+/// running
+fn ssh_identities() -> usize {
+    match Command::new("ssh-add").arg("-l").output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).lines().count()
+        }
+        _ => 0,
+    }
+}
+
+impl ConfigBlock for KeyAgents {
+    type Config = KeyAgentsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(KeyAgents {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("security")?,
+            update_interval: block_config.interval,
+            format: block_config.format.with_default("gpg {gpg} ssh {ssh}")?,
+        })
+    }
+}
+
+impl Block for KeyAgents {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let gpg = cached_gpg_keys();
+        let ssh = ssh_identities();
+
+        let values = map!(
+            "gpg" => Value::from_integer(gpg as i64),
+            "ssh" => Value::from_integer(ssh as i64),
+            "total" => Value::from_integer((gpg + ssh) as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if gpg + ssh > 0 {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            // Either agent may be missing, so failures are ignored. Restarting gpg-agent is the
+            // only way to make it forget all cached passphrases.
+            let _ = Command::new("gpg-connect-agent")
+                .arg("--no-autostart")
+                .arg("RELOADAGENT")
+                .arg("/bye")
+                .output();
+            let _ = Command::new("ssh-add").arg("-D").output();
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_cached_gpg_keys() {
+        let keyinfo = concat!(
+            "S KEYINFO 0D3A1B8BB3E6F2F1C71C0F32A9F2B0C5D9E1A3B7 D - - 1 P - - -\n",
+            "S KEYINFO 6E2B3F63A4CCB1A2B6A5B54C61A3F8B9D0E2C4A1 D - - - P - - -\n",
+            "S KEYINFO 9A1C3E5B7D9F1A3C5E7B9D1F3A5C7E9B1D3F5A7C T D2760001240102 OPENPGP.1 1 - - - -\n",
+            "OK\n",
+        );
+        assert_eq!(count_cached_gpg_keys(keyinfo), 2);
+        assert_eq!(count_cached_gpg_keys("OK\n"), 0);
+        assert_eq!(count_cached_gpg_keys(""), 0);
+    }
+}
@@ -1,18 +1,27 @@
 use std::collections::HashMap;
 use std::process::Command;
+#[cfg(any(feature = "dbus", feature = "sway"))]
 use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "dbus", feature = "sway"))]
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+#[cfg(any(feature = "dbus", feature = "sway"))]
+use std::time::Instant;
 
 use crossbeam_channel::Sender;
+#[cfg(feature = "dbus")]
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+#[cfg(feature = "dbus")]
 use dbus::{
     ffidisp::{MsgHandlerResult, MsgHandlerType},
     Message,
 };
 use serde_derive::Deserialize;
+#[cfg(feature = "sway")]
 use swayipc::reply::Event;
+#[cfg(feature = "sway")]
 use swayipc::reply::InputChange;
+#[cfg(feature = "sway")]
 use swayipc::{Connection, EventType};
 
 use crate::blocks::{Block, ConfigBlock, Update};
@@ -21,6 +30,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
@@ -28,6 +38,8 @@ use crate::widgets::I3BarWidget;
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum KeyboardLayoutDriver {
+    /// Sway if it's running, or else setxkbmap
+    Auto,
     SetXkbMap,
     LocaleBus,
     KbddBus,
@@ -48,6 +60,14 @@ pub trait KeyboardLayoutMonitor {
     /// Monitor layout changes and send updates via the `update_request`
     /// channel. By default, this method does nothing.
     fn monitor(&self, _id: usize, _update_request: Sender<Task>) {}
+
+    /// Switch to the next layout. By default, the driver can't.
+    fn next_layout(&self) -> Result<()> {
+        Err(BlockError(
+            "keyboard_layout".to_string(),
+            "This driver can't switch layouts.".to_string(),
+        ))
+    }
 }
 
 pub struct SetXkbMap;
@@ -59,32 +79,43 @@ impl SetXkbMap {
     }
 }
 
-fn setxkbmap_layouts() -> Result<String> {
-    let output = Command::new("setxkbmap")
+fn setxkbmap_query() -> Result<String> {
+    Command::new("setxkbmap")
         .args(&["-query"])
         .output()
         .block_error("keyboard_layout", "Failed to execute setxkbmap.")
         .and_then(|raw| {
             String::from_utf8(raw.stdout).block_error("keyboard_layout", "Non-UTF8 input.")
-        })?;
+        })
+}
 
-    // Find the "layout:    xxxx" entry.
-    let layout = output
+/// The value of an entry like "layout:    xxxx" in the output of `setxkbmap -query`
+fn setxkbmap_entry(output: &str, entry: &str) -> Option<String> {
+    output
         .split('\n')
-        .find(|line| line.starts_with("layout"))
-        .block_error(
-            "keyboard_layout",
-            "Could not find the layout entry from setxkbmap.",
-        )?
+        .find(|line| line.starts_with(entry))?
         .split(char::is_whitespace)
-        .last();
+        .last()
+        .map(|s| s.to_string())
+}
 
-    layout.map(|s| s.to_string()).block_error(
+fn setxkbmap_layouts() -> Result<String> {
+    let output = setxkbmap_query()?;
+    setxkbmap_entry(&output, "layout").block_error(
         "keyboard_layout",
-        "Could not read the layout entry from setxkbmap.",
+        "Could not find the layout entry from setxkbmap.",
     )
 }
 
+/// Moves the first of comma-separated layouts or variants to the end, which makes the next one
+/// the active one for setxkbmap
+fn rotate(list: &str) -> String {
+    match list.split_once(',') {
+        Some((first, rest)) => format!("{},{}", rest, first),
+        None => list.to_string(),
+    }
+}
+
 impl KeyboardLayoutMonitor for SetXkbMap {
     fn keyboard_layout(&self) -> Result<String> {
         setxkbmap_layouts()
@@ -98,12 +129,32 @@ impl KeyboardLayoutMonitor for SetXkbMap {
     fn must_poll(&self) -> bool {
         true
     }
+
+    fn next_layout(&self) -> Result<()> {
+        let output = setxkbmap_query()?;
+        let layouts = setxkbmap_entry(&output, "layout").block_error(
+            "keyboard_layout",
+            "Could not find the layout entry from setxkbmap.",
+        )?;
+        let mut args = vec!["-layout".to_string(), rotate(&layouts)];
+        if let Some(variants) = setxkbmap_entry(&output, "variant") {
+            args.push("-variant".to_string());
+            args.push(rotate(&variants));
+        }
+        Command::new("setxkbmap")
+            .args(&args)
+            .status()
+            .block_error("keyboard_layout", "Failed to execute setxkbmap.")?;
+        Ok(())
+    }
 }
 
+#[cfg(feature = "dbus")]
 pub struct LocaleBus {
     con: dbus::ffidisp::Connection,
 }
 
+#[cfg(feature = "dbus")]
 impl LocaleBus {
     pub fn new() -> Result<Self> {
         let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
@@ -113,6 +164,7 @@ impl LocaleBus {
     }
 }
 
+#[cfg(feature = "dbus")]
 impl KeyboardLayoutMonitor for LocaleBus {
     fn keyboard_layout(&self) -> Result<String> {
         self.con
@@ -172,11 +224,13 @@ impl KeyboardLayoutMonitor for LocaleBus {
 // KbdDaemonBus - use this option if you have kbdd running (https://github.com/qnikst/kbdd,
 // also available in AUR and Debian) running, which enables per window keyboard layout,
 // really handy for dual-language typists who often change window focus
+#[cfg(feature = "dbus")]
 pub struct KbdDaemonBus {
     // extracted from kbdd dbus message
     kbdd_layout_id: Arc<Mutex<u32>>,
 }
 
+#[cfg(feature = "dbus")]
 impl KbdDaemonBus {
     pub fn new() -> Result<Self> {
         Command::new("setxkbmap")
@@ -217,6 +271,7 @@ impl KbdDaemonBus {
     }
 }
 
+#[cfg(feature = "dbus")]
 impl KeyboardLayoutMonitor for KbdDaemonBus {
     fn keyboard_layout(&self) -> Result<String> {
         let layouts_str = setxkbmap_layouts()?;
@@ -249,6 +304,21 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
         false
     }
 
+    fn next_layout(&self) -> Result<()> {
+        let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
+            .block_error("kbddaemonbus", "can't connect to dbus")?;
+        let msg = Message::new_method_call(
+            "ru.gentoo.KbddService",
+            "/ru/gentoo/KbddService",
+            "ru.gentoo.kbdd",
+            "next_layout",
+        )
+        .block_error("kbddaemonbus", "Create next-layout message failure")?;
+        c.send_with_reply_and_block(msg, 5000)
+            .block_error("kbddaemonbus", "Is kbdd running?")?;
+        Ok(())
+    }
+
     // Monitor KbdDaemon 'layoutChanged' property in a separate thread and send updates
     // via the `update_request` channel.
     fn monitor(&self, id: usize, update_request: Sender<Task>) {
@@ -286,8 +356,10 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
     }
 }
 
+#[cfg(feature = "dbus")]
 struct KbddMessageHandler(Arc<Mutex<u32>>);
 
+#[cfg(feature = "dbus")]
 impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
     fn handler_type(&self) -> MsgHandlerType {
         dbus::ffidisp::MsgHandlerType::MsgType(dbus::MessageType::Signal)
@@ -308,10 +380,13 @@ impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
     }
 }
 
+#[cfg(feature = "sway")]
 pub struct Sway {
     sway_kb_layout: Arc<Mutex<String>>,
+    sway_kb_identifier: Option<String>,
 }
 
+#[cfg(feature = "sway")]
 impl Sway {
     pub fn new(sway_kb_identifier: Option<String>) -> Result<Self> {
         let layout = swayipc::Connection::new()
@@ -331,10 +406,12 @@ impl Sway {
 
         Ok(Sway {
             sway_kb_layout: Arc::new(Mutex::new(layout)),
+            sway_kb_identifier,
         })
     }
 }
 
+#[cfg(feature = "sway")]
 impl KeyboardLayoutMonitor for Sway {
     fn keyboard_layout(&self) -> Result<String> {
         // Layout is either `layout (varinat)` or `layout`
@@ -365,6 +442,26 @@ impl KeyboardLayoutMonitor for Sway {
         false
     }
 
+    fn next_layout(&self) -> Result<()> {
+        let input = match &self.sway_kb_identifier {
+            Some(identifier) => format!("\"{}\"", identifier),
+            None => "type:keyboard".to_string(),
+        };
+        let outcomes = Connection::new()
+            .block_error("sway", "Failed to connect to sway.")?
+            .run_command(format!("input {} xkb_switch_layout next", input))
+            .block_error("sway", "Failed to switch the layout.")?;
+        match outcomes.into_iter().find(|outcome| !outcome.success) {
+            Some(outcome) => Err(BlockError(
+                "sway".to_string(),
+                outcome
+                    .error
+                    .unwrap_or_else(|| "Failed to switch the layout.".to_string()),
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// Monitor layout changes in a separate thread and send updates
     /// via the `update_request` channel.
     fn monitor(&self, id: usize, update_request: Sender<Task>) {
@@ -426,16 +523,31 @@ pub struct KeyboardLayoutConfig {
 
     // Used to ovrreride long layout names: "German (dead acute)" => "DE"
     mappings: Option<HashMap<String, String>>,
+
+    /// Icons and formats of layouts, by "layout (variant)" or just the layout
+    layouts: HashMap<String, LayoutConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Name of the icon to show while the layout is active
+    pub icon: Option<String>,
+
+    /// Format override for this layout
+    #[serde(default)]
+    pub format: Option<FormatTemplate>,
 }
 
 impl Default for KeyboardLayoutConfig {
     fn default() -> Self {
         Self {
             format: FormatTemplate::default(),
-            driver: KeyboardLayoutDriver::SetXkbMap,
+            driver: KeyboardLayoutDriver::Auto,
             interval: Duration::from_secs(60),
             sway_kb_identifier: None,
             mappings: None,
+            layouts: HashMap::new(),
         }
     }
 }
@@ -447,34 +559,54 @@ pub struct KeyboardLayout {
     update_interval: Option<Duration>,
     format: FormatTemplate,
     mappings: Option<HashMap<String, String>>,
+    layouts: HashMap<String, LayoutConfig>,
 }
 
 impl ConfigBlock for KeyboardLayout {
     type Config = KeyboardLayoutConfig;
 
+    #[cfg_attr(not(any(feature = "dbus", feature = "sway")), allow(unused_variables))]
     fn new(
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let monitor: Box<dyn KeyboardLayoutMonitor> = match block_config.driver {
+        let driver = match block_config.driver {
+            #[cfg(feature = "sway")]
+            KeyboardLayoutDriver::Auto if std::env::var_os("SWAYSOCK").is_some() => {
+                KeyboardLayoutDriver::Sway
+            }
+            KeyboardLayoutDriver::Auto => KeyboardLayoutDriver::SetXkbMap,
+            driver => driver,
+        };
+        let monitor: Box<dyn KeyboardLayoutMonitor> = match driver {
             KeyboardLayoutDriver::SetXkbMap => Box::new(SetXkbMap::new()?),
+            #[cfg(feature = "dbus")]
             KeyboardLayoutDriver::LocaleBus => {
                 let monitor = LocaleBus::new()?;
                 monitor.monitor(id, send);
                 Box::new(monitor)
             }
+            #[cfg(feature = "dbus")]
             KeyboardLayoutDriver::KbddBus => {
                 let monitor = KbdDaemonBus::new()?;
                 monitor.monitor(id, send);
                 Box::new(monitor)
             }
+            #[cfg(feature = "sway")]
             KeyboardLayoutDriver::Sway => {
                 let monitor = Sway::new(block_config.sway_kb_identifier)?;
                 monitor.monitor(id, send);
                 Box::new(monitor)
             }
+            #[allow(unreachable_patterns)]
+            driver => {
+                return Err(BlockError(
+                    "keyboard_layout".to_string(),
+                    format!("The {:?} driver isn't available in this build.", driver),
+                ))
+            }
         };
         let update_interval = if monitor.must_poll() {
             Some(block_config.interval)
@@ -489,6 +621,7 @@ impl ConfigBlock for KeyboardLayout {
             update_interval,
             format: block_config.format.with_default("{layout}")?,
             mappings: block_config.mappings,
+            layouts: block_config.layouts,
         })
     }
 }
@@ -501,6 +634,17 @@ impl Block for KeyboardLayout {
     fn update(&mut self) -> Result<Option<Update>> {
         let mut layout = self.monitor.keyboard_layout()?;
         let variant = self.monitor.keyboard_variant()?;
+        let layouts = &self.layouts;
+        let layout_config = layouts
+            .get(&format!("{} ({})", layout, variant))
+            .or_else(|| layouts.get(&layout));
+        match layout_config.and_then(|config| config.icon.as_ref()) {
+            Some(icon) => self.output.set_icon(icon)?,
+            None => self.output.unset_icon(),
+        }
+        let format = layout_config
+            .and_then(|config| config.format.as_ref())
+            .unwrap_or(&self.format);
         if let Some(ref mappings) = self.mappings {
             if let Some(mapped) = mappings.get(&format!("{} ({})", layout, variant)) {
                 layout = mapped.to_string();
@@ -511,11 +655,37 @@ impl Block for KeyboardLayout {
             "variant" => Value::from_string(variant)
         );
 
-        self.output.set_texts(self.format.render(&values)?);
+        self.output.set_texts(format.render(&values)?);
         Ok(self.update_interval.map(|d| d.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.output]
     }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.monitor.next_layout()?;
+            // Drivers that are polled don't hear of the switch otherwise
+            if self.monitor.must_poll() {
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setxkbmap() {
+        let output =
+            "rules:      evdev\nmodel:      pc105\nlayout:     us,de\nvariant:    ,nodeadkeys\n";
+        assert_eq!(setxkbmap_entry(output, "layout"), Some("us,de".to_string()));
+        assert_eq!(rotate("us,de"), "de,us");
+        assert_eq!(rotate(",nodeadkeys"), "nodeadkeys,");
+        assert_eq!(rotate("us"), "us");
+    }
 }
@@ -0,0 +1,243 @@
+//! Shows the context and namespace that kubectl currently uses, and optionally how many pods in
+//! that namespace aren't ready, so that commands don't end up on the wrong cluster
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::util::wildcard_match;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Where kubectl points at
+#[derive(Debug, Clone, PartialEq)]
+struct Context {
+    name: String,
+    cluster: String,
+    server: String,
+    namespace: String,
+}
+
+pub struct Kubernetes {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_none: FormatTemplate,
+    pods: bool,
+    critical_contexts: Vec<String>,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct KubernetesConfig {
+    /// Ask the API server how many pods in the namespace aren't ready
+    pub pods: bool,
+
+    /// Wildcard patterns of contexts, like "prod-*", for which the block is shown as critical
+    pub critical_contexts: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Format override while no context is set
+    pub format_none: FormatTemplate,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            pods: false,
+            critical_contexts: Vec::new(),
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+            format_none: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Kubernetes {
+    type Config = KubernetesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Kubernetes {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("kubernetes")?,
+            format: block_config.format.with_default("{context}:{namespace}")?,
+            format_none: block_config.format_none.with_default("none")?,
+            pods: block_config.pods,
+            critical_contexts: block_config.critical_contexts,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+/// The current context in the output of `kubectl config view --minify -o json`, which leaves
+/// out everything but the current context and its cluster
+fn parse_context(config: &JsonValue) -> Option<Context> {
+    let name = config["current-context"]
+        .as_str()
+        .filter(|name| !name.is_empty())?;
+    let context = &config["contexts"][0]["context"];
+    Some(Context {
+        name: name.to_string(),
+        cluster: context["cluster"].as_str().unwrap_or_default().to_string(),
+        server: config["clusters"][0]["cluster"]["server"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        namespace: context["namespace"]
+            .as_str()
+            .unwrap_or("default")
+            .to_string(),
+    })
+}
+
+/// The pods in the output of `kubectl get pods -o json` that should run but aren't ready, and all
+/// of them
+fn count_not_ready(pods: &JsonValue) -> (usize, usize) {
+    let pods = pods["items"].as_array().map_or(&[][..], Vec::as_slice);
+    let not_ready = pods
+        .iter()
+        // Pods of finished jobs are never ready
+        .filter(|pod| pod["status"]["phase"] != "Succeeded")
+        .filter(|pod| {
+            let containers = pod["status"]["containerStatuses"].as_array();
+            pod["status"]["phase"] != "Running"
+                || containers.is_none_or(|containers| {
+                    containers
+                        .iter()
+                        .any(|container| container["ready"] != true)
+                })
+        })
+        .count();
+    (not_ready, pods.len())
+}
+
+impl Kubernetes {
+    fn kubectl(&self, arguments: &str) -> Result<Option<JsonValue>> {
+        let output = self.command_runner.run(&format!("kubectl {}", arguments))?;
+        if output.status != Some(0) {
+            return Ok(None);
+        }
+        serde_json::from_str(&output.stdout)
+            .block_error("kubernetes", "unexpected output of kubectl")
+            .map(Some)
+    }
+}
+
+impl Block for Kubernetes {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Fails while there is no current context
+        let context = self
+            .kubectl("config view --minify -o json")?
+            .as_ref()
+            .and_then(parse_context);
+        let context = match context {
+            Some(context) => context,
+            None => {
+                let values = map!("context" => Value::from_string(String::new()));
+                self.text.set_texts(self.format_none.render(&values)?);
+                self.text.set_state(State::Idle);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+
+        let pods = if self.pods {
+            self.kubectl(&format!(
+                "get pods --request-timeout=5s -o json -n '{}'",
+                context.namespace
+            ))?
+            .map(|pods| count_not_ready(&pods))
+        } else {
+            None
+        };
+
+        let critical = self
+            .critical_contexts
+            .iter()
+            .any(|pattern| wildcard_match(pattern, &context.name));
+        let count = |count: Option<usize>| match count {
+            Some(count) => Value::from_integer(count as i64),
+            None => Value::from_string("-".to_string()),
+        };
+        let values = map!(
+            "context" => Value::from_string(context.name),
+            "cluster" => Value::from_string(context.cluster),
+            "server" => Value::from_string(context.server),
+            "namespace" => Value::from_string(context.namespace),
+            "not_ready" => count(pods.map(|(not_ready, _)| not_ready)),
+            "pods" => count(pods.map(|(_, pods)| pods)),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if critical {
+            State::Critical
+        } else if pods.is_some_and(|(not_ready, _)| not_ready > 0) {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = serde_json::json!({
+            "current-context": "prod-eu",
+            "contexts": [{"name": "prod-eu", "context": {"cluster": "eks-eu-west-1", "user": "admin"}}],
+            "clusters": [{"name": "eks-eu-west-1", "cluster": {"server": "https://10.0.0.1"}}]
+        });
+        assert_eq!(
+            parse_context(&config),
+            Some(Context {
+                name: "prod-eu".to_string(),
+                cluster: "eks-eu-west-1".to_string(),
+                server: "https://10.0.0.1".to_string(),
+                namespace: "default".to_string(),
+            })
+        );
+
+        let pods = serde_json::json!({"items": [
+            {"status": {"phase": "Running", "containerStatuses": [{"ready": true}, {"ready": true}]}},
+            {"status": {"phase": "Running", "containerStatuses": [{"ready": true}, {"ready": false}]}},
+            {"status": {"phase": "Pending"}},
+            {"status": {"phase": "Succeeded", "containerStatuses": [{"ready": false}]}}
+        ]});
+        assert_eq!(count_not_ready(&pods), (2, 4));
+    }
+}
@@ -0,0 +1,260 @@
+//! Shows the IP addresses of running libvirt VMs, as reported by `virsh domifaddr`, which asks the
+//! QEMU guest agent inside the VMs by default
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::{spawn_child_async, CommandRunner};
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Where `virsh domifaddr` gets the addresses from
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressSource {
+    /// The QEMU guest agent
+    Agent,
+    /// The DHCP leases of libvirt's networks
+    Lease,
+    /// The ARP table of the host
+    Arp,
+}
+
+impl AddressSource {
+    fn name(self) -> &'static str {
+        match self {
+            AddressSource::Agent => "agent",
+            AddressSource::Lease => "lease",
+            AddressSource::Arp => "arp",
+        }
+    }
+}
+
+struct Vm {
+    domain: String,
+    addresses: Vec<String>,
+}
+
+pub struct Libvirt {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    vms: Vec<Vm>,
+    format: FormatTemplate,
+    uri: String,
+    domains: Vec<String>,
+    source: AddressSource,
+    ipv6: bool,
+    command: Option<String>,
+    interval: Duration,
+    shared_config: SharedConfig,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct LibvirtConfig {
+    /// URI of the hypervisor
+    pub uri: String,
+
+    /// Names of the VMs to show. All running VMs are shown if empty.
+    pub domains: Vec<String>,
+
+    pub source: AddressSource,
+
+    /// Show IPv6 addresses as well as IPv4 ones
+    pub ipv6: bool,
+
+    /// Shell command run on left click, with `{ip}` replaced by the address of the VM
+    pub command: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for LibvirtConfig {
+    fn default() -> Self {
+        Self {
+            uri: "qemu:///system".to_string(),
+            domains: Vec::new(),
+            source: AddressSource::Agent,
+            ipv6: false,
+            command: None,
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Libvirt {
+    type Config = LibvirtConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Libvirt {
+            id,
+            widgets: Vec::new(),
+            vms: Vec::new(),
+            format: block_config.format.with_default("{domain} {ip}")?,
+            uri: block_config.uri,
+            domains: block_config.domains,
+            source: block_config.source,
+            ipv6: block_config.ipv6,
+            command: block_config.command,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner.clone(),
+            shared_config,
+        })
+    }
+}
+
+/// The addresses of the lines of `virsh domifaddr` like
+/// " eth0       52:54:00:8c:bb:12    ipv4         192.168.122.45/24", without the prefix length and
+/// without loopback and link-local addresses
+fn parse_addresses(output: &str, ipv6: bool) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [_, _, "ipv4", address] => Some(address),
+                [_, _, "ipv6", address] if ipv6 => Some(address),
+                _ => None,
+            }
+        })
+        .map(|address| address.split('/').next().unwrap_or(address))
+        .filter(|address| !address.starts_with("127.") && *address != "::1")
+        .filter(|address| !address.starts_with("fe80:"))
+        .map(String::from)
+        .collect()
+}
+
+impl Libvirt {
+    fn virsh(&self, arguments: &str) -> Result<String> {
+        let command = format!("virsh -c '{}' {}", self.uri, arguments);
+        let output = self.command_runner.run(&command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "libvirt".to_string(),
+                format!("{} failed", command),
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    fn running_vms(&self) -> Result<Vec<Vm>> {
+        let running = self.virsh("list --name")?;
+        let running: Vec<&str> = running.lines().filter(|name| !name.is_empty()).collect();
+        let domains: Vec<&str> = if self.domains.is_empty() {
+            running
+        } else {
+            self.domains
+                .iter()
+                .map(String::as_str)
+                .filter(|domain| running.contains(domain))
+                .collect()
+        };
+        Ok(domains
+            .into_iter()
+            .map(|domain| Vm {
+                domain: domain.to_string(),
+                // Fails while the guest agent hasn't started yet
+                addresses: self
+                    .virsh(&format!(
+                        "domifaddr --source {} '{}'",
+                        self.source.name(),
+                        domain
+                    ))
+                    .map(|output| parse_addresses(&output, self.ipv6))
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+}
+
+impl Block for Libvirt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.vms = self.running_vms()?;
+        self.widgets = Vec::with_capacity(self.vms.len());
+        for (instance, vm) in self.vms.iter().enumerate() {
+            let values = map!(
+                "domain" => Value::from_string(vm.domain.clone()),
+                "ip" => Value::from_string(vm.addresses.first().cloned().unwrap_or_default()),
+                "ips" => Value::from_string(vm.addresses.join(" ")),
+            );
+            let mut widget = TextWidget::new(self.id, instance, self.shared_config.clone());
+            if instance == 0 {
+                widget = widget.with_icon("cpu")?;
+            }
+            widget.set_texts(self.format.render(&values)?);
+            widget.set_state(if vm.addresses.is_empty() {
+                State::Warning
+            } else {
+                State::Idle
+            });
+            self.widgets.push(widget);
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let vm = event.instance.and_then(|instance| self.vms.get(instance));
+        if let (Some(command), Some(ip)) = (&self.command, vm.and_then(|vm| vm.addresses.first())) {
+            let command = command.replace("{ip}", ip);
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("libvirt", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_addresses() {
+        let output = " Name       MAC address          Protocol     Address
+-------------------------------------------------------------------------------
+ lo         00:00:00:00:00:00    ipv4         127.0.0.1/8
+ -          -                    ipv6         ::1/128
+ eth0       52:54:00:8c:bb:12    ipv4         192.168.122.45/24
+ -          -                    ipv6         fd00::45/64
+ -          -                    ipv6         fe80::5054:ff:fe8c:bb12/64
+";
+        assert_eq!(parse_addresses(output, false), vec!["192.168.122.45"]);
+        assert_eq!(
+            parse_addresses(output, true),
+            vec!["192.168.122.45", "fd00::45"]
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Shows which of a set of Twitch or YouTube channels are streaming live
+
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TWITCH_CLIENT_ID_ENV: &str = "I3RS_TWITCH_CLIENT_ID";
+const TWITCH_TOKEN_ENV: &str = "I3RS_TWITCH_TOKEN";
+const YOUTUBE_API_KEY_ENV: &str = "I3RS_YOUTUBE_API_KEY";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum StreamService {
+    Twitch {
+        #[serde(default = "StreamService::getenv_twitch_client_id")]
+        client_id: Option<String>,
+        #[serde(default = "StreamService::getenv_twitch_token")]
+        token: Option<String>,
+    },
+    YouTube {
+        #[serde(default = "StreamService::getenv_youtube_api_key")]
+        api_key: Option<String>,
+    },
+}
+
+impl StreamService {
+    fn getenv_twitch_client_id() -> Option<String> {
+        env::var(TWITCH_CLIENT_ID_ENV).ok()
+    }
+    fn getenv_twitch_token() -> Option<String> {
+        env::var(TWITCH_TOKEN_ENV).ok()
+    }
+    fn getenv_youtube_api_key() -> Option<String> {
+        env::var(YOUTUBE_API_KEY_ENV).ok()
+    }
+}
+
+/// A channel that is live
+#[derive(Debug, Clone, PartialEq)]
+struct Stream {
+    channel: String,
+    title: String,
+    url: String,
+    viewers: u64,
+}
+
+pub struct LiveStreams {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    service: StreamService,
+    channels: Vec<String>,
+    interval: Duration,
+    hide_if_none: bool,
+    open_command: String,
+    streams: Vec<Stream>,
+    /// Whether the streams couldn't be fetched at the last update
+    failed: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LiveStreamsConfig {
+    /// Twitch or YouTube and how to access its API
+    pub service: StreamService,
+
+    /// Twitch logins or YouTube channel IDs
+    pub channels: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "LiveStreamsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Hide the block while none of the channels is live
+    #[serde(default)]
+    pub hide_if_none: bool,
+
+    /// Command that opens the URL of a stream, which is appended
+    #[serde(default = "LiveStreamsConfig::default_open_command")]
+    pub open_command: String,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl LiveStreamsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_open_command() -> String {
+        "xdg-open".to_string()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TwitchStreams {
+    data: Vec<TwitchStream>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TwitchStream {
+    user_login: String,
+    user_name: String,
+    title: String,
+    viewer_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct YouTubeSearch {
+    #[serde(default)]
+    items: Vec<YouTubeItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct YouTubeItem {
+    id: YouTubeId,
+    snippet: YouTubeSnippet,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YouTubeId {
+    video_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YouTubeSnippet {
+    channel_title: String,
+    title: String,
+}
+
+impl ConfigBlock for LiveStreams {
+    type Config = LiveStreamsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.channels.is_empty() {
+            return Err(ConfigurationError(
+                "live_streams".to_string(),
+                "channels must be set".to_string(),
+            ));
+        }
+        match &block_config.service {
+            StreamService::Twitch {
+                client_id: None, ..
+            }
+            | StreamService::Twitch { token: None, .. } => {
+                return Err(ConfigurationError(
+                    "live_streams".to_string(),
+                    format!(
+                        "Missing member 'service.client_id' or 'service.token'. Add the members or configure with the environment variables {} and {}",
+                        TWITCH_CLIENT_ID_ENV, TWITCH_TOKEN_ENV
+                    ),
+                ))
+            }
+            StreamService::YouTube { api_key: None } => {
+                return Err(ConfigurationError(
+                    "live_streams".to_string(),
+                    format!(
+                        "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+                        YOUTUBE_API_KEY_ENV
+                    ),
+                ))
+            }
+            _ => {}
+        }
+
+        Ok(LiveStreams {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{count} {channel}")?,
+            service: block_config.service,
+            channels: block_config.channels,
+            interval: block_config.interval,
+            hide_if_none: block_config.hide_if_none,
+            open_command: block_config.open_command,
+            streams: Vec::new(),
+            failed: false,
+        })
+    }
+}
+
+/// The streams of a Twitch answer, by viewers from most to least
+fn twitch_streams(response: TwitchStreams) -> Vec<Stream> {
+    let mut streams: Vec<Stream> = response
+        .data
+        .into_iter()
+        .map(|stream| Stream {
+            url: format!("https://www.twitch.tv/{}", stream.user_login),
+            channel: stream.user_name,
+            title: stream.title,
+            viewers: stream.viewer_count,
+        })
+        .collect();
+    streams.sort_by_key(|stream| std::cmp::Reverse(stream.viewers));
+    streams
+}
+
+impl LiveStreams {
+    fn fetch(&self) -> Result<Vec<Stream>> {
+        match &self.service {
+            StreamService::Twitch {
+                client_id: Some(client_id),
+                token: Some(token),
+            } => {
+                let logins: Vec<String> = self
+                    .channels
+                    .iter()
+                    .map(|login| format!("user_login={}", login))
+                    .collect();
+                let url = format!(
+                    "https://api.twitch.tv/helix/streams?first=100&{}",
+                    logins.join("&")
+                );
+                let authorization = format!("Bearer {}", token);
+                let response = http::http_get_json(
+                    &url,
+                    Some(Duration::from_secs(10)),
+                    vec![("Client-Id", client_id), ("Authorization", &authorization)],
+                )?;
+                if response.code != 200 {
+                    return Err(BlockError(
+                        "live_streams".to_string(),
+                        format!("could not get the streams (HTTP {})", response.code),
+                    ));
+                }
+                let streams = serde_json::from_value(response.content)
+                    .block_error("live_streams", "could not parse the streams")?;
+                Ok(twitch_streams(streams))
+            }
+            StreamService::YouTube {
+                api_key: Some(api_key),
+            } => {
+                // YouTube can only be searched one channel at a time. Without viewer counts, the
+                // channels keep the order of the configuration.
+                let mut streams = Vec::new();
+                for channel in &self.channels {
+                    let url = format!(
+                        "https://www.googleapis.com/youtube/v3/search?part=snippet&type=video&eventType=live&channelId={}&key={}",
+                        channel, api_key
+                    );
+                    let response =
+                        http::http_get_json(&url, Some(Duration::from_secs(10)), vec![])?;
+                    if response.code != 200 {
+                        return Err(BlockError(
+                            "live_streams".to_string(),
+                            format!(
+                                "could not search channel {} (HTTP {})",
+                                channel, response.code
+                            ),
+                        ));
+                    }
+                    let search: YouTubeSearch = serde_json::from_value(response.content)
+                        .block_error("live_streams", "could not parse the search results")?;
+                    streams.extend(search.items.into_iter().take(1).map(|item| Stream {
+                        url: format!("https://www.youtube.com/watch?v={}", item.id.video_id),
+                        channel: item.snippet.channel_title,
+                        title: item.snippet.title,
+                        viewers: 0,
+                    }));
+                }
+                Ok(streams)
+            }
+            _ => unreachable!("checked when the block was created"),
+        }
+    }
+}
+
+impl Block for LiveStreams {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.failed = false;
+        self.streams = match self.fetch() {
+            Ok(streams) => streams,
+            Err(error) => {
+                // Streams that may have ended aren't offered to be opened
+                self.streams.clear();
+                self.failed = true;
+                match error {
+                    BlockError(block, _) | InternalError(block, _, _) if block == "curl" => {
+                        self.text.set_text("×".to_string());
+                        self.text.set_state(State::Warning);
+                    }
+                    error => {
+                        self.text.set_text(format!("live_streams error {}", error));
+                        self.text.set_state(State::Critical);
+                    }
+                }
+                return Ok(Some(self.interval.into()));
+            }
+        };
+        let top = self.streams.first();
+        let values = map!(
+            "count" => Value::from_integer(self.streams.len() as i64),
+            "channel" => Value::from_string(top.map(|s| s.channel.clone()).unwrap_or_default()),
+            "title" => Value::from_string(top.map(|s| s.title.clone()).unwrap_or_default()),
+            "viewers" => Value::from_integer(top.map_or(0, |s| s.viewers) as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match top {
+            Some(_) => State::Info,
+            None => State::Idle,
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            if let Some(stream) = self.streams.first() {
+                let command = format!("{} '{}'", self.open_command, stream.url);
+                spawn_child_async("sh", &["-c", &command])
+                    .block_error("live_streams", &format!("failed to run {}", command))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_none && self.streams.is_empty() && !self.failed {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_twitch_streams() {
+        let response = serde_json::from_str(
+            r#"{"data":[
+                {"user_login":"small","user_name":"Small","title":"Chill","viewer_count":12,"type":"live"},
+                {"user_login":"big","user_name":"Big","title":"Finals","viewer_count":40000,"type":"live"}
+            ],"pagination":{}}"#,
+        )
+        .unwrap();
+        let streams = twitch_streams(response);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].channel, "Big");
+        assert_eq!(streams[0].url, "https://www.twitch.tv/big");
+        assert_eq!(streams[1].viewers, 12);
+    }
+}
@@ -0,0 +1,459 @@
+//! Shows the unread mail of IMAP mailboxes. Every mailbox is watched by a thread of its own that
+//! keeps a connection open and waits with IDLE (RFC 2177) for the server to report changes, so the
+//! counts are updated as soon as mail arrives instead of by polling.
+//!
+//! TLS connections are made through `openssl s_client`, which verifies the certificate of the
+//! server against the system's trust store.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long to wait before connecting again after the connection failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Clone)]
+struct Mailbox {
+    unread: Option<u64>,
+    error: Option<String>,
+}
+
+pub struct Mail {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    format: FormatTemplate,
+    names: Vec<String>,
+    mailboxes: Arc<Mutex<Vec<Mailbox>>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MailConfig {
+    pub host: String,
+
+    /// Defaults to 993 with TLS and 143 without
+    pub port: Option<u16>,
+
+    /// Connect with TLS. Without it, the password is sent in the clear.
+    pub tls: bool,
+
+    pub username: String,
+
+    pub password: Option<String>,
+
+    /// Shell command that prints the password, e.g. `pass show mail`
+    pub password_command: Option<String>,
+
+    pub mailboxes: Vec<String>,
+
+    /// How often to check for mail on servers that don't support IDLE
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: None,
+            tls: true,
+            username: String::new(),
+            password: None,
+            password_command: None,
+            mailboxes: vec!["INBOX".to_string()],
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Everything a watching thread needs to connect
+#[derive(Clone)]
+struct Server {
+    host: String,
+    port: u16,
+    tls: bool,
+    username: String,
+    password: String,
+    interval: Duration,
+}
+
+impl ConfigBlock for Mail {
+    type Config = MailConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.host.is_empty() || block_config.username.is_empty() {
+            return Err(ConfigurationError(
+                "mail".to_string(),
+                "host and username must be set".to_string(),
+            ));
+        }
+        let password = match (block_config.password, &block_config.password_command) {
+            (Some(password), _) => password,
+            (None, Some(command)) => {
+                let output = shared_config.command_runner.run(command)?;
+                if output.status != Some(0) {
+                    return Err(BlockError(
+                        "mail".to_string(),
+                        format!("failed to get the password from {}", command),
+                    ));
+                }
+                output.stdout.trim_end_matches('\n').to_string()
+            }
+            (None, None) => {
+                return Err(ConfigurationError(
+                    "mail".to_string(),
+                    "password or password_command must be set".to_string(),
+                ))
+            }
+        };
+        let server = Server {
+            port: block_config
+                .port
+                .unwrap_or(if block_config.tls { 993 } else { 143 }),
+            host: block_config.host,
+            tls: block_config.tls,
+            username: block_config.username,
+            password,
+            interval: block_config.interval,
+        };
+
+        let mailboxes = Arc::new(Mutex::new(vec![
+            Mailbox::default();
+            block_config.mailboxes.len()
+        ]));
+        for (index, name) in block_config.mailboxes.iter().enumerate() {
+            let server = server.clone();
+            let name = name.clone();
+            let mailboxes = mailboxes.clone();
+            let tx = tx_update_request.clone();
+            thread::Builder::new()
+                .name("mail".into())
+                .spawn(move || watch(id, &server, &name, index, &mailboxes, &tx))
+                .internal_error("mail", "failed to start watching thread")?;
+        }
+
+        let widgets = (0..block_config.mailboxes.len())
+            .map(|instance| {
+                let widget = TextWidget::new(id, instance, shared_config.clone());
+                if instance == 0 {
+                    widget.with_icon("mail")
+                } else {
+                    Ok(widget)
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Mail {
+            id,
+            widgets,
+            format: block_config
+                .format
+                .with_default(if block_config.mailboxes.len() > 1 {
+                    "{mailbox} {unread}"
+                } else {
+                    "{unread}"
+                })?,
+            names: block_config.mailboxes,
+            mailboxes,
+        })
+    }
+}
+
+/// Keeps the unread count of a mailbox up to date until the block goes away
+fn watch(
+    id: usize,
+    server: &Server,
+    name: &str,
+    index: usize,
+    mailboxes: &Arc<Mutex<Vec<Mailbox>>>,
+    tx: &Sender<Task>,
+) {
+    let report = |mailbox: Mailbox| {
+        if let Ok(mut mailboxes) = mailboxes.lock() {
+            mailboxes[index] = mailbox;
+        }
+        let _ = tx.send(Task {
+            id,
+            update_time: Instant::now(),
+        });
+    };
+    // The block holds the other reference, until a reload of the configuration drops it
+    while Arc::strong_count(mailboxes) > 1 {
+        let result = Connection::open(server).and_then(|mut connection| {
+            connection.login(&server.username, &server.password)?;
+            connection.command(&format!("EXAMINE {}", quote(name)))?;
+            while Arc::strong_count(mailboxes) > 1 {
+                let unread = connection.unread()?;
+                report(Mailbox {
+                    unread: Some(unread),
+                    error: None,
+                });
+                if !connection.idle()? {
+                    thread::sleep(server.interval);
+                    connection.command("NOOP")?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(error) = result {
+            log::warn!("mailbox {}: {}", name, error);
+            let message = match error {
+                BlockError(_, message) => message,
+                error => error.to_string(),
+            };
+            report(Mailbox {
+                unread: None,
+                error: Some(format!("{}: {}", name, message)),
+            });
+            thread::sleep(RECONNECT_DELAY);
+        }
+    }
+}
+
+/// Quotes a string for IMAP
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Counts the message numbers of the untagged responses to a SEARCH
+fn count_found(responses: &[String]) -> u64 {
+    responses
+        .iter()
+        .filter_map(|line| line.strip_prefix("* SEARCH"))
+        .map(|numbers| numbers.split_whitespace().count() as u64)
+        .sum()
+}
+
+struct Connection {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+    /// `openssl s_client` for TLS connections
+    child: Option<Child>,
+    tag: u32,
+}
+
+impl Connection {
+    fn open(server: &Server) -> Result<Connection> {
+        let mut connection = if server.tls {
+            let address = format!("{}:{}", server.host, server.port);
+            let mut child = Command::new("openssl")
+                .args([
+                    "s_client",
+                    "-quiet",
+                    "-verify_return_error",
+                    "-verify_hostname",
+                    &server.host,
+                    "-servername",
+                    &server.host,
+                    "-connect",
+                    &address,
+                ])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .block_error("mail", "failed to run openssl")?;
+            Connection {
+                reader: Box::new(BufReader::new(
+                    child.stdout.take().internal_error("mail", "no stdout")?,
+                )),
+                writer: Box::new(child.stdin.take().internal_error("mail", "no stdin")?),
+                child: Some(child),
+                tag: 0,
+            }
+        } else {
+            let stream = TcpStream::connect((server.host.as_str(), server.port))
+                .block_error("mail", &format!("failed to connect to {}", server.host))?;
+            Connection {
+                reader: Box::new(BufReader::new(
+                    stream
+                        .try_clone()
+                        .internal_error("mail", "failed to clone the connection")?,
+                )),
+                writer: Box::new(stream),
+                child: None,
+                tag: 0,
+            }
+        };
+        let greeting = connection.read_line()?;
+        if !greeting.starts_with("* OK") {
+            return Err(BlockError(
+                "mail".to_string(),
+                format!("unexpected greeting: {}", greeting),
+            ));
+        }
+        Ok(connection)
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .block_error("mail", "failed to read from the server")?;
+        if read == 0 {
+            return Err(BlockError(
+                "mail".to_string(),
+                "the server closed the connection".to_string(),
+            ));
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        write!(self.writer, "{}\r\n", line)
+            .and_then(|_| self.writer.flush())
+            .block_error("mail", "failed to write to the server")
+    }
+
+    fn send(&mut self, command: &str) -> Result<String> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        self.write_line(&format!("{} {}", tag, command))?;
+        Ok(tag)
+    }
+
+    /// Reads the untagged responses up to the tagged one, which must be OK
+    fn responses(&mut self, tag: &str) -> Result<Vec<String>> {
+        let mut responses = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            match line.strip_prefix(tag) {
+                Some(status) if status.trim_start().starts_with("OK") => return Ok(responses),
+                Some(status) => {
+                    return Err(BlockError(
+                        "mail".to_string(),
+                        format!("the server answered {}", status.trim()),
+                    ))
+                }
+                None => responses.push(line),
+            }
+        }
+    }
+
+    /// Runs a command and returns its untagged responses
+    fn command(&mut self, command: &str) -> Result<Vec<String>> {
+        log::debug!("IMAP: {}", command);
+        let tag = self.send(command)?;
+        self.responses(&tag)
+    }
+
+    fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        let tag = self.send(&format!("LOGIN {} {}", quote(username), quote(password)))?;
+        self.responses(&tag).map(|_| ())
+    }
+
+    fn unread(&mut self) -> Result<u64> {
+        Ok(count_found(&self.command("SEARCH UNSEEN")?))
+    }
+
+    /// Waits until the server reports a change. Returns `false` if the server doesn't support
+    /// IDLE.
+    fn idle(&mut self) -> Result<bool> {
+        let tag = self.send("IDLE")?;
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with('+') {
+                break;
+            }
+            if line.starts_with(&tag) {
+                return Ok(false);
+            }
+        }
+        loop {
+            let line = self.read_line()?;
+            log::debug!("IMAP: {}", line);
+            if ["EXISTS", "EXPUNGE", "FETCH", "RECENT"]
+                .iter()
+                .any(|change| line.starts_with('*') && line.contains(change))
+            {
+                break;
+            }
+        }
+        self.write_line("DONE")?;
+        self.responses(&tag)?;
+        Ok(true)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Block for Mail {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mailboxes = self
+            .mailboxes
+            .lock()
+            .internal_error("mail", "failed to lock the mailboxes")?
+            .clone();
+        if let Some(error) = mailboxes.iter().find_map(|m| m.error.clone()) {
+            return Err(BlockError("mail".to_string(), error));
+        }
+        for ((widget, mailbox), name) in self.widgets.iter_mut().zip(mailboxes).zip(&self.names) {
+            let unread = mailbox.unread.unwrap_or(0);
+            let values = map!(
+                "mailbox" => Value::from_string(name.clone()),
+                "unread" => Value::from_integer(unread as i64),
+            );
+            widget.set_texts(self.format.render(&values)?);
+            widget.set_state(if unread > 0 { State::Info } else { State::Idle });
+        }
+        // The watching threads ask for updates
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_found() {
+        let responses = vec![
+            "* 3 EXISTS".to_string(),
+            "* SEARCH 2 5 8".to_string(),
+            "* SEARCH".to_string(),
+        ];
+        assert_eq!(count_found(&responses), 3);
+        assert_eq!(quote(r#"Me "Work"\"#), r#""Me \"Work\"\\""#);
+    }
+}
@@ -0,0 +1,240 @@
+//! Counts the mail that waits in the queue of msmtpq or Postfix to be sent, and warns once mail is
+//! stuck there for long
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueKind {
+    Msmtp,
+    Postfix,
+}
+
+pub struct MailQueue {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    kind: QueueKind,
+    path: PathBuf,
+    warning: Duration,
+    flush_command: Option<String>,
+    hide_if_empty: bool,
+    count: usize,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MailQueueConfig {
+    pub queue: QueueKind,
+
+    /// Directory of the queue of msmtpq
+    pub path: String,
+
+    /// Time in minutes that mail may wait in the queue before the block is shown as warning
+    pub warning: u64,
+
+    /// Shell command that sends the queued mail, run on click
+    pub flush_command: Option<String>,
+
+    /// Hide the block while the queue is empty
+    pub hide_if_empty: bool,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for MailQueueConfig {
+    fn default() -> Self {
+        Self {
+            queue: QueueKind::Msmtp,
+            path: "~/.msmtp.queue".to_string(),
+            warning: 60,
+            flush_command: None,
+            hide_if_empty: false,
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for MailQueue {
+    type Config = MailQueueConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(MailQueue {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("mail")?,
+            format: block_config.format.with_default("{count}")?,
+            kind: block_config.queue,
+            path: PathBuf::from(shellexpand::tilde(&block_config.path).to_string()),
+            warning: Duration::from_secs(block_config.warning * 60),
+            flush_command: block_config.flush_command,
+            hide_if_empty: block_config.hide_if_empty,
+            count: 0,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+/// The arrival times of the queued mail in the output of `postqueue -j`, which prints a JSON
+/// object per mail
+fn parse_postqueue(output: &str) -> Vec<SystemTime> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<JsonValue>(line).ok())
+        // Mail on hold isn't going to be sent until it's released
+        .filter(|mail| mail["queue_name"] != "hold")
+        .filter_map(|mail| mail["arrival_time"].as_u64())
+        .map(|time| UNIX_EPOCH + Duration::from_secs(time))
+        .collect()
+}
+
+impl MailQueue {
+    /// When the mail in the queue was queued
+    fn queued(&self) -> Result<Vec<SystemTime>> {
+        match self.kind {
+            // msmtpq keeps every mail in a ".mail" file, next to a ".msmtp" file with its
+            // arguments for msmtp
+            QueueKind::Msmtp => {
+                let entries = match fs::read_dir(&self.path) {
+                    Ok(entries) => entries,
+                    // The queue is only created once mail is queued
+                    Err(_) if !self.path.exists() => return Ok(Vec::new()),
+                    Err(_) => {
+                        return Err(BlockError(
+                            "mail_queue".to_string(),
+                            format!("failed to read {}", self.path.display()),
+                        ))
+                    }
+                };
+                Ok(entries
+                    .flatten()
+                    .filter(|entry| entry.path().extension().is_some_and(|e| e == "mail"))
+                    .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+                    .collect())
+            }
+            QueueKind::Postfix => {
+                let output = self.command_runner.run("postqueue -j")?;
+                if output.status != Some(0) {
+                    return Err(BlockError(
+                        "mail_queue".to_string(),
+                        "failed to list the queue of Postfix".to_string(),
+                    ));
+                }
+                Ok(parse_postqueue(&output.stdout))
+            }
+        }
+    }
+}
+
+impl Block for MailQueue {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let queued = self.queued()?;
+        let now = SystemTime::now();
+        let age = queued
+            .iter()
+            .map(|time| now.duration_since(*time).unwrap_or_default())
+            .max()
+            .unwrap_or_default();
+        self.count = queued.len();
+
+        let values = map!(
+            "count" => Value::from_integer(self.count as i64),
+            "age" => Value::from_integer(age.as_secs() as i64).seconds(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if self.count == 0 {
+            State::Idle
+        } else if age >= self.warning {
+            State::Warning
+        } else {
+            State::Info
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let Some(command) = self.flush_command.clone() {
+            let id = self.id;
+            let tx_update_request = self.tx_update_request.clone();
+            // Count again once the mail is sent
+            thread::Builder::new()
+                .name("mail_queue".into())
+                .spawn(move || {
+                    let _ = Command::new("sh").args(["-c", &command]).status();
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                })
+                .internal_error("mail_queue", "failed to start a thread")?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_empty && self.count == 0 {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_postqueue() {
+        let output = r#"{"queue_name": "deferred", "queue_id": "4C3B22A0C5", "arrival_time": 1634290000, "message_size": 1523, "sender": "me@example.com", "recipients": [{"address": "you@example.org", "delay_reason": "connect to mx.example.org[203.0.113.5]:25: Connection timed out"}]}
+{"queue_name": "hold", "queue_id": "5D4C33B1D6", "arrival_time": 1634291000, "message_size": 880, "sender": "me@example.com", "recipients": [{"address": "you@example.org"}]}
+"#;
+        assert_eq!(
+            parse_postqueue(output),
+            vec![UNIX_EPOCH + Duration::from_secs(1_634_290_000)]
+        );
+    }
+}
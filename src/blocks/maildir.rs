@@ -1,9 +1,13 @@
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
 use maildir::Maildir as ExtMaildir;
 use serde_derive::Deserialize;
 
+use crate::blocks::base_block::{follow_inotify, FileWatch};
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
@@ -28,6 +32,42 @@ impl MailType {
             MailType::All => maildir.count_new() + maildir.count_cur(),
         }
     }
+
+    /// The subdirectories of a maildir that the mail is counted in
+    fn subdirs(&self) -> &'static [&'static str] {
+        match self {
+            MailType::New => &["new"],
+            MailType::Cur => &["cur"],
+            MailType::All => &["new", "cur"],
+        }
+    }
+}
+
+/// Watches the directories that mail is counted in, so that the block is updated as soon as mail
+/// arrives or is read. Returns `None` if they can't be watched, e.g. on some network file systems.
+fn watch(id: usize, dirs: &[PathBuf], tx_update_request: Sender<Task>) -> Option<FileWatch> {
+    let mut notify = match Inotify::init() {
+        Ok(notify) => notify,
+        Err(error) => {
+            log::warn!("failed to start inotify: {}", error);
+            return None;
+        }
+    };
+    let mask = WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO;
+    for dir in dirs {
+        if let Err(error) = notify.add_watch(dir, mask) {
+            log::warn!("failed to watch {}: {}", dir.display(), error);
+            return None;
+        }
+    }
+
+    match follow_inotify(notify, id, tx_update_request, |_| true) {
+        Ok(watch) => Some(watch),
+        Err(error) => {
+            log::warn!("failed to watch for changes: {:?}", error);
+            None
+        }
+    }
 }
 
 pub struct Maildir {
@@ -38,13 +78,15 @@ pub struct Maildir {
     threshold_warning: usize,
     threshold_critical: usize,
     display_type: MailType,
+    /// Set while the inboxes are watched, so that they don't need to be polled
+    watch: Option<FileWatch>,
 }
 
 //TODO add `format`
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct MaildirConfig {
-    /// Update interval in seconds
+    /// Update interval in seconds, for inboxes that can't be watched
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
     pub inboxes: Vec<String>,
@@ -74,9 +116,19 @@ impl ConfigBlock for Maildir {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let widget = TextWidget::new(id, 0, shared_config).with_text("");
+        let dirs: Vec<PathBuf> = block_config
+            .inboxes
+            .iter()
+            .flat_map(|inbox| {
+                let subdirs = block_config.display_type.subdirs();
+                subdirs
+                    .iter()
+                    .map(move |subdir| Path::new(inbox).join(subdir))
+            })
+            .collect();
         Ok(Maildir {
             id,
             update_interval: block_config.interval,
@@ -89,6 +141,7 @@ impl ConfigBlock for Maildir {
             threshold_warning: block_config.threshold_warning,
             threshold_critical: block_config.threshold_critical,
             display_type: block_config.display_type,
+            watch: watch(id, &dirs, tx_update_request),
         })
     }
 }
@@ -109,7 +162,11 @@ impl Block for Maildir {
         }
         self.text.set_state(state);
         self.text.set_text(format!("{}", newmails));
-        Ok(Some(self.update_interval.into()))
+        if self.watch.is_some() {
+            Ok(None)
+        } else {
+            Ok(Some(self.update_interval.into()))
+        }
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -0,0 +1,273 @@
+//! Shows the unread notifications and mentions of a Mastodon account, or of an account on another
+//! server of the Fediverse that speaks the Mastodon API
+
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Most notifications older servers list at once
+const PAGE_SIZE: usize = 80;
+
+pub struct Mastodon {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    instance: String,
+    token: String,
+    /// Whether the server knows the unread count endpoint of Mastodon 4.3
+    unread_count: bool,
+    hide_if_none: bool,
+    open_command: String,
+    notifications: u64,
+    /// Whether the last update failed, so that the block is shown even without notifications
+    failed: bool,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MastodonConfig {
+    /// URL of the server of the account, e.g. "https://mastodon.social"
+    pub instance: String,
+
+    /// Access token with the scope "read:notifications"
+    pub token: Option<String>,
+
+    /// Hide the block while there are no unread notifications
+    pub hide_if_none: bool,
+
+    /// Command that opens the notifications in the web UI on click
+    pub open_command: String,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for MastodonConfig {
+    fn default() -> Self {
+        Self {
+            instance: String::new(),
+            token: env::var("MASTODON_TOKEN").ok(),
+            hide_if_none: false,
+            open_command: "xdg-open".to_string(),
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Mastodon {
+    type Config = MastodonConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.instance.is_empty() {
+            return Err(ConfigurationError(
+                "mastodon".to_string(),
+                "instance must be set".to_string(),
+            ));
+        }
+        let token = block_config.token.ok_or_else(|| {
+            ConfigurationError(
+                "mastodon".to_string(),
+                "missing token, set token or MASTODON_TOKEN".to_string(),
+            )
+        })?;
+
+        Ok(Mastodon {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("notification")?,
+            format: block_config.format.with_default("{notifications}")?,
+            instance: block_config.instance.trim_end_matches('/').to_string(),
+            token,
+            unread_count: true,
+            hide_if_none: block_config.hide_if_none,
+            open_command: block_config.open_command,
+            notifications: 0,
+            failed: false,
+            interval: block_config.interval,
+        })
+    }
+}
+
+/// Counts the notifications newer than the read marker and how many of them are mentions
+fn count_unread(notifications: &JsonValue) -> (u64, u64) {
+    let notifications = notifications.as_array().map_or(&[][..], Vec::as_slice);
+    let mentions = notifications
+        .iter()
+        .filter(|notification| notification["type"] == "mention")
+        .count();
+    (notifications.len() as u64, mentions as u64)
+}
+
+impl Mastodon {
+    fn get(&self, path: &str) -> Result<http::HttpResponse<JsonValue>> {
+        let url = format!("{}{}", self.instance, path);
+        let authorization = format!("Bearer {}", self.token);
+        let response = http::http_get_json(
+            &url,
+            Some(Duration::from_secs(10)),
+            vec![("Authorization", &authorization)],
+        )?;
+        match response.code {
+            200 | 404 => Ok(response),
+            401 | 403 => Err(BlockError(
+                "mastodon".to_string(),
+                "the token was rejected".to_string(),
+            )),
+            code => Err(BlockError(
+                "mastodon".to_string(),
+                format!("{} answered with HTTP {}", self.instance, code),
+            )),
+        }
+    }
+
+    /// The unread notifications and mentions, as the server counts them
+    fn unread_count(&self) -> Result<Option<(u64, u64)>> {
+        let count = |query: &str| -> Result<Option<u64>> {
+            let response = self.get(&format!(
+                "/api/v1/notifications/unread_count?limit=1000{}",
+                query
+            ))?;
+            if response.code == 404 {
+                return Ok(None);
+            }
+            response.content["count"]
+                .as_u64()
+                .block_error("mastodon", "unexpected answer of the server")
+                .map(Some)
+        };
+        Ok(match count("")? {
+            Some(notifications) => Some((notifications, count("&types[]=mention")?.unwrap_or(0))),
+            None => None,
+        })
+    }
+
+    /// The unread notifications and mentions among the newest page of notifications, for servers
+    /// before Mastodon 4.3 and other software that keeps the read marker
+    fn unread_since_marker(&self) -> Result<(u64, u64)> {
+        let markers = self.get("/api/v1/markers?timeline[]=notifications")?;
+        let last_read = markers.content["notifications"]["last_read_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let notifications = self.get(&format!(
+            "/api/v1/notifications?since_id={}&limit={}",
+            last_read, PAGE_SIZE
+        ))?;
+        Ok(count_unread(&notifications.content))
+    }
+
+    /// The unread notifications and mentions, from the unread count endpoint if the server knows it
+    fn unread(&mut self) -> Result<(u64, u64)> {
+        let unread = if self.unread_count {
+            self.unread_count()?
+        } else {
+            None
+        };
+        match unread {
+            Some(unread) => Ok(unread),
+            None => {
+                self.unread_count = false;
+                self.unread_since_marker()
+            }
+        }
+    }
+}
+
+impl Block for Mastodon {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (notifications, mentions) = match self.unread() {
+            Ok(unread) => unread,
+            Err(error) => {
+                // Tried again with the next update
+                self.failed = true;
+                match error {
+                    BlockError(block, _) | InternalError(block, _, _) if block == "curl" => {
+                        self.text.set_text("N/A".to_string());
+                    }
+                    error => self.text.set_text(format!("mastodon error {}", error)),
+                }
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.interval.into()));
+            }
+        };
+        self.failed = false;
+        self.notifications = notifications;
+
+        let values = map!(
+            "notifications" => Value::from_integer(notifications as i64),
+            "mentions" => Value::from_integer(mentions as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if mentions > 0 {
+            State::Warning
+        } else if notifications > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            let command = format!("{} '{}/notifications'", self.open_command, self.instance);
+            spawn_child_async("sh", &["-c", &command])
+                .block_error("mastodon", &format!("failed to run {}", command))?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_none && self.notifications == 0 && !self.failed {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_unread() {
+        let notifications = serde_json::json!([
+            {"id": "113", "type": "mention"},
+            {"id": "112", "type": "favourite"},
+            {"id": "111", "type": "reblog"},
+        ]);
+        assert_eq!(count_unread(&notifications), (3, 1));
+        assert_eq!(count_unread(&serde_json::json!({"error": "x"})), (0, 0));
+    }
+}
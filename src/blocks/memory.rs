@@ -1,6 +1,8 @@
 use regex::Regex;
-use std::fs::File;
+use std::collections::VecDeque;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
+use std::process::Command;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 
@@ -8,6 +10,7 @@ use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
@@ -15,6 +18,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::util::Hysteresis;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -25,6 +29,22 @@ pub enum Memtype {
     Memory,
 }
 
+/// What the state of the memory view is based on
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryStateBy {
+    /// The used memory in percent
+    Usage,
+    /// The memory pressure, and how soon the available memory runs out at its current trend
+    Pressure,
+}
+
+/// How long the trend of the available memory is taken over
+const TREND: Duration = Duration::from_secs(60);
+
+/// How often the journal is asked for processes killed for lack of memory
+const OOM_CHECK: Duration = Duration::from_secs(60);
+
 #[derive(Clone, Copy, Debug)]
 // Not following naming convention, because of naming in /proc/meminfo
 struct Memstate {
@@ -113,6 +133,17 @@ pub struct Memory {
     tx_update_request: Sender<Task>,
     warning: (f64, f64),
     critical: (f64, f64),
+    hysteresis: (Hysteresis, Hysteresis),
+    state_by: MemoryStateBy,
+    warning_pressure: f64,
+    critical_pressure: f64,
+    warning_exhaustion: Duration,
+    critical_exhaustion: Duration,
+    /// The available memory over the last minute, in bytes
+    available: VecDeque<(Instant, f64)>,
+    oom_window: Option<Duration>,
+    /// When the journal was last asked, and the kills and last victim it told of
+    oom_kills: Option<(Instant, usize, Option<String>)>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -148,6 +179,27 @@ pub struct MemoryConfig {
 
     /// Percentage of swap usage, where state is set to critical
     pub critical_swap: f64,
+
+    /// Margin by which the usage has to drop below a threshold before the state is lowered
+    pub hysteresis: f64,
+
+    /// Whether the state of the memory view follows the usage or the pressure
+    pub state_by: MemoryStateBy,
+
+    /// Percentage of time that some processes stalled on memory, where state is set to warning
+    pub warning_pressure: f64,
+
+    /// Percentage of time that some processes stalled on memory, where state is set to critical
+    pub critical_pressure: f64,
+
+    /// Seconds until the available memory runs out at its current trend, where state is set to warning
+    pub warning_exhaustion: u64,
+
+    /// Seconds until the available memory runs out at its current trend, where state is set to critical
+    pub critical_exhaustion: u64,
+
+    /// Minutes that processes killed by earlyoom or systemd-oomd are shown for
+    pub oom_window: u64,
 }
 
 impl Default for MemoryConfig {
@@ -163,10 +215,66 @@ impl Default for MemoryConfig {
             warning_swap: 80.,
             critical_mem: 95.,
             critical_swap: 95.,
+            hysteresis: 0.,
+            state_by: MemoryStateBy::Usage,
+            warning_pressure: 10.,
+            critical_pressure: 30.,
+            warning_exhaustion: 300,
+            critical_exhaustion: 60,
+            oom_window: 60,
         }
     }
 }
 
+/// The share of time in percent that some processes stalled on memory over the last 10 seconds,
+/// from the first line of /proc/pressure/memory, like "some avg10=1.53 avg60=0.80 avg300=0.20
+/// total=123456"
+fn parse_pressure(pressure: &str) -> Option<f64> {
+    pressure
+        .lines()
+        .find(|line| line.starts_with("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+/// How long until the available memory runs out if it keeps falling as it did over the samples,
+/// which are seconds and bytes
+fn exhaustion(samples: &[(f64, f64)]) -> Option<Duration> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let rate = (first.1 - last.1) / (last.0 - first.0);
+    if rate.is_finite() && rate > 0. {
+        Some(Duration::from_secs_f64(last.1.max(0.) / rate))
+    } else {
+        None
+    }
+}
+
+/// The number of processes killed by earlyoom or systemd-oomd in their journal, and the name of
+/// the last one
+fn parse_oom_kills(journal: &str) -> (usize, Option<String>) {
+    let victims: Vec<String> = journal
+        .lines()
+        .filter_map(|line| {
+            if let Some(rest) = line
+                .split_once("sending SIGTERM to process ")
+                .map(|(_, r)| r)
+            {
+                // earlyoom: sending SIGTERM to process 1234 uid 1000 "firefox": badness 800, ...
+                Some(rest.split('"').nth(1).unwrap_or(rest).to_string())
+            } else if let Some(rest) = line.strip_prefix("Killed ") {
+                // systemd-oomd: Killed /user.slice/.../app-firefox.scope due to memory pressure ...
+                let unit = rest.split_whitespace().next()?;
+                Some(unit.rsplit('/').next().unwrap_or(unit).to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+    (victims.len(), victims.last().cloned())
+}
+
 impl Memory {
     fn format_insert_values(&mut self, mem_state: Memstate) -> Result<(String, Option<String>)> {
         let mem_total = mem_state.mem_total() as f64 * 1024.;
@@ -204,20 +312,97 @@ impl Memory {
             "cached_percent" => Value::from_float(cached / mem_total * 100.).percents(),
         );
 
+        let mut values = values;
+        let now = Instant::now();
+        self.available.push_back((now, mem_avail));
+        while matches!(self.available.front(), Some((time, _)) if now - *time > TREND) {
+            self.available.pop_front();
+        }
+        let samples: Vec<(f64, f64)> = self
+            .available
+            .iter()
+            .map(|(time, available)| ((*time - self.available[0].0).as_secs_f64(), *available))
+            .collect();
+        let exhaustion = exhaustion(&samples);
+        values.insert(
+            "exhaustion",
+            match exhaustion {
+                Some(exhaustion) => Value::from_integer(exhaustion.as_secs() as i64).seconds(),
+                None => Value::from_string("-".to_string()),
+            },
+        );
+        // Without PSI, e.g. on the BSDs, there is no pressure
+        let pressure = fs::read_to_string("/proc/pressure/memory")
+            .ok()
+            .and_then(|pressure| parse_pressure(&pressure))
+            .unwrap_or(0.);
+        values.insert("pressure", Value::from_float(pressure).percents());
+        let oom_kills = self.oom_kills()?;
+        if let Some((kills, victim)) = &oom_kills {
+            values.insert("oom_kills", Value::from_integer(*kills as i64));
+            values.insert(
+                "oom_victim",
+                Value::from_string(victim.clone().unwrap_or_default()),
+            );
+        }
+
+        let (warning, critical) = (self.warning, self.critical);
         match self.memtype {
-            Memtype::Memory => self.output.0.set_state(match mem_used / mem_total * 100. {
-                x if x > self.critical.0 => State::Critical,
-                x if x > self.warning.0 => State::Warning,
-                _ => State::Idle,
-            }),
-            Memtype::Swap => self
-                .output
-                .1
-                .set_state(match swap_used / swap_total * 100. {
-                    x if x > self.critical.1 => State::Critical,
-                    x if x > self.warning.1 => State::Warning,
+            Memtype::Memory if self.state_by == MemoryStateBy::Pressure => {
+                let (warning_exhaustion, critical_exhaustion) =
+                    (self.warning_exhaustion, self.critical_exhaustion);
+                let (warning_pressure, critical_pressure) =
+                    (self.warning_pressure, self.critical_pressure);
+                let by_pressure = self
+                    .hysteresis
+                    .0
+                    .apply(pressure, |pressure| match pressure {
+                        x if x >= critical_pressure => State::Critical,
+                        x if x >= warning_pressure => State::Warning,
+                        _ => State::Idle,
+                    });
+                let by_trend = match exhaustion {
+                    Some(x) if x <= critical_exhaustion => State::Critical,
+                    Some(x) if x <= warning_exhaustion => State::Warning,
                     _ => State::Idle,
-                }),
+                };
+                let killed = match oom_kills {
+                    Some((1.., _)) => State::Warning,
+                    _ => State::Idle,
+                };
+                let state = [by_pressure, by_trend, killed]
+                    .iter()
+                    .copied()
+                    .max_by_key(|state| match state {
+                        State::Critical => 2,
+                        State::Warning => 1,
+                        _ => 0,
+                    })
+                    .unwrap_or(State::Idle);
+                self.output.0.set_state(state)
+            }
+            Memtype::Memory => {
+                let state =
+                    self.hysteresis
+                        .0
+                        .apply(mem_used / mem_total * 100., |used| match used {
+                            x if x > critical.0 => State::Critical,
+                            x if x > warning.0 => State::Warning,
+                            _ => State::Idle,
+                        });
+                self.output.0.set_state(state)
+            }
+            Memtype::Swap => {
+                let state =
+                    self.hysteresis
+                        .1
+                        .apply(swap_used / swap_total * 100., |used| match used {
+                            x if x > critical.1 => State::Critical,
+                            x if x > warning.1 => State::Warning,
+                            _ => State::Idle,
+                        });
+                self.output.1.set_state(state)
+            }
         };
 
         Ok(match self.memtype {
@@ -226,6 +411,35 @@ impl Memory {
         })
     }
 
+    /// The processes killed by earlyoom or systemd-oomd lately, if the format shows them
+    fn oom_kills(&mut self) -> Result<Option<(usize, Option<String>)>> {
+        let window = match self.oom_window {
+            Some(window) => window,
+            None => return Ok(None),
+        };
+        let fresh =
+            matches!(self.oom_kills, Some((checked, _, _)) if checked.elapsed() < OOM_CHECK);
+        if !fresh {
+            let output = Command::new("journalctl")
+                .args([
+                    "--quiet",
+                    "--no-pager",
+                    "--output=cat",
+                    &format!("--since=-{}s", window.as_secs()),
+                    "--unit=earlyoom",
+                    "--unit=systemd-oomd",
+                ])
+                .output()
+                .block_error("memory", "failed to run journalctl")?;
+            let (kills, victim) = parse_oom_kills(&String::from_utf8_lossy(&output.stdout));
+            self.oom_kills = Some((Instant::now(), kills, victim));
+        }
+        Ok(self
+            .oom_kills
+            .as_ref()
+            .map(|(_, kills, victim)| (*kills, victim.clone())))
+    }
+
     pub fn switch(&mut self) {
         let old: Memtype = self.memtype.clone();
         self.memtype = match old {
@@ -245,6 +459,17 @@ impl ConfigBlock for Memory {
         tx: Sender<Task>,
     ) -> Result<Self> {
         let widget = TextWidget::new(id, 0, shared_config);
+        let format_mem = block_config
+            .format_mem
+            .with_default("{mem_free;M}/{mem_total;M}({mem_total_used_percents})")?;
+        let format_swap = block_config
+            .format_swap
+            .with_default("{swap_free;M}/{swap_total;M}({swap_used_percents})")?;
+        // The journal is only read if the kills are shown
+        let shows_kills = [&format_mem, &format_swap]
+            .iter()
+            .any(|format| format.contains("oom_kills") || format.contains("oom_victim"));
+        let oom_window = Duration::from_secs(block_config.oom_window * 60);
         Ok(Memory {
             id,
             memtype: block_config.display_type,
@@ -257,18 +482,23 @@ impl ConfigBlock for Memory {
                 (widget.clone(), widget)
             },
             clickable: block_config.clickable,
-            format: (
-                block_config
-                    .format_mem
-                    .with_default("{mem_free;M}/{mem_total;M}({mem_total_used_percents})")?,
-                block_config
-                    .format_swap
-                    .with_default("{swap_free;M}/{swap_total;M}({swap_used_percents})")?,
-            ),
+            format: (format_mem, format_swap),
             update_interval: block_config.interval,
             tx_update_request: tx,
             warning: (block_config.warning_mem, block_config.warning_swap),
             critical: (block_config.critical_mem, block_config.critical_swap),
+            hysteresis: (
+                Hysteresis::new(block_config.hysteresis),
+                Hysteresis::new(block_config.hysteresis),
+            ),
+            state_by: block_config.state_by,
+            warning_pressure: block_config.warning_pressure,
+            critical_pressure: block_config.critical_pressure,
+            warning_exhaustion: Duration::from_secs(block_config.warning_exhaustion),
+            critical_exhaustion: Duration::from_secs(block_config.critical_exhaustion),
+            available: VecDeque::new(),
+            oom_window: shows_kills.then_some(oom_window),
+            oom_kills: None,
         })
     }
 }
@@ -279,101 +509,19 @@ impl Block for Memory {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let f =
-            File::open("/proc/meminfo").block_error("memory", "/proc/meminfo does not exist")?;
-        let f = BufReader::new(f);
-
-        let mut mem_state = Memstate::new();
-
-        for line in f.lines() {
-            // stop reading if all values are already present
-            if mem_state.done() {
-                break;
+        let mem_state = if bsd::bsd().is_some() {
+            let info = bsd::memory()?;
+            Memstate {
+                mem_total: (info.total, true),
+                mem_free: (info.free, true),
+                cached: (info.cached, true),
+                swap_total: (info.swap_total, true),
+                swap_free: (info.swap_free, true),
+                ..Memstate::new()
             }
-
-            let line = match line {
-                Ok(s) => s,
-                _ => continue,
-            };
-            let line = line.split_whitespace().collect::<Vec<&str>>();
-
-            match line.get(0) {
-                Some(&"MemTotal:") => {
-                    mem_state.mem_total = (
-                        u64::from_str(line[1])
-                            .block_error("memory", "failed to parse mem_total")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"MemFree:") => {
-                    mem_state.mem_free = (
-                        u64::from_str(line[1]).block_error("memory", "failed to parse mem_free")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"Buffers:") => {
-                    mem_state.buffers = (
-                        u64::from_str(line[1]).block_error("memory", "failed to parse buffers")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"Cached:") => {
-                    mem_state.cached = (
-                        u64::from_str(line[1]).block_error("memory", "failed to parse cached")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"SReclaimable:") => {
-                    mem_state.s_reclaimable = (
-                        u64::from_str(line[1])
-                            .block_error("memory", "failed to parse s_reclaimable")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"Shmem:") => {
-                    mem_state.shmem = (
-                        u64::from_str(line[1]).block_error("memory", "failed to parse shmem")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"SwapTotal:") => {
-                    mem_state.swap_total = (
-                        u64::from_str(line[1])
-                            .block_error("memory", "failed to parse swap_total")?,
-                        true,
-                    );
-                    continue;
-                }
-                Some(&"SwapFree:") => {
-                    mem_state.swap_free = (
-                        u64::from_str(line[1])
-                            .block_error("memory", "failed to parse swap_free")?,
-                        true,
-                    );
-                    continue;
-                }
-                _ => {
-                    continue;
-                }
-            }
-        }
-
-        // Read ZFS arc cache size to add to total cache size
-        let zfs_arcstats_file = std::fs::read_to_string("/proc/spl/kstat/zfs/arcstats");
-        if let Ok(arcstats) = zfs_arcstats_file {
-            let size_re = Regex::new(r"size\s+\d+\s+(\d+)").unwrap(); // Valid regex is safe to unwrap.
-            let size = &size_re
-                .captures(&arcstats)
-                .block_error("memory", "failed to find zfs_arc_cache size")?[1];
-            mem_state.zfs_arc_cache =
-                u64::from_str(size).block_error("memory", "failed to parse zfs_arc_cache size")?;
-        }
+        } else {
+            read_meminfo()?
+        };
 
         // Now, create the string to be shown
         let output_text = self.format_insert_values(mem_state)?;
@@ -406,3 +554,127 @@ impl Block for Memory {
         }]
     }
 }
+
+/// Read the memory statistics of Linux from /proc/meminfo
+fn read_meminfo() -> Result<Memstate> {
+    let f = File::open("/proc/meminfo").block_error("memory", "/proc/meminfo does not exist")?;
+    let f = BufReader::new(f);
+
+    let mut mem_state = Memstate::new();
+
+    for line in f.lines() {
+        // stop reading if all values are already present
+        if mem_state.done() {
+            break;
+        }
+
+        let line = match line {
+            Ok(s) => s,
+            _ => continue,
+        };
+        let line = line.split_whitespace().collect::<Vec<&str>>();
+
+        match line.get(0) {
+            Some(&"MemTotal:") => {
+                mem_state.mem_total = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse mem_total")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"MemFree:") => {
+                mem_state.mem_free = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse mem_free")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"Buffers:") => {
+                mem_state.buffers = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse buffers")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"Cached:") => {
+                mem_state.cached = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse cached")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"SReclaimable:") => {
+                mem_state.s_reclaimable = (
+                    u64::from_str(line[1])
+                        .block_error("memory", "failed to parse s_reclaimable")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"Shmem:") => {
+                mem_state.shmem = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse shmem")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"SwapTotal:") => {
+                mem_state.swap_total = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse swap_total")?,
+                    true,
+                );
+                continue;
+            }
+            Some(&"SwapFree:") => {
+                mem_state.swap_free = (
+                    u64::from_str(line[1]).block_error("memory", "failed to parse swap_free")?,
+                    true,
+                );
+                continue;
+            }
+            _ => {
+                continue;
+            }
+        }
+    }
+
+    // Read ZFS arc cache size to add to total cache size
+    let zfs_arcstats_file = std::fs::read_to_string("/proc/spl/kstat/zfs/arcstats");
+    if let Ok(arcstats) = zfs_arcstats_file {
+        let size_re = Regex::new(r"size\s+\d+\s+(\d+)").unwrap(); // Valid regex is safe to unwrap.
+        let size = &size_re
+            .captures(&arcstats)
+            .block_error("memory", "failed to find zfs_arc_cache size")?[1];
+        mem_state.zfs_arc_cache =
+            u64::from_str(size).block_error("memory", "failed to parse zfs_arc_cache size")?;
+    }
+
+    Ok(mem_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure_and_kills() {
+        let pressure = "some avg10=12.50 avg60=3.10 avg300=0.80 total=1234567\n\
+                        full avg10=4.00 avg60=1.00 avg300=0.20 total=234567\n";
+        assert_eq!(parse_pressure(pressure), Some(12.5));
+
+        // 100 MB less every 10 seconds, with 300 MB left
+        let samples = [(0., 500e6), (10., 400e6), (20., 300e6)];
+        assert_eq!(exhaustion(&samples), Some(Duration::from_secs(30)));
+        assert_eq!(exhaustion(&[(0., 300e6), (10., 400e6)]), None);
+        assert_eq!(exhaustion(&[(0., 300e6)]), None);
+
+        let journal = "earlyoom v1.7\n\
+                       sending SIGTERM to process 1234 uid 1000 \"firefox\": badness 800, VmRSS 2000 MiB\n\
+                       process exited after 0.2 seconds\n\
+                       Killed /user.slice/user-1000.slice/user@1000.service/app.slice/app-code.scope due to memory pressure for /user.slice being 72.31% > 50.00% for > 20s with reclaim activity\n";
+        assert_eq!(
+            parse_oom_kills(journal),
+            (2, Some("app-code.scope".to_string()))
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Shows a live level meter of the default microphone, from the samples of a small PipeWire capture
+//! stream, so that it's easy to see whether the microphone picks up anything
+
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// The level that the meter starts at, in dBFS
+const FLOOR: f64 = -60.0;
+
+/// How long to wait before starting the capture command again after it exited
+const RESTART: Duration = Duration::from_secs(5);
+
+/// The samples read since the last update
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Levels {
+    peak: f32,
+    sum_of_squares: f64,
+    samples: usize,
+}
+
+impl Levels {
+    fn add(&mut self, sample: f32) {
+        self.peak = self.peak.max(sample.abs());
+        self.sum_of_squares += f64::from(sample) * f64::from(sample);
+        self.samples += 1;
+    }
+
+    /// Peak and RMS level in dBFS
+    fn decibels(&self) -> (f64, f64) {
+        let rms = (self.sum_of_squares / self.samples.max(1) as f64).sqrt();
+        (decibels(f64::from(self.peak)), decibels(rms))
+    }
+}
+
+fn decibels(amplitude: f64) -> f64 {
+    // Digital silence is shown as -140 dBFS rather than minus infinity
+    20.0 * amplitude.max(1e-7).log10()
+}
+
+#[derive(Debug, Default)]
+struct Capture {
+    /// Whether the capture command is running
+    running: bool,
+    /// The process group of the capture command
+    pid: Option<u32>,
+    levels: Levels,
+}
+
+impl Capture {
+    fn kill(&self) {
+        if let Some(pid) = self.pid {
+            let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        }
+    }
+}
+
+pub struct MicLevel {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    silence: f64,
+    clipping: f64,
+    interval: Duration,
+    capture: Arc<Mutex<Capture>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MicLevelConfig {
+    /// Command that prints mono 32 bit float samples of the microphone
+    pub command: String,
+
+    /// Peak level in dBFS below which the block is shown as warning
+    pub silence: f64,
+
+    /// Peak level in dBFS from which on the block is shown as critical
+    pub clipping: f64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for MicLevelConfig {
+    fn default() -> Self {
+        Self {
+            command: "pw-record --raw --rate 8000 --channels 1 --format f32 -".to_string(),
+            silence: -50.0,
+            clipping: -1.0,
+            interval: Duration::from_millis(250),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Runs the capture command and adds up the samples that it prints, over and over again until the
+/// block is dropped
+fn capture(command: String, capture: Arc<Mutex<Capture>>) {
+    // The block holds the other reference, until a reload of the configuration drops it
+    while Arc::strong_count(&capture) > 1 {
+        // In a group of its own, so that the recorder ends with the shell
+        let child = Command::new("sh")
+            .args(["-c", &command])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(error) => {
+                log::warn!("failed to run {}: {}", command, error);
+                return;
+            }
+        };
+        {
+            let mut capture = capture.lock().unwrap();
+            capture.running = true;
+            capture.pid = Some(child.id());
+        }
+
+        let mut stdout = child.stdout.take().unwrap();
+        let mut buffer = [0; 4096];
+        // Samples can be split across reads
+        let mut filled = 0;
+        while let Ok(read @ 1..) = stdout.read(&mut buffer[filled..]) {
+            filled += read;
+            let whole = filled - filled % 4;
+            // The block may have been dropped before the command started
+            if Arc::strong_count(&capture) == 1 {
+                capture.lock().unwrap().kill();
+                break;
+            }
+            let mut capture = capture.lock().unwrap();
+            for sample in buffer[..whole].chunks_exact(4) {
+                capture.levels.add(f32::from_le_bytes([
+                    sample[0], sample[1], sample[2], sample[3],
+                ]));
+            }
+            drop(capture);
+            buffer.copy_within(whole..filled, 0);
+            filled -= whole;
+        }
+
+        let _ = child.wait();
+        *capture.lock().unwrap() = Capture::default();
+        thread::sleep(RESTART);
+    }
+}
+
+impl ConfigBlock for MicLevel {
+    type Config = MicLevelConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let capture_state = Arc::new(Mutex::new(Capture::default()));
+        let command = block_config.command;
+        let thread_state = capture_state.clone();
+        thread::Builder::new()
+            .name("mic_level".into())
+            .spawn(move || capture(command, thread_state))
+            .internal_error("mic_level", "failed to start a thread")?;
+
+        Ok(MicLevel {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("microphone_full")?,
+            format: block_config.format.with_default("{level:5#100}")?,
+            silence: block_config.silence,
+            clipping: block_config.clipping,
+            interval: block_config.interval,
+            capture: capture_state,
+        })
+    }
+}
+
+impl Drop for MicLevel {
+    // Ends the capture, which keeps the microphone open
+    fn drop(&mut self) {
+        self.capture.lock().unwrap().kill();
+    }
+}
+
+impl Block for MicLevel {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (running, levels) = {
+            let mut capture = self.capture.lock().unwrap();
+            let levels = capture.levels;
+            capture.levels = Levels::default();
+            (capture.running, levels)
+        };
+        // The command prints samples in chunks, which may be further apart than updates
+        if running && levels.samples == 0 {
+            return Ok(Some(self.interval.into()));
+        }
+
+        let (peak, rms) = levels.decibels();
+        let level = ((peak - FLOOR) / -FLOOR * 100.0).clamp(0.0, 100.0);
+        let values = map!(
+            "level" => Value::from_integer(level as i64).percents(),
+            "peak" => Value::from_float(peak),
+            "rms" => Value::from_float(rms),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_icon(if running {
+            "microphone_full"
+        } else {
+            "microphone_muted"
+        })?;
+        self.text.set_state(if !running {
+            State::Idle
+        } else if peak >= self.clipping {
+            State::Critical
+        } else if peak < self.silence {
+            State::Warning
+        } else {
+            State::Good
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let mut levels = Levels::default();
+        for sample in [0.5, -0.5, 0.5, -0.5] {
+            levels.add(sample);
+        }
+        let (peak, rms) = levels.decibels();
+        assert!((peak - -6.02).abs() < 0.01);
+        assert!((rms - -6.02).abs() < 0.01);
+        assert_eq!(Levels::default().decibels(), (-140.0, -140.0));
+    }
+}
@@ -0,0 +1,314 @@
+//! Shows the latest messages of MQTT topics, like the values that home automation publishes, as
+//! soon as they arrive. The connection is kept by `mosquitto_sub` of
+//! [Mosquitto](https://mosquitto.org/), which reconnects by itself.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long to wait before running `mosquitto_sub` again after it exited
+const RETRY: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TopicConfig {
+    /// Placeholder of the value in the format
+    pub name: String,
+
+    /// Topic to subscribe to, which may contain the wildcards `+` and `#`
+    pub topic: String,
+
+    /// JSON pointer to the value in payloads that are JSON, like "/temperature"
+    pub pointer: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Status {
+    /// The process of `mosquitto_sub` while it runs
+    pid: Option<u32>,
+    /// Whether `mosquitto_sub` failed to run or exited
+    failed: bool,
+    /// The latest value of each topic by its name
+    values: HashMap<String, String>,
+}
+
+pub struct Mqtt {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    topics: Vec<TopicConfig>,
+    status: Arc<Mutex<Status>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    pub topics: Vec<TopicConfig>,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            topics: Vec::new(),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Whether `topic` matches the subscription `filter`, where `+` stands for one level and a final
+/// `#` for any number of levels
+fn matches(filter: &str, topic: &str) -> bool {
+    let mut topic = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(name)) if level == name => {}
+            _ => return false,
+        }
+    }
+    topic.next().is_none()
+}
+
+/// The text of the value in a payload, or `None` if the pointer doesn't point to anything
+fn extract(payload: &str, pointer: Option<&str>) -> Option<String> {
+    let pointer = match pointer {
+        Some(pointer) => pointer,
+        None => return Some(payload.to_string()),
+    };
+    let json: JsonValue = serde_json::from_str(payload).ok()?;
+    match json.pointer(pointer)? {
+        JsonValue::String(text) => Some(text.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+fn update_now(id: usize, tx_update_request: &Sender<Task>) {
+    let _ = tx_update_request.send(Task {
+        id,
+        update_time: Instant::now(),
+    });
+}
+
+/// Runs `mosquitto_sub` and keeps the latest values of the topics, until the block is dropped
+fn subscribe(
+    id: usize,
+    args: Vec<String>,
+    topics: Vec<TopicConfig>,
+    status: Arc<Mutex<Status>>,
+    tx_update_request: Sender<Task>,
+) {
+    // The block holds the other reference, until a reload of the configuration drops it
+    while Arc::strong_count(&status) > 1 {
+        let mut child = match Command::new("mosquitto_sub")
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(error) => {
+                log::warn!("failed to run mosquitto_sub: {}", error);
+                status.lock().unwrap().failed = true;
+                update_now(id, &tx_update_request);
+                thread::sleep(RETRY);
+                continue;
+            }
+        };
+        {
+            let mut status = status.lock().unwrap();
+            status.pid = Some(child.id());
+            status.failed = false;
+        }
+        update_now(id, &tx_update_request);
+
+        // -v prints the topic in front of each payload
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                let (topic, payload) = line.split_once(' ').unwrap_or((&line, ""));
+                let mut changed = false;
+                let mut status = status.lock().unwrap();
+                for config in topics.iter().filter(|config| matches(&config.topic, topic)) {
+                    if let Some(value) = extract(payload, config.pointer.as_deref()) {
+                        changed |=
+                            status.values.insert(config.name.clone(), value.clone()) != Some(value);
+                    }
+                }
+                drop(status);
+                if changed {
+                    update_now(id, &tx_update_request);
+                }
+            }
+        }
+
+        let exit = child.wait();
+        {
+            let mut status = status.lock().unwrap();
+            status.pid = None;
+            status.failed = true;
+        }
+        if Arc::strong_count(&status) > 1 {
+            log::warn!("mosquitto_sub exited: {:?}", exit);
+            update_now(id, &tx_update_request);
+            thread::sleep(RETRY);
+        }
+    }
+}
+
+impl ConfigBlock for Mqtt {
+    type Config = MqttConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.topics.is_empty() {
+            return Err(ConfigurationError(
+                "mqtt".to_string(),
+                "topics must be set".to_string(),
+            ));
+        }
+
+        let mut args = vec![
+            "-h".to_string(),
+            block_config.host,
+            "-p".to_string(),
+            block_config.port.to_string(),
+            "-v".to_string(),
+        ];
+        if let Some(username) = block_config.username {
+            args.extend(["-u".to_string(), username]);
+        }
+        if let Some(password) = block_config.password {
+            args.extend(["-P".to_string(), password]);
+        }
+        for topic in &block_config.topics {
+            args.extend(["-t".to_string(), topic.topic.clone()]);
+        }
+
+        let status = Arc::new(Mutex::new(Status::default()));
+        let subscribe_status = status.clone();
+        let topics = block_config.topics.clone();
+        thread::Builder::new()
+            .name("mqtt".into())
+            .spawn(move || subscribe(id, args, topics, subscribe_status, tx_update_request))
+            .internal_error("mqtt", "failed to start a thread")?;
+
+        let default_format: Vec<String> = block_config
+            .topics
+            .iter()
+            .map(|topic| format!("{{{}}}", topic.name))
+            .collect();
+
+        Ok(Mqtt {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config
+                .format
+                .with_default(&default_format.join(" "))?,
+            topics: block_config.topics,
+            status,
+        })
+    }
+}
+
+impl Drop for Mqtt {
+    // Ends the thread, which waits for mosquitto_sub
+    fn drop(&mut self) {
+        if let Some(pid) = self.status.lock().unwrap().pid {
+            let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        }
+    }
+}
+
+impl Block for Mqtt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self.status.lock().unwrap();
+        let values: HashMap<String, Value> = self
+            .topics
+            .iter()
+            .map(|topic| {
+                let value = match status.values.get(&topic.name) {
+                    Some(text) => match (text.parse::<i64>(), text.parse::<f64>()) {
+                        (Ok(integer), _) => Value::from_integer(integer),
+                        (_, Ok(number)) => Value::from_float(number),
+                        _ => Value::from_string(text.clone()),
+                    },
+                    None => Value::from_string("-".to_string()),
+                };
+                (topic.name.clone(), value)
+            })
+            .collect();
+        self.text.set_texts(self.format.render(&values)?);
+        // The values stay shown while mosquitto_sub reconnects
+        self.text.set_state(if status.failed {
+            State::Critical
+        } else {
+            State::Idle
+        });
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("home/+/temperature", "home/kitchen/temperature"));
+        assert!(!matches("home/+/temperature", "home/kitchen/humidity"));
+        assert!(matches("zigbee2mqtt/#", "zigbee2mqtt/door/state"));
+        assert!(!matches("home/door", "home/door/state"));
+        assert_eq!(
+            extract(r#"{"temperature": 21.5, "state": "OPEN"}"#, Some("/state")),
+            Some("OPEN".to_string())
+        );
+        assert_eq!(extract("21.5", None), Some("21.5".to_string()));
+    }
+}
@@ -26,7 +26,8 @@ use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
 use crate::util::pseudo_uuid;
 use crate::widgets::{
-    rotatingtext::RotatingTextWidget, text::TextWidget, I3BarWidget, Spacing, State,
+    button_row::ButtonRowWidget, rotatingtext::RotatingTextWidget, text::TextWidget, I3BarWidget,
+    Spacing, State,
 };
 
 #[derive(Debug, Clone)]
@@ -83,15 +84,10 @@ impl Default for PlaybackStatus {
 
 pub struct Music {
     id: usize,
-    play_id: usize,
-    next_id: usize,
-    prev_id: usize,
     collapsed_id: usize,
 
     current_song_widget: RotatingTextWidget,
-    prev: Option<TextWidget>,
-    play: Option<TextWidget>,
-    next: Option<TextWidget>,
+    buttons: ButtonRowWidget,
     on_collapsed_click_widget: TextWidget,
     on_collapsed_click: Option<String>,
     on_click: Option<String>,
@@ -215,7 +211,7 @@ pub struct MusicConfig {
     pub separator: String,
 
     /// Array of control buttons to be displayed. Options are prev (previous title),
-    /// play (play/pause) and next (next title).
+    /// play (play/pause) and next (next title), shown in the given order.
     pub buttons: Vec<String>,
 
     pub on_collapsed_click: Option<String>,
@@ -262,9 +258,6 @@ impl ConfigBlock for Music {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let play_id = pseudo_uuid();
-        let prev_id = pseudo_uuid();
-        let next_id = pseudo_uuid();
         let collapsed_id = pseudo_uuid();
 
         let dbus_conn = Connection::get_private(BusType::Session)
@@ -341,7 +334,8 @@ impl ConfigBlock for Music {
                 // Skip the NameAcquired event.
                 dbus_conn.incoming(10_000).next();
 
-                loop {
+                // Ends at the next timeout once the block is gone
+                while Arc::strong_count(&players_clone) > 1 {
                     for ref signal in dbus_conn.incoming(60_000) {
                         let mut players = players_clone
                             .lock()
@@ -421,35 +415,12 @@ impl ConfigBlock for Music {
             })
             .unwrap();
 
-        let mut play: Option<TextWidget> = None;
-        let mut prev: Option<TextWidget> = None;
-        let mut next: Option<TextWidget> = None;
+        let mut buttons = ButtonRowWidget::new(id, shared_config.clone()).with_state(State::Info);
         for button in block_config.buttons {
-            match &*button {
-                "play" => {
-                    play = Some(
-                        TextWidget::new(id, play_id, shared_config.clone())
-                            .with_icon("music_play")?
-                            .with_state(State::Info)
-                            .with_spacing(Spacing::Hidden),
-                    )
-                }
-                "next" => {
-                    next = Some(
-                        TextWidget::new(id, next_id, shared_config.clone())
-                            .with_icon("music_next")?
-                            .with_state(State::Info)
-                            .with_spacing(Spacing::Hidden),
-                    )
-                }
-                "prev" => {
-                    prev = Some(
-                        TextWidget::new(id, prev_id, shared_config.clone())
-                            .with_icon("music_prev")?
-                            .with_state(State::Info)
-                            .with_spacing(Spacing::Hidden),
-                    )
-                }
+            let icon = match &*button {
+                "play" => "music_play",
+                "next" => "music_next",
+                "prev" => "music_prev",
                 x => {
                     return Err(BlockError(
                         "music".to_owned(),
@@ -457,6 +428,7 @@ impl ConfigBlock for Music {
                     ))
                 }
             };
+            buttons = buttons.with_segment(&button, icon)?;
         }
 
         fn compile_regexps(patterns: Vec<String>) -> result::Result<Vec<Regex>, regex::Error> {
@@ -465,9 +437,6 @@ impl ConfigBlock for Music {
 
         Ok(Music {
             id,
-            play_id,
-            prev_id,
-            next_id,
             collapsed_id,
             current_song_widget: RotatingTextWidget::new(
                 id,
@@ -481,9 +450,7 @@ impl ConfigBlock for Music {
             .with_icon("music")?
             .with_state(State::Info)
             .with_spacing(Spacing::Hidden),
-            prev,
-            play,
-            next,
+            buttons,
             on_click: None,
             on_collapsed_click_widget: TextWidget::new(id, collapsed_id, shared_config.clone())
                 .with_icon("music")?
@@ -574,14 +541,11 @@ impl Block for Music {
             _ => State::Idle,
         };
 
-        [&mut self.play, &mut self.prev, &mut self.next]
-            .iter_mut()
-            .filter_map(|button| button.as_mut())
-            .for_each(|button| button.set_state(state));
+        self.buttons.set_state(state);
 
         self.current_song_widget.set_state(state);
 
-        if let Some(ref mut play) = self.play {
+        if let Some(play) = self.buttons.segment("play") {
             play.set_icon(match metadata.playback_status {
                 PlaybackStatus::Playing => "music_pause",
                 PlaybackStatus::Paused => "music_play",
@@ -606,12 +570,11 @@ impl Block for Music {
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if let Some(event_id) = event.instance {
-            let action = match event_id {
-                id if id == self.play_id => "PlayPause",
-                id if id == self.next_id => "Next",
-                id if id == self.prev_id => "Previous",
-                id if id == self.id => "",
-                id if id == self.collapsed_id => "",
+            let action = match self.buttons.clicked(event) {
+                Some("play") => "PlayPause",
+                Some("next") => "Next",
+                Some("prev") => "Previous",
+                _ if event_id == self.id || event_id == self.collapsed_id => "",
                 _ => return Ok(()),
             };
 
@@ -703,15 +666,7 @@ impl Block for Music {
             vec![]
         } else if players.len() > 0 && !self.current_song_widget.is_empty() {
             let mut elements: Vec<&dyn I3BarWidget> = vec![&self.current_song_widget];
-            if let Some(ref prev) = self.prev {
-                elements.push(prev);
-            }
-            if let Some(ref play) = self.play {
-                elements.push(play);
-            }
-            if let Some(ref next) = self.next {
-                elements.push(next);
-            }
+            elements.extend(self.buttons.widgets());
             elements
         } else if self.current_song_widget.is_empty() {
             vec![&self.on_collapsed_click_widget]
@@ -1,42 +1,57 @@
 use std::fmt;
-use std::fs::{read_to_string, OpenOptions};
+use std::fs::read_to_string;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
+use nix::sys::socket::SockAddr;
 use regex::bytes::{Captures, Regex};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_smoothing};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::netlink;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{escape_pango_text, format_vec_to_bar_graph};
+use crate::util::{escape_pango_text, Smoother, Smoothing};
+use crate::widgets::graph::{GraphConfig, History};
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing};
 
 lazy_static! {
-    static ref DEFAULT_DEV_REGEX: Regex = Regex::new("default.*dev (\\w*).*").unwrap();
     static ref WHITESPACE_REGEX: Regex = Regex::new("\\s+").unwrap();
     static ref ETHTOOL_SPEED_REGEX: Regex = Regex::new("Speed: (\\d+\\w\\w/s)").unwrap();
     static ref IW_BITRATE_REGEX: Regex =
         Regex::new("tx bitrate: (\\d+(?:\\.?\\d+) [[:alpha:]]+/s)").unwrap();
 }
 
+/// The drivers of phones that share their connection over USB
+const TETHER_DRIVERS: &[&str] = &["rndis_host", "ipheth", "cdc_ncm"];
+
+/// An address outside of any local network, to find the route that traffic actually takes, which
+/// may be a VPN's even while the default route isn't, as with `wg-quick`
+const ROUTE_PROBE: Ipv4Addr = Ipv4Addr::new(1, 1, 1, 1);
+
 #[derive(Debug)]
 pub struct NetworkDevice {
     device: String,
-    device_path: PathBuf,
     wireless: bool,
     tun: bool,
     wg: bool,
     ppp: bool,
+    tether: bool,
 }
 
 impl NetworkDevice {
@@ -46,7 +61,12 @@ impl NetworkDevice {
         let device_path = Path::new("/sys/class/net").join(device.clone());
 
         // I don't believe that this should ever change, so set it now:
-        let wireless = device_path.join("wireless").exists();
+        let wireless = match bsd::bsd() {
+            Some(_) => bsd::ifconfig(&device)
+                .map(|ifconfig| bsd::ifconfig_is_wireless(&ifconfig))
+                .unwrap_or(false),
+            None => device_path.join("wireless").exists(),
+        };
         let tun = device_path.join("tun_flags").exists()
             || device.starts_with("tun")
             || device.starts_with("tap");
@@ -56,20 +76,25 @@ impl NetworkDevice {
 
         let wg = match &uevent_content {
             Ok(s) => s.contains("wireguard"),
-            Err(_e) => false,
+            Err(_e) => bsd::bsd().is_some() && device.starts_with("wg"),
         };
         let ppp = match &uevent_content {
             Ok(s) => s.contains("ppp"),
-            Err(_e) => false,
+            Err(_e) => bsd::bsd().is_some() && device.starts_with("ppp"),
         };
+        let tether = std::fs::read_link(device_path.join("device/driver"))
+            .ok()
+            .and_then(|driver| Some(driver.file_name()?.to_str()?.to_string()))
+            .map(|driver| TETHER_DRIVERS.contains(&driver.as_str()))
+            .unwrap_or(false);
 
         NetworkDevice {
             device,
-            device_path,
             wireless,
             tun,
             wg,
             ppp,
+            tether,
         }
     }
 
@@ -81,67 +106,64 @@ impl NetworkDevice {
     /// A default device is usually selected by the network manager
     /// and will change when the status of devices change.
     pub fn default_device() -> Option<String> {
-        String::from_utf8(
-            Command::new("ip")
-                .args(&["route", "show", "default"])
-                .output()
-                .ok()
-                .and_then(|o| {
-                    let mut captures = DEFAULT_DEV_REGEX.captures_iter(&o.stdout);
-                    if let Some(cap) = captures.next() {
-                        cap.get(1).map(|x| x.as_bytes().to_vec())
-                    } else {
-                        None
-                    }
-                })?,
-        )
-        .ok()
+        if bsd::bsd().is_some() {
+            return bsd::default_interface();
+        }
+        netlink::default_device()
+    }
+
+    /// The device that traffic to the internet goes through
+    pub fn route_device() -> Option<String> {
+        if bsd::bsd().is_some() {
+            return bsd::default_interface();
+        }
+        netlink::route_device(ROUTE_PROBE)
     }
 
     /// Check whether the device exists.
     pub fn exists(&self) -> Result<bool> {
-        Ok(self.device_path.exists())
+        if bsd::bsd().is_some() {
+            return Ok(bsd::ifconfig(&self.device).is_some());
+        }
+        Ok(netlink::link(&self.device)?.is_some())
     }
 
     /// Check whether this network device is in the `up` state. Note that a
     /// device that is not `up` is not necessarily `down`.
     pub fn is_up(&self) -> Result<bool> {
-        let operstate_file = self.device_path.join("operstate");
-        if !operstate_file.exists() {
+        if bsd::bsd().is_some() {
+            return Ok(bsd::ifconfig(&self.device)
+                .map(|ifconfig| bsd::ifconfig_is_up(&ifconfig))
+                .unwrap_or(false));
+        }
+        Ok(match netlink::link(&self.device)? {
             // It seems more reasonable to treat these as inactive networks as
             // opposed to erroring out the entire block.
-            Ok(false)
-        } else if self.tun || self.wg || self.ppp {
-            Ok(true)
-        } else {
-            let operstate = read_file(&operstate_file)?;
-            let carrier_file = self.device_path.join("carrier");
-            if !carrier_file.exists() {
-                Ok(operstate == "up")
-            } else if operstate == "up" {
-                Ok(true)
-            } else {
-                let carrier = read_file(&carrier_file);
-                match carrier {
-                    Ok(carrier) => Ok(carrier == "1"),
-                    Err(_e) => Ok(operstate == "up"),
-                }
-            }
-        }
+            None => false,
+            // Tunnels don't report a carrier or an operational state
+            Some(link) if self.is_vpn() => link.up,
+            Some(link) => link.up && (link.operational || link.carrier),
+        })
     }
 
     /// Query the device for the current `tx_bytes` statistic.
     pub fn tx_bytes(&self) -> Result<u64> {
-        read_file(&self.device_path.join("statistics/tx_bytes"))?
-            .parse::<u64>()
-            .block_error("net", "Failed to parse tx_bytes")
+        if bsd::bsd().is_some() {
+            return bsd::interface_bytes(&self.device).map(|(_, tx)| tx);
+        }
+        Ok(netlink::link(&self.device)?
+            .map(|link| link.tx_bytes)
+            .unwrap_or(0))
     }
 
     /// Query the device for the current `rx_bytes` statistic.
     pub fn rx_bytes(&self) -> Result<u64> {
-        read_file(&self.device_path.join("statistics/rx_bytes"))?
-            .parse::<u64>()
-            .block_error("net", "Failed to parse rx_bytes")
+        if bsd::bsd().is_some() {
+            return bsd::interface_bytes(&self.device).map(|(rx, _)| rx);
+        }
+        Ok(netlink::link(&self.device)?
+            .map(|link| link.rx_bytes)
+            .unwrap_or(0))
     }
 
     /// Checks whether this device is wireless.
@@ -154,12 +176,39 @@ impl NetworkDevice {
         self.tun || self.wg || self.ppp
     }
 
+    /// The icon for the kind of device
+    pub fn icon(&self) -> &'static str {
+        if self.wireless {
+            "net_wireless"
+        } else if self.is_vpn() {
+            "net_vpn"
+        } else if self.tether {
+            "net_tether"
+        } else if self.device == "lo" {
+            "net_loopback"
+        } else {
+            "net_wired"
+        }
+    }
+
     /// Queries the wireless SSID of this device, if it is connected to one.
     pub fn wifi_info(&self) -> Result<(Option<String>, Option<f64>, Option<i64>)> {
         if !self.is_up()? || !self.wireless {
             return Ok((None, None, None));
         }
 
+        if bsd::bsd().is_some() {
+            let ssid = bsd::ifconfig(&self.device)
+                .and_then(|ifconfig| bsd::ifconfig_ssid(&ifconfig))
+                .map(|ssid| escape_pango_text(&ssid));
+            return Ok((ssid, None, None));
+        }
+
+        self.nl80211_info()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn nl80211_info(&self) -> Result<(Option<String>, Option<f64>, Option<i64>)> {
         let interfaces = nl80211::Socket::connect()
             .block_error("net", "nl80211: failed to connect to the socket")?
             .get_interfaces_info()
@@ -189,81 +238,83 @@ impl NetworkDevice {
         Ok((None, None, None))
     }
 
-    /// Queries the inet IP of this device (using `ip`).
+    /// nl80211 is a Linux interface
+    #[cfg(not(target_os = "linux"))]
+    fn nl80211_info(&self) -> Result<(Option<String>, Option<f64>, Option<i64>)> {
+        Ok((None, None, None))
+    }
+
+    /// Queries the inet IP of this device.
     pub fn ip_addr(&self) -> Result<Option<String>> {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
+        if bsd::bsd().is_some() {
+            return Ok(Some(
+                bsd::ifconfig(&self.device)
+                    .and_then(|ifconfig| bsd::ifconfig_address(&ifconfig, "inet"))
+                    .unwrap_or_default(),
+            ));
         }
-
-        let ip = ip_devs
-            .iter()
-            .filter(|dev| dev.addr_info.is_some())
-            .flat_map(|dev| &dev.addr_info)
-            .flatten()
-            .filter_map(|addr| addr.local.clone())
-            .next();
-
-        Ok(match ip {
-            Some(addr) => Some(addr),
-            _ => Some("".to_string()),
-        })
+        let addresses = self.addresses()?;
+        Ok(Some(
+            addresses
+                .iter()
+                .find(|address| address.is_ipv4())
+                .map(IpAddr::to_string)
+                .unwrap_or_default(),
+        ))
     }
 
-    /// Queries the inet IPv6 of this device (using `ip`).
+    /// Queries the inet IPv6 of this device, preferring addresses that aren't link-local.
     pub fn ipv6_addr(&self) -> Result<Option<String>> {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet6", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
+        if bsd::bsd().is_some() {
+            return Ok(Some(
+                bsd::ifconfig(&self.device)
+                    .and_then(|ifconfig| bsd::ifconfig_address(&ifconfig, "inet6"))
+                    .unwrap_or_default(),
+            ));
         }
+        let addresses: Vec<Ipv6Addr> = self
+            .addresses()?
+            .into_iter()
+            .filter_map(|address| match address {
+                IpAddr::V6(address) => Some(address),
+                IpAddr::V4(_) => None,
+            })
+            .collect();
+        let link_local = |address: &Ipv6Addr| address.segments()[0] & 0xffc0 == 0xfe80;
+        Ok(Some(
+            addresses
+                .iter()
+                .find(|address| !link_local(address))
+                .or_else(|| addresses.first())
+                .map(Ipv6Addr::to_string)
+                .unwrap_or_default(),
+        ))
+    }
 
-        let ip = ip_devs
-            .iter()
-            .filter(|dev| dev.addr_info.is_some())
-            .flat_map(|dev| &dev.addr_info)
-            .flatten()
-            .filter_map(|addr| addr.local.clone())
-            .next();
-
-        Ok(match ip {
-            Some(addr) => Some(addr),
-            _ => Some("".to_string()),
-        })
+    /// The addresses of this device, in the order the kernel lists them
+    fn addresses(&self) -> Result<Vec<IpAddr>> {
+        Ok(nix::ifaddrs::getifaddrs()
+            .block_error("net", "failed to get the addresses of the interfaces")?
+            .filter(|interface| interface.interface_name == self.device)
+            .filter_map(|interface| match interface.address {
+                Some(SockAddr::Inet(address)) => Some(address.to_std().ip()),
+                _ => None,
+            })
+            .collect())
     }
 
     /// Queries the bitrate of this device
     pub fn bitrate(&self) -> Result<Option<String>> {
         let up = self.is_up()?;
-        // Doesn't really make sense to crash the bar here
-        if !up {
+        // Doesn't really make sense to crash the bar here, and neither iw nor ethtool exist on
+        // the BSDs
+        if !up || bsd::bsd().is_some() {
             return Ok(None);
         }
         if self.wireless {
@@ -304,6 +355,138 @@ impl NetworkDevice {
     }
 }
 
+/// The process that used the most bandwidth during the last interval
+#[derive(Debug, Clone, PartialEq)]
+struct TopProcess {
+    name: String,
+    speed_up: f64,
+    speed_down: f64,
+}
+
+/// Parses a line of `nethogs -t` output like `/usr/bin/curl/1234/1000\t1.5\t20.25`, where the
+/// speeds are in KiB/s.
+fn parse_nethogs_line(line: &str) -> Option<TopProcess> {
+    let mut fields = line.split('\t');
+    let program = fields.next()?;
+    let speed_up: f64 = fields.next()?.trim().parse().ok()?;
+    let speed_down: f64 = fields.next()?.trim().parse().ok()?;
+
+    // Strip the trailing `/<pid>/<uid>` and the directory
+    let mut parts = program.rsplitn(3, '/');
+    let path = match (parts.next(), parts.next(), parts.next()) {
+        (Some(_uid), Some(_pid), Some(path)) => path,
+        _ => program,
+    };
+    let name = path.rsplit('/').next().unwrap_or(path);
+
+    Some(TopProcess {
+        name: name.to_string(),
+        speed_up: speed_up * 1024.,
+        speed_down: speed_down * 1024.,
+    })
+}
+
+/// Runs `nethogs` in trace mode and keeps track of the process with the most traffic, until the
+/// block drops `top_process`. nethogs needs the `cap_net_admin` and `cap_net_raw` capabilities,
+/// without them the placeholders stay empty.
+fn watch_top_process(
+    device: Option<String>,
+    interval: Duration,
+    top_process: Arc<Mutex<Option<TopProcess>>>,
+) -> Result<()> {
+    let delay = interval.as_secs().max(1).to_string();
+    let mut args = vec!["-t", "-d", &delay];
+    if let Some(device) = &device {
+        args.push(device);
+    }
+    let mut child = Command::new("nethogs")
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .block_error("net", "failed to start nethogs")?;
+    let stdout = child
+        .stdout
+        .take()
+        .block_error("net", "failed to read nethogs output")?;
+
+    thread::Builder::new()
+        .name("net_nethogs".into())
+        .spawn(move || {
+            let mut top: Option<TopProcess> = None;
+            for line in BufReader::new(stdout).lines().scan((), |_, line| line.ok()) {
+                // nethogs prints every interval, and is ended once the block is dropped
+                if Arc::strong_count(&top_process) == 1 {
+                    child.kill().ok();
+                    break;
+                }
+                if line.starts_with("Refreshing:") {
+                    // Each refresh lists all processes with traffic
+                    if let Ok(mut current) = top_process.lock() {
+                        *current = top.take();
+                    }
+                } else if let Some(process) = parse_nethogs_line(&line) {
+                    let total = process.speed_up + process.speed_down;
+                    if top
+                        .as_ref()
+                        .map(|top| top.speed_up + top.speed_down < total)
+                        .unwrap_or(true)
+                    {
+                        top = Some(process);
+                    }
+                }
+            }
+            child.wait().ok();
+        })
+        .block_error("net", "failed to start nethogs thread")?;
+
+    Ok(())
+}
+
+/// The device of the default route, the loopback device while there is none
+fn default_device() -> String {
+    match NetworkDevice::default_device() {
+        Some(device) if !device.is_empty() => device,
+        _ => "lo".to_string(),
+    }
+}
+
+/// How often the netlink thread checks whether the block is still there
+const WATCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Updates the block as soon as a link goes up or down or an address or route changes, e.g.
+/// because another network was joined. Wireless drivers also report roaming to another access
+/// point as a change of the link. Ends once the block drops `changed`.
+fn watch_changes(id: usize, changed: Arc<AtomicBool>, update_request: Sender<Task>) -> Result<()> {
+    let events = match netlink::subscribe(Some(WATCH_TIMEOUT)) {
+        Ok(events) => events,
+        Err(error) => {
+            // The block still updates every interval
+            log::warn!("not watching the network for changes: {:?}", error);
+            return Ok(());
+        }
+    };
+    thread::Builder::new()
+        .name("net_netlink".into())
+        .spawn(move || {
+            while Arc::strong_count(&changed) > 1 {
+                if !netlink::wait_for_change(&events, Duration::from_millis(100)) {
+                    continue;
+                }
+                changed.store(true, Ordering::Relaxed);
+                let task = Task {
+                    id,
+                    update_time: Instant::now(),
+                };
+                if update_request.send(task).is_err() {
+                    break;
+                }
+            }
+        })
+        .block_error("net", "failed to start netlink thread")?;
+    Ok(())
+}
+
 pub struct Net {
     id: usize,
     format: FormatTemplate,
@@ -314,19 +497,24 @@ pub struct Net {
     bitrate: Option<String>,
     speed_up: f64,
     speed_down: f64,
-    graph_tx: String,
-    graph_rx: String,
+    smoothing_up: Smoother,
+    smoothing_down: Smoother,
+    graph_tx: History,
+    graph_rx: History,
+    top_process: Option<Arc<Mutex<Option<TopProcess>>>>,
     update_interval: Duration,
     device: NetworkDevice,
     auto_device: bool,
-    tx_buff: Vec<f64>,
-    rx_buff: Vec<f64>,
+    /// Set when the network changed since the last update
+    changed: Arc<AtomicBool>,
     tx_bytes: u64,
     rx_bytes: u64,
+    last_bytes: Instant,
     active: bool,
     exists: bool,
     hide_inactive: bool,
     hide_missing: bool,
+    vpn_badge: bool,
     last_update: Instant,
     shared_config: SharedConfig,
 }
@@ -363,14 +551,24 @@ pub struct NetConfig {
 
     pub format_alt: Option<FormatTemplate>,
 
-    /// Which interface in /sys/class/net/ to read from.
-    pub device: Option<String>,
+    /// The interface to show, or "auto" for the one of the default route
+    pub device: String,
 
     /// Whether to hide networks that are down/inactive completely.
     pub hide_inactive: bool,
 
     /// Whether to hide networks that are missing.
     pub hide_missing: bool,
+
+    /// Whether to add a lock to the icon while traffic goes through a VPN
+    pub vpn_badge: bool,
+
+    /// Smoothing applied to the up and down speeds
+    #[serde(deserialize_with = "deserialize_opt_smoothing")]
+    pub smoothing: Option<Smoothing>,
+
+    /// How `graph_up` and `graph_down` are drawn
+    pub graph: GraphConfig,
 }
 
 impl Default for NetConfig {
@@ -379,9 +577,12 @@ impl Default for NetConfig {
             interval: Duration::from_secs(1),
             format: FormatTemplate::default(),
             format_alt: None,
-            device: None,
+            device: "auto".to_string(),
             hide_inactive: false,
             hide_missing: false,
+            vpn_badge: true,
+            smoothing: None,
+            graph: GraphConfig::default(),
         }
     }
 }
@@ -393,39 +594,50 @@ impl ConfigBlock for Net {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let default_device = match NetworkDevice::default_device() {
-            Some(ref s) if !s.is_empty() => s.to_string(),
-            _ => "lo".to_string(),
-        };
-        let device = match block_config.device.clone() {
-            Some(d) => NetworkDevice::from_device(d),
-            _ => NetworkDevice::from_device(default_device),
+        let auto_device = block_config.device == "auto";
+        let device = match auto_device {
+            true => NetworkDevice::from_device(default_device()),
+            false => NetworkDevice::from_device(block_config.device.clone()),
         };
         let init_rx_bytes = device.rx_bytes().unwrap_or(0);
         let init_tx_bytes = device.tx_bytes().unwrap_or(0);
-        let wireless = device.is_wireless();
-        let vpn = device.is_vpn();
+        let icon = device.icon();
 
         let format = block_config
             .format
             .with_default("{speed_down;K}{speed_up;K}")?;
         let format_alt = block_config.format_alt;
 
+        // Only run nethogs if its results are shown
+        let top_process = if format.contains("top_process")
+            || format_alt
+                .as_ref()
+                .map(|f| f.contains("top_process"))
+                .unwrap_or(false)
+        {
+            let top_process = Arc::new(Mutex::new(None));
+            watch_top_process(
+                (!auto_device).then_some(block_config.device.clone()),
+                block_config.interval,
+                top_process.clone(),
+            )?;
+            Some(top_process)
+        } else {
+            None
+        };
+
+        let changed = Arc::new(AtomicBool::new(false));
+        if bsd::bsd().is_none() {
+            watch_changes(id, changed.clone(), tx_update_request)?;
+        }
+
         Ok(Net {
             id,
             update_interval: block_config.interval,
             output: TextWidget::new(id, 0, shared_config.clone())
-                .with_icon(if wireless {
-                    "net_wireless"
-                } else if vpn {
-                    "net_vpn"
-                } else if device.device == "lo" {
-                    "net_loopback"
-                } else {
-                    "net_wired"
-                })?
+                .with_icon(icon)?
                 .with_text("")
                 .with_spacing(Spacing::Inline),
             // TODO: a better way to deal with this?
@@ -449,18 +661,22 @@ impl ConfigBlock for Net {
             .then(String::new),
             speed_up: 0.0,
             speed_down: 0.0,
-            graph_tx: String::new(),
-            graph_rx: String::new(),
+            smoothing_up: Smoother::new(block_config.smoothing),
+            smoothing_down: Smoother::new(block_config.smoothing),
+            graph_tx: History::new(block_config.graph.clone()),
+            graph_rx: History::new(block_config.graph),
+            top_process,
             device,
-            auto_device: block_config.device.is_none(),
-            rx_buff: vec![0.; 10],
-            tx_buff: vec![0.; 10],
+            auto_device,
+            changed,
             rx_bytes: init_rx_bytes,
             tx_bytes: init_tx_bytes,
+            last_bytes: Instant::now(),
             active: true,
             exists: true,
             hide_inactive: block_config.hide_inactive,
             hide_missing: block_config.hide_missing,
+            vpn_badge: block_config.vpn_badge,
             last_update: Instant::now() - Duration::from_secs(30),
             shared_config,
             format,
@@ -469,19 +685,6 @@ impl ConfigBlock for Net {
     }
 }
 
-fn read_file(path: &Path) -> Result<String> {
-    let mut f = OpenOptions::new().read(true).open(path).block_error(
-        "net",
-        &format!("failed to open file {}", path.to_string_lossy()),
-    )?;
-    let mut content = String::new();
-    f.read_to_string(&mut content)
-        .block_error("net", &format!("failed to read {}", path.to_string_lossy()))?;
-    // Removes trailing newline
-    content.pop();
-    Ok(content)
-}
-
 impl Net {
     fn update_bitrate(&mut self) -> Result<()> {
         if let Some(ref mut bitrate_string) = self.bitrate {
@@ -510,32 +713,25 @@ impl Net {
     }
 
     fn update_tx_rx(&mut self) -> Result<()> {
-        // TODO: consider using `as_nanos`
-        let update_interval = (self.update_interval.as_secs() as f64)
-            + (self.update_interval.subsec_nanos() as f64 / 1_000_000_000.0);
+        // Changes of the network update the block between intervals
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_bytes).as_secs_f64();
+        self.last_bytes = now;
+        if elapsed <= 0. {
+            return Ok(());
+        }
 
-        // Update the throughput/graph widgets if they are enabled
         let current_tx = self.device.tx_bytes()?;
-        let diff = current_tx.saturating_sub(self.tx_bytes);
-        let tx_bytes = (diff as f64 / update_interval) as u64;
+        let tx_bytes = current_tx.saturating_sub(self.tx_bytes) as f64 / elapsed;
         self.tx_bytes = current_tx;
-
-        self.speed_up = tx_bytes as f64;
-
-        self.tx_buff.remove(0);
-        self.tx_buff.push(tx_bytes as f64);
-        self.graph_tx = format_vec_to_bar_graph(&self.tx_buff, None, None);
+        self.speed_up = self.smoothing_up.push(tx_bytes);
+        self.graph_tx.push(tx_bytes);
 
         let current_rx = self.device.rx_bytes()?;
-        let diff = current_rx.saturating_sub(self.rx_bytes);
-        let rx_bytes = (diff as f64 / update_interval) as u64;
+        let rx_bytes = current_rx.saturating_sub(self.rx_bytes) as f64 / elapsed;
         self.rx_bytes = current_rx;
-
-        self.speed_down = rx_bytes as f64;
-
-        self.rx_buff.remove(0);
-        self.rx_buff.push(rx_bytes as f64);
-        self.graph_rx = format_vec_to_bar_graph(&self.rx_buff, None, None);
+        self.speed_down = self.smoothing_down.push(rx_bytes);
+        self.graph_rx.push(rx_bytes);
 
         Ok(())
     }
@@ -543,27 +739,30 @@ impl Net {
 
 impl Block for Net {
     fn update(&mut self) -> Result<Option<Update>> {
-        // Update device
-        if self.auto_device {
-            let dev = match NetworkDevice::default_device() {
-                Some(ref s) if !s.is_empty() => s.to_string(),
-                _ => "lo".to_string(),
-            };
+        let changed = self.changed.swap(false, Ordering::Relaxed);
 
+        // Follow the default route
+        if self.auto_device && changed {
+            let dev = default_device();
             if self.device.device() != dev {
                 self.device = NetworkDevice::from_device(dev);
-                self.output.set_icon(if self.device.is_wireless() {
-                    "net_wireless"
-                } else if self.device.is_vpn() {
-                    "net_vpn"
-                } else if self.device.device == "lo" {
-                    "net_loopback"
-                } else {
-                    "net_wired"
-                })?;
+                self.tx_bytes = self.device.tx_bytes()?;
+                self.rx_bytes = self.device.rx_bytes()?;
+                self.last_bytes = Instant::now();
             }
         }
 
+        let mut icons = vec![self.device.icon()];
+        if self.vpn_badge
+            && !self.device.is_vpn()
+            && NetworkDevice::route_device()
+                .map(|device| NetworkDevice::from_device(device).is_vpn())
+                .unwrap_or(false)
+        {
+            icons.push("net_vpn");
+        }
+        self.output.set_icons(&icons)?;
+
         // skip updating if device is not up.
         self.exists = self.device.exists()?;
         self.active = self.exists && self.device.is_up()?;
@@ -572,9 +771,9 @@ impl Block for Net {
             return Ok(Some(self.update_interval.into()));
         }
 
-        // Update SSID and IP address every 30s and the bitrate every 10s
+        // Update the IP address when the network changes and every 30s, and the bitrate every 10s
         let now = Instant::now();
-        if now.duration_since(self.last_update).as_secs() % 10 == 0 {
+        if changed || now.duration_since(self.last_update).as_secs() % 10 == 0 {
             self.update_bitrate()?;
         }
 
@@ -590,7 +789,8 @@ impl Block for Net {
             Some(_) => false,
         };
 
-        if (now.duration_since(self.last_update).as_secs() > 30)
+        if changed
+            || (now.duration_since(self.last_update).as_secs() > 30)
             || waiting_for_ip
             || waiting_for_ipv6
         {
@@ -605,6 +805,15 @@ impl Block for Net {
         let empty_string = "".to_string();
         let na_string = "N/A".to_string();
 
+        let top_process = self
+            .top_process
+            .as_ref()
+            .and_then(|top_process| top_process.lock().ok()?.clone());
+        let (top_process, top_up, top_down) = match top_process {
+            Some(process) => (process.name, process.speed_up, process.speed_down),
+            None => (String::new(), 0., 0.),
+        };
+
         let values = map!(
             "ssid" => Value::from_string(ssid.unwrap_or(na_string)),
             "signal_strength" => Value::from_integer(signal.unwrap_or(0)).percents(),
@@ -614,8 +823,11 @@ impl Block for Net {
             "ipv6" => Value::from_string(self.ipv6_addr.clone().unwrap_or(empty_string)),
             "speed_up" => Value::from_float(self.speed_up).bytes().icon(self.shared_config.get_icon("net_up")?),
             "speed_down" => Value::from_float(self.speed_down).bytes().icon(self.shared_config.get_icon("net_down")?),
-            "graph_up" => Value::from_string(self.graph_tx.clone()),
-            "graph_down" => Value::from_string(self.graph_rx.clone()),
+            "graph_up" => Value::from_string(self.graph_tx.draw()),
+            "graph_down" => Value::from_string(self.graph_rx.draw()),
+            "top_process" => Value::from_string(top_process),
+            "top_process_up" => Value::from_float(top_up).bytes(),
+            "top_process_down" => Value::from_float(top_down).bytes(),
         );
 
         self.output.set_texts(self.format.render(&values)?);
@@ -646,16 +858,7 @@ impl Block for Net {
     }
 }
 
-#[derive(Deserialize)]
-struct IpDev {
-    addr_info: Option<Vec<IpAddrInfo>>,
-}
-
-#[derive(Deserialize)]
-struct IpAddrInfo {
-    local: Option<String>,
-}
-
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
 fn decode_escaped_unicode(raw: &[u8]) -> String {
     // Match escape sequences like \x2a or \x0D
     let re = Regex::new(r"\\x([0-9A-Fa-f]{2})").unwrap();
@@ -669,6 +872,7 @@ fn decode_escaped_unicode(raw: &[u8]) -> String {
     String::from_utf8_lossy(&result).to_string()
 }
 
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
 fn signal_percents(raw: i8) -> i64 {
     let raw = raw as f64;
 
@@ -684,7 +888,44 @@ fn signal_percents(raw: i8) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::blocks::net::decode_escaped_unicode;
+    use crate::blocks::net::{
+        decode_escaped_unicode, parse_nethogs_line, NetworkDevice, TopProcess,
+    };
+
+    #[test]
+    fn test_icon() {
+        let device = |device: &str, wireless, wg, tether| NetworkDevice {
+            device: device.to_string(),
+            wireless,
+            tun: false,
+            wg,
+            ppp: false,
+            tether,
+        };
+        assert_eq!(device("wlan0", true, false, false).icon(), "net_wireless");
+        assert_eq!(device("wg0", false, true, false).icon(), "net_vpn");
+        assert_eq!(device("usb0", false, false, true).icon(), "net_tether");
+        assert_eq!(device("lo", false, false, false).icon(), "net_loopback");
+        assert_eq!(device("eth0", false, false, false).icon(), "net_wired");
+    }
+
+    #[test]
+    fn test_parse_nethogs_line() {
+        assert_eq!(
+            parse_nethogs_line("/usr/lib/firefox/firefox/4242/1000\t1.5\t20.25"),
+            Some(TopProcess {
+                name: "firefox".to_string(),
+                speed_up: 1536.,
+                speed_down: 20736.,
+            })
+        );
+        assert_eq!(
+            parse_nethogs_line("unknown TCP/0/0\t0\t0.5").map(|p| p.name),
+            Some("unknown TCP".to_string())
+        );
+        assert_eq!(parse_nethogs_line("Refreshing:"), None);
+        assert_eq!(parse_nethogs_line(""), None);
+    }
 
     #[test]
     fn test_ssid_decode_escaped_unicode() {
@@ -1,38 +1,200 @@
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Instant;
+//! Shows whether the notification daemon is in do not disturb mode and how many notifications
+//! haven't been dismissed yet. Left click toggles do not disturb and right click dismisses all
+//! notifications.
+//!
+//! The daemons announce changes of do not disturb and closed notifications with D-Bus signals,
+//! but nothing tells about new notifications short of eavesdropping on the bus, so the count is
+//! also polled.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
-use dbus::ffidisp::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
-use dbus::ffidisp::{BusType, Connection};
-use dbus::message::SignalArgs;
+use dbus::arg::PropMap;
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    Properties, PropertiesPropertiesChanged as PPC,
+};
+use dbus::blocking::SyncConnection;
+use dbus::message::{MatchRule, SignalArgs};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::{Bus, Watch};
 use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
-use crate::widgets::I3BarWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const DUNST: &str = "org.dunstproject.cmd0";
+const DUNST_PATH: &str = "/org/freedesktop/Notifications";
+const MAKO: &str = "fr.emersion.Mako";
+const MAKO_PATH: &str = "/fr/emersion/Mako";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyDriver {
+    Dunst,
+    Mako,
+}
+
+impl NotifyDriver {
+    /// Whether do not disturb is on
+    fn paused(self, con: &SyncConnection, dnd_mode: &str) -> Result<bool> {
+        match self {
+            NotifyDriver::Dunst => con
+                .with_proxy(NOTIFICATIONS, DUNST_PATH, TIMEOUT)
+                .get(DUNST, "paused")
+                .block_error("notify", "Failed to get dunst state. Is it running?"),
+            NotifyDriver::Mako => Ok(self.mako_modes(con)?.iter().any(|mode| mode == dnd_mode)),
+        }
+    }
+
+    /// The notifications that are shown or waiting to be shown
+    fn count(self, con: &SyncConnection) -> Result<u32> {
+        match self {
+            NotifyDriver::Dunst => {
+                let proxy = con.with_proxy(NOTIFICATIONS, DUNST_PATH, TIMEOUT);
+                let length = |name| -> Result<u32> {
+                    proxy
+                        .get(DUNST, name)
+                        .block_error("notify", "Failed to count the notifications of dunst")
+                };
+                Ok(length("displayedLength")? + length("waitingLength")?)
+            }
+            NotifyDriver::Mako => {
+                let (notifications,): (Vec<PropMap>,) = con
+                    .with_proxy(MAKO, MAKO_PATH, TIMEOUT)
+                    .method_call(MAKO, "ListNotifications", ())
+                    .block_error(
+                        "notify",
+                        "Failed to get the notifications of mako. Is it running?",
+                    )?;
+                Ok(notifications.len() as u32)
+            }
+        }
+    }
+
+    fn mako_modes(self, con: &SyncConnection) -> Result<Vec<String>> {
+        let (modes,): (Vec<String>,) = con
+            .with_proxy(MAKO, MAKO_PATH, TIMEOUT)
+            .method_call(MAKO, "ListModes", ())
+            .block_error(
+                "notify",
+                "Failed to get the modes of mako, which needs mako 1.7",
+            )?;
+        Ok(modes)
+    }
+
+    fn set_paused(self, con: &SyncConnection, dnd_mode: &str, paused: bool) -> Result<()> {
+        match self {
+            NotifyDriver::Dunst => con
+                .with_proxy(NOTIFICATIONS, DUNST_PATH, TIMEOUT)
+                .set(DUNST, "paused", paused)
+                .block_error("notify", "Failed to query D-Bus"),
+            NotifyDriver::Mako => {
+                let mut modes = self.mako_modes(con)?;
+                modes.retain(|mode| mode != dnd_mode);
+                if paused {
+                    modes.push(dnd_mode.to_string());
+                }
+                con.with_proxy(MAKO, MAKO_PATH, TIMEOUT)
+                    .method_call(MAKO, "SetModes", (modes,))
+                    .block_error("notify", "Failed to set the modes of mako")
+            }
+        }
+    }
 
-// TODO
-// Add driver option so can choose between dunst, mako, etc.
+    fn dismiss_all(self, con: &SyncConnection) -> Result<()> {
+        let (destination, path, interface, method) = match self {
+            NotifyDriver::Dunst => (NOTIFICATIONS, DUNST_PATH, DUNST, "NotificationCloseAll"),
+            NotifyDriver::Mako => (MAKO, MAKO_PATH, MAKO, "DismissAllNotifications"),
+        };
+        con.with_proxy(destination, path, TIMEOUT)
+            .method_call(interface, method, ())
+            .block_error("notify", "Failed to dismiss the notifications")
+    }
+}
+
+/// Updates the block when do not disturb is toggled or notifications are closed
+fn watch(
+    id: usize,
+    shared_config: &SharedConfig,
+    tx_update_request: Sender<Task>,
+) -> Result<Vec<Watch>> {
+    let update = move || {
+        tx_update_request
+            .send(Task {
+                id,
+                update_time: Instant::now(),
+            })
+            .is_ok()
+    };
+    // Filtered by interface, as signals come from the unique name of the daemon
+    let changed = {
+        let update = update.clone();
+        shared_config.buses.watch(
+            Bus::Session,
+            PPC::match_rule(None, None).static_clone(),
+            move |changed: PPC, _, _| {
+                if changed.interface_name == DUNST || changed.interface_name == MAKO {
+                    update()
+                } else {
+                    true
+                }
+            },
+        )?
+    };
+    let closed = shared_config.buses.watch(
+        Bus::Session,
+        MatchRule::new_signal(NOTIFICATIONS, "NotificationClosed"),
+        move |_: (u32, u32), _, _| update(),
+    )?;
+    Ok(vec![changed, closed])
+}
 
 pub struct Notify {
     id: usize,
-    paused: Arc<Mutex<i64>>,
     format: FormatTemplate,
     output: TextWidget,
+    con: Arc<SyncConnection>,
+    _watches: Vec<Watch>,
+    driver: NotifyDriver,
+    dnd_mode: String,
+    interval: Duration,
 }
 
-#[derive(Deserialize, Debug, Default, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct NotifyConfig {
+    pub driver: NotifyDriver,
+
     /// Format string which describes the output of this block.
     pub format: FormatTemplate,
+
+    /// The mode of mako that is do not disturb
+    pub dnd_mode: String,
+
+    /// How often to count the notifications
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            driver: NotifyDriver::Dunst,
+            format: FormatTemplate::default(),
+            dnd_mode: "do-not-disturb".to_string(),
+            interval: Duration::from_secs(5),
+        }
+    }
 }
 
 impl ConfigBlock for Notify {
@@ -44,64 +206,18 @@ impl ConfigBlock for Notify {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let c = Connection::get_private(BusType::Session).block_error(
-            "notify",
-            &"Failed to establish D-Bus connection".to_string(),
-        )?;
-
-        let p = c.with_path(
-            "org.freedesktop.Notifications",
-            "/org/freedesktop/Notifications",
-            5000,
-        );
-        let initial_state: bool = p.get("org.dunstproject.cmd0", "paused").block_error(
-            "notify",
-            &"Failed to get dunst state. Is it running?".to_string(),
-        )?;
-
-        let icon = if initial_state { "bell-slash" } else { "bell" };
-
-        // TODO: revisit this lint
-        #[allow(clippy::mutex_atomic)]
-        let state = Arc::new(Mutex::new(initial_state as i64));
-        let state_copy = state.clone();
-
-        thread::Builder::new()
-            .name("notify".into())
-            .spawn(move || {
-                let c = Connection::get_private(BusType::Session)
-                    .expect("Failed to establish D-Bus connection in thread");
-
-                let matched_signal = PropertiesPropertiesChanged::match_str(
-                    Some(&"org.freedesktop.Notifications".into()),
-                    None,
-                );
-                c.add_match(&matched_signal).unwrap();
-                loop {
-                    for msg in c.incoming(1000) {
-                        if let Some(signal) = PropertiesPropertiesChanged::from_message(&msg) {
-                            let value = signal.changed_properties.get("paused").unwrap();
-                            let status = &value.0.as_i64().unwrap();
-                            let mut paused = state_copy.lock().unwrap();
-                            *paused = *status;
-
-                            // Tell block to update now.
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                        }
-                    }
-                }
-            })
-            .unwrap();
+        let con = shared_config.buses.get(Bus::Session)?;
+        let watches = watch(id, &shared_config, send)?;
 
         Ok(Notify {
             id,
-            paused: state,
-            format: block_config.format.with_default("")?,
-            output: TextWidget::new(id, 0, shared_config).with_icon(icon)?,
+            format: block_config.format.with_default("{count}")?,
+            output: TextWidget::new(id, 0, shared_config).with_icon("bell")?,
+            con,
+            _watches: watches,
+            driver: block_config.driver,
+            dnd_mode: block_config.dnd_mode,
+            interval: block_config.interval,
         })
     }
 }
@@ -112,21 +228,26 @@ impl Block for Notify {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let paused = *self
-            .paused
-            .lock()
-            .block_error("notify", "failed to acquire lock for `state`")?;
+        let paused = self.driver.paused(&self.con, &self.dnd_mode)?;
+        let count = self.driver.count(&self.con)?;
 
         let values = map!(
-            "state" => Value::from_string(paused.to_string())
+            "state" => Value::from_string((paused as i64).to_string()),
+            "count" => Value::from_integer(count as i64),
         );
 
         self.output.set_texts(self.format.render(&values)?);
 
-        let icon = if paused == 1 { "bell-slash" } else { "bell" };
+        let icon = if paused { "bell-slash" } else { "bell" };
         self.output.set_icon(icon)?;
+        // Notifications pile up while paused
+        self.output.set_state(match (paused, count) {
+            (_, 0) => State::Idle,
+            (true, _) => State::Warning,
+            (false, _) => State::Info,
+        });
 
-        Ok(None)
+        Ok(Some(self.interval.into()))
     }
 
     // Returns the view of the block, comprised of widgets.
@@ -135,33 +256,16 @@ impl Block for Notify {
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        if let MouseButton::Left = e.button {
-            let c = Connection::get_private(BusType::Session).block_error(
-                "notify",
-                &"Failed to establish D-Bus connection".to_string(),
-            )?;
-
-            let p = c.with_path(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                5000,
-            );
-
-            let paused = *self
-                .paused
-                .lock()
-                .block_error("notify", "failed to acquire lock")?;
-
-            if paused == 1 {
-                p.set("org.dunstproject.cmd0", "paused", false)
-                    .block_error("notify", &"Failed to query D-Bus".to_string())?;
-            } else {
-                p.set("org.dunstproject.cmd0", "paused", true)
-                    .block_error("notify", &"Failed to query D-Bus".to_string())?;
+        match e.button {
+            MouseButton::Left => {
+                let paused = self.driver.paused(&self.con, &self.dnd_mode)?;
+                self.driver.set_paused(&self.con, &self.dnd_mode, !paused)?;
             }
-
-            // block will auto-update due to monitoring the bus
+            MouseButton::Right => self.driver.dismiss_all(&self.con)?,
+            _ => return Ok(()),
         }
+        // Not every daemon signals the change
+        self.update()?;
         Ok(())
     }
 }
@@ -0,0 +1,243 @@
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::SyncConnection;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::Bus;
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const KEEPASSXC_SERVICE: &str = "org.keepassxc.KeePassXC.MainWindow";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordManagerDriver {
+    KeePassXC,
+    Bitwarden,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Locked,
+    Unlocked,
+    /// The password manager isn't running or not logged in
+    Closed,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Locked => "locked",
+            Status::Unlocked => "unlocked",
+            Status::Closed => "closed",
+        }
+    }
+}
+
+pub struct PasswordManager {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    hide_closed: bool,
+    status: Status,
+    /// The session bus, which only KeePassXC is looked up on
+    con: Option<Arc<SyncConnection>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PasswordManagerConfig {
+    /// The password manager to watch
+    pub driver: PasswordManagerDriver,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Hide the block while the password manager isn't running
+    pub hide_closed: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for PasswordManagerConfig {
+    fn default() -> Self {
+        Self {
+            driver: PasswordManagerDriver::KeePassXC,
+            interval: Duration::from_secs(5),
+            hide_closed: false,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Reads the status from the output of `bw status`
+fn parse_bitwarden_status(output: &str) -> Status {
+    #[derive(Deserialize)]
+    struct BitwardenStatus {
+        status: String,
+    }
+    match serde_json::from_str::<BitwardenStatus>(output) {
+        Ok(status) if status.status == "unlocked" => Status::Unlocked,
+        Ok(status) if status.status == "locked" => Status::Locked,
+        _ => Status::Closed,
+    }
+}
+
+/// Bitwarden CLI can only tell a session is unlocked if `BW_SESSION` is set in our environment
+fn bitwarden_status() -> Status {
+    Command::new("bw")
+        .arg("status")
+        .output()
+        .map(|output| parse_bitwarden_status(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(Status::Closed)
+}
+
+/// KeePassXC has no D-Bus method for its lock state, but its Secret Service integration exposes
+/// whether the default database is locked
+fn keepassxc_status(con: &SyncConnection) -> Result<Status> {
+    let (running,): (bool,) = con
+        .with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_secs(1),
+        )
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (KEEPASSXC_SERVICE,))
+        .block_error("password_manager", "Failed to look up KeePassXC on D-Bus.")?;
+    if !running {
+        return Ok(Status::Closed);
+    }
+    let locked: bool = con
+        .with_proxy(
+            "org.freedesktop.secrets",
+            "/org/freedesktop/secrets/aliases/default",
+            Duration::from_secs(1),
+        )
+        .get("org.freedesktop.Secret.Collection", "Locked")
+        // Without the Secret Service integration, nothing can be read without unlocking
+        .unwrap_or(true);
+    Ok(if locked {
+        Status::Locked
+    } else {
+        Status::Unlocked
+    })
+}
+
+fn lock_keepassxc(con: &SyncConnection) -> Result<()> {
+    con.with_proxy(KEEPASSXC_SERVICE, "/keepassxc", Duration::from_secs(1))
+        .method_call(KEEPASSXC_SERVICE, "lockAllDatabases", ())
+        .block_error("password_manager", "Failed to lock KeePassXC.")
+}
+
+impl ConfigBlock for PasswordManager {
+    type Config = PasswordManagerConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let con = match block_config.driver {
+            PasswordManagerDriver::KeePassXC => Some(shared_config.buses.get(Bus::Session)?),
+            PasswordManagerDriver::Bitwarden => None,
+        };
+
+        Ok(PasswordManager {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("security")?,
+            update_interval: block_config.interval,
+            format: block_config.format.with_default("{status}")?,
+            hide_closed: block_config.hide_closed,
+            status: Status::Closed,
+            con,
+        })
+    }
+}
+
+impl Block for PasswordManager {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.status = match self.con {
+            Some(ref con) => keepassxc_status(con)?,
+            None => bitwarden_status(),
+        };
+
+        let values = map!(
+            "status" => Value::from_string(self.status.as_str().to_string()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.status {
+            Status::Unlocked => State::Warning,
+            Status::Locked => State::Good,
+            Status::Closed => State::Idle,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_closed && self.status == Status::Closed {
+            Vec::new()
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left && self.status == Status::Unlocked {
+            match self.con {
+                Some(ref con) => lock_keepassxc(con)?,
+                None => {
+                    Command::new("bw")
+                        .arg("lock")
+                        .output()
+                        .block_error("password_manager", "Failed to run bw lock.")?;
+                }
+            }
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bitwarden_status() {
+        assert_eq!(
+            parse_bitwarden_status(
+                r#"{"serverUrl":null,"lastSync":"2021-05-03T10:12:33.901Z","userEmail":"user@example.com","userId":"00000000-0000-0000-0000-000000000000","status":"unlocked"}"#
+            ),
+            Status::Unlocked
+        );
+        assert_eq!(
+            parse_bitwarden_status(r#"{"status":"locked"}"#),
+            Status::Locked
+        );
+        assert_eq!(
+            parse_bitwarden_status(r#"{"status":"unauthenticated"}"#),
+            Status::Closed
+        );
+        assert_eq!(parse_bitwarden_status(""), Status::Closed);
+    }
+}
@@ -0,0 +1,282 @@
+//! Pings a host in the background and shows the latency and packet loss over a sliding window of
+//! probes, e.g. to spot a flaky Wi-Fi connection
+
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Probes in a row that have to fail for the host to count as unreachable
+const UNREACHABLE: usize = 3;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Icmp,
+    Tcp,
+}
+
+/// The round trip times of the latest probes in seconds, oldest first, none for lost probes
+type Window = Arc<Mutex<VecDeque<Option<f64>>>>;
+
+pub struct Ping {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    host: String,
+    window: Window,
+    warning_rtt: f64,
+    warning_loss: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PingConfig {
+    pub host: String,
+
+    /// Send ICMP echo requests with `ping`, or measure how long a TCP connection takes
+    pub method: Method,
+
+    /// Port for TCP probes
+    pub port: u16,
+
+    /// Number of probes that the average and the loss are computed over
+    pub window: usize,
+
+    /// Time in seconds after which a probe counts as lost
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+
+    /// Average round trip time in milliseconds from which on the block is shown as warning
+    pub warning_rtt: f64,
+
+    /// Packet loss in percent from which on the block is shown as warning
+    pub warning_loss: f64,
+
+    /// Time between probes in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            method: Method::Icmp,
+            port: 443,
+            window: 20,
+            timeout: Duration::from_secs(2),
+            warning_rtt: 100.0,
+            warning_loss: 10.0,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The round trip time in the output of `ping`, like "64 bytes from 1.1.1.1: icmp_seq=1 ttl=57
+/// time=12.3 ms", or "time<1 ms" on BSD for very short ones
+fn parse_ping(output: &str) -> Option<f64> {
+    let time = output.split("time").nth(1)?;
+    let time = time.trim_start_matches(['=', '<']);
+    let milliseconds: f64 = time.split_whitespace().next()?.parse().ok()?;
+    Some(milliseconds / 1000.0)
+}
+
+/// Sends an ICMP echo request with `ping`, or none if `ping` can't send one, e.g. because it
+/// isn't installed or isn't allowed to open a raw socket
+fn probe_icmp(host: &str, timeout: Duration) -> Option<Option<f64>> {
+    // The option for the time to wait for a reply differs between the implementations of `ping`
+    let wait = if cfg!(any(target_os = "freebsd", target_os = "macos")) {
+        "-t"
+    } else if cfg!(target_os = "openbsd") {
+        "-w"
+    } else {
+        "-W"
+    };
+    let output = Command::new("ping")
+        .args(["-n", "-c", "1", wait])
+        .arg(timeout.as_secs().max(1).to_string())
+        .arg(host)
+        .output()
+        .ok()?;
+    match output.status.code() {
+        // No reply
+        Some(1) => Some(None),
+        Some(0) => Some(parse_ping(&String::from_utf8_lossy(&output.stdout))),
+        _ => None,
+    }
+}
+
+fn probe_tcp(host: &str, port: u16, timeout: Duration) -> Option<f64> {
+    let address = (host, port).to_socket_addrs().ok()?.next()?;
+    let start = Instant::now();
+    match TcpStream::connect_timeout(&address, timeout) {
+        // A refused connection still took a round trip to the host
+        Ok(_) => Some(start.elapsed().as_secs_f64()),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => Some(start.elapsed().as_secs_f64()),
+        Err(_) => None,
+    }
+}
+
+impl ConfigBlock for Ping {
+    type Config = PingConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.host.is_empty() || block_config.window == 0 {
+            return Err(ConfigurationError(
+                "ping".to_string(),
+                "host must be set and window must be positive".to_string(),
+            ));
+        }
+
+        let window: Window = Arc::new(Mutex::new(VecDeque::new()));
+        let host = block_config.host.clone();
+        let probes = window.clone();
+        let (port, timeout, interval) = (
+            block_config.port,
+            block_config.timeout,
+            block_config.interval,
+        );
+        let size = block_config.window;
+        let mut method = block_config.method;
+        thread::Builder::new()
+            .name("ping".into())
+            // Ends once the block is gone
+            .spawn(move || {
+                while Arc::strong_count(&probes) > 1 {
+                    let rtt = match method {
+                        Method::Icmp => probe_icmp(&host, timeout).unwrap_or_else(|| {
+                            method = Method::Tcp;
+                            probe_tcp(&host, port, timeout)
+                        }),
+                        Method::Tcp => probe_tcp(&host, port, timeout),
+                    };
+                    {
+                        let mut probes = probes
+                            .lock()
+                            .expect("main thread panicked while holding the probes");
+                        probes.push_back(rtt);
+                        while probes.len() > size {
+                            probes.pop_front();
+                        }
+                    }
+                    if tx_update_request
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                    thread::sleep(interval);
+                }
+            })
+            .internal_error("ping", "failed to start a thread")?;
+
+        Ok(Ping {
+            id,
+            text: TextWidget::new(id, 0, shared_config)
+                .with_icon("ping")?
+                .with_text("..."),
+            format: block_config.format.with_default("{avg} {loss}")?,
+            host: block_config.host,
+            window,
+            warning_rtt: block_config.warning_rtt / 1000.0,
+            warning_loss: block_config.warning_loss,
+        })
+    }
+}
+
+impl Block for Ping {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let probes = self
+            .window
+            .lock()
+            .internal_error("ping", "failed to lock the probes")?
+            .clone();
+        let last = match probes.back() {
+            Some(last) => *last,
+            None => return Ok(None),
+        };
+        let replies: Vec<f64> = probes.iter().flatten().copied().collect();
+        let loss = 100.0 * (probes.len() - replies.len()) as f64 / probes.len() as f64;
+        let avg = if replies.is_empty() {
+            None
+        } else {
+            Some(replies.iter().sum::<f64>() / replies.len() as f64)
+        };
+
+        let seconds = |rtt: Option<f64>| match rtt {
+            Some(rtt) => Value::from_float(rtt).seconds(),
+            None => Value::from_string("-".to_string()),
+        };
+        let values = map!(
+            "host" => Value::from_string(self.host.clone()),
+            "rtt" => seconds(last),
+            "avg" => seconds(avg),
+            "loss" => Value::from_integer(loss.round() as i64).percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        let unreachable = probes.len() >= UNREACHABLE
+            && probes.iter().rev().take(UNREACHABLE).all(Option::is_none);
+        self.text.set_state(if unreachable {
+            State::Critical
+        } else if loss >= self.warning_loss || avg.is_some_and(|avg| avg >= self.warning_rtt) {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ping() {
+        let linux = "PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.\n\
+                     64 bytes from 1.1.1.1: icmp_seq=1 ttl=57 time=12.3 ms\n\n\
+                     --- 1.1.1.1 ping statistics ---\n";
+        assert!((parse_ping(linux).unwrap() - 0.0123).abs() < 1e-9);
+        assert_eq!(
+            parse_ping("64 bytes from 127.0.0.1: icmp_seq=0 ttl=64 time<1 ms\n"),
+            Some(0.001)
+        );
+    }
+}
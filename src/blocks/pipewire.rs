@@ -0,0 +1,528 @@
+//! Controls the volume of the default PipeWire sink or source. The block follows the PipeWire
+//! registry as `pw-dump --monitor` reports it, so it updates as soon as a volume, mute state or
+//! default device changes, and sets them through WirePlumber with `wpctl`.
+//!
+//! The PipeWire API itself isn't used, as the Rust bindings need libpipewire, its headers and
+//! clang to build, and the registry as `pw-dump` prints it carries the same events.
+
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::sound::DeviceKind;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, Spacing, State};
+
+/// How long to wait before following the registry again after `pw-dump` exited
+const RESTART: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    name: String,
+    description: String,
+    media_class: String,
+    /// Volume as shown by mixers, in percent
+    volume: u32,
+    muted: bool,
+}
+
+/// The audio devices and defaults of the PipeWire registry
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: HashMap<u32, Node>,
+    /// The metadata entries of the "default" metadata object, like "default.audio.sink"
+    defaults: HashMap<String, String>,
+    /// Why the registry can't be followed
+    error: Option<String>,
+}
+
+/// What a block keeps track of from the PipeWire registry
+pub trait Registry: Default + Send + 'static {
+    /// Applies the objects of one array printed by `pw-dump`, which are new, changed or removed
+    fn apply(&mut self, objects: &JsonValue);
+
+    /// Notes why the registry can't be followed
+    fn set_error(&mut self, error: String);
+}
+
+impl Registry for Graph {
+    fn apply(&mut self, objects: &JsonValue) {
+        for object in objects.as_array().into_iter().flatten() {
+            let id = match object["id"].as_u64() {
+                Some(id) => id as u32,
+                None => continue,
+            };
+            match object["type"].as_str() {
+                // Removed objects come with nothing but their ID and a `null` info
+                None => {
+                    self.nodes.remove(&id);
+                }
+                Some("PipeWire:Interface:Node") => self.apply_node(id, &object["info"]),
+                Some("PipeWire:Interface:Metadata")
+                    if object["props"]["metadata.name"] == "default" =>
+                {
+                    self.apply_defaults(&object["metadata"])
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+}
+
+impl Graph {
+    fn apply_node(&mut self, id: u32, info: &JsonValue) {
+        let props = &info["props"];
+        let media_class = match props["media.class"].as_str() {
+            Some(class @ ("Audio/Sink" | "Audio/Source")) => class.to_string(),
+            _ => return,
+        };
+        let text = |key: &str| props[key].as_str().unwrap_or_default().to_string();
+        let mut node = self.nodes.remove(&id).unwrap_or(Node {
+            name: text("node.name"),
+            description: text("node.description"),
+            media_class,
+            volume: 0,
+            muted: false,
+        });
+        // Changes of other parameters leave out the volume
+        if let Some(params) = info["params"]["Props"].as_array() {
+            for param in params {
+                if let Some(volumes) = param["channelVolumes"].as_array() {
+                    let volumes: Vec<f64> = volumes.iter().filter_map(|v| v.as_f64()).collect();
+                    if !volumes.is_empty() {
+                        // The channel volumes are linear, mixers show their cube root
+                        let average = volumes.iter().sum::<f64>() / volumes.len() as f64;
+                        node.volume = (average.cbrt() * 100.0).round() as u32;
+                    }
+                }
+                if let Some(muted) = param["mute"].as_bool() {
+                    node.muted = muted;
+                }
+            }
+        }
+        self.nodes.insert(id, node);
+    }
+
+    fn apply_defaults(&mut self, metadata: &JsonValue) {
+        for entry in metadata.as_array().into_iter().flatten() {
+            let key = match entry["key"].as_str() {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+            match entry["value"]["name"].as_str() {
+                Some(name) => self.defaults.insert(key, name.to_string()),
+                None => self.defaults.remove(&key),
+            };
+        }
+    }
+
+    fn media_class(kind: DeviceKind) -> &'static str {
+        match kind {
+            DeviceKind::Sink => "Audio/Sink",
+            DeviceKind::Source => "Audio/Source",
+        }
+    }
+
+    /// The default device of a kind, where the one that the user picked wins over the one that
+    /// WirePlumber would pick
+    fn default_node(&self, kind: DeviceKind) -> Option<(u32, &Node)> {
+        let kind = match kind {
+            DeviceKind::Sink => "audio.sink",
+            DeviceKind::Source => "audio.source",
+        };
+        let name = self
+            .defaults
+            .get(&format!("default.configured.{}", kind))
+            .filter(|name| self.nodes.values().any(|node| &&node.name == name))
+            .or_else(|| self.defaults.get(&format!("default.{}", kind)))?;
+        self.nodes
+            .iter()
+            .find(|(_, node)| &node.name == name)
+            .map(|(id, node)| (*id, node))
+    }
+
+    /// The devices of a kind, in a stable order
+    fn nodes(&self, kind: DeviceKind) -> Vec<(u32, &Node)> {
+        let mut nodes: Vec<(u32, &Node)> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.media_class == Graph::media_class(kind))
+            .map(|(id, node)| (*id, node))
+            .collect();
+        nodes.sort_by_key(|(id, _)| *id);
+        nodes
+    }
+}
+
+/// Keeps the registry of a block followed, see `monitor`. Dropping it ends `pw-dump` and the thread.
+pub struct Monitor {
+    pid: Arc<Mutex<Option<u32>>>,
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid.lock().unwrap().take() {
+            let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        }
+    }
+}
+
+/// Follows the registry with `pw-dump --monitor` in a thread named `name`, over and over again
+/// until the returned `Monitor` is dropped
+pub fn monitor<R: Registry>(
+    name: &str,
+    id: usize,
+    graph: Arc<Mutex<R>>,
+    tx_update_request: Sender<Task>,
+) -> Result<Monitor> {
+    let pid = Arc::new(Mutex::new(None));
+    let thread_pid = pid.clone();
+    thread::Builder::new()
+        .name(name.into())
+        .spawn(move || follow(id, graph, thread_pid, tx_update_request))
+        .internal_error(name, "failed to start a thread")?;
+    Ok(Monitor { pid })
+}
+
+fn follow<R: Registry>(
+    id: usize,
+    graph: Arc<Mutex<R>>,
+    pid: Arc<Mutex<Option<u32>>>,
+    tx_update_request: Sender<Task>,
+) {
+    // The monitor holds the other reference, until the block is dropped
+    let dropped = || Arc::strong_count(&pid) == 1;
+    while !dropped() {
+        let child = Command::new("pw-dump")
+            .arg("--monitor")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(error) => {
+                graph
+                    .lock()
+                    .unwrap()
+                    .set_error(format!("failed to run pw-dump: {}", error));
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+                return;
+            }
+        };
+        *pid.lock().unwrap() = Some(child.id());
+        // The block may have been dropped before pw-dump started
+        if dropped() {
+            let _ = child.kill();
+        }
+
+        // pw-dump prints one array of objects after another
+        let stdout = child.stdout.take().unwrap();
+        for objects in serde_json::Deserializer::from_reader(stdout).into_iter::<JsonValue>() {
+            match objects {
+                Ok(objects) => graph.lock().unwrap().apply(&objects),
+                Err(_) => break,
+            }
+            if dropped() {
+                break;
+            }
+            let sent = tx_update_request.send(Task {
+                id,
+                update_time: Instant::now(),
+            });
+            if sent.is_err() {
+                return;
+            }
+        }
+
+        let _ = child.kill();
+        let _ = child.wait();
+        *pid.lock().unwrap() = None;
+        if dropped() {
+            return;
+        }
+        *graph.lock().unwrap() = R::default();
+        thread::sleep(RESTART);
+    }
+}
+
+pub struct Pipewire {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    device_kind: DeviceKind,
+    step_width: u32,
+    max_vol: u32,
+    show_volume_when_muted: bool,
+    scrolling: Scrolling,
+    on_click: Option<String>,
+    graph: Arc<Mutex<Graph>>,
+    _monitor: Monitor,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PipewireConfig {
+    /// Type of device: sink or source
+    pub device_kind: DeviceKind,
+
+    /// The steps volume is in/decreased by, in percent
+    pub step_width: u32,
+
+    /// The volume that scrolling stops at, in percent
+    pub max_vol: u32,
+
+    pub show_volume_when_muted: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for PipewireConfig {
+    fn default() -> Self {
+        Self {
+            device_kind: DeviceKind::default(),
+            step_width: 5,
+            max_vol: 100,
+            show_volume_when_muted: false,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Pipewire {
+    type Config = PipewireConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let graph = Arc::new(Mutex::new(Graph::default()));
+        let monitor = monitor("pipewire", id, graph.clone(), tx_update_request)?;
+
+        Ok(Pipewire {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("volume_empty")?,
+            format: block_config.format.with_default("{volume}")?,
+            device_kind: block_config.device_kind,
+            step_width: block_config.step_width.min(50),
+            max_vol: block_config.max_vol,
+            show_volume_when_muted: block_config.show_volume_when_muted,
+            scrolling: shared_config.scrolling,
+            on_click: None,
+            graph,
+            _monitor: monitor,
+        })
+    }
+
+    fn override_on_click(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.on_click)
+    }
+}
+
+impl Pipewire {
+    fn wpctl(args: &[&str]) -> Result<()> {
+        // wpctl answers quickly, and the registry tells when it's done
+        spawn_child_async("wpctl", args).block_error("pipewire", "failed to run wpctl")
+    }
+
+    fn default_node(&self) -> Option<(u32, Node)> {
+        self.graph
+            .lock()
+            .unwrap()
+            .default_node(self.device_kind)
+            .map(|(id, node)| (id, node.clone()))
+    }
+
+    /// Makes the device after the current default one the default
+    fn cycle(&self) -> Result<()> {
+        let next = {
+            let graph = self.graph.lock().unwrap();
+            let nodes = graph.nodes(self.device_kind);
+            let current = graph
+                .default_node(self.device_kind)
+                .and_then(|(id, _)| nodes.iter().position(|(node, _)| *node == id));
+            let next = match current {
+                Some(current) => current + 1,
+                None => 0,
+            };
+            nodes.get(next % nodes.len().max(1)).map(|(id, _)| *id)
+        };
+        match next {
+            Some(id) => Pipewire::wpctl(&["set-default", &id.to_string()]),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Block for Pipewire {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if let Some(error) = &self.graph.lock().unwrap().error {
+            return Err(BlockError("pipewire".to_string(), error.clone()));
+        }
+        // Until pw-dump told about the devices, and while there are none
+        let node = match self.default_node() {
+            Some((_, node)) => node,
+            None => {
+                self.text.set_icon("volume_empty")?;
+                self.text.set_spacing(Spacing::Hidden);
+                self.text.set_text(String::new());
+                self.text.set_state(State::Idle);
+                return Ok(None);
+            }
+        };
+
+        let values = map!(
+            "volume" => Value::from_integer(node.volume as i64).percents(),
+            "output_name" => Value::from_string(node.name.clone()),
+            "output_description" => Value::from_string(node.description.clone()),
+        );
+        let prefix = match self.device_kind {
+            DeviceKind::Sink => "volume",
+            DeviceKind::Source => "microphone",
+        };
+        let suffix = match (node.muted, node.volume) {
+            (true, _) | (false, 0) => "muted",
+            (false, 1..=20) => "empty",
+            (false, 21..=70) => "half",
+            _ => "full",
+        };
+        self.text.set_icon(&format!("{}_{}", prefix, suffix))?;
+        if node.muted && !self.show_volume_when_muted {
+            self.text.set_text(String::new());
+        } else {
+            self.text.set_spacing(Spacing::Normal);
+            self.text.set_texts(self.format.render(&values)?);
+        }
+        self.text.set_state(if node.muted {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => {
+                if let Some(ref cmd) = self.on_click {
+                    spawn_child_async("sh", &["-c", cmd])
+                        .block_error("pipewire", "could not spawn child")?;
+                }
+            }
+            MouseButton::Middle => {
+                if let Some((id, _)) = self.default_node() {
+                    Pipewire::wpctl(&["set-mute", &id.to_string(), "toggle"])?;
+                }
+            }
+            MouseButton::Right => self.cycle()?,
+            _ => {
+                let step = match self.scrolling.to_logical_direction(event.button) {
+                    Some(LogicalDirection::Up) => self.step_width as i64,
+                    Some(LogicalDirection::Down) => -(self.step_width as i64),
+                    None => return Ok(()),
+                };
+                if let Some((id, node)) = self.default_node() {
+                    // Scrolling doesn't lower a volume that is above the maximum already
+                    let volume = (node.volume as i64 + step)
+                        .min((self.max_vol as i64).max(node.volume as i64))
+                        .max(0);
+                    Pipewire::wpctl(&[
+                        "set-volume",
+                        &id.to_string(),
+                        &format!("{:.2}", volume as f64 / 100.0),
+                    ])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply() {
+        let mut graph = Graph::default();
+        graph.apply(&json!([
+            {
+                "id": 40,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "props": {
+                        "media.class": "Audio/Sink",
+                        "node.name": "alsa_output.speakers",
+                        "node.description": "Speakers"
+                    },
+                    "params": {
+                        "Props": [{ "channelVolumes": [0.125, 0.125], "mute": false }]
+                    }
+                }
+            },
+            {
+                "id": 41,
+                "type": "PipeWire:Interface:Node",
+                "info": { "props": { "media.class": "Audio/Sink", "node.name": "bluez_output.headset" } }
+            },
+            {
+                "id": 30,
+                "type": "PipeWire:Interface:Metadata",
+                "props": { "metadata.name": "default" },
+                "metadata": [
+                    { "subject": 0, "key": "default.audio.sink", "value": { "name": "alsa_output.speakers" } }
+                ]
+            }
+        ]));
+        let (id, node) = graph.default_node(DeviceKind::Sink).unwrap();
+        assert_eq!((id, node.volume, node.muted), (40, 50, false));
+
+        graph.apply(&json!([
+            { "id": 40, "type": "PipeWire:Interface:Node", "info": { "props": { "media.class": "Audio/Sink" }, "params": { "Props": [{ "mute": true }] } } },
+            { "id": 30, "type": "PipeWire:Interface:Metadata", "props": { "metadata.name": "default" }, "metadata": [
+                { "subject": 0, "key": "default.configured.audio.sink", "value": { "name": "bluez_output.headset" } }
+            ] }
+        ]));
+        assert!(graph.nodes[&40].muted);
+        assert_eq!(graph.nodes[&40].volume, 50);
+        assert_eq!(graph.default_node(DeviceKind::Sink).unwrap().0, 41);
+
+        graph.apply(&json!([{ "id": 41, "info": null }]));
+        assert_eq!(graph.default_node(DeviceKind::Sink).unwrap().0, 40);
+        assert_eq!(graph.nodes(DeviceKind::Source), vec![]);
+    }
+}
@@ -0,0 +1,265 @@
+//! Counts the new episodes of podcast feeds (RSS or Atom) that were not played yet. Episodes that
+//! are already in a feed when it is first seen count as played, so only new releases show up.
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::feed;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Episode {
+    /// URL of the feed
+    feed: String,
+    podcast: String,
+    title: String,
+    guid: String,
+    link: String,
+    published: Option<DateTime<FixedOffset>>,
+}
+
+/// What has been played, which survives restarts
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Played {
+    /// Feeds whose episodes were seen before
+    feeds: BTreeSet<String>,
+    /// GUIDs of played episodes
+    episodes: BTreeSet<String>,
+}
+
+pub struct Podcasts {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    feeds: Vec<String>,
+    interval: Duration,
+    hide_if_none: bool,
+    open_command: String,
+    played: Played,
+    state_file: PathBuf,
+    /// Unplayed episodes, newest first
+    unplayed: Vec<Episode>,
+    /// Whether a feed couldn't be fetched at the last update
+    failed: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PodcastsConfig {
+    /// URLs of the RSS or Atom feeds of the podcasts
+    pub feeds: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Hide the block while there is no new episode
+    pub hide_if_none: bool,
+
+    /// Command that opens the link of an episode, which is appended
+    pub open_command: String,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for PodcastsConfig {
+    fn default() -> Self {
+        Self {
+            feeds: Vec::new(),
+            interval: Duration::from_secs(1800),
+            hide_if_none: false,
+            open_command: "xdg-open".to_string(),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Podcasts {
+    type Config = PodcastsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.feeds.is_empty() {
+            return Err(ConfigurationError(
+                "podcasts".to_string(),
+                "feeds must be set".to_string(),
+            ));
+        }
+        let state_file = xdg_state_home().join("i3status-rust/podcasts.toml");
+        let played = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(Podcasts {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{count}")?,
+            feeds: block_config.feeds,
+            interval: block_config.interval,
+            hide_if_none: block_config.hide_if_none,
+            open_command: block_config.open_command,
+            played,
+            state_file,
+            unplayed: Vec::new(),
+            failed: false,
+        })
+    }
+}
+
+impl Podcasts {
+    fn fetch(&self, url: &str) -> Result<Vec<Episode>> {
+        let response = http::http_get(url, Some(Duration::from_secs(10)), vec![])?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "podcasts".to_string(),
+                format!("could not get {} (HTTP {})", url, response.code),
+            ));
+        }
+        Ok(feed::parse(&String::from_utf8_lossy(&response.content))
+            .into_iter()
+            .map(|item| Episode {
+                feed: url.to_string(),
+                podcast: item.feed,
+                title: item.title,
+                guid: item.guid,
+                link: item.enclosure.unwrap_or(item.link),
+                published: item.published,
+            })
+            .collect())
+    }
+
+    fn save(&self) {
+        // Failing to save only means that episodes show up as new again after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&self.played) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let newest = self.unplayed.first();
+        let values = map!(
+            "count" => Value::from_integer(self.unplayed.len() as i64),
+            "podcast" => Value::from_string(newest.map(|e| e.podcast.clone()).unwrap_or_default()),
+            "title" => Value::from_string(newest.map(|e| e.title.clone()).unwrap_or_default()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match newest {
+            _ if self.failed => State::Warning,
+            Some(_) => State::Info,
+            None => State::Idle,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Podcasts {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut unplayed = Vec::new();
+        let mut current = BTreeSet::new();
+        let mut changed = false;
+        self.failed = false;
+        for url in &self.feeds {
+            let episodes = match self.fetch(url) {
+                Ok(episodes) => episodes,
+                Err(error) => {
+                    // The feed keeps its unplayed episodes until it can be fetched again
+                    log::warn!("failed to fetch {}: {}", url, error);
+                    self.failed = true;
+                    unplayed.extend(self.unplayed.iter().filter(|e| &e.feed == url).cloned());
+                    continue;
+                }
+            };
+            current.extend(episodes.iter().map(|e| e.guid.clone()));
+            if self.played.feeds.insert(url.clone()) {
+                self.played
+                    .episodes
+                    .extend(episodes.iter().map(|e| e.guid.clone()));
+                changed = true;
+            } else {
+                unplayed.extend(
+                    episodes
+                        .into_iter()
+                        .filter(|e| !self.played.episodes.contains(&e.guid)),
+                );
+            }
+        }
+        // Forget episodes that left their feeds, so that the state doesn't grow forever. Which
+        // episodes a feed that couldn't be fetched has is unknown.
+        let before = self.played.episodes.len();
+        if !self.failed {
+            self.played.episodes.retain(|guid| current.contains(guid));
+        }
+        let feeds = &self.feeds;
+        self.played.feeds.retain(|feed| feeds.contains(feed));
+        if changed || self.played.episodes.len() != before {
+            self.save();
+        }
+
+        unplayed.sort_by_key(|e| Reverse(e.published));
+        self.unplayed = unplayed;
+        self.set_text()?;
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left if !self.unplayed.is_empty() => {
+                let episode = self.unplayed.remove(0);
+                self.played.episodes.insert(episode.guid);
+                if !episode.link.is_empty() {
+                    let command = format!("{} '{}'", self.open_command, episode.link);
+                    spawn_child_async("sh", &["-c", &command])
+                        .block_error("podcasts", &format!("failed to run {}", command))?;
+                }
+            }
+            MouseButton::Right => {
+                self.played
+                    .episodes
+                    .extend(self.unplayed.drain(..).map(|e| e.guid));
+            }
+            _ => return Ok(()),
+        }
+        self.save();
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_none && self.unplayed.is_empty() && !self.failed {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
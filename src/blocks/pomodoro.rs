@@ -1,15 +1,19 @@
 use std::fmt;
-use std::time::{Duration, Instant};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
 
 use crossbeam_channel::Sender;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::state_store::{self, StateStore};
 use crate::subprocess::spawn_child_async;
+use crate::tracking::{Tracker, TrackingConfig};
+use crate::widgets::progress::{ProgressConfig, ProgressWidget};
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -21,6 +25,10 @@ enum State {
 }
 
 impl State {
+    fn working(&self) -> bool {
+        matches!(self, State::Started(_))
+    }
+
     fn elapsed(&self) -> Duration {
         match self {
             State::Started(start) => Instant::now().duration_since(start.to_owned()),
@@ -31,6 +39,45 @@ impl State {
     }
 }
 
+/// The state as saved for the next start, with the starts on the wall clock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SavedState {
+    Started(SystemTime),
+    Stopped,
+    Paused(Duration),
+    OnBreak(SystemTime),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Saved {
+    state: SavedState,
+    count: usize,
+}
+
+impl From<&State> for SavedState {
+    fn from(state: &State) -> Self {
+        match state {
+            State::Started(start) => SavedState::Started(state_store::system_time(*start)),
+            State::Stopped => SavedState::Stopped,
+            State::Paused(elapsed) => SavedState::Paused(*elapsed),
+            State::OnBreak(start) => SavedState::OnBreak(state_store::system_time(*start)),
+        }
+    }
+}
+
+impl From<SavedState> for State {
+    fn from(state: SavedState) -> Self {
+        match state {
+            // A pomodoro or break that ended while the bar was stopped ends with the next update
+            SavedState::Started(start) => State::Started(state_store::instant(start)),
+            SavedState::Stopped => State::Stopped,
+            SavedState::Paused(elapsed) => State::Paused(elapsed),
+            SavedState::OnBreak(start) => State::OnBreak(state_store::instant(start)),
+        }
+    }
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -63,6 +110,7 @@ pub enum Notifier {
 pub struct Pomodoro {
     id: usize,
     time: TextWidget,
+    progress: Option<ProgressWidget>,
     state: State,
     length: Duration,
     break_length: Duration,
@@ -73,6 +121,8 @@ pub struct Pomodoro {
     notifier: Notifier,
     notifier_path: std::path::PathBuf,
     shared_config: SharedConfig,
+    tracker: Option<Tracker>,
+    state_store: Rc<StateStore>,
     // Following two are deprecated - remove in a later release
     use_nag: bool,
     nag_path: std::path::PathBuf,
@@ -87,12 +137,45 @@ impl Pomodoro {
             State::Paused(_) => "pomodoro_paused".to_string(),
         };
 
+        self.time.set_state(match &self.state {
+            State::Stopped => crate::widgets::State::Idle,
+            State::Started(_) => crate::widgets::State::Info,
+            State::Paused(_) => crate::widgets::State::Warning,
+            State::OnBreak(_) => crate::widgets::State::Good,
+        });
         self.time.set_text(format!(
             "{} | {} {}",
             self.count,
             self.shared_config.get_icon(&state_icon).unwrap(),
             self.state
         ));
+
+        if let Some(progress) = &mut self.progress {
+            let (elapsed, length, state) = match &self.state {
+                State::Stopped => (0., 1., crate::widgets::State::Idle),
+                State::Started(_) | State::Paused(_) => (
+                    self.state.elapsed().as_secs_f64(),
+                    self.length.as_secs_f64(),
+                    crate::widgets::State::Idle,
+                ),
+                State::OnBreak(_) => (
+                    self.state.elapsed().as_secs_f64(),
+                    self.break_length.as_secs_f64(),
+                    crate::widgets::State::Good,
+                ),
+            };
+            progress.set_value(elapsed / length * 100., state);
+        }
+    }
+
+    /// Saves the state and count, so that the pomodoro goes on after a restart
+    fn save(&self) {
+        let saved = Saved {
+            state: SavedState::from(&self.state),
+            count: self.count,
+        };
+        self.state_store
+            .set(&format!("pomodoro/{}", self.id), &saved);
     }
 
     fn notify(&self, message: &str, level: String) {
@@ -115,6 +198,20 @@ impl Pomodoro {
 
         spawn_child_async(binary, &args).expect("Failed to start notifier");
     }
+
+    /// Moves on to `state`, starting or stopping the time tracking if work starts or stops
+    fn set_state(&mut self, state: State) {
+        let was_working = self.state.working();
+        self.state = state;
+        if let Some(tracker) = &self.tracker {
+            if !was_working && self.state.working() {
+                // The pomodoro that is being worked on, counting from 1
+                tracker.start(&[("count", (self.count + 1).to_string())]);
+            } else if was_working && !self.state.working() {
+                tracker.stop();
+            }
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -126,6 +223,13 @@ pub struct PomodoroConfig {
     pub break_message: String,
     pub notifier: Notifier,
     pub notifier_path: Option<std::path::PathBuf>,
+    /// Show the progress of the current pomodoro or break as a bar
+    pub progress: Option<ProgressConfig>,
+    /// Track the time worked with Timewarrior or Toggl
+    pub tracking: Option<TrackingConfig>,
+    /// Track the pomodoros with Timewarrior under these tags, like `tracking` with
+    /// `name = "timewarrior"`
+    pub timewarrior_tags: Option<Vec<String>>,
     // Following two are deprecated - remove in a later release
     pub use_nag: bool,
     pub nag_path: std::path::PathBuf,
@@ -140,6 +244,9 @@ impl Default for PomodoroConfig {
             break_message: "Break over! Time to work!".to_string(),
             notifier: Notifier::None,
             notifier_path: None,
+            progress: None,
+            tracking: None,
+            timewarrior_tags: None,
             // Following two are deprecated - remove in a later release
             use_nag: false,
             nag_path: std::path::PathBuf::from("i3-nagbar"),
@@ -156,9 +263,18 @@ impl ConfigBlock for Pomodoro {
         shared_config: SharedConfig,
         _send: Sender<Task>,
     ) -> Result<Self> {
-        Ok(Pomodoro {
+        let timewarrior_tags = block_config.timewarrior_tags;
+        let tracking = block_config
+            .tracking
+            .or_else(|| timewarrior_tags.map(|tags| TrackingConfig::Timewarrior { tags }));
+        let state_store = shared_config.state_store();
+        let saved = state_store.get::<Saved>(&format!("pomodoro/{}", id));
+        let mut pomodoro = Pomodoro {
             id,
             time: TextWidget::new(id, 0, shared_config.clone()).with_icon("pomodoro")?,
+            progress: block_config
+                .progress
+                .map(|progress| ProgressWidget::new(id, 1, shared_config.clone(), progress)),
             state: State::Stopped,
             length: Duration::from_secs(block_config.length * 60), // convert to minutes
             break_length: Duration::from_secs(block_config.break_length * 60), // convert to minutes
@@ -178,10 +294,18 @@ impl ConfigBlock for Pomodoro {
                 }
             },
             shared_config,
+            tracker: tracking.map(Tracker::new).transpose()?,
+            state_store,
             // Following two are deprecated - remove in a later release
             use_nag: block_config.use_nag,
             nag_path: block_config.nag_path,
-        })
+        };
+        if let Some(saved) = saved {
+            // Tracks the time again that `stop` stopped tracking
+            pomodoro.count = saved.count;
+            pomodoro.set_state(saved.state.into());
+        }
+        Ok(pomodoro)
     }
 }
 
@@ -199,7 +323,8 @@ impl Block for Pomodoro {
                         self.notify(&self.message, "error".to_string());
                     }
 
-                    self.state = State::OnBreak(Instant::now());
+                    self.set_state(State::OnBreak(Instant::now()));
+                    self.save();
                 }
             }
             State::OnBreak(_) => {
@@ -207,8 +332,9 @@ impl Block for Pomodoro {
                     if self.use_nag || self.notifier != Notifier::None {
                         self.notify(&self.break_message, "warning".to_string());
                     }
-                    self.state = State::Stopped;
+                    self.set_state(State::Stopped);
                     self.count += 1;
+                    self.save();
                 }
             }
             _ => {}
@@ -220,31 +346,56 @@ impl Block for Pomodoro {
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         match event.button {
             MouseButton::Right => {
-                self.state = State::Stopped;
+                self.set_state(State::Stopped);
                 self.count = 0;
             }
+            // Skip to the end of the pomodoro or break
+            MouseButton::Middle => match &self.state {
+                State::Stopped => {}
+                State::Started(_) | State::Paused(_) => {
+                    self.set_state(State::OnBreak(Instant::now()));
+                }
+                State::OnBreak(_) => {
+                    self.set_state(State::Stopped);
+                    self.count += 1;
+                }
+            },
             _ => match &self.state {
                 State::Stopped => {
-                    self.state = State::Started(Instant::now());
+                    self.set_state(State::Started(Instant::now()));
                 }
                 State::Started(_) => {
-                    self.state = State::Paused(self.state.elapsed());
+                    self.set_state(State::Paused(self.state.elapsed()));
                 }
                 State::Paused(duration) => {
-                    self.state =
-                        State::Started(Instant::now().checked_sub(duration.to_owned()).unwrap());
+                    let started = Instant::now().checked_sub(duration.to_owned()).unwrap();
+                    self.set_state(State::Started(started));
                 }
                 State::OnBreak(_) => {
-                    self.state = State::Started(Instant::now());
+                    self.set_state(State::Started(Instant::now()));
                 }
             },
         }
+        self.save();
         self.set_text();
 
         Ok(())
     }
 
+    fn stop(&mut self) -> Result<()> {
+        // The interval would otherwise be tracked until the bar is started again. The saved state
+        // isn't changed, so the pomodoro goes on after a restart.
+        self.set_state(State::Stopped);
+        if let Some(tracker) = &self.tracker {
+            tracker.wait(Duration::from_secs(5));
+        }
+        Ok(())
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.time]
+        match &self.progress {
+            Some(progress) => vec![&self.time, progress],
+            None => vec![&self.time],
+        }
     }
 }
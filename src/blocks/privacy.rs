@@ -0,0 +1,344 @@
+//! A software privacy LED: shows while the microphone, a camera or the screen is being captured.
+//! Microphone and screen capture are streams in PipeWire, which the block follows like the
+//! `pipewire` block does. Cameras are mostly opened directly, so `/proc` is also searched for
+//! processes that hold `/dev/video*` open.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{read_dir, read_link, read_to_string};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::pipewire::{monitor, Monitor, Registry};
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Stream {
+    media_class: String,
+    application: String,
+    /// Cameras have a device API like "v4l2", shared screens have none
+    device: bool,
+    /// Monitors of the level, like the meters of pavucontrol, don't record anything
+    monitor: bool,
+    running: bool,
+}
+
+/// The capturing nodes of the PipeWire registry
+#[derive(Debug, Default)]
+struct Streams {
+    nodes: HashMap<u32, Stream>,
+    error: Option<String>,
+}
+
+impl Registry for Streams {
+    fn apply(&mut self, objects: &JsonValue) {
+        for object in objects.as_array().into_iter().flatten() {
+            let id = match object["id"].as_u64() {
+                Some(id) => id as u32,
+                None => continue,
+            };
+            match object["type"].as_str() {
+                None => {
+                    self.nodes.remove(&id);
+                }
+                Some("PipeWire:Interface:Node") => {
+                    let info = &object["info"];
+                    let props = &info["props"];
+                    let previous = self.nodes.get(&id);
+                    let media_class = match props["media.class"].as_str() {
+                        Some(
+                            class @ ("Stream/Input/Audio" | "Stream/Input/Video" | "Video/Source"),
+                        ) => class.to_string(),
+                        Some(_) => continue,
+                        // Changes may leave out the properties
+                        None => match previous {
+                            Some(node) => node.media_class.clone(),
+                            None => continue,
+                        },
+                    };
+                    let application = ["application.name", "node.description", "node.name"]
+                        .iter()
+                        .find_map(|key| props[*key].as_str())
+                        .map(str::to_string)
+                        .or_else(|| Some(previous?.application.clone()))
+                        .unwrap_or_default();
+                    let running = match info["state"].as_str() {
+                        Some(state) => state == "running",
+                        None => previous.map(|node| node.running).unwrap_or(false),
+                    };
+                    let device = props["device.api"].is_string()
+                        || previous.map(|node| node.device).unwrap_or(false);
+                    let monitor = props["stream.monitor"].as_bool().unwrap_or(false)
+                        || previous.map(|node| node.monitor).unwrap_or(false);
+                    self.nodes.insert(
+                        id,
+                        Stream {
+                            media_class,
+                            application,
+                            device,
+                            monitor,
+                            running,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+}
+
+/// The applications that capture each kind of device
+#[derive(Debug, Default, PartialEq)]
+struct Usage {
+    microphone: BTreeSet<String>,
+    camera: BTreeSet<String>,
+    screen: BTreeSet<String>,
+}
+
+impl Streams {
+    fn usage(&self, ignore: &[String]) -> Usage {
+        let mut usage = Usage::default();
+        let mut video_clients = BTreeSet::new();
+        let mut cameras = false;
+        for node in self.nodes.values() {
+            if !node.running || node.monitor || ignore.contains(&node.application) {
+                continue;
+            }
+            match node.media_class.as_str() {
+                "Stream/Input/Audio" => {
+                    usage.microphone.insert(node.application.clone());
+                }
+                "Stream/Input/Video" => {
+                    video_clients.insert(node.application.clone());
+                }
+                _ if node.device => cameras = true,
+                // Screen casts are sources of their own, made by the desktop portal
+                _ => {
+                    usage.screen.insert(node.application.clone());
+                }
+            }
+        }
+        // Which clients watch a camera and which a screen isn't told apart
+        if cameras {
+            usage.camera.extend(video_clients);
+        } else if !usage.screen.is_empty() && !video_clients.is_empty() {
+            usage.screen = video_clients;
+        }
+        usage
+    }
+}
+
+/// The names of the processes that have a video device open, as far as they can be seen
+fn video_device_users() -> BTreeSet<String> {
+    let mut users = BTreeSet::new();
+    for process in read_dir("/proc").into_iter().flatten().flatten() {
+        let fds = match read_dir(process.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let uses_camera = fds.flatten().any(|fd| {
+            read_link(fd.path())
+                .map(|target| target.starts_with("/dev/video"))
+                .unwrap_or(false)
+        });
+        if uses_camera {
+            if let Ok(name) = read_to_string(process.path().join("comm")) {
+                users.insert(name.trim().to_string());
+            }
+        }
+    }
+    users
+}
+
+pub struct Privacy {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    streams: Arc<Mutex<Streams>>,
+    _monitor: Monitor,
+    ignore: Vec<String>,
+    interval: Duration,
+    icons: (String, String, String),
+    /// Whether anything is being captured
+    active: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PrivacyConfig {
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Applications to leave out, like "pavucontrol"
+    pub ignore: Vec<String>,
+
+    /// How often to look for processes that use a camera
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            format: FormatTemplate::default(),
+            ignore: Vec::new(),
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ConfigBlock for Privacy {
+    type Config = PrivacyConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let streams = Arc::new(Mutex::new(Streams::default()));
+        let monitor = monitor("privacy", id, streams.clone(), tx_update_request)?;
+
+        let icon =
+            |name| -> Result<String> { Ok(shared_config.get_icon(name)?.trim().to_string()) };
+        Ok(Privacy {
+            id,
+            icons: (
+                icon("privacy_microphone")?,
+                icon("privacy_camera")?,
+                icon("privacy_screen")?,
+            ),
+            text: TextWidget::new(id, 0, shared_config.clone()),
+            format: block_config
+                .format
+                .with_default("{microphone}{camera}{screen}")?,
+            streams,
+            _monitor: monitor,
+            ignore: block_config.ignore,
+            interval: block_config.interval,
+            active: false,
+        })
+    }
+}
+
+impl Block for Privacy {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut usage = {
+            let streams = self
+                .streams
+                .lock()
+                .block_error("privacy", "failed to acquire lock")?;
+            if let Some(error) = &streams.error {
+                return Err(BlockError("privacy".to_string(), error.clone()));
+            }
+            streams.usage(&self.ignore)
+        };
+        usage.camera.extend(
+            video_device_users()
+                .into_iter()
+                .filter(|name| !self.ignore.contains(name)),
+        );
+
+        let icon = |apps: &BTreeSet<String>, icon: &String| {
+            Value::from_string(if apps.is_empty() {
+                String::new()
+            } else {
+                icon.clone()
+            })
+        };
+        let apps: BTreeSet<&String> = usage
+            .microphone
+            .iter()
+            .chain(&usage.camera)
+            .chain(&usage.screen)
+            .collect();
+        let values = map!(
+            "microphone" => icon(&usage.microphone, &self.icons.0),
+            "camera" => icon(&usage.camera, &self.icons.1),
+            "screen" => icon(&usage.screen, &self.icons.2),
+            "apps" => Value::from_string(
+                apps.into_iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if !usage.camera.is_empty() {
+            State::Critical
+        } else {
+            State::Warning
+        });
+        self.active = usage != Usage::default();
+
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.active {
+            vec![&self.text]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_usage() {
+        let mut streams = Streams::default();
+        let node = |id: u32, class: &str, app: &str, state: &str| {
+            json!({
+                "id": id,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "state": state,
+                    "props": { "media.class": class, "application.name": app }
+                }
+            })
+        };
+        streams.apply(&json!([
+            node(50, "Stream/Input/Audio", "Firefox", "running"),
+            node(51, "Stream/Input/Audio", "Recorder", "idle"),
+            node(52, "Video/Source", "xdg-desktop-portal-wlr", "running"),
+            node(53, "Stream/Input/Video", "OBS", "running"),
+            node(54, "Audio/Sink", "Speakers", "running"),
+        ]));
+        let set = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        assert_eq!(
+            streams.usage(&[]),
+            Usage {
+                microphone: set(&["Firefox"]),
+                camera: set(&[]),
+                screen: set(&["OBS"]),
+            }
+        );
+        assert_eq!(streams.usage(&["Firefox".to_string()]).microphone, set(&[]));
+
+        // The stream stops
+        streams.apply(&json!([
+            { "id": 50, "type": "PipeWire:Interface:Node", "info": { "state": "idle" } }
+        ]));
+        assert_eq!(streams.usage(&[]).microphone, set(&[]));
+    }
+}
@@ -0,0 +1,298 @@
+//! Shows whether a process like syncthing, mpd or a compositor helper is running, with its CPU
+//! and memory use, and turns to warning while it isn't. A click starts or kills it.
+//!
+//! Processes are found in `/proc`, by name or through the pid file that they write.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{sysconf, Pid, SysconfVar};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long after a click the block looks again, to give the process time to start or exit
+const CLICK_DELAY: Duration = Duration::from_secs(1);
+
+pub struct Process {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_stopped: FormatTemplate,
+    name: String,
+    pidfile: Option<PathBuf>,
+    start_command: Option<String>,
+    interval: Duration,
+    /// CPU time of the processes at the last update, in clock ticks
+    ticks: HashMap<i32, u64>,
+    last_update: Instant,
+    pids: Vec<i32>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ProcessConfig {
+    /// Name of the process, as in `ps` or `pgrep`. The block's `name` is taken by includes.
+    pub process: Option<String>,
+
+    /// File that the process writes its pid to
+    pub pidfile: Option<String>,
+
+    /// Shell command that a left click runs while the process isn't running
+    pub start_command: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format while the process is running
+    pub format: FormatTemplate,
+
+    /// Format while it isn't
+    pub format_stopped: FormatTemplate,
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        Self {
+            process: None,
+            pidfile: None,
+            start_command: None,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+            format_stopped: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Process {
+    type Config = ProcessConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let pidfile = block_config
+            .pidfile
+            .map(|path| PathBuf::from(shellexpand::tilde(&path).to_string()));
+        let name = match (block_config.process, &pidfile) {
+            (Some(name), _) => name,
+            (None, Some(pidfile)) => pidfile
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            (None, None) => {
+                return Err(ConfigurationError(
+                    "process".to_string(),
+                    "process or pidfile must be set".to_string(),
+                ))
+            }
+        };
+
+        Ok(Process {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{name} {cpu} {memory}")?,
+            format_stopped: block_config.format_stopped.with_default("{name}")?,
+            name,
+            pidfile,
+            start_command: block_config.start_command,
+            interval: block_config.interval,
+            ticks: HashMap::new(),
+            last_update: Instant::now(),
+            pids: Vec::new(),
+            tx_update_request,
+        })
+    }
+}
+
+/// Whether the process with the `comm` and `cmdline` of /proc is called `name`. The kernel cuts
+/// `comm` to 15 characters, so the program in the command line is checked as well.
+fn is_named(name: &str, comm: &str, cmdline: &[u8]) -> bool {
+    if comm.trim_end() == name {
+        return true;
+    }
+    let program = cmdline.split(|&byte| byte == 0).next().unwrap_or_default();
+    let program = String::from_utf8_lossy(program);
+    program.rsplit('/').next() == Some(name)
+}
+
+/// The CPU time from the `stat` of a process, in clock ticks
+fn parse_ticks(stat: &str) -> Option<u64> {
+    // The name in parentheses may contain spaces and parentheses itself
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace();
+    // The user and system time are the 14th and 15th fields, counting the pid and name
+    let user: u64 = fields.nth(11)?.parse().ok()?;
+    let system: u64 = fields.next()?.parse().ok()?;
+    Some(user + system)
+}
+
+/// The resident memory from the `status` of a process, in bytes
+fn parse_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn read_proc(pid: i32, file: &str) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/{}", pid, file)).ok()
+}
+
+impl Process {
+    /// The processes that are watched and running
+    fn find(&self) -> Result<Vec<i32>> {
+        if let Some(pidfile) = &self.pidfile {
+            let pid = fs::read_to_string(pidfile)
+                .ok()
+                .and_then(|pid| pid.trim().parse::<i32>().ok());
+            // A pid file may be left behind by a process that crashed
+            return Ok(pid
+                .filter(|pid| kill(Pid::from_raw(*pid), None).is_ok())
+                .into_iter()
+                .collect());
+        }
+
+        let own = std::process::id() as i32;
+        let entries = fs::read_dir("/proc").block_error("process", "failed to read /proc")?;
+        let mut pids: Vec<i32> = entries
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+            .filter(|pid| *pid != own)
+            .filter(|pid| {
+                let comm = read_proc(*pid, "comm").unwrap_or_default();
+                let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+                is_named(&self.name, &comm, &cmdline)
+            })
+            .collect();
+        pids.sort_unstable();
+        Ok(pids)
+    }
+
+    fn schedule_update(&self) {
+        let _ = self.tx_update_request.send(Task {
+            id: self.id,
+            update_time: Instant::now() + CLICK_DELAY,
+        });
+    }
+}
+
+impl Block for Process {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.pids = self.find()?;
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+
+        if self.pids.is_empty() {
+            self.ticks.clear();
+            let values = map!("name" => Value::from_string(self.name.clone()));
+            self.text.set_texts(self.format_stopped.render(&values)?);
+            self.text.set_state(State::Warning);
+            return Ok(Some(self.interval.into()));
+        }
+
+        let ticks: HashMap<i32, u64> = self
+            .pids
+            .iter()
+            .filter_map(|pid| Some((*pid, parse_ticks(&read_proc(*pid, "stat")?)?)))
+            .collect();
+        // Processes that just started are counted from the next update on
+        let used: u64 = ticks
+            .iter()
+            .filter_map(|(pid, ticks)| Some(ticks.saturating_sub(*self.ticks.get(pid)?)))
+            .sum();
+        let ticks_per_second = sysconf(SysconfVar::CLK_TCK).ok().flatten().unwrap_or(100) as f64;
+        let cpu = if elapsed > 0. {
+            used as f64 / ticks_per_second / elapsed * 100.
+        } else {
+            0.
+        };
+        self.ticks = ticks;
+        let memory: u64 = self
+            .pids
+            .iter()
+            .filter_map(|pid| parse_rss(&read_proc(*pid, "status")?))
+            .sum();
+
+        let values = map!(
+            "name" => Value::from_string(self.name.clone()),
+            "pid" => Value::from_integer(self.pids[0] as i64),
+            "count" => Value::from_integer(self.pids.len() as i64),
+            "cpu" => Value::from_float(cpu).percents(),
+            "memory" => Value::from_float(memory as f64).bytes(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(State::Idle);
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left if self.pids.is_empty() => {
+                if let Some(command) = &self.start_command {
+                    spawn_child_async("sh", &["-c", command])
+                        .block_error("process", "failed to run start_command")?;
+                    self.schedule_update();
+                }
+            }
+            MouseButton::Right => {
+                for pid in &self.pids {
+                    if let Err(error) = kill(Pid::from_raw(*pid), Signal::SIGTERM) {
+                        log::warn!("failed to kill {}: {}", pid, error);
+                    }
+                }
+                self.schedule_update();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "4242 (tmux: server) S 1 4242 4242 0 -1 4194560 1693 0 0 0 150 42 0 0 20 0 1 0 \
+                    8391 12169216 1058 18446744073709551615";
+        assert_eq!(parse_ticks(stat), Some(192));
+        let status = "Name:\tsyncthing\nVmPeak:\t  900000 kB\nVmRSS:\t   51200 kB\nThreads:\t14\n";
+        assert_eq!(parse_rss(status), Some(51200 * 1024));
+
+        assert!(is_named("mpd", "mpd\n", b"/usr/bin/mpd\0--no-daemon\0"));
+        // Longer names are cut in `comm`
+        assert!(is_named(
+            "xdg-desktop-portal-wlr",
+            "xdg-desktop-por\n",
+            b"/usr/libexec/xdg-desktop-portal-wlr\0"
+        ));
+        assert!(!is_named("mpd", "mpdscribble\n", b"mpdscribble\0"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Shows the result of a PromQL query, which makes anything that a [Prometheus](https://prometheus.io/)
+//! server collects available in the bar.
+
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The unit of the result, so that it is formatted with SI prefixes and a symbol
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrometheusUnit {
+    None,
+    Bytes,
+    Bits,
+    Percents,
+    Degrees,
+    Seconds,
+    Watts,
+    Hertz,
+}
+
+impl PrometheusUnit {
+    fn apply(self, value: Value) -> Value {
+        match self {
+            PrometheusUnit::None => value,
+            PrometheusUnit::Bytes => value.bytes(),
+            PrometheusUnit::Bits => value.bits(),
+            PrometheusUnit::Percents => value.percents(),
+            PrometheusUnit::Degrees => value.degrees(),
+            PrometheusUnit::Seconds => value.seconds(),
+            PrometheusUnit::Watts => value.watts(),
+            PrometheusUnit::Hertz => value.hertz(),
+        }
+    }
+}
+
+/// The value of a scalar result or of the first series of a vector result, `None` if the vector is
+/// empty
+fn parse_result(data: &JsonValue) -> Result<Option<f64>> {
+    let sample = match data["resultType"].as_str() {
+        Some("scalar") => &data["result"],
+        Some("vector") => match data["result"].get(0) {
+            Some(series) => &series["value"],
+            None => return Ok(None),
+        },
+        _ => {
+            return Err(BlockError(
+                "prometheus".to_string(),
+                "the query must return a scalar or an instant vector".to_string(),
+            ))
+        }
+    };
+    // Samples are pairs of a timestamp and the value as a string, which may be "NaN" or "+Inf"
+    sample[1]
+        .as_str()
+        .and_then(|value| value.parse().ok())
+        .map(Some)
+        .block_error("prometheus", "unexpected value in the result")
+}
+
+pub struct Prometheus {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    url: String,
+    bearer_token: Option<String>,
+    unit: PrometheusUnit,
+    scale: f64,
+    warning: Option<f64>,
+    critical: Option<f64>,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct PrometheusConfig {
+    /// Base URL of the server, like "http://prometheus:9090"
+    pub url: String,
+
+    /// PromQL expression that is evaluated at the time of each update
+    pub query: String,
+
+    /// Token to send in the `Authorization` header
+    pub bearer_token: Option<String>,
+
+    pub unit: PrometheusUnit,
+
+    /// Factor that the value is multiplied with, e.g. 100 for ratios shown as percents
+    pub scale: f64,
+
+    /// Scaled value from which on the block is shown as warning
+    pub warning: Option<f64>,
+
+    /// Scaled value from which on the block is shown as critical
+    pub critical: Option<f64>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for PrometheusConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:9090".to_string(),
+            query: String::new(),
+            bearer_token: None,
+            unit: PrometheusUnit::None,
+            scale: 1.,
+            warning: None,
+            critical: None,
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Prometheus {
+    type Config = PrometheusConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.query.is_empty() {
+            return Err(ConfigurationError(
+                "prometheus".to_string(),
+                "query must be set".to_string(),
+            ));
+        }
+
+        Ok(Prometheus {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("prometheus")?,
+            format: block_config.format.with_default("{value}")?,
+            url: format!(
+                "{}/api/v1/query?query={}",
+                block_config.url.trim_end_matches('/'),
+                http::url_encode(&block_config.query)
+            ),
+            bearer_token: block_config.bearer_token,
+            unit: block_config.unit,
+            scale: block_config.scale,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Prometheus {
+    /// The value of the query, `None` if it has none
+    fn query(&self) -> Result<Option<f64>> {
+        let authorization = self
+            .bearer_token
+            .as_ref()
+            .map(|token| format!("Bearer {}", token));
+        let headers = match &authorization {
+            Some(authorization) => vec![("Authorization", authorization.as_str())],
+            None => vec![],
+        };
+        let response = http::http_get_json(&self.url, Some(TIMEOUT), headers)?;
+        if response.content["status"] != "success" {
+            return Err(BlockError(
+                "prometheus".to_string(),
+                format!(
+                    "query failed: {}",
+                    response.content["error"]
+                        .as_str()
+                        .unwrap_or("unexpected response")
+                ),
+            ));
+        }
+        parse_result(&response.content["data"])
+    }
+
+    /// Thresholds below each other mean that lower values are worse
+    fn state(&self, value: f64) -> State {
+        let lower_is_worse = match (self.warning, self.critical) {
+            (Some(warning), Some(critical)) => warning > critical,
+            _ => false,
+        };
+        let reached = |threshold: Option<f64>| match threshold {
+            Some(threshold) if lower_is_worse => value <= threshold,
+            Some(threshold) => value >= threshold,
+            None => false,
+        };
+        if reached(self.critical) {
+            State::Critical
+        } else if reached(self.warning) {
+            State::Warning
+        } else {
+            State::Idle
+        }
+    }
+}
+
+impl Block for Prometheus {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.query() {
+            Ok(Some(value)) => {
+                let value = value * self.scale;
+                let values = map!("value" => self.unit.apply(Value::from_float(value)));
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(self.state(value));
+            }
+            // An empty vector, e.g. because a filter matched no series
+            Ok(None) => {
+                self.text.set_text("-".to_string());
+                self.text.set_state(State::Idle);
+            }
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // The server can't be reached, e.g. away from home
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+            }
+            Err(error) => {
+                self.text.set_text(format!("prometheus error {}", error));
+                self.text.set_state(State::Critical);
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_result() {
+        let vector = json!({
+            "resultType": "vector",
+            "result": [{ "metric": { "instance": "nas" }, "value": [1700000000.5, "0.875"] }],
+        });
+        assert_eq!(parse_result(&vector).unwrap(), Some(0.875));
+        let scalar = json!({ "resultType": "scalar", "result": [1700000000.5, "42"] });
+        assert_eq!(parse_result(&scalar).unwrap(), Some(42.));
+        let empty = json!({ "resultType": "vector", "result": [] });
+        assert_eq!(parse_result(&empty).unwrap(), None);
+        assert!(parse_result(&json!({ "resultType": "matrix", "result": [] })).is_err());
+    }
+}
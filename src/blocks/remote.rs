@@ -0,0 +1,346 @@
+//! Shows the load, temperature, memory and disk usage of another machine, collected over SSH.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Prints the metrics as `key=value` lines with the tools every Linux machine has, so that
+/// nothing needs to be installed on the remote host. `{path}` is replaced by the quoted path.
+const SCRIPT: &str = "\
+echo cores=$(nproc 2>/dev/null || getconf _NPROCESSORS_ONLN)
+echo load=$(cut -d' ' -f1-3 /proc/loadavg)
+echo temperature=$(cat /sys/class/thermal/thermal_zone*/temp 2>/dev/null | sort -n | tail -n1)
+echo memory=$(awk '/^MemTotal:|^MemAvailable:/ { printf \"%s \", $2 }' /proc/meminfo)
+echo disk=$(df -Pk {path} | tail -n1)
+echo uptime=$(cut -d' ' -f1 /proc/uptime)
+";
+
+/// What was collected from the remote host
+#[derive(Debug, Default, PartialEq)]
+struct Report {
+    cores: Option<u32>,
+    load: Option<(f64, f64, f64)>,
+    /// °C
+    temperature: Option<f64>,
+    /// Total and available memory in bytes
+    memory: Option<(u64, u64)>,
+    /// Total and free space in bytes
+    disk: Option<(u64, u64)>,
+    /// Seconds
+    uptime: Option<u64>,
+}
+
+/// Parses the output of `SCRIPT`. Values that couldn't be read on the remote host are left out.
+fn parse_report(output: &str) -> Report {
+    let lines: HashMap<&str, Vec<&str>> = output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key, value.split_whitespace().collect()))
+        .collect();
+    let field = |key, index: usize| lines.get(key).and_then(|fields| fields.get(index).copied());
+    let number = |key, index| field(key, index).and_then(|field| field.parse::<f64>().ok());
+
+    Report {
+        cores: field("cores", 0).and_then(|cores| cores.parse().ok()),
+        load: match (number("load", 0), number("load", 1), number("load", 2)) {
+            (Some(m1), Some(m5), Some(m15)) => Some((m1, m5, m15)),
+            _ => None,
+        },
+        // Millidegrees
+        temperature: number("temperature", 0).map(|temperature| temperature / 1000.),
+        memory: match (number("memory", 0), number("memory", 1)) {
+            (Some(total), Some(available)) => Some((total as u64 * 1024, available as u64 * 1024)),
+            _ => None,
+        },
+        // `<filesystem> <1024-blocks> <used> <available> <capacity> <mount point>`
+        disk: match (number("disk", 1), number("disk", 3)) {
+            (Some(total), Some(free)) => Some((total as u64 * 1024, free as u64 * 1024)),
+            _ => None,
+        },
+        uptime: number("uptime", 0).map(|uptime| uptime as u64),
+    }
+}
+
+pub struct Remote {
+    id: usize,
+    text: TextWidget,
+    host: String,
+    format: FormatTemplate,
+    unreachable_format: FormatTemplate,
+    info: f64,
+    warning: f64,
+    critical: f64,
+    report: Arc<Mutex<Option<Result<Report>>>>,
+    tx_collect: Sender<()>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RemoteConfig {
+    /// Host to connect to, as it would be passed to ssh
+    pub host: String,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// Format used while the host can't be reached
+    pub unreachable_format: FormatTemplate,
+
+    /// Filesystem of the remote host to report the disk space of
+    pub path: String,
+
+    /// Command to connect with, followed by the host and the command to run remotely
+    pub ssh: Vec<String>,
+
+    /// Minimum load per core, where state is set to info
+    pub info: f64,
+
+    /// Minimum load per core, where state is set to warning
+    pub warning: f64,
+
+    /// Minimum load per core, where state is set to critical
+    pub critical: f64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+            unreachable_format: FormatTemplate::default(),
+            path: "/".to_string(),
+            ssh: vec![
+                "ssh".to_string(),
+                "-o".to_string(),
+                "BatchMode=yes".to_string(),
+                "-o".to_string(),
+                "ConnectTimeout=5".to_string(),
+            ],
+            info: 0.3,
+            warning: 0.6,
+            critical: 0.9,
+        }
+    }
+}
+
+/// Runs `SCRIPT` on `host`
+fn collect(ssh: &[String], host: &str, path: &str) -> Result<Report> {
+    let (program, args) = ssh
+        .split_first()
+        .block_error("remote", "ssh must not be empty")?;
+    let script = SCRIPT.replace("{path}", &format!("'{}'", path.replace('\'', "'\\''")));
+    let output = Command::new(program)
+        .args(args)
+        .arg(host)
+        .arg(script)
+        .output()
+        .block_error("remote", &format!("failed to run {}", program))?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "remote".to_string(),
+            format!(
+                "{} failed: {}",
+                host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(parse_report(&String::from_utf8_lossy(&output.stdout)))
+}
+
+impl ConfigBlock for Remote {
+    type Config = RemoteConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.host.is_empty() {
+            return Err(ConfigurationError(
+                "remote".to_string(),
+                "host must be set".to_string(),
+            ));
+        }
+
+        // SSH may take seconds to connect, so collect in the background and update when done.
+        // Clicks ask for an early collection through `tx_collect`.
+        let report = Arc::new(Mutex::new(None));
+        let (tx_collect, rx_collect) = crossbeam_channel::unbounded();
+        {
+            let report = report.clone();
+            let RemoteConfig {
+                ssh,
+                host,
+                path,
+                interval,
+                ..
+            } = block_config.clone();
+            thread::Builder::new()
+                .name("remote".into())
+                .spawn(move || loop {
+                    *report.lock().unwrap() = Some(collect(&ssh, &host, &path));
+                    if tx_update_request
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                    if let Err(crossbeam_channel::RecvTimeoutError::Disconnected) =
+                        rx_collect.recv_timeout(interval)
+                    {
+                        break;
+                    }
+                })
+                .unwrap();
+        }
+
+        Ok(Remote {
+            id,
+            text: TextWidget::new(id, 0, shared_config)
+                .with_icon("cogs")?
+                .with_text("..."),
+            format: block_config.format.with_default("{host} {load_1m}")?,
+            unreachable_format: block_config.unreachable_format.with_default("{host} ×")?,
+            host: block_config.host,
+            info: block_config.info,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            report,
+            tx_collect,
+        })
+    }
+}
+
+impl Block for Remote {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let report = self.report.lock().unwrap();
+        let report = match &*report {
+            Some(Ok(report)) => report,
+            Some(Err(_)) => {
+                let values = map!("host" => Value::from_string(self.host.clone()));
+                self.text
+                    .set_texts(self.unreachable_format.render(&values)?);
+                self.text.set_state(State::Critical);
+                return Ok(None);
+            }
+            None => return Ok(None),
+        };
+
+        // Values the remote host doesn't provide are shown as ×, like in the battery block
+        let missing = || Value::from_string("×".to_string());
+        let (load_1m, load_5m, load_15m) = match report.load {
+            Some((m1, m5, m15)) => (
+                Value::from_float(m1),
+                Value::from_float(m5),
+                Value::from_float(m15),
+            ),
+            None => (missing(), missing(), missing()),
+        };
+        let values = map!(
+            "host" => Value::from_string(self.host.clone()),
+            "load_1m" => load_1m,
+            "load_5m" => load_5m,
+            "load_15m" => load_15m,
+            "temperature" => report
+                .temperature
+                .map_or_else(missing, |temperature| Value::from_float(temperature).degrees()),
+            "mem_used_percents" => report.memory.map_or_else(missing, |(total, available)| {
+                Value::from_float((total - available) as f64 / total as f64 * 100.).percents()
+            }),
+            "mem_available" => report.memory.map_or_else(missing, |(_, available)| {
+                Value::from_float(available as f64).bytes()
+            }),
+            "disk_used_percents" => report.disk.map_or_else(missing, |(total, free)| {
+                Value::from_float((total - free) as f64 / total as f64 * 100.).percents()
+            }),
+            "disk_free" => report
+                .disk
+                .map_or_else(missing, |(_, free)| Value::from_float(free as f64).bytes()),
+            "uptime" => report
+                .uptime
+                .map_or_else(missing, |uptime| Value::from_integer(uptime as i64).seconds()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+
+        let load = report
+            .load
+            .map(|(m1, _, _)| m1 / report.cores.unwrap_or(1).max(1) as f64);
+        self.text.set_state(match load {
+            Some(load) if load >= self.critical => State::Critical,
+            Some(load) if load >= self.warning => State::Warning,
+            Some(load) if load >= self.info => State::Info,
+            _ => State::Idle,
+        });
+
+        Ok(None)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.tx_collect.send(())?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_report() {
+        let output = "cores=4\n\
+                      load=0.52 0.41 0.30\n\
+                      temperature=47000\n\
+                      memory=16000000 12000000 \n\
+                      disk=/dev/sda1 1000000 250000 750000 25% /\n\
+                      uptime=86400.12\n";
+        assert_eq!(
+            parse_report(output),
+            Report {
+                cores: Some(4),
+                load: Some((0.52, 0.41, 0.30)),
+                temperature: Some(47.),
+                memory: Some((16_384_000_000, 12_288_000_000)),
+                disk: Some((1_024_000_000, 768_000_000)),
+                uptime: Some(86400),
+            }
+        );
+
+        // No thermal zones
+        assert_eq!(parse_report("temperature=\n").temperature, None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Counts the unread items of RSS and Atom feeds. The GUIDs of read items are kept in a state file,
+//! and a click marks every item as read.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::feed::{self, Item};
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// The GUIDs of the read items of every feed, which survive restarts
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+struct Seen {
+    feeds: BTreeMap<String, BTreeSet<String>>,
+}
+
+pub struct Rss {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    feeds: Vec<String>,
+    interval: Duration,
+    hide_if_none: bool,
+    seen: Seen,
+    state_file: PathBuf,
+    /// Unread items of all feeds, newest first
+    unread: Vec<(String, Item)>,
+    /// Whether a feed couldn't be fetched at the last update
+    failed: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RssConfig {
+    /// URLs of the RSS or Atom feeds
+    pub feeds: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Hide the block while there is no unread item
+    pub hide_if_none: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for RssConfig {
+    fn default() -> Self {
+        Self {
+            feeds: Vec::new(),
+            interval: Duration::from_secs(1800),
+            hide_if_none: false,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Rss {
+    type Config = RssConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.feeds.is_empty() {
+            return Err(ConfigurationError(
+                "rss".to_string(),
+                "feeds must be set".to_string(),
+            ));
+        }
+        let state_file = xdg_state_home().join("i3status-rust/rss.toml");
+        let seen = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(Rss {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config
+                .format
+                .with_default("{count}")?
+                .with_default_short("{title}")?,
+            feeds: block_config.feeds,
+            interval: block_config.interval,
+            hide_if_none: block_config.hide_if_none,
+            seen,
+            state_file,
+            unread: Vec::new(),
+            failed: false,
+        })
+    }
+}
+
+impl Seen {
+    /// Remembers the items of a feed and returns the unread ones. All items of a feed that wasn't
+    /// seen before count as read, so that adding a feed doesn't bring up its whole history.
+    fn update(&mut self, url: &str, items: Vec<Item>) -> Vec<Item> {
+        match self.feeds.get_mut(url) {
+            None => {
+                self.feeds
+                    .insert(url.to_string(), items.into_iter().map(|i| i.guid).collect());
+                Vec::new()
+            }
+            Some(guids) => {
+                // Forget items that left the feed, so that the state doesn't grow forever
+                guids.retain(|guid| items.iter().any(|i| &i.guid == guid));
+                items
+                    .into_iter()
+                    .filter(|i| !guids.contains(&i.guid))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Rss {
+    fn fetch(&self, url: &str) -> Result<Vec<Item>> {
+        let response = http::http_get(url, Some(Duration::from_secs(10)), vec![])?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "rss".to_string(),
+                format!("could not get {} (HTTP {})", url, response.code),
+            ));
+        }
+        Ok(feed::parse(&String::from_utf8_lossy(&response.content)))
+    }
+
+    fn save(&self) {
+        // Failing to save only means that items show up as unread again after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&self.seen) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let newest = self.unread.first().map(|(_, item)| item);
+        let values = map!(
+            "count" => Value::from_integer(self.unread.len() as i64),
+            "feed" => Value::from_string(newest.map(|i| i.feed.clone()).unwrap_or_default()),
+            "title" => Value::from_string(newest.map(|i| i.title.clone()).unwrap_or_default()),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match newest {
+            _ if self.failed => State::Warning,
+            Some(_) => State::Info,
+            None => State::Idle,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Rss {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let before = self.seen.clone();
+        let mut unread = Vec::new();
+        self.failed = false;
+        for url in &self.feeds {
+            match self.fetch(url) {
+                Ok(items) => {
+                    let items = self.seen.update(url, items);
+                    unread.extend(items.into_iter().map(|item| (url.clone(), item)));
+                }
+                Err(error) => {
+                    // The feed keeps its unread items until it can be fetched again
+                    log::warn!("failed to fetch {}: {}", url, error);
+                    self.failed = true;
+                    unread.extend(self.unread.iter().filter(|(feed, _)| feed == url).cloned());
+                }
+            }
+        }
+        let feeds = &self.feeds;
+        self.seen.feeds.retain(|url, _| feeds.contains(url));
+        if self.seen != before {
+            self.save();
+        }
+
+        unread.sort_by_key(|(_, item)| Reverse(item.published));
+        self.unread = unread;
+        self.set_text()?;
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left || self.unread.is_empty() {
+            return Ok(());
+        }
+        for (url, item) in self.unread.drain(..) {
+            self.seen.feeds.entry(url).or_default().insert(item.guid);
+        }
+        self.save();
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_none && self.unread.is_empty() && !self.failed {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(guid: &str) -> Item {
+        Item {
+            feed: "Blog".to_string(),
+            title: guid.to_string(),
+            guid: guid.to_string(),
+            link: String::new(),
+            enclosure: None,
+            published: None,
+        }
+    }
+
+    #[test]
+    fn test_seen_update() {
+        let mut seen = Seen::default();
+        assert!(seen.update("url", vec![item("a"), item("b")]).is_empty());
+        let unread = seen.update("url", vec![item("c"), item("b")]);
+        assert_eq!(unread, vec![item("c")]);
+        assert_eq!(
+            seen.feeds["url"],
+            std::iter::once("b".to_string()).collect()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Shows the age of the newest object under a prefix of an S3 bucket, to notice when backups that
+//! are uploaded there stop. Any S3-compatible storage that the AWS CLI can talk to works.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct S3Backup {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    command: String,
+    warning: i64,
+    critical: i64,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct S3BackupConfig {
+    pub bucket: String,
+
+    /// Only objects whose key starts with this count
+    pub prefix: String,
+
+    /// Profile of the AWS CLI to use
+    pub profile: Option<String>,
+
+    /// URL of an S3-compatible service other than AWS
+    pub endpoint_url: Option<String>,
+
+    /// Hours since the newest upload from which on the block is shown as warning
+    pub warning: i64,
+
+    /// Hours since the newest upload from which on the block is shown as critical
+    pub critical: i64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for S3BackupConfig {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            prefix: String::new(),
+            profile: None,
+            endpoint_url: None,
+            warning: 26,
+            critical: 50,
+            interval: Duration::from_secs(3600),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for S3Backup {
+    type Config = S3BackupConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.bucket.is_empty() {
+            return Err(ConfigurationError(
+                "s3_backup".to_string(),
+                "bucket must be set".to_string(),
+            ));
+        }
+
+        let mut command = format!(
+            "aws s3api list-objects-v2 --bucket '{}' --prefix '{}' \
+             --query 'sort_by(Contents || `[]`, &LastModified)[-1].[Key, LastModified]' \
+             --output text",
+            block_config.bucket, block_config.prefix
+        );
+        if let Some(profile) = &block_config.profile {
+            command.push_str(&format!(" --profile '{}'", profile));
+        }
+        if let Some(endpoint_url) = &block_config.endpoint_url {
+            command.push_str(&format!(" --endpoint-url '{}'", endpoint_url));
+        }
+
+        Ok(S3Backup {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("disk_drive")?,
+            format: block_config.format.with_default("{age:dur}")?,
+            command,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+/// The key and upload time of the newest object in the output of the command, which is "None"
+/// without any object
+fn parse_newest(output: &str) -> Result<Option<(String, DateTime<Utc>)>> {
+    let output = output.trim();
+    if output == "None" || output.is_empty() {
+        return Ok(None);
+    }
+    let (key, modified) = output
+        .rsplit_once('\t')
+        .block_error("s3_backup", "unexpected output of aws")?;
+    let modified = DateTime::parse_from_rfc3339(modified)
+        .block_error("s3_backup", "unexpected date in the output of aws")?;
+    Ok(Some((key.to_string(), modified.with_timezone(&Utc))))
+}
+
+impl Block for S3Backup {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let output = self.command_runner.run(&self.command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "s3_backup".to_string(),
+                "failed to list the bucket".to_string(),
+            ));
+        }
+
+        match parse_newest(&output.stdout)? {
+            Some((key, modified)) => {
+                let age = (Utc::now() - modified).num_seconds().max(0);
+                let values = map!(
+                    "age" => Value::from_integer(age).seconds(),
+                    "key" => Value::from_string(key),
+                );
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(if age >= self.critical * 3600 {
+                    State::Critical
+                } else if age >= self.warning * 3600 {
+                    State::Warning
+                } else {
+                    State::Idle
+                });
+            }
+            None => {
+                self.text.set_text("none".to_string());
+                self.text.set_state(State::Critical);
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_newest() {
+        assert_eq!(parse_newest("None\n").unwrap(), None);
+        assert_eq!(
+            parse_newest("host/2021-10-15 full.tar.zst\t2021-10-15T03:12:45+00:00\n").unwrap(),
+            Some((
+                "host/2021-10-15 full.tar.zst".to_string(),
+                Utc.ymd(2021, 10, 15).and_hms(3, 12, 45)
+            ))
+        );
+    }
+}
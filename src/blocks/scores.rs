@@ -0,0 +1,387 @@
+//! Follows teams through the public scoreboards of ESPN, showing the score of a game while it is
+//! played and when the next one starts otherwise.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const API_SERVER: &str = "https://site.api.espn.com/apis/site/v2/sports";
+/// How many days ahead to look for the next game
+const DAYS_AHEAD: i64 = 14;
+
+pub struct Scores {
+    id: usize,
+    text: TextWidget,
+    league: String,
+    teams: Vec<String>,
+    interval: Duration,
+    idle_interval: Duration,
+    format: FormatTemplate,
+    format_upcoming: FormatTemplate,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ScoresConfig {
+    /// Sport and league as in the ESPN API, e.g. `soccer/eng.1` or `basketball/nba`
+    pub league: String,
+
+    /// Abbreviations or names of the teams to follow
+    pub teams: Vec<String>,
+
+    /// Update interval while a game is played
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Update interval while no game is played
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub idle_interval: Duration,
+
+    /// Format of games that are played or finished today
+    pub format: FormatTemplate,
+
+    /// Format of the next game
+    pub format_upcoming: FormatTemplate,
+}
+
+impl Default for ScoresConfig {
+    fn default() -> Self {
+        Self {
+            league: String::new(),
+            teams: Vec::new(),
+            interval: Duration::from_secs(30),
+            idle_interval: Duration::from_secs(3600),
+            format: FormatTemplate::default(),
+            format_upcoming: FormatTemplate::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Scoreboard {
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Event {
+    date: String,
+    status: Status,
+    competitions: Vec<Competition>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Status {
+    #[serde(rename = "type")]
+    kind: StatusType,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StatusType {
+    /// `pre`, `in` or `post`
+    state: String,
+    /// The game clock while the game is played, e.g. "63'" or "Q3 5:12"
+    short_detail: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Competition {
+    competitors: Vec<Competitor>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Competitor {
+    home_away: String,
+    #[serde(default)]
+    score: Option<String>,
+    team: Team,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Team {
+    abbreviation: String,
+    display_name: String,
+    #[serde(default)]
+    short_display_name: String,
+}
+
+impl Team {
+    fn is(&self, name: &str) -> bool {
+        [
+            &self.abbreviation,
+            &self.display_name,
+            &self.short_display_name,
+        ]
+        .iter()
+        .any(|own| own.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Phase {
+    Upcoming,
+    Live,
+    Finished,
+}
+
+/// A game of a followed team
+#[derive(Debug)]
+struct Game<'a> {
+    phase: Phase,
+    start: DateTime<Utc>,
+    clock: &'a str,
+    home: &'a Competitor,
+    away: &'a Competitor,
+}
+
+impl Event {
+    fn game(&self, teams: &[String]) -> Option<Game<'_>> {
+        // ESPN leaves out the seconds
+        let start = NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%MZ")
+            .or_else(|_| NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%SZ"))
+            .ok()?;
+        let competitors = &self.competitions.first()?.competitors;
+        let home = competitors.iter().find(|c| c.home_away == "home")?;
+        let away = competitors.iter().find(|c| c.home_away == "away")?;
+        if !teams
+            .iter()
+            .any(|team| home.team.is(team) || away.team.is(team))
+        {
+            return None;
+        }
+        Some(Game {
+            phase: match self.status.kind.state.as_str() {
+                "in" => Phase::Live,
+                "post" => Phase::Finished,
+                _ => Phase::Upcoming,
+            },
+            start: DateTime::from_utc(start, Utc),
+            clock: &self.status.kind.short_detail,
+            home,
+            away,
+        })
+    }
+}
+
+/// The game to show: one that is played right now, else one that finished today, else the next one
+fn pick_game<'a>(events: &'a [Event], teams: &[String], now: DateTime<Local>) -> Option<Game<'a>> {
+    let games: Vec<Game> = events
+        .iter()
+        .filter_map(|event| event.game(teams))
+        .collect();
+    let today = now.date();
+    let mut live = None;
+    let mut finished = None;
+    let mut upcoming = None;
+    for game in games {
+        match game.phase {
+            Phase::Live => live = live.or(Some(game)),
+            Phase::Finished if game.start.with_timezone(&Local).date() == today => {
+                if finished
+                    .as_ref()
+                    .is_none_or(|f: &Game| f.start < game.start)
+                {
+                    finished = Some(game);
+                }
+            }
+            Phase::Upcoming => {
+                if upcoming
+                    .as_ref()
+                    .is_none_or(|u: &Game| u.start > game.start)
+                {
+                    upcoming = Some(game);
+                }
+            }
+            Phase::Finished => {}
+        }
+    }
+    live.or(finished).or(upcoming)
+}
+
+impl ConfigBlock for Scores {
+    type Config = ScoresConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.league.is_empty() || block_config.teams.is_empty() {
+            return Err(ConfigurationError(
+                "scores".to_string(),
+                "league and teams must be set".to_string(),
+            ));
+        }
+
+        Ok(Scores {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            league: block_config.league,
+            teams: block_config.teams,
+            interval: block_config.interval,
+            idle_interval: block_config.idle_interval,
+            format: block_config
+                .format
+                .with_default("{home} {home_score}-{away_score} {away} {clock}")?,
+            format_upcoming: block_config
+                .format_upcoming
+                .with_default("{home}-{away} {start}")?,
+        })
+    }
+}
+
+impl Scores {
+    fn fetch(&self, now: DateTime<Local>) -> Result<Scoreboard> {
+        let from = now.with_timezone(&Utc) - chrono::Duration::days(1);
+        let to = from + chrono::Duration::days(DAYS_AHEAD);
+        let url = format!(
+            "{}/{}/scoreboard?dates={}-{}",
+            API_SERVER,
+            self.league,
+            from.format("%Y%m%d"),
+            to.format("%Y%m%d")
+        );
+        let response = http::http_get_json(&url, Some(Duration::from_secs(10)), vec![])?;
+        if response.code != 200 {
+            return Err(BlockError(
+                "scores".to_string(),
+                format!("could not get the scoreboard of {}", self.league),
+            ));
+        }
+        serde_json::from_value(response.content)
+            .block_error("scores", "could not parse the scoreboard")
+    }
+}
+
+impl Block for Scores {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Local::now();
+        let scoreboard = match self.fetch(now) {
+            Ok(scoreboard) => scoreboard,
+            Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
+                // Offline, tried again as often as during a game
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+                return Ok(Some(self.interval.into()));
+            }
+            Err(error) => {
+                self.text.set_text(format!("scores error {}", error));
+                self.text.set_state(State::Critical);
+                return Ok(Some(self.idle_interval.into()));
+            }
+        };
+        let game = match pick_game(&scoreboard.events, &self.teams, now) {
+            Some(game) => game,
+            None => {
+                self.text.set_text(String::new());
+                self.text.set_state(State::Idle);
+                return Ok(Some(self.idle_interval.into()));
+            }
+        };
+
+        let values = map!(
+            "home" => Value::from_string(game.home.team.abbreviation.clone()),
+            "away" => Value::from_string(game.away.team.abbreviation.clone()),
+            "home_score" => Value::from_string(game.home.score.clone().unwrap_or_default()),
+            "away_score" => Value::from_string(game.away.score.clone().unwrap_or_default()),
+            "clock" => Value::from_string(game.clock.to_string()),
+            "start" => Value::from_string(game.start.with_timezone(&Local).format("%a %H:%M").to_string()),
+        );
+        self.text.set_texts(match game.phase {
+            Phase::Upcoming => self.format_upcoming.render(&values)?,
+            Phase::Live | Phase::Finished => self.format.render(&values)?,
+        });
+        self.text.set_state(match game.phase {
+            Phase::Live => State::Info,
+            _ => State::Idle,
+        });
+
+        // Poll often only while a game is played, and wake up for the start of the next one
+        let interval = match game.phase {
+            Phase::Live => self.interval,
+            _ => (game.start - now.with_timezone(&Utc))
+                .to_std()
+                .unwrap_or(self.idle_interval)
+                .clamp(self.interval, self.idle_interval),
+        };
+        Ok(Some(interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button == MouseButton::Left {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(date: &str, state: &str, home: &str, away: &str) -> String {
+        format!(
+            r#"{{"date":"{}","status":{{"type":{{"state":"{}","shortDetail":"63'"}}}},
+            "competitions":[{{"competitors":[
+                {{"homeAway":"home","score":"2","team":{{"abbreviation":"{}","displayName":"{} FC"}}}},
+                {{"homeAway":"away","score":"1","team":{{"abbreviation":"{}","displayName":"{} FC"}}}}
+            ]}}]}}"#,
+            date, state, home, home, away, away
+        )
+    }
+
+    #[test]
+    fn test_pick_game() {
+        let now = Utc.ymd(2021, 10, 2).and_hms(15, 0, 0).with_timezone(&Local);
+        let teams = vec!["ars".to_string()];
+        let scoreboard = |events: &[String]| -> Scoreboard {
+            serde_json::from_str(&format!(r#"{{"events":[{}]}}"#, events.join(","))).unwrap()
+        };
+
+        let board = scoreboard(&[
+            event("2021-10-09T14:00Z", "pre", "ARS", "CHE"),
+            event("2021-10-02T14:00Z", "in", "LIV", "MCI"),
+            event("2021-10-05T19:00Z", "pre", "TOT", "ARS"),
+        ]);
+        let game = pick_game(&board.events, &teams, now).unwrap();
+        assert_eq!(game.phase, Phase::Upcoming);
+        assert_eq!(game.home.team.abbreviation, "TOT");
+
+        let board = scoreboard(&[
+            event("2021-10-05T19:00Z", "pre", "TOT", "ARS"),
+            event("2021-10-02T14:00Z", "in", "Ars", "MCI"),
+        ]);
+        let game = pick_game(&board.events, &teams, now).unwrap();
+        assert_eq!(game.phase, Phase::Live);
+        assert_eq!(game.clock, "63'");
+
+        assert!(pick_game(&board.events, &["BUR".to_string()], now).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::I3BarEvent;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const VULNERABILITIES_DIR: &str = "/sys/devices/system/cpu/vulnerabilities";
+const SECURE_BOOT_VAR: &str =
+    "/sys/firmware/efi/efivars/SecureBoot-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+pub struct Security {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    screenlockers: Vec<String>,
+    format: FormatTemplate,
+    scrolling: Scrolling,
+    /// Index into the details shown instead of the format, 0 shows the format
+    page: usize,
+    summary: (String, Option<String>),
+    details: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SecurityConfig {
+    /// Process names of screen lockers and idle daemons, one of which should be running
+    pub screenlockers: Vec<String>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            screenlockers: [
+                "swaylock",
+                "swayidle",
+                "i3lock",
+                "xss-lock",
+                "xautolock",
+                "xidlehook",
+                "light-locker",
+                "xscreensaver",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+            interval: Duration::from_secs(300),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Whether a file in `/sys/devices/system/cpu/vulnerabilities` reports the CPU as unprotected
+fn is_vulnerable(status: &str) -> bool {
+    status.trim_start().starts_with("Vulnerable")
+}
+
+/// Reads an EFI variable as exposed by efivarfs: four bytes of attributes followed by the value
+fn secure_boot_enabled(efivar: &[u8]) -> bool {
+    efivar.get(4) == Some(&1)
+}
+
+/// The CPU vulnerabilities that aren't mitigated
+fn vulnerabilities() -> Vec<String> {
+    let mut vulnerable: Vec<String> = fs::read_dir(VULNERABILITIES_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path())
+                .map(|status| is_vulnerable(&status))
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    vulnerable.sort();
+    vulnerable
+}
+
+/// The first of `names` that is running, compared the way the kernel truncates process names
+fn running_process(names: &[String]) -> Option<String> {
+    let running: Vec<String> = fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim_end().to_string())
+        .collect();
+    names
+        .iter()
+        .find(|name| {
+            let comm: String = name.chars().take(15).collect();
+            running.contains(&comm)
+        })
+        .cloned()
+}
+
+impl ConfigBlock for Security {
+    type Config = SecurityConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Security {
+            id,
+            update_interval: block_config.interval,
+            screenlockers: block_config.screenlockers,
+            format: block_config.format.with_default("{checklist}")?,
+            scrolling: shared_config.scrolling,
+            text: TextWidget::new(id, 0, shared_config).with_icon("security")?,
+            page: 0,
+            summary: (String::new(), None),
+            details: Vec::new(),
+        })
+    }
+}
+
+impl Security {
+    fn show_page(&mut self) {
+        match self.page {
+            0 => self.text.set_texts(self.summary.clone()),
+            page => self.text.set_text(self.details[page - 1].clone()),
+        }
+    }
+}
+
+impl Block for Security {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let vulnerabilities = vulnerabilities();
+        // Machines without EFI can't have Secure Boot
+        let secure_boot = if Path::new("/sys/firmware/efi").exists() {
+            Some(
+                fs::read(SECURE_BOOT_VAR)
+                    .map(|var| secure_boot_enabled(&var))
+                    .unwrap_or(false),
+            )
+        } else {
+            None
+        };
+        let locker = running_process(&self.screenlockers);
+
+        let checks = [
+            vulnerabilities.is_empty(),
+            secure_boot.unwrap_or(true),
+            locker.is_some(),
+        ];
+        let checklist: String = checks
+            .iter()
+            .map(|&ok| if ok { '✓' } else { '✗' })
+            .collect();
+        let secure_boot = match secure_boot {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "n/a",
+        };
+
+        let values = map!(
+            "checklist" => Value::from_string(checklist),
+            "vulnerable" => Value::from_integer(vulnerabilities.len() as i64),
+            "vulnerabilities" => Value::from_string(vulnerabilities.join(" ")),
+            "secure_boot" => Value::from_string(secure_boot.to_string()),
+            "locker" => Value::from_string(locker.clone().unwrap_or_default()),
+        );
+        self.summary = self.format.render(&values)?;
+        self.details = vec![
+            if vulnerabilities.is_empty() {
+                "vulnerable: none".to_string()
+            } else {
+                format!("vulnerable: {}", vulnerabilities.join(" "))
+            },
+            format!("secure boot: {}", secure_boot),
+            format!("locker: {}", locker.as_deref().unwrap_or("not running")),
+        ];
+        self.show_page();
+        self.text.set_state(if checks.iter().all(|&ok| ok) {
+            State::Good
+        } else {
+            State::Warning
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let pages = self.details.len() + 1;
+        self.page = match self.scrolling.to_logical_direction(event.button) {
+            Some(LogicalDirection::Up) => (self.page + 1) % pages,
+            Some(LogicalDirection::Down) => (self.page + pages - 1) % pages,
+            None => 0,
+        };
+        self.show_page();
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        assert!(is_vulnerable("Vulnerable\n"));
+        assert!(is_vulnerable(
+            "Vulnerable: Clear CPU buffers attempted, no microcode\n"
+        ));
+        assert!(!is_vulnerable("Mitigation: PTI\n"));
+        assert!(!is_vulnerable("Not affected\n"));
+
+        assert!(secure_boot_enabled(&[6, 0, 0, 0, 1]));
+        assert!(!secure_boot_enabled(&[6, 0, 0, 0, 0]));
+        assert!(!secure_boot_enabled(&[]));
+    }
+}
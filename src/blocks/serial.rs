@@ -0,0 +1,344 @@
+//! Shows whether a USB serial device, like a microcontroller board, is plugged in, and optionally
+//! the last value that it printed which matches a regex
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::termios::{self, BaudRate, SetArg};
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How long to wait before opening a device that just appeared, so that udev can set its
+/// permissions
+const SETTLE: Duration = Duration::from_millis(500);
+/// How often the thread checks whether the block is gone while it waits for a device, in ms
+const WATCH_TIMEOUT: i32 = 1000;
+
+#[derive(Debug, Default)]
+struct Status {
+    /// The device node, like /dev/ttyACM0, while the device is plugged in
+    device: Option<PathBuf>,
+    /// The last value that was read
+    value: Option<String>,
+}
+
+pub struct Serial {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_absent: FormatTemplate,
+    status: Arc<Mutex<Status>>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SerialConfig {
+    /// USB vendor and product ID of the device, like "2341:0043"
+    pub usb_id: String,
+
+    /// Baud rate to set on the device before reading it
+    pub baud_rate: Option<u32>,
+
+    /// Regex that lines must match to be shown, with the value in the first group if there is one
+    pub regex: Option<String>,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Format override while the device is unplugged
+    pub format_absent: FormatTemplate,
+}
+
+fn baud_rate(rate: u32) -> Option<BaudRate> {
+    Some(match rate {
+        9600 => BaudRate::B9600,
+        19200 => BaudRate::B19200,
+        38400 => BaudRate::B38400,
+        57600 => BaudRate::B57600,
+        115200 => BaudRate::B115200,
+        230400 => BaudRate::B230400,
+        _ => return None,
+    })
+}
+
+/// The USB device that `dir` in /sys belongs to, as vendor and product ID
+fn usb_id(dir: &Path) -> Option<String> {
+    // The tty is a child of the USB interface, which is a child of the USB device
+    dir.ancestors().find_map(|dir| {
+        let vendor = fs::read_to_string(dir.join("idVendor")).ok()?;
+        let product = fs::read_to_string(dir.join("idProduct")).ok()?;
+        Some(format!("{}:{}", vendor.trim(), product.trim()))
+    })
+}
+
+/// The device node of the first tty of the USB device with `id`
+fn find_device(id: &str) -> Option<PathBuf> {
+    let mut ttys: Vec<_> = fs::read_dir("/sys/class/tty")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    ttys.sort_by_key(|entry| entry.file_name());
+    ttys.into_iter()
+        .filter_map(|entry| {
+            let device = fs::canonicalize(entry.path().join("device")).ok()?;
+            Some((entry.file_name(), device))
+        })
+        .find(|(_, device)| usb_id(device).is_some_and(|found| found.eq_ignore_ascii_case(id)))
+        .map(|(name, _)| Path::new("/dev").join(name))
+}
+
+/// The value in a line, or the line itself without a regex
+fn extract(line: &str, regex: Option<&Regex>) -> Option<String> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match regex {
+        None => Some(line.to_string()),
+        Some(regex) => {
+            let captures = regex.captures(line)?;
+            let value = captures.get(1).or_else(|| captures.get(0))?;
+            Some(value.as_str().to_string())
+        }
+    }
+}
+
+fn open(device: &Path, baud_rate: Option<BaudRate>) -> Result<File> {
+    let file = File::open(device)
+        .block_error("serial", &format!("failed to open {}", device.display()))?;
+    if let Some(baud_rate) = baud_rate {
+        let fd = file.as_raw_fd();
+        let mut attributes = termios::tcgetattr(fd)
+            .block_error("serial", &format!("{} is not a tty", device.display()))?;
+        termios::cfmakeraw(&mut attributes);
+        termios::cfsetspeed(&mut attributes, baud_rate)
+            .block_error("serial", "failed to set the baud rate")?;
+        termios::tcsetattr(fd, SetArg::TCSANOW, &attributes)
+            .block_error("serial", "failed to set the baud rate")?;
+    }
+    Ok(file)
+}
+
+fn update_now(id: usize, tx_update_request: &Sender<Task>) -> bool {
+    tx_update_request
+        .send(Task {
+            id,
+            update_time: Instant::now(),
+        })
+        .is_ok()
+}
+
+/// Waits until something in /dev changes. Returns `false` if the block is gone or /dev can't be
+/// watched anymore.
+fn wait_for_change(notify: &mut Inotify, buffer: &mut [u8], status: &Arc<Mutex<Status>>) -> bool {
+    while Arc::strong_count(status) > 1 {
+        let mut fds = [PollFd::new(notify.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, WATCH_TIMEOUT) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => return notify.read_events(buffer).is_ok(),
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+/// Waits for the device to appear, reads it until it disappears and starts over
+fn monitor(
+    id: usize,
+    usb_id: String,
+    baud_rate: Option<BaudRate>,
+    regex: Option<Regex>,
+    status: Arc<Mutex<Status>>,
+    tx_update_request: Sender<Task>,
+) {
+    // Device nodes are created in /dev when the device is plugged in, and watched before looking
+    // for the device so that none is missed
+    let mut notify = match Inotify::init() {
+        Ok(notify) => notify,
+        Err(error) => {
+            log::warn!("failed to start inotify: {}", error);
+            return;
+        }
+    };
+    if let Err(error) = notify.add_watch("/dev", WatchMask::CREATE | WatchMask::DELETE) {
+        log::warn!("failed to watch /dev: {}", error);
+        return;
+    }
+    let mut buffer = [0; 1024];
+    while Arc::strong_count(&status) > 1 {
+        let device = find_device(&usb_id);
+        *status.lock().unwrap() = Status {
+            device: device.clone(),
+            value: None,
+        };
+        if !update_now(id, &tx_update_request) {
+            return;
+        }
+
+        match device {
+            Some(device) => {
+                thread::sleep(SETTLE);
+                let file = match open(&device, baud_rate) {
+                    Ok(file) => file,
+                    Err(error) => {
+                        log::warn!("{}", error);
+                        // Try again once something changes
+                        if !wait_for_change(&mut notify, &mut buffer, &status) {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                // Ends when the device is unplugged, or with the next line once the block is gone
+                for line in BufReader::new(file).split(b'\n') {
+                    if Arc::strong_count(&status) == 1 {
+                        return;
+                    }
+                    let line = match line {
+                        Ok(line) => String::from_utf8_lossy(&line).into_owned(),
+                        Err(_) => break,
+                    };
+                    let value = match extract(&line, regex.as_ref()) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    let mut status = status.lock().unwrap();
+                    if status.value.as_ref() != Some(&value) {
+                        status.value = Some(value);
+                        drop(status);
+                        if !update_now(id, &tx_update_request) {
+                            return;
+                        }
+                    }
+                }
+            }
+            None => {
+                if !wait_for_change(&mut notify, &mut buffer, &status) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl ConfigBlock for Serial {
+    type Config = SerialConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let usb_id = block_config.usb_id.to_lowercase();
+        let valid = usb_id.split_once(':').is_some_and(|(vendor, product)| {
+            [vendor, product]
+                .iter()
+                .all(|id| id.len() == 4 && id.chars().all(|c| c.is_ascii_hexdigit()))
+        });
+        if !valid {
+            return Err(ConfigurationError(
+                "serial".to_string(),
+                "usb_id must look like \"2341:0043\"".to_string(),
+            ));
+        }
+        let baud_rate = match block_config.baud_rate {
+            None => None,
+            Some(rate) => Some(baud_rate(rate).block_error(
+                "serial",
+                "baud_rate must be one of 9600, 19200, 38400, 57600, 115200 and 230400",
+            )?),
+        };
+        let regex = match block_config.regex {
+            None => None,
+            Some(regex) => Some(Regex::new(&regex).configuration_error("invalid regex")?),
+        };
+
+        let status = Arc::new(Mutex::new(Status::default()));
+        let monitor_status = status.clone();
+        thread::Builder::new()
+            .name("serial".into())
+            .spawn(move || {
+                monitor(
+                    id,
+                    usb_id,
+                    baud_rate,
+                    regex,
+                    monitor_status,
+                    tx_update_request,
+                )
+            })
+            .internal_error("serial", "failed to start a thread")?;
+
+        Ok(Serial {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("usb")?,
+            format: block_config.format.with_default("{value}")?,
+            format_absent: block_config.format_absent.with_default("absent")?,
+            status,
+        })
+    }
+}
+
+impl Block for Serial {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self.status.lock().unwrap();
+        match &status.device {
+            Some(device) => {
+                let values = map!(
+                    "device" => Value::from_string(device.display().to_string()),
+                    "value" => Value::from_string(
+                        status.value.clone().unwrap_or_else(|| "-".to_string())
+                    ),
+                );
+                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_state(State::Good);
+            }
+            None => {
+                let values = map!("device" => Value::from_string(String::new()));
+                self.text.set_texts(self.format_absent.render(&values)?);
+                self.text.set_state(State::Idle);
+            }
+        }
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let regex = Regex::new(r"temp=(-?[0-9.]+)").unwrap();
+        assert_eq!(
+            extract("sensor temp=21.5 hum=40\r\n", Some(&regex)),
+            Some("21.5".to_string())
+        );
+        assert_eq!(extract("boot ok\r", Some(&regex)), None);
+        assert_eq!(extract("boot ok\r", None), Some("boot ok".to_string()));
+    }
+}
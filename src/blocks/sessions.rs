@@ -0,0 +1,264 @@
+//! Shows who is logged in, and above all how many sessions come from other machines, e.g. over
+//! SSH. The sessions are read from utmp, where login programs record them, or from logind.
+
+use std::collections::BTreeSet;
+use std::fs::read;
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const UTMP: &str = "/var/run/utmp";
+/// The size of `struct utmp` of glibc and musl
+const UTMP_RECORD: usize = 384;
+/// `ut_type` of logged in users
+const USER_PROCESS: i16 = 7;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionsDriver {
+    Utmp,
+    Loginctl,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Session {
+    user: String,
+    /// The machine the session comes from, if it isn't this one
+    remote_host: Option<String>,
+}
+
+/// The text of a NUL padded field
+fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Parses the records of utmp as Linux lays them out
+fn parse_utmp(data: &[u8]) -> Vec<Session> {
+    data.chunks_exact(UTMP_RECORD)
+        .filter(|record| i16::from_ne_bytes([record[0], record[1]]) == USER_PROCESS)
+        .map(|record| {
+            let host = c_string(&record[76..332]);
+            // Local sessions leave the host empty, or put the X display (":0") or the name of a
+            // terminal multiplexer ("tmux(1234).%0") there
+            let local = host.is_empty() || host.starts_with(':') || host.contains('(');
+            Session {
+                user: c_string(&record[44..76]),
+                remote_host: (!local).then_some(host),
+            }
+        })
+        .collect()
+}
+
+/// Parses the properties that `loginctl show-session` prints for several sessions, which are
+/// separated by empty lines
+fn parse_loginctl(output: &str) -> Vec<Session> {
+    output
+        .split("\n\n")
+        .filter_map(|properties| {
+            let property = |key: &str| {
+                properties
+                    .lines()
+                    .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                    .unwrap_or_default()
+            };
+            // Greeters and the managers of users aren't logins
+            if property("Class") != "user" {
+                return None;
+            }
+            let host = property("RemoteHost");
+            Some(Session {
+                user: property("Name").to_string(),
+                remote_host: (property("Remote") == "yes").then(|| {
+                    if host.is_empty() {
+                        "?".to_string()
+                    } else {
+                        host.to_string()
+                    }
+                }),
+            })
+        })
+        .collect()
+}
+
+fn run(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .block_error("sessions", "Failed to run loginctl.")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "sessions".to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl SessionsDriver {
+    fn sessions(self) -> Result<Vec<Session>> {
+        match self {
+            SessionsDriver::Utmp => Ok(parse_utmp(
+                &read(UTMP).block_error("sessions", "Failed to read utmp.")?,
+            )),
+            SessionsDriver::Loginctl => {
+                let list = run(Command::new("loginctl").args(["list-sessions", "--no-legend"]))?;
+                let ids: Vec<&str> = list
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().next())
+                    .collect();
+                if ids.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Ok(parse_loginctl(&run(Command::new("loginctl")
+                    .arg("show-session")
+                    .args(&ids)
+                    .args([
+                        "--property=Name",
+                        "--property=Class",
+                        "--property=Remote",
+                        "--property=RemoteHost",
+                    ]))?))
+            }
+        }
+    }
+}
+
+pub struct Sessions {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    driver: SessionsDriver,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SessionsConfig {
+    pub driver: SessionsDriver,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Update interval
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            driver: SessionsDriver::Utmp,
+            format: FormatTemplate::default(),
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ConfigBlock for Sessions {
+    type Config = SessionsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Sessions {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("sessions")?,
+            format: block_config.format.with_default("{remote}")?,
+            driver: block_config.driver,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Block for Sessions {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let sessions = self.driver.sessions()?;
+        let users: BTreeSet<&str> = sessions.iter().map(|s| s.user.as_str()).collect();
+        let hosts: BTreeSet<&str> = sessions
+            .iter()
+            .filter_map(|s| s.remote_host.as_deref())
+            .collect();
+        let remote = sessions.iter().filter(|s| s.remote_host.is_some()).count();
+
+        let values = map!(
+            "sessions" => Value::from_integer(sessions.len() as i64),
+            "remote" => Value::from_integer(remote as i64),
+            "users" => Value::from_string(users.into_iter().collect::<Vec<_>>().join(", ")),
+            "hosts" => Value::from_string(hosts.into_iter().collect::<Vec<_>>().join(", ")),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if remote > 0 {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let record = |kind: i16, user: &str, host: &str| {
+            let mut record = vec![0; UTMP_RECORD];
+            record[..2].copy_from_slice(&kind.to_ne_bytes());
+            record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+            record[76..76 + host.len()].copy_from_slice(host.as_bytes());
+            record
+        };
+        let utmp = [
+            record(2, "reboot", "5.15.0"),
+            record(USER_PROCESS, "me", ":0"),
+            record(USER_PROCESS, "me", "tmux(4242).%0"),
+            record(USER_PROCESS, "admin", "203.0.113.7"),
+        ]
+        .concat();
+        let session = |user: &str, host: Option<&str>| Session {
+            user: user.to_string(),
+            remote_host: host.map(str::to_string),
+        };
+        assert_eq!(
+            parse_utmp(&utmp),
+            vec![
+                session("me", None),
+                session("me", None),
+                session("admin", Some("203.0.113.7")),
+            ]
+        );
+
+        let loginctl = "Name=me\nClass=user\nRemote=no\nRemoteHost=\n\n\
+                        Name=gdm\nClass=greeter\nRemote=no\nRemoteHost=\n\n\
+                        Name=admin\nClass=user\nRemote=yes\nRemoteHost=203.0.113.7\n";
+        assert_eq!(
+            parse_loginctl(loginctl),
+            vec![session("me", None), session("admin", Some("203.0.113.7"))]
+        );
+    }
+}
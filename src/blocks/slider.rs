@@ -0,0 +1,251 @@
+//! Shows a number that a pair of commands reads and sets, and changes it by scrolling, like the
+//! brightness of a keyboard, the speed of a fan or the contrast of a monitor through `ddcutil`.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::I3BarEvent;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+pub struct Slider {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    get_command: String,
+    set_command: String,
+    min: f64,
+    max: f64,
+    step: f64,
+    value: Option<f64>,
+    interval: Duration,
+    scrolling: Scrolling,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SliderConfig {
+    /// Command that prints the current value
+    pub get_command: String,
+
+    /// Command that sets the value, with `{value}` replaced by it
+    pub set_command: String,
+
+    pub min: f64,
+    pub max: f64,
+
+    /// How much one step of the mouse wheel changes the value
+    pub step: f64,
+
+    /// Name of the icon to show
+    pub icon: Option<String>,
+
+    /// How often the value is read, to notice when something else changes it
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for SliderConfig {
+    fn default() -> Self {
+        Self {
+            get_command: String::new(),
+            set_command: String::new(),
+            min: 0.,
+            max: 100.,
+            step: 5.,
+            icon: None,
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Slider {
+    type Config = SliderConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.get_command.is_empty() || block_config.set_command.is_empty() {
+            return Err(ConfigurationError(
+                "slider".to_string(),
+                "get_command and set_command must be set".to_string(),
+            ));
+        }
+        if block_config.min >= block_config.max || block_config.step <= 0. {
+            return Err(ConfigurationError(
+                "slider".to_string(),
+                "min must be below max and step must be positive".to_string(),
+            ));
+        }
+
+        let mut text = TextWidget::new(id, 0, shared_config.clone());
+        if let Some(icon) = &block_config.icon {
+            text = text.with_icon(icon)?;
+        }
+
+        Ok(Slider {
+            id,
+            text,
+            format: block_config
+                .format
+                .with_default("{percentage:bar(5)} {value}")?,
+            get_command: block_config.get_command,
+            set_command: block_config.set_command,
+            min: block_config.min,
+            max: block_config.max,
+            step: block_config.step,
+            value: None,
+            interval: block_config.interval,
+            scrolling: shared_config.scrolling,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+/// Whole numbers are integers, so that they are shown without decimals
+fn number(value: f64) -> Value {
+    if value.fract() == 0. {
+        Value::from_integer(value as i64)
+    } else {
+        Value::from_float(value)
+    }
+}
+
+impl Slider {
+    fn read(&self) -> Result<f64> {
+        let output = self.command_runner.run(&self.get_command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "slider".to_string(),
+                "get_command failed".to_string(),
+            ));
+        }
+        output.stdout.trim().parse().block_error(
+            "slider",
+            &format!(
+                "get_command printed '{}', not a number",
+                output.stdout.trim()
+            ),
+        )
+    }
+
+    fn write(&mut self, value: f64) -> Result<()> {
+        let command = self.set_command.replace("{value}", &value.to_string());
+        let output = self.command_runner.run(&command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "slider".to_string(),
+                "set_command failed".to_string(),
+            ));
+        }
+        // Reading the value back may be slow, e.g. over DDC
+        self.value = Some(value);
+        self.render()
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let value = self.value.unwrap_or(self.min);
+        let values = map!(
+            "value" => number(value),
+            "percentage" => Value::from_float((value - self.min) / (self.max - self.min) * 100.)
+                .percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        Ok(())
+    }
+}
+
+impl Block for Slider {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.value = Some(self.read()?);
+        self.render()?;
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let sign = match self.scrolling.to_logical_direction(event.button) {
+            Some(LogicalDirection::Up) => 1.,
+            Some(LogicalDirection::Down) => -1.,
+            None => return Ok(()),
+        };
+        let value = match self.value {
+            Some(value) => value,
+            None => self.read()?,
+        };
+        // Rounded, so that steps like 0.1 don't add up to values like 0.30000000000000004
+        let target = ((value + sign * self.step).clamp(self.min, self.max) * 1e6).round() / 1e6;
+        if target != value {
+            self.write(target)?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::i3bar_event::MouseButton;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_scroll() {
+        let runner = Rc::new(MockRunner::default());
+        runner.set("cat level", "9\n");
+        runner.set("set-level 10", "");
+        let mut shared_config = SharedConfig::default();
+        shared_config.command_runner = runner.clone();
+        let config = SliderConfig {
+            get_command: "cat level".to_string(),
+            set_command: "set-level {value}".to_string(),
+            max: 10.,
+            step: 2.,
+            format: FormatTemplate::new("{value}", None).unwrap(),
+            ..SliderConfig::default()
+        };
+        let mut slider =
+            Slider::new(0, config, shared_config, crossbeam_channel::unbounded().0).unwrap();
+        slider.update().unwrap();
+
+        let scroll = |button| I3BarEvent {
+            id: Some(0),
+            instance: None,
+            button,
+            modifiers: Vec::new(),
+            double_click: false,
+        };
+        slider.click(&scroll(MouseButton::WheelUp)).unwrap();
+        assert_eq!(runner.ran.borrow().last().unwrap(), "set-level 10");
+        assert_eq!(slider.text.get_data().full_text.trim(), "10");
+
+        // Already at the maximum
+        slider.click(&scroll(MouseButton::WheelUp)).unwrap();
+        assert_eq!(runner.ran.borrow().len(), 2);
+    }
+}
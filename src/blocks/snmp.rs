@@ -0,0 +1,240 @@
+//! Polls values such as temperatures, PDU loads or the status of switch ports from network
+//! equipment over SNMP, using `snmpget` of [Net-SNMP](http://www.net-snmp.org/).
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OidConfig {
+    /// Placeholder of the value in the format
+    pub name: String,
+
+    /// Numeric or, if the MIB is installed, symbolic OID
+    pub oid: String,
+
+    /// Factor that the value is multiplied with, e.g. 0.1 for tenths of degrees
+    #[serde(default = "OidConfig::default_scale")]
+    pub scale: f64,
+
+    /// Scaled value from which on the block is shown as warning
+    pub warning: Option<f64>,
+
+    /// Scaled value from which on the block is shown as critical
+    pub critical: Option<f64>,
+}
+
+impl OidConfig {
+    fn default_scale() -> f64 {
+        1.
+    }
+
+    fn state(&self, value: f64) -> State {
+        match (self.warning, self.critical) {
+            (_, Some(critical)) if value >= critical => State::Critical,
+            (Some(warning), _) if value >= warning => State::Warning,
+            _ => State::Idle,
+        }
+    }
+}
+
+pub struct Snmp {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    command: String,
+    oids: Vec<OidConfig>,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SnmpConfig {
+    /// Host name or address of the agent, optionally with a port like "switch:161"
+    pub host: String,
+
+    /// SNMP version, `1` or `2c`
+    pub version: String,
+
+    pub community: String,
+
+    pub oids: Vec<OidConfig>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            version: "2c".to_string(),
+            community: "public".to_string(),
+            oids: Vec::new(),
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Snmp {
+    type Config = SnmpConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.host.is_empty() || block_config.oids.is_empty() {
+            return Err(ConfigurationError(
+                "snmp".to_string(),
+                "host and oids must be set".to_string(),
+            ));
+        }
+        if !["1", "2c"].contains(&block_config.version.as_str()) {
+            return Err(ConfigurationError(
+                "snmp".to_string(),
+                format!("unsupported SNMP version '{}'", block_config.version),
+            ));
+        }
+        let oids: Vec<&str> = block_config.oids.iter().map(|o| o.oid.as_str()).collect();
+        // -Oqv prints only the values, one per line, and -Oe enumerations as numbers
+        let command = format!(
+            "snmpget -v{} -c '{}' -t 2 -r 1 -Oqve '{}' {}",
+            block_config.version,
+            block_config.community,
+            block_config.host,
+            oids.join(" ")
+        );
+        let default_format: Vec<String> = block_config
+            .oids
+            .iter()
+            .map(|o| format!("{{{}}}", o.name))
+            .collect();
+
+        Ok(Snmp {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()),
+            format: block_config
+                .format
+                .with_default(&default_format.join(" "))?,
+            command,
+            oids: block_config.oids,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+        })
+    }
+}
+
+impl Block for Snmp {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let output = self.command_runner.run(&self.command)?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "snmp".to_string(),
+                format!("snmpget failed: {}", output.stdout.trim()),
+            ));
+        }
+
+        let mut values = HashMap::new();
+        let mut state = State::Idle;
+        for (oid, line) in self.oids.iter().zip(output.stdout.lines()) {
+            let text = line.trim().trim_matches('"');
+            let (value, number) = match (text.parse::<i64>(), text.parse::<f64>()) {
+                // Counters and enumerations are shown as they are
+                (Ok(integer), _) if oid.scale == 1. => {
+                    (Value::from_integer(integer), Some(integer as f64))
+                }
+                (_, Ok(number)) => (
+                    Value::from_float(number * oid.scale),
+                    Some(number * oid.scale),
+                ),
+                _ => (Value::from_string(text.to_string()), None),
+            };
+            if let Some(oid_state) = number.map(|number| oid.state(number)) {
+                if oid_state.severity() > state.severity() {
+                    state = oid_state;
+                }
+            }
+            values.insert(oid.name.clone(), value);
+        }
+        if values.len() < self.oids.len() {
+            return Err(BlockError(
+                "snmp".to_string(),
+                "snmpget printed fewer values than were asked for".to_string(),
+            ));
+        }
+
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(state);
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_update() {
+        let runner = Rc::new(MockRunner::default());
+        runner.set(
+            "snmpget -v2c -c 'public' -t 2 -r 1 -Oqve 'pdu' temp load port",
+            "235\n\"7.5\"\n2\n",
+        );
+        let mut shared_config = SharedConfig::default();
+        shared_config.command_runner = runner;
+        let oid = |name: &str, scale, critical| OidConfig {
+            name: name.to_string(),
+            oid: name.to_string(),
+            scale,
+            warning: None,
+            critical,
+        };
+        let config = SnmpConfig {
+            host: "pdu".to_string(),
+            oids: vec![
+                oid("temp", 0.1, Some(30.)),
+                oid("load", 1., Some(10.)),
+                oid("port", 1., Some(2.)),
+            ],
+            format: FormatTemplate::new("{temp:4} {load} {port:1}", None).unwrap(),
+            ..SnmpConfig::default()
+        };
+        let mut snmp =
+            Snmp::new(0, config, shared_config, crossbeam_channel::unbounded().0).unwrap();
+        snmp.update().unwrap();
+        assert_eq!(snmp.text.state(), State::Critical);
+        assert_eq!(snmp.text.get_data().full_text.trim(), "23.5 7.5 2");
+    }
+}
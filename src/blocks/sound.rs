@@ -2,10 +2,11 @@
 use {
     crate::pulse::callbacks::ListResult,
     crate::pulse::context::{
-        introspect::ServerInfo, introspect::SinkInfo, introspect::SourceInfo, subscribe::Facility,
-        subscribe::InterestMaskSet, subscribe::Operation as SubscribeOperation, Context, FlagSet,
-        State as PulseState,
+        introspect::ServerInfo, introspect::SinkInfo, introspect::SinkInputInfo,
+        introspect::SourceInfo, subscribe::Facility, subscribe::InterestMaskSet,
+        subscribe::Operation as SubscribeOperation, Context, FlagSet, State as PulseState,
     },
+    crate::pulse::def::{SinkState, SourceState},
     crate::pulse::mainloop::standard::IterateResult,
     crate::pulse::mainloop::standard::Mainloop,
     crate::pulse::proplist::{properties, Proplist},
@@ -22,6 +23,7 @@ use {
 
 use std::cmp::{max, min};
 use std::collections::BTreeMap;
+use std::fs;
 use std::io::Read;
 use std::process::{Command, Stdio};
 use std::thread;
@@ -42,12 +44,14 @@ use crate::subprocess::spawn_child_async;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, Spacing, State};
 
-trait SoundDevice {
+pub trait SoundDevice {
     fn volume(&self) -> u32;
     fn muted(&self) -> bool;
     fn output_name(&self) -> String;
     fn output_description(&self) -> Option<String>;
     fn active_port(&self) -> Option<String>;
+    /// Whether a stream is playing to or recording from the device
+    fn playing(&self) -> bool;
 
     fn get_info(&mut self) -> Result<()>;
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()>;
@@ -61,6 +65,25 @@ struct AlsaSoundDevice {
     natural_mapping: bool,
     volume: u32,
     muted: bool,
+    playing: bool,
+}
+
+/// Whether any ALSA playback stream is running
+fn alsa_playing() -> bool {
+    let cards = match fs::read_dir("/proc/asound") {
+        Ok(cards) => cards,
+        Err(_) => return false,
+    };
+    cards
+        .filter_map(|card| fs::read_dir(card.ok()?.path()).ok())
+        .flatten()
+        .filter_map(|pcm| pcm.ok())
+        // Playback devices are named like pcm0p, capture devices like pcm0c
+        .filter(|pcm| pcm.file_name().to_string_lossy().ends_with('p'))
+        .filter_map(|pcm| fs::read_dir(pcm.path()).ok())
+        .flatten()
+        .filter_map(|substream| fs::read_to_string(substream.ok()?.path().join("status")).ok())
+        .any(|status| status.contains("state: RUNNING"))
 }
 
 impl AlsaSoundDevice {
@@ -71,6 +94,7 @@ impl AlsaSoundDevice {
             natural_mapping,
             volume: 0,
             muted: false,
+            playing: false,
         };
         sd.get_info()?;
 
@@ -95,6 +119,9 @@ impl SoundDevice for AlsaSoundDevice {
     fn active_port(&self) -> Option<String> {
         None
     }
+    fn playing(&self) -> bool {
+        self.playing
+    }
 
     fn get_info(&mut self) -> Result<()> {
         let mut args = Vec::new();
@@ -127,6 +154,7 @@ impl SoundDevice for AlsaSoundDevice {
             .block_error("sound", "could not parse volume to u32")?;
 
         self.muted = last.get(1).map(|muted| *muted == "off").unwrap_or(false);
+        self.playing = alsa_playing();
 
         Ok(())
     }
@@ -230,6 +258,7 @@ struct PulseAudioSoundDevice {
     volume: Option<ChannelVolumes>,
     volume_avg: u32,
     muted: bool,
+    running: bool,
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -240,6 +269,7 @@ struct PulseAudioVolInfo {
     name: String,
     description: Option<String>,
     active_port: Option<String>,
+    running: bool,
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -262,6 +292,7 @@ impl TryFrom<&SourceInfo<'_>> for PulseAudioVolInfo {
                     .as_ref()
                     .map(|a| a.name.as_ref().map(|n| n.to_string()))
                     .flatten(),
+                running: source_info.state == SourceState::Running,
             }),
         }
     }
@@ -287,11 +318,23 @@ impl TryFrom<&SinkInfo<'_>> for PulseAudioVolInfo {
                     .as_ref()
                     .map(|a| a.name.as_ref().map(|n| n.to_string()))
                     .flatten(),
+                running: sink_info.state == SinkState::Running,
             }),
         }
     }
 }
 
+/// A stream that an application plays, like the audio of a browser
+#[cfg(feature = "pulseaudio")]
+#[derive(Debug, Clone)]
+struct PulseAudioStreamInfo {
+    index: u32,
+    application: String,
+    binary: String,
+    volume: ChannelVolumes,
+    mute: bool,
+}
+
 #[cfg(feature = "pulseaudio")]
 #[derive(Debug)]
 enum PulseAudioClientRequest {
@@ -300,6 +343,9 @@ enum PulseAudioClientRequest {
     GetInfoByName(DeviceKind, String),
     SetVolumeByName(DeviceKind, String, ChannelVolumes),
     SetMuteByName(DeviceKind, String, bool),
+    GetSinkInputs,
+    SetSinkInputVolume(u32, ChannelVolumes),
+    SetSinkInputMute(u32, bool),
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -315,6 +361,11 @@ lazy_static! {
     // State for each device
     static ref PULSEAUDIO_DEVICES: Mutex<HashMap<(DeviceKind, String), PulseAudioVolInfo>> =
         Mutex::new(HashMap::new());
+
+    // Streams of applications, and the list of them that is being received
+    static ref PULSEAUDIO_SINK_INPUTS: Mutex<Vec<PulseAudioStreamInfo>> = Mutex::new(Vec::new());
+    static ref PULSEAUDIO_SINK_INPUTS_RECEIVED: Mutex<Vec<PulseAudioStreamInfo>> =
+        Mutex::new(Vec::new());
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -459,6 +510,17 @@ impl PulseAudioClient {
                                 SetMuteByName(DeviceKind::Source, name, mute) => {
                                     introspector.set_source_mute_by_name(&name, mute, None);
                                 }
+                                GetSinkInputs => {
+                                    introspector.get_sink_input_info_list(
+                                        PulseAudioClient::sink_input_info_callback,
+                                    );
+                                }
+                                SetSinkInputVolume(index, volumes) => {
+                                    introspector.set_sink_input_volume(index, &volumes, None);
+                                }
+                                SetSinkInputMute(index, mute) => {
+                                    introspector.set_sink_input_mute(index, mute, None);
+                                }
                             };
 
                             // send request and receive response
@@ -483,7 +545,10 @@ impl PulseAudioClient {
                     .borrow_mut()
                     .set_subscribe_callback(Some(Box::new(PulseAudioClient::subscribe_callback)));
                 connection.context.borrow_mut().subscribe(
-                    InterestMaskSet::SERVER | InterestMaskSet::SINK | InterestMaskSet::SOURCE,
+                    InterestMaskSet::SERVER
+                        | InterestMaskSet::SINK
+                        | InterestMaskSet::SOURCE
+                        | InterestMaskSet::SINK_INPUT,
                     |_| {},
                 );
 
@@ -551,6 +616,28 @@ impl PulseAudioClient {
         }
     }
 
+    fn sink_input_info_callback(result: ListResult<&SinkInputInfo>) {
+        let mut received = PULSEAUDIO_SINK_INPUTS_RECEIVED.lock().unwrap();
+        match result {
+            ListResult::Item(info) => {
+                let property = |name| info.proplist.get_str(name).unwrap_or_default();
+                received.push(PulseAudioStreamInfo {
+                    index: info.index,
+                    application: property(properties::APPLICATION_NAME),
+                    binary: property(properties::APPLICATION_PROCESS_BINARY),
+                    volume: info.volume,
+                    mute: info.mute,
+                });
+            }
+            ListResult::End => {
+                *PULSEAUDIO_SINK_INPUTS.lock().unwrap() = std::mem::take(&mut *received);
+                drop(received);
+                PulseAudioClient::send_update_event();
+            }
+            ListResult::Error => received.clear(),
+        }
+    }
+
     fn subscribe_callback(
         facility: Option<Facility>,
         _operation: Option<SubscribeOperation>,
@@ -576,6 +663,10 @@ impl PulseAudioClient {
                     ))
                     .ok();
                 }
+                // Streams come and go, so the whole list is asked for again
+                Facility::SinkInput => {
+                    PulseAudioClient::send(PulseAudioClientRequest::GetSinkInputs).ok();
+                }
                 _ => {}
             },
         }
@@ -593,6 +684,29 @@ impl PulseAudioClient {
     }
 }
 
+/// Changes all channels of `volume` by `step` percent, up to `max_vol` percent
+#[cfg(feature = "pulseaudio")]
+fn step_volume(volume: &mut ChannelVolumes, step: i32, max_vol: Option<u32>) {
+    let step = (step as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as i32;
+    for vol in volume.get_mut().iter_mut() {
+        let uncapped_vol = max(0, vol.0 as i32 + step) as u32;
+        let capped_vol = if let Some(vol_cap) = max_vol {
+            min(
+                uncapped_vol,
+                (vol_cap as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as u32,
+            )
+        } else {
+            uncapped_vol
+        };
+        vol.0 = min(capped_vol, Volume::MAX.0);
+    }
+}
+
+#[cfg(feature = "pulseaudio")]
+fn volume_percent(volume: ChannelVolumes) -> u32 {
+    (volume.avg().0 as f32 / Volume::NORMAL.0 as f32 * 100.0).round() as u32
+}
+
 #[cfg(feature = "pulseaudio")]
 impl PulseAudioSoundDevice {
     fn new(device_kind: DeviceKind) -> Result<Self> {
@@ -606,6 +720,7 @@ impl PulseAudioSoundDevice {
             volume: None,
             volume_avg: 0,
             muted: false,
+            running: false,
         };
 
         PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
@@ -629,7 +744,7 @@ impl PulseAudioSoundDevice {
 
     fn volume(&mut self, volume: ChannelVolumes) {
         self.volume = Some(volume);
-        self.volume_avg = (volume.avg().0 as f32 / Volume::NORMAL.0 as f32 * 100.0).round() as u32;
+        self.volume_avg = volume_percent(volume);
     }
 }
 
@@ -655,6 +770,10 @@ impl SoundDevice for PulseAudioSoundDevice {
         self.active_port.clone()
     }
 
+    fn playing(&self) -> bool {
+        self.running
+    }
+
     fn get_info(&mut self) -> Result<()> {
         let devices = PULSEAUDIO_DEVICES.lock().unwrap();
 
@@ -663,6 +782,7 @@ impl SoundDevice for PulseAudioSoundDevice {
             self.muted = info.mute;
             self.description = info.description.clone();
             self.active_port = info.active_port.clone();
+            self.running = info.running;
         }
 
         Ok(())
@@ -670,21 +790,7 @@ impl SoundDevice for PulseAudioSoundDevice {
 
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
         let mut volume = self.volume.block_error("sound", "volume unknown")?;
-
-        // apply step to volumes
-        let step = (step as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as i32;
-        for vol in volume.get_mut().iter_mut() {
-            let uncapped_vol = max(0, vol.0 as i32 + step) as u32;
-            let capped_vol = if let Some(vol_cap) = max_vol {
-                min(
-                    uncapped_vol,
-                    (vol_cap as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as u32,
-                )
-            } else {
-                uncapped_vol
-            };
-            vol.0 = min(capped_vol, Volume::MAX.0);
-        }
+        step_volume(&mut volume, step, max_vol);
 
         // update volumes
         self.volume(volume);
@@ -718,6 +824,106 @@ impl SoundDevice for PulseAudioSoundDevice {
     }
 }
 
+/// The stream of an application, found by its name or the name of its binary, which only exists
+/// while the application plays audio
+#[cfg(feature = "pulseaudio")]
+struct PulseAudioApplicationStream {
+    application: String,
+    stream: Option<PulseAudioStreamInfo>,
+}
+
+#[cfg(feature = "pulseaudio")]
+impl PulseAudioApplicationStream {
+    fn new(application: String) -> Result<Self> {
+        PulseAudioClient::send(PulseAudioClientRequest::GetSinkInputs)?;
+        Ok(PulseAudioApplicationStream {
+            application,
+            stream: None,
+        })
+    }
+}
+
+#[cfg(feature = "pulseaudio")]
+impl SoundDevice for PulseAudioApplicationStream {
+    fn volume(&self) -> u32 {
+        self.stream
+            .as_ref()
+            .map_or(0, |stream| volume_percent(stream.volume))
+    }
+
+    fn muted(&self) -> bool {
+        self.stream.as_ref().is_some_and(|stream| stream.mute)
+    }
+
+    fn output_name(&self) -> String {
+        self.application.clone()
+    }
+
+    fn output_description(&self) -> Option<String> {
+        None
+    }
+
+    fn active_port(&self) -> Option<String> {
+        None
+    }
+
+    fn playing(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn get_info(&mut self) -> Result<()> {
+        self.stream = PULSEAUDIO_SINK_INPUTS
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|stream| {
+                stream.application.eq_ignore_ascii_case(&self.application)
+                    || stream.binary.eq_ignore_ascii_case(&self.application)
+            })
+            .cloned();
+        Ok(())
+    }
+
+    fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
+        // Nothing to do while the application doesn't play anything
+        if let Some(stream) = &mut self.stream {
+            step_volume(&mut stream.volume, step, max_vol);
+            PulseAudioClient::send(PulseAudioClientRequest::SetSinkInputVolume(
+                stream.index,
+                stream.volume,
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> Result<()> {
+        if let Some(stream) = &mut self.stream {
+            stream.mute = !stream.mute;
+            PulseAudioClient::send(PulseAudioClientRequest::SetSinkInputMute(
+                stream.index,
+                stream.mute,
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn monitor(&mut self, id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+        PULSEAUDIO_EVENT_LISTENER
+            .lock()
+            .unwrap()
+            .insert(id, tx_update_request);
+        Ok(())
+    }
+}
+
+/// Another device that scrolling with a modifier key held down changes the volume of
+struct ScrollTarget {
+    /// Format key of the volume of the device
+    key: &'static str,
+    modifier: String,
+    device: Box<dyn SoundDevice>,
+}
+
 // TODO: Use the alsa control bindings to implement push updates
 pub struct Sound {
     text: TextWidget,
@@ -732,6 +938,7 @@ pub struct Sound {
     mappings: Option<BTreeMap<String, String>>,
     max_vol: Option<u32>,
     scrolling: Scrolling,
+    scroll_targets: Vec<ScrollTarget>,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -806,6 +1013,15 @@ pub struct SoundConfig {
     pub mappings: Option<BTreeMap<String, String>>,
 
     pub max_vol: Option<u32>,
+
+    /// Modifier key that makes scrolling change the volume of the default source instead
+    pub source_modifier: Option<String>,
+
+    /// Modifier key that makes scrolling change the volume of `application` instead
+    pub application_modifier: Option<String>,
+
+    /// Name of the application, or of its binary, whose stream to change (PulseAudio only)
+    pub application: Option<String>,
 }
 
 impl Default for SoundConfig {
@@ -822,6 +1038,9 @@ impl Default for SoundConfig {
             show_volume_when_muted: false,
             mappings: None,
             max_vol: None,
+            source_modifier: None,
+            application_modifier: None,
+            application: None,
         }
     }
 }
@@ -848,6 +1067,50 @@ impl Sound {
     }
 }
 
+/// Opens a PulseAudio device if the feature is enabled and `driver` allows it, or else an ALSA
+/// mixer control
+#[cfg_attr(not(feature = "pulseaudio"), allow(unused_variables))]
+pub fn sound_device(
+    driver: SoundDriver,
+    name: Option<String>,
+    device: Option<String>,
+    device_kind: DeviceKind,
+    natural_mapping: bool,
+) -> Result<Box<dyn SoundDevice>> {
+    #[cfg(not(feature = "pulseaudio"))]
+    type PulseAudioSoundDevice = AlsaSoundDevice;
+
+    // try to create a pulseaudio device if feature is enabled and `driver != "alsa"`
+    let pulseaudio_device: Result<PulseAudioSoundDevice> = match driver {
+        #[cfg(feature = "pulseaudio")]
+        SoundDriver::Auto | SoundDriver::PulseAudio => {
+            let sound_device = PulseAudioSoundDevice::new(device_kind);
+
+            match name.as_ref() {
+                None => sound_device,
+                Some(name) => sound_device.map(|device| device.with_name(name.to_string())),
+            }
+        }
+        _ => Err(BlockError(
+            "sound".into(),
+            "PulseAudio feature or driver disabled".into(),
+        )),
+    };
+
+    // prefer PulseAudio if available and selected, fallback to ALSA
+    Ok(match pulseaudio_device {
+        Ok(dev) => Box::new(dev),
+        Err(_) => Box::new(AlsaSoundDevice::new(
+            name.unwrap_or_else(|| match device_kind {
+                DeviceKind::Sink => "Master".into(),
+                DeviceKind::Source => "Capture".into(),
+            }),
+            device.unwrap_or_else(|| "default".into()),
+            natural_mapping,
+        )?),
+    })
+}
+
 impl ConfigBlock for Sound {
     type Config = SoundConfig;
 
@@ -862,35 +1125,50 @@ impl ConfigBlock for Sound {
             step_width = 50;
         }
 
-        #[cfg(not(feature = "pulseaudio"))]
-        type PulseAudioSoundDevice = AlsaSoundDevice;
-
-        // try to create a pulseaudio device if feature is enabled and `driver != "alsa"`
-        let pulseaudio_device: Result<PulseAudioSoundDevice> = match block_config.driver {
+        let device = sound_device(
+            block_config.driver,
+            block_config.name,
+            block_config.device.clone(),
+            block_config.device_kind,
+            block_config.natural_mapping,
+        )?;
+
+        let mut scroll_targets = Vec::new();
+        if let Some(modifier) = block_config.source_modifier {
+            scroll_targets.push(ScrollTarget {
+                key: "source_volume",
+                modifier,
+                device: sound_device(
+                    block_config.driver,
+                    None,
+                    block_config.device,
+                    DeviceKind::Source,
+                    block_config.natural_mapping,
+                )?,
+            });
+        }
+        match (block_config.application_modifier, block_config.application) {
+            (None, None) => {}
             #[cfg(feature = "pulseaudio")]
-            SoundDriver::Auto | SoundDriver::PulseAudio => {
-                let sound_device = PulseAudioSoundDevice::new(block_config.device_kind);
-
-                match block_config.name.as_ref() {
-                    None => sound_device,
-                    Some(name) => sound_device.map(|device| device.with_name(name.to_string())),
-                }
+            (Some(modifier), Some(application)) => scroll_targets.push(ScrollTarget {
+                key: "application_volume",
+                modifier,
+                device: Box::new(PulseAudioApplicationStream::new(application)?),
+            }),
+            #[cfg(not(feature = "pulseaudio"))]
+            (Some(_), Some(_)) => {
+                return Err(ConfigurationError(
+                    "sound".to_string(),
+                    "application needs the pulseaudio feature".to_string(),
+                ))
             }
-            _ => Err(BlockError(
-                "sound".into(),
-                "PulseAudio feature or driver disabled".into(),
-            )),
-        };
-
-        // prefer PulseAudio if available and selected, fallback to ALSA
-        let device: Box<dyn SoundDevice> = match pulseaudio_device {
-            Ok(dev) => Box::new(dev),
-            Err(_) => Box::new(AlsaSoundDevice::new(
-                block_config.name.unwrap_or_else(|| "Master".into()),
-                block_config.device.unwrap_or_else(|| "default".into()),
-                block_config.natural_mapping,
-            )?),
-        };
+            _ => {
+                return Err(ConfigurationError(
+                    "sound".to_string(),
+                    "application_modifier and application need to be set together".to_string(),
+                ))
+            }
+        }
 
         let mut sound = Self {
             id,
@@ -904,6 +1182,7 @@ impl ConfigBlock for Sound {
             mappings: block_config.mappings,
             max_vol: block_config.max_vol,
             scrolling: shared_config.scrolling,
+            scroll_targets,
             text: TextWidget::new(id, 0, shared_config).with_icon("volume_empty")?,
         };
 
@@ -945,11 +1224,18 @@ impl Block for Sound {
             (output_name, output_description)
         };
 
-        let values = map!(
+        let mut values = map!(
             "volume" => Value::from_integer(volume as i64).percents(),
             "output_name" => Value::from_string(output_name),
             "output_description" => Value::from_string(output_description),
         );
+        for target in &mut self.scroll_targets {
+            target.device.get_info()?;
+            values.insert(
+                target.key,
+                Value::from_integer(target.device.volume() as i64).percents(),
+            );
+        }
         let texts = self.format.render(&values)?;
 
         // TODO: Query port names instead? See https://github.com/greshake/i3status-rust/pull/1363#issue-1069904082
@@ -994,14 +1280,18 @@ impl Block for Sound {
                 }
             }
             _ => {
+                let device = match self
+                    .scroll_targets
+                    .iter_mut()
+                    .find(|target| e.has_modifier(&target.modifier))
+                {
+                    Some(target) => &mut target.device,
+                    None => &mut self.device,
+                };
                 use LogicalDirection::*;
                 match self.scrolling.to_logical_direction(e.button) {
-                    Some(Up) => self
-                        .device
-                        .set_volume(self.step_width as i32, self.max_vol)?,
-                    Some(Down) => self
-                        .device
-                        .set_volume(-(self.step_width as i32), self.max_vol)?,
+                    Some(Up) => device.set_volume(self.step_width as i32, self.max_vol)?,
+                    Some(Down) => device.set_volume(-(self.step_width as i32), self.max_vol)?,
                     None => (),
                 }
             }
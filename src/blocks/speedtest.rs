@@ -85,8 +85,9 @@ fn make_thread(
 ) {
     thread::Builder::new()
         .name("speedtest".into())
-        .spawn(move || loop {
-            if recv.recv().is_ok() {
+        // Ends once the block, which holds the sender, is gone
+        .spawn(move || {
+            while recv.recv().is_ok() {
                 if let Ok(output) = get_values() {
                     if let Ok(vals) = parse_values(&output) {
                         if vals.len() == 3 {
@@ -0,0 +1,311 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use chrono::{Datelike, Local};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// NVMe drives count data units of 1000 sectors of 512 bytes
+const NVME_DATA_UNIT: f64 = 512_000.;
+/// SATA attributes whose normalized value is the remaining life in percent
+const SATA_LIFE_ATTRIBUTES: &[u64] = &[177, 231, 233];
+const SATA_LBAS_WRITTEN: u64 = 241;
+
+pub struct SsdWear {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    device: String,
+    tbw: Option<f64>,
+    use_sudo: bool,
+    warning: f64,
+    critical: f64,
+    format: FormatTemplate,
+    state_file: PathBuf,
+    baseline: Option<Baseline>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SsdWearConfig {
+    /// The drive to read the SMART attributes of
+    pub device: String,
+
+    /// The rated endurance of the drive in terabytes written
+    pub tbw: Option<f64>,
+
+    /// Run `smartctl` with `sudo -n`
+    pub use_sudo: bool,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Remaining life (in percent) below which the block is shown as warning
+    pub warning: f64,
+
+    /// Remaining life (in percent) below which the block is shown as critical
+    pub critical: f64,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for SsdWearConfig {
+    fn default() -> Self {
+        Self {
+            device: "/dev/nvme0".to_string(),
+            tbw: None,
+            use_sudo: false,
+            interval: Duration::from_secs(3600),
+            warning: 20.,
+            critical: 10.,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The figures of a drive's SMART report that the block uses
+#[derive(Debug, PartialEq)]
+struct SmartData {
+    /// Bytes written over the lifetime of the drive
+    written: f64,
+    /// Life used up according to the drive itself, in percent
+    used: Option<f64>,
+}
+
+/// Reads the output of `smartctl --json`
+fn parse_smartctl(output: &str) -> Option<SmartData> {
+    let report: serde_json::Value = serde_json::from_str(output).ok()?;
+
+    let nvme = &report["nvme_smart_health_information_log"];
+    if let Some(units) = nvme["data_units_written"].as_f64() {
+        return Some(SmartData {
+            written: units * NVME_DATA_UNIT,
+            used: nvme["percentage_used"].as_f64(),
+        });
+    }
+
+    let attributes = report["ata_smart_attributes"]["table"].as_array()?;
+    let attribute = |id: u64| {
+        attributes
+            .iter()
+            .find(|attribute| attribute["id"].as_u64() == Some(id))
+    };
+    let block_size = report["logical_block_size"].as_f64().unwrap_or(512.);
+    Some(SmartData {
+        written: attribute(SATA_LBAS_WRITTEN)?["raw"]["value"].as_f64()? * block_size,
+        used: SATA_LIFE_ATTRIBUTES
+            .iter()
+            .find_map(|&id| attribute(id)?["value"].as_f64())
+            .map(|life| 100. - life),
+    })
+}
+
+/// Write counters remembered across restarts, to tell how much was written per day
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct Baseline {
+    /// Day (counted from the common era) of the first reading
+    first_day: i32,
+    first_written: f64,
+    /// Day of the first reading today
+    day: i32,
+    day_written: f64,
+}
+
+impl SsdWear {
+    fn read_smart_data(&self) -> Result<SmartData> {
+        let mut command = if self.use_sudo {
+            let mut command = Command::new("sudo");
+            command.arg("-n").arg("smartctl");
+            command
+        } else {
+            Command::new("smartctl")
+        };
+        // The exit code of smartctl is a bit mask that is set for warnings as well, so only the
+        // output tells whether reading the attributes worked
+        let output = command
+            .arg("--json")
+            .arg("--info")
+            .arg("--attributes")
+            .arg(&self.device)
+            .output()
+            .block_error("ssd_wear", "could not run smartctl")?;
+        parse_smartctl(&String::from_utf8_lossy(&output.stdout)).block_error(
+            "ssd_wear",
+            &format!("could not read SMART attributes of {}", self.device),
+        )
+    }
+
+    /// Returns the baseline for today, starting a new one if needed
+    fn update_baseline(&mut self, written: f64) -> Baseline {
+        let today = Local::today().num_days_from_ce();
+        let baseline = match self.baseline {
+            Some(baseline) if baseline.day == today => return baseline,
+            Some(baseline) => Baseline {
+                day: today,
+                day_written: written,
+                ..baseline
+            },
+            None => Baseline {
+                first_day: today,
+                first_written: written,
+                day: today,
+                day_written: written,
+            },
+        };
+        self.baseline = Some(baseline);
+
+        // Failing to save only means that the statistics start over after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&baseline) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+        baseline
+    }
+}
+
+impl ConfigBlock for SsdWear {
+    type Config = SsdWearConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let device_name = block_config
+            .device
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let state_file =
+            xdg_state_home().join(format!("i3status-rust/ssd_wear/{}.toml", device_name));
+        let baseline = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok());
+
+        Ok(SsdWear {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("disk_drive")?,
+            update_interval: block_config.interval,
+            device: block_config.device,
+            tbw: block_config.tbw,
+            use_sudo: block_config.use_sudo,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            format: block_config.format.with_default("{life} {today}")?,
+            state_file,
+            baseline,
+        })
+    }
+}
+
+impl Block for SsdWear {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let data = self.read_smart_data()?;
+        let baseline = self.update_baseline(data.written);
+
+        let days = (baseline.day - baseline.first_day).max(1) as f64;
+        let daily = (data.written - baseline.first_written).max(0.) / days;
+        let tbw = self.tbw.map(|tbw| tbw * 1e12);
+
+        // Whichever of the drive's own estimate and the rated endurance is more pessimistic
+        let used = match (data.used, tbw) {
+            (Some(used), Some(tbw)) => used.max(data.written / tbw * 100.),
+            (Some(used), None) => used,
+            (None, Some(tbw)) => data.written / tbw * 100.,
+            (None, None) => 0.,
+        };
+        let life = (100. - used).max(0.);
+        let days_left = match tbw {
+            Some(tbw) if daily > 0. => ((tbw - data.written).max(0.) / daily) as i64,
+            _ => 0,
+        };
+
+        let values = map!(
+            "written" => Value::from_float(data.written).bytes(),
+            "today" => Value::from_float(data.written - baseline.day_written).bytes(),
+            "daily" => Value::from_float(daily).bytes(),
+            "used" => Value::from_float(used).percents(),
+            "life" => Value::from_float(life).percents(),
+            "days_left" => Value::from_integer(days_left),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if life < self.critical {
+            State::Critical
+        } else if life < self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smartctl() {
+        let nvme = r#"{
+            "device": {"name": "/dev/nvme0", "protocol": "NVMe"},
+            "nvme_smart_health_information_log": {
+                "percentage_used": 3,
+                "data_units_written": 20000000
+            }
+        }"#;
+        assert_eq!(
+            parse_smartctl(nvme),
+            Some(SmartData {
+                written: 10.24e12,
+                used: Some(3.)
+            })
+        );
+
+        let sata = r#"{
+            "logical_block_size": 512,
+            "ata_smart_attributes": {"table": [
+                {"id": 9, "name": "Power_On_Hours", "value": 97, "raw": {"value": 12000}},
+                {"id": 177, "name": "Wear_Leveling_Count", "value": 95, "raw": {"value": 57}},
+                {"id": 241, "name": "Total_LBAs_Written", "value": 99, "raw": {"value": 2000000000}}
+            ]}
+        }"#;
+        assert_eq!(
+            parse_smartctl(sata),
+            Some(SmartData {
+                written: 1.024e12,
+                used: Some(5.)
+            })
+        );
+
+        assert_eq!(parse_smartctl(r#"{"smartctl": {"exit_status": 2}}"#), None);
+        assert_eq!(parse_smartctl(""), None);
+    }
+}
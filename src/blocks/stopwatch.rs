@@ -0,0 +1,148 @@
+//! A stopwatch with laps, e.g. to time meetings
+
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Stopwatch {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    /// Time counted before the stopwatch was last started
+    counted: Duration,
+    running_since: Option<Instant>,
+    /// Elapsed time at which each lap ended
+    laps: Vec<Duration>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StopwatchConfig {
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl ConfigBlock for Stopwatch {
+    type Config = StopwatchConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Stopwatch {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("time")?,
+            format: block_config.format.with_default("{elapsed}")?,
+            counted: Duration::ZERO,
+            running_since: None,
+            laps: Vec::new(),
+        })
+    }
+}
+
+/// Formats a duration as "h:mm:ss"
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl Stopwatch {
+    fn elapsed(&self) -> Duration {
+        self.counted
+            + self
+                .running_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let elapsed = self.elapsed();
+        let lap_start = self.laps.last().copied().unwrap_or_default();
+        let last_lap = match self.laps.as_slice() {
+            [.., before, last] => *last - *before,
+            [last] => *last,
+            [] => Duration::ZERO,
+        };
+        let values = map!(
+            "elapsed" => Value::from_string(format_duration(elapsed)),
+            "lap" => Value::from_string(format_duration(elapsed - lap_start)),
+            "last_lap" => Value::from_string(format_duration(last_lap)),
+            "laps" => Value::from_integer(self.laps.len() as i64),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.running_since {
+            Some(_) => State::Info,
+            None => State::Idle,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Stopwatch {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.set_text()?;
+        Ok(Some(Duration::from_secs(1).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => match self.running_since.take() {
+                Some(since) => self.counted += since.elapsed(),
+                None => self.running_since = Some(Instant::now()),
+            },
+            MouseButton::Middle => {
+                let elapsed = self.elapsed();
+                if elapsed > self.laps.last().copied().unwrap_or_default() {
+                    self.laps.push(elapsed);
+                }
+            }
+            MouseButton::Right => {
+                self.counted = Duration::ZERO;
+                self.running_since = None;
+                self.laps.clear();
+            }
+            _ => return Ok(()),
+        }
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(59)), "0:00:59");
+        assert_eq!(format_duration(Duration::from_secs(3600 + 125)), "1:02:05");
+        assert_eq!(
+            format_duration(Duration::from_millis(26 * 3600 * 1000 + 999)),
+            "26:00:00"
+        );
+    }
+}
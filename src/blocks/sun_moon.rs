@@ -0,0 +1,252 @@
+//! Shows the times of sunrise and sunset and the phase of the moon, computed locally from the
+//! configured coordinates
+
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+/// Length of a synodic month in days
+const SYNODIC_MONTH: f64 = 29.530_588_853;
+
+/// A new moon, at 2000-01-06 18:14 UTC
+const NEW_MOON: i64 = 947_182_440;
+
+/// The sun on a day at some place
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Daylight {
+    Regular {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    /// The sun doesn't set
+    PolarDay,
+    /// The sun doesn't rise
+    PolarNight,
+}
+
+/// Sunrise and sunset on a date, by the sunrise equation as NOAA uses it. It is accurate to
+/// about a minute, which is as good as refraction near the horizon allows.
+fn daylight(date: NaiveDate, latitude: f64, longitude: f64) -> Daylight {
+    let (sin, cos) = (|d: f64| d.to_radians().sin(), |d: f64| d.to_radians().cos());
+
+    // Days since noon of 2000-01-01, the J2000 epoch, at mean solar noon of the longitude
+    let days = (date - NaiveDate::from_ymd(2000, 1, 1)).num_days() as f64 - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * days).rem_euclid(360.0);
+    let center = 1.9148 * sin(anomaly) + 0.02 * sin(2.0 * anomaly) + 0.0003 * sin(3.0 * anomaly);
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = days + 0.0053 * sin(anomaly) - 0.0069 * sin(2.0 * ecliptic_longitude);
+    let declination = (sin(ecliptic_longitude) * sin(23.4397)).asin().to_degrees();
+
+    // The center of the sun is 0.833° below the horizon at sunrise due to refraction and its size
+    let cos_hour_angle =
+        (sin(-0.833) - sin(latitude) * sin(declination)) / (cos(latitude) * cos(declination));
+    if cos_hour_angle > 1.0 {
+        return Daylight::PolarNight;
+    }
+    if cos_hour_angle < -1.0 {
+        return Daylight::PolarDay;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let time = |days: f64| Utc.timestamp(946_728_000 + (days * 86400.0).round() as i64, 0);
+    Daylight::Regular {
+        sunrise: time(transit - hour_angle / 360.0),
+        sunset: time(transit + hour_angle / 360.0),
+    }
+}
+
+/// The age of the moon in days since the last new moon
+fn moon_age(time: DateTime<Utc>) -> f64 {
+    ((time.timestamp() - NEW_MOON) as f64 / 86400.0).rem_euclid(SYNODIC_MONTH)
+}
+
+fn moon_phase(age: f64) -> &'static str {
+    const PHASES: [&str; 8] = [
+        "new moon",
+        "waxing crescent",
+        "first quarter",
+        "waxing gibbous",
+        "full moon",
+        "waning gibbous",
+        "last quarter",
+        "waning crescent",
+    ];
+    PHASES[(age / SYNODIC_MONTH * 8.0).round() as usize % 8]
+}
+
+pub struct SunMoon {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    latitude: f64,
+    longitude: f64,
+    time_format: String,
+    auto_icon: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SunMoonConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+
+    /// strftime format of sunrise and sunset
+    #[serde(default = "SunMoonConfig::default_time_format")]
+    pub time_format: String,
+
+    /// Whether to show a sun at day and a moon at night instead of a fixed icon
+    #[serde(default = "SunMoonConfig::default_auto_icon")]
+    pub auto_icon: bool,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl SunMoonConfig {
+    fn default_time_format() -> String {
+        "%H:%M".to_string()
+    }
+
+    fn default_auto_icon() -> bool {
+        true
+    }
+}
+
+impl ConfigBlock for SunMoon {
+    type Config = SunMoonConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if !(-90.0..=90.0).contains(&block_config.latitude)
+            || !(-180.0..=180.0).contains(&block_config.longitude)
+        {
+            return Err(ConfigurationError(
+                "sun_moon".to_string(),
+                "latitude and longitude must be in degrees".to_string(),
+            ));
+        }
+
+        Ok(SunMoon {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("sun")?,
+            format: block_config.format.with_default("{sunrise} {sunset}")?,
+            latitude: block_config.latitude,
+            longitude: block_config.longitude,
+            time_format: block_config.time_format,
+            auto_icon: block_config.auto_icon,
+        })
+    }
+}
+
+impl Block for SunMoon {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Utc::now();
+        let today = now.with_timezone(&Local).date();
+        let daylight = daylight(today.naive_local(), self.latitude, self.longitude);
+
+        let format_time = |time: DateTime<Utc>| {
+            time.with_timezone(&Local)
+                .format(&self.time_format)
+                .to_string()
+        };
+        let (sunrise, sunset, day_length, is_day) = match daylight {
+            Daylight::Regular { sunrise, sunset } => (
+                format_time(sunrise),
+                format_time(sunset),
+                (sunset - sunrise).num_seconds(),
+                sunrise <= now && now < sunset,
+            ),
+            Daylight::PolarDay => ("-".to_string(), "-".to_string(), 86400, true),
+            Daylight::PolarNight => ("-".to_string(), "-".to_string(), 0, false),
+        };
+        let age = moon_age(now);
+        let illumination = (1.0 - (2.0 * PI * age / SYNODIC_MONTH).cos()) / 2.0;
+
+        let values = map!(
+            "sunrise" => Value::from_string(sunrise),
+            "sunset" => Value::from_string(sunset),
+            "day_length" => Value::from_integer(day_length).seconds(),
+            "moon_phase" => Value::from_string(moon_phase(age).to_string()),
+            "moon_age" => Value::from_integer(age as i64),
+            "moon_illumination" => Value::from_integer((illumination * 100.0).round() as i64).percents(),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        if self.auto_icon {
+            self.text.set_icon(if is_day { "sun" } else { "moon" })?;
+        }
+
+        // Update at the next sunrise or sunset, and at midnight for the times of the next day
+        let tomorrow = today.succ().and_hms(0, 0, 0).with_timezone(&Utc);
+        let next = match daylight {
+            Daylight::Regular { sunrise, sunset } => [sunrise, sunset]
+                .iter()
+                .copied()
+                .filter(|&time| time > now)
+                .chain(std::iter::once(tomorrow))
+                .min()
+                .unwrap_or(tomorrow),
+            _ => tomorrow,
+        };
+        // The illumination of the moon changes by about a percent every two hours
+        let next = (next - now)
+            .to_std()
+            .unwrap_or_default()
+            .min(Duration::from_secs(3600));
+        Ok(Some(Update::Every(next + Duration::from_secs(1))))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daylight() {
+        // Berlin on the summer solstice rises at 02:43 UTC and sets at 19:33 UTC
+        match daylight(NaiveDate::from_ymd(2021, 6, 21), 52.52, 13.405) {
+            Daylight::Regular { sunrise, sunset } => {
+                let minutes = |time: DateTime<Utc>, h, m| {
+                    (time - Utc.ymd(2021, 6, 21).and_hms(h, m, 0))
+                        .num_minutes()
+                        .abs()
+                };
+                assert!(minutes(sunrise, 2, 43) <= 2, "sunrise at {}", sunrise);
+                assert!(minutes(sunset, 19, 33) <= 2, "sunset at {}", sunset);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            daylight(NaiveDate::from_ymd(2021, 6, 21), 78.22, 15.65),
+            Daylight::PolarDay
+        );
+
+        // Full moon on 2021-10-20 14:57 UTC
+        let age = moon_age(Utc.ymd(2021, 10, 20).and_hms(14, 57, 0));
+        assert_eq!(moon_phase(age), "full moon");
+    }
+}
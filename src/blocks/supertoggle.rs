@@ -1,12 +1,11 @@
 use std::collections::HashMap;
-use std::env;
 use std::fmt::Debug;
-use std::process::Command;
+use std::rc::Rc;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
 use regex::Regex;
-use serde::Deserialize;
+use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
@@ -14,8 +13,11 @@ use crate::de::deserialize_opt_duration;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::menu::MenuEntry;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::state_store::StateStore;
+use crate::subprocess::CommandRunner;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -24,6 +26,7 @@ pub struct SuperToggle {
     text: TextWidget,
     command_on: String,
     command_off: String,
+    command_on_exit: Option<String>,
     command_current_state: String,
     format_on: FormatTemplate,
     format_off: FormatTemplate,
@@ -32,6 +35,12 @@ pub struct SuperToggle {
     icon_on: String,
     icon_off: String,
     update_interval: Option<Duration>,
+    menu_command: Option<String>,
+    menu_action: String,
+    /// Whether `command_on` still has to be run because the toggle was on when the bar stopped
+    resume: bool,
+    state_store: Option<Rc<StateStore>>,
+    command_runner: Rc<dyn CommandRunner>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -53,6 +62,10 @@ pub struct SuperToggleConfig {
     // #[serde(default = "SuperToggleConfig::default_command_off")]
     pub command_off: String,
 
+    /// Shell Command to run when the bar is terminated, e.g. to stop time tracking
+    #[serde(default)]
+    pub command_on_exit: Option<String>,
+
     /// Format override
     pub format_on: FormatTemplate,
 
@@ -77,6 +90,19 @@ pub struct SuperToggleConfig {
 
     /// Text to display in i3bar for this block
     pub text: Option<String>,
+
+    /// Shell Command whose output lines are shown in a menu on right click
+    #[serde(default)]
+    pub menu_command: Option<String>,
+
+    /// Shell Command to run with the chosen line in `$ENTRY`
+    #[serde(default)]
+    pub menu_action: Option<String>,
+
+    /// Turn the toggle on again when the bar starts if it was on when the bar stopped, e.g.
+    /// because `command_on_exit` turned it off
+    #[serde(default)]
+    pub resume: bool,
 }
 
 impl SuperToggleConfig {
@@ -98,12 +124,31 @@ impl ConfigBlock for SuperToggle {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let menu_action = match (&block_config.menu_command, block_config.menu_action) {
+            (Some(_), Some(action)) => action,
+            (None, None) => String::new(),
+            _ => {
+                return Err(ConfigurationError(
+                    "toggle".to_owned(),
+                    "menu_command and menu_action must be set together".to_owned(),
+                ))
+            }
+        };
+        let state_store = block_config.resume.then(|| shared_config.state_store());
+        let resume = state_store
+            .as_ref()
+            .and_then(|store| store.get::<bool>(&state_key(id)))
+            .unwrap_or(false);
         Ok(SuperToggle {
             id,
+            resume,
+            state_store,
+            command_runner: shared_config.command_runner.clone(),
             text: TextWidget::new(id, 0, shared_config)
                 .with_text(&block_config.text.unwrap_or_default()),
             command_on: block_config.command_on,
             command_off: block_config.command_off,
+            command_on_exit: block_config.command_on_exit,
             format_on: block_config.format_on.with_default("")?,
             format_off: block_config.format_off.with_default("")?,
             command_current_state: block_config.command_current_state,
@@ -112,15 +157,19 @@ impl ConfigBlock for SuperToggle {
             icon_on: block_config.icon_on,
             icon_off: block_config.icon_off,
             update_interval: block_config.interval,
+            menu_command: block_config.menu_command,
+            menu_action,
         })
     }
 }
 
-fn get_output_of_command(command: &str) -> Result<String> {
-    Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
-        .args(&["-c", command])
-        .output()
-        .map(|o| Ok(String::from_utf8_lossy(&o.stdout).trim().to_owned()))?
+fn state_key(id: usize) -> String {
+    format!("supertoggle/{}", id)
+}
+
+/// Quotes a string for the shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
 }
 
 fn get_mapped_matches_from_string<'a>(
@@ -140,6 +189,12 @@ fn get_mapped_matches_from_string<'a>(
 }
 
 impl SuperToggle {
+    fn get_output_of_command(&self, command: &str) -> Result<String> {
+        self.command_runner
+            .run(command)
+            .map(|output| output.stdout.trim().to_owned())
+    }
+
     fn is_on_status_from_output(&self, output: &str) -> Result<bool> {
         if self.command_status_on_regex.is_match(output) {
             return Ok(true);
@@ -149,6 +204,12 @@ impl SuperToggle {
             return Ok(false);
         }
 
+        log::debug!(
+            "neither {:?} nor {:?} matches {:?}",
+            self.command_status_on_regex.as_str(),
+            self.command_status_off_regex.as_str(),
+            output
+        );
         Err(BlockError(
             "is_on_status".to_owned(),
             "Unable to match either the command_data_on or the command_data_off regex".to_owned(),
@@ -158,7 +219,19 @@ impl SuperToggle {
 
 impl Block for SuperToggle {
     fn update(&mut self) -> Result<Option<Update>> {
-        let output = get_output_of_command(&self.command_current_state)?;
+        let mut output = self.get_output_of_command(&self.command_current_state)?;
+        if self.resume {
+            // Only once, a bar that is killed doesn't get to save that the toggle is off
+            self.resume = false;
+            if let Some(store) = &self.state_store {
+                store.set(&state_key(self.id), &false);
+            }
+            if !self.is_on_status_from_output(&output)? {
+                self.get_output_of_command(&self.command_on)
+                    .block_error("toggle", "Failed to run toggle command")?;
+                output = self.get_output_of_command(&self.command_current_state)?;
+            }
+        }
 
         let on = &self.is_on_status_from_output(&output)?;
         let tags_option = get_mapped_matches_from_string(
@@ -200,8 +273,27 @@ impl Block for SuperToggle {
         vec![&self.text]
     }
 
+    fn menu(&mut self) -> Result<Option<Vec<MenuEntry>>> {
+        let command = match &self.menu_command {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        let output = self.get_output_of_command(command)?;
+        // The entry is split into words like tags where the action uses $ENTRY without quotes
+        Ok(Some(
+            output
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| MenuEntry {
+                    label: line.to_owned(),
+                    command: format!("ENTRY={}; {}", quote(line), self.menu_action),
+                })
+                .collect(),
+        ))
+    }
+
     fn click(&mut self, _e: &I3BarEvent) -> Result<()> {
-        let output = get_output_of_command(&self.command_current_state)?;
+        let output = self.get_output_of_command(&self.command_current_state)?;
         let on = &self.is_on_status_from_output(&output)?;
 
         let cmd = match on {
@@ -209,8 +301,9 @@ impl Block for SuperToggle {
             false => &self.command_on,
         };
 
-        let output =
-            get_output_of_command(cmd).block_error("toggle", "Failed to run toggle command");
+        let output = self
+            .get_output_of_command(cmd)
+            .block_error("toggle", "Failed to run toggle command");
 
         if output.is_ok() {
             self.text.set_state(State::Idle);
@@ -230,7 +323,100 @@ impl Block for SuperToggle {
         Ok(())
     }
 
+    fn stop(&mut self) -> Result<()> {
+        if let Some(store) = &self.state_store {
+            let output = self.get_output_of_command(&self.command_current_state)?;
+            store.set(
+                &state_key(self.id),
+                &self.is_on_status_from_output(&output)?,
+            );
+        }
+        if let Some(command) = &self.command_on_exit {
+            self.get_output_of_command(command)
+                .block_error("toggle", "Failed to run the exit command")?;
+        }
+        Ok(())
+    }
+
     fn id(&self) -> usize {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::i3bar_event::MouseButton;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_toggle() {
+        let runner = Rc::new(MockRunner::default());
+        runner.set("timew", "Tracking focus\n");
+        let config: SuperToggleConfig = toml::from_str(
+            r#"
+            command_current_state = "timew"
+            command_on = "timew start"
+            command_off = "timew stop"
+            format_on = "{tag}"
+            format_off = "off"
+            command_status_on_regex = "Tracking (?P<tag>.*)"
+            command_status_off_regex = "There is no active time tracking"
+            "#,
+        )
+        .unwrap();
+        let mut shared_config = SharedConfig::default();
+        shared_config.command_runner = runner.clone();
+        let mut toggle =
+            SuperToggle::new(0, config, shared_config, crossbeam_channel::unbounded().0).unwrap();
+
+        toggle.update().unwrap();
+        assert!(toggle.text.get_data().full_text.ends_with("focus "));
+
+        toggle
+            .click(&I3BarEvent {
+                id: Some(0),
+                instance: None,
+                button: MouseButton::Left,
+                modifiers: Vec::new(),
+                double_click: false,
+            })
+            .unwrap();
+        assert!(runner.ran.borrow().contains(&"timew stop".to_string()));
+
+        runner.set("timew", "There is no active time tracking.\n");
+        toggle.update().unwrap();
+        assert!(toggle.text.get_data().full_text.ends_with("off "));
+    }
+
+    #[test]
+    fn test_menu() {
+        let runner = Rc::new(MockRunner::default());
+        runner.set("recent", "focus\nmeeting project-x\n");
+        let config: SuperToggleConfig = toml::from_str(
+            r#"
+            command_current_state = "timew"
+            command_on = "timew continue"
+            command_off = "timew stop"
+            format_on = "on"
+            format_off = "off"
+            command_status_on_regex = "Tracking"
+            command_status_off_regex = "There is no active time tracking"
+            menu_command = "recent"
+            menu_action = "timew start $ENTRY"
+            "#,
+        )
+        .unwrap();
+        let mut shared_config = SharedConfig::default();
+        shared_config.command_runner = runner;
+        let mut toggle =
+            SuperToggle::new(0, config, shared_config, crossbeam_channel::unbounded().0).unwrap();
+
+        let entries = toggle.menu().unwrap().unwrap();
+        assert_eq!(entries[1].label, "meeting project-x");
+        assert_eq!(
+            entries[1].command,
+            "ENTRY='meeting project-x'; timew start $ENTRY"
+        );
+    }
+}
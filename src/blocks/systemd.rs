@@ -0,0 +1,253 @@
+//! Shows the state of systemd units and turns critical when one fails. Units are watched over
+//! D-Bus, and a click restarts or stops one through systemd, so that polkit decides who may.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    Properties, PropertiesPropertiesChanged as PPC,
+};
+use dbus::blocking::SyncConnection;
+use dbus::message::SignalArgs;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::buses::{Bus, Watch};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const SYSTEMD: &str = "org.freedesktop.systemd1";
+const MANAGER: &str = "org.freedesktop.systemd1.Manager";
+const UNIT: &str = "org.freedesktop.systemd1.Unit";
+
+struct Unit {
+    name: String,
+    path: dbus::Path<'static>,
+    widget: TextWidget,
+}
+
+pub struct Systemd {
+    id: usize,
+    units: Vec<Unit>,
+    format: FormatTemplate,
+    con: Arc<SyncConnection>,
+    _watches: Vec<Watch>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SystemdConfig {
+    /// Units to watch, ".service" is added to names without a type
+    pub units: Vec<String>,
+
+    /// Watch the units of the user's service manager instead of system units
+    pub user: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+/// The full name of a unit, like "sshd.service" for "sshd"
+fn unit_name(name: &str) -> String {
+    let types = [
+        "service",
+        "socket",
+        "timer",
+        "mount",
+        "automount",
+        "swap",
+        "target",
+        "path",
+        "slice",
+        "scope",
+        "device",
+    ];
+    match name.rsplit_once('.') {
+        Some((_, kind)) if types.contains(&kind) => name.to_string(),
+        _ => format!("{}.service", name),
+    }
+}
+
+/// Updates the block whenever the properties of a unit change
+fn watch(
+    id: usize,
+    shared_config: &SharedConfig,
+    bus: Bus,
+    path: &dbus::Path<'static>,
+    tx_update_request: Sender<Task>,
+) -> Result<Watch> {
+    let rule = PPC::match_rule(Some(&SYSTEMD.into()), Some(path)).static_clone();
+    shared_config.buses.watch(bus, rule, move |_: PPC, _, _| {
+        tx_update_request
+            .send(Task {
+                id,
+                update_time: Instant::now(),
+            })
+            .is_ok()
+    })
+}
+
+impl ConfigBlock for Systemd {
+    type Config = SystemdConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.units.is_empty() {
+            return Err(ConfigurationError(
+                "systemd".to_string(),
+                "units must not be empty".to_string(),
+            ));
+        }
+
+        let bus = if block_config.user {
+            Bus::Session
+        } else {
+            Bus::System
+        };
+        let con = shared_config.buses.get(bus)?;
+        // systemd only sends most signals while a client is subscribed. The connection may be
+        // subscribed already for another block.
+        let manager = con.with_proxy(SYSTEMD, "/org/freedesktop/systemd1", Duration::from_secs(5));
+        if let Err(error) = manager.method_call::<(), _, _, _>(MANAGER, "Subscribe", ()) {
+            log::debug!("failed to subscribe to systemd: {}", error);
+        }
+        let mut units = Vec::with_capacity(block_config.units.len());
+        for (instance, name) in block_config.units.iter().enumerate() {
+            let name = unit_name(name);
+            let manager =
+                con.with_proxy(SYSTEMD, "/org/freedesktop/systemd1", Duration::from_secs(5));
+            // Unlike GetUnit, LoadUnit also finds units that aren't loaded because they're inactive
+            let (path,): (dbus::Path<'static>,) = manager
+                .method_call(MANAGER, "LoadUnit", (name.as_str(),))
+                .block_error("systemd", &format!("failed to load {}", name))?;
+            let mut widget = TextWidget::new(id, instance, shared_config.clone());
+            if instance == 0 {
+                widget = widget.with_icon("cogs")?;
+            }
+            units.push(Unit { name, path, widget });
+        }
+
+        let watches = units
+            .iter()
+            .map(|unit| {
+                watch(
+                    id,
+                    &shared_config,
+                    bus,
+                    &unit.path,
+                    tx_update_request.clone(),
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Systemd {
+            id,
+            units,
+            format: block_config.format.with_default("{unit} {state}")?,
+            con,
+            _watches: watches,
+        })
+    }
+}
+
+impl Block for Systemd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        for unit in &mut self.units {
+            let proxy = self
+                .con
+                .with_proxy(SYSTEMD, &unit.path, Duration::from_secs(5));
+            let load_state: String = proxy.get(UNIT, "LoadState").block_error(
+                "systemd",
+                &format!("failed to get the state of {}", unit.name),
+            )?;
+            let active_state: String = proxy.get(UNIT, "ActiveState").block_error(
+                "systemd",
+                &format!("failed to get the state of {}", unit.name),
+            )?;
+            let sub_state: String = proxy.get(UNIT, "SubState").unwrap_or_default();
+            // A unit that doesn't exist is shown as "not-found" rather than "inactive"
+            let state = if load_state == "loaded" {
+                active_state
+            } else {
+                load_state
+            };
+
+            let short_name = unit.name.trim_end_matches(".service").to_string();
+            let values = map!(
+                "unit" => Value::from_string(short_name),
+                "state" => Value::from_string(state.clone()),
+                "sub_state" => Value::from_string(sub_state),
+            );
+            unit.widget.set_texts(self.format.render(&values)?);
+            unit.widget.set_state(match state.as_str() {
+                "active" => State::Good,
+                "inactive" => State::Idle,
+                "activating" | "deactivating" | "reloading" => State::Info,
+                _ => State::Critical,
+            });
+        }
+        Ok(None)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let method = match event.button {
+            MouseButton::Left => "RestartUnit",
+            MouseButton::Right => "StopUnit",
+            _ => return Ok(()),
+        };
+        let unit = match event.instance.and_then(|instance| self.units.get(instance)) {
+            Some(unit) => unit,
+            None => return Ok(()),
+        };
+        // The job is queued, the unit tells when it's done
+        let manager =
+            self.con
+                .with_proxy(SYSTEMD, "/org/freedesktop/systemd1", Duration::from_secs(5));
+        let _: (dbus::Path<'static>,) = manager
+            .method_call(MANAGER, method, (unit.name.as_str(), "replace"))
+            .block_error(
+                "systemd",
+                &format!(
+                    "failed to {} {}",
+                    method.trim_end_matches("Unit").to_lowercase(),
+                    unit.name
+                ),
+            )?;
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.units
+            .iter()
+            .map(|unit| &unit.widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_name() {
+        assert_eq!(unit_name("sshd"), "sshd.service");
+        assert_eq!(unit_name("backup.timer"), "backup.timer");
+        assert_eq!(unit_name("getty@tty1.service"), "getty@tty1.service");
+        assert_eq!(unit_name("nginx.example"), "nginx.example.service");
+    }
+}
@@ -0,0 +1,248 @@
+//! Shows the state of Tailscale, the exit node in use and the MagicDNS name of this machine. A left
+//! click brings Tailscale up or down and a right click switches to the next configured exit node.
+
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// What `tailscale status --json` tells about this machine
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Status {
+    /// "Running", "Stopped", "NeedsLogin" and so on
+    state: String,
+    hostname: String,
+    ip: String,
+    /// Names under which the exit node in use can be configured
+    exit_node: Option<Vec<String>>,
+}
+
+pub struct Tailscale {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    exit_nodes: Vec<String>,
+    sudo: bool,
+    status: Status,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TailscaleConfig {
+    /// Exit nodes to cycle through on right click, by host name or IP, followed by none
+    pub exit_nodes: Vec<String>,
+
+    /// Run `tailscale` with `sudo`, which has to allow it without a password, for users that
+    /// aren't the operator of Tailscale
+    pub sudo: bool,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for TailscaleConfig {
+    fn default() -> Self {
+        Self {
+            exit_nodes: Vec::new(),
+            sudo: false,
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Tailscale {
+    type Config = TailscaleConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Tailscale {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("net_vpn")?,
+            format: block_config.format.with_default("{state} {exit_node}")?,
+            exit_nodes: block_config.exit_nodes,
+            sudo: block_config.sudo,
+            status: Status::default(),
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+fn parse_status(status: &JsonValue) -> Status {
+    let text = |value: &JsonValue| value.as_str().unwrap_or_default().to_string();
+    let exit_node = status["Peer"]
+        .as_object()
+        .into_iter()
+        .flat_map(|peers| peers.values())
+        .find(|peer| peer["ExitNode"] == true)
+        .map(|peer| {
+            let mut names = vec![text(&peer["HostName"])];
+            // The first label of the MagicDNS name may differ from the host name
+            let dns_name = text(&peer["DNSName"]);
+            names.extend(dns_name.split('.').next().map(str::to_string));
+            let ips = peer["TailscaleIPs"].as_array().into_iter().flatten();
+            names.extend(ips.map(text));
+            names.retain(|name| !name.is_empty());
+            names
+        });
+    Status {
+        state: text(&status["BackendState"]),
+        hostname: text(&status["Self"]["DNSName"])
+            .trim_end_matches('.')
+            .to_string(),
+        ip: text(&status["Self"]["TailscaleIPs"][0]),
+        exit_node,
+    }
+}
+
+impl Tailscale {
+    /// Runs `tailscale` in the background and shows the outcome once it's done
+    fn run(&self, arguments: String) -> Result<()> {
+        let command = format!(
+            "{}tailscale {}",
+            if self.sudo { "sudo -n " } else { "" },
+            arguments
+        );
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        thread::Builder::new()
+            .name("tailscale".into())
+            .spawn(move || {
+                let _ = Command::new("sh").args(["-c", &command]).status();
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("tailscale", "failed to start a thread")?;
+        Ok(())
+    }
+
+    /// The configured exit node after the one in use, or none after the last one
+    fn next_exit_node(&self) -> String {
+        let current = self
+            .status
+            .exit_node
+            .as_ref()
+            .and_then(|names| self.exit_nodes.iter().position(|node| names.contains(node)));
+        // Start with the first one when none or one that isn't configured is in use
+        let next = current.map_or(0, |index| index + 1);
+        self.exit_nodes.get(next).cloned().unwrap_or_default()
+    }
+}
+
+impl Block for Tailscale {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let output = self.command_runner.run("tailscale status --json")?;
+        let json: JsonValue = serde_json::from_str(&output.stdout).block_error(
+            "tailscale",
+            "failed to get the status, is tailscaled running?",
+        )?;
+        self.status = parse_status(&json);
+
+        let values = map!(
+            "state" => Value::from_string(self.status.state.clone()),
+            "hostname" => Value::from_string(self.status.hostname.clone()),
+            "ip" => Value::from_string(self.status.ip.clone()),
+            "exit_node" => Value::from_string(match &self.status.exit_node {
+                Some(names) => names.first().cloned().unwrap_or_default(),
+                None => "-".to_string(),
+            }),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.status.state.as_str() {
+            "Running" => State::Good,
+            "Starting" => State::Info,
+            "NeedsLogin" | "NeedsMachineAuth" => State::Warning,
+            _ => State::Idle,
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left if self.status.state == "Running" => self.run("down".to_string()),
+            MouseButton::Left => self.run("up".to_string()),
+            MouseButton::Right if !self.exit_nodes.is_empty() => {
+                self.run(format!("set --exit-node='{}'", self.next_exit_node()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = serde_json::json!({
+            "BackendState": "Running",
+            "Self": {
+                "HostName": "laptop",
+                "DNSName": "laptop.tail1234.ts.net.",
+                "TailscaleIPs": ["100.101.102.103", "fd7a:115c:a1e0::1"]
+            },
+            "Peer": {
+                "nodekey:1": {"HostName": "nas", "DNSName": "nas.tail1234.ts.net.", "ExitNode": false},
+                "nodekey:2": {
+                    "HostName": "vps-fra",
+                    "DNSName": "fra.tail1234.ts.net.",
+                    "TailscaleIPs": ["100.64.0.7"],
+                    "ExitNode": true
+                }
+            }
+        });
+        assert_eq!(
+            parse_status(&status),
+            Status {
+                state: "Running".to_string(),
+                hostname: "laptop.tail1234.ts.net".to_string(),
+                ip: "100.101.102.103".to_string(),
+                exit_node: Some(vec![
+                    "vps-fra".to_string(),
+                    "fra".to_string(),
+                    "100.64.0.7".to_string()
+                ]),
+            }
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::process::Command;
+use std::rc::Rc;
 use std::time::Duration;
 
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
@@ -12,6 +13,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -26,6 +28,8 @@ pub struct Taskwarrior {
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_everything_done: FormatTemplate,
+    due_format: String,
+    command_runner: Rc<dyn CommandRunner>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -80,6 +84,9 @@ pub struct TaskwarriorConfig {
 
     /// Format override if the count is zero
     pub format_everything_done: FormatTemplate,
+
+    /// strftime format of the `next_due` placeholder
+    pub due_format: String,
 }
 
 impl Default for TaskwarriorConfig {
@@ -96,6 +103,7 @@ impl Default for TaskwarriorConfig {
             format: FormatTemplate::default(),
             format_singular: FormatTemplate::default(),
             format_everything_done: FormatTemplate::default(),
+            due_format: "%a %d %b %H:%M".to_string(),
         }
     }
 }
@@ -109,6 +117,7 @@ impl ConfigBlock for Taskwarrior {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let command_runner = shared_config.command_runner.clone();
         let output = TextWidget::new(id, 0, shared_config)
             .with_icon("tasks")?
             .with_text("-");
@@ -133,62 +142,99 @@ impl ConfigBlock for Taskwarrior {
             format_everything_done: block_config
                 .format_everything_done
                 .with_default("{count}")?,
+            due_format: block_config.due_format,
             filter_index: 0,
             filters,
             output,
+            command_runner,
         })
     }
 }
 
-fn has_taskwarrior() -> Result<bool> {
-    Ok(String::from_utf8(
-        Command::new("sh")
-            .args(&["-c", "type -P task"])
-            .output()
-            .block_error(
-                "taskwarrior",
-                "failed to start command to check for taskwarrior",
-            )?
-            .stdout,
-    )
-    .block_error("taskwarrior", "failed to check for taskwarrior")?
-    .trim()
-        != "")
+/// The fields of `task export` that the block uses
+#[derive(Deserialize, Debug)]
+struct ExportedTask {
+    description: String,
+    due: Option<String>,
+    #[serde(default)]
+    urgency: f64,
+}
+
+/// What the block shows about the tasks matching a filter
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    count: usize,
+    due: usize,
+    overdue: usize,
+    next_due: Option<DateTime<Utc>>,
+    urgent: Option<String>,
+}
+
+fn summarize(tasks: &[ExportedTask], now: DateTime<Utc>) -> Summary {
+    // Taskwarrior exports dates in UTC, e.g. 20211231T230000Z
+    let due_dates: Vec<DateTime<Utc>> = tasks
+        .iter()
+        .filter_map(|task| task.due.as_ref())
+        .filter_map(|due| NaiveDateTime::parse_from_str(due, "%Y%m%dT%H%M%SZ").ok())
+        .map(|due| DateTime::from_utc(due, Utc))
+        .collect();
+    Summary {
+        count: tasks.len(),
+        due: due_dates.len(),
+        overdue: due_dates.iter().filter(|&&due| due < now).count(),
+        next_due: due_dates.iter().filter(|&&due| due >= now).min().cloned(),
+        urgent: tasks
+            .iter()
+            .max_by(|a, b| {
+                a.urgency
+                    .partial_cmp(&b.urgency)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|task| task.description.clone()),
+    }
 }
 
-fn get_number_of_tasks(filter: &str) -> Result<u32> {
-    String::from_utf8(
-        Command::new("sh")
-            .args(&["-c", &format!("task rc.gc=off {} count", filter)])
-            .output()
+impl Taskwarrior {
+    fn has_taskwarrior(&self) -> Result<bool> {
+        Ok(self.command_runner.run("command -v task")?.status == Some(0))
+    }
+
+    fn get_tasks(&self, filter: &str) -> Result<Vec<ExportedTask>> {
+        let output = self
+            .command_runner
+            .run(&format!(
+                "task rc.gc=off rc.verbose=nothing rc.json.array=on {} export",
+                filter
+            ))
             .block_error(
                 "taskwarrior",
-                "failed to run taskwarrior for getting the number of tasks",
-            )?
-            .stdout,
-    )
-    .block_error(
-        "taskwarrior",
-        "failed to get the number of tasks from taskwarrior",
-    )?
-    .trim()
-    .parse::<u32>()
-    .block_error("taskwarrior", "could not parse the result of taskwarrior")
+                "failed to run taskwarrior for getting the tasks",
+            )?;
+        serde_json::from_str(&output.stdout)
+            .block_error("taskwarrior", "could not parse the result of taskwarrior")
+    }
 }
 
 impl Block for Taskwarrior {
     fn update(&mut self) -> Result<Option<Update>> {
-        if !has_taskwarrior()? {
+        if !self.has_taskwarrior()? {
             self.output.set_text("?".to_string())
         } else {
             let filter = self.filters.get(self.filter_index).block_error(
                 "taskwarrior",
                 &format!("Filter at index {} does not exist", self.filter_index),
             )?;
-            let number_of_tasks = get_number_of_tasks(&filter.filter)?;
+            let summary = summarize(&self.get_tasks(&filter.filter)?, Utc::now());
+            let number_of_tasks = summary.count as u32;
             let values = map!(
                 "count" => Value::from_integer(number_of_tasks as i64),
                 "filter_name" => Value::from_string(filter.name.clone()),
+                "due" => Value::from_integer(summary.due as i64),
+                "overdue" => Value::from_integer(summary.overdue as i64),
+                "next_due" => Value::from_string(summary.next_due.map_or_else(String::new, |due| {
+                    due.with_timezone(&Local).format(&self.due_format).to_string()
+                })),
+                "urgent" => Value::from_string(summary.urgent.unwrap_or_default()),
             );
             self.output.set_texts(match number_of_tasks {
                 0 => self.format_everything_done.render(&values)?,
@@ -232,3 +278,35 @@ impl Block for Taskwarrior {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let tasks: Vec<ExportedTask> = serde_json::from_str(
+            r#"[
+                {"id":1,"description":"pay rent","due":"20210601T000000Z","urgency":9.2},
+                {"id":2,"description":"water plants","due":"20210603T120000Z","urgency":3.1},
+                {"id":3,"description":"read book","urgency":0.8},
+                {"id":4,"description":"file taxes","due":"20210610T000000Z","urgency":5}
+            ]"#,
+        )
+        .unwrap();
+        let now = DateTime::from_utc(
+            NaiveDateTime::parse_from_str("20210602T000000Z", "%Y%m%dT%H%M%SZ").unwrap(),
+            Utc,
+        );
+        let summary = summarize(&tasks, now);
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.due, 3);
+        assert_eq!(summary.overdue, 1);
+        assert_eq!(
+            summary.next_due.unwrap().format("%d %H:%M").to_string(),
+            "03 12:00"
+        );
+        assert_eq!(summary.urgent.as_deref(), Some("pay rent"));
+        assert_eq!(summarize(&[], now), Summary::default());
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 use std::time::Duration;
 
@@ -7,6 +8,7 @@ use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
@@ -14,6 +16,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::util::{wildcard_match, Hysteresis};
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing, State};
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -42,17 +45,63 @@ impl Default for TemperatureDriver {
     }
 }
 
+/// The kinds of devices that sensors are grouped by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum DeviceClass {
+    Cpu,
+    Gpu,
+    Nvme,
+    Other,
+}
+
+impl DeviceClass {
+    /// The class of a chip by its hwmon, lm_sensors or sysctl name, like "k10temp-pci-00c3"
+    fn of(chip: &str) -> Self {
+        let chip = chip.to_lowercase();
+        let any = |names: &[&str]| names.iter().any(|name| chip.contains(name));
+        if any(&[
+            "coretemp",
+            "k10temp",
+            "k8temp",
+            "zenpower",
+            "x86_pkg_temp",
+            "cpu",
+        ]) {
+            DeviceClass::Cpu
+        } else if any(&["amdgpu", "radeon", "nouveau", "nvidia", "i915", "gpu"]) {
+            DeviceClass::Gpu
+        } else if chip.contains("nvme") {
+            DeviceClass::Nvme
+        } else {
+            DeviceClass::Other
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DeviceClass::Cpu => "CPU",
+            DeviceClass::Gpu => "GPU",
+            DeviceClass::Nvme => "NVMe",
+            DeviceClass::Other => "Other",
+        }
+    }
+}
+
 pub struct Temperature {
     id: usize,
     text: TextWidget,
     output: (String, Option<String>),
+    /// The hottest group, which is shown while collapsed if `grouped`
+    hottest: (String, Option<String>),
     collapsed: bool,
+    grouped: bool,
     update_interval: Duration,
     scale: TemperatureScale,
     maximum_good: f64,
     maximum_idle: f64,
     maximum_info: f64,
     maximum_warning: f64,
+    hysteresis: Hysteresis,
     format: FormatTemplate,
     driver: TemperatureDriver,
     chip: Option<String>,
@@ -70,6 +119,9 @@ pub struct TemperatureConfig {
     /// Collapsed by default?
     pub collapsed: bool,
 
+    /// Group the sensors by the class of their device, like CPU or GPU
+    pub grouped: bool,
+
     /// The temperature scale to use for display and thresholds
     #[serde(default)]
     pub scale: TemperatureScale,
@@ -90,6 +142,9 @@ pub struct TemperatureConfig {
     #[serde(default)]
     pub warning: Option<f64>,
 
+    /// Margin by which the temperature has to drop below a threshold before the state is lowered
+    pub hysteresis: f64,
+
     /// Format override
     pub format: FormatTemplate,
 
@@ -109,11 +164,13 @@ impl Default for TemperatureConfig {
             format: FormatTemplate::default(),
             interval: Duration::from_secs(5),
             collapsed: true,
+            grouped: false,
             scale: TemperatureScale::default(),
             good: None,
             idle: None,
             info: None,
             warning: None,
+            hysteresis: 0.,
             driver: TemperatureDriver::default(),
             chip: None,
             inputs: None,
@@ -135,13 +192,15 @@ impl ConfigBlock for Temperature {
             update_interval: block_config.interval,
             text: TextWidget::new(id, 0, shared_config)
                 .with_icon("thermometer")?
-                .with_spacing(if block_config.collapsed {
+                .with_spacing(if block_config.collapsed && !block_config.grouped {
                     Spacing::Hidden
                 } else {
                     Spacing::Normal
                 }),
             output: (String::new(), None),
+            hottest: (String::new(), None),
             collapsed: block_config.collapsed,
+            grouped: block_config.grouped,
             scale: block_config.scale,
             maximum_good: block_config
                 .good
@@ -167,9 +226,12 @@ impl ConfigBlock for Temperature {
                     TemperatureScale::Celsius => 80f64,
                     TemperatureScale::Fahrenheit => 176f64,
                 }),
-            format: block_config
-                .format
-                .with_default("{average} avg, {max} max")?,
+            hysteresis: Hysteresis::new(block_config.hysteresis),
+            format: block_config.format.with_default(if block_config.grouped {
+                "{group} {max}"
+            } else {
+                "{average} avg, {max} max"
+            })?,
             driver: block_config.driver,
             chip: block_config.chip,
             inputs: block_config.inputs,
@@ -195,14 +257,142 @@ impl ConfigBlock for Temperature {
     }
 }
 
+/// Whether an input is one of `inputs`, which may contain `*` and `?` as wildcards
+fn selected(inputs: &[String], input: &str) -> bool {
+    inputs.iter().any(|pattern| wildcard_match(pattern, input))
+}
+
+/// Whether the hwmon chip `name` is the chip that the user chose
+fn chip_matches(chip: &str, name: &str) -> bool {
+    if chip.contains(['*', '?']) {
+        wildcard_match(chip, name)
+    } else {
+        // Without wildcards, hwmon names that are substrings of the given chip name or vice versa
+        // match, so that lm_sensors chip names like "coretemp-isa-0000" work too
+        chip.contains(name) || name.contains(chip)
+    }
+}
+
+/// The temperatures of the hwmon chips in `root`, in °C, along with the names of their chips,
+/// narrowed to a chip and to inputs by their labels, or by names like "temp1" for inputs without a
+/// label
+fn hwmon_temperatures(
+    root: &Path,
+    chip: Option<&str>,
+    inputs: Option<&[String]>,
+) -> Result<Vec<(String, f64)>> {
+    let mut temperatures = Vec::new();
+    for hwmon_dir in fs::read_dir(root)? {
+        let hwmon = &hwmon_dir?.path();
+        let name = fs::read_to_string(hwmon.join("name"))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_default();
+        if let Some(chip) = chip {
+            if !chip_matches(chip, &name) {
+                continue;
+            }
+        }
+        for entry in hwmon.read_dir()? {
+            let file_name = entry?.file_name();
+            let input = match file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix("_input"))
+            {
+                Some(input) if input.starts_with("temp") => input,
+                _ => continue,
+            };
+            if let Some(inputs) = inputs {
+                let label = fs::read_to_string(hwmon.join(format!("{}_label", input)))
+                    .map_or_else(|_| input.to_string(), |label| label.trim().to_string());
+                if !selected(inputs, &label) {
+                    continue;
+                }
+            }
+            // Sensors that are gone or broken fail to read, which shouldn't hide the others
+            let value = match fs::read_to_string(hwmon.join(&file_name))
+                .ok()
+                .and_then(|value| value.trim().parse::<f64>().ok())
+            {
+                Some(value) => value / 1000f64,
+                None => continue,
+            };
+
+            if value > -101f64 && value < 151f64 {
+                temperatures.push((name.clone(), value));
+            } else {
+                // This error is recoverable and therefore should not stop the program
+                log::warn!("Temperature ({}) outside of range ([-100, 150])", value);
+            }
+        }
+    }
+    Ok(temperatures)
+}
+
+/// The placeholders of some temperatures, which mustn't be empty
+fn temperature_values(temperatures: &[f64]) -> HashMap<&'static str, Value> {
+    let max = temperatures.iter().cloned().fold(f64::MIN, f64::max);
+    let min = temperatures.iter().cloned().fold(f64::MAX, f64::min);
+    let avg = temperatures.iter().sum::<f64>() / temperatures.len() as f64;
+    map!(
+        "average" => Value::from_float(avg).degrees(),
+        "min" => Value::from_float(min).degrees(),
+        "max" => Value::from_float(max).degrees()
+    )
+}
+
+/// The temperatures of each class of devices, hottest first
+fn group_temperatures(temperatures: &[(String, f64)]) -> Vec<(DeviceClass, Vec<f64>)> {
+    let mut groups: Vec<(DeviceClass, Vec<f64>)> = Vec::new();
+    for (chip, value) in temperatures {
+        let class = DeviceClass::of(chip);
+        match groups.iter_mut().find(|(group, _)| *group == class) {
+            Some((_, values)) => values.push(*value),
+            None => groups.push((class, vec![*value])),
+        }
+    }
+    let hottest = |values: &Vec<f64>| values.iter().cloned().fold(f64::MIN, f64::max);
+    groups.sort_by(|(a, a_values), (b, b_values)| {
+        hottest(b_values)
+            .partial_cmp(&hottest(a_values))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.cmp(b))
+    });
+    groups
+}
+
+impl Temperature {
+    /// Shows the output as far as the block is expanded
+    fn show(&mut self) {
+        match (self.collapsed, self.grouped) {
+            (true, false) => {
+                self.text.set_text(String::new());
+                self.text.set_spacing(Spacing::Hidden);
+            }
+            (true, true) => {
+                self.text.set_texts(self.hottest.clone());
+                self.text.set_spacing(Spacing::Normal);
+            }
+            (false, _) => {
+                self.text.set_texts(self.output.clone());
+                self.text.set_spacing(Spacing::Normal);
+            }
+        }
+    }
+}
+
 type SensorsOutput = HashMap<String, HashMap<String, serde_json::Value>>;
 type InputReadings = HashMap<String, f64>;
 
 impl Block for Temperature {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut temperatures: Vec<f64> = Vec::new();
+        // Along with the names of their chips
+        let mut temperatures: Vec<(String, f64)> = Vec::new();
 
         match self.driver {
+            // Neither lm_sensors nor hwmon exist on the BSDs, which have sysctl for both drivers
+            _ if bsd::bsd().is_some() => {
+                temperatures = bsd::temperatures()?;
+            }
             TemperatureDriver::Sensors => {
                 let mut args = if self.fallback_required {
                     vec!["-u"]
@@ -220,7 +410,12 @@ impl Block for Temperature {
                     .unwrap_or_else(|e| e.to_string());
 
                 if self.fallback_required {
+                    let mut chip = "";
                     for line in output.lines() {
+                        // Chips start with their name on a line of its own
+                        if !line.is_empty() && !line.starts_with(' ') && !line.contains(':') {
+                            chip = line;
+                        }
                         if let Some(rest) = line.strip_prefix("  temp") {
                             let rest = rest
                                 .split('_')
@@ -232,12 +427,12 @@ impl Block for Temperature {
                                 match rest[2].parse::<f64>() {
                                     Ok(t) if t == 0f64 => Ok(()),
                                     Ok(t) if t > -101f64 && t < 151f64 => {
-                                        temperatures.push(t);
+                                        temperatures.push((chip.to_string(), t));
                                         Ok(())
                                     }
                                     Ok(t) => {
                                         // This error is recoverable and therefore should not stop the program
-                                        eprintln!(
+                                        log::warn!(
                                             "Temperature ({}) outside of range ([-100, 150])",
                                             t
                                         );
@@ -254,10 +449,10 @@ impl Block for Temperature {
                 } else {
                     let parsed: SensorsOutput = serde_json::from_str(&output)
                         .block_error("temperature", "sensors output is invalid")?;
-                    for (_chip, inputs) in parsed {
+                    for (chip, inputs) in parsed {
                         for (input_name, input_values) in inputs {
-                            if let Some(ref whitelist) = self.inputs {
-                                if !whitelist.contains(&input_name) {
+                            if let Some(ref inputs) = self.inputs {
+                                if !selected(inputs, &input_name) {
                                     continue;
                                 }
                             }
@@ -275,10 +470,10 @@ impl Block for Temperature {
                                 }
 
                                 if value > -101f64 && value < 151f64 {
-                                    temperatures.push(value);
+                                    temperatures.push((chip.clone(), value));
                                 } else {
                                     // This error is recoverable and therefore should not stop the program
-                                    eprintln!(
+                                    log::warn!(
                                         "Temperature ({}) outside of range ([-100, 150])",
                                         value
                                     );
@@ -289,92 +484,67 @@ impl Block for Temperature {
                 }
             }
             TemperatureDriver::Sysfs => {
-                for hwmon_dir in fs::read_dir("/sys/class/hwmon")? {
-                    let hwmon = &hwmon_dir?.path();
-                    if let Some(ref chip_name) = self.chip {
-                        // Narrow to hwmon names that are substrings of the given chip name or vice versa
-                        let hwmon_untrimmed = fs::read_to_string(hwmon.join("name"))?;
-                        let hwmon_name = hwmon_untrimmed.trim();
-                        if !(chip_name.contains(hwmon_name) || hwmon_name.contains(chip_name)) {
-                            continue;
-                        }
-                    }
-                    for entry in hwmon.read_dir()? {
-                        let entry = entry?;
-                        if let Ok(name) = entry.file_name().into_string() {
-                            if name.starts_with("temp") && name.ends_with("label") {
-                                if let Some(ref whitelist) = self.inputs {
-                                    //narrow to labels that are an exact match for one of the inputs
-                                    if !whitelist.contains(
-                                        &fs::read_to_string(entry.path())?.trim().to_string(),
-                                    ) {
-                                        continue;
-                                    }
-                                }
-                                let value: f64 =
-                                    fs::read_to_string(hwmon.join(name.replace("label", "input")))?
-                                        .trim()
-                                        .parse::<f64>()
-                                        .block_error(
-                                            "temperature",
-                                            "failed to parse temperature as an integer",
-                                        )?
-                                        / 1000f64;
-
-                                if value > -101f64 && value < 151f64 {
-                                    temperatures.push(value);
-                                } else {
-                                    // This error is recoverable and therefore should not stop the program
-                                    eprintln!(
-                                        "Temperature ({}) outside of range ([-100, 150])",
-                                        value
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
+                temperatures = hwmon_temperatures(
+                    Path::new("/sys/class/hwmon"),
+                    self.chip.as_deref(),
+                    self.inputs.as_deref(),
+                )?;
             }
         }
 
         if let TemperatureScale::Fahrenheit = self.scale {
             temperatures
                 .iter_mut()
-                .for_each(|c| *c = *c * 9f64 / 5f64 + 32f64);
+                .for_each(|(_, c)| *c = *c * 9f64 / 5f64 + 32f64);
         }
 
         if !temperatures.is_empty() {
             let max: f64 = temperatures
                 .iter()
-                .cloned()
+                .map(|(_, value)| *value)
                 .reduce(f64::max)
                 .block_error("temperature", "failed to get max temperature")?;
-            let min: f64 = temperatures
-                .iter()
-                .cloned()
-                .reduce(f64::min)
-                .block_error("temperature", "failed to get min temperature")?;
-            let avg: f64 = temperatures.iter().sum::<f64>() / temperatures.len() as f64;
-
-            let values = map!(
-                "average" => Value::from_float(avg).degrees(),
-                "min" => Value::from_float(min).degrees(),
-                "max" => Value::from_float(max).degrees()
-            );
 
-            self.output = self.format.render(&values)?;
-            if !self.collapsed {
-                self.text.set_texts(self.output.clone());
+            if self.grouped {
+                let mut texts = Vec::new();
+                for (class, values) in group_temperatures(&temperatures) {
+                    let mut values = temperature_values(&values);
+                    values.insert("group", Value::from_string(class.name().to_string()));
+                    texts.push(self.format.render(&values)?);
+                }
+                self.hottest = texts[0].clone();
+                let short = texts
+                    .iter()
+                    .map(|(full, short)| short.as_ref().unwrap_or(full).clone())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let full = texts
+                    .into_iter()
+                    .map(|(full, _)| full)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.output = (full, Some(short));
+            } else {
+                let values: Vec<f64> = temperatures.iter().map(|(_, value)| *value).collect();
+                self.output = self.format.render(&temperature_values(&values))?;
             }
+            self.show();
 
-            let state = match max {
-                m if m <= self.maximum_good => State::Good,
-                m if m <= self.maximum_idle => State::Idle,
-                m if m <= self.maximum_info => State::Info,
-                m if m <= self.maximum_warning => State::Warning,
+            let (good, idle, info, warning) = (
+                self.maximum_good,
+                self.maximum_idle,
+                self.maximum_info,
+                self.maximum_warning,
+            );
+            let state = self.hysteresis.apply(max, |max| match max {
+                m if m <= good => State::Good,
+                m if m <= idle => State::Idle,
+                m if m <= info => State::Info,
+                m if m <= warning => State::Warning,
                 _ => State::Critical,
-            };
+            });
 
+            self.text.set_gradient_value(max);
             self.text.set_state(state);
         }
 
@@ -388,13 +558,7 @@ impl Block for Temperature {
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if e.button == MouseButton::Left {
             self.collapsed = !self.collapsed;
-            if self.collapsed {
-                self.text.set_text(String::new());
-                self.text.set_spacing(Spacing::Hidden);
-            } else {
-                self.text.set_texts(self.output.clone());
-                self.text.set_spacing(Spacing::Normal);
-            }
+            self.show();
         }
 
         Ok(())
@@ -404,3 +568,73 @@ impl Block for Temperature {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_hwmon_temperatures() {
+        let root = TempDir::new().unwrap();
+        root.child("hwmon0/name").write_str("acpitz\n").unwrap();
+        root.child("hwmon0/temp1_input")
+            .write_str("27800\n")
+            .unwrap();
+        root.child("hwmon1/name").write_str("coretemp\n").unwrap();
+        root.child("hwmon1/temp1_input")
+            .write_str("52000\n")
+            .unwrap();
+        root.child("hwmon1/temp1_label")
+            .write_str("Package id 0\n")
+            .unwrap();
+        root.child("hwmon1/temp2_input")
+            .write_str("48000\n")
+            .unwrap();
+        root.child("hwmon1/temp2_label")
+            .write_str("Core 0\n")
+            .unwrap();
+
+        let values = |temperatures: Vec<(String, f64)>| {
+            let mut values: Vec<f64> = temperatures.into_iter().map(|(_, value)| value).collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values
+        };
+        let all = hwmon_temperatures(root.path(), None, None).unwrap();
+        assert_eq!(values(all), vec![27.8, 48.0, 52.0]);
+        let cores =
+            hwmon_temperatures(root.path(), Some("core*"), Some(&["Core *".to_string()])).unwrap();
+        assert_eq!(cores, vec![("coretemp".to_string(), 48.0)]);
+        let unlabeled = hwmon_temperatures(
+            root.path(),
+            Some("acpitz-acpi-0"),
+            Some(&["temp1".to_string()]),
+        )
+        .unwrap();
+        assert_eq!(unlabeled, vec![("acpitz".to_string(), 27.8)]);
+    }
+
+    #[test]
+    fn test_group_temperatures() {
+        let temperatures = [
+            ("k10temp-pci-00c3", 55.0),
+            ("nvme-pci-0100", 41.0),
+            ("amdgpu-pci-0300", 68.0),
+            ("k10temp-pci-00c3", 61.0),
+            ("acpitz-acpi-0", 30.0),
+        ]
+        .iter()
+        .map(|(chip, value)| (chip.to_string(), *value))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            group_temperatures(&temperatures),
+            vec![
+                (DeviceClass::Gpu, vec![68.0]),
+                (DeviceClass::Cpu, vec![55.0, 61.0]),
+                (DeviceClass::Nvme, vec![41.0]),
+                (DeviceClass::Other, vec![30.0]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Shows the price of cryptocurrencies from CoinGecko or of stocks from Yahoo Finance, one symbol
+//! at a time cycling on scroll
+
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum QuoteSource {
+    /// Symbols are IDs of coins, like "bitcoin"
+    CoinGecko {
+        #[serde(default = "QuoteSource::getenv_coingecko_api_key")]
+        api_key: Option<String>,
+        #[serde(default = "QuoteSource::default_currency")]
+        currency: String,
+    },
+    /// Symbols are tickers, like "AAPL", "SAP.DE" or "EURUSD=X"
+    Yahoo,
+}
+
+impl QuoteSource {
+    fn getenv_coingecko_api_key() -> Option<String> {
+        env::var("COINGECKO_API_KEY").ok()
+    }
+
+    fn default_currency() -> String {
+        "usd".to_string()
+    }
+
+    fn quotes(&self, symbols: &[String]) -> Result<Vec<Quote>> {
+        match self {
+            QuoteSource::CoinGecko { api_key, currency } => {
+                let currency = currency.to_lowercase();
+                let url = format!(
+                    "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={}&include_24hr_change=true",
+                    symbols.join(","),
+                    currency
+                );
+                let mut headers = Vec::new();
+                if let Some(api_key) = api_key {
+                    headers.push(("x-cg-demo-api-key", api_key.as_str()));
+                }
+                let prices = get(&url, headers)?;
+                symbols
+                    .iter()
+                    .map(|symbol| parse_coingecko(&prices, symbol, &currency))
+                    .collect()
+            }
+            // The chart API is the only one of Yahoo that works without a key, and it only knows
+            // one symbol per request
+            QuoteSource::Yahoo => symbols
+                .iter()
+                .map(|symbol| {
+                    let url = format!(
+                        "https://query1.finance.yahoo.com/v8/finance/chart/{}?range=1d&interval=1d",
+                        symbol
+                    );
+                    parse_yahoo(&get(&url, vec![("User-Agent", "Mozilla/5.0")])?, symbol)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Quote {
+    symbol: String,
+    price: f64,
+    currency: String,
+    /// Change in percent over the last 24 hours, or since the last close for stocks
+    change: f64,
+}
+
+fn get(url: &str, headers: Vec<(&str, &str)>) -> Result<JsonValue> {
+    let response = http::http_get_json(url, Some(Duration::from_secs(10)), headers)?;
+    if response.code != 200 {
+        return Err(BlockError(
+            "ticker".to_string(),
+            format!("the quote source answered with HTTP {}", response.code),
+        ));
+    }
+    Ok(response.content)
+}
+
+fn parse_coingecko(prices: &JsonValue, symbol: &str, currency: &str) -> Result<Quote> {
+    let coin = &prices[symbol];
+    Ok(Quote {
+        symbol: symbol.to_string(),
+        price: coin[currency]
+            .as_f64()
+            .block_error("ticker", &format!("no price for {}", symbol))?,
+        currency: currency.to_uppercase(),
+        change: coin[format!("{}_24h_change", currency)]
+            .as_f64()
+            .unwrap_or_default(),
+    })
+}
+
+fn parse_yahoo(chart: &JsonValue, symbol: &str) -> Result<Quote> {
+    let meta = &chart["chart"]["result"][0]["meta"];
+    let price = meta["regularMarketPrice"]
+        .as_f64()
+        .block_error("ticker", &format!("no price for {}", symbol))?;
+    let previous = meta["chartPreviousClose"]
+        .as_f64()
+        .or_else(|| meta["previousClose"].as_f64());
+    Ok(Quote {
+        symbol: symbol.to_string(),
+        price,
+        currency: meta["currency"].as_str().unwrap_or_default().to_string(),
+        change: previous
+            .filter(|&previous| previous != 0.0)
+            .map_or(0.0, |previous| (price / previous - 1.0) * 100.0),
+    })
+}
+
+/// A price with two decimals, or four significant digits for prices below one
+fn format_price(price: f64) -> String {
+    if price >= 1.0 {
+        format!("{:.2}", price)
+    } else {
+        let decimals = 3 - price.log10().floor() as i32;
+        format!("{:.*}", decimals.clamp(2, 10) as usize, price)
+    }
+}
+
+pub struct Ticker {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    source: QuoteSource,
+    symbols: Vec<String>,
+    quotes: Vec<Quote>,
+    /// The symbol shown
+    current: usize,
+    warning: f64,
+    critical: f64,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TickerConfig {
+    pub source: QuoteSource,
+
+    pub symbols: Vec<String>,
+
+    /// Percentage the price has to move from which on the block is shown as warning
+    #[serde(default = "TickerConfig::default_warning")]
+    pub warning: f64,
+
+    /// Percentage the price has to move from which on the block is shown as critical
+    #[serde(default = "TickerConfig::default_critical")]
+    pub critical: f64,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "TickerConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl TickerConfig {
+    fn default_warning() -> f64 {
+        5.0
+    }
+
+    fn default_critical() -> f64 {
+        10.0
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+}
+
+impl ConfigBlock for Ticker {
+    type Config = TickerConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.symbols.is_empty() {
+            return Err(ConfigurationError(
+                "ticker".to_string(),
+                "symbols must be set".to_string(),
+            ));
+        }
+
+        Ok(Ticker {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config
+                .format
+                .with_default("{symbol} {price} {change_24h}")?,
+            source: block_config.source,
+            symbols: block_config.symbols,
+            quotes: Vec::new(),
+            current: 0,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            interval: block_config.interval,
+        })
+    }
+}
+
+impl Ticker {
+    fn set_texts(&mut self) -> Result<()> {
+        let quote = match self.quotes.get(self.current) {
+            Some(quote) => quote,
+            None => return Ok(()),
+        };
+        let values = map!(
+            "symbol" => Value::from_string(quote.symbol.clone()),
+            "price" => Value::from_string(format_price(quote.price)),
+            "currency" => Value::from_string(quote.currency.clone()),
+            "change_24h" => Value::from_string(format!("{:+.2}%", quote.change)),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(if quote.change.abs() >= self.critical {
+            State::Critical
+        } else if quote.change.abs() >= self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+        Ok(())
+    }
+}
+
+impl Block for Ticker {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.source.quotes(&self.symbols) {
+            Ok(quotes) => {
+                self.quotes = quotes;
+                self.set_texts()?;
+            }
+            Err(error) => {
+                // Old prices would pass for current ones
+                self.quotes.clear();
+                match error {
+                    BlockError(block, _) | InternalError(block, _, _) if block == "curl" => {
+                        self.text.set_text("×".to_string());
+                        self.text.set_state(State::Warning);
+                    }
+                    error => {
+                        self.text.set_text(format!("ticker error {}", error));
+                        self.text.set_state(State::Critical);
+                    }
+                }
+            }
+        }
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let count = self.symbols.len();
+        self.current = match event.button {
+            MouseButton::WheelUp => (self.current + 1) % count,
+            MouseButton::WheelDown => (self.current + count - 1) % count,
+            _ => return Ok(()),
+        };
+        self.set_texts()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let prices = serde_json::json!({
+            "bitcoin": {"eur": 52341.0, "eur_24h_change": -3.25},
+        });
+        assert_eq!(
+            parse_coingecko(&prices, "bitcoin", "eur").unwrap(),
+            Quote {
+                symbol: "bitcoin".to_string(),
+                price: 52341.0,
+                currency: "EUR".to_string(),
+                change: -3.25,
+            }
+        );
+        assert!(parse_coingecko(&prices, "dogecoin", "eur").is_err());
+
+        let chart = serde_json::json!({"chart": {"result": [{"meta": {
+            "currency": "USD", "regularMarketPrice": 154.5, "chartPreviousClose": 150.0,
+        }}]}});
+        assert!((parse_yahoo(&chart, "AAPL").unwrap().change - 3.0).abs() < 1e-9);
+
+        assert_eq!(format_price(52341.0), "52341.00");
+        assert_eq!(format_price(0.061234), "0.06123");
+    }
+}
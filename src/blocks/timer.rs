@@ -0,0 +1,284 @@
+//! A countdown timer that is set by scrolling and started by clicking. It keeps counting down
+//! while the bar restarts.
+
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::state_store::{self, StateStore};
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+enum Countdown {
+    /// Time left while paused or not started
+    Paused(Duration),
+    Running {
+        until: Instant,
+    },
+    Expired,
+}
+
+/// A countdown as saved for the next start, with the time it runs out on the wall clock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Saved {
+    Paused(Duration),
+    Running(SystemTime),
+    Expired,
+}
+
+impl From<&Countdown> for Saved {
+    fn from(countdown: &Countdown) -> Self {
+        match countdown {
+            Countdown::Paused(remaining) => Saved::Paused(*remaining),
+            Countdown::Running { until } => Saved::Running(state_store::system_time(*until)),
+            Countdown::Expired => Saved::Expired,
+        }
+    }
+}
+
+impl From<Saved> for Countdown {
+    fn from(saved: Saved) -> Self {
+        match saved {
+            Saved::Paused(remaining) => Countdown::Paused(remaining),
+            // Runs out with the next update if it did while the bar was stopped
+            Saved::Running(until) => Countdown::Running {
+                until: state_store::instant(until),
+            },
+            Saved::Expired => Countdown::Expired,
+        }
+    }
+}
+
+impl Countdown {
+    fn remaining(&self) -> Duration {
+        match self {
+            Countdown::Paused(remaining) => *remaining,
+            Countdown::Running { until } => until.saturating_duration_since(Instant::now()),
+            Countdown::Expired => Duration::from_secs(0),
+        }
+    }
+}
+
+pub struct Timer {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    duration: Duration,
+    step: Duration,
+    warning: Duration,
+    critical: Duration,
+    command: Option<String>,
+    countdown: Countdown,
+    state_store: Rc<StateStore>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TimerConfig {
+    /// Minutes the timer is set to at the start and when it is reset
+    pub duration: u64,
+
+    /// Minutes added or removed by scrolling
+    pub step: u64,
+
+    /// Seconds left from which on the block is shown as warning
+    pub warning: u64,
+
+    /// Seconds left from which on the block is shown as critical
+    pub critical: u64,
+
+    /// Shell command run when the time is up
+    pub command: Option<String>,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for TimerConfig {
+    fn default() -> Self {
+        Self {
+            duration: 5,
+            step: 1,
+            warning: 60,
+            critical: 10,
+            command: None,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Timer {
+    type Config = TimerConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let duration = Duration::from_secs(block_config.duration * 60);
+        let state_store = shared_config.state_store();
+        let countdown = state_store
+            .get::<Saved>(&state_key(id))
+            .map(Countdown::from)
+            .unwrap_or(Countdown::Paused(duration));
+        Ok(Timer {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("time")?,
+            format: block_config.format.with_default("{remaining}")?,
+            duration,
+            step: Duration::from_secs(block_config.step * 60),
+            warning: Duration::from_secs(block_config.warning),
+            critical: Duration::from_secs(block_config.critical),
+            command: block_config.command,
+            countdown,
+            state_store,
+        })
+    }
+}
+
+fn state_key(id: usize) -> String {
+    format!("timer/{}", id)
+}
+
+impl Timer {
+    fn save(&self) {
+        self.state_store
+            .set(&state_key(self.id), &Saved::from(&self.countdown));
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let remaining = self.countdown.remaining();
+        // Round up, so that the timer shows 0:00 only once the time is up
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        let values = map!(
+            "remaining" => Value::from_string(format!("{}:{:02}", seconds / 60, seconds % 60)),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.countdown {
+            Countdown::Paused(_) => State::Idle,
+            Countdown::Expired => State::Critical,
+            Countdown::Running { .. } if remaining <= self.critical => State::Critical,
+            Countdown::Running { .. } if remaining <= self.warning => State::Warning,
+            Countdown::Running { .. } => State::Info,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Timer {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if let Countdown::Running { until } = self.countdown {
+            if Instant::now() >= until {
+                self.countdown = Countdown::Expired;
+                self.save();
+                if let Some(command) = &self.command {
+                    spawn_child_async("sh", &["-c", command])
+                        .block_error("timer", &format!("failed to run {}", command))?;
+                }
+            }
+        }
+        self.set_text()?;
+        Ok(Some(Duration::from_secs(1).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let remaining = self.countdown.remaining();
+        self.countdown = match (event.button, &self.countdown) {
+            (MouseButton::Left, Countdown::Paused(remaining)) if *remaining > Duration::ZERO => {
+                Countdown::Running {
+                    until: Instant::now() + *remaining,
+                }
+            }
+            (MouseButton::Left, Countdown::Running { .. }) => Countdown::Paused(remaining),
+            (MouseButton::Right, _) => Countdown::Paused(self.duration),
+            (MouseButton::WheelUp, Countdown::Running { until }) => Countdown::Running {
+                until: *until + self.step,
+            },
+            (MouseButton::WheelUp, _) => Countdown::Paused(remaining + self.step),
+            (MouseButton::WheelDown, Countdown::Running { until }) => Countdown::Running {
+                until: until
+                    .checked_sub(self.step)
+                    .unwrap_or(*until)
+                    .max(Instant::now()),
+            },
+            (MouseButton::WheelDown, _) => Countdown::Paused(remaining.saturating_sub(self.step)),
+            _ => return Ok(()),
+        };
+        self.save();
+        self.set_text()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_by_scrolling() {
+        let config = TimerConfig {
+            duration: 1,
+            ..TimerConfig::default()
+        };
+        let shared_config = SharedConfig::default();
+        let mut timer = Timer::new(
+            0,
+            config,
+            shared_config.clone(),
+            crossbeam_channel::unbounded().0,
+        )
+        .unwrap();
+        let click = |timer: &mut Timer, button| {
+            timer
+                .click(&I3BarEvent {
+                    id: Some(0),
+                    instance: None,
+                    button,
+                    modifiers: Vec::new(),
+                    double_click: false,
+                })
+                .unwrap();
+            timer.countdown.remaining().as_secs()
+        };
+
+        assert_eq!(click(&mut timer, MouseButton::WheelUp), 120);
+        assert_eq!(click(&mut timer, MouseButton::WheelDown), 60);
+        assert_eq!(click(&mut timer, MouseButton::WheelDown), 0);
+        assert_eq!(click(&mut timer, MouseButton::WheelDown), 0);
+        // There is nothing to count down
+        click(&mut timer, MouseButton::Left);
+        assert!(matches!(timer.countdown, Countdown::Paused(_)));
+        assert_eq!(click(&mut timer, MouseButton::Right), 60);
+
+        // The countdown goes on after a restart
+        click(&mut timer, MouseButton::Left);
+        let restarted = Timer::new(
+            0,
+            TimerConfig::default(),
+            shared_config,
+            crossbeam_channel::unbounded().0,
+        )
+        .unwrap();
+        assert!(matches!(restarted.countdown, Countdown::Running { .. }));
+        assert_eq!(restarted.countdown.remaining().as_secs(), 59);
+    }
+}
@@ -1,5 +1,4 @@
-use std::env;
-use std::process::Command;
+use std::rc::Rc;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -11,6 +10,7 @@ use crate::de::deserialize_opt_duration;
 use crate::errors::*;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -24,6 +24,7 @@ pub struct Toggle {
     icon_off: String,
     update_interval: Option<Duration>,
     toggled: bool,
+    command_runner: Rc<dyn CommandRunner>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -75,6 +76,7 @@ impl ConfigBlock for Toggle {
     ) -> Result<Self> {
         Ok(Toggle {
             id,
+            command_runner: shared_config.command_runner.clone(),
             text: TextWidget::new(id, 0, shared_config)
                 .with_text(&block_config.text.unwrap_or_default()),
             command_on: block_config.command_on,
@@ -90,10 +92,10 @@ impl ConfigBlock for Toggle {
 
 impl Block for Toggle {
     fn update(&mut self) -> Result<Option<Update>> {
-        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
-            .args(&["-c", &self.command_state])
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
+        let output = self
+            .command_runner
+            .run(&self.command_state)
+            .map(|o| o.stdout.trim().to_owned())
             .unwrap_or_else(|e| e.to_string());
 
         self.text.set_icon(match output.trim_start() {
@@ -123,12 +125,12 @@ impl Block for Toggle {
             &self.command_on
         };
 
-        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
-            .args(&["-c", cmd])
-            .output()
+        let output = self
+            .command_runner
+            .run(cmd)
             .block_error("toggle", "failed to run toggle command")?;
 
-        if output.status.success() {
+        if output.status == Some(0) {
             self.text.set_state(State::Idle);
             self.toggled = !self.toggled;
             self.text.set_icon(if self.toggled {
@@ -147,3 +149,44 @@ impl Block for Toggle {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::i3bar_event::MouseButton;
+    use crate::subprocess::MockRunner;
+
+    #[test]
+    fn test_toggle() {
+        let runner = Rc::new(MockRunner::default());
+        runner.set("nmcli radio wifi | grep enabled", "enabled\n");
+        runner.set("nmcli radio wifi off", "");
+        let config: ToggleConfig = toml::from_str(
+            r#"
+            command_state = "nmcli radio wifi | grep enabled"
+            command_on = "nmcli radio wifi on"
+            command_off = "nmcli radio wifi off"
+            "#,
+        )
+        .unwrap();
+        let mut shared_config = SharedConfig::default();
+        shared_config.command_runner = runner.clone();
+        let mut toggle =
+            Toggle::new(0, config, shared_config, crossbeam_channel::unbounded().0).unwrap();
+
+        toggle.update().unwrap();
+        assert!(toggle.toggled);
+
+        toggle
+            .click(&I3BarEvent {
+                id: Some(0),
+                instance: None,
+                button: MouseButton::Left,
+                modifiers: Vec::new(),
+                double_click: false,
+            })
+            .unwrap();
+        assert!(!toggle.toggled);
+        assert_eq!(runner.ran.borrow().last().unwrap(), "nmcli radio wifi off");
+    }
+}
@@ -0,0 +1,230 @@
+//! Shows how much is in the trash of the desktop, as specified by freedesktop.org. A click, and a
+//! second one to confirm, empties it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{disk_usage, xdg_data_home};
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Time to confirm emptying the trash with a second click
+const CONFIRM: Duration = Duration::from_secs(5);
+
+pub struct Trash {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_confirm: FormatTemplate,
+    path: PathBuf,
+    hide_below: u64,
+    warning: Option<u64>,
+    size: u64,
+    count: usize,
+    /// When the first click to empty the trash happened
+    armed: Option<Instant>,
+    interval: Duration,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TrashConfig {
+    /// Size in MB below which the block is hidden
+    pub hide_below: u64,
+
+    /// Size in MB from which on the block is shown as warning
+    pub warning: Option<u64>,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Format override while waiting for the click that confirms emptying the trash
+    pub format_confirm: FormatTemplate,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            hide_below: 0,
+            warning: None,
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+            format_confirm: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Trash {
+    type Config = TrashConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Trash {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("trash")?,
+            format: block_config.format.with_default("{size;M}")?,
+            format_confirm: block_config
+                .format_confirm
+                .with_default("empty {count} items?")?,
+            path: xdg_data_home().join("Trash"),
+            hide_below: block_config.hide_below * 1024 * 1024,
+            warning: block_config.warning.map(|warning| warning * 1024 * 1024),
+            size: 0,
+            count: 0,
+            armed: None,
+            interval: block_config.interval,
+            tx_update_request,
+        })
+    }
+}
+
+/// Deletes everything in a directory, but not the directory
+fn clear(dir: &Path) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let result = match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => fs::remove_dir_all(&path),
+            _ => fs::remove_file(&path),
+        };
+        if let Err(error) = result {
+            log::warn!("failed to delete {}: {}", path.display(), error);
+        }
+    }
+}
+
+impl Trash {
+    /// Empties the trash in the background and counts again once it's done
+    fn empty(&self) -> Result<()> {
+        let path = self.path.clone();
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        thread::Builder::new()
+            .name("trash".into())
+            .spawn(move || {
+                // The info files go last, so that a trashed file is never left without one
+                clear(&path.join("files"));
+                clear(&path.join("info"));
+                let _ = fs::remove_file(path.join("directorysizes"));
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("trash", "failed to start a thread")?;
+        Ok(())
+    }
+
+    /// Asks for a second click, and forgets about the first one after a while
+    fn arm(&mut self) -> Result<()> {
+        self.armed = Some(Instant::now());
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        thread::Builder::new()
+            .name("trash".into())
+            .spawn(move || {
+                thread::sleep(CONFIRM);
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("trash", "failed to start a thread")?;
+        self.set_text()
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let values = map!(
+            "size" => Value::from_float(self.size as f64).bytes(),
+            "count" => Value::from_integer(self.count as i64),
+        );
+        if self.armed.is_some() {
+            self.text.set_texts(self.format_confirm.render(&values)?);
+            self.text.set_state(State::Critical);
+        } else {
+            self.text.set_texts(self.format.render(&values)?);
+            self.text.set_state(match self.warning {
+                Some(warning) if self.size >= warning => State::Warning,
+                _ => State::Idle,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Block for Trash {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let files = self.path.join("files");
+        self.count = fs::read_dir(&files).map_or(0, |entries| entries.count());
+        self.size = disk_usage(&files);
+        if self.armed.is_some_and(|armed| armed.elapsed() >= CONFIRM) {
+            self.armed = None;
+        }
+        self.set_text()?;
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match (event.button, self.armed.take()) {
+            (MouseButton::Left, Some(_)) => {
+                self.set_text()?;
+                self.empty()
+            }
+            (MouseButton::Left, None) => self.arm(),
+            _ => self.set_text(),
+        }
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.armed.is_none() && (self.count == 0 || self.size < self.hide_below) {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_usage() {
+        let dir = std::env::temp_dir().join(format!("i3status-rs-trash-{}", std::process::id()));
+        fs::create_dir_all(dir.join("files/photos")).unwrap();
+        fs::write(dir.join("files/notes.txt"), "x".repeat(100)).unwrap();
+        fs::write(dir.join("files/photos/a.jpg"), "y".repeat(1000)).unwrap();
+        let usage = disk_usage(&dir.join("files"));
+        clear(&dir.join("files"));
+        let left = fs::read_dir(dir.join("files")).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        // Directories take some space themselves
+        assert!(usage >= 1100);
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,322 @@
+//! Counts today's keystrokes and the time spent actively at the keyboard and mouse from the evdev
+//! input devices, and warns when it's time for a break. Only counts are kept, never which keys
+//! were pressed, and they stay on this machine.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local};
+use crossbeam_channel::Sender;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::xdg_state_home;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Size of a `struct input_event`: a timeval, then the u16 type and code and the i32 value
+const EVENT_SIZE: usize = std::mem::size_of::<nix::libc::timeval>() + 8;
+const EV_KEY: u16 = 1;
+const EV_REP: u64 = 1 << 20;
+
+/// Pauses in input up to this long count as active time
+const IDLE: Duration = Duration::from_secs(60);
+
+/// A pause this long counts as a break
+const BREAK: Duration = Duration::from_secs(5 * 60);
+
+/// The activity of a day
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+struct Activity {
+    day: i32,
+    keys: u64,
+    active_seconds: u64,
+}
+
+impl Activity {
+    /// The activity of `day`, which starts over if it is a new day
+    fn on(self, day: i32) -> Activity {
+        if self.day == day {
+            self
+        } else {
+            Activity {
+                day,
+                ..Activity::default()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tracker {
+    today: Activity,
+    last_input: Option<Instant>,
+    /// When the input started after the last break
+    session_start: Option<Instant>,
+    /// Active time of less than a second that isn't counted yet
+    carry: Duration,
+    /// Devices that are read
+    devices: HashSet<PathBuf>,
+}
+
+impl Tracker {
+    fn record(&mut self, now: Instant, keys: u64) {
+        self.today = self.today.on(Local::today().num_days_from_ce());
+        self.today.keys += keys;
+        let gap = self
+            .last_input
+            .map(|last| now.saturating_duration_since(last));
+        match gap {
+            Some(gap) if gap < IDLE => {
+                let active = self.carry + gap;
+                self.today.active_seconds += active.as_secs();
+                self.carry = active - Duration::from_secs(active.as_secs());
+            }
+            Some(gap) if gap < BREAK => {}
+            _ => self.session_start = Some(now),
+        }
+        self.last_input = Some(now);
+    }
+
+    /// How long the input went on without a break, if it still does
+    fn session(&self, now: Instant) -> Duration {
+        match (self.session_start, self.last_input) {
+            (Some(start), Some(last)) if now.saturating_duration_since(last) < BREAK => {
+                now.saturating_duration_since(start)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// The keyboards and mice in the contents of /proc/bus/input/devices, whose devices are
+/// separated by empty lines and have lines like "H: Handlers=sysrq kbd event3 leds" and
+/// "B: EV=120013"
+fn input_devices(contents: &str) -> Vec<PathBuf> {
+    contents
+        .split("\n\n")
+        .filter_map(|device| {
+            let handlers: Vec<&str> = device
+                .lines()
+                .find_map(|line| line.strip_prefix("H: Handlers="))?
+                .split_whitespace()
+                .collect();
+            let events = device
+                .lines()
+                .find_map(|line| line.strip_prefix("B: EV="))
+                .and_then(|events| u64::from_str_radix(events.trim(), 16).ok())
+                .unwrap_or(0);
+            // Power buttons and the like are "kbd" too, but don't repeat keys
+            let keyboard = handlers.contains(&"kbd") && events & EV_REP != 0;
+            let mouse = handlers.iter().any(|handler| handler.starts_with("mouse"));
+            if !keyboard && !mouse {
+                return None;
+            }
+            let event = handlers
+                .iter()
+                .find(|handler| handler.starts_with("event"))?;
+            Some(PathBuf::from("/dev/input").join(event))
+        })
+        .collect()
+}
+
+/// The key presses among the events, without releases and repeats
+fn key_presses(events: &[u8]) -> u64 {
+    events
+        .chunks_exact(EVENT_SIZE)
+        .filter(|event| {
+            let rest = &event[EVENT_SIZE - 8..];
+            let kind = u16::from_ne_bytes([rest[0], rest[1]]);
+            let value = i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]);
+            kind == EV_KEY && value == 1
+        })
+        .count() as u64
+}
+
+/// Reads the devices that aren't read yet, each in a thread that ends when the device is gone, or
+/// with the first input after the block is gone
+fn watch_devices(tracker: &Arc<Mutex<Tracker>>) {
+    let devices = fs::read_to_string("/proc/bus/input/devices")
+        .map(|contents| input_devices(&contents))
+        .unwrap_or_default();
+    for device in devices {
+        if !tracker.lock().unwrap().devices.insert(device.clone()) {
+            continue;
+        }
+        let tracker = Arc::downgrade(tracker);
+        let spawned = thread::Builder::new().name("typing".into()).spawn(move || {
+            match File::open(&device) {
+                Ok(mut file) => {
+                    let mut buffer = vec![0; EVENT_SIZE * 64];
+                    // The kernel only hands out whole events
+                    while let Ok(read @ 1..) = file.read(&mut buffer) {
+                        let keys = key_presses(&buffer[..read]);
+                        match tracker.upgrade() {
+                            Some(tracker) => tracker.lock().unwrap().record(Instant::now(), keys),
+                            None => return,
+                        }
+                    }
+                }
+                Err(error) => log::warn!("failed to open {}: {}", device.display(), error),
+            }
+            if let Some(tracker) = tracker.upgrade() {
+                tracker.lock().unwrap().devices.remove(&device);
+            }
+        });
+        if spawned.is_err() {
+            log::warn!("failed to start a thread");
+        }
+    }
+}
+
+pub struct Typing {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    break_after: Option<Duration>,
+    tracker: Arc<Mutex<Tracker>>,
+    state_file: PathBuf,
+    saved: Activity,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TypingConfig {
+    /// Minutes of input without a break of 5 minutes after which the block warns
+    pub break_after: Option<u64>,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl ConfigBlock for Typing {
+    type Config = TypingConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let state_file = xdg_state_home().join("i3status-rust/typing.toml");
+        let saved: Activity = fs::read_to_string(&state_file)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        let tracker = Arc::new(Mutex::new(Tracker {
+            today: saved,
+            ..Tracker::default()
+        }));
+        watch_devices(&tracker);
+        if tracker.lock().unwrap().devices.is_empty() {
+            return Err(BlockError(
+                "typing".to_string(),
+                "found no keyboard or mouse".to_string(),
+            ));
+        }
+
+        Ok(Typing {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("keyboard")?,
+            format: block_config.format.with_default("{keys} {active}")?,
+            break_after: block_config
+                .break_after
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            tracker,
+            state_file,
+            saved,
+        })
+    }
+}
+
+impl Typing {
+    fn save(&mut self, today: Activity) {
+        // Failing to save only means that today's counts start over after a restart
+        if let Some(dir) = self.state_file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(contents) = toml::to_string(&today) {
+            let _ = fs::write(&self.state_file, contents);
+        }
+        self.saved = today;
+    }
+}
+
+impl Block for Typing {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Pick up keyboards that were plugged in since
+        watch_devices(&self.tracker);
+        let now = Instant::now();
+        let (today, session) = {
+            let tracker = self.tracker.lock().unwrap();
+            (
+                tracker.today.on(Local::today().num_days_from_ce()),
+                tracker.session(now),
+            )
+        };
+        if today != self.saved {
+            self.save(today);
+        }
+
+        let active = today.active_seconds / 60;
+        let values = map!(
+            "keys" => Value::from_integer(today.keys as i64),
+            "active" => Value::from_string(format!("{}:{:02}", active / 60, active % 60)),
+            "session" => Value::from_integer(session.as_secs() as i64 / 60),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match self.break_after {
+            Some(break_after) if session >= break_after => State::Warning,
+            _ => State::Idle,
+        });
+        Ok(Some(Duration::from_secs(30).into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_devices() {
+        let contents = "I: Bus=0019 Vendor=0000 Product=0001 Version=0000\n\
+                        N: Name=\"Power Button\"\n\
+                        H: Handlers=kbd event0 \n\
+                        B: EV=3\n\
+                        \n\
+                        I: Bus=0011 Vendor=0001 Product=0001 Version=ab41\n\
+                        N: Name=\"AT Translated Set 2 keyboard\"\n\
+                        H: Handlers=sysrq kbd leds event3 \n\
+                        B: EV=120013\n\
+                        \n\
+                        I: Bus=0003 Vendor=046d Product=c52b Version=0111\n\
+                        N: Name=\"Logitech USB Receiver Mouse\"\n\
+                        H: Handlers=event5 mouse0 \n\
+                        B: EV=17\n";
+        assert_eq!(
+            input_devices(contents),
+            vec![
+                PathBuf::from("/dev/input/event3"),
+                PathBuf::from("/dev/input/event5")
+            ]
+        );
+    }
+}
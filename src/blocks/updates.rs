@@ -0,0 +1,583 @@
+//! Shows the pending package updates of pacman, apt, dnf and xbps, and of Flatpak and snap, and how
+//! many of them fix security issues. A click upgrades in a terminal.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::{deserialize_duration, deserialize_opt_duration};
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::util::has_command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Pending {
+    count: usize,
+    security: usize,
+}
+
+/// A package manager whose pending updates are counted
+trait UpdatesBackend {
+    fn name(&self) -> &'static str;
+
+    /// Command that upgrades everything, run in a terminal
+    fn upgrade_command(&self) -> &'static str;
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending>;
+}
+
+struct Pacman;
+
+impl UpdatesBackend for Pacman {
+    fn name(&self) -> &'static str {
+        "pacman"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "sudo pacman -Syu"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        // checkupdates syncs a copy of the database, so it needs no root, and exits with 2 if
+        // nothing is outdated
+        let output = runner.run("checkupdates")?;
+        if output.status != Some(0) && output.status != Some(2) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "checkupdates failed, is pacman-contrib installed?".to_string(),
+            ));
+        }
+        // Arch has no security repository, but arch-audit knows which upgrades fix issues
+        let audit = runner.run("arch-audit --upgradable --quiet")?;
+        Ok(Pending {
+            count: count_lines(&output.stdout),
+            security: if audit.status == Some(0) {
+                count_lines(&audit.stdout)
+            } else {
+                0
+            },
+        })
+    }
+}
+
+struct Apt;
+
+impl UpdatesBackend for Apt {
+    fn name(&self) -> &'static str {
+        "apt"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "sudo apt update && sudo apt upgrade"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        // Uses the package lists as the system last updated them, e.g. with apt-daily
+        let output = runner.run("LC_ALL=C apt-get --simulate -o Debug::NoLocking=1 upgrade")?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "apt-get --simulate upgrade failed".to_string(),
+            ));
+        }
+        Ok(parse_apt(&output.stdout))
+    }
+}
+
+struct Dnf;
+
+impl UpdatesBackend for Dnf {
+    fn name(&self) -> &'static str {
+        "dnf"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "sudo dnf upgrade"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        // Exits with 100 if there are updates
+        let output = runner.run("LC_ALL=C dnf check-update -q")?;
+        if output.status != Some(0) && output.status != Some(100) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "dnf check-update failed".to_string(),
+            ));
+        }
+        let advisories = runner.run("LC_ALL=C dnf updateinfo list --security -q")?;
+        Ok(Pending {
+            count: parse_dnf_count(&output.stdout),
+            security: if advisories.status == Some(0) {
+                parse_dnf_security(&advisories.stdout)
+            } else {
+                0
+            },
+        })
+    }
+}
+
+struct Xbps;
+
+impl UpdatesBackend for Xbps {
+    fn name(&self) -> &'static str {
+        "xbps"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "sudo xbps-install -Su"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        // -M fetches the repository index to memory, so it needs no root. Void publishes no
+        // security advisories.
+        let output = runner.run("xbps-install -Mun")?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "xbps-install -Mun failed".to_string(),
+            ));
+        }
+        Ok(Pending {
+            count: output
+                .stdout
+                .lines()
+                .filter(|line| line.split_whitespace().nth(1) == Some("update"))
+                .count(),
+            security: 0,
+        })
+    }
+}
+
+struct Flatpak;
+
+impl UpdatesBackend for Flatpak {
+    fn name(&self) -> &'static str {
+        "flatpak"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "flatpak update"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        // Lists the apps and runtimes of both the system and the user installation
+        let output = runner.run("flatpak remote-ls --updates --columns=application")?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "flatpak remote-ls --updates failed".to_string(),
+            ));
+        }
+        Ok(Pending {
+            count: count_lines(&output.stdout),
+            security: 0,
+        })
+    }
+}
+
+struct Snap;
+
+impl UpdatesBackend for Snap {
+    fn name(&self) -> &'static str {
+        "snap"
+    }
+
+    fn upgrade_command(&self) -> &'static str {
+        "sudo snap refresh"
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> Result<Pending> {
+        let output = runner.run("LC_ALL=C snap refresh --list")?;
+        if output.status != Some(0) {
+            return Err(BlockError(
+                "updates".to_string(),
+                "snap refresh --list failed".to_string(),
+            ));
+        }
+        Ok(Pending {
+            count: parse_snap(&output.stdout),
+            security: 0,
+        })
+    }
+}
+
+fn count_lines(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count()
+}
+
+/// The updates in the output of `apt-get --simulate upgrade`, with lines like
+/// "Inst libssl3 [3.0.11-1] (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])"
+fn parse_apt(output: &str) -> Pending {
+    let installs: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("Inst "))
+        .collect();
+    Pending {
+        count: installs.len(),
+        security: installs
+            .iter()
+            .filter(|line| line.contains("-security"))
+            .count(),
+    }
+}
+
+/// The updates in the output of `dnf check-update -q`, with lines like
+/// "openssl.x86_64  1:3.1.1-4.fc39  updates", followed by obsoleted packages
+fn parse_dnf_count(output: &str) -> usize {
+    output
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter(|line| line.split_whitespace().count() == 3)
+        .count()
+}
+
+/// The packages in the output of `dnf updateinfo list --security -q`, with lines like
+/// "FEDORA-2024-1a2b3c4d5e Important/Sec. openssl-1:3.1.4-1.fc39.x86_64", some of which are named
+/// by several advisories
+fn parse_dnf_security(output: &str) -> usize {
+    let mut packages: Vec<&str> = output
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .collect();
+    packages.sort_unstable();
+    packages.dedup();
+    packages.len()
+}
+
+/// The updates in the output of `snap refresh --list`, a table with a header like
+/// "Name  Version  Rev  Size  Publisher  Notes", which prints nothing to stdout if there are none
+fn parse_snap(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with("Name "))
+        .count()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Pacman,
+    Apt,
+    Dnf,
+    Xbps,
+    Flatpak,
+    Snap,
+}
+
+impl BackendKind {
+    fn backend(self) -> Box<dyn UpdatesBackend> {
+        match self {
+            BackendKind::Pacman => Box::new(Pacman),
+            BackendKind::Apt => Box::new(Apt),
+            BackendKind::Dnf => Box::new(Dnf),
+            BackendKind::Xbps => Box::new(Xbps),
+            BackendKind::Flatpak => Box::new(Flatpak),
+            BackendKind::Snap => Box::new(Snap),
+        }
+    }
+
+    /// The command that tells whether the package manager is installed
+    fn command(self) -> &'static str {
+        match self {
+            BackendKind::Pacman => "checkupdates",
+            BackendKind::Apt => "apt-get",
+            BackendKind::Dnf => "dnf",
+            BackendKind::Xbps => "xbps-install",
+            BackendKind::Flatpak => "flatpak",
+            BackendKind::Snap => "snap",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BackendConfig {
+    pub backend: BackendKind,
+
+    /// Update interval in seconds, instead of the one of the block
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval: Option<Duration>,
+
+    /// Upgrade command override
+    pub upgrade_command: Option<String>,
+}
+
+struct Source {
+    backend: Box<dyn UpdatesBackend>,
+    interval: Duration,
+    upgrade_command: String,
+    next_check: Instant,
+    pending: Pending,
+}
+
+pub struct Updates {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    sources: Vec<Source>,
+    terminal_command: String,
+    hide_when_uptodate: bool,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct UpdatesConfig {
+    /// Package managers to check, all installed ones by default
+    pub backends: Vec<BackendConfig>,
+
+    /// Update interval in seconds of backends that don't set their own
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Command that runs the rest of the command line in a terminal
+    pub terminal_command: String,
+
+    /// Hide the block while everything is up to date
+    pub hide_when_uptodate: bool,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Alternative format override for when no updates are available
+    pub format_up_to_date: FormatTemplate,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            interval: Duration::from_secs(1800),
+            terminal_command: "xterm -e".to_string(),
+            hide_when_uptodate: false,
+            format: FormatTemplate::default(),
+            format_up_to_date: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Updates {
+    type Config = UpdatesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let mut backends = block_config.backends;
+        if backends.is_empty() {
+            for kind in [
+                BackendKind::Pacman,
+                BackendKind::Apt,
+                BackendKind::Dnf,
+                BackendKind::Xbps,
+                BackendKind::Flatpak,
+                BackendKind::Snap,
+            ] {
+                if has_command("updates", kind.command())? {
+                    backends.push(BackendConfig {
+                        backend: kind,
+                        interval: None,
+                        upgrade_command: None,
+                    });
+                }
+            }
+        }
+        if backends.is_empty() {
+            return Err(ConfigurationError(
+                "updates".to_string(),
+                "found none of checkupdates, apt-get, dnf, xbps-install, flatpak and snap"
+                    .to_string(),
+            ));
+        }
+
+        let interval = block_config.interval;
+        let now = Instant::now();
+        let sources = backends
+            .into_iter()
+            .map(|config| {
+                let backend = config.backend.backend();
+                Source {
+                    interval: config.interval.unwrap_or(interval),
+                    upgrade_command: config
+                        .upgrade_command
+                        .unwrap_or_else(|| backend.upgrade_command().to_string()),
+                    backend,
+                    next_check: now,
+                    pending: Pending::default(),
+                }
+            })
+            .collect();
+
+        Ok(Updates {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("update")?,
+            format: block_config.format.with_default("{count}")?,
+            format_up_to_date: block_config.format_up_to_date.with_default("{count}")?,
+            sources,
+            terminal_command: block_config.terminal_command,
+            hide_when_uptodate: block_config.hide_when_uptodate,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+impl Block for Updates {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Instant::now();
+        for source in &mut self.sources {
+            if source.next_check <= now {
+                source.pending = source.backend.check(self.command_runner.as_ref())?;
+                source.next_check = now + source.interval;
+            }
+        }
+
+        let count = self.sources.iter().map(|s| s.pending.count).sum::<usize>();
+        let security = self
+            .sources
+            .iter()
+            .map(|s| s.pending.security)
+            .sum::<usize>();
+        let mut values: HashMap<&str, Value> = self
+            .sources
+            .iter()
+            .map(|source| {
+                (
+                    source.backend.name(),
+                    Value::from_integer(source.pending.count as i64),
+                )
+            })
+            .collect();
+        values.insert("count", Value::from_integer(count as i64));
+        values.insert("security_count", Value::from_integer(security as i64));
+
+        self.text.set_texts(if count == 0 {
+            self.format_up_to_date.render(&values)?
+        } else {
+            self.format.render(&values)?
+        });
+        self.text.set_state(if security > 0 {
+            State::Warning
+        } else if count > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+
+        let next_check = self
+            .sources
+            .iter()
+            .map(|source| source.next_check)
+            .min()
+            .unwrap_or(now);
+        Ok(Some(next_check.saturating_duration_since(now).into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let commands: Vec<&str> = self
+            .sources
+            .iter()
+            .filter(|source| source.pending.count > 0)
+            .map(|source| source.upgrade_command.as_str())
+            .collect();
+        if commands.is_empty() {
+            return Ok(());
+        }
+        // Keep the terminal open so that the output can be read
+        let script = format!("{}; echo; read -r _", commands.join(" && "));
+        let command = format!("{} sh -c {}", self.terminal_command, quote(&script));
+        for source in &mut self.sources {
+            source.next_check = Instant::now();
+        }
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        // Check again once the terminal is closed
+        thread::Builder::new()
+            .name("updates".into())
+            .spawn(move || {
+                let _ = Command::new("sh").args(["-c", &command]).status();
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("updates", "failed to start a thread")?;
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        let up_to_date = self.sources.iter().all(|source| source.pending.count == 0);
+        if up_to_date && self.hide_when_uptodate {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let apt = "Reading package lists...\n\
+                   Calculating upgrade...\n\
+                   The following packages will be upgraded:\n   curl libssl3\n\
+                   Inst libssl3 [3.0.11-1~deb12u2] (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])\n\
+                   Inst curl [7.88.1-10] (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])\n\
+                   Conf libssl3 (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])\n";
+        assert_eq!(
+            parse_apt(apt),
+            Pending {
+                count: 2,
+                security: 1
+            }
+        );
+
+        let dnf = "\nopenssl.x86_64      1:3.1.4-1.fc39     updates\n\
+                   curl.x86_64         8.2.1-4.fc39       updates\n\
+                   Obsoleting Packages\n\
+                   grub2-tools.x86_64  1:2.06-110.fc39    updates\n";
+        assert_eq!(parse_dnf_count(dnf), 2);
+
+        let advisories = "FEDORA-2024-1a2b3c4d5e Important/Sec. openssl-1:3.1.4-1.fc39.x86_64\n\
+                          FEDORA-2024-6f7a8b9c0d Moderate/Sec.  openssl-1:3.1.4-1.fc39.x86_64\n";
+        assert_eq!(parse_dnf_security(advisories), 1);
+
+        let snap = "Name     Version  Rev   Size   Publisher   Notes\n\
+                    firefox  125.0.2  4173  280MB  mozilla✓    -\n";
+        assert_eq!(parse_snap(snap), 1);
+    }
+}
@@ -0,0 +1,333 @@
+//! Shows the charge, load and remaining runtime of a UPS, from the network server of Network UPS
+//! Tools or of apcupsd, and turns critical while the UPS runs on battery
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpsDriver {
+    /// upsd of Network UPS Tools
+    Nut,
+    /// The Network Information Server of apcupsd
+    Apcupsd,
+}
+
+impl UpsDriver {
+    fn default_port(self) -> u16 {
+        match self {
+            UpsDriver::Nut => 3493,
+            UpsDriver::Apcupsd => 3551,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct UpsStatus {
+    online: bool,
+    charging: bool,
+    low_battery: bool,
+    /// In percent
+    charge: Option<f64>,
+    /// In percent
+    load: Option<f64>,
+    /// In seconds
+    runtime: Option<f64>,
+}
+
+pub struct Ups {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    driver: UpsDriver,
+    host: String,
+    port: u16,
+    name: String,
+    interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct UpsConfig {
+    /// Which daemon to ask
+    pub driver: UpsDriver,
+
+    pub host: String,
+
+    /// Port, by default the one of the driver
+    pub port: Option<u16>,
+
+    /// Name of the UPS in NUT
+    pub name: String,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+impl Default for UpsConfig {
+    fn default() -> Self {
+        Self {
+            driver: UpsDriver::Nut,
+            host: "localhost".to_string(),
+            port: None,
+            name: "ups".to_string(),
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Ups {
+    type Config = UpsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let driver = block_config.driver;
+        Ok(Ups {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("bat_charging")?,
+            format: block_config.format.with_default("{charge} {runtime}")?,
+            driver,
+            port: block_config.port.unwrap_or_else(|| driver.default_port()),
+            host: block_config.host,
+            name: block_config.name,
+            interval: block_config.interval,
+        })
+    }
+}
+
+/// The variables in the answer of upsd to `LIST VAR`, with lines like
+/// `VAR ups battery.charge "100"`
+fn parse_nut(answer: &str) -> UpsStatus {
+    let variables: HashMap<&str, &str> = answer
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("VAR ")?;
+            let (_ups, rest) = rest.split_once(' ')?;
+            let (name, value) = rest.split_once(' ')?;
+            Some((name, value.trim_matches('"')))
+        })
+        .collect();
+    let number = |name| variables.get(name).and_then(|value| value.parse().ok());
+    // Status flags like "OL CHRG" or "OB DISCHRG LB"
+    let flags: Vec<&str> = variables
+        .get("ups.status")
+        .map_or(Vec::new(), |status| status.split_whitespace().collect());
+    UpsStatus {
+        online: !flags.contains(&"OB"),
+        charging: flags.contains(&"CHRG"),
+        low_battery: flags.contains(&"LB"),
+        charge: number("battery.charge"),
+        load: number("ups.load"),
+        runtime: number("battery.runtime"),
+    }
+}
+
+/// The records that apcupsd answers `status` with, lines like "BCHARGE  : 100.0 Percent"
+fn parse_apcupsd(records: &str) -> UpsStatus {
+    let records: HashMap<&str, &str> = records
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim(), value.trim()))
+        })
+        .collect();
+    let number = |key| {
+        records
+            .get(key)
+            .and_then(|value| value.split_whitespace().next()?.parse::<f64>().ok())
+    };
+    // Like "ONLINE", or "ONBATT LOWBATT"
+    let status = records.get("STATUS").copied().unwrap_or_default();
+    UpsStatus {
+        online: !status.contains("ONBATT"),
+        // apcupsd doesn't tell whether the battery charges
+        charging: false,
+        low_battery: status.contains("LOWBATT"),
+        charge: number("BCHARGE"),
+        load: number("LOADPCT"),
+        runtime: number("TIMELEFT").map(|minutes| minutes * 60.0),
+    }
+}
+
+impl Ups {
+    fn connect(&self) -> Result<TcpStream> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).block_error(
+            "ups",
+            &format!("failed to connect to {}:{}", self.host, self.port),
+        )?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .internal_error("ups", "failed to set a timeout")?;
+        Ok(stream)
+    }
+
+    fn query_nut(&self) -> Result<UpsStatus> {
+        let mut stream = self.connect()?;
+        writeln!(stream, "LIST VAR {}", self.name).block_error("ups", "failed to send to upsd")?;
+        let mut answer = String::new();
+        for line in BufReader::new(&stream).lines() {
+            let line = line.block_error("ups", "failed to read from upsd")?;
+            if let Some(error) = line.strip_prefix("ERR ") {
+                return Err(BlockError(
+                    "ups".to_string(),
+                    format!("upsd failed with {} for {}", error, self.name),
+                ));
+            }
+            if line.starts_with("END LIST") {
+                break;
+            }
+            answer.push_str(&line);
+            answer.push('\n');
+        }
+        let _ = writeln!(stream, "LOGOUT");
+        Ok(parse_nut(&answer))
+    }
+
+    fn query_apcupsd(&self) -> Result<UpsStatus> {
+        let mut stream = self.connect()?;
+        // Messages start with their length as two bytes in big endian
+        stream
+            .write_all(b"\x00\x06status")
+            .block_error("ups", "failed to send to apcupsd")?;
+        let mut records = String::new();
+        loop {
+            let mut length = [0; 2];
+            stream
+                .read_exact(&mut length)
+                .block_error("ups", "failed to read from apcupsd")?;
+            // An empty record ends the answer
+            let length = u16::from_be_bytes(length) as usize;
+            if length == 0 {
+                break;
+            }
+            let mut record = vec![0; length];
+            stream
+                .read_exact(&mut record)
+                .block_error("ups", "failed to read from apcupsd")?;
+            records.push_str(&String::from_utf8_lossy(&record));
+        }
+        Ok(parse_apcupsd(&records))
+    }
+}
+
+impl Block for Ups {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = match self.driver {
+            UpsDriver::Nut => self.query_nut()?,
+            UpsDriver::Apcupsd => self.query_apcupsd()?,
+        };
+
+        let percents = |value: Option<f64>| match value {
+            Some(value) => Value::from_integer(value.round() as i64).percents(),
+            None => Value::from_string("-".to_string()),
+        };
+        let runtime = match status.runtime {
+            Some(seconds) => {
+                let minutes = (seconds / 60.0).round() as i64;
+                format!("{}:{:02}", minutes / 60, minutes % 60)
+            }
+            None => "-".to_string(),
+        };
+        let values = map!(
+            "status" => Value::from_string(match (status.online, status.low_battery) {
+                (_, true) => "low battery",
+                (false, false) => "on battery",
+                (true, false) => "online",
+            }.to_string()),
+            "charge" => percents(status.charge),
+            "load" => percents(status.load),
+            "runtime" => Value::from_string(runtime),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_icon(if status.online {
+            "bat_charging"
+        } else {
+            "bat_discharging"
+        })?;
+        self.text
+            .set_state(if !status.online || status.low_battery {
+                State::Critical
+            } else if status.charging {
+                State::Info
+            } else {
+                State::Idle
+            });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let nut = "BEGIN LIST VAR ups\n\
+                   VAR ups battery.charge \"87\"\n\
+                   VAR ups battery.runtime \"1530\"\n\
+                   VAR ups ups.load \"23\"\n\
+                   VAR ups ups.status \"OB DISCHRG\"\n";
+        assert_eq!(
+            parse_nut(nut),
+            UpsStatus {
+                online: false,
+                charging: false,
+                low_battery: false,
+                charge: Some(87.0),
+                load: Some(23.0),
+                runtime: Some(1530.0),
+            }
+        );
+
+        let apcupsd = "APC      : 001,036,0857\n\
+                       STATUS   : ONLINE \n\
+                       LOADPCT  : 12.0 Percent\n\
+                       BCHARGE  : 100.0 Percent\n\
+                       TIMELEFT : 45.5 Minutes\n";
+        assert_eq!(
+            parse_apcupsd(apcupsd),
+            UpsStatus {
+                online: true,
+                charging: false,
+                low_battery: false,
+                charge: Some(100.0),
+                load: Some(12.0),
+                runtime: Some(2730.0),
+            }
+        );
+    }
+}
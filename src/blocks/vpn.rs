@@ -0,0 +1,276 @@
+//! Shows the active VPN tunnels, like WireGuard or OpenVPN interfaces, and how long ago WireGuard
+//! last completed a handshake. A click brings a configured tunnel up or down.
+
+use std::fs;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::net::NetworkDevice;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::bsd;
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::CommandRunner;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Manager {
+    #[serde(rename = "nmcli")]
+    NetworkManager,
+    #[serde(rename = "wg-quick")]
+    WgQuick,
+}
+
+pub struct Vpn {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_down: FormatTemplate,
+    tunnel: Option<String>,
+    manager: Manager,
+    sudo: bool,
+    handshake_warning: u64,
+    /// Whether the configured tunnel is up
+    up: bool,
+    interval: Duration,
+    command_runner: Rc<dyn CommandRunner>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct VpnConfig {
+    /// The connection of NetworkManager or the configuration of wg-quick to show and to bring up
+    /// or down on click, instead of all tunnels
+    pub tunnel: Option<String>,
+
+    /// How to bring the tunnel up or down
+    pub manager: Manager,
+
+    /// Run wg-quick with `sudo`, which has to allow it without a password
+    pub sudo: bool,
+
+    /// Seconds since the last WireGuard handshake from which on the block is shown as warning
+    pub handshake_warning: u64,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Format override
+    pub format: FormatTemplate,
+
+    /// Format override while no tunnel is up
+    pub format_down: FormatTemplate,
+}
+
+impl Default for VpnConfig {
+    fn default() -> Self {
+        Self {
+            tunnel: None,
+            manager: Manager::NetworkManager,
+            sudo: false,
+            handshake_warning: 300,
+            interval: Duration::from_secs(10),
+            format: FormatTemplate::default(),
+            format_down: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Vpn {
+    type Config = VpnConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Vpn {
+            id,
+            text: TextWidget::new(id, 0, shared_config.clone()).with_icon("net_vpn")?,
+            format: block_config.format.with_default("{name}")?,
+            format_down: block_config.format_down.with_default("down")?,
+            tunnel: block_config.tunnel,
+            manager: block_config.manager,
+            sudo: block_config.sudo,
+            handshake_warning: block_config.handshake_warning,
+            up: false,
+            interval: block_config.interval,
+            command_runner: shared_config.command_runner,
+            tx_update_request,
+        })
+    }
+}
+
+/// The time of the newest handshake of every interface in the output of
+/// `wg show all latest-handshakes`, which has a line per peer, with 0 for no handshake yet
+fn parse_handshakes(output: &str) -> Vec<(String, u64)> {
+    let mut handshakes: Vec<(String, u64)> = Vec::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (interface, time) = match fields.as_slice() {
+            [interface, _peer, time] => (*interface, time.parse().unwrap_or(0)),
+            _ => continue,
+        };
+        match handshakes.iter_mut().find(|(i, _)| i == interface) {
+            Some((_, newest)) => *newest = (*newest).max(time),
+            None => handshakes.push((interface.to_string(), time)),
+        }
+    }
+    handshakes
+}
+
+/// The VPN interfaces that are up
+fn vpn_interfaces() -> Result<Vec<String>> {
+    let names = match bsd::bsd() {
+        Some(_) => bsd::interfaces("vpn")?,
+        None => fs::read_dir("/sys/class/net")
+            .block_error("vpn", "failed to list the network interfaces")?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect(),
+    };
+    Ok(names
+        .into_iter()
+        .map(NetworkDevice::from_device)
+        .filter(|device| device.is_vpn() && device.is_up().unwrap_or(false))
+        .map(|device| device.device())
+        .collect())
+}
+
+impl Vpn {
+    /// The interfaces of the tunnels to show
+    fn interfaces(&self) -> Result<Vec<String>> {
+        match (&self.tunnel, self.manager) {
+            (None, _) => vpn_interfaces(),
+            (Some(tunnel), Manager::NetworkManager) => {
+                let output = self
+                    .command_runner
+                    .run("nmcli -t -f NAME,DEVICE connection show --active")?;
+                // Colons in names are escaped, but interfaces have none
+                Ok(output
+                    .stdout
+                    .lines()
+                    .filter_map(|line| line.rsplit_once(':'))
+                    .filter(|(name, _)| name.replace("\\:", ":") == *tunnel)
+                    .map(|(_, device)| device.to_string())
+                    .collect())
+            }
+            // wg-quick names the interface after the configuration
+            (Some(tunnel), Manager::WgQuick) => Ok(vpn_interfaces()?
+                .into_iter()
+                .filter(|interface| interface == tunnel)
+                .collect()),
+        }
+    }
+}
+
+impl Block for Vpn {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let interfaces = self.interfaces()?;
+        // A configured tunnel is shown by its name, which may differ from its interface
+        let name = self.tunnel.clone().unwrap_or_else(|| interfaces.join(", "));
+        self.up = !interfaces.is_empty();
+        if !self.up {
+            let values = map!("name" => Value::from_string(name));
+            self.text.set_texts(self.format_down.render(&values)?);
+            self.text.set_state(State::Idle);
+            return Ok(Some(self.interval.into()));
+        }
+
+        // Reading the handshakes needs CAP_NET_ADMIN, without it they are unknown
+        let output = self.command_runner.run("wg show all latest-handshakes")?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        let handshake = parse_handshakes(&output.stdout)
+            .into_iter()
+            .filter(|(interface, time)| interfaces.contains(interface) && *time > 0)
+            .map(|(_, time)| time)
+            .max()
+            .map(|time| now.saturating_sub(time));
+
+        let values = map!(
+            "name" => Value::from_string(name),
+            "count" => Value::from_integer(interfaces.len() as i64),
+            "handshake" => match handshake {
+                Some(age) => Value::from_integer(age as i64).seconds(),
+                None => Value::from_string("-".to_string()),
+            },
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_state(match handshake {
+            Some(age) if age >= self.handshake_warning => State::Warning,
+            _ => State::Good,
+        });
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let tunnel = match &self.tunnel {
+            Some(tunnel) if event.button == MouseButton::Left => tunnel,
+            _ => return Ok(()),
+        };
+        let action = if self.up { "down" } else { "up" };
+        let command = match self.manager {
+            Manager::NetworkManager => format!("nmcli connection {} id '{}'", action, tunnel),
+            Manager::WgQuick => format!(
+                "{}wg-quick {} '{}'",
+                if self.sudo { "sudo -n " } else { "" },
+                action,
+                tunnel
+            ),
+        };
+        let id = self.id;
+        let tx_update_request = self.tx_update_request.clone();
+        // Show the new state once the tunnel is up or down
+        thread::Builder::new()
+            .name("vpn".into())
+            .spawn(move || {
+                let _ = Command::new("sh").args(["-c", &command]).status();
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("vpn", "failed to start a thread")?;
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_handshakes() {
+        let output = "wg0\tx3Vt4NkSEgCL2Rl1mN5Qd0Oi8q0DgKZ3pZhW1S6eBnU=\t1634290000\n\
+                      wg0\t9dQ2bFj1XvH7aLk0mP4sR6tU8wY3zC5eG1iK7nO2qSs=\t1634290100\n\
+                      wg1\tq8Wn3ZpL0sV6xB2dF4hJ7kM9rT1uY5aC3eG6iN8oQw=\t0\n";
+        assert_eq!(
+            parse_handshakes(output),
+            vec![("wg0".to_string(), 1_634_290_100), ("wg1".to_string(), 0)]
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
+use crate::blocks::base_block::{follow_inotify, FileWatch};
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
@@ -27,6 +27,7 @@ pub struct Watson {
     show_time: bool,
     prev_state: Option<WatsonState>,
     update_interval: Duration,
+    _watch: FileWatch,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -64,63 +65,41 @@ impl ConfigBlock for Watson {
         shared_config: SharedConfig,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let watson = Watson {
+        // Split filepath into filename and parent directory
+        let (file_name, parent_dir) = {
+            let name = block_config
+                .state_path
+                .file_name()
+                .block_error("watson", "watson state file had no name")?
+                .to_owned();
+
+            let mut s = block_config.state_path.clone();
+            s.pop();
+            (name, s)
+        };
+        let mut notify = Inotify::init().block_error("watson", "failed to start inotify")?;
+
+        // We have to watch the parent directory because watson never modifies the state file,
+        // but rather write to a temporary file, ensures its not corrupted, backups the
+        // previous state file and then renames the new state file. This means that we're
+        // always looking for `CREATE` events with the name of the state file.
+        notify
+            .add_watch(&parent_dir, WatchMask::CREATE)
+            .block_error("watson", "failed to watch watson state file")?;
+        // Schedules an update whenever the state file changes, until the block is gone
+        let watch = follow_inotify(notify, id, tx_update_request, move |event| {
+            event.mask.contains(EventMask::CREATE) && event.name == Some(file_name.as_os_str())
+        })?;
+
+        Ok(Watson {
             id,
             text: TextWidget::new(id, 0, shared_config),
-            state_path: block_config.state_path.clone(),
+            state_path: block_config.state_path,
             show_time: block_config.show_time,
             update_interval: block_config.interval,
             prev_state: None,
-        };
-
-        // Spin up a thread to watch for changes to the watson state file
-        // and schedule an update if needed.
-        thread::spawn(move || {
-            // Split filepath into filename and parent directory
-            let (file_name, parent_dir) = {
-                let name = block_config
-                    .state_path
-                    .file_name()
-                    .expect("watson state file had no name")
-                    .to_owned();
-
-                let mut s = block_config.state_path;
-                s.pop();
-                (name, s)
-            };
-            let mut notify = Inotify::init().expect("failed to start inotify");
-
-            // We have to watch the parent directory because watson never modifies the state file,
-            // but rather write to a temporary file, ensures its not corrupted, backups the
-            // previous state file and then renames the new state file. This means that we're
-            // always looking for `CREATE` events with the name of the state file.
-            notify
-                .add_watch(&parent_dir, WatchMask::CREATE)
-                .expect("failed to watch watson state file");
-
-            let mut buffer = [0; 1024];
-            loop {
-                let events = notify
-                    .read_events_blocking(&mut buffer)
-                    .expect("error while reading inotify events");
-
-                for event in events {
-                    match event.mask {
-                        EventMask::CREATE if event.name == Some(&file_name) => {
-                            tx_update_request
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .expect("unable to send task from watson watcher");
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        });
-
-        Ok(watson)
+            _watch: watch,
+        })
     }
 }
 
@@ -4,6 +4,7 @@ use std::time::Duration;
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
@@ -20,6 +21,9 @@ const OPENWEATHERMAP_API_KEY_ENV: &str = "OPENWEATHERMAP_API_KEY";
 const OPENWEATHERMAP_CITY_ID_ENV: &str = "OPENWEATHERMAP_CITY_ID";
 const OPENWEATHERMAP_PLACE_ENV: &str = "OPENWEATHERMAP_PLACE";
 
+/// met.no refuses requests with more precise coordinates
+const METNO_MAX_PRECISION: usize = 4;
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(tag = "name", rename_all = "lowercase")]
 pub enum WeatherService {
@@ -31,10 +35,19 @@ pub enum WeatherService {
         #[serde(default = "WeatherService::getenv_openweathermap_place")]
         place: Option<String>,
         coordinates: Option<(String, String)>,
-        units: OpenWeatherMapUnits,
+        units: WeatherUnits,
         #[serde(default = "WeatherService::default_lang")]
         lang: Option<String>,
     },
+    /// The Norwegian Meteorological Institute, which needs no API key
+    MetNo {
+        coordinates: Option<(String, String)>,
+        #[serde(default = "WeatherService::default_units")]
+        units: WeatherUnits,
+        /// Shown as the location, since met.no only knows coordinates
+        #[serde(default)]
+        location: Option<String>,
+    },
 }
 
 impl WeatherService {
@@ -50,23 +63,136 @@ impl WeatherService {
     fn default_lang() -> Option<String> {
         Some("en".to_string())
     }
+    fn default_units() -> WeatherUnits {
+        WeatherUnits::Metric
+    }
+
+    fn into_provider(self) -> Box<dyn WeatherProvider> {
+        match self {
+            WeatherService::OpenWeatherMap {
+                api_key,
+                city_id,
+                place,
+                coordinates,
+                units,
+                lang,
+            } => Box::new(OpenWeatherMap {
+                api_key,
+                city_id,
+                place,
+                coordinates,
+                units,
+                lang: lang.unwrap_or_else(|| "en".to_string()),
+            }),
+            WeatherService::MetNo {
+                coordinates,
+                units,
+                location,
+            } => Box::new(MetNo {
+                coordinates,
+                units,
+                location,
+            }),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
-pub enum OpenWeatherMapUnits {
+pub enum WeatherUnits {
     Metric,
     Imperial,
 }
 
-pub struct Weather {
-    id: usize,
-    weather: TextWidget,
-    format: FormatTemplate,
-    weather_keys: HashMap<&'static str, Value>,
-    service: WeatherService,
-    update_interval: Duration,
-    autolocate: bool,
+/// Kinds of weather that have an icon
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Condition {
+    Clear,
+    Clouds,
+    Fog,
+    Rain,
+    Snow,
+    Thunder,
+    Other,
+}
+
+impl Condition {
+    /// The name OpenWeatherMap uses, for providers that don't have names of their own
+    fn name(self) -> &'static str {
+        match self {
+            Condition::Clear => "Clear",
+            Condition::Clouds => "Clouds",
+            Condition::Fog => "Fog",
+            Condition::Rain => "Rain",
+            Condition::Snow => "Snow",
+            Condition::Thunder => "Thunderstorm",
+            Condition::Other => "Unknown",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Condition::Clear => "weather_sun",
+            Condition::Rain => "weather_rain",
+            Condition::Clouds | Condition::Fog => "weather_clouds",
+            Condition::Thunder => "weather_thunder",
+            Condition::Snow => "weather_snow",
+            Condition::Other => "weather_default",
+        }
+    }
+}
+
+/// The current weather as reported by a provider, in the units it was configured with
+#[derive(Clone, Debug, PartialEq)]
+struct Observation {
+    condition: Condition,
+    /// Brief description, e.g. "Rain"
+    weather: String,
+    /// Verbose description, e.g. "light rain"
+    weather_verbose: String,
+    temp: f64,
+    humidity: f64,
+    /// In m/s or mph
+    wind_speed: f64,
+    wind_direction: Option<f64>,
+    location: String,
+}
+
+/// Where the IP location service puts the user
+struct IpLocation {
+    city: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// A source of current weather
+trait WeatherProvider {
+    fn units(&self) -> WeatherUnits;
+
+    /// Rounds the coordinates to `precision` decimal places before they are sent
+    fn round_coordinates(&mut self, precision: Option<usize>) -> Result<()>;
+
+    /// Whether a location to fall back to is configured
+    fn has_location(&self) -> bool;
+
+    /// The current weather, at `autolocated` if the block is set to find the location itself and
+    /// that worked
+    fn observe(&self, autolocated: Option<&IpLocation>) -> Result<Observation>;
+}
+
+struct OpenWeatherMap {
+    api_key: Option<String>,
+    city_id: Option<String>,
+    place: Option<String>,
+    coordinates: Option<(String, String)>,
+    units: WeatherUnits,
+    lang: String,
+}
+
+struct MetNo {
+    coordinates: Option<(String, String)>,
+    units: WeatherUnits,
+    location: Option<String>,
 }
 
 fn malformed_json_error() -> Error {
@@ -74,21 +200,26 @@ fn malformed_json_error() -> Error {
 }
 
 // TODO: might be good to allow for different geolocation services to be used, similar to how we have `service` for the weather API
-fn find_ip_location() -> Result<Option<String>> {
+fn find_ip_location() -> Result<Option<IpLocation>> {
     let http_call_result = http::http_get_json(
         "https://ipapi.co/json/",
         Some(Duration::from_secs(3)),
         vec![],
     )?;
-
-    let city = http_call_result
-        .content
-        .pointer("/city")
-        .map(|v| v.as_str())
-        .flatten()
-        .map(|s| s.to_string());
-
-    Ok(city)
+    let json = http_call_result.content;
+
+    let city = json.pointer("/city").and_then(|v| v.as_str());
+    let latitude = json.pointer("/latitude").and_then(|v| v.as_f64());
+    let longitude = json.pointer("/longitude").and_then(|v| v.as_f64());
+
+    Ok(match (city, latitude, longitude) {
+        (Some(city), Some(latitude), Some(longitude)) => Some(IpLocation {
+            city: city.to_string(),
+            latitude,
+            longitude,
+        }),
+        _ => None,
+    })
 }
 
 // Compute the Australian Apparent Temperature (AT),
@@ -98,11 +229,11 @@ fn australian_apparent_temp(
     raw_temp: f64,
     raw_humidity: f64,
     raw_wind_speed: f64,
-    units: OpenWeatherMapUnits,
+    units: WeatherUnits,
 ) -> f64 {
-    let metric = units == OpenWeatherMapUnits::Metric;
+    let metric = units == WeatherUnits::Metric;
 
-    let temp_celsius = if units == OpenWeatherMapUnits::Metric {
+    let temp_celsius = if units == WeatherUnits::Metric {
         raw_temp
     } else {
         // convert Fahrenheit to Celsius
@@ -146,160 +277,349 @@ fn convert_wind_direction(direction_opt: Option<f64>) -> String {
     }
 }
 
-fn configuration_error(msg: &str) -> Result<()> {
+/// Rounds a coordinate to `precision` decimal places, so that the weather service learns less
+/// about where the user is. Two places are about a kilometer.
+fn round_coordinate(coordinate: &str, precision: usize) -> Result<String> {
+    let coordinate: f64 = coordinate
+        .trim()
+        .parse()
+        .configuration_error("coordinates must be numbers")?;
+    Ok(format!("{:.*}", precision, coordinate))
+}
+
+fn round_coordinates(
+    coordinates: &mut Option<(String, String)>,
+    precision: Option<usize>,
+) -> Result<()> {
+    if let (Some((lat, lon)), Some(precision)) = (coordinates, precision) {
+        *lat = round_coordinate(lat, precision)?;
+        *lon = round_coordinate(lon, precision)?;
+    }
+    Ok(())
+}
+
+fn configuration_error<T>(msg: &str) -> Result<T> {
     Err(ConfigurationError("weather".to_owned(), msg.to_owned()))
 }
 
+impl WeatherProvider for OpenWeatherMap {
+    fn units(&self) -> WeatherUnits {
+        self.units
+    }
+
+    fn round_coordinates(&mut self, precision: Option<usize>) -> Result<()> {
+        round_coordinates(&mut self.coordinates, precision)
+    }
+
+    fn has_location(&self) -> bool {
+        self.city_id.is_some() || self.place.is_some() || self.coordinates.is_some()
+    }
+
+    fn observe(&self, autolocated: Option<&IpLocation>) -> Result<Observation> {
+        let api_key = match &self.api_key {
+            Some(api_key) => api_key,
+            None => {
+                return configuration_error(&format!(
+                    "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+                    OPENWEATHERMAP_API_KEY_ENV))
+            }
+        };
+
+        let location_query = if let Some(location) = autolocated {
+            format!("q={}", location.city)
+        } else if let Some(cid) = self.city_id.as_ref() {
+            format!("id={}", cid)
+        } else if let Some(p) = self.place.as_ref() {
+            format!("q={}", p)
+        } else if let Some((lat, lon)) = &self.coordinates {
+            format!("lat={}&lon={}", lat, lon)
+        } else {
+            return configuration_error(&format!(
+                "Either 'service.city_id' or 'service.place' must be provided. Add one to your config file or set with the environment variables {} or {}",
+                OPENWEATHERMAP_CITY_ID_ENV,
+                OPENWEATHERMAP_PLACE_ENV));
+        };
+
+        // This uses the "Current Weather Data" API endpoint
+        // Refer to https://openweathermap.org/current
+        let openweather_url = &format!(
+            "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units={units}&lang={lang}",
+            location_query = location_query,
+            api_key = api_key,
+            units = match self.units {
+                WeatherUnits::Metric => "metric",
+                WeatherUnits::Imperial => "imperial",
+            },
+            lang = self.lang,
+        );
+
+        let output = http::http_get_json(openweather_url, Some(Duration::from_secs(3)), vec![])?;
+
+        // All 300-399 and >500 http codes should be considered as temporary error,
+        // and not result in block error, i.e. leave the output empty.
+        if (output.code >= 300 && output.code < 400) || output.code >= 500 {
+            return Err(BlockError(
+                "weather".to_owned(),
+                format!("Invalid result from curl: {}", output.code),
+            ));
+        };
+
+        let json = output.content;
+
+        // Try to convert an API error into a block error.
+        if let Some(val) = json.get("message") {
+            return Err(BlockError(
+                "weather".to_string(),
+                format!("API Error: {}", val.as_str().unwrap()),
+            ));
+        };
+
+        let raw_weather = json
+            .pointer("/weather/0/main")
+            .and_then(|v| v.as_str())
+            .ok_or_else(malformed_json_error)?
+            .to_string();
+
+        let raw_weather_verbose = json
+            .pointer("/weather/0/description")
+            .and_then(|v| v.as_str())
+            .ok_or_else(malformed_json_error)?
+            .to_string();
+
+        let raw_temp = json
+            .pointer("/main/temp")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?;
+
+        let raw_humidity = json
+            .pointer("/main/humidity")
+            .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+            .ok_or_else(malformed_json_error)?;
+
+        let raw_wind_speed: f64 = json
+            .pointer("/wind/speed")
+            .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+            .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+        let raw_wind_direction: Option<f64> = json
+            .pointer("/wind/deg")
+            .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
+            .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+        let raw_location = json
+            .pointer("/name")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(malformed_json_error)?;
+
+        Ok(Observation {
+            condition: match raw_weather.as_str() {
+                "Clear" => Condition::Clear,
+                "Rain" | "Drizzle" => Condition::Rain,
+                "Clouds" => Condition::Clouds,
+                "Fog" | "Mist" => Condition::Fog,
+                "Thunderstorm" => Condition::Thunder,
+                "Snow" => Condition::Snow,
+                _ => Condition::Other,
+            },
+            weather: raw_weather,
+            weather_verbose: raw_weather_verbose,
+            temp: raw_temp,
+            humidity: raw_humidity,
+            wind_speed: raw_wind_speed,
+            wind_direction: raw_wind_direction,
+            location: raw_location,
+        })
+    }
+}
+
+/// The words that met.no symbol codes like "lightrainshowersandthunder_day" are made of
+const METNO_WORDS: &[&str] = &[
+    "and", "clear", "cloudy", "fair", "fog", "heavy", "light", "partly", "rain", "showers", "sky",
+    "sleet", "snow", "thunder",
+];
+
+/// The condition and verbose description of a met.no symbol code
+fn metno_symbol(symbol_code: &str) -> (Condition, String) {
+    let code = symbol_code.split('_').next().unwrap_or_default();
+    let mut words = Vec::new();
+    let mut rest = code;
+    while !rest.is_empty() {
+        match METNO_WORDS.iter().find(|word| rest.starts_with(*word)) {
+            Some(word) => {
+                words.push(*word);
+                rest = &rest[word.len()..];
+            }
+            None => {
+                words.push(rest);
+                break;
+            }
+        }
+    }
+
+    let condition = if code.contains("thunder") {
+        Condition::Thunder
+    } else if code.contains("snow") || code.contains("sleet") {
+        Condition::Snow
+    } else if code.contains("rain") {
+        Condition::Rain
+    } else if code == "fog" {
+        Condition::Fog
+    } else if code.contains("cloudy") {
+        Condition::Clouds
+    } else if code == "clearsky" || code == "fair" {
+        Condition::Clear
+    } else {
+        Condition::Other
+    };
+    (condition, words.join(" "))
+}
+
+impl MetNo {
+    /// The current weather in a response of the Locationforecast API, in metric units
+    fn parse(json: &JsonValue) -> Result<Observation> {
+        let now = json
+            .pointer("/properties/timeseries/0/data")
+            .ok_or_else(malformed_json_error)?;
+        let details = &now["instant"]["details"];
+        let symbol_code = now
+            .pointer("/next_1_hours/summary/symbol_code")
+            .or_else(|| now.pointer("/next_6_hours/summary/symbol_code"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let (condition, weather_verbose) = metno_symbol(symbol_code);
+
+        Ok(Observation {
+            condition,
+            weather: condition.name().to_string(),
+            weather_verbose,
+            temp: details["air_temperature"]
+                .as_f64()
+                .ok_or_else(malformed_json_error)?,
+            humidity: details["relative_humidity"].as_f64().unwrap_or(0.0),
+            wind_speed: details["wind_speed"].as_f64().unwrap_or(0.0),
+            wind_direction: details["wind_from_direction"].as_f64(),
+            location: String::new(),
+        })
+    }
+}
+
+impl WeatherProvider for MetNo {
+    fn units(&self) -> WeatherUnits {
+        self.units
+    }
+
+    fn round_coordinates(&mut self, precision: Option<usize>) -> Result<()> {
+        let precision = precision.map_or(METNO_MAX_PRECISION, |p| p.min(METNO_MAX_PRECISION));
+        round_coordinates(&mut self.coordinates, Some(precision))
+    }
+
+    fn has_location(&self) -> bool {
+        self.coordinates.is_some()
+    }
+
+    fn observe(&self, autolocated: Option<&IpLocation>) -> Result<Observation> {
+        let (lat, lon) = match (autolocated, &self.coordinates) {
+            (Some(location), _) => (
+                round_coordinate(&location.latitude.to_string(), METNO_MAX_PRECISION)?,
+                round_coordinate(&location.longitude.to_string(), METNO_MAX_PRECISION)?,
+            ),
+            (None, Some((lat, lon))) => (lat.clone(), lon.clone()),
+            (None, None) => return configuration_error("'service.coordinates' must be provided"),
+        };
+
+        // This uses the compact Locationforecast, whose first entry is the current weather.
+        // Refer to https://api.met.no/weatherapi/locationforecast/2.0/documentation
+        let url = format!(
+            "https://api.met.no/weatherapi/locationforecast/2.0/compact?lat={}&lon={}",
+            lat, lon
+        );
+        // met.no blocks requests that don't identify the application
+        let user_agent = format!(
+            "i3status-rust/{} https://github.com/greshake/i3status-rust",
+            env!("CARGO_PKG_VERSION")
+        );
+        let output = http::http_get_json(
+            &url,
+            Some(Duration::from_secs(3)),
+            vec![("User-Agent", &user_agent)],
+        )?;
+        if output.code != 200 {
+            return Err(BlockError(
+                "weather".to_owned(),
+                format!("Invalid result from curl: {}", output.code),
+            ));
+        }
+
+        let mut observation = Self::parse(&output.content)?;
+        if self.units == WeatherUnits::Imperial {
+            observation.temp = observation.temp * 1.8 + 32.0;
+            // convert m/s to mph
+            observation.wind_speed /= 0.447;
+        }
+        observation.location = match (&self.location, autolocated) {
+            (Some(location), _) => location.clone(),
+            (None, Some(location)) => location.city.clone(),
+            (None, None) => String::new(),
+        };
+        Ok(observation)
+    }
+}
+
+pub struct Weather {
+    id: usize,
+    weather: TextWidget,
+    format: FormatTemplate,
+    weather_keys: HashMap<&'static str, Value>,
+    provider: Box<dyn WeatherProvider>,
+    update_interval: Duration,
+    autolocate: bool,
+}
+
 impl Weather {
     fn update_weather(&mut self) -> Result<()> {
-        match &self.service {
-            WeatherService::OpenWeatherMap {
-                api_key: api_key_opt,
-                city_id,
-                place,
-                units,
-                coordinates,
-                lang,
-            } => {
-                if api_key_opt.is_none() {
-                    return configuration_error(&format!(
-                        "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
-                        OPENWEATHERMAP_API_KEY_ENV));
-                }
-
-                let api_key = api_key_opt.as_ref().unwrap();
-
-                let geoip_city = if self.autolocate {
-                    find_ip_location().ok().unwrap_or(None) // If geo location fails, try other configuration methods
-                } else {
-                    None
-                };
-
-                let location_query = if let Some(city) = geoip_city {
-                    format!("q={}", city)
-                } else if let Some(cid) = city_id.as_ref() {
-                    format!("id={}", cid)
-                } else if let Some(p) = place.as_ref() {
-                    format!("q={}", p)
-                } else if let Some((lat, lon)) = coordinates {
-                    format!("lat={}&lon={}", lat, lon)
-                } else if self.autolocate {
-                    return configuration_error(
-                        "weather is configured to use geolocation, but it could not be obtained",
-                    );
-                } else {
-                    return configuration_error(&format!(
-                        "Either 'service.city_id' or 'service.place' must be provided. Add one to your config file or set with the environment variables {} or {}",
-                        OPENWEATHERMAP_CITY_ID_ENV,
-                        OPENWEATHERMAP_PLACE_ENV));
-                };
-
-                // This uses the "Current Weather Data" API endpoint
-                // Refer to https://openweathermap.org/current
-                let openweather_url = &format!(
-                    "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units={units}&lang={lang}",
-                    location_query = location_query,
-                    api_key = api_key,
-                    units = match *units {
-                        OpenWeatherMapUnits::Metric => "metric",
-                        OpenWeatherMapUnits::Imperial => "imperial",
-                    },
-                    lang = lang.as_ref().unwrap(),
-                );
-
-                let output =
-                    http::http_get_json(openweather_url, Some(Duration::from_secs(3)), vec![])?;
-
-                // All 300-399 and >500 http codes should be considered as temporary error,
-                // and not result in block error, i.e. leave the output empty.
-                if (output.code >= 300 && output.code < 400) || output.code >= 500 {
-                    return Err(BlockError(
-                        "weather".to_owned(),
-                        format!("Invalid result from curl: {}", output.code),
-                    ));
-                };
-
-                let json = output.content;
-
-                // Try to convert an API error into a block error.
-                if let Some(val) = json.get("message") {
-                    return Err(BlockError(
-                        "weather".to_string(),
-                        format!("API Error: {}", val.as_str().unwrap()),
-                    ));
-                };
-
-                let raw_weather = json
-                    .pointer("/weather/0/main")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(malformed_json_error)?
-                    .to_string();
-
-                let raw_weather_verbose = json
-                    .pointer("/weather/0/description")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(malformed_json_error)?
-                    .to_string();
-
-                let raw_temp = json
-                    .pointer("/main/temp")
-                    .and_then(|v| v.as_f64())
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_humidity = json
-                    .pointer("/main/humidity")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_wind_speed: f64 = json
-                    .pointer("/wind/speed")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_wind_direction: Option<f64> = json
-                    .pointer("/wind/deg")
-                    .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_location = json
-                    .pointer("/name")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(malformed_json_error)?;
-
-                self.weather.set_icon(match raw_weather.as_str() {
-                    "Clear" => "weather_sun",
-                    "Rain" | "Drizzle" => "weather_rain",
-                    "Clouds" | "Fog" | "Mist" => "weather_clouds",
-                    "Thunderstorm" => "weather_thunder",
-                    "Snow" => "weather_snow",
-                    _ => "weather_default",
-                })?;
-
-                let kmh_wind_speed = if *units == OpenWeatherMapUnits::Metric {
-                    raw_wind_speed * 3600.0 / 1000.0
-                } else {
-                    // convert mph to m/s, then km/h
-                    (raw_wind_speed * 0.447) * 3600.0 / 1000.0
-                };
-
-                let apparent_temp =
-                    australian_apparent_temp(raw_temp, raw_humidity, raw_wind_speed, *units);
-
-                self.weather_keys = map!(
-                    "weather" => Value::from_string(raw_weather),
-                    "weather_verbose" => Value::from_string(raw_weather_verbose),
-                    "temp" => Value::from_integer(raw_temp as i64).degrees(),
-                    "humidity" => Value::from_integer(raw_humidity as i64),
-                    "apparent" => Value::from_integer(apparent_temp as i64).degrees(),
-                    "wind" => Value::from_float(raw_wind_speed),
-                    "wind_kmh" => Value::from_float(kmh_wind_speed),
-                    "direction" => Value::from_string(convert_wind_direction(raw_wind_direction)),
-                    "location" => Value::from_string(raw_location),
-                );
-                Ok(())
-            }
+        let autolocated = if self.autolocate {
+            find_ip_location().ok().unwrap_or(None) // If geo location fails, try other configuration methods
+        } else {
+            None
+        };
+        if self.autolocate && autolocated.is_none() && !self.provider.has_location() {
+            return configuration_error(
+                "weather is configured to use geolocation, but it could not be obtained",
+            );
         }
+
+        let observation = self.provider.observe(autolocated.as_ref())?;
+        let units = self.provider.units();
+
+        self.weather.set_icon(observation.condition.icon())?;
+
+        let kmh_wind_speed = if units == WeatherUnits::Metric {
+            observation.wind_speed * 3600.0 / 1000.0
+        } else {
+            // convert mph to m/s, then km/h
+            (observation.wind_speed * 0.447) * 3600.0 / 1000.0
+        };
+
+        let apparent_temp = australian_apparent_temp(
+            observation.temp,
+            observation.humidity,
+            observation.wind_speed,
+            units,
+        );
+
+        self.weather_keys = map!(
+            "weather" => Value::from_string(observation.weather),
+            "weather_verbose" => Value::from_string(observation.weather_verbose),
+            "temp" => Value::from_integer(observation.temp as i64).degrees(),
+            "humidity" => Value::from_integer(observation.humidity as i64),
+            "apparent" => Value::from_integer(apparent_temp as i64).degrees(),
+            "wind" => Value::from_float(observation.wind_speed),
+            "wind_kmh" => Value::from_float(kmh_wind_speed),
+            "direction" => Value::from_string(convert_wind_direction(observation.wind_direction)),
+            "location" => Value::from_string(observation.location),
+        );
+        Ok(())
     }
 }
 
@@ -316,6 +636,9 @@ pub struct WeatherConfig {
     pub service: WeatherService,
     #[serde(default)]
     pub autolocate: bool,
+    /// Number of decimal places the coordinates are rounded to before they are sent
+    #[serde(default)]
+    pub coordinate_precision: Option<usize>,
 }
 
 impl WeatherConfig {
@@ -333,12 +656,15 @@ impl ConfigBlock for Weather {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let mut provider = block_config.service.into_provider();
+        provider.round_coordinates(block_config.coordinate_precision)?;
+
         Ok(Weather {
             id,
             weather: TextWidget::new(id, 0, shared_config),
             format: block_config.format.with_default("{weather} {temp}")?,
             weather_keys: HashMap::new(),
-            service: block_config.service,
+            provider,
             update_interval: block_config.interval,
             autolocate: block_config.autolocate,
         })
@@ -383,3 +709,36 @@ impl Block for Weather {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metno_parse() {
+        let json = serde_json::json!({
+            "properties": {"timeseries": [{
+                "time": "2021-10-15T12:00:00Z",
+                "data": {
+                    "instant": {"details": {
+                        "air_temperature": 11.3,
+                        "relative_humidity": 81.2,
+                        "wind_from_direction": 242.1,
+                        "wind_speed": 4.5,
+                    }},
+                    "next_1_hours": {"summary": {"symbol_code": "lightrainshowers_day"}},
+                },
+            }]},
+        });
+        let observation = MetNo::parse(&json).unwrap();
+        assert_eq!(observation.condition, Condition::Rain);
+        assert_eq!(observation.weather, "Rain");
+        assert_eq!(observation.weather_verbose, "light rain showers");
+        assert_eq!(observation.temp, 11.3);
+        assert_eq!(observation.wind_direction, Some(242.1));
+        assert_eq!(
+            metno_symbol("partlycloudy_night"),
+            (Condition::Clouds, "partly cloudy".to_string())
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Shows the time in several time zones at once, or one at a time cycling on click
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneConfig {
+    /// IANA name of the time zone, e.g. "America/New_York"
+    pub timezone: Tz,
+
+    /// Defaults to the city of the time zone, e.g. "New York"
+    pub label: Option<String>,
+
+    /// Format override for this zone
+    #[serde(default)]
+    pub format: Option<FormatTemplate>,
+}
+
+struct Zone {
+    timezone: Tz,
+    label: String,
+    format: FormatTemplate,
+}
+
+pub struct Worldclock {
+    id: usize,
+    widgets: Vec<TextWidget>,
+    zones: Vec<Zone>,
+    interval: Duration,
+    /// The zone shown while cycling
+    current: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct WorldclockConfig {
+    pub zones: Vec<ZoneConfig>,
+
+    /// Show one zone at a time and switch zones on click and scroll
+    pub cycle: bool,
+
+    /// Format of the zones without a format of their own. `{label}` is replaced by the label of
+    /// the zone, the rest is a format string of
+    /// [chrono](https://docs.rs/chrono/0.4/chrono/format/strftime/index.html#specifiers).
+    pub format: FormatTemplate,
+
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for WorldclockConfig {
+    fn default() -> Self {
+        Self {
+            zones: Vec::new(),
+            cycle: false,
+            format: FormatTemplate::default(),
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The city of a time zone, e.g. "Buenos Aires" for "America/Argentina/Buenos_Aires"
+fn city(timezone: Tz) -> String {
+    let name = timezone.name();
+    name.rsplit('/').next().unwrap_or(name).replace('_', " ")
+}
+
+impl ConfigBlock for Worldclock {
+    type Config = WorldclockConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.zones.is_empty() {
+            return Err(ConfigurationError(
+                "worldclock".to_string(),
+                "zones must be set".to_string(),
+            ));
+        }
+        let format = block_config.format.with_default("{label} %H:%M")?;
+        let zones: Vec<Zone> = block_config
+            .zones
+            .into_iter()
+            .map(|zone| {
+                let timezone = zone.timezone;
+                Zone {
+                    timezone,
+                    label: zone.label.unwrap_or_else(|| city(timezone)),
+                    format: zone.format.unwrap_or_else(|| format.clone()),
+                }
+            })
+            .collect();
+        let count = if block_config.cycle { 1 } else { zones.len() };
+        let widgets = (0..count)
+            .map(|instance| {
+                let widget = TextWidget::new(id, instance, shared_config.clone());
+                if instance == 0 {
+                    widget.with_icon("time")
+                } else {
+                    Ok(widget)
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Worldclock {
+            id,
+            widgets,
+            zones,
+            interval: block_config.interval,
+            current: if block_config.cycle { Some(0) } else { None },
+        })
+    }
+}
+
+impl Zone {
+    fn render(&self, now: DateTime<Utc>) -> Result<(String, Option<String>)> {
+        let values = map!(
+            "label" => Value::from_string(self.label.clone()),
+        );
+        let (full, short) = self.format.render(&values)?;
+        let time = now.with_timezone(&self.timezone);
+        Ok((
+            time.format(&full).to_string(),
+            short.map(|short| time.format(&short).to_string()),
+        ))
+    }
+}
+
+impl Worldclock {
+    fn set_texts(&mut self) -> Result<()> {
+        let now = Utc::now();
+        match self.current {
+            Some(current) => self.widgets[0].set_texts(self.zones[current].render(now)?),
+            None => {
+                for (widget, zone) in self.widgets.iter_mut().zip(&self.zones) {
+                    widget.set_texts(zone.render(now)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Block for Worldclock {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.set_texts()?;
+        Ok(Some(self.interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let count = self.zones.len();
+        if let Some(current) = self.current {
+            self.current = Some(match event.button {
+                MouseButton::Left | MouseButton::WheelUp => (current + 1) % count,
+                MouseButton::Right | MouseButton::WheelDown => (current + count - 1) % count,
+                _ => return Ok(()),
+            });
+            self.set_texts()?;
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets.iter().map(|w| w as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render() {
+        let zone = Zone {
+            timezone: "America/Argentina/Buenos_Aires".parse().unwrap(),
+            label: city("America/Argentina/Buenos_Aires".parse().unwrap()),
+            format: FormatTemplate::new("{label} %H:%M", Some("%H")).unwrap(),
+        };
+        let now = Utc.ymd(2021, 10, 5).and_hms(15, 30, 0);
+        assert_eq!(
+            zone.render(now).unwrap(),
+            ("Buenos Aires 12:30".to_string(), Some("12".to_string()))
+        );
+    }
+}
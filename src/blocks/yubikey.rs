@@ -0,0 +1,222 @@
+use std::collections::BTreeSet;
+use std::io::{ErrorKind, Read};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// How often the block switches colors while a touch is requested
+const FLASH_INTERVAL: Duration = Duration::from_millis(500);
+/// How long to wait before connecting to the detector again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Yubikey {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    /// The kinds of requests (`GPG`, `U2F`, `HMAC`) that wait for a touch
+    waiting: Arc<Mutex<BTreeSet<String>>>,
+    shown: bool,
+    flash: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct YubikeyConfig {
+    /// Socket of yubikey-touch-detector, `$XDG_RUNTIME_DIR/yubikey-touch-detector.socket` if unset
+    pub socket_path: Option<String>,
+
+    /// Format override
+    pub format: FormatTemplate,
+}
+
+/// Takes the complete messages of yubikey-touch-detector such as `GPG_1` off the start of
+/// `pending`, returning the kinds of the requests and whether they wait for a touch. The messages
+/// aren't separated, but all of them end with `_0` or `_1`.
+fn parse_messages(pending: &mut String) -> Vec<(String, bool)> {
+    let mut messages = Vec::new();
+    while let Some(separator) = pending.find('_') {
+        let state = match pending[separator + 1..].chars().next() {
+            Some(state) => state,
+            None => break,
+        };
+        let kind = pending[..separator].to_string();
+        pending.drain(..separator + 1 + state.len_utf8());
+        match state {
+            '1' => messages.push((kind, true)),
+            '0' => messages.push((kind, false)),
+            _ => {}
+        }
+    }
+    messages
+}
+
+/// Follows the messages of the detector, reconnecting whenever it goes away, until the block is
+/// gone
+fn watch_detector(
+    socket_path: PathBuf,
+    waiting: Arc<Mutex<BTreeSet<String>>>,
+    id: usize,
+    tx_update_request: Sender<Task>,
+) {
+    let request_update = || {
+        let _ = tx_update_request.send(Task {
+            id,
+            update_time: Instant::now(),
+        });
+    };
+    // Ends once the block is gone, which the read timeout makes sure to notice
+    while Arc::strong_count(&waiting) > 1 {
+        if let Ok(mut stream) = UnixStream::connect(&socket_path) {
+            let _ = stream.set_read_timeout(Some(RECONNECT_INTERVAL));
+            let mut buffer = [0; 64];
+            let mut pending = String::new();
+            while Arc::strong_count(&waiting) > 1 {
+                let len = match stream.read(&mut buffer) {
+                    Ok(len @ 1..=64) => len,
+                    Err(error)
+                        if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                    {
+                        continue
+                    }
+                    _ => break,
+                };
+                pending.push_str(&String::from_utf8_lossy(&buffer[..len]));
+                let messages = parse_messages(&mut pending);
+                if messages.is_empty() {
+                    continue;
+                }
+                let mut waiting = waiting.lock().unwrap();
+                for (kind, touch) in messages {
+                    if touch {
+                        waiting.insert(kind);
+                    } else {
+                        waiting.remove(&kind);
+                    }
+                }
+                drop(waiting);
+                request_update();
+            }
+            // Nothing can be waiting without the detector
+            waiting.lock().unwrap().clear();
+            request_update();
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+impl ConfigBlock for Yubikey {
+    type Config = YubikeyConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let socket_path = match block_config.socket_path {
+            Some(path) => PathBuf::from(shellexpand::tilde(&path).to_string()),
+            None => PathBuf::from(std::env::var("XDG_RUNTIME_DIR").block_error(
+                "yubikey",
+                "XDG_RUNTIME_DIR is not set, please set socket_path",
+            )?)
+            .join("yubikey-touch-detector.socket"),
+        };
+
+        let waiting = Arc::new(Mutex::new(BTreeSet::new()));
+        let waiting_copy = waiting.clone();
+        thread::Builder::new()
+            .name("yubikey".into())
+            .spawn(move || watch_detector(socket_path, waiting_copy, id, tx_update_request))
+            .block_error("yubikey", "failed to start watching thread")?;
+
+        Ok(Yubikey {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("yubikey")?,
+            format: block_config.format.with_default("touch {kind}")?,
+            waiting,
+            shown: false,
+            flash: false,
+        })
+    }
+}
+
+impl Block for Yubikey {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let waiting = self.waiting.lock().unwrap();
+        self.shown = !waiting.is_empty();
+        if !self.shown {
+            self.flash = false;
+            return Ok(None);
+        }
+
+        let kinds: Vec<&str> = waiting.iter().map(String::as_str).collect();
+        let values = map!(
+            "kind" => Value::from_string(kinds.join(" ")),
+        );
+        self.text.set_texts(self.format.render(&values)?);
+        self.flash = !self.flash;
+        self.text.set_state(if self.flash {
+            State::Critical
+        } else {
+            State::Warning
+        });
+
+        Ok(Some(FLASH_INTERVAL.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        // The block is only shown while a touch is requested
+        if self.shown {
+            vec![&self.text]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        let mut pending = "GPG_1HMAC_1U2F_0GP".to_string();
+        assert_eq!(
+            parse_messages(&mut pending),
+            vec![
+                ("GPG".to_string(), true),
+                ("HMAC".to_string(), true),
+                ("U2F".to_string(), false),
+            ]
+        );
+        assert_eq!(pending, "GP");
+
+        pending.push_str("G_");
+        assert_eq!(parse_messages(&mut pending), vec![]);
+        assert_eq!(pending, "GPG_");
+        pending.push('0');
+        assert_eq!(
+            parse_messages(&mut pending),
+            vec![("GPG".to_string(), false)]
+        );
+        assert!(pending.is_empty());
+    }
+}
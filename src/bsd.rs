@@ -0,0 +1,406 @@
+//! Backends for FreeBSD and OpenBSD, which have neither `/proc` nor `/sys`.
+//!
+//! Everything is read with `sysctl(8)` and the other tools of the base system instead of
+//! through libc, so the blocks pick their backend at runtime and this code is built (and
+//! checked) on every platform.
+
+use std::process::Command;
+
+use crate::errors::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bsd {
+    FreeBsd,
+    OpenBsd,
+}
+
+/// The BSD the bar runs on, if any
+pub fn bsd() -> Option<Bsd> {
+    match std::env::consts::OS {
+        "freebsd" => Some(Bsd::FreeBsd),
+        "openbsd" => Some(Bsd::OpenBsd),
+        _ => None,
+    }
+}
+
+/// Runs `command` and returns its output if it succeeded
+fn run(block: &str, command: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(command)
+        .args(args)
+        .output()
+        .block_error(block, &format!("failed to run {}", command))?;
+    if !output.status.success() {
+        return Err(BlockError(
+            block.to_string(),
+            format!("{} {} failed", command, args.join(" ")),
+        ));
+    }
+    String::from_utf8(output.stdout).block_error(block, "non-UTF8 output")
+}
+
+fn sysctl(block: &str, name: &str) -> Result<String> {
+    Ok(run(block, "sysctl", &["-n", name])?.trim().to_string())
+}
+
+fn sysctl_number(block: &str, name: &str) -> Result<u64> {
+    sysctl(block, name)?
+        .parse()
+        .block_error(block, &format!("failed to parse {}", name))
+}
+
+/// The idle and non-idle CPU ticks, first of all CPUs together and then of each core
+pub fn cpu_times() -> Result<Vec<(u64, u64)>> {
+    match bsd() {
+        Some(Bsd::FreeBsd) => {
+            // user, nice, system, interrupt and idle ticks
+            let mut times = parse_cp_time(&sysctl("cpu", "kern.cp_time")?, 5);
+            times.extend(parse_cp_time(&sysctl("cpu", "kern.cp_times")?, 5));
+            Ok(times)
+        }
+        Some(Bsd::OpenBsd) => {
+            // Separated by commas, and with an additional spin field since OpenBSD 6.4
+            let mut times = parse_cp_time(&sysctl("cpu", "kern.cp_time")?, 0);
+            for cpu in 0..sysctl_number("cpu", "hw.ncpu")? {
+                times.extend(parse_cp_time(
+                    &sysctl("cpu", &format!("kern.cp_time2.{}", cpu))?,
+                    0,
+                ));
+            }
+            Ok(times)
+        }
+        None => Err(BlockError("cpu".to_string(), "not a BSD".to_string())),
+    }
+}
+
+/// Splits a list of tick counters into groups of `fields` (all of them if `0`), in which the
+/// last one counts the idle ticks
+fn parse_cp_time(text: &str, fields: usize) -> Vec<(u64, u64)> {
+    let ticks: Vec<u64> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter_map(|tick| tick.parse().ok())
+        .collect();
+    if ticks.is_empty() {
+        return Vec::new();
+    }
+    let fields = if fields == 0 { ticks.len() } else { fields };
+    ticks
+        .chunks(fields)
+        .filter(|cpu| cpu.len() == fields)
+        .map(|cpu| {
+            let (idle, busy) = cpu.split_last().unwrap();
+            (*idle, busy.iter().sum())
+        })
+        .collect()
+}
+
+/// The current CPU frequency in Hz, which the BSDs only report for the package as a whole
+pub fn cpu_frequencies() -> Vec<f64> {
+    let name = match bsd() {
+        Some(Bsd::FreeBsd) => "dev.cpu.0.freq",
+        Some(Bsd::OpenBsd) => "hw.cpuspeed",
+        None => return Vec::new(),
+    };
+    // Not every CPU driver reports it
+    sysctl_number("cpu", name)
+        .map(|mhz| vec![mhz as f64 * 1e6])
+        .unwrap_or_default()
+}
+
+/// Memory statistics in KiB
+#[derive(Debug, Default)]
+pub struct MemoryInfo {
+    pub total: u64,
+    pub free: u64,
+    /// Inactive pages, which the kernel frees when memory runs low
+    pub cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+pub fn memory() -> Result<MemoryInfo> {
+    let vmstat = run("memory", "vmstat", &["-s"])?;
+    let pagesize = vmstat_value(&vmstat, "bytes per page").unwrap_or(4096);
+    let pages = |name| vmstat_value(&vmstat, name).unwrap_or(0) * pagesize / 1024;
+
+    // Lists swap devices as `<device> <size> <used> ...` in KiB, below a header that differs
+    let (swap_total, swap_used) = run("memory", "swapctl", &["-lk"])
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| line.starts_with('/'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let size: u64 = fields.next()?.parse().ok()?;
+            let used: u64 = fields.next()?.parse().ok()?;
+            Some((size, used))
+        })
+        .fold((0, 0), |(size, used), device| {
+            (size + device.0, used + device.1)
+        });
+
+    Ok(MemoryInfo {
+        total: sysctl_number("memory", "hw.physmem")? / 1024,
+        free: pages("pages free"),
+        cached: pages("pages inactive"),
+        swap_total,
+        swap_free: swap_total.saturating_sub(swap_used),
+    })
+}
+
+/// Looks up a counter in `vmstat -s` output, which has lines like `  123456 pages free`
+fn vmstat_value(vmstat: &str, name: &str) -> Option<u64> {
+    vmstat.lines().find_map(|line| {
+        let (value, description) = line.trim().split_once(' ')?;
+        if description.trim() == name {
+            value.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// All temperature sensors in °C, along with the sysctl names of their devices
+pub fn temperatures() -> Result<Vec<(String, f64)>> {
+    let output = match bsd() {
+        // Fails if any of the subtrees is missing, but prints the others regardless
+        Some(Bsd::FreeBsd) => {
+            Command::new("sysctl")
+                .args(["-e", "dev.cpu", "hw.acpi.thermal"])
+                .output()
+                .block_error("temperature", "failed to run sysctl")?
+                .stdout
+        }
+        Some(Bsd::OpenBsd) => run("temperature", "sysctl", &["hw.sensors"])?.into_bytes(),
+        None => return Ok(Vec::new()),
+    };
+    Ok(parse_temperatures(&String::from_utf8_lossy(&output)))
+}
+
+/// Picks the temperatures from `name=value` lines like `dev.cpu.0.temperature=45.0C` (FreeBSD)
+/// or `hw.sensors.cpu0.temp0=45.00 degC` (OpenBSD), along with names like `dev.cpu.0`
+fn parse_temperatures(sysctl: &str) -> Vec<(String, f64)> {
+    sysctl
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            let (device, sensor) = name.rsplit_once('.')?;
+            let is_temperature = sensor == "temperature"
+                || sensor
+                    .strip_prefix("temp")
+                    .map(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+                    .unwrap_or(false);
+            if !is_temperature || !value.contains('C') {
+                return None;
+            }
+            let value = value
+                .trim()
+                .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                .next()?
+                .parse()
+                .ok()?;
+            Some((device.to_string(), value))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct BatteryInfo {
+    /// Percent
+    pub capacity: u64,
+    /// As in `/sys/class/power_supply/*/status`
+    pub status: &'static str,
+    /// Minutes until the battery is empty
+    pub time_remaining: Option<u64>,
+}
+
+/// The state of the battery, `None` if there is none
+pub fn battery() -> Result<Option<BatteryInfo>> {
+    match bsd() {
+        Some(Bsd::FreeBsd) => {
+            if sysctl_number("battery", "hw.acpi.battery.units").unwrap_or(0) == 0 {
+                return Ok(None);
+            }
+            // A bitmask of discharging (1), charging (2) and critical (4)
+            let state = sysctl_number("battery", "hw.acpi.battery.state")?;
+            let status = if state & 2 != 0 {
+                "Charging"
+            } else if state & 1 != 0 {
+                "Discharging"
+            } else {
+                "Not charging"
+            };
+            Ok(Some(BatteryInfo {
+                capacity: sysctl_number("battery", "hw.acpi.battery.life")?,
+                status,
+                // -1 while charging or on AC
+                time_remaining: sysctl("battery", "hw.acpi.battery.time")?.parse().ok(),
+            }))
+        }
+        Some(Bsd::OpenBsd) => {
+            let apm = |flag| run("battery", "apm", &[flag]).map(|out| out.trim().to_string());
+            // High (0), low (1), critical (2), charging (3), absent (4) or unknown (255)
+            let status = match apm("-b")?.as_str() {
+                "4" => return Ok(None),
+                "3" => "Charging",
+                _ if apm("-a")? == "1" => "Not charging",
+                _ => "Discharging",
+            };
+            Ok(Some(BatteryInfo {
+                capacity: apm("-l")?
+                    .parse()
+                    .block_error("battery", "failed to parse battery life")?,
+                status,
+                // `unknown` while charging or on AC
+                time_remaining: apm("-m")?.parse().ok(),
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The names of all network interfaces
+pub fn interfaces(block: &str) -> Result<Vec<String>> {
+    Ok(run(block, "ifconfig", &["-l"])?
+        .split_whitespace()
+        .map(str::to_string)
+        .collect())
+}
+
+/// The output of `ifconfig <device>`, `None` if the device doesn't exist
+pub fn ifconfig(device: &str) -> Option<String> {
+    run("net", "ifconfig", &[device]).ok()
+}
+
+/// Whether the interface is up and, if it reports a link state, connected
+pub fn ifconfig_is_up(ifconfig: &str) -> bool {
+    let flags = ifconfig
+        .lines()
+        .next()
+        .and_then(|line| line.split_once('<'))
+        .and_then(|(_, flags)| flags.split_once('>'))
+        .map(|(flags, _)| flags.split(',').collect::<Vec<_>>())
+        .unwrap_or_default();
+    if !flags.contains(&"UP") || !flags.contains(&"RUNNING") {
+        return false;
+    }
+    ifconfig
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("status:"))
+        // Wireless interfaces are `associated` with a network
+        .map(|status| matches!(status.trim(), "active" | "associated"))
+        .unwrap_or(true)
+}
+
+pub fn ifconfig_is_wireless(ifconfig: &str) -> bool {
+    ifconfig
+        .lines()
+        .any(|line| line.trim().starts_with("media: IEEE 802.11"))
+}
+
+/// The first address of `family` (`inet` or `inet6`), leaving out link-local IPv6 addresses
+pub fn ifconfig_address(ifconfig: &str, family: &str) -> Option<String> {
+    ifconfig.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != family {
+            return None;
+        }
+        let address = fields.next()?.split('%').next()?;
+        if address.starts_with("fe80:") {
+            None
+        } else {
+            Some(address.to_string())
+        }
+    })
+}
+
+/// The network a wireless interface is connected to, from `ssid <name>` (FreeBSD) or
+/// `nwid <name>` (OpenBSD)
+pub fn ifconfig_ssid(ifconfig: &str) -> Option<String> {
+    ifconfig.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        fields.find(|field| *field == "ssid" || *field == "nwid")?;
+        fields.next().map(|ssid| ssid.trim_matches('"').to_string())
+    })
+}
+
+/// The interface of the default route
+pub fn default_interface() -> Option<String> {
+    run("net", "route", &["-n", "get", "default"])
+        .ok()?
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("interface:"))
+        .map(|device| device.trim().to_string())
+}
+
+/// The bytes received and sent by `device`
+pub fn interface_bytes(device: &str) -> Result<(u64, u64)> {
+    parse_netstat(&run("net", "netstat", &["-ibn", "-I", device])?)
+        .block_error("net", "failed to parse netstat output")
+}
+
+/// Reads the byte counters from the link line of `netstat -ibn`. The columns are counted from
+/// the end, because the address column is empty for some interfaces.
+fn parse_netstat(netstat: &str) -> Option<(u64, u64)> {
+    let mut lines = netstat.lines();
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let from_end = |column| {
+        header
+            .iter()
+            .position(|name| *name == column)
+            .map(|index| header.len() - index)
+    };
+    let (rx, tx) = (from_end("Ibytes")?, from_end("Obytes")?);
+    let link: Vec<&str> = lines
+        .find(|line| line.contains("<Link"))?
+        .split_whitespace()
+        .collect();
+    let column = |from_end: usize| link.get(link.len().checked_sub(from_end)?)?.parse().ok();
+    Some((column(rx)?, column(tx)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsers() {
+        assert_eq!(
+            parse_cp_time("10 0 5 1 84 20 0 10 2 68", 5),
+            vec![(84, 16), (68, 32)]
+        );
+        assert_eq!(parse_cp_time("10,0,5,0,1,84", 0), vec![(84, 16)]);
+
+        assert_eq!(
+            parse_temperatures(
+                "dev.cpu.0.temperature=45.0C\n\
+                 dev.cpu.0.coretemp.tjmax=100.0C\n\
+                 hw.sensors.acpitz0.temp0=38.50 degC (zone temperature)\n\
+                 hw.sensors.acpibat0.volt0=12.00 VDC"
+            ),
+            vec![
+                ("dev.cpu.0".to_string(), 45.0),
+                ("hw.sensors.acpitz0".to_string(), 38.5)
+            ]
+        );
+
+        let netstat = "Name    Mtu Network       Address              Ipkts Ierrs Idrop     Ibytes    Opkts Oerrs     Obytes  Coll\n\
+                       em0    1500 <Link#1>      52:54:00:12:34:56   1000     0     0    2000000      500     0      80000     0\n\
+                       em0       - 10.0.2.0/24   10.0.2.15            900     -     -    1900000      450     -      75000     -";
+        assert_eq!(parse_netstat(netstat), Some((2000000, 80000)));
+
+        let ifconfig =
+            "wlan0: flags=8843<UP,BROADCAST,RUNNING,SIMPLEX,MULTICAST> metric 0 mtu 1500\n\
+                        \tinet6 fe80::1%wlan0 prefixlen 64 scopeid 0x2\n\
+                        \tinet 192.168.1.20 netmask 0xffffff00 broadcast 192.168.1.255\n\
+                        \tmedia: IEEE 802.11 Wireless Ethernet OFDM/54Mbps mode 11g\n\
+                        \tstatus: associated\n\
+                        \tssid home channel 6 (2437 MHz 11g) bssid 00:11:22:33:44:55";
+        assert!(ifconfig_is_up(ifconfig));
+        assert!(ifconfig_is_wireless(ifconfig));
+        assert_eq!(
+            ifconfig_address(ifconfig, "inet").as_deref(),
+            Some("192.168.1.20")
+        );
+        assert_eq!(ifconfig_address(ifconfig, "inet6"), None);
+        assert_eq!(ifconfig_ssid(ifconfig).as_deref(), Some("home"));
+    }
+}
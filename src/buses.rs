@@ -0,0 +1,201 @@
+//! Connections to the session and system bus that blocks share, so that a config with many blocks
+//! that use D-Bus doesn't open a connection and start a thread for each of them. Every block that
+//! watches for a signal is sent it.
+//!
+//! Some blocks still connect on their own: music, networkmanager, bluetooth, ibus and
+//! keyboard_layout read their signals through the older `dbus::ffidisp` API in threads of their
+//! own, custom_dbus needs a connection that owns its bus name, and backlight only connects when
+//! it falls back to logind to set the brightness.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use dbus::arg::ReadAll;
+use dbus::blocking::SyncConnection;
+use dbus::channel::{MatchingReceiver, Token};
+use dbus::message::MatchRule;
+use dbus::Message;
+
+use crate::errors::*;
+
+/// How long the thread of a signal connection waits for messages before it checks whether the
+/// connection is still used
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bus {
+    Session,
+    System,
+}
+
+fn connect(bus: Bus) -> Result<SyncConnection> {
+    match bus {
+        Bus::Session => SyncConnection::new_session(),
+        Bus::System => SyncConnection::new_system(),
+    }
+    .block_error("dbus", "failed to establish D-Bus connection")
+}
+
+/// Calls a method of the bus itself without waiting for the reply, which the thread of the
+/// connection would take away from a blocking call
+fn call_bus(con: &SyncConnection, method: &str, match_str: &str) {
+    let message = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        method,
+    )
+    .map(|message| message.append1(match_str));
+    if let Ok(message) = message {
+        let _ = dbus::channel::Sender::send(con, message);
+    }
+}
+
+/// Hands out the shared connections. A connection lasts as long as a block uses it.
+///
+/// Each bus has a connection for method calls and one for signals. Signals are read by a thread,
+/// and blocking method calls on the same connection could lose their replies to it.
+#[derive(Debug, Default)]
+pub struct Buses {
+    session: RefCell<Weak<SyncConnection>>,
+    system: RefCell<Weak<SyncConnection>>,
+    session_signals: RefCell<Weak<SyncConnection>>,
+    system_signals: RefCell<Weak<SyncConnection>>,
+}
+
+impl Buses {
+    /// The connection to `bus` for method calls, which is established on first use
+    pub fn get(&self, bus: Bus) -> Result<Arc<SyncConnection>> {
+        let slot = match bus {
+            Bus::Session => &self.session,
+            Bus::System => &self.system,
+        };
+        if let Some(con) = slot.borrow().upgrade() {
+            return Ok(con);
+        }
+        let con = Arc::new(connect(bus)?);
+        *slot.borrow_mut() = Arc::downgrade(&con);
+        Ok(con)
+    }
+
+    /// The connection to `bus` for signals, with a thread that hands them to the watches
+    fn signals(&self, bus: Bus) -> Result<Arc<SyncConnection>> {
+        let slot = match bus {
+            Bus::Session => &self.session_signals,
+            Bus::System => &self.system_signals,
+        };
+        if let Some(con) = slot.borrow().upgrade() {
+            return Ok(con);
+        }
+
+        let con = connect(bus)?;
+        // Signals that several blocks watch for go to each of them, rather than to the first
+        con.set_signal_match_mode(true);
+        let con = Arc::new(con);
+        let process_con = con.clone();
+        thread::Builder::new()
+            .name(format!("dbus {:?}", bus).to_lowercase())
+            .spawn(move || {
+                // Ends once the watches are gone
+                while Arc::strong_count(&process_con) > 1 {
+                    if let Err(error) = process_con.process(PROCESS_TIMEOUT) {
+                        log::warn!("lost the D-Bus connection: {}", error);
+                        break;
+                    }
+                }
+            })
+            .internal_error("dbus", "failed to start a thread")?;
+
+        *slot.borrow_mut() = Arc::downgrade(&con);
+        Ok(con)
+    }
+
+    /// Calls `callback` in the thread of the connection whenever a signal that matches `rule`
+    /// arrives on `bus`, until the returned `Watch` is dropped. The callback ends the watch by
+    /// returning `false`.
+    pub fn watch<S, F>(&self, bus: Bus, rule: MatchRule<'static>, mut callback: F) -> Result<Watch>
+    where
+        S: ReadAll,
+        F: FnMut(S, &SyncConnection, &Message) -> bool + Send + Sync + 'static,
+    {
+        let con = self.signals(bus)?;
+        let match_str = rule.match_str();
+        call_bus(&con, "AddMatch", &match_str);
+        let callback_match_str = match_str.clone();
+        let token = con.start_receive(
+            rule,
+            Box::new(move |message, con| {
+                // Signals with other arguments than expected are left alone
+                let keep = match message.read_all::<S>() {
+                    Ok(signal) => callback(signal, con, &message),
+                    Err(_) => true,
+                };
+                if !keep {
+                    call_bus(con, "RemoveMatch", &callback_match_str);
+                }
+                keep
+            }),
+        );
+        Ok(Watch {
+            con,
+            token,
+            match_str,
+        })
+    }
+}
+
+/// Keeps a signal watched, see `Buses::watch`
+pub struct Watch {
+    con: Arc<SyncConnection>,
+    token: Token,
+    match_str: String,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        if self.con.stop_receive(self.token).is_some() {
+            call_bus(&self.con, "RemoveMatch", &self.match_str);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_signals_reach_every_watch() {
+        let buses = Buses::default();
+        // Needs a session bus, e.g. in `dbus-run-session`
+        let con = match buses.get(Bus::Session) {
+            Ok(con) => con,
+            Err(_) => return,
+        };
+        let (tx, rx) = mpsc::channel();
+        let rule = || MatchRule::new_signal("org.example.Test", "Ping");
+        let watches: Vec<Watch> = (0..2)
+            .map(|n| {
+                let tx = tx.clone();
+                buses
+                    .watch(Bus::Session, rule(), move |_: (), _, _| tx.send(n).is_ok())
+                    .unwrap()
+            })
+            .collect();
+        assert!(Arc::ptr_eq(&con, &buses.get(Bus::Session).unwrap()));
+        // The bus isn't waited for when the matches are added
+        thread::sleep(Duration::from_millis(100));
+
+        let ping = Message::new_signal("/org/example", "org.example.Test", "Ping").unwrap();
+        dbus::channel::Sender::send(&*con, ping).unwrap();
+        let mut received = vec![
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+        ];
+        received.sort_unstable();
+        assert_eq!(received, [0, 1]);
+        drop(watches);
+    }
+}
@@ -0,0 +1,226 @@
+//! Detection of what the machine offers, so that a config shared between desktops, laptops and
+//! virtual machines can leave out the blocks that don't apply instead of failing on them.
+//!
+//! Blocks list what they need in their `requires` option. Every capability is probed only once
+//! and the result is kept for the lifetime of the bar.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::errors::*;
+use crate::util::has_command;
+
+lazy_static! {
+    static ref PROBED: Mutex<HashMap<Capability, bool>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Capability {
+    Battery,
+    Backlight,
+    VirtualMachine,
+    Container,
+    Command(String),
+    SessionBus(String),
+    SystemBus(String),
+    File(String),
+}
+
+/// A capability as written in `requires`, e.g. `battery`, `!vm` or `command:nvidia-smi`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    capability: Capability,
+    negated: bool,
+}
+
+impl FromStr for Requirement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ConfigurationError(
+                format!("invalid capability '{}'", s),
+                "expected battery, backlight, vm, container, command:<name>, dbus:<name>, \
+                 system_dbus:<name> or file:<path>, optionally preceded by !"
+                    .to_string(),
+            )
+        };
+        let (negated, capability) = match s.strip_prefix('!') {
+            Some(capability) => (true, capability),
+            None => (false, s),
+        };
+        let capability = match capability.split_once(':') {
+            None => match capability {
+                "battery" => Capability::Battery,
+                "backlight" => Capability::Backlight,
+                "vm" => Capability::VirtualMachine,
+                "container" => Capability::Container,
+                _ => return Err(invalid()),
+            },
+            Some((_, "")) => return Err(invalid()),
+            Some((kind, name)) => match kind {
+                "command" => Capability::Command(name.to_string()),
+                "dbus" => Capability::SessionBus(name.to_string()),
+                "system_dbus" => Capability::SystemBus(name.to_string()),
+                "file" => Capability::File(shellexpand::tilde(name).to_string()),
+                _ => return Err(invalid()),
+            },
+        };
+        Ok(Requirement {
+            capability,
+            negated,
+        })
+    }
+}
+
+/// Whether the machine meets all `requirements`
+pub fn satisfied(requirements: &[String]) -> Result<bool> {
+    for requirement in requirements {
+        let requirement: Requirement = requirement.parse()?;
+        if available(&requirement.capability) == requirement.negated {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn available(capability: &Capability) -> bool {
+    let mut probed = PROBED.lock().unwrap();
+    if let Some(&available) = probed.get(capability) {
+        return available;
+    }
+    let available = probe(capability);
+    probed.insert(capability.clone(), available);
+    available
+}
+
+fn probe(capability: &Capability) -> bool {
+    match capability {
+        Capability::Battery => fs::read_dir("/sys/class/power_supply")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|supply| {
+                fs::read_to_string(supply.path().join("type"))
+                    .map(|kind| kind.trim() == "Battery")
+                    .unwrap_or(false)
+            }),
+        Capability::Backlight => fs::read_dir("/sys/class/backlight")
+            .map(|mut devices| devices.next().is_some())
+            .unwrap_or(false),
+        Capability::VirtualMachine => {
+            let dmi = ["sys_vendor", "product_name", "board_vendor"]
+                .iter()
+                .filter_map(|file| fs::read_to_string(format!("/sys/class/dmi/id/{}", file)).ok())
+                .any(|value| is_vm_vendor(&value));
+            dmi || fs::read_to_string("/proc/cpuinfo")
+                .map(|cpuinfo| {
+                    cpuinfo
+                        .lines()
+                        .filter(|line| line.starts_with("flags"))
+                        .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"))
+                })
+                .unwrap_or(false)
+        }
+        Capability::Container => {
+            [
+                "/.dockerenv",
+                "/run/.containerenv",
+                "/run/systemd/container",
+            ]
+            .iter()
+            .any(|path| Path::new(path).exists())
+                || fs::read("/proc/1/environ")
+                    .map(|environ| {
+                        environ
+                            .split(|&byte| byte == 0)
+                            .any(|var| var.starts_with(b"container="))
+                    })
+                    .unwrap_or(false)
+        }
+        Capability::Command(name) => has_command("capabilities", name).unwrap_or(false),
+        #[cfg(feature = "dbus")]
+        Capability::SessionBus(name) => {
+            has_bus_name(dbus::ffidisp::BusType::Session, name).unwrap_or(false)
+        }
+        #[cfg(feature = "dbus")]
+        Capability::SystemBus(name) => {
+            has_bus_name(dbus::ffidisp::BusType::System, name).unwrap_or(false)
+        }
+        // Without D-Bus support, nothing on the bus is of any use
+        #[cfg(not(feature = "dbus"))]
+        Capability::SessionBus(_) | Capability::SystemBus(_) => false,
+        Capability::File(path) => Path::new(path).exists(),
+    }
+}
+
+/// Whether a service owns `name` on the bus or can be started by asking for it
+#[cfg(feature = "dbus")]
+fn has_bus_name(bus: dbus::ffidisp::BusType, name: &str) -> Option<bool> {
+    let con = dbus::ffidisp::Connection::get_private(bus).ok()?;
+    let proxy = con.with_path("org.freedesktop.DBus", "/org/freedesktop/DBus", 1000);
+    let (owned,): (bool,) = proxy
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (name,))
+        .ok()?;
+    if owned {
+        return Some(true);
+    }
+    let (activatable,): (Vec<String>,) = proxy
+        .method_call("org.freedesktop.DBus", "ListActivatableNames", ())
+        .ok()?;
+    Some(activatable.iter().any(|activatable| activatable == name))
+}
+
+/// Whether a DMI vendor or product name belongs to a hypervisor
+fn is_vm_vendor(value: &str) -> bool {
+    let value = value.trim();
+    [
+        "QEMU",
+        "KVM",
+        "VMware",
+        "VirtualBox",
+        "innotek GmbH",
+        "Xen",
+        "Bochs",
+        "Parallels",
+        "Virtual Machine",
+        "Amazon EC2",
+        "Google Compute Engine",
+    ]
+    .iter()
+    .any(|vendor| value.contains(vendor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requirement() {
+        assert_eq!(
+            "!vm".parse::<Requirement>().unwrap(),
+            Requirement {
+                capability: Capability::VirtualMachine,
+                negated: true,
+            }
+        );
+        assert_eq!(
+            "command:nvidia-smi".parse::<Requirement>().unwrap(),
+            Requirement {
+                capability: Capability::Command("nvidia-smi".to_string()),
+                negated: false,
+            }
+        );
+        assert!("lasers".parse::<Requirement>().is_err());
+        assert!("dbus:".parse::<Requirement>().is_err());
+
+        assert!(is_vm_vendor("QEMU\n"));
+        assert!(is_vm_vendor("VMware Virtual Platform\n"));
+        assert!(!is_vm_vendor("LENOVO\n"));
+    }
+}
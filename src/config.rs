@@ -1,14 +1,33 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
 
 use serde::de::{Deserialize, Deserializer};
 use serde_derive::Deserialize;
 use toml::value;
 
+#[cfg(feature = "dbus")]
+use crate::buses::Buses;
+use crate::de::deserialize_duration;
 use crate::errors;
+use crate::errors::{OptionExt, ResultExtInternal};
 use crate::icons::Icons;
+use crate::profiles::Profile;
+use crate::protocol::export::ExportConfig;
+use crate::protocol::headless::HeadlessConfig;
+use crate::protocol::i3bar_block::{I3BarBlockAlign, I3BarBlockMinWidth};
 use crate::protocol::i3bar_event::MouseButton;
-use crate::themes::Theme;
+use crate::state_store::StateStore;
+use crate::subprocess::{CommandRunner, ShellRunner};
+use crate::themes::{Gradient, MonochromeConfig, Theme};
+use crate::util::{deserialize_file, interpolate, wildcard_match};
+use crate::widgets::{EmptyPolicy, Markup, Overflow};
 
 #[derive(Debug)]
 pub struct SharedConfig {
@@ -16,15 +35,51 @@ pub struct SharedConfig {
     icons: Rc<Icons>,
     icons_format: String,
     pub scrolling: Scrolling,
+    pub if_empty: Option<EmptyPolicy>,
+    pub if_empty_text: String,
+    pub max_text_width: Option<usize>,
+    pub overflow: Option<Overflow>,
+    pub markup: Option<Markup>,
+    pub font: Option<String>,
+    pub min_width: Option<I3BarBlockMinWidth>,
+    pub align: Option<I3BarBlockAlign>,
+    pub gradient: Option<Gradient>,
+    pub monochrome: Option<Rc<MonochromeConfig>>,
+    pub jitter: Duration,
+    pub menu_launcher: Rc<String>,
+    #[cfg(feature = "dbus")]
+    pub buses: Rc<Buses>,
+    pub command_runner: Rc<dyn CommandRunner>,
+    state_store: Rc<StateStore>,
 }
 
 impl SharedConfig {
     pub fn new(config: &Config) -> Self {
+        let monochrome = config.monochrome.clone().map(Rc::new);
         Self {
-            theme: Rc::new(config.theme.clone()),
+            theme: Rc::new(match &monochrome {
+                Some(monochrome) => monochrome.theme(),
+                None => config.theme.clone(),
+            }),
             icons: Rc::new(config.icons.clone()),
             icons_format: config.icons_format.clone(),
             scrolling: config.scrolling,
+            if_empty: None,
+            if_empty_text: SharedConfig::default_if_empty_text(),
+            max_text_width: None,
+            overflow: None,
+            markup: None,
+            font: None,
+            min_width: None,
+            align: None,
+            gradient: None,
+            monochrome,
+            jitter: config.jitter,
+            menu_launcher: Rc::new(config.menu_launcher.clone()),
+            #[cfg(feature = "dbus")]
+            buses: Rc::new(Buses::default()),
+            command_runner: Rc::new(ShellRunner),
+            state_store: Rc::new(StateStore::user()),
         }
     }
 
@@ -32,6 +87,52 @@ impl SharedConfig {
         self.icons_format = icons_format;
     }
 
+    pub fn icons_override(&mut self, overrides: &HashMap<String, String>) {
+        let mut icons = self.icons.as_ref().clone();
+        icons.0.extend(overrides.clone());
+        self.icons = Rc::new(icons);
+    }
+
+    pub fn if_empty_override(&mut self, policy: Option<EmptyPolicy>, text: Option<String>) {
+        self.if_empty = policy;
+        if let Some(text) = text {
+            self.if_empty_text = text;
+        }
+    }
+
+    pub fn overflow_override(&mut self, max_text_width: Option<usize>, overflow: Option<Overflow>) {
+        self.max_text_width = max_text_width;
+        self.overflow = match overflow {
+            // Scrolling text redraws the bar twice a second
+            None | Some(Overflow::Marquee) if self.monochrome.is_some() => Some(Overflow::Truncate),
+            overflow => overflow,
+        };
+    }
+
+    pub fn markup_override(&mut self, markup: Option<Markup>) {
+        self.markup = markup;
+    }
+
+    pub fn layout_override(
+        &mut self,
+        font: Option<String>,
+        min_width: Option<I3BarBlockMinWidth>,
+        align: Option<I3BarBlockAlign>,
+    ) {
+        self.font = font;
+        self.min_width = min_width;
+        self.align = align;
+    }
+
+    pub fn gradient_override(&mut self, gradient: Option<Gradient>) {
+        // A gradient between the two colors would only give shades of gray
+        self.gradient = gradient.filter(|_| self.monochrome.is_none());
+    }
+
+    fn default_if_empty_text() -> String {
+        "-".to_string()
+    }
+
     pub fn theme_override(&mut self, overrides: &HashMap<String, String>) -> errors::Result<()> {
         let mut theme = self.theme.as_ref().clone();
         theme.apply_overrides(overrides)?;
@@ -39,15 +140,28 @@ impl SharedConfig {
         Ok(())
     }
 
+    /// The store in which blocks keep state across restarts of the bar
+    pub fn state_store(&self) -> Rc<StateStore> {
+        Rc::clone(&self.state_store)
+    }
+
+    /// Looks up an icon in the icon set. Icons missing from it fall back to the builtin `none`
+    /// icon set and then to an empty icon, so that a custom icon set that lacks icons of newer
+    /// blocks doesn't keep them from working.
     pub fn get_icon(&self, icon: &str) -> crate::errors::Result<String> {
-        use crate::errors::OptionExt;
-        Ok(self.icons_format.clone().replace(
-            "{icon}",
-            self.icons
-                .0
-                .get(icon)
-                .internal_error("get_icon()", &format!("icon '{}' not found in your icons file. If you recently upgraded to v0.2 please check NEWS.md.", icon))?,
-        ))
+        self.get_icons(&[icon])
+    }
+
+    /// Like `get_icon`, but puts several icons side by side in one, like a badge on an icon
+    pub fn get_icons(&self, icons: &[&str]) -> crate::errors::Result<String> {
+        let value: String = icons
+            .iter()
+            .map(|icon| match self.icons.0.get(*icon) {
+                Some(value) => value.clone(),
+                None => Icons::default().0.remove(*icon).unwrap_or_default(),
+            })
+            .collect();
+        Ok(self.icons_format.replace("{icon}", &value))
     }
 }
 
@@ -58,6 +172,22 @@ impl Default for SharedConfig {
             icons: Rc::new(Icons::default()),
             icons_format: " {icon} ".to_string(),
             scrolling: Scrolling::default(),
+            if_empty: None,
+            if_empty_text: SharedConfig::default_if_empty_text(),
+            max_text_width: None,
+            overflow: None,
+            markup: None,
+            font: None,
+            min_width: None,
+            align: None,
+            gradient: None,
+            monochrome: None,
+            jitter: Duration::default(),
+            menu_launcher: Rc::new(Config::default_menu_launcher()),
+            #[cfg(feature = "dbus")]
+            buses: Rc::new(Buses::default()),
+            command_runner: Rc::new(ShellRunner),
+            state_store: Rc::new(StateStore::default()),
         }
     }
 }
@@ -69,6 +199,22 @@ impl Clone for SharedConfig {
             icons: Rc::clone(&self.icons),
             icons_format: self.icons_format.clone(),
             scrolling: self.scrolling,
+            if_empty: self.if_empty,
+            if_empty_text: self.if_empty_text.clone(),
+            max_text_width: self.max_text_width,
+            overflow: self.overflow,
+            markup: self.markup,
+            font: self.font.clone(),
+            min_width: self.min_width.clone(),
+            align: self.align,
+            gradient: self.gradient,
+            monochrome: self.monochrome.clone(),
+            jitter: self.jitter,
+            menu_launcher: Rc::clone(&self.menu_launcher),
+            #[cfg(feature = "dbus")]
+            buses: Rc::clone(&self.buses),
+            command_runner: Rc::clone(&self.command_runner),
+            state_store: Rc::clone(&self.state_store),
         }
     }
 }
@@ -92,6 +238,37 @@ pub struct Config {
     #[serde(default)]
     pub scrolling: Scrolling,
 
+    /// Settings of `--headless`
+    #[serde(default)]
+    pub headless: HeadlessConfig,
+
+    /// Settings of the JSON file the blocks are exported to
+    #[serde(default)]
+    pub export: ExportConfig,
+
+    /// Renders the bar without colors and updates it less often
+    #[serde(default)]
+    pub monochrome: Option<MonochromeConfig>,
+
+    /// Longest delay that the updates of blocks are shifted by, so that blocks with the same
+    /// interval don't all update at once
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub jitter: Duration,
+
+    /// Longest time between two clicks that makes them a double click
+    #[serde(
+        default = "Config::default_double_click_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub double_click_interval: Duration,
+
+    /// Command that shows the menus of blocks, see `menu`
+    #[serde(default = "Config::default_menu_launcher")]
+    pub menu_launcher: String,
+
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<Profile>,
+
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
@@ -100,6 +277,14 @@ impl Config {
     fn default_icons_format() -> String {
         " {icon} ".to_string()
     }
+
+    fn default_double_click_interval() -> Duration {
+        Duration::from_millis(300)
+    }
+
+    fn default_menu_launcher() -> String {
+        "rofi -dmenu".to_string()
+    }
 }
 
 impl Default for Config {
@@ -109,6 +294,13 @@ impl Default for Config {
             theme: Theme::default(),
             icons_format: Config::default_icons_format(),
             scrolling: Scrolling::default(),
+            headless: HeadlessConfig::default(),
+            export: ExportConfig::default(),
+            monochrome: None,
+            jitter: Duration::default(),
+            double_click_interval: Config::default_double_click_interval(),
+            menu_launcher: Config::default_menu_launcher(),
+            profiles: Vec::new(),
             blocks: Vec::new(),
         }
     }
@@ -146,6 +338,257 @@ impl Default for Scrolling {
     }
 }
 
+/// A directory and a shell-style pattern matching the names of config files in it
+pub type ConfigFilePattern = (PathBuf, String);
+
+/// Reads the config file at `path` and merges in the files listed in its `include` option.
+///
+/// Top-level tables of included files are merged into the ones read so far. Blocks are appended,
+/// unless they have a `name` that an already defined block has, in which case their settings are
+/// merged over that block instead. Included files can not include further files.
+///
+/// Returns the merged TOML and the patterns of all files that make up the config.
+pub fn read_config(path: &Path) -> errors::Result<(value::Value, Vec<ConfigFilePattern>)> {
+    let mut config: value::Table = deserialize_file(path)?;
+    let mut patterns = vec![split_pattern(path)?];
+
+    let includes = match config.remove("include") {
+        Some(includes) => Vec::<String>::deserialize(includes)
+            .configuration_error("`include` must be a list of file patterns")?,
+        None => Vec::new(),
+    };
+
+    let config_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    for include in includes {
+        let expanded = shellexpand::full(&include)
+            .configuration_error(&format!("failed to expand include path {}", include))?;
+        let (dir, file_pattern) = split_pattern(&config_dir.join(&*expanded))?;
+
+        let mut files: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| wildcard_match(&file_pattern, &entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect(),
+            // A missing directory simply contributes no files
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+
+        for file in files {
+            let mut included: value::Table = deserialize_file(&file)?;
+            included.remove("include");
+            merge_config(&mut config, included);
+        }
+
+        patterns.push((dir, file_pattern));
+    }
+
+    if let Some(value::Value::Array(blocks)) = config.get_mut("block") {
+        for block in blocks {
+            interpolate_value(block)?;
+        }
+    }
+
+    Ok((value::Value::Table(config), patterns))
+}
+
+/// Resolves `${VAR}` and `$(command)` in all string options of a block, see `util::interpolate`.
+///
+/// Options that are run as shell commands anyway are left alone, so that substitutions in them
+/// still happen every time the command runs, and so are format strings, where `${price}` is a `$`
+/// in front of a placeholder.
+fn interpolate_value(value: &mut value::Value) -> errors::Result<()> {
+    match value {
+        value::Value::String(text) => *text = interpolate(text)?,
+        value::Value::Array(values) => {
+            for value in values {
+                interpolate_value(value)?;
+            }
+        }
+        value::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if !keeps_dollars(key) {
+                    interpolate_value(value)?;
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn keeps_dollars(key: &str) -> bool {
+    key.starts_with("on_")
+        || key.contains("command")
+        || key.contains("format")
+        || key == "cycle"
+        || key == "menu_action"
+}
+
+fn split_pattern(path: &Path) -> errors::Result<ConfigFilePattern> {
+    let file_pattern = path
+        .file_name()
+        .internal_error("config", "config path has no file name")?
+        .to_string_lossy()
+        .into_owned();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    Ok((dir, file_pattern))
+}
+
+fn merge_config(config: &mut value::Table, included: value::Table) {
+    for (key, value) in included {
+        if key == "block" {
+            if let value::Value::Array(blocks) = value {
+                merge_blocks(config, blocks);
+            }
+        } else {
+            match (config.get_mut(&key), value) {
+                (Some(value::Value::Table(table)), value::Value::Table(overlay)) => {
+                    merge_tables(table, overlay)
+                }
+                (_, value) => {
+                    config.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+fn merge_blocks(config: &mut value::Table, blocks: Vec<value::Value>) {
+    let existing = config
+        .entry("block")
+        .or_insert_with(|| value::Value::Array(Vec::new()));
+    let existing = match existing {
+        value::Value::Array(existing) => existing,
+        _ => return,
+    };
+
+    for block in blocks {
+        let name = block.get("name").and_then(|name| name.as_str());
+        let target = name.and_then(|name| {
+            existing
+                .iter_mut()
+                .find(|b| b.get("name").and_then(|n| n.as_str()) == Some(name))
+        });
+        match (target, block) {
+            (Some(value::Value::Table(target)), value::Value::Table(overlay)) => {
+                merge_tables(target, overlay)
+            }
+            (_, block) => existing.push(block),
+        }
+    }
+}
+
+fn merge_tables(table: &mut value::Table, overlay: value::Table) {
+    for (key, value) in overlay {
+        match (table.get_mut(&key), value) {
+            (Some(value::Value::Table(inner)), value::Value::Table(overlay)) => {
+                merge_tables(inner, overlay)
+            }
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The lines (starting at 1) of the `[[block]]` headers in a config file, in the order of the
+/// blocks created from them. The blocks of the main config file come first, so their positions in
+/// the bar index this list. Empty if the lines can't be told, e.g. for an array of inline tables.
+pub fn block_lines(path: &Path) -> Vec<usize> {
+    fs::read_to_string(path)
+        .map(|contents| parse_block_lines(&contents))
+        .unwrap_or_default()
+}
+
+fn parse_block_lines(contents: &str) -> Vec<usize> {
+    let blocks = match contents.parse::<value::Value>() {
+        Ok(value::Value::Table(mut config)) => match config.remove("block") {
+            Some(value::Value::Array(blocks)) => blocks,
+            _ => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    let headers: Vec<usize> = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with("[[block]]"))
+        .map(|(index, _)| index + 1)
+        .collect();
+    if headers.len() != blocks.len() {
+        return Vec::new();
+    }
+    // Like `deserialize_blocks`, skip entries that don't say which block they are
+    headers
+        .into_iter()
+        .zip(blocks)
+        .filter(|(_, block)| block.get("block").is_some())
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// Starts a thread that watches the files that make up the config and sends a message on the
+/// provided channel whenever one of them is modified.
+///
+/// Directories are watched rather than the files themselves because most editors save by writing
+/// a temporary file and renaming it over the original. The thread exits once the receiving end of
+/// the channel is dropped and another change comes in.
+pub fn watch_config_files(
+    patterns: &[ConfigFilePattern],
+    sender: Sender<()>,
+) -> errors::Result<()> {
+    let mut notify = Inotify::init().internal_error("config watcher", "failed to start inotify")?;
+    let mut watches = Vec::new();
+    for (dir, file_pattern) in patterns {
+        // Include directories that do not exist (yet) are skipped
+        if let Ok(wd) = notify.add_watch(
+            dir,
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE | WatchMask::DELETE,
+        ) {
+            watches.push((wd, file_pattern.clone()));
+        }
+    }
+
+    thread::Builder::new()
+        .name("config watcher".into())
+        .spawn(move || {
+            let mut buffer = [0; 1024];
+            loop {
+                let mut events = notify
+                    .read_events_blocking(&mut buffer)
+                    .expect("error while reading inotify events");
+
+                let changed = events.any(|event| match event.name {
+                    Some(name) => watches.iter().any(|(wd, file_pattern)| {
+                        *wd == event.wd && wildcard_match(file_pattern, &name.to_string_lossy())
+                    }),
+                    None => false,
+                });
+
+                if changed {
+                    // Editors tend to touch the file several times while saving, so give them
+                    // a moment to finish and drop whatever arrived in the meantime.
+                    thread::sleep(Duration::from_millis(250));
+                    while let Ok(mut events) = notify.read_events(&mut buffer) {
+                        if events.next().is_none() {
+                            break;
+                        }
+                    }
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                }
+            }
+        })
+        .internal_error("config watcher", "failed to spawn thread")?;
+
+    Ok(())
+}
+
 fn deserialize_blocks<'de, D>(deserializer: D) -> Result<Vec<(String, value::Value)>, D::Error>
 where
     D: Deserializer<'de>,
@@ -153,6 +596,8 @@ where
     let mut blocks: Vec<(String, value::Value)> = Vec::new();
     let raw_blocks: Vec<value::Table> = Deserialize::deserialize(deserializer)?;
     for mut entry in raw_blocks {
+        // The name is only used to refer to blocks from included files
+        entry.remove("name");
         if let Some(name) = entry.remove("block") {
             if let Some(name) = name.as_str() {
                 blocks.push((name.to_owned(), value::Value::Table(entry)))
@@ -162,3 +607,45 @@ where
 
     Ok(blocks)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_block_lines() {
+        let contents = "theme = \"plain\"\n\
+                        \n\
+                        [[block]]\n\
+                        block = \"cpu\"\n\
+                        \n\
+                        [[block]]\n\
+                        name = \"root\"\n\
+                        \n\
+                        [[block]] # load\n\
+                        block = \"load\"\n";
+        assert_eq!(parse_block_lines(contents), vec![3, 9]);
+        assert_eq!(
+            parse_block_lines("block = [{ block = \"cpu\" }]"),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_interpolate_value() {
+        std::env::set_var("I3RS_TEST_CITY", "Berlin");
+        let mut block: value::Value = toml::from_str(
+            "city = \"${I3RS_TEST_CITY}\"\n\
+             format = \"${price}\"\n\
+             on_scroll_up = \"echo $(date)\"\n\
+             [format_alt]\n\
+             full = \"${price}\"\n",
+        )
+        .unwrap();
+        interpolate_value(&mut block).unwrap();
+        assert_eq!(block["city"].as_str(), Some("Berlin"));
+        assert_eq!(block["format"].as_str(), Some("${price}"));
+        assert_eq!(block["on_scroll_up"].as_str(), Some("echo $(date)"));
+        assert_eq!(block["format_alt"]["full"].as_str(), Some("${price}"));
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 use std::time::Duration;
 
 use crate::blocks::Update;
+use crate::util::Smoothing;
 use chrono::{DateTime, Local};
 use serde::de::{self, Deserialize, Deserializer};
 
@@ -92,6 +93,14 @@ where
     deserialize_duration(deserializer).map(Some)
 }
 
+pub fn deserialize_opt_smoothing<'de, D>(deserializer: D) -> Result<Option<Smoothing>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let smoothing = String::deserialize(deserializer)?;
+    smoothing.parse().map(Some).map_err(de::Error::custom)
+}
+
 pub fn deserialize_local_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
 where
     D: Deserializer<'de>,
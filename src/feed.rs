@@ -0,0 +1,159 @@
+//! Reads the items of RSS and Atom feeds, as published by blogs, news sites, podcasts and
+//! release pages.
+//!
+//! This is not a full XML parser: it looks for the few elements that are needed to show new items
+//! and doesn't handle namespace prefixes on them.
+
+use chrono::{DateTime, FixedOffset};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// Title of the feed the item belongs to
+    pub feed: String,
+    pub title: String,
+    /// The link or enclosure if the feed doesn't give a GUID
+    pub guid: String,
+    pub link: String,
+    /// URL of the attached media file, e.g. the audio of a podcast episode
+    pub enclosure: Option<String>,
+    pub published: Option<DateTime<FixedOffset>>,
+}
+
+/// The items of an RSS feed or the entries of an Atom feed, in the order of the feed
+pub fn parse(xml: &str) -> Vec<Item> {
+    let (tag, body) = if xml.contains("<item") {
+        ("item", &xml[xml.find("<item").unwrap_or(0)..])
+    } else {
+        ("entry", &xml[xml.find("<entry").unwrap_or(0)..])
+    };
+    let feed = tag_text(&xml[..xml.len() - body.len()], "title").unwrap_or_default();
+
+    let mut items = Vec::new();
+    let mut rest = body;
+    while let Some((start, end)) = element(rest, tag) {
+        let xml = &rest[start..end];
+        let link = tag_text(xml, "link")
+            .or_else(|| tag_attribute(xml, "link", "href"))
+            .unwrap_or_default();
+        let enclosure = tag_attribute(xml, "enclosure", "url");
+        let guid = tag_text(xml, "guid")
+            .or_else(|| tag_text(xml, "id"))
+            .or_else(|| enclosure.clone())
+            .unwrap_or_else(|| link.clone());
+        let published = tag_text(xml, "pubDate")
+            .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+            .or_else(|| {
+                tag_text(xml, "published")
+                    .or_else(|| tag_text(xml, "updated"))
+                    .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+            });
+        items.push(Item {
+            feed: feed.clone(),
+            title: tag_text(xml, "title").unwrap_or_default(),
+            guid,
+            link,
+            enclosure,
+            published,
+        });
+        rest = &rest[end..];
+    }
+    items
+}
+
+/// The text of the first `<tag>` element in `xml`, without CDATA markers and entities
+fn tag_text(xml: &str, tag: &str) -> Option<String> {
+    let (start, end) = element(xml, tag)?;
+    let text = xml[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"))
+        .map(str::to_string)
+        .unwrap_or_else(|| unescape(text));
+    Some(text.trim().to_string())
+}
+
+/// The value of `attribute` of the first `<tag>` element in `xml`
+fn tag_attribute(xml: &str, tag: &str, attribute: &str) -> Option<String> {
+    let open = format!("<{}", tag);
+    let start = xml
+        .match_indices(&open)
+        .map(|(i, _)| i + open.len())
+        .find(|i| xml[*i..].starts_with(|c: char| c.is_whitespace()))?;
+    let tag_end = start + xml[start..].find('>')?;
+    let attributes = &xml[start..tag_end];
+    let name = format!("{}=", attribute);
+    let value_start = attributes
+        .match_indices(&name)
+        .map(|(i, _)| i + name.len())
+        .find(|i| attributes[..*i - name.len()].ends_with(char::is_whitespace))?;
+    let quote = attributes[value_start..].chars().next()?;
+    let value = &attributes[value_start + 1..];
+    Some(unescape(&value[..value.find(quote)?]))
+}
+
+/// Byte range of the content of the first `<tag>` element in `xml`
+fn element(xml: &str, tag: &str) -> Option<(usize, usize)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let (open_at, _) = xml.match_indices(&open).find(|(i, _)| {
+        xml[i + open.len()..].starts_with(|c: char| c == '>' || c.is_whitespace())
+    })?;
+    let start = open_at + xml[open_at..].find('>')? + 1;
+    if xml[..start].ends_with("/>") {
+        return None;
+    }
+    let end = start + xml[start..].find(&close)?;
+    Some((start, end))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+<title>Tech &amp; Talk</title>
+<item>
+  <title><![CDATA[Episode 2: <Rust>]]></title>
+  <guid isPermaLink="false">ep-2</guid>
+  <enclosure url="https://example.com/2.mp3?a=1&amp;b=2" type="audio/mpeg"/>
+  <pubDate>Tue, 05 Oct 2021 10:00:00 +0000</pubDate>
+</item>
+<item>
+  <title>Episode 1</title>
+  <link>https://example.com/1</link>
+</item>
+</channel></rss>"#;
+        let items = parse(rss);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].feed, "Tech & Talk");
+        assert_eq!(items[0].title, "Episode 2: <Rust>");
+        assert_eq!(items[0].guid, "ep-2");
+        assert_eq!(
+            items[0].enclosure.as_deref(),
+            Some("https://example.com/2.mp3?a=1&b=2")
+        );
+        assert!(items[0].published.is_some());
+        assert_eq!(items[1].guid, "https://example.com/1");
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Releases</title>
+<entry><title>v1.0</title><id>tag:1</id><link href="https://example.com/v1"/>
+<updated>2021-10-05T10:00:00Z</updated></entry></feed>"#;
+        let items = parse(atom);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].feed, "Releases");
+        assert_eq!(items[0].link, "https://example.com/v1");
+        assert!(items[0].published.is_some());
+    }
+}
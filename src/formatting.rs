@@ -1,9 +1,11 @@
+pub mod condition;
 pub mod placeholder;
 pub mod prefix;
 pub mod unit;
 pub mod value;
 
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
@@ -12,14 +14,33 @@ use serde::de::{MapAccess, Visitor};
 use serde::{de, Deserialize, Deserializer};
 
 use crate::errors::*;
+use condition::Condition;
 use placeholder::unexpected_token;
 use placeholder::Placeholder;
 use value::Value;
 
+thread_local! {
+    /// The values rendered while `record_values` runs
+    static RECORDED: RefCell<Option<HashMap<String, String>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` and returns the raw values of the placeholders of the formats it rendered, so that
+/// what a block shows can be used without the block knowing about it
+pub fn record_values<T>(f: impl FnOnce() -> T) -> (T, HashMap<String, String>) {
+    let outer = RECORDED.with(|recorded| recorded.replace(Some(HashMap::new())));
+    let result = f();
+    let values = RECORDED.with(|recorded| recorded.replace(outer));
+    (result, values.unwrap_or_default())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum Token {
     Text(String),
     Var(Placeholder),
+    /// A placeholder that is replaced by the given tokens if its value is empty
+    Fallback(Placeholder, Vec<Token>),
+    /// Tokens that are only rendered if the condition holds
+    If(Condition, Vec<Token>),
 }
 
 #[derive(Debug, Default, Clone)]
@@ -56,22 +77,81 @@ impl FormatTemplate {
         Ok(self)
     }
 
+    /// Initialize `short` field if it is `None`
+    pub fn with_default_short(mut self, default_short: &str) -> Result<Self> {
+        if self.short.is_none() {
+            self.short = Some(Self::tokens_from_string(default_short)?);
+        }
+        Ok(self)
+    }
+
     /// Whether the format string contains a given placeholder
     pub fn contains(&self, var: &str) -> bool {
         Self::format_contains(&self.full, var) || Self::format_contains(&self.short, var)
     }
 
     fn format_contains(format: &Option<Vec<Token>>, var: &str) -> bool {
-        if let Some(tokens) = format {
-            for token in tokens {
-                if let Token::Var(ref placeholder) = token {
-                    if placeholder.name == var {
-                        return true;
-                    }
-                }
+        match format {
+            Some(tokens) => Self::tokens_contain(tokens, var),
+            None => false,
+        }
+    }
+
+    fn tokens_contain(tokens: &[Token], var: &str) -> bool {
+        tokens.iter().any(|token| match token {
+            Token::Text(_) => false,
+            Token::Var(placeholder) => placeholder.refers_to(var),
+            Token::Fallback(placeholder, tokens) => {
+                placeholder.refers_to(var) || Self::tokens_contain(tokens, var)
+            }
+            Token::If(condition, tokens) => {
+                condition.name == var || Self::tokens_contain(tokens, var)
+            }
+        })
+    }
+
+    /// Finds the `}` that closes a placeholder, skipping over nested placeholders
+    fn closing_brace(s: &str) -> Result<usize> {
+        let mut depth = 0;
+        for (i, c) in s.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(i),
+                '}' => depth -= 1,
+                _ => (),
             }
         }
-        false
+        Err(InternalError(
+            "format parser".to_string(),
+            "missing '}'".to_string(),
+            None,
+        ))
+    }
+
+    /// Parses the contents of a pair of braces, i.e. a placeholder, a placeholder with a fallback
+    /// (`{name|empty:text}`) or a conditional (`{if condition:text}`)
+    fn token_from_braces(s: &str) -> Result<Token> {
+        if let Some(conditional) = s.strip_prefix("if ") {
+            let (condition, tokens) = conditional.split_once(':').internal_error(
+                "format parser",
+                &format!("missing ':' after the condition in '{{{}}}'", s),
+            )?;
+            Ok(Token::If(
+                condition.parse()?,
+                Self::tokens_from_string(tokens)?,
+            ))
+        } else if let Some((placeholder, fallback)) = s.split_once("|empty:") {
+            Ok(Token::Fallback(
+                placeholder.parse()?,
+                Self::tokens_from_string(fallback)?,
+            ))
+        } else if s.contains('{') {
+            unexpected_token('{')
+        } else {
+            // `s.parse()` parses the placeholder's configuration string (e.g. something like
+            // `"key:1;K"`) into `Placeholder` struct.
+            Ok(Token::Var(s.parse()?))
+        }
     }
 
     fn tokens_from_string(mut s: &str) -> Result<Vec<Token>> {
@@ -104,24 +184,9 @@ impl FormatTemplate {
                     // `before` is just a text
                     push_text(&mut tokens, before)?;
                     // Split `"key:1} {key}"` into `"key:1"` and `" {key}"`
-                    match after.split_once('}') {
-                        // No matching `}`!
-                        None => {
-                            return Err(InternalError(
-                                "format parser".to_string(),
-                                "missing '}'".to_string(),
-                                None,
-                            ));
-                        }
-                        // Found the entire placeholder
-                        Some((placeholder, rest)) => {
-                            // `placeholder.parse()` parses the placeholder's configuration string
-                            // (e.g. something like `"key:1;K"`) into `Placeholder` struct. We don't
-                            // need to think about that in this code.
-                            tokens.push(Token::Var(placeholder.parse()?));
-                            s = rest;
-                        }
-                    }
+                    let end = Self::closing_brace(after)?;
+                    tokens.push(Self::token_from_braces(&after[..end])?);
+                    s = &after[end + 1..];
                 }
             }
         }
@@ -133,6 +198,13 @@ impl FormatTemplate {
         &self,
         vars: &HashMap<impl FormatMapKey, Value>,
     ) -> Result<(String, Option<String>)> {
+        RECORDED.with(|recorded| {
+            if let Some(recorded) = recorded.borrow_mut().as_mut() {
+                for (name, value) in vars {
+                    recorded.insert(name.borrow().to_string(), value.as_text());
+                }
+            }
+        });
         let full = match &self.full {
             Some(tokens) => Self::render_tokens(tokens, vars)?,
             None => String::new(), // TODO: throw an error that says that it's a bug?
@@ -149,15 +221,27 @@ impl FormatTemplate {
         for token in tokens {
             match token {
                 Token::Text(text) => rendered.push_str(text),
-                Token::Var(var) => rendered.push_str(
-                    &vars
-                        .get(&*var.name)
-                        .internal_error(
-                            "util",
-                            &format!("Unknown placeholder in format string: '{}'", var.name),
-                        )?
-                        .format(var)?,
-                ),
+                Token::Var(var) => {
+                    let (value, operations) = var.lookup(vars).internal_error(
+                        "util",
+                        &format!(
+                            "Unknown placeholder in format string: '{}'",
+                            var.full_name.as_ref().unwrap_or(&var.name)
+                        ),
+                    )?;
+                    rendered.push_str(&value.format(var, operations)?)
+                }
+                Token::Fallback(var, fallback) => match var.lookup(vars) {
+                    Some((value, operations)) if !value.as_text().is_empty() => {
+                        rendered.push_str(&value.format(var, operations)?)
+                    }
+                    _ => rendered.push_str(&Self::render_tokens(fallback, vars)?),
+                },
+                Token::If(condition, tokens) => {
+                    if condition.eval(vars)? {
+                        rendered.push_str(&Self::render_tokens(tokens, vars)?);
+                    }
+                }
             }
         }
         Ok(rendered)
@@ -169,7 +253,7 @@ impl<'de> Deserialize<'de> for FormatTemplate {
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
+        #[derive(serde_derive::Deserialize)]
         #[serde(field_identifier, rename_all = "lowercase")]
         enum Field {
             Full,
@@ -271,4 +355,126 @@ mod tests {
         assert!(!format.contains("foobar"));
         assert!(!format.contains("random string"));
     }
+
+    #[test]
+    fn conditionals() {
+        let values = map!(
+            "tags" => Value::from_string(String::new()),
+            "project" => Value::from_string("work".to_string()),
+            "elapsed" => Value::from_integer(4000).seconds(),
+        );
+        let render = |format: &str| {
+            FormatTemplate::new(format, None)
+                .unwrap()
+                .render(&values)
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(render("{tags|empty:IDLE}"), "IDLE");
+        assert_eq!(render("{project|empty:IDLE}"), "work");
+        assert_eq!(render("{missing|empty:-}"), "-");
+        assert_eq!(render("{if elapsed > 3600: !OVERTIME!}"), " !OVERTIME!");
+        assert_eq!(render("{if elapsed <= 3600: !OVERTIME!}"), "");
+        assert_eq!(render("{if project == work:[{project}]}"), "[work]");
+        assert_eq!(render("{if !tags:no tags}{if tags:tags}"), "no tags");
+        assert!(FormatTemplate::new("{if elapsed > 1:{elapsed}", None).is_err());
+        assert!(FormatTemplate::new("{if elapsed > 1}", None).is_err());
+
+        let format = FormatTemplate::new("{if elapsed > 1:{project}}", None).unwrap();
+        assert!(format.contains("elapsed"));
+        assert!(format.contains("project"));
+    }
+
+    #[test]
+    fn renderers() {
+        let format =
+            FormatTemplate::new("{volume:bar(4)}|{load:spark(3)}|{load:spark#4}", None).unwrap();
+        let render = |volume: i64, load: f64| {
+            let values = map!(
+                "volume" => Value::from_integer(volume).percents(),
+                "load" => Value::from_float(load),
+            );
+            format.render(&values).unwrap().0
+        };
+
+        assert_eq!(render(50, 2.), "\u{2588}\u{2588}  |\u{2588}|\u{2585}");
+        assert_eq!(
+            render(100, 4.),
+            "\u{2588}\u{2588}\u{2588}\u{2588}|\u{2585}\u{2588}|\u{2585}\u{2588}"
+        );
+        assert_eq!(
+            render(0, 0.),
+            "    |\u{2585}\u{2588}\u{2581}|\u{2585}\u{2588}\u{2581}"
+        );
+        assert_eq!(
+            render(0, 0.),
+            "    |\u{2588}\u{2581}\u{2581}|\u{2585}\u{2588}\u{2581}\u{2581}"
+        );
+
+        assert!(FormatTemplate::new("{volume:bar}", None).is_err());
+        assert!(FormatTemplate::new("{volume:bar(x)}", None).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        let format = FormatTemplate::new("{elapsed:dur}|{elapsed:hms}", None).unwrap();
+        let render = |seconds: i64| {
+            format
+                .render(&map!("elapsed" => Value::from_integer(seconds).seconds()))
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(render(8133), "2h 15m|02:15:33");
+        assert_eq!(render(45), "45s|00:00:45");
+        assert_eq!(render(0), "0s|00:00:00");
+        assert_eq!(render(90061), "1d 1h|25:01:01");
+        assert_eq!(render(-600), "-10m 0s|-00:10:00");
+
+        let values = map!("elapsed" => Value::from_string("1:02:03".to_string()));
+        assert_eq!(format.render(&values).unwrap().0, "1h 2m|01:02:03");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let values = map!(
+            "seconds" => Value::from_integer(5400).seconds(),
+            "bytes" => Value::from_integer(1000).bytes(),
+            "celsius" => Value::from_float(20.),
+        );
+        let render = |format: &str| {
+            FormatTemplate::new(format, None)
+                .unwrap()
+                .render(&values)
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(render("{seconds/3600:3*_}h"), "1.5h");
+        assert_eq!(render("{bytes*8*_}"), "8000");
+        assert_eq!(render("{celsius*1.8+32*_}"), " 68");
+        assert_eq!(render("{seconds-5400:1*_}"), "0");
+        // Anything else is part of the name
+        assert!(FormatTemplate::new("{bytes/x}", None)
+            .unwrap()
+            .render(&values)
+            .is_err());
+
+        let values = map!(
+            "sensor" => Value::from_integer(1),
+            "sensor-1" => Value::from_integer(5),
+            "home/temp" => Value::from_integer(20),
+        );
+        let render = |format: &str| {
+            FormatTemplate::new(format, None)
+                .unwrap()
+                .render(&values)
+                .unwrap()
+                .0
+        };
+        assert_eq!(render("{sensor-1}"), " 5");
+        assert_eq!(render("{sensor-2}"), "-1");
+        assert_eq!(render("{home/temp*2:1}"), "40");
+    }
 }
@@ -0,0 +1,107 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::value::Value;
+use super::FormatMapKey;
+use crate::errors::*;
+
+/// Comparison operators, longer ones first so that `>=` isn't mistaken for `>`
+const OPERATORS: &[(&str, Comparison)] = &[
+    (">=", Comparison::GreaterOrEqual),
+    ("<=", Comparison::LessOrEqual),
+    ("!=", Comparison::NotEqual),
+    ("==", Comparison::Equal),
+    (">", Comparison::Greater),
+    ("<", Comparison::Less),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// The condition of an `{if <condition>:<text>}` block. Either the name of a placeholder, which
+/// holds if its value is a non-empty text or a non-zero number, or a comparison of a placeholder
+/// with a constant, e.g. `elapsed > 3600` or `state == on`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub name: String,
+    negated: bool,
+    comparison: Option<(Comparison, String)>,
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (s, negated) = match s.strip_prefix('!') {
+            Some(s) => (s.trim_start(), true),
+            None => (s, false),
+        };
+
+        let (name, comparison) = match OPERATORS
+            .iter()
+            .find_map(|(op, comparison)| Some((s.split_once(op)?, *comparison)))
+        {
+            Some(((name, constant), comparison)) => {
+                let constant = constant.trim().trim_matches(&['"', '\''][..]);
+                (name.trim(), Some((comparison, constant.to_string())))
+            }
+            None => (s, None),
+        };
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(InternalError(
+                "format parser".to_string(),
+                format!("invalid condition '{}'", s),
+                None,
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            negated,
+            comparison,
+        })
+    }
+}
+
+impl Condition {
+    pub fn eval(&self, vars: &HashMap<impl FormatMapKey, Value>) -> Result<bool> {
+        let value = vars.get(&*self.name).internal_error(
+            "util",
+            &format!("Unknown placeholder in condition: '{}'", self.name),
+        )?;
+
+        let holds = match &self.comparison {
+            None => match value.as_number() {
+                Some(number) => number != 0.,
+                None => !value.as_text().is_empty(),
+            },
+            Some((comparison, constant)) => {
+                let ordering = match (value.as_number(), constant.parse::<f64>()) {
+                    (Some(number), Ok(constant)) => number.partial_cmp(&constant),
+                    _ => Some(value.as_text().as_str().cmp(constant.as_str())),
+                };
+                match (comparison, ordering) {
+                    (_, None) => false,
+                    (Comparison::Equal, Some(o)) => o == Ordering::Equal,
+                    (Comparison::NotEqual, Some(o)) => o != Ordering::Equal,
+                    (Comparison::Less, Some(o)) => o == Ordering::Less,
+                    (Comparison::LessOrEqual, Some(o)) => o != Ordering::Greater,
+                    (Comparison::Greater, Some(o)) => o == Ordering::Greater,
+                    (Comparison::GreaterOrEqual, Some(o)) => o != Ordering::Less,
+                }
+            }
+        };
+
+        Ok(holds != self.negated)
+    }
+}
@@ -1,7 +1,11 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
 
 use super::prefix::Prefix;
 use super::unit::Unit;
+use super::value::Value;
+use super::FormatMapKey;
 use crate::errors::*;
 
 const DELIMETERS: &[char] = &[':', '^', ';', '*', '#'];
@@ -14,11 +18,119 @@ const BAR_MAX_VAL_TOKEN: char = DELIMETERS[4];
 #[derive(Debug, Clone, PartialEq)]
 pub struct Placeholder {
     pub name: String,
+    /// The name as written if arithmetic was split off it, e.g. `sensor-1` for `sensor` and `-1`
+    pub full_name: Option<String>,
     pub min_width: MinWidthConfig,
     pub unit: UnitConfig,
     pub min_prefix: MinPrefixConfig,
     pub max_width: Option<usize>,
     pub bar_max_value: Option<f64>,
+    pub renderer: Option<Renderer>,
+    pub operations: Vec<Operation>,
+}
+
+/// Arithmetic on a numeric value before it is formatted, e.g. `/3600` in `{seconds/3600}`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Add(f64),
+    Subtract(f64),
+    Multiply(f64),
+    Divide(f64),
+}
+
+impl Operation {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            Operation::Add(operand) => value + operand,
+            Operation::Subtract(operand) => value - operand,
+            Operation::Multiply(operand) => value * operand,
+            Operation::Divide(operand) => value / operand,
+        }
+    }
+
+    /// Parses operations like `*8/1024`, which have to make up all of `s`
+    fn parse_all(mut s: &str) -> Option<Vec<Self>> {
+        let mut operations = Vec::new();
+        while let Some(operator) = s.chars().next().filter(|c| "+-*/".contains(*c)) {
+            let operand_len = s[1..]
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(s.len() - 1);
+            let operand = s[1..1 + operand_len].parse().ok()?;
+            operations.push(match operator {
+                '+' => Operation::Add(operand),
+                '-' => Operation::Subtract(operand),
+                '*' => Operation::Multiply(operand),
+                _ => Operation::Divide(operand),
+            });
+            s = &s[1 + operand_len..];
+        }
+        if s.is_empty() {
+            Some(operations)
+        } else {
+            None
+        }
+    }
+
+    /// Splits operations like `/3600` off the end of a name like `seconds/3600`
+    fn split_off(name: &str) -> Option<(&str, Vec<Self>)> {
+        name.char_indices()
+            .skip(1)
+            .filter(|(_, c)| "+-*/".contains(*c))
+            .find_map(|(i, _)| Some((&name[..i], Self::parse_all(&name[i..])?)))
+    }
+}
+
+/// Shows a numeric value as a graphic or a duration instead of a number, set in place of the
+/// min width
+#[derive(Debug, Clone, PartialEq)]
+pub enum Renderer {
+    /// `bar(<length>)`: a bar of the given length, full at the bar max value (default 100)
+    Bar(usize),
+    /// `spark(<length>)`: a sparkline of the most recent values, scaled to the largest of them
+    /// (or to the bar max value)
+    Spark(usize, RefCell<VecDeque<f64>>),
+    /// `dur`: a number of seconds as the two largest units of time, e.g. `2h 15m`
+    Duration,
+    /// `hms`: a number of seconds as hours, minutes and seconds, e.g. `02:15:33`
+    Hms,
+}
+
+impl Renderer {
+    const DEFAULT_SPARK_LENGTH: usize = 10;
+
+    fn parse(s: &str) -> Option<Result<Self>> {
+        let (name, argument) = match s.split_once('(') {
+            Some((name, argument)) => (name, argument.strip_suffix(')')),
+            None => (s, Some("")),
+        };
+        let length = || -> Result<Option<usize>> {
+            let argument = argument.internal_error("format parser", "missing ')'")?;
+            if argument.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(argument.parse().internal_error(
+                "format parser",
+                &format!("failed to parse length '{}'", argument),
+            )?))
+        };
+        match name {
+            "bar" => Some(length().and_then(|length| {
+                Ok(Renderer::Bar(length.internal_error(
+                    "format parser",
+                    "bar() requires a length",
+                )?))
+            })),
+            "spark" => Some(length().map(|length| {
+                Renderer::Spark(
+                    length.unwrap_or(Self::DEFAULT_SPARK_LENGTH),
+                    RefCell::new(VecDeque::new()),
+                )
+            })),
+            "dur" => Some(Ok(Renderer::Duration)),
+            "hms" => Some(Ok(Renderer::Hms)),
+            _ => None,
+        }
+    }
 }
 
 pub(super) fn unexpected_token<T>(token: char) -> Result<T> {
@@ -29,10 +141,46 @@ pub(super) fn unexpected_token<T>(token: char) -> Result<T> {
     ))
 }
 
+impl Placeholder {
+    /// The value of the placeholder and the operations to apply to it. A name that only looks like
+    /// it ends in arithmetic, e.g. the MQTT topic `sensor-1`, is looked up as a whole first.
+    pub fn lookup<'a>(
+        &self,
+        vars: &'a HashMap<impl FormatMapKey, Value>,
+    ) -> Option<(&'a Value, &[Operation])> {
+        match self.full_name.as_deref().and_then(|name| vars.get(name)) {
+            Some(value) => Some((value, &[])),
+            None => vars
+                .get(&*self.name)
+                .map(|value| (value, &*self.operations)),
+        }
+    }
+
+    /// Whether the placeholder shows the value of `var`
+    pub fn refers_to(&self, var: &str) -> bool {
+        self.name == var || self.full_name.as_deref() == Some(var)
+    }
+}
+
 impl FromStr for Placeholder {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
+        // The name ends at the first delimiter, but a `*` followed by a number multiplies
+        let name_len = s
+            .char_indices()
+            .find(|&(i, c)| {
+                DELIMETERS.contains(&c)
+                    && !(c == UNIT_TOKEN
+                        && s[i + 1..].starts_with(|c: char| c.is_ascii_digit() || c == '.'))
+            })
+            .map_or(s.len(), |(i, _)| i);
+        let (name, s) = s.split_at(name_len);
+        let (name, operations, full_name) = match Operation::split_off(name) {
+            Some((short_name, operations)) => (short_name, operations, Some(name.to_string())),
+            None => (name, Vec::new(), None),
+        };
+
         // A handy macro for parsing placeholders configuration
         macro_rules! parse {
             ($delim:expr) => {
@@ -46,13 +194,21 @@ impl FromStr for Placeholder {
             };
         }
 
-        let name = s.split_once(DELIMETERS).unwrap_or((s, "")).0;
-        let min_width = parse!(MIN_WIDTH_TOKEN);
+        let mut min_width = parse!(MIN_WIDTH_TOKEN);
         let max_width = parse!(MAX_WIDTH_TOKEN);
         let min_prefix = parse!(MIN_PREFIX_TOKEN);
         let unit = parse!(UNIT_TOKEN);
         let bar_max_value = parse!(BAR_MAX_VAL_TOKEN);
 
+        // A renderer takes the place of min_width
+        let renderer = match Renderer::parse(min_width) {
+            Some(renderer) => {
+                min_width = "";
+                Some(renderer?)
+            }
+            None => None,
+        };
+
         // Parse max_width
         let max_width = if max_width.is_empty() {
             None
@@ -74,11 +230,14 @@ impl FromStr for Placeholder {
 
         Ok(Self {
             name: name.to_string(),
+            full_name,
             min_width: min_width.parse()?,
             unit: unit.parse()?,
             min_prefix: min_prefix.parse()?,
             max_width,
             bar_max_value,
+            renderer,
+            operations,
         })
     }
 }
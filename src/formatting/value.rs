@@ -1,6 +1,6 @@
 use crate::errors::*;
 
-use super::placeholder::{MinPrefixConfig, Placeholder};
+use super::placeholder::{MinPrefixConfig, Operation, Placeholder, Renderer};
 use super::prefix::Prefix;
 use super::unit::Unit;
 
@@ -101,7 +101,7 @@ fn format_number(
     }
 }
 
-fn format_bar(value: f64, length: usize) -> String {
+pub fn format_bar(value: f64, length: usize) -> String {
     let value = value.clamp(0., 1.);
     let chars_to_fill = value * length as f64;
     (0..length)
@@ -124,6 +124,74 @@ fn format_bar(value: f64, length: usize) -> String {
         .collect()
 }
 
+fn format_spark(values: &[f64], max: f64) -> String {
+    const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > 0. {
+                (value / max).clamp(0., 1.)
+            } else {
+                0.
+            };
+            BARS[(level * (BARS.len() - 1) as f64).round() as usize]
+        })
+        .collect()
+}
+
+fn format_duration(seconds: f64) -> String {
+    const UNITS: &[(&str, u64)] = &[
+        ("w", 604_800),
+        ("d", 86_400),
+        ("h", 3_600),
+        ("m", 60),
+        ("s", 1),
+    ];
+    let sign = if seconds < 0. { "-" } else { "" };
+    let seconds = seconds.abs() as u64;
+
+    // Show the largest unit that isn't zero and the one after it
+    let first = UNITS
+        .iter()
+        .position(|&(_, size)| seconds >= size)
+        .unwrap_or(UNITS.len() - 1);
+    let parts: Vec<String> = UNITS[first..]
+        .iter()
+        .take(2)
+        .enumerate()
+        .map(|(i, &(unit, size))| {
+            let value = if i == 0 {
+                seconds / size
+            } else {
+                seconds % UNITS[first].1 / size
+            };
+            format!("{}{}", value, unit)
+        })
+        .collect();
+    format!("{}{}", sign, parts.join(" "))
+}
+
+/// Parses a duration like `1:02:03` or `02:03` into seconds
+fn parse_clock(text: &str) -> Option<f64> {
+    let mut seconds = 0.;
+    for part in text.trim().split(':') {
+        seconds = seconds * 60. + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+fn format_hms(seconds: f64) -> String {
+    let sign = if seconds < 0. { "-" } else { "" };
+    let seconds = seconds.abs() as u64;
+    format!(
+        "{}{:02}:{:02}:{:02}",
+        sign,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
 impl Value {
     // Constructors
     pub fn from_string(text: String) -> Self {
@@ -191,13 +259,106 @@ impl Value {
         self
     }
 
-    pub fn format(&self, var: &Placeholder) -> Result<String> {
+    /// The raw value if it is a number, or a text that holds a number (e.g. from a regex capture)
+    pub fn as_number(&self) -> Option<f64> {
+        match self.value {
+            InternalValue::Text(ref text) => text.trim().parse().ok(),
+            InternalValue::Integer(value) => Some(value as f64),
+            InternalValue::Float(value) => Some(value),
+        }
+    }
+
+    /// The raw value as text, without any formatting applied
+    pub fn as_text(&self) -> String {
+        match &self.value {
+            InternalValue::Text(text) => text.clone(),
+            InternalValue::Integer(value) => value.to_string(),
+            InternalValue::Float(value) => value.to_string(),
+        }
+    }
+
+    pub fn format(&self, var: &Placeholder, operations: &[Operation]) -> Result<String> {
+        if operations.is_empty() {
+            return self.format_value(var);
+        }
+
+        // Integers stay integers unless the result may have a fractional part
+        let integral = operations.iter().all(|operation| match operation {
+            Operation::Divide(_) => false,
+            Operation::Add(operand)
+            | Operation::Subtract(operand)
+            | Operation::Multiply(operand) => operand.fract() == 0.,
+        });
+        let value = match self.value {
+            InternalValue::Text(_) => return self.format_value(var),
+            InternalValue::Integer(value) if integral => InternalValue::Integer(
+                operations
+                    .iter()
+                    .fold(value as f64, |value, operation| operation.apply(value))
+                    as i64,
+            ),
+            InternalValue::Integer(value) => InternalValue::Float(
+                operations
+                    .iter()
+                    .fold(value as f64, |value, operation| operation.apply(value)),
+            ),
+            InternalValue::Float(value) => InternalValue::Float(
+                operations
+                    .iter()
+                    .fold(value, |value, operation| operation.apply(value)),
+            ),
+        };
+        let min_width = match value {
+            InternalValue::Float(_) => 3,
+            _ => self.min_width,
+        };
+        Self {
+            value,
+            min_width,
+            ..self.clone()
+        }
+        .format_value(var)
+    }
+
+    fn format_value(&self, var: &Placeholder) -> Result<String> {
         // Get user-specified min_width and pad_with values. Use defaults instead
         let min_width = var.min_width.min_width.unwrap_or(self.min_width);
         let pad_with = var.min_width.pad_with;
         // Apply unit override
         let unit = var.unit.unit.unwrap_or(self.unit);
 
+        // Draw a graphic instead of the usual formatting if a renderer is set
+        // (only for integers and floats)
+        let number = match (&var.renderer, &self.value) {
+            // Durations may also be given as text like `1:02:03`
+            (Some(Renderer::Duration), InternalValue::Text(text))
+            | (Some(Renderer::Hms), InternalValue::Text(text)) => {
+                self.as_number().or_else(|| parse_clock(text))
+            }
+            _ => self.as_number(),
+        };
+        if let (Some(renderer), Some(number)) = (&var.renderer, number) {
+            return Ok(match renderer {
+                Renderer::Bar(length) => {
+                    format_bar(number / var.bar_max_value.unwrap_or(100.), *length)
+                }
+                Renderer::Spark(length, history) => {
+                    let mut history = history.borrow_mut();
+                    history.push_back(number);
+                    while history.len() > *length {
+                        history.pop_front();
+                    }
+                    let history: Vec<f64> = history.iter().copied().collect();
+                    let max = var
+                        .bar_max_value
+                        .unwrap_or_else(|| history.iter().copied().fold(0., f64::max));
+                    format_spark(&history, max)
+                }
+                Renderer::Duration => format_duration(number),
+                Renderer::Hms => format_hms(number),
+            });
+        }
+
         // Draw the bar instead of usual formatting if `bar_max_value` is set
         // (only for integers and floats)
         if let Some(bar_max_value) = var.bar_max_value {
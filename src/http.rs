@@ -15,6 +15,10 @@ fn http_easy(mut easy: Easy) -> Result<HttpResponse<Vec<u8>>> {
     let mut buf: Vec<u8> = Vec::new();
     let mut headers: Vec<String> = Vec::new();
 
+    // libcurl built with rustls has no CA bundle, so the certificates of the system are used
+    #[cfg(feature = "rustls")]
+    easy.ssl_options(curl::easy::SslOpt::new().native_ca(true))?;
+
     {
         let mut transfer = easy.transfer();
 
@@ -59,11 +63,24 @@ pub fn http_get_socket_json(path: std::path::PathBuf, url: &str) -> Result<HttpR
     })
 }
 
-pub fn http_get_json(
+/// Sends a POST request without a body to a server listening on a Unix socket, like the API of
+/// Docker, and returns the status code
+pub fn http_post_socket(path: std::path::PathBuf, url: &str) -> Result<u32> {
+    let mut easy = curl::easy::Easy::new();
+
+    easy.url(&url.replace(" ", "%20"))?;
+    easy.unix_socket_path(Some(path))?;
+    easy.post(true)?;
+    easy.post_fields_copy(&[])?;
+
+    Ok(http_easy(easy)?.code)
+}
+
+fn get_request(
     url: &str,
     timeout: Option<Duration>,
     request_headers: Vec<(&str, &str)>,
-) -> Result<HttpResponse<Value>> {
+) -> Result<Easy> {
     let mut easy = curl::easy::Easy::new();
 
     let cleaned_url = url.replace(" ", "%20");
@@ -83,8 +100,80 @@ pub fn http_get_json(
 
     easy.http_headers(header_list)?;
 
+    Ok(easy)
+}
+
+pub fn http_get(
+    url: &str,
+    timeout: Option<Duration>,
+    request_headers: Vec<(&str, &str)>,
+) -> Result<HttpResponse<Vec<u8>>> {
+    http_easy(get_request(url, timeout, request_headers)?)
+}
+
+/// Like `http_get`, but logs in with a user name and password. Devices with self-signed
+/// certificates need `verify_tls` to be `false`.
+pub fn http_get_with_credentials(
+    url: &str,
+    timeout: Option<Duration>,
+    username: &str,
+    password: &str,
+    verify_tls: bool,
+) -> Result<HttpResponse<Vec<u8>>> {
+    let mut easy = get_request(url, timeout, vec![])?;
+    easy.username(username)?;
+    easy.password(password)?;
+    easy.ssl_verify_peer(verify_tls)?;
+    easy.ssl_verify_host(verify_tls)?;
+    http_easy(easy)
+}
+
+/// Sends a request with `method`, like "POST" or "PATCH", and an optional JSON body to an API
+/// that logs in with a user name and password, and parses the answer as JSON
+pub fn http_send_json(
+    method: &str,
+    url: &str,
+    timeout: Option<Duration>,
+    username: &str,
+    password: &str,
+    body: Option<&Value>,
+) -> Result<HttpResponse<Value>> {
+    let mut easy = get_request(url, timeout, vec![("Content-Type", "application/json")])?;
+    easy.username(username)?;
+    easy.password(password)?;
+    easy.custom_request(method)?;
+    if let Some(body) = body {
+        easy.post_fields_copy(body.to_string().as_bytes())?;
+    }
     let response = http_easy(easy)?;
 
+    // Some requests are answered without a body
+    let content = if response.content.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&response.content)
+            .internal_error("curl", "could not parse json response from server")?
+    };
+
+    Ok(HttpResponse {
+        code: response.code,
+        content,
+        headers: response.headers,
+    })
+}
+
+/// Percent-encodes a text for use in a URL, e.g. a path as a single path segment
+pub fn url_encode(text: &str) -> String {
+    curl::easy::Easy::new().url_encode(text.as_bytes())
+}
+
+pub fn http_get_json(
+    url: &str,
+    timeout: Option<Duration>,
+    request_headers: Vec<(&str, &str)>,
+) -> Result<HttpResponse<Value>> {
+    let response = http_get(url, timeout, request_headers)?;
+
     let content = serde_json::from_slice(&response.content)
         .internal_error("curl", "could not parse json response from server")?;
 
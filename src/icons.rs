@@ -45,14 +45,20 @@ impl Default for Icons {
             "cpu" => "CPU",
             "disk_drive" => "DISK",
             "docker" => "DOCKER",
+            "focus" => "FOCUS",
             "github" => "GITHUB",
             "gpu" => "GPU",
             "headphones" => "HEAD",
+            "healthchecks" => "HC",
+            "idle_inhibitor_off" => "IDLE",
+            "idle_inhibitor_on" => "AWAKE",
             "joystick" => "JOY",
             "keyboard" => "KBD",
+            "kubernetes" => "K8S",
             "mail" => "MAIL",
             "memory_mem" => "MEM",
             "memory_swap" => "SWAP",
+            "moon" => "NIGHT",
             "mouse" => "MOUSE",
             "music" => "MUSIC",
             "music_pause" => "||",
@@ -63,6 +69,7 @@ impl Default for Icons {
             "music_prev" => "&lt;",
             "net_down" => "DOWN",
             "net_loopback" => "LO",
+            "net_tether" => "TETHER",
             "net_up" => "UP ",
             "net_vpn" => "VPN",
             "net_wired" => "ETH",
@@ -76,14 +83,24 @@ impl Default for Icons {
             "pomodoro_paused" => "PAUSED",
             "pomodoro_started" => "STARTED",
             "pomodoro_stopped" => "STOPPED",
+            "privacy_camera" => "CAM",
+            "privacy_microphone" => "MIC",
+            "privacy_screen" => "SCREEN",
+            "prometheus" => "PROM",
             "resolution" => "RES",
+            "security" => "SEC",
+            "sessions" => "SSH",
+            "stale" => "STALE",
+            "sun" => "DAY",
             "tasks" => "TSK",
             "thermometer" => "TEMP",
             "time" => "TIME",
             "toggle_off" => "OFF",
             "toggle_on" => "ON",
+            "trash" => "TRASH",
             "update" => "UPD",
             "uptime" => "UP",
+            "usb" => "USB",
             "volume_empty" => "VOL",
             "volume_full" => "VOL",
             "volume_half" => "VOL",
@@ -99,6 +116,7 @@ impl Default for Icons {
             "weather_sun" => "SUNNY",
             "weather_thunder" => "STORM",
             "xrandr" => "SCREEN",
+            "yubikey" => "KEY",
             "cpu_boost_on" => "ON",
             "cpu_boost_off" => "OFF"
         })
@@ -0,0 +1,230 @@
+//! Reads the events of iCalendar (`.ics`) files, as published for sports calendars and by most
+//! calendar services.
+//!
+//! Only what is needed to show upcoming events is supported: the summary, location, start and end
+//! of each `VEVENT`. Recurrence rules are ignored, so recurring events only count once.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::errors::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub summary: String,
+    pub location: String,
+    pub start: DateTime<Utc>,
+    /// The start if the calendar doesn't say
+    pub end: DateTime<Utc>,
+    /// Whether the event lasts whole days rather than starting at a time
+    pub all_day: bool,
+}
+
+/// User name and password to log in to a calendar server with
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub struct Credentials<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+}
+
+/// Reads a calendar from a file, the `.ics` files of a directory or, with the `http` feature,
+/// an http(s) URL. Errors are reported as errors of `block`.
+pub fn fetch(block: &str, calendar: &str, credentials: Option<&Credentials>) -> Result<Vec<Event>> {
+    if calendar.starts_with("http://") || calendar.starts_with("https://") {
+        return Ok(parse(&fetch_url(block, calendar, credentials)?));
+    }
+    let path = shellexpand::full(calendar)
+        .configuration_error(&format!("failed to expand {}", calendar))?;
+    let path = Path::new(path.as_ref());
+    let files = if path.is_dir() {
+        fs::read_dir(path)
+            .block_error(block, &format!("failed to read {}", calendar))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|extension| extension == "ics"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut events = Vec::new();
+    for file in files {
+        let text = fs::read_to_string(&file)
+            .block_error(block, &format!("failed to read {}", file.display()))?;
+        events.extend(parse(&text));
+    }
+    Ok(events)
+}
+
+#[cfg(feature = "http")]
+fn fetch_url(block: &str, url: &str, credentials: Option<&Credentials>) -> Result<String> {
+    let timeout = Some(std::time::Duration::from_secs(10));
+    let response = match credentials {
+        Some(credentials) => crate::http::http_get_with_credentials(
+            url,
+            timeout,
+            credentials.username,
+            credentials.password,
+            true,
+        )?,
+        None => crate::http::http_get(url, timeout, vec![])?,
+    };
+    if response.code != 200 {
+        return Err(BlockError(
+            block.to_string(),
+            format!("failed to get {} (HTTP {})", url, response.code),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&response.content).into_owned())
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_url(block: &str, url: &str, _credentials: Option<&Credentials>) -> Result<String> {
+    Err(ConfigurationError(
+        block.to_string(),
+        format!("{} can't be fetched without the http feature", url),
+    ))
+}
+
+/// The properties of a `VEVENT` read so far
+#[derive(Default)]
+struct Draft {
+    summary: String,
+    location: String,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    all_day: bool,
+}
+
+/// Parses the events of a calendar, leaving out events without a start or with one that can't be
+/// read
+pub fn parse(text: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut draft: Option<Draft> = None;
+
+    for line in unfold(text) {
+        let (name, value) = match line.split_once(':') {
+            Some(property) => property,
+            None => continue,
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        match (name, &mut draft) {
+            ("BEGIN", None) if value == "VEVENT" => draft = Some(Draft::default()),
+            ("END", Some(event)) if value == "VEVENT" => {
+                if let Some(start) = event.start {
+                    events.push(Event {
+                        summary: unescape(&event.summary),
+                        location: unescape(&event.location),
+                        start,
+                        end: event.end.unwrap_or(start),
+                        all_day: event.all_day,
+                    });
+                }
+                draft = None;
+            }
+            ("SUMMARY", Some(event)) => event.summary = value.to_string(),
+            ("LOCATION", Some(event)) => event.location = value.to_string(),
+            ("DTSTART", Some(event)) => {
+                event.start = parse_time(params, value);
+                event.all_day = !value.contains('T');
+            }
+            ("DTEND", Some(event)) => event.end = parse_time(params, value),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Joins the lines that were folded, i.e. continued on lines that start with a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (
+            line.strip_prefix(|c| c == ' ' || c == '\t'),
+            lines.last_mut(),
+        ) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push(' '),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Reads a date or date-time in UTC (`20211003T130000Z`), in the time zone of a `TZID` parameter,
+/// or else in local time
+fn parse_time(params: &str, value: &str) -> Option<DateTime<Utc>> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(DateTime::from_utc(time, Utc));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d").map(|date| date.and_hms(0, 0, 0)))
+        .ok()?;
+    let tz = params
+        .split(';')
+        .find_map(|param| param.strip_prefix("TZID="))
+        .and_then(|tz| tz.trim_matches('"').parse::<Tz>().ok());
+    match tz {
+        Some(tz) => tz
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc)),
+        None => Local
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let calendar = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+SUMMARY:Qualifying\\, Monza\r
+LOCATION:Autodromo Nazionale\r
+ Monza\r
+DTSTART:20210911T140000Z\r
+DTEND:20210911T150000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Race\r
+DTSTART;TZID=Europe/Rome:20210912T150000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:No start\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse(calendar);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].summary, "Qualifying, Monza");
+        assert_eq!(events[0].location, "Autodromo NazionaleMonza");
+        assert_eq!(events[0].start, Utc.ymd(2021, 9, 11).and_hms(14, 0, 0));
+        assert_eq!(events[0].end, Utc.ymd(2021, 9, 11).and_hms(15, 0, 0));
+        assert_eq!(events[1].start, Utc.ymd(2021, 9, 12).and_hms(13, 0, 0));
+        assert_eq!(events[1].end, events[1].start);
+        assert!(!events[1].all_day);
+    }
+}
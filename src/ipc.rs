@@ -0,0 +1,139 @@
+//! Commands sent to a running bar over a Unix socket, e.g. to save what it shows for a bug report.
+//!
+//! Every connection sends one command as a line of text and gets one line back. Commands are
+//! handed to the main loop, which owns the blocks, together with a channel for the reply.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, Sender};
+
+use crate::errors::*;
+
+/// How long a client waits for the bar to answer
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// The JSON that the bar was last sent, written to a file or else sent back
+    Snapshot(Option<PathBuf>),
+}
+
+impl Command {
+    fn parse(line: &str) -> StdResult<Command, String> {
+        let line = line.trim();
+        let (name, argument) = match line.split_once(' ') {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None),
+        };
+        match (name, argument) {
+            ("snapshot", argument) => Ok(Command::Snapshot(argument.map(PathBuf::from))),
+            _ => Err(format!("unknown command \"{}\"", line)),
+        }
+    }
+}
+
+pub struct Request {
+    pub command: Command,
+    reply: Sender<String>,
+}
+
+impl Request {
+    /// Answers a snapshot with `rendered`, the JSON array that i3bar is sent for the blocks
+    pub fn snapshot(self, rendered: &str) {
+        // Pretty printed, so that snapshots can be compared line by line
+        let pretty = serde_json::from_str::<serde_json::Value>(rendered)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or_else(|_| rendered.to_string());
+        let reply = match self.command {
+            Command::Snapshot(Some(path)) => match fs::write(&path, pretty + "\n") {
+                Ok(()) => format!("wrote {}", path.display()),
+                Err(error) => format!("error: failed to write {}: {}", path.display(), error),
+            },
+            // One line per reply
+            Command::Snapshot(None) => rendered.to_string(),
+        };
+        let _ = self.reply.send(reply);
+    }
+}
+
+/// Listens on the socket at `path` in a separate thread and sends the commands on `sender`
+pub fn listen(path: &Path, sender: Sender<Request>) -> Result<()> {
+    if path.exists() {
+        // A socket nobody listens on is left over from a bar that ended
+        if UnixStream::connect(path).is_ok() {
+            return Err(Error::ConfigurationError(
+                format!("{} is in use by another bar", path.display()),
+                String::new(),
+            ));
+        }
+        fs::remove_file(path).internal_error("ipc", "failed to remove the old socket")?;
+    }
+    let listener = UnixListener::bind(path).internal_error("ipc", "failed to create the socket")?;
+
+    thread::Builder::new()
+        .name("ipc".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(error) = serve(stream, &sender) {
+                    log::warn!("failed to answer a command: {:?}", error);
+                }
+            }
+        })
+        .internal_error("ipc", "failed to start a thread")?;
+
+    Ok(())
+}
+
+fn serve(stream: UnixStream, sender: &Sender<Request>) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = match Command::parse(&line) {
+        Ok(command) => {
+            log::debug!("received command {:?}", command);
+            let (reply, rx_reply) = bounded(1);
+            sender.send(Request { command, reply })?;
+            rx_reply
+                .recv_timeout(TIMEOUT)
+                .unwrap_or_else(|_| "error: the bar didn't answer".to_string())
+        }
+        Err(error) => format!("error: {}", error),
+    };
+    writeln!(&stream, "{}", reply)?;
+    Ok(())
+}
+
+/// Sends `command` to the bar listening on `path` and returns its reply
+pub fn send(path: &Path, command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(path).configuration_error(&format!(
+        "no bar listens on {}, see --socket",
+        path.display()
+    ))?;
+    stream.set_read_timeout(Some(TIMEOUT * 2))?;
+    writeln!(stream, "{}", command)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim_end().strip_prefix("error: ") {
+        Some(error) => Err(Error::ConfigurationError(error.to_string(), String::new())),
+        None => Ok(reply.trim_end().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("snapshot\n"), Ok(Command::Snapshot(None)));
+        assert_eq!(
+            Command::parse("snapshot /tmp/bar.json\n"),
+            Ok(Command::Snapshot(Some(PathBuf::from("/tmp/bar.json"))))
+        );
+        assert!(Command::parse("restart").is_err());
+    }
+}
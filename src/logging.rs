@@ -0,0 +1,120 @@
+//! Log messages of the scheduler and the blocks, written to stderr or to a file.
+//!
+//! Every line is tagged with the block whose code logged it, which is tracked while the bar
+//! creates, updates, clicks or signals a block. Threads of blocks aren't tracked, so their
+//! messages name the block themselves.
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::errors::*;
+
+thread_local! {
+    /// Name and id of the block that is running on this thread
+    static BLOCK: RefCell<Option<(String, usize)>> = const { RefCell::new(None) };
+}
+
+struct Logger {
+    level: LevelFilter,
+    /// Where to write the log instead of stderr
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = BLOCK.with(|block| {
+            format_line(
+                &Local::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+                record.level(),
+                block.borrow().as_ref(),
+                &record.args().to_string(),
+            )
+        });
+        // There is nowhere to report that the log can't be written
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", line);
+                }
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+fn format_line(time: &str, level: Level, block: Option<&(String, usize)>, message: &str) -> String {
+    match block {
+        Some((name, id)) => format!("{} {:<5} [{} {}] {}", time, level, name, id, message),
+        None => format!("{} {:<5} {}", time, level, message),
+    }
+}
+
+/// Installs the logger, which logs messages up to `level` (e.g. `"debug"`) to `file` if given
+/// and to stderr otherwise
+pub fn init(level: &str, file: Option<&str>) -> Result<()> {
+    let level = LevelFilter::from_str(level)
+        .configuration_error(&format!("invalid log level {}", level))?;
+    let file = match file {
+        Some(path) => Some(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .configuration_error(&format!("failed to open log file {}", path))?,
+        )),
+        None => None,
+    };
+    log::set_boxed_logger(Box::new(Logger { level, file }))
+        .internal_error("logging", "logger already installed")?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Runs `f` on behalf of the block `name` with `id`, so that the messages it logs are tagged
+/// with the block
+pub fn in_block<T>(name: &str, id: usize, f: impl FnOnce() -> T) -> T {
+    let outer = BLOCK.with(|block| block.replace(Some((name.to_string(), id))));
+    let result = f();
+    BLOCK.with(|block| *block.borrow_mut() = outer);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        let time = "2021-06-01T12:00:00.000";
+        assert_eq!(
+            format_line(time, Level::Info, None, "started"),
+            "2021-06-01T12:00:00.000 INFO  started"
+        );
+        let line = in_block("Custom", 3, || {
+            BLOCK.with(|block| format_line(time, Level::Debug, block.borrow().as_ref(), "ran"))
+        });
+        assert_eq!(line, "2021-06-01T12:00:00.000 DEBUG [Custom 3] ran");
+        assert!(BLOCK.with(|block| block.borrow().is_none()));
+    }
+}
@@ -4,36 +4,60 @@ mod de;
 mod util;
 #[macro_use]
 mod formatting;
+mod bar_output;
 pub mod blocks;
+mod bsd;
+#[cfg(feature = "dbus")]
+mod buses;
+mod capabilities;
 mod config;
 mod errors;
+#[cfg(feature = "http")]
+mod feed;
+#[cfg(feature = "http")]
 mod http;
 mod icons;
+mod ics;
+mod ipc;
+mod logging;
+mod menu;
+mod netlink;
+mod profiles;
 mod protocol;
 mod scheduler;
 mod signals;
+mod state_store;
 mod subprocess;
 mod themes;
+mod tracking;
 mod widgets;
 
 #[cfg(feature = "pulseaudio")]
 use libpulse_binding as pulse;
 
-use std::time::Duration;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use clap::{crate_authors, crate_description, App, Arg, ArgMatches};
-use crossbeam_channel::{select, Receiver, Sender};
+use clap::{crate_authors, crate_description, App, Arg, ArgMatches, SubCommand};
+use crossbeam_channel::{never, select, Receiver, Sender};
+use serde::Deserialize;
 
+use crate::blocks::base_block::CLICK_FEEDBACK;
 use crate::blocks::create_block;
 use crate::blocks::Block;
 use crate::config::Config;
 use crate::config::SharedConfig;
+use crate::config::{block_lines, read_config, watch_config_files, ConfigFilePattern};
 use crate::errors::*;
+use crate::ipc::{Command, Request};
+use crate::protocol::export;
+use crate::protocol::headless::Publisher;
 use crate::protocol::i3bar_event::{process_events, I3BarEvent};
+use crate::protocol::Output;
 use crate::scheduler::{Task, UpdateScheduler};
 use crate::signals::process_signals;
-use crate::util::deserialize_file;
-use crate::widgets::text::TextWidget;
+use crate::widgets::text::{TextWidget, MARQUEE_STEP};
 use crate::widgets::{I3BarWidget, State};
 
 fn main() {
@@ -72,16 +96,127 @@ fn main() {
                 .long("never-pause")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("output")
+                .help(
+                    "Print the blocks for i3bar/swaybar, as plain text or as text with ANSI colors",
+                )
+                .long("output")
+                .takes_value(true)
+                .possible_values(&["i3bar", "plain", "ansi"])
+                .default_value("i3bar"),
+        )
+        .arg(
+            Arg::with_name("bar-output")
+                .value_name("NAME")
+                .help("The output the bar is on, e.g. DP-1, for blocks with outputs or outputs_exclude")
+                .long("bar-output")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .help("Text between the blocks in the plain and ansi outputs")
+                .long("separator")
+                .takes_value(true)
+                .default_value(" | "),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .help("Publish the blocks as JSON lines or to MQTT instead of running as a bar")
+                .long("headless")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("once")
+                .help("Update every block once, print them and exit, failing if any block fails")
+                .long("once")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .help("Log messages up to this level, e.g. debug to see what the blocks do")
+                .long("log-level")
+                .takes_value(true)
+                .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                .default_value("warn")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .value_name("FILE")
+                .help("Append the log to FILE instead of writing it to stderr")
+                .long("log-file")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("socket")
+                .value_name("PATH")
+                .help("Take commands on a Unix socket at PATH, e.g. to take snapshots of the bar")
+                .long("socket")
+                .takes_value(true)
+                .global(true),
+        )
         .arg(
             Arg::with_name("no-init")
                 .help("Do not send an init sequence")
                 .long("no-init")
                 .takes_value(false)
                 .hidden(true),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the config file and reports the errors of all blocks")
+                .arg(
+                    Arg::with_name("config")
+                        .value_name("CONFIG_FILE")
+                        .help("Sets a toml config file")
+                        .required(false)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Saves the JSON that the bar listening on --socket shows, or else prints it")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Writes the snapshot to FILE")
+                        .required(false)
+                        .index(1),
+                ),
         );
 
     let matches = builder.get_matches();
-    let exit_on_error = matches.is_present("exit-on-error");
+
+    if let Err(error) = logging::init(
+        matches.value_of("log-level").unwrap_or_default(),
+        matches.value_of("log-file"),
+    ) {
+        eprintln!("{:?}", error);
+        ::std::process::exit(1);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("check") {
+        if let Err(error) = check(&config_path(matches)) {
+            eprintln!("{:?}", error);
+            ::std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(snapshot_matches) = matches.subcommand_matches("snapshot") {
+        if let Err(error) = snapshot(snapshot_matches) {
+            eprintln!("{:?}", error);
+            ::std::process::exit(1);
+        }
+        return;
+    }
+
+    // Only a bar can show errors in place of the blocks
+    let exit_on_error = matches.is_present("exit-on-error")
+        || matches.is_present("once")
+        || output(&matches) != Output::I3Bar;
 
     // Run and match for potential error
     if let Err(error) = run(&matches) {
@@ -99,78 +234,186 @@ fn main() {
         println!("[{}],", error_widget.get_data().render());
         eprintln!("\n\n{:?}", error);
 
-        // Wait for USR2 signal to restart
-        signal_hook::iterator::Signals::new(&[signal_hook::consts::SIGUSR2])
-            .unwrap()
-            .forever()
-            .next()
-            .unwrap();
-        restart();
+        // Wait for USR2 signal to restart, or for TERM or INT to exit
+        let signal = signal_hook::iterator::Signals::new(&[
+            signal_hook::consts::SIGUSR2,
+            signal_hook::consts::SIGTERM,
+            signal_hook::consts::SIGINT,
+        ])
+        .unwrap()
+        .forever()
+        .next();
+        if signal == Some(signal_hook::consts::SIGUSR2) {
+            restart();
+        }
+    }
+}
+
+/// How the blocks are shown, according to the command line
+fn output(matches: &ArgMatches) -> Output {
+    if matches.is_present("headless") {
+        return Output::Headless;
+    }
+    match matches.value_of("output") {
+        Some(output @ "plain") | Some(output @ "ansi") => Output::Plain {
+            separator: matches
+                .value_of("separator")
+                .unwrap_or_default()
+                .to_string(),
+            ansi: output == "ansi",
+        },
+        _ => Output::I3Bar,
+    }
+}
+
+fn config_path(matches: &ArgMatches) -> PathBuf {
+    match matches.value_of("config") {
+        Some(config_path) => PathBuf::from(config_path),
+        None => util::xdg_config_home().join("i3status-rust/config.toml"),
     }
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
-    if !matches.is_present("no-init") {
+    let output = output(matches);
+
+    // We wait for signals in a separate thread. It starts before anything is printed, so that a
+    // bar stopped while it starts up still stops its blocks. A single run just exits.
+    let rx_signals = if matches.is_present("once") {
+        never()
+    } else {
+        let (tx_signals, rx_signals): (Sender<i32>, Receiver<i32>) = crossbeam_channel::unbounded();
+        process_signals(tx_signals);
+        rx_signals
+    };
+
+    if output == Output::I3Bar && !matches.is_present("no-init") && !matches.is_present("once") {
         // Now we can start to run the i3bar protocol
         protocol::init(matches.is_present("never-pause"));
     }
 
+    bar_output::init(matches.value_of("bar-output"));
+
     // Read & parse the config file
-    let config_path = match matches.value_of("config") {
-        Some(config_path) => std::path::PathBuf::from(config_path),
-        None => util::xdg_config_home().join("i3status-rust/config.toml"),
-    };
-    let config: Config = deserialize_file(&config_path)?;
+    let config_path = config_path(matches);
+    let (mut config, mut raw_config, mut config_files) = load_config(&config_path)?;
 
     // Update request channel
     let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
         crossbeam_channel::unbounded();
 
-    let shared_config = SharedConfig::new(&config);
+    let mut shared_config = SharedConfig::new(&config);
 
-    // Initialize the blocks
-    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
-    for &(ref block_name, ref block_config) in &config.blocks {
-        blocks.push(create_block(
-            blocks.len(),
-            block_name,
-            block_config.clone(),
-            shared_config.clone(),
-            tx_update_requests.clone(),
-        )?);
+    if matches.is_present("once") {
+        return run_once(&config_path, &config, &shared_config, &output);
     }
 
+    // Initialize the blocks
+    let mut blocks: Vec<Box<dyn Block>> =
+        create_blocks(&config, &shared_config, &tx_update_requests, |_| false)?
+            .into_iter()
+            .flatten()
+            .collect();
+
     let mut scheduler = UpdateScheduler::new(&blocks);
 
+    // Themes with a dark variant follow the dark mode, which is watched in a separate thread.
+    // Changing how it is detected requires a restart.
+    let rx_dark_mode = match config.theme.dark_mode() {
+        Some(mode) => {
+            let (tx_dark_mode, rx_dark_mode) = crossbeam_channel::unbounded();
+            themes::dark_mode::watch(mode, tx_dark_mode)?;
+            rx_dark_mode
+        }
+        None => never(),
+    };
+
+    // Profiles follow the network, which is watched in a separate thread. Changing the profiles
+    // requires a restart.
+    let rx_profiles = if config.profiles.is_empty() {
+        never()
+    } else {
+        let (tx_profiles, rx_profiles) = crossbeam_channel::unbounded();
+        profiles::watch(config.profiles.clone(), tx_profiles)?;
+        rx_profiles
+    };
+
+    // We watch the config file in a separate thread and reload the blocks when it changes
+    let mut rx_config = config_watcher(&config_files);
+
     // We wait for click events in a separate thread, to avoid blocking to wait for stdin
-    let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
-        crossbeam_channel::unbounded();
-    process_events(tx_clicks);
+    let rx_clicks = match output {
+        Output::I3Bar => {
+            let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
+                crossbeam_channel::unbounded();
+            process_events(tx_clicks, config.double_click_interval);
+            rx_clicks
+        }
+        Output::Plain { .. } | Output::Headless => never(),
+    };
 
-    // We wait for signals in a separate thread
-    let (tx_signals, rx_signals): (Sender<i32>, Receiver<i32>) = crossbeam_channel::unbounded();
-    process_signals(tx_signals);
+    // Without a bar, the blocks are published periodically instead.
+    // Changing how requires a restart.
+    let (mut publisher, rx_publish) = match output {
+        Output::I3Bar | Output::Plain { .. } => (None, never()),
+        Output::Headless => (
+            Some(Publisher::new(&config.headless)?),
+            crossbeam_channel::tick(config.headless.interval),
+        ),
+    };
+
+    // We take commands on a socket in a separate thread
+    let rx_ipc = match matches.value_of("socket") {
+        Some(path) => {
+            let (tx_ipc, rx_ipc): (Sender<Request>, Receiver<Request>) =
+                crossbeam_channel::unbounded();
+            ipc::listen(Path::new(path), tx_ipc)?;
+            rx_ipc
+        }
+        None => never(),
+    };
 
     // Time to next update channel.
     // Fires immediately for first updates
     let mut ttnu = crossbeam_channel::after(Duration::from_millis(0));
 
+    // Redraw timer for blocks that scroll their text
+    let mut redraw = never();
+
+    // Redraw timer for a block that is shown as clicked
+    let mut release = never();
+
+    // Writes the blocks to a file for other programs, if the config asks for it
+    let mut rx_export = exporter(&config);
+
+    // Checks for blocks that stopped updating, if any block asks for it
+    let mut rx_watchdog = watchdog(&config);
+    let mut last_watchdog = Instant::now();
+    let mut failed_restarts = HashMap::new();
+
     loop {
+        let mut reload = false;
+
         // We use the message passing concept of channel selection
         // to avoid busy wait
         select! {
             // Receive click events
             recv(rx_clicks) -> res => if let Ok(event) = res {
-                if let Some(id) = event.id {
-                        blocks.get_mut(id)
-                    .internal_error("click handler", "could not get required block")?
-                            .click(&event)?;
-                    protocol::print_blocks(&blocks, &shared_config)?;
+                // Clicks on blocks that went away with a config reload are ignored
+                if let Some(id) = event.id.filter(|&id| id < blocks.len()) {
+                    log::debug!("{:?} click on block {}", event.button, id);
+                    // Acknowledge the click before it is handled, which may take a while
+                    blocks[id].press();
+                    output.print_blocks(&blocks, &shared_config)?;
+                    blocks[id].click(&event)?;
+                    output.print_blocks(&blocks, &shared_config)?;
+                    release = crossbeam_channel::after(CLICK_FEEDBACK);
                 }
             },
             // Receive async update requests
             recv(rx_update_requests) -> request => if let Ok(req) = request {
-                if scheduler.schedule.iter().any(|x| x.id == req.id) {
+                if req.id >= blocks.len() {
+                    // Stale request from a block that was removed by a config reload
+                } else if scheduler.schedule.iter().any(|x| x.id == req.id) {
                 // If block is already scheduled then process immediately and forget
                 blocks.get_mut(req.id)
                     .internal_error("scheduler", "could not get required block")?
@@ -181,16 +424,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 scheduler.schedule.push(req);
                 scheduler.do_scheduled_updates(&mut blocks)?;
                 }
-                protocol::print_blocks(&blocks, &shared_config)?;
+                output.print_blocks(&blocks, &shared_config)?;
             },
             // Receive update timer events
             recv(ttnu) -> _ => {
                 scheduler.do_scheduled_updates(&mut blocks)?;
                 // redraw the blocks, state changed
-                protocol::print_blocks(&blocks, &shared_config)?;
+                output.print_blocks(&blocks, &shared_config)?;
             },
             // Receive signal events
             recv(rx_signals) -> res => if let Ok(sig) = res {
+                log::debug!("received signal {}", sig);
                 match sig {
                     signal_hook::consts::SIGUSR1 => {
                         //USR1 signal that updates every block in the bar
@@ -203,6 +447,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         blocks.drain(..);
                         restart();
                     },
+                    signal_hook::consts::SIGTERM | signal_hook::consts::SIGINT => {
+                        // Let the blocks clean up and leave the bar with a placeholder rather
+                        // than with blocks that no longer update
+                        for block in blocks.iter_mut() {
+                            if let Err(error) = block.stop() {
+                                log::warn!("failed to stop block {}: {:?}", block.id(), error);
+                            }
+                        }
+                        output.print_stopped();
+                        return Ok(());
+                    },
                     _ => {
                         //Real time signal that updates only the blocks listening
                         //for that signal
@@ -211,7 +466,89 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         }
                     },
                 };
-                protocol::print_blocks(&blocks, &shared_config)?;
+                output.print_blocks(&blocks, &shared_config)?;
+            },
+            // Publish the blocks without a bar
+            recv(rx_publish) -> _ => if let Some(ref mut publisher) = publisher {
+                publisher.publish(&blocks, &config)?;
+            },
+            // Redraw animated blocks
+            recv(redraw) -> _ => {
+                output.print_blocks(&blocks, &shared_config)?;
+            },
+            // Show a clicked block as usual again
+            recv(release) -> _ => {
+                output.print_blocks(&blocks, &shared_config)?;
+            },
+            // Redraw with the other variant of the theme
+            recv(rx_dark_mode) -> _ => {
+                output.print_blocks(&blocks, &shared_config)?;
+            },
+            // Answer commands from the socket
+            recv(rx_ipc) -> res => if let Ok(request) = res {
+                match request.command {
+                    Command::Snapshot(_) => {
+                        request.snapshot(&protocol::render_blocks(&blocks, &shared_config))
+                    }
+                }
+            },
+            // Keep the export file up to date
+            recv(rx_export) -> _ => if let Some(path) = config.export.path() {
+                if let Err(error) = export::export(&blocks, &config, &path) {
+                    log::warn!("failed to export the blocks: {:?}", error);
+                }
+            },
+            // Show and restart blocks that stopped updating
+            recv(rx_watchdog) -> _ => {
+                let now = Instant::now();
+                // A late tick means that the bar itself was busy, e.g. with a command that hung,
+                // which made all blocks late alike
+                if now - last_watchdog < WATCHDOG_TICK * 2
+                    && restart_stale_blocks(
+                        &mut blocks,
+                        &config,
+                        &shared_config,
+                        &tx_update_requests,
+                        &mut scheduler,
+                        &mut failed_restarts,
+                    )
+                {
+                    output.print_blocks(&blocks, &shared_config)?;
+                }
+                last_watchdog = now;
+            },
+            // Receive config file changes
+            recv(rx_config) -> res => reload = res.is_ok(),
+            // Receive changes of the active profiles, which may change the config
+            recv(rx_profiles) -> res => reload = res.is_ok(),
+        }
+
+        if reload {
+            log::info!("reloading the config");
+            match reload_blocks(
+                &config_path,
+                &config,
+                &raw_config,
+                &mut blocks,
+                &tx_update_requests,
+            ) {
+                Ok((new_config, new_raw_config, new_config_files, new_shared_config)) => {
+                    if new_config_files != config_files {
+                        // The set of included files changed, replace the watcher
+                        rx_config = config_watcher(&new_config_files);
+                        config_files = new_config_files;
+                    }
+                    config = new_config;
+                    raw_config = new_raw_config;
+                    shared_config = new_shared_config;
+                    scheduler = UpdateScheduler::new(&blocks);
+                    rx_export = exporter(&config);
+                    rx_watchdog = watchdog(&config);
+                    failed_restarts.clear();
+                    scheduler.do_scheduled_updates(&mut blocks)?;
+                    output.print_blocks(&blocks, &shared_config)?;
+                }
+                Err(error) => log::error!("Failed to reload config: {:?}", error),
             }
         }
 
@@ -219,7 +556,313 @@ fn run(matches: &ArgMatches) -> Result<()> {
         if let Some(time) = scheduler.time_to_next_update() {
             ttnu = crossbeam_channel::after(time)
         }
+        redraw = if blocks.iter().any(|block| block.animated()) {
+            crossbeam_channel::after(MARQUEE_STEP)
+        } else {
+            never()
+        };
+    }
+}
+
+/// Creates and updates every block once and prints them
+fn run_once(
+    config_path: &Path,
+    config: &Config,
+    shared_config: &SharedConfig,
+    output: &Output,
+) -> Result<()> {
+    let (blocks, failed) = try_blocks(config_path, config, shared_config, true);
+
+    match output {
+        Output::Headless => Publisher::new(&config.headless)?.publish(&blocks, config)?,
+        _ => output.print_blocks(&blocks, shared_config)?,
+    }
+
+    failures(failed, config.blocks.len())
+}
+
+/// Asks the bar on the socket for a snapshot, which it writes to the file or else is printed
+fn snapshot(matches: &ArgMatches) -> Result<()> {
+    let socket = matches.value_of("socket").ok_or_else(|| {
+        Error::ConfigurationError(
+            "--socket is required to find the bar".to_string(),
+            String::new(),
+        )
+    })?;
+    match matches.value_of("file") {
+        Some(file) => {
+            // The bar may run in another directory
+            let file = std::env::current_dir()?.join(file);
+            ipc::send(Path::new(socket), &format!("snapshot {}", file.display()))?;
+        }
+        None => {
+            let rendered = ipc::send(Path::new(socket), "snapshot")?;
+            let value: serde_json::Value = serde_json::from_str(&rendered)
+                .internal_error("snapshot", "the bar sent invalid JSON")?;
+            println!("{:#}", value);
+        }
+    }
+    Ok(())
+}
+
+/// Creates every block without updating it, which deserializes its config and compiles its
+/// formats and regexes
+fn check(config_path: &Path) -> Result<()> {
+    let (config, _, _) = load_config(config_path)?;
+    let shared_config = SharedConfig::new(&config);
+    let (_, failed) = try_blocks(config_path, &config, &shared_config, false);
+    failures(failed, config.blocks.len())?;
+    println!(
+        "{}: {} blocks OK",
+        config_path.display(),
+        config.blocks.len()
+    );
+    Ok(())
+}
+
+/// Creates every configured block and updates it once if `update` is set. Unlike at startup of
+/// the bar, all blocks are tried, and each error is printed to stderr along with the line that
+/// defines the block. Returns the blocks that work and the number of those that don't.
+fn try_blocks(
+    config_path: &Path,
+    config: &Config,
+    shared_config: &SharedConfig,
+    update: bool,
+) -> (Vec<Box<dyn Block>>, usize) {
+    // Blocks that update asynchronously may send requests, which are never answered
+    let (tx_update_requests, _rx_update_requests) = crossbeam_channel::unbounded();
+    let lines = block_lines(config_path);
+
+    let mut blocks = Vec::with_capacity(config.blocks.len());
+    let mut failed = 0;
+    for (id, (block_name, block_config)) in config.blocks.iter().enumerate() {
+        let block = create_block(
+            id,
+            block_name,
+            block_config.clone(),
+            shared_config.clone(),
+            tx_update_requests.clone(),
+        )
+        .and_then(|mut block| {
+            if update {
+                block.update()?;
+            }
+            Ok(block)
+        });
+        match block {
+            Ok(block) => blocks.push(block),
+            Err(error) => {
+                // Blocks from included files are only told by their position
+                let location = match lines.get(id) {
+                    Some(line) => format!("{}:{}: ", config_path.display(), line),
+                    None => String::new(),
+                };
+                eprintln!("{}block {} ({}): {:?}", location, id, block_name, error);
+                failed += 1;
+            }
+        }
+    }
+    (blocks, failed)
+}
+
+fn failures(failed: usize, total: usize) -> Result<()> {
+    if failed > 0 {
+        return Err(ConfigurationError(
+            format!("{} of {} blocks failed", failed, total),
+            "see above".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads and parses the config file and the files it includes. The raw TOML is returned as well
+/// so that config reloads can tell which parts of it have changed.
+fn load_config(path: &Path) -> Result<(Config, toml::Value, Vec<ConfigFilePattern>)> {
+    let (mut raw_config, config_files) = read_config(path)?;
+    profiles::apply(&mut raw_config)?;
+    let config = Config::deserialize(raw_config.clone())
+        .configuration_error("failed to parse TOML from file contents")?;
+    Ok((config, raw_config, config_files))
+}
+
+/// The global part of the raw config, i.e. everything but the block definitions
+fn without_blocks(raw_config: &toml::Value) -> Option<toml::value::Table> {
+    raw_config.as_table().map(|table| {
+        let mut table = table.clone();
+        table.remove("block");
+        table
+    })
+}
+
+/// Instantiates all configured blocks. The slots of blocks for which `keep` returns `true` are
+/// left empty, so that the caller can fill them with an already running block instead.
+fn create_blocks(
+    config: &Config,
+    shared_config: &SharedConfig,
+    tx_update_requests: &Sender<Task>,
+    keep: impl Fn(usize) -> bool,
+) -> Result<Vec<Option<Box<dyn Block>>>> {
+    let mut blocks = Vec::with_capacity(config.blocks.len());
+    for (id, &(ref block_name, ref block_config)) in config.blocks.iter().enumerate() {
+        if keep(id) {
+            blocks.push(None);
+        } else {
+            blocks.push(Some(create_block(
+                id,
+                block_name,
+                block_config.clone(),
+                shared_config.clone(),
+                tx_update_requests.clone(),
+            )?));
+        }
+    }
+    Ok(blocks)
+}
+
+/// Re-reads the config file and rebuilds the block list in place.
+///
+/// Blocks are identified by their position, so a block keeps its state across the reload only if
+/// neither its own config nor its position nor any of the global settings changed. If the new
+/// config fails to load, the running blocks are left untouched.
+fn reload_blocks(
+    config_path: &Path,
+    config: &Config,
+    raw_config: &toml::Value,
+    blocks: &mut Vec<Box<dyn Block>>,
+    tx_update_requests: &Sender<Task>,
+) -> Result<(Config, toml::Value, Vec<ConfigFilePattern>, SharedConfig)> {
+    let (new_config, new_raw_config, config_files) = load_config(config_path)?;
+    let globals_unchanged = without_blocks(&new_raw_config) == without_blocks(raw_config);
+    let shared_config = SharedConfig::new(&new_config);
+
+    let new_blocks = create_blocks(&new_config, &shared_config, tx_update_requests, |id| {
+        globals_unchanged && id < blocks.len() && config.blocks.get(id) == new_config.blocks.get(id)
+    })?;
+
+    let mut old_blocks: Vec<Option<Box<dyn Block>>> = blocks.drain(..).map(Some).collect();
+    for (id, block) in new_blocks.into_iter().enumerate() {
+        match block {
+            Some(block) => blocks.push(block),
+            None => blocks.push(
+                old_blocks
+                    .get_mut(id)
+                    .and_then(Option::take)
+                    .internal_error("config reload", "could not get required block")?,
+            ),
+        }
+    }
+
+    // The blocks that were replaced or removed undo what they changed outside of the bar. Their
+    // watchers, threads and subprocesses end as they are dropped here.
+    for (id, mut block) in old_blocks.into_iter().enumerate() {
+        if let Some(block) = block.as_mut() {
+            if let Err(error) = block.stop() {
+                log::warn!("failed to stop block {}: {:?}", id, error);
+            }
+        }
+    }
+
+    Ok((new_config, new_raw_config, config_files, shared_config))
+}
+
+/// Watches the config files. Without a watcher, e.g. because inotify is out of watches, the bar
+/// works as usual but needs a restart to pick up changes.
+fn config_watcher(config_files: &[ConfigFilePattern]) -> Receiver<()> {
+    let (tx_config, rx_config) = crossbeam_channel::unbounded();
+    match watch_config_files(config_files, tx_config) {
+        Ok(()) => rx_config,
+        Err(error) => {
+            log::warn!("failed to watch the config: {:?}", error);
+            never()
+        }
+    }
+}
+
+fn exporter(config: &Config) -> Receiver<Instant> {
+    match config.export.path {
+        Some(_) => crossbeam_channel::tick(config.export.interval),
+        None => never(),
+    }
+}
+
+/// How often the blocks are checked for having stopped updating
+const WATCHDOG_TICK: Duration = Duration::from_secs(1);
+
+/// How long to wait before trying again to restart a block that failed to restart
+const RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+fn watchdog(config: &Config) -> Receiver<Instant> {
+    if config
+        .blocks
+        .iter()
+        .any(|(_, block_config)| block_config.get("watchdog").is_some())
+    {
+        crossbeam_channel::tick(WATCHDOG_TICK)
+    } else {
+        never()
+    }
+}
+
+/// Recreates the blocks that stopped updating. The stale block is stopped and dropped, which ends
+/// whatever it waits on, e.g. a thread, a file watch or a subprocess, and the new one starts its
+/// own. Until it updates again, a restarted block is scheduled like a new one.
+///
+/// Returns whether any block was stale, so that the bar shows it.
+fn restart_stale_blocks(
+    blocks: &mut [Box<dyn Block>],
+    config: &Config,
+    shared_config: &SharedConfig,
+    tx_update_requests: &Sender<Task>,
+    scheduler: &mut UpdateScheduler,
+    failed_restarts: &mut HashMap<usize, Instant>,
+) -> bool {
+    let mut any_stale = false;
+    for (id, block) in blocks.iter_mut().enumerate() {
+        if !block.stale() {
+            continue;
+        }
+        any_stale = true;
+        if failed_restarts
+            .get(&id)
+            .map(|failed| failed.elapsed() < RESTART_BACKOFF)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        let (name, block_config) = &config.blocks[id];
+        log::warn!("block {} ({}) stopped updating, restarting it", id, name);
+        match create_block(
+            id,
+            name,
+            block_config.clone(),
+            shared_config.clone(),
+            tx_update_requests.clone(),
+        ) {
+            Ok(new_block) => {
+                let mut old_block = std::mem::replace(block, new_block);
+                if let Err(error) = old_block.stop() {
+                    log::warn!("failed to stop block {}: {:?}", id, error);
+                }
+                drop(old_block);
+                failed_restarts.remove(&id);
+                scheduler.schedule = scheduler
+                    .schedule
+                    .drain()
+                    .filter(|task| task.id != id)
+                    .collect();
+                scheduler.schedule.push(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            }
+            Err(error) => {
+                log::warn!("failed to restart block {}: {:?}", id, error);
+                failed_restarts.insert(id, Instant::now());
+            }
+        }
     }
+    any_stale
 }
 
 /// Restart `i3status-rs` in-place
@@ -0,0 +1,118 @@
+//! Menus that blocks show in a launcher like rofi or dmenu on a right click, e.g. to pick what to
+//! start from a list.
+//!
+//! The launcher gets the labels of the entries as lines on its stdin and prints the chosen one.
+//! It waits for the user, so it runs in a thread of its own, which also runs the command of the
+//! chosen entry and then updates the block.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::Sender;
+
+use crate::errors::*;
+use crate::scheduler::Task;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuEntry {
+    pub label: String,
+    /// Shell command that choosing the entry runs
+    pub command: String,
+}
+
+pub struct Launcher {
+    command: String,
+    update_request: Sender<Task>,
+}
+
+impl Launcher {
+    pub fn new(command: String, update_request: Sender<Task>) -> Self {
+        Self {
+            command,
+            update_request,
+        }
+    }
+
+    /// Shows `entries` in the launcher, runs the command of the one that is chosen and then
+    /// updates the block with the id `id`
+    pub fn show(&self, id: usize, entries: Vec<MenuEntry>) -> Result<()> {
+        let launcher = self.command.clone();
+        let update_request = self.update_request.clone();
+        thread::Builder::new()
+            .name("menu".into())
+            .spawn(move || {
+                let entry = match choose(&launcher, &entries) {
+                    Ok(Some(index)) => &entries[index],
+                    Ok(None) => return,
+                    Err(error) => {
+                        log::warn!("{:?}", error);
+                        return;
+                    }
+                };
+                match Command::new("sh").args(["-c", &entry.command]).status() {
+                    Ok(status) if !status.success() => {
+                        log::warn!("'{}' failed: {}", entry.command, status)
+                    }
+                    Err(error) => log::warn!("failed to run '{}': {}", entry.command, error),
+                    Ok(_) => {}
+                }
+                let _ = update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .internal_error("menu", "failed to start a thread")?;
+        Ok(())
+    }
+}
+
+/// Runs `launcher` with the labels of `entries` and returns the index of the chosen one, `None`
+/// if the launcher was dismissed
+fn choose(launcher: &str, entries: &[MenuEntry]) -> Result<Option<usize>> {
+    let mut child = Command::new("sh")
+        .args(["-c", launcher])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .block_error("menu", &format!("failed to run '{}'", launcher))?;
+
+    let labels: Vec<&str> = entries.iter().map(|entry| entry.label.as_str()).collect();
+    if let Some(mut stdin) = child.stdin.take() {
+        // A launcher that exits early doesn't read them all
+        let _ = writeln!(stdin, "{}", labels.join("\n"));
+    }
+    let mut chosen = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        let _ = stdout.read_to_string(&mut chosen);
+    }
+    let status = child
+        .wait()
+        .block_error("menu", &format!("failed to run '{}'", launcher))?;
+    if !status.success() {
+        return Ok(None);
+    }
+    let chosen = chosen.trim_end_matches('\n');
+    Ok(labels.iter().position(|label| *label == chosen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose() {
+        let entries: Vec<MenuEntry> = ["work", "home"]
+            .iter()
+            .map(|label| MenuEntry {
+                label: label.to_string(),
+                command: String::new(),
+            })
+            .collect();
+        assert_eq!(choose("tail -n 1", &entries).unwrap(), Some(1));
+        // Dismissed, or something that isn't an entry was typed
+        assert_eq!(choose("cat > /dev/null; exit 1", &entries).unwrap(), None);
+        assert_eq!(choose("echo garden", &entries).unwrap(), None);
+    }
+}
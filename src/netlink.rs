@@ -0,0 +1,410 @@
+//! Routes, links and their changes from the kernel over rtnetlink, so that blocks don't have to run
+//! `ip` or read `/sys` to learn about the network, and hear about changes as they happen.
+//!
+//! Messages are a header followed by a fixed struct and attributes, see rtnetlink(7). Only the
+//! few fields that are used are read.
+
+use std::convert::TryInto;
+use std::net::Ipv4Addr;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use crate::errors::*;
+
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const RTM_NEWLINK: u16 = 16;
+const RTM_GETLINK: u16 = 18;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_GETROUTE: u16 = 26;
+
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_MULTI: u16 = 0x2;
+const NLM_F_DUMP: u16 = 0x300;
+
+const HEADER_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const RTMSG_LEN: usize = 12;
+
+const IFLA_IFNAME: u16 = 3;
+const IFLA_OPERSTATE: u16 = 16;
+const IFLA_STATS64: u16 = 23;
+const IFLA_CARRIER: u16 = 33;
+const RTA_DST: u16 = 1;
+const RTA_OIF: u16 = 4;
+const RTA_PRIORITY: u16 = 6;
+const RTA_TABLE: u16 = 15;
+
+const AF_INET: u8 = 2;
+const RT_TABLE_MAIN: u32 = 254;
+const RTN_UNICAST: u8 = 1;
+const IFF_UP: u32 = 0x1;
+const IF_OPER_UP: u8 = 6;
+
+/// Groups of the changes of links, addresses and routes
+const RTMGRP_LINK: u32 = 0x1;
+const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+const RTMGRP_IPV4_ROUTE: u32 = 0x40;
+const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+const RTMGRP_IPV6_ROUTE: u32 = 0x400;
+
+/// A netlink socket, closed when dropped
+#[derive(Debug)]
+pub struct Socket(RawFd);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open(groups: u32) -> Result<Socket> {
+    use nix::sys::socket::*;
+
+    let socket = socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkRoute,
+    )
+    .block_error("netlink", "failed to open a netlink socket")?;
+    let socket = Socket(socket);
+    bind(socket.0, &SockAddr::Netlink(NetlinkAddr::new(0, groups)))
+        .block_error("netlink", "failed to bind the netlink socket")?;
+    Ok(socket)
+}
+
+/// Netlink is a Linux interface
+#[cfg(not(target_os = "linux"))]
+fn open(_groups: u32) -> Result<Socket> {
+    Err(BlockError(
+        "netlink".to_string(),
+        "netlink is only available on Linux".to_string(),
+    ))
+}
+
+/// A socket that receives a message whenever a link, an address or a route changes. Reads time out
+/// after `timeout`, if set.
+pub fn subscribe(timeout: Option<Duration>) -> Result<Socket> {
+    let socket = open(
+        RTMGRP_LINK
+            | RTMGRP_IPV4_IFADDR
+            | RTMGRP_IPV6_IFADDR
+            | RTMGRP_IPV4_ROUTE
+            | RTMGRP_IPV6_ROUTE,
+    )?;
+    if let Some(timeout) = timeout {
+        use nix::sys::socket::{setsockopt, sockopt};
+        use nix::sys::time::{TimeVal, TimeValLike};
+
+        setsockopt(
+            socket.0,
+            sockopt::ReceiveTimeout,
+            &TimeVal::milliseconds(timeout.as_millis() as i64),
+        )
+        .block_error("netlink", "failed to set a timeout")?;
+    }
+    Ok(socket)
+}
+
+/// Waits for changes on a socket of `subscribe` and returns whether there were any before the
+/// timeout. Changes rarely come alone, e.g. connecting adds an address and then routes, so the
+/// ones that follow within `settle` are waited for as well.
+pub fn wait_for_change(socket: &Socket, settle: Duration) -> bool {
+    use nix::sys::socket::{recv, MsgFlags};
+
+    let mut buffer = [0; 8192];
+    if recv(socket.0, &mut buffer, MsgFlags::empty()).is_err() {
+        return false;
+    }
+    std::thread::sleep(settle);
+    while recv(socket.0, &mut buffer, MsgFlags::MSG_DONTWAIT).is_ok() {}
+    true
+}
+
+/// The types and payloads of the replies to a request, or the error that the kernel refused it with
+type Replies = std::result::Result<Vec<(u16, Vec<u8>)>, Errno>;
+
+/// Sends a request and returns the replies, up to the end of a dump
+fn request(message_type: u16, flags: u16, payload: &[u8]) -> Result<Replies> {
+    use nix::sys::socket::{recv, send, MsgFlags};
+
+    let socket = open(0)?;
+    let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+    message.extend(((HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+    message.extend(message_type.to_ne_bytes());
+    message.extend((NLM_F_REQUEST | flags).to_ne_bytes());
+    // Sequence number and port, which the kernel fills in
+    message.extend(1u32.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    message.extend(payload);
+    send(socket.0, &message, MsgFlags::empty())
+        .block_error("netlink", "failed to send a netlink request")?;
+
+    let mut replies = Vec::new();
+    let mut buffer = vec![0; 32768];
+    loop {
+        let len = recv(socket.0, &mut buffer, MsgFlags::empty())
+            .block_error("netlink", "failed to receive a netlink reply")?;
+        let mut more = false;
+        for (message_type, flags, payload) in messages(&buffer[..len]) {
+            match message_type {
+                NLMSG_DONE => return Ok(Ok(replies)),
+                NLMSG_ERROR => {
+                    let error = payload
+                        .get(..4)
+                        .map(|code| i32::from_ne_bytes([code[0], code[1], code[2], code[3]]))
+                        .unwrap_or(0);
+                    // An error of 0 acknowledges the request
+                    if error != 0 {
+                        return Ok(Err(Errno::from_i32(-error)));
+                    }
+                }
+                _ => replies.push((message_type, payload.to_vec())),
+            }
+            more |= flags & NLM_F_MULTI != 0;
+        }
+        if !more {
+            return Ok(Ok(replies));
+        }
+    }
+}
+
+/// The type, flags and payload of each message in `buffer`
+fn messages(mut buffer: &[u8]) -> Vec<(u16, u16, &[u8])> {
+    let mut messages = Vec::new();
+    while buffer.len() >= HEADER_LEN {
+        let len = u32::from_ne_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+        if len < HEADER_LEN || len > buffer.len() {
+            break;
+        }
+        let message_type = u16::from_ne_bytes([buffer[4], buffer[5]]);
+        let flags = u16::from_ne_bytes([buffer[6], buffer[7]]);
+        messages.push((message_type, flags, &buffer[HEADER_LEN..len]));
+        buffer = &buffer[align(len).min(buffer.len())..];
+    }
+    messages
+}
+
+/// The type and payload of each attribute in `buffer`
+fn attributes(mut buffer: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attributes = Vec::new();
+    while buffer.len() >= 4 {
+        let len = u16::from_ne_bytes([buffer[0], buffer[1]]) as usize;
+        if len < 4 || len > buffer.len() {
+            break;
+        }
+        // The upper bits are flags, like whether the attribute is nested
+        let attribute_type = u16::from_ne_bytes([buffer[2], buffer[3]]) & 0x3fff;
+        attributes.push((attribute_type, &buffer[4..len]));
+        buffer = &buffer[align(len).min(buffer.len())..];
+    }
+    attributes
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// The payload of the attribute `wanted` among those after the fixed struct of `offset` bytes
+fn attribute(buffer: &[u8], offset: usize, wanted: u16) -> Option<&[u8]> {
+    attributes(buffer.get(offset..)?)
+        .into_iter()
+        .find(|(attribute_type, _)| *attribute_type == wanted)
+        .map(|(_, payload)| payload)
+}
+
+fn read_u32(payload: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(payload.get(..4)?.try_into().ok()?))
+}
+
+fn read_u64(payload: &[u8]) -> Option<u64> {
+    Some(u64::from_ne_bytes(payload.get(..8)?.try_into().ok()?))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    family: u8,
+    dst_len: u8,
+    table: u32,
+    route_type: u8,
+    oif: Option<u32>,
+    priority: u32,
+}
+
+fn parse_route(payload: &[u8]) -> Option<Route> {
+    let header = payload.get(..RTMSG_LEN)?;
+    Some(Route {
+        family: header[0],
+        dst_len: header[1],
+        // Tables above 255 are only in the attribute
+        table: attribute(payload, RTMSG_LEN, RTA_TABLE)
+            .and_then(read_u32)
+            .unwrap_or(header[4] as u32),
+        route_type: header[7],
+        oif: attribute(payload, RTMSG_LEN, RTA_OIF).and_then(read_u32),
+        priority: attribute(payload, RTMSG_LEN, RTA_PRIORITY)
+            .and_then(read_u32)
+            .unwrap_or(0),
+    })
+}
+
+/// The interface of the best default route of the main table, IPv4 before IPv6
+fn best_default(routes: &[Route]) -> Option<u32> {
+    routes
+        .iter()
+        .filter(|route| {
+            route.dst_len == 0 && route.table == RT_TABLE_MAIN && route.route_type == RTN_UNICAST
+        })
+        .filter_map(|route| Some(((route.family != AF_INET, route.priority), route.oif?)))
+        .min()
+        .map(|(_, oif)| oif)
+}
+
+fn interface_name(index: u32) -> Option<String> {
+    let interfaces = nix::net::if_::if_nameindex().ok()?;
+    let name = interfaces
+        .iter()
+        .find(|interface| interface.index() == index)?
+        .name()
+        .to_string_lossy()
+        .into_owned();
+    Some(name)
+}
+
+/// The interface of the default route, like `ip route show default`
+pub fn default_device() -> Option<String> {
+    // A dump of all families
+    let request = [0; RTMSG_LEN];
+    let routes: Vec<Route> = self::request(RTM_GETROUTE, NLM_F_DUMP, &request)
+        .ok()?
+        .ok()?
+        .iter()
+        .filter(|(message_type, _)| *message_type == RTM_NEWROUTE)
+        .filter_map(|(_, payload)| parse_route(payload))
+        .collect();
+    interface_name(best_default(&routes)?)
+}
+
+/// The interface that traffic to `destination` goes through, like `ip route get`, which takes
+/// rules into account that send traffic elsewhere than the default route
+pub fn route_device(destination: Ipv4Addr) -> Option<String> {
+    let mut request = vec![0; RTMSG_LEN];
+    request[0] = AF_INET;
+    request[1] = 32;
+    request.extend(8u16.to_ne_bytes());
+    request.extend(RTA_DST.to_ne_bytes());
+    request.extend(destination.octets());
+    // Fails with ENETUNREACH while there is no route
+    let replies = self::request(RTM_GETROUTE, 0, &request).ok()?.ok()?;
+    let route = replies
+        .iter()
+        .find(|(message_type, _)| *message_type == RTM_NEWROUTE)
+        .and_then(|(_, payload)| parse_route(payload))?;
+    interface_name(route.oif?)
+}
+
+/// The state and traffic of a link
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// Whether the link was set up
+    pub up: bool,
+    /// Whether the link is operational, see RFC 2863
+    pub operational: bool,
+    pub carrier: bool,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+fn parse_link(payload: &[u8]) -> Option<Link> {
+    let header = payload.get(..IFINFOMSG_LEN)?;
+    let flags = read_u32(&header[8..12])?;
+    // Counts the received and sent packets, then the bytes
+    let stats = attribute(payload, IFINFOMSG_LEN, IFLA_STATS64).unwrap_or(&[]);
+    Some(Link {
+        up: flags & IFF_UP != 0,
+        operational: attribute(payload, IFINFOMSG_LEN, IFLA_OPERSTATE)
+            .is_some_and(|state| state.first() == Some(&IF_OPER_UP)),
+        carrier: attribute(payload, IFINFOMSG_LEN, IFLA_CARRIER)
+            .is_some_and(|carrier| carrier.first() == Some(&1)),
+        rx_bytes: stats.get(16..).and_then(read_u64).unwrap_or(0),
+        tx_bytes: stats.get(24..).and_then(read_u64).unwrap_or(0),
+    })
+}
+
+/// The link of the interface `name`, `None` if there is no such interface
+pub fn link(name: &str) -> Result<Option<Link>> {
+    let mut request = vec![0; IFINFOMSG_LEN];
+    let mut name_attribute = name.as_bytes().to_vec();
+    name_attribute.push(0);
+    request.extend(((4 + name_attribute.len()) as u16).to_ne_bytes());
+    request.extend(IFLA_IFNAME.to_ne_bytes());
+    request.extend(&name_attribute);
+    request.resize(align(request.len()), 0);
+
+    let replies = match self::request(RTM_GETLINK, 0, &request)? {
+        Ok(replies) => replies,
+        Err(Errno::ENODEV) => return Ok(None),
+        Err(error) => {
+            return Err(BlockError(
+                "netlink".to_string(),
+                format!("failed to get the link of {}: {}", name, error),
+            ))
+        }
+    };
+    Ok(replies
+        .iter()
+        .find(|(message_type, _)| *message_type == RTM_NEWLINK)
+        .and_then(|(_, payload)| parse_link(payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_attribute(buffer: &mut Vec<u8>, attribute_type: u16, payload: &[u8]) {
+        buffer.extend(((4 + payload.len()) as u16).to_ne_bytes());
+        buffer.extend(attribute_type.to_ne_bytes());
+        buffer.extend(payload);
+        buffer.resize(align(buffer.len()), 0);
+    }
+
+    fn route(family: u8, dst_len: u8, oif: u32, priority: u32) -> Vec<u8> {
+        let mut payload = vec![family, dst_len, 0, 0, 254, 3, 0, RTN_UNICAST, 0, 0, 0, 0];
+        push_attribute(&mut payload, RTA_OIF, &oif.to_ne_bytes());
+        push_attribute(&mut payload, RTA_PRIORITY, &priority.to_ne_bytes());
+        payload
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let mut buffer = Vec::new();
+        for payload in [
+            route(2, 24, 2, 100),
+            route(2, 0, 3, 600),
+            route(2, 0, 2, 100),
+        ] {
+            buffer.extend(((HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+            buffer.extend(RTM_NEWROUTE.to_ne_bytes());
+            buffer.extend(NLM_F_MULTI.to_ne_bytes());
+            buffer.extend([0; 8]);
+            buffer.extend(payload);
+        }
+        let routes: Vec<Route> = messages(&buffer)
+            .into_iter()
+            .filter_map(|(_, _, payload)| parse_route(payload))
+            .collect();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[1].oif, Some(3));
+        assert_eq!(routes[1].priority, 600);
+        // The default route with the lowest metric, not the route to the local network
+        assert_eq!(best_default(&routes), Some(2));
+        // IPv6 only if there is no IPv4 default route
+        let ipv6 = parse_route(&route(10, 0, 4, 1)).unwrap();
+        assert_eq!(best_default(&[ipv6.clone(), routes[1].clone()]), Some(3));
+        assert_eq!(best_default(&[ipv6]), Some(4));
+    }
+}
@@ -0,0 +1,224 @@
+//! Profiles switch parts of the config depending on the network the machine is connected to, e.g.
+//! to hide work blocks at home and vice versa.
+//!
+//! Blocks list the profiles they belong to in their `profiles` option. A profile may also replace
+//! the theme while it is active, which is done by rewriting the config before it is parsed, so
+//! that switching profiles is just a config reload.
+
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use serde::de::Deserialize;
+use serde_derive::Deserialize;
+use toml::value;
+
+use crate::blocks::net::NetworkDevice;
+use crate::errors::*;
+use crate::netlink;
+use crate::util::escape_pango_text;
+
+lazy_static! {
+    /// Names of the profiles that match the current network
+    static ref ACTIVE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+
+    /// SSIDs of Wi-Fi networks that activate the profile
+    #[serde(default)]
+    pub ssid: Vec<String>,
+
+    /// Subnets (e.g. `10.1.0.0/16`) that activate the profile if the machine has an address in them
+    #[serde(default)]
+    pub subnet: Vec<String>,
+
+    /// Theme used instead of the global one while the profile is active
+    pub theme: Option<value::Value>,
+}
+
+/// What the machine is connected to
+#[derive(Debug, Default)]
+struct Network {
+    ssids: Vec<String>,
+    addresses: Vec<IpAddr>,
+}
+
+impl Profile {
+    fn matches(&self, network: &Network) -> bool {
+        self.ssid
+            .iter()
+            .any(|ssid| network.ssids.contains(&escape_pango_text(ssid)))
+            || self.subnet.iter().any(|subnet| {
+                network
+                    .addresses
+                    .iter()
+                    .any(|address| in_subnet(*address, subnet))
+            })
+    }
+}
+
+/// Whether `address` is in `subnet`, which is written as an address and a prefix length
+fn in_subnet(address: IpAddr, subnet: &str) -> bool {
+    let (network, prefix) = match subnet.split_once('/') {
+        Some((network, prefix)) => (network.parse::<IpAddr>(), prefix.parse::<u32>()),
+        None => return subnet.parse::<IpAddr>() == Ok(address),
+    };
+    match (address, network, prefix) {
+        (IpAddr::V4(address), Ok(IpAddr::V4(network)), Ok(prefix)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), Ok(IpAddr::V6(network)), Ok(prefix)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn current_network() -> Network {
+    let mut network = Network::default();
+    let interfaces = match nix::ifaddrs::getifaddrs() {
+        Ok(interfaces) => interfaces,
+        Err(_) => return network,
+    };
+    let mut names: Vec<String> = Vec::new();
+    for interface in interfaces {
+        if let Some(nix::sys::socket::SockAddr::Inet(address)) = interface.address {
+            network.addresses.push(address.to_std().ip());
+        }
+        if !names.contains(&interface.interface_name) {
+            names.push(interface.interface_name);
+        }
+    }
+    for name in names {
+        let device = NetworkDevice::from_device(name);
+        if device.is_wireless() {
+            if let Ok((Some(ssid), _, _)) = device.wifi_info() {
+                network.ssids.push(ssid);
+            }
+        }
+    }
+    network
+}
+
+/// Makes `active` the active profiles, returning whether they changed
+fn set_active(active: Vec<String>) -> bool {
+    match ACTIVE.lock() {
+        Ok(mut current) if *current != active => {
+            *current = active;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn evaluate(profiles: &[Profile]) -> bool {
+    let network = current_network();
+    set_active(
+        profiles
+            .iter()
+            .filter(|profile| profile.matches(&network))
+            .map(|profile| profile.name.clone())
+            .collect(),
+    )
+}
+
+fn is_active(profile: &str) -> bool {
+    ACTIVE
+        .lock()
+        .map(|active| active.iter().any(|name| name == profile))
+        .unwrap_or(false)
+}
+
+/// Whether a block that belongs to `profiles` is shown. It is if any of them is active, and names
+/// prefixed with `!` hide the block while that profile is active instead.
+pub fn shown(profiles: &[String]) -> bool {
+    let (hidden_in, shown_in): (Vec<&String>, Vec<&String>) =
+        profiles.iter().partition(|name| name.starts_with('!'));
+    !hidden_in.iter().any(|name| is_active(&name[1..]))
+        && (shown_in.is_empty() || shown_in.iter().any(|name| is_active(name)))
+}
+
+/// Reads the `profile` definitions of the raw config, finds out which are active right now and
+/// puts the theme of the first active profile that has one in place of the global theme
+pub fn apply(config: &mut value::Value) -> Result<()> {
+    let table = match config.as_table_mut() {
+        Some(table) => table,
+        None => return Ok(()),
+    };
+    let profiles = match table.get("profile") {
+        Some(profiles) => Vec::<Profile>::deserialize(profiles.clone())
+            .configuration_error("failed to parse the profiles")?,
+        None => return Ok(()),
+    };
+    evaluate(&profiles);
+    let theme = profiles
+        .iter()
+        .filter(|profile| is_active(&profile.name))
+        .find_map(|profile| profile.theme.clone());
+    if let Some(theme) = theme {
+        table.insert("theme".to_string(), theme);
+    }
+    Ok(())
+}
+
+/// Finds out which profiles are active whenever the network changes, in a separate thread. `tx`
+/// is notified if they changed.
+pub fn watch(profiles: Vec<Profile>, tx: Sender<()>) -> Result<()> {
+    let events = network_events();
+    thread::Builder::new()
+        .name("profiles".into())
+        .spawn(move || loop {
+            wait_for_change(&events);
+            if evaluate(&profiles) && tx.send(()).is_err() {
+                break;
+            }
+        })
+        .internal_error("profiles", "failed to start watching thread")?;
+    Ok(())
+}
+
+fn network_events() -> Option<netlink::Socket> {
+    // Changing the SSID doesn't always change an address, so check now and then anyway
+    match netlink::subscribe(Some(Duration::from_secs(60))) {
+        Ok(socket) => Some(socket),
+        Err(error) => {
+            log::debug!("polling the network: {:?}", error);
+            None
+        }
+    }
+}
+
+fn wait_for_change(events: &Option<netlink::Socket>) {
+    match events {
+        // Connecting takes a series of changes, wait for them to settle
+        Some(socket) => {
+            netlink::wait_for_change(socket, Duration::from_secs(2));
+        }
+        None => thread::sleep(Duration::from_secs(30)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_subnet() {
+        let address: IpAddr = "10.1.2.3".parse().unwrap();
+        assert!(in_subnet(address, "10.1.0.0/16"));
+        assert!(!in_subnet(address, "10.2.0.0/16"));
+        assert!(in_subnet(address, "0.0.0.0/0"));
+        assert!(in_subnet(address, "10.1.2.3"));
+        assert!(!in_subnet(address, "fd00::/8"));
+        assert!(in_subnet("fd00::1".parse().unwrap(), "fd00::/8"));
+    }
+}
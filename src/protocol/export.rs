@@ -0,0 +1,74 @@
+//! Keeps a JSON file with the text of the blocks up to date while the bar runs, so that desktop
+//! widgets, overlays or scripts can reuse what the bar already collects.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde_derive::Deserialize;
+
+use super::headless::report;
+use crate::blocks::Block;
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct ExportConfig {
+    /// File to write the blocks to, nothing is exported without it
+    pub path: Option<String>,
+
+    /// How often the file is written
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ExportConfig {
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path
+            .as_ref()
+            .map(|path| PathBuf::from(shellexpand::tilde(path).to_string()))
+    }
+}
+
+/// Writes the same JSON object as `--headless` to `path`. The file is replaced in one step, so
+/// that readers never see it half written.
+pub fn export(blocks: &[Box<dyn Block>], config: &Config, path: &Path) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .internal_error("export", "the path has no file name")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).internal_error("export", "failed to create the directory")?;
+    }
+    let temp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    fs::write(&temp, report(blocks, config, SystemTime::now()))
+        .internal_error("export", "failed to write the file")?;
+    fs::rename(&temp, path).internal_error("export", "failed to replace the file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("bar").join("blocks.json");
+        export(&[], &Config::default(), &path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["blocks"], serde_json::json!([]));
+        assert!(!dir.path().join("bar").join(".blocks.json.tmp").exists());
+    }
+}
@@ -0,0 +1,164 @@
+//! Publishes the text of the blocks as JSON instead of running the i3bar protocol, e.g. to collect
+//! them as metrics on a machine without a bar.
+
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use crate::blocks::Block;
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HeadlessConfig {
+    /// How often the blocks are published
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Publish to an MQTT broker instead of stdout
+    pub mqtt: Option<MqttConfig>,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            mqtt: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub topic: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            topic: "i3status-rs".to_string(),
+        }
+    }
+}
+
+/// Writes one JSON object per line, either to stdout or to `mosquitto_pub`, which publishes every
+/// line it reads as a message
+pub enum Publisher {
+    Stdout,
+    Mqtt(Child, ChildStdin),
+}
+
+impl Publisher {
+    pub fn new(config: &HeadlessConfig) -> Result<Self> {
+        let mqtt = match &config.mqtt {
+            Some(mqtt) => mqtt,
+            None => return Ok(Publisher::Stdout),
+        };
+        let mut child = Command::new("mosquitto_pub")
+            .arg("-h")
+            .arg(&mqtt.host)
+            .arg("-p")
+            .arg(mqtt.port.to_string())
+            .arg("-t")
+            .arg(&mqtt.topic)
+            .arg("-l")
+            .stdin(Stdio::piped())
+            .spawn()
+            .configuration_error("failed to run mosquitto_pub")?;
+        let stdin = child
+            .stdin
+            .take()
+            .internal_error("headless", "failed to open stdin of mosquitto_pub")?;
+        Ok(Publisher::Mqtt(child, stdin))
+    }
+
+    pub fn publish(&mut self, blocks: &[Box<dyn Block>], config: &Config) -> Result<()> {
+        let line = report(blocks, config, SystemTime::now());
+        match self {
+            Publisher::Stdout => println!("{}", line),
+            Publisher::Mqtt(child, stdin) => {
+                if let Ok(Some(status)) = child.try_wait() {
+                    return Err(InternalError(
+                        "headless".to_string(),
+                        format!("mosquitto_pub exited with {}", status),
+                        None,
+                    ));
+                }
+                writeln!(stdin, "{}", line)
+                    .internal_error("headless", "failed to write to mosquitto_pub")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The text of every visible block that isn't private, along with the kind of the block as in the
+/// config
+pub(super) fn report(blocks: &[Box<dyn Block>], config: &Config, time: SystemTime) -> String {
+    let blocks: Vec<serde_json::Value> = blocks
+        .iter()
+        .filter(|block| !block.private() && !block.view().is_empty())
+        .map(|block| {
+            let text: Vec<String> = block
+                .view()
+                .iter()
+                .map(|widget| widget.get_data().full_text.trim().to_string())
+                .filter(|text| !text.is_empty())
+                .collect();
+            json!({
+                "id": block.id(),
+                "block": config.blocks.get(block.id()).map(|(name, _)| name),
+                "text": text.join(" "),
+            })
+        })
+        .collect();
+    json!({
+        "time": time.duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        "blocks": blocks,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::text::TextWidget;
+    use crate::widgets::I3BarWidget;
+
+    struct Static(TextWidget);
+
+    impl Block for Static {
+        fn id(&self) -> usize {
+            0
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![&self.0]
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let blocks: Vec<Box<dyn Block>> = vec![Box::new(Static(
+            TextWidget::new(0, 0, Default::default()).with_text("50%"),
+        ))];
+        let config = Config {
+            blocks: vec![("cpu".to_string(), toml::Value::Boolean(true))],
+            ..Config::default()
+        };
+        assert_eq!(
+            report(&blocks, &config, UNIX_EPOCH + Duration::from_secs(60)),
+            r#"{"blocks":[{"block":"cpu","id":0,"text":"50%"}],"time":60}"#
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use serde_derive::Deserialize;
+
 use crate::themes::Color;
 
 /// Represent block as described in https://i3wm.org/docs/i3bar-protocol.html
@@ -114,14 +116,18 @@ impl Default for I3BarBlock {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Where i3bar puts the text in a block that is wider than it because of `min_width`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum I3BarBlockAlign {
     Center,
     Right,
     Left,
 }
 
-#[derive(Debug, Clone)]
+/// The width of a block in pixels or of a sample text
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
 pub enum I3BarBlockMinWidth {
     Pixels(usize),
     Text(String),
@@ -3,6 +3,7 @@ use std::io;
 use std::option::Option;
 use std::string::*;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde::{de, Deserializer};
@@ -31,6 +32,10 @@ struct I3BarEventInternal {
 
     #[serde(deserialize_with = "deserialize_mousebutton")]
     pub button: MouseButton,
+
+    /// Only sent by newer versions of i3bar and swaybar
+    #[serde(default)]
+    pub modifiers: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +43,12 @@ pub struct I3BarEvent {
     pub id: Option<usize>,
     pub instance: Option<usize>,
     pub button: MouseButton,
+    /// Modifier keys held down while clicking, like "Shift", "Control" or "Mod1"
+    pub modifiers: Vec<String>,
+    /// Whether this is the second of two clicks with the same button on the same widget within
+    /// the double click interval. The bar only reports presses, so the first click has been
+    /// handled as a single click already, and how long a button is held isn't known.
+    pub double_click: bool,
 }
 
 impl I3BarEvent {
@@ -47,9 +58,49 @@ impl I3BarEvent {
             _ => false,
         }
     }
+
+    /// Whether `modifier` was held down, ignoring case
+    pub fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers
+            .iter()
+            .any(|held| held.eq_ignore_ascii_case(modifier))
+    }
+}
+
+/// Recognizes the second of two quick clicks with the same button on the same widget
+struct DoubleClicks {
+    interval: Duration,
+    /// The click that the next one would complete to a double click
+    last: Option<(Option<usize>, Option<usize>, MouseButton, Instant)>,
+}
+
+impl DoubleClicks {
+    fn is_double_click(
+        &mut self,
+        id: Option<usize>,
+        instance: Option<usize>,
+        button: MouseButton,
+        now: Instant,
+    ) -> bool {
+        let double_click = matches!(self.last, Some((last_id, last_instance, last_button, time))
+            if last_id == id
+                && last_instance == instance
+                && last_button == button
+                && now.duration_since(time) <= self.interval);
+        // A third click starts the next double click
+        self.last = match double_click {
+            true => None,
+            false => Some((id, instance, button, now)),
+        };
+        double_click
+    }
 }
 
-pub fn process_events(sender: Sender<I3BarEvent>) {
+pub fn process_events(sender: Sender<I3BarEvent>, double_click_interval: Duration) {
+    let mut double_clicks = DoubleClicks {
+        interval: double_click_interval,
+        last: None,
+    };
     thread::Builder::new()
         .name("input".into())
         .spawn(move || loop {
@@ -62,11 +113,17 @@ pub fn process_events(sender: Sender<I3BarEvent>) {
 
             if !slice.is_empty() {
                 let e: I3BarEventInternal = serde_json::from_str(slice).unwrap();
+                let id = e.name.map(|x| x.parse::<usize>().unwrap());
+                let instance = e.instance.map(|x| x.parse::<usize>().unwrap());
+                let double_click =
+                    double_clicks.is_double_click(id, instance, e.button, Instant::now());
                 sender
                     .send(I3BarEvent {
-                        id: e.name.map(|x| x.parse::<usize>().unwrap()),
-                        instance: e.instance.map(|x| x.parse::<usize>().unwrap()),
+                        id,
+                        instance,
                         button: e.button,
+                        modifiers: e.modifiers,
+                        double_click,
                     })
                     .unwrap();
             }
@@ -108,3 +165,32 @@ where
 
     deserializer.deserialize_any(MouseButtonVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_clicks() {
+        let mut double_clicks = DoubleClicks {
+            interval: Duration::from_millis(300),
+            last: None,
+        };
+        let start = Instant::now();
+        let mut click = |id, button, millis| {
+            double_clicks.is_double_click(
+                Some(id),
+                None,
+                button,
+                start + Duration::from_millis(millis),
+            )
+        };
+        assert!(!click(0, MouseButton::Left, 0));
+        assert!(click(0, MouseButton::Left, 200));
+        // The next click after a double click is a single one again
+        assert!(!click(0, MouseButton::Left, 300));
+        assert!(!click(0, MouseButton::Right, 400));
+        assert!(!click(1, MouseButton::Right, 500));
+        assert!(!click(1, MouseButton::Right, 900));
+    }
+}
@@ -1,5 +1,8 @@
+pub mod export;
+pub mod headless;
 pub mod i3bar_block;
 pub mod i3bar_event;
+pub mod plain;
 
 use crate::blocks::Block;
 use crate::config::SharedConfig;
@@ -16,7 +19,48 @@ pub fn init(never_pause: bool) {
     }
 }
 
-pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<()> {
+/// Where the blocks are shown
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    I3Bar,
+    /// One line of text per refresh, optionally in the colors of the theme
+    Plain {
+        separator: String,
+        ansi: bool,
+    },
+    /// Nowhere, the blocks are published periodically instead, see `headless`
+    Headless,
+}
+
+impl Output {
+    pub fn print_blocks(&self, blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<()> {
+        match self {
+            Output::I3Bar => print_blocks(blocks, config),
+            Output::Plain { separator, ansi } => plain::print_blocks(blocks, separator, *ansi),
+            Output::Headless => Ok(()),
+        }
+    }
+
+    /// Replaces the blocks with a placeholder when the bar is terminated
+    pub fn print_stopped(&self) {
+        if *self == Output::I3Bar {
+            let placeholder = I3BarBlock {
+                full_text: "i3status-rs stopped".to_string(),
+                ..Default::default()
+            };
+            println!("[{}],", placeholder.render());
+        }
+    }
+}
+
+fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<()> {
+    println!("{},", render_blocks(blocks, config));
+
+    Ok(())
+}
+
+/// The JSON array of the widgets and separators that i3bar is sent for the blocks
+pub fn render_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> String {
     let mut last_bg = Color::None;
 
     let mut rendered_blocks = vec![];
@@ -39,6 +83,8 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
             continue;
         }
 
+        let stale = block.stale();
+        let pressed = block.pressed();
         let mut rendered_widgets: Vec<I3BarBlock> = widgets
             .iter()
             .map(|widget| {
@@ -49,10 +95,28 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
                     data.background = data.background + config.theme.alternating_tint_bg;
                     data.color = data.color + config.theme.alternating_tint_fg;
                 }
+                if stale && config.monochrome.is_none() {
+                    data.color = data.color.dimmed();
+                }
+                if pressed {
+                    let (bg, fg) = config.theme.clicked_colors(data.background, data.color);
+                    data.background = bg;
+                    data.color = fg;
+                }
                 data
             })
             .collect();
 
+        if stale {
+            // Keep the last value, but mark it as out of date
+            let first = &mut rendered_widgets[0];
+            first.full_text = format!(
+                "{}{}",
+                config.get_icon("stale").unwrap_or_default(),
+                first.full_text
+            );
+        }
+
         alternator = !alternator;
 
         if config.theme.separator.is_none() {
@@ -104,7 +168,5 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
         last_bg = rendered_widgets.last().unwrap().background;
     }
 
-    println!("[{}],", rendered_blocks.join(","));
-
-    Ok(())
+    format!("[{}]", rendered_blocks.join(","))
 }
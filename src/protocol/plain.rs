@@ -0,0 +1,95 @@
+//! Prints the blocks as one line of text per refresh, e.g. for the status line of tmux
+
+use crate::blocks::Block;
+use crate::errors::*;
+use crate::themes::Color;
+
+use super::i3bar_block::I3BarBlock;
+
+pub fn print_blocks(blocks: &[Box<dyn Block>], separator: &str, ansi: bool) -> Result<()> {
+    let line: Vec<String> = blocks
+        .iter()
+        .map(|block| {
+            let view = block.view();
+            let widgets = view.iter().map(|widget| widget.get_data());
+            if ansi {
+                widgets.map(|data| colored(&data)).collect::<String>()
+            } else {
+                widgets
+                    .map(|data| text(&data).trim().to_string())
+                    .filter(|text| !text.is_empty())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            }
+        })
+        .filter(|block| !block.is_empty())
+        .collect();
+
+    println!("{}", line.join(separator));
+
+    Ok(())
+}
+
+/// The text of a widget without Pango markup
+fn text(data: &I3BarBlock) -> String {
+    if data.markup.as_deref() == Some("pango") {
+        strip_markup(&data.full_text)
+    } else {
+        data.full_text.clone()
+    }
+}
+
+/// The text of a widget in the colors of the theme
+fn colored(data: &I3BarBlock) -> String {
+    let text = text(data);
+    if text.is_empty() {
+        return text;
+    }
+    let mut colored = String::new();
+    if let Color::Rgba(r, g, b, _) = data.color {
+        colored.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b));
+    }
+    if let Color::Rgba(r, g, b, _) = data.background {
+        colored.push_str(&format!("\x1b[48;2;{};{};{}m", r, g, b));
+    }
+    if colored.is_empty() {
+        return text;
+    }
+    colored.push_str(&text);
+    colored.push_str("\x1b[0m");
+    colored
+}
+
+/// Removes the tags of Pango markup and resolves the entities of the escaped characters
+pub fn strip_markup(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut in_tag = false;
+    for c in markup.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markup() {
+        assert_eq!(
+            strip_markup("<span foreground='red'>Tom &amp; Jerry</span> &lt;3"),
+            "Tom & Jerry <3"
+        );
+        assert_eq!(strip_markup("50%"), "50%");
+    }
+}
@@ -104,6 +104,7 @@ impl UpdateScheduler {
         let now = Instant::now();
 
         for task in tasks_next {
+            log::trace!("updating block {}", task.id);
             if let Some(dur) = blocks
                 .get_mut(task.id as usize)
                 .internal_error("scheduler", "could not get required block")?
@@ -114,7 +115,10 @@ impl UpdateScheduler {
                         id: task.id,
                         update_time: now + d,
                     }),
-                    Update::Once => {} // do not schedule this task again
+                    Update::Once => {
+                        // do not schedule this task again
+                        log::debug!("block {} won't be updated again", task.id);
+                    }
                 }
             }
         }
@@ -4,22 +4,22 @@ use std::thread;
 
 /// Starts a thread that listens for provided signals and sends these on the provided channel
 pub fn process_signals(sender: Sender<i32>) {
+    let (sigmin, sigmax) = realtime_signals().unwrap_or((0, 0));
+    let mut signals = (sigmin..sigmax).collect::<Vec<_>>();
+    signals.push(signal_hook::consts::SIGUSR1);
+    signals.push(signal_hook::consts::SIGUSR2);
+    signals.push(signal_hook::consts::SIGTERM);
+    signals.push(signal_hook::consts::SIGINT);
+    // Registered before the thread runs, so that SIGTERM can't kill the bar before the blocks
+    // are stopped
+    let mut signals = signal_hook::iterator::Signals::new(&signals).unwrap();
     thread::Builder::new()
         .name("signals".into())
         .spawn(move || {
-            let sigmin;
-            let sigmax;
-            unsafe {
-                sigmin = __libc_current_sigrtmin();
-                sigmax = __libc_current_sigrtmax();
-            }
-            loop {
-                let mut signals = (sigmin..sigmax).collect::<Vec<_>>();
-                signals.push(signal_hook::consts::SIGUSR1);
-                signals.push(signal_hook::consts::SIGUSR2);
-                let mut signals = signal_hook::iterator::Signals::new(&signals).unwrap();
-                for sig in signals.forever() {
-                    sender.send(sig).unwrap();
+            for sig in signals.forever() {
+                // The bar stopped listening after an error, which handles the signals itself
+                if sender.send(sig).is_err() {
+                    break;
                 }
             }
         })
@@ -27,12 +27,12 @@ pub fn process_signals(sender: Sender<i32>) {
 }
 
 pub fn convert_to_valid_signal(signal: i32) -> Result<i32> {
-    let sigmin;
-    let sigmax;
-    unsafe {
-        sigmin = __libc_current_sigrtmin();
-        sigmax = __libc_current_sigrtmax();
-    }
+    let (sigmin, sigmax) = realtime_signals().ok_or_else(|| {
+        Error::ConfigurationError(
+            "Signals are not supported on this system".to_string(),
+            "It has no realtime signals".to_string(),
+        )
+    })?;
     if signal < 0 || signal > sigmax - sigmin {
         //NOTE If some important information is encoded in the third field of this error this might
         //need to be added
@@ -54,9 +54,28 @@ pub fn convert_to_valid_signal(signal: i32) -> Result<i32> {
     }
 }
 
+/// The range of realtime signals, SIGRTMIN to SIGRTMAX
+#[cfg(target_os = "linux")]
+fn realtime_signals() -> Option<(i32, i32)> {
+    unsafe { Some((__libc_current_sigrtmin(), __libc_current_sigrtmax())) }
+}
+
+/// The range of realtime signals, SIGRTMIN to SIGRTMAX
+#[cfg(target_os = "freebsd")]
+fn realtime_signals() -> Option<(i32, i32)> {
+    Some((65, 126))
+}
+
+/// OpenBSD, for one, has no realtime signals
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn realtime_signals() -> Option<(i32, i32)> {
+    None
+}
+
 //TODO when libc exposes this through their library and even better when the nix crate does we
 //should be using that binding rather than a C-binding.
-///C bindings to SIGMIN and SIGMAX values
+//C bindings to SIGMIN and SIGMAX values
+#[cfg(target_os = "linux")]
 extern "C" {
     fn __libc_current_sigrtmin() -> i32;
     fn __libc_current_sigrtmax() -> i32;
@@ -0,0 +1,132 @@
+//! State that blocks keep across restarts of the bar, like which groups are collapsed or how far a
+//! pomodoro got, so that reloading the config or logging in again doesn't lose it.
+//!
+//! Blocks get the store from `SharedConfig::state_store()`. Each block keeps its state as one JSON
+//! value under a key of its own, in `$XDG_STATE_HOME/i3status-rust/state.json`. The file is read
+//! again before each write, so that the bars of several outputs don't undo each other's changes.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::util::xdg_state_home;
+
+/// Keeps the state in a file, or only in memory if there is none, e.g. in tests
+#[derive(Debug, Default)]
+pub struct StateStore {
+    path: Option<PathBuf>,
+    memory: RefCell<Map<String, JsonValue>>,
+}
+
+impl StateStore {
+    pub fn new(path: PathBuf) -> Self {
+        StateStore {
+            path: Some(path),
+            memory: RefCell::new(Map::new()),
+        }
+    }
+
+    /// The store of the bar, in the state directory of the user
+    pub fn user() -> Self {
+        Self::new(xdg_state_home().join("i3status-rust/state.json"))
+    }
+
+    fn load(&self) -> Map<String, JsonValue> {
+        match &self.path {
+            Some(path) => fs::read_to_string(path)
+                .ok()
+                .and_then(|contents| serde_json::from_str(&contents).ok())
+                .unwrap_or_default(),
+            None => self.memory.borrow().clone(),
+        }
+    }
+
+    /// The state saved under `key`, `None` if there is none or it doesn't fit `T`, e.g. after an
+    /// update changed it
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        serde_json::from_value(self.load().remove(key)?).ok()
+    }
+
+    /// Saves `value` under `key`. Failing to save only costs the state at the next start, so it
+    /// is logged rather than shown.
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) {
+        let value = match serde_json::to_value(value) {
+            Ok(value) => value,
+            Err(error) => {
+                log::warn!("failed to save the state of {}: {}", key, error);
+                return;
+            }
+        };
+        let mut state = self.load();
+        state.insert(key.to_string(), value);
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                *self.memory.borrow_mut() = state;
+                return;
+            }
+        };
+        // Written aside and moved in place, so that a bar that stops meanwhile can't leave half
+        // a file
+        let temporary = path.with_extension(format!("json.{}", std::process::id()));
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temporary, JsonValue::Object(state).to_string()))
+            .and_then(|_| fs::rename(&temporary, path));
+        if let Err(error) = result {
+            log::warn!("failed to save the state to {}: {}", path.display(), error);
+        }
+    }
+}
+
+/// The wall clock time of `instant`, to save it
+pub fn system_time(instant: Instant) -> SystemTime {
+    let now = Instant::now();
+    if instant <= now {
+        SystemTime::now() - now.duration_since(instant)
+    } else {
+        SystemTime::now() + instant.duration_since(now)
+    }
+}
+
+/// The instant of the saved wall clock time `time`
+pub fn instant(time: SystemTime) -> Instant {
+    let now = Instant::now();
+    match time.duration_since(SystemTime::now()) {
+        Ok(ahead) => now + ahead,
+        Err(error) => now.checked_sub(error.duration()).unwrap_or(now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_state_store() {
+        let dir = std::env::temp_dir().join(format!("i3status-rs-state-{}", std::process::id()));
+        let path = dir.join("state.json");
+        let store = StateStore::new(path.clone());
+        assert_eq!(store.get::<bool>("group/media"), None);
+        store.set("group/media", &true);
+        store.set("timer/3", &vec![1, 2]);
+
+        // Another bar, or the next start
+        let other = StateStore::new(path);
+        assert_eq!(other.get::<bool>("group/media"), Some(true));
+        assert_eq!(other.get::<Vec<u8>>("timer/3"), Some(vec![1, 2]));
+        assert_eq!(other.get::<String>("timer/3"), None);
+        fs::remove_dir_all(dir).unwrap();
+
+        let start = Instant::now() - Duration::from_secs(60);
+        let elapsed = instant(system_time(start)).elapsed().as_secs_f64();
+        assert!((elapsed - 60.).abs() < 1.);
+    }
+}
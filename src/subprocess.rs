@@ -1,7 +1,11 @@
+use std::env;
+use std::fmt::Debug;
 use std::io;
 use std::process::{Command, Stdio};
 use std::thread;
 
+use crate::errors::*;
+
 /// Spawns a new child process. This closes stdin and stdout, and returns to the caller after the
 /// child has been started, while a background thread waits for the child to exit.
 pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
@@ -16,3 +20,81 @@ pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
         .unwrap();
     Ok(())
 }
+
+/// What a command printed and how it exited
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    /// `None` if the command was killed by a signal
+    pub status: Option<i32>,
+}
+
+/// Runs shell commands for blocks. Blocks get the runner from the `SharedConfig`, so that tests
+/// can replace the commands with canned output.
+pub trait CommandRunner: Debug {
+    /// Runs `command` with the user's shell and waits for it to exit
+    fn run(&self, command: &str) -> Result<CommandOutput>;
+}
+
+/// Runs commands with `$SHELL`, or `sh` if it isn't set
+#[derive(Debug, Default)]
+pub struct ShellRunner;
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &str) -> Result<CommandOutput> {
+        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
+            .arg("-c")
+            .arg(command)
+            .output()
+            .internal_error("command runner", &format!("failed to run {}", command))?;
+        log::debug!(
+            "{:?} exited with {} and printed {:?}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stdout)
+        );
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            status: output.status.code(),
+        })
+    }
+}
+
+/// Answers commands with the output given for them and remembers which commands were run
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockRunner {
+    outputs: std::cell::RefCell<std::collections::HashMap<String, CommandOutput>>,
+    pub ran: std::cell::RefCell<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    /// Makes `command` print `stdout` and exit successfully from now on
+    pub fn set(&self, command: &str, stdout: &str) {
+        self.outputs.borrow_mut().insert(
+            command.to_string(),
+            CommandOutput {
+                stdout: stdout.to_string(),
+                status: Some(0),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockRunner {
+    fn run(&self, command: &str) -> Result<CommandOutput> {
+        self.ran.borrow_mut().push(command.to_string());
+        // Unknown commands fail like in a shell
+        Ok(self
+            .outputs
+            .borrow()
+            .get(command)
+            .cloned()
+            .unwrap_or(CommandOutput {
+                stdout: String::new(),
+                status: Some(127),
+            }))
+    }
+}
@@ -1,14 +1,23 @@
+mod base16;
+pub mod dark_mode;
+mod xresources;
+
 use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
 use std::ops::Add;
 use std::str::FromStr;
+use std::time::Duration;
 
+use chrono::NaiveTime;
 use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
 use serde_derive::Deserialize;
 
+use crate::de::deserialize_duration;
 use crate::errors::ToSerdeError;
 use crate::util;
+use crate::widgets::State;
+use dark_mode::DarkMode;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
@@ -42,6 +51,13 @@ impl FromStr for Color {
             Ok(Color::None)
         } else if s == "auto" {
             Ok(Color::Auto)
+        } else if let Some(name) = s.strip_prefix("x:") {
+            use crate::errors::OptionExt;
+            let value = xresources::get(name).internal_error(
+                "color parser",
+                &format!("color '{}' not found in the X resources", name),
+            )?;
+            xresources::normalize_color(value).parse()
         } else {
             use crate::errors::{OptionExt, ResultExtInternal};
             let err_msg = "invaild RGBA color";
@@ -92,6 +108,57 @@ impl Color {
             _ => None,
         }
     }
+
+    /// The color a share of `amount` (between 0 and 1) of the way from `self` to `other`
+    pub fn mix(self, other: Color, amount: f64) -> Color {
+        let mix =
+            |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * amount).round() as u8;
+        match (self, other) {
+            (Color::Rgba(r1, g1, b1, a1), Color::Rgba(r2, g2, b2, a2)) => {
+                Color::Rgba(mix(r1, r2), mix(g1, g2), mix(b1, b2), mix(a1, a2))
+            }
+            _ if amount < 0.5 => self,
+            _ => other,
+        }
+    }
+
+    /// The color at half its opacity, to tone down widgets that are out of date
+    pub fn dimmed(self) -> Color {
+        match self {
+            Color::Rgba(r, g, b, a) => Color::Rgba(r, g, b, a / 2),
+            _ => self,
+        }
+    }
+}
+
+/// Colors a widget by a value, going from the good colors of the theme at `min` through the
+/// warning colors to the critical colors at `max`. `min` may be larger than `max` for values where
+/// higher is better.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Gradient {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Gradient {
+    /// The background and foreground colors for `value`
+    pub fn colors(self, theme: &Theme, value: f64) -> (Color, Color) {
+        let stops = [
+            (theme.good_bg, theme.good_fg),
+            (theme.warning_bg, theme.warning_fg),
+            (theme.critical_bg, theme.critical_fg),
+        ];
+        let position = if self.max == self.min {
+            0.
+        } else {
+            ((value - self.min) / (self.max - self.min)).clamp(0., 1.)
+        } * (stops.len() - 1) as f64;
+        let stop = (position as usize).min(stops.len() - 2);
+        let amount = position - stop as f64;
+        let ((from_bg, from_fg), (to_bg, to_fg)) = (stops[stop], stops[stop + 1]);
+        (from_bg.mix(to_bg, amount), from_fg.mix(to_fg, amount))
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -112,6 +179,8 @@ pub struct InternalTheme {
     pub separator_fg: Color,
     pub alternating_tint_bg: Color,
     pub alternating_tint_fg: Color,
+    pub clicked_bg: Color,
+    pub clicked_fg: Color,
 }
 
 impl Default for InternalTheme {
@@ -132,39 +201,14 @@ impl Default for InternalTheme {
             separator_fg: Color::None,
             alternating_tint_bg: Color::None,
             alternating_tint_fg: Color::None,
+            clicked_bg: Color::None,
+            clicked_fg: Color::None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Theme(pub InternalTheme);
-
-impl Default for Theme {
-    fn default() -> Self {
-        Self::from_file("plain").unwrap_or_else(|| Self(InternalTheme::default()))
-    }
-}
-
-impl std::ops::Deref for Theme {
-    type Target = InternalTheme;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for Theme {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl Theme {
-    pub fn from_file(file: &str) -> Option<Theme> {
-        let file = util::find_file(file, Some("themes"), Some("toml"))?;
-        Some(Theme(util::deserialize_file(&file).ok()?))
-    }
-
-    pub fn apply_overrides(
+impl InternalTheme {
+    fn apply_overrides(
         &mut self,
         overrides: &HashMap<String, String>,
     ) -> Result<(), crate::errors::Error> {
@@ -192,8 +236,181 @@ impl Theme {
         apply!(separator_fg);
         apply!(alternating_tint_bg);
         apply!(alternating_tint_fg);
+        apply!(clicked_bg);
+        apply!(clicked_fg);
         Ok(())
     }
+
+    /// The background and foreground colors of a widget that was just clicked. Unless the theme
+    /// sets them, the colors of the widget are swapped, and `auto` keeps them as they are.
+    pub fn clicked_colors(&self, bg: Color, fg: Color) -> (Color, Color) {
+        let clicked = |theme: Color, current: Color, other: Color| match theme {
+            Color::None => other,
+            Color::Auto => current,
+            color => color,
+        };
+        (
+            clicked(self.clicked_bg, bg, fg),
+            clicked(self.clicked_fg, fg, bg),
+        )
+    }
+}
+
+/// Settings of the monochrome mode, for e-ink displays and slow remote connections. It replaces the
+/// theme with two colors, shows warnings and critical states inverted and marked by text, and
+/// updates blocks less often.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct MonochromeConfig {
+    pub foreground: Color,
+    pub background: Color,
+
+    /// Shortest interval that blocks are updated at
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min_interval: Duration,
+
+    /// Texts in front of widgets in these states
+    pub info_marker: String,
+    pub good_marker: String,
+    pub warning_marker: String,
+    pub critical_marker: String,
+}
+
+impl Default for MonochromeConfig {
+    fn default() -> Self {
+        Self {
+            foreground: Color::Rgba(0, 0, 0, 255),
+            background: Color::Rgba(255, 255, 255, 255),
+            min_interval: Duration::from_secs(30),
+            info_marker: String::new(),
+            good_marker: String::new(),
+            warning_marker: "!".to_string(),
+            critical_marker: "!!".to_string(),
+        }
+    }
+}
+
+impl MonochromeConfig {
+    pub fn theme(&self) -> Theme {
+        let (fg, bg) = (self.foreground, self.background);
+        InternalTheme {
+            idle_bg: bg,
+            idle_fg: fg,
+            info_bg: bg,
+            info_fg: fg,
+            good_bg: bg,
+            good_fg: fg,
+            warning_bg: fg,
+            warning_fg: bg,
+            critical_bg: fg,
+            critical_fg: bg,
+            // The separators of the bar itself
+            separator: None,
+            ..InternalTheme::default()
+        }
+        .into()
+    }
+
+    pub fn marker(&self, state: State) -> &str {
+        match state {
+            State::Idle => "",
+            State::Info => &self.info_marker,
+            State::Good => &self.good_marker,
+            State::Warning => &self.warning_marker,
+            State::Critical => &self.critical_marker,
+        }
+    }
+}
+
+/// A theme, optionally with a variant that is used instead while the desktop is in dark mode
+#[derive(Debug, Clone)]
+pub struct Theme {
+    light: InternalTheme,
+    dark: Option<(InternalTheme, DarkMode)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_file("plain").unwrap_or_else(|| InternalTheme::default().into())
+    }
+}
+
+impl From<InternalTheme> for Theme {
+    fn from(theme: InternalTheme) -> Self {
+        Self {
+            light: theme,
+            dark: None,
+        }
+    }
+}
+
+impl std::ops::Deref for Theme {
+    type Target = InternalTheme;
+    fn deref(&self) -> &Self::Target {
+        match &self.dark {
+            Some((dark, _)) if dark_mode::is_dark() => dark,
+            _ => &self.light,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads a theme file. Besides regular theme files, Base16 schemes are accepted, either in
+    /// their native YAML format or converted to TOML.
+    pub fn from_file(file: &str) -> Option<Theme> {
+        let is_yaml = file.ends_with(".yaml") || file.ends_with(".yml");
+        let extension = if is_yaml {
+            std::path::Path::new(file).extension()?.to_str()?
+        } else {
+            "toml"
+        };
+        let file = util::find_file(file, Some("themes"), Some(extension))?;
+
+        if is_yaml {
+            let scheme = base16::parse_yaml(&std::fs::read_to_string(file).ok()?);
+            return Self::from_base16(&scheme);
+        }
+
+        let values: toml::value::Table = util::deserialize_file(&file).ok()?;
+        let strings: HashMap<String, String> = values
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect();
+        if base16::is_scheme(&strings) {
+            Self::from_base16(&strings)
+        } else {
+            Some(Self::from(
+                toml::Value::Table(values)
+                    .try_into::<InternalTheme>()
+                    .ok()?,
+            ))
+        }
+    }
+
+    fn from_base16(scheme: &HashMap<String, String>) -> Option<Theme> {
+        let mut theme = InternalTheme::default();
+        theme
+            .apply_overrides(&base16::to_overrides(scheme).ok()?)
+            .ok()?;
+        Some(theme.into())
+    }
+
+    /// Overrides colors of the theme, in both variants
+    pub fn apply_overrides(
+        &mut self,
+        overrides: &HashMap<String, String>,
+    ) -> Result<(), crate::errors::Error> {
+        self.light.apply_overrides(overrides)?;
+        if let Some((dark, _)) = &mut self.dark {
+            dark.apply_overrides(overrides)?;
+        }
+        Ok(())
+    }
+
+    /// How to switch to the dark variant, if the theme has one
+    pub fn dark_mode(&self) -> Option<DarkMode> {
+        self.dark.as_ref().map(|(_, mode)| *mode)
+    }
 }
 
 impl<'de> Deserialize<'de> for Theme {
@@ -202,11 +419,14 @@ impl<'de> Deserialize<'de> for Theme {
         D: Deserializer<'de>,
     {
         #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
+        #[serde(field_identifier, rename_all = "snake_case")]
         enum Field {
             Name,
             File,
             Overrides,
+            Dark,
+            LightFrom,
+            DarkFrom,
         }
 
         struct ThemeVisitor;
@@ -243,6 +463,9 @@ impl<'de> Deserialize<'de> for Theme {
             {
                 let mut theme: Option<String> = None;
                 let mut overrides: Option<HashMap<String, String>> = None;
+                let mut dark: Option<String> = None;
+                let mut light_from: Option<String> = None;
+                let mut dark_from: Option<String> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         // TODO merge name and file into one option (let's say "theme")
@@ -264,6 +487,24 @@ impl<'de> Deserialize<'de> for Theme {
                             }
                             overrides = Some(map.next_value()?);
                         }
+                        Field::Dark => {
+                            if dark.is_some() {
+                                return Err(de::Error::duplicate_field("dark"));
+                            }
+                            dark = Some(map.next_value()?);
+                        }
+                        Field::LightFrom => {
+                            if light_from.is_some() {
+                                return Err(de::Error::duplicate_field("light_from"));
+                            }
+                            light_from = Some(map.next_value()?);
+                        }
+                        Field::DarkFrom => {
+                            if dark_from.is_some() {
+                                return Err(de::Error::duplicate_field("dark_from"));
+                            }
+                            dark_from = Some(map.next_value()?);
+                        }
                     }
                 }
 
@@ -271,6 +512,33 @@ impl<'de> Deserialize<'de> for Theme {
                 let mut theme = Theme::from_file(&theme)
                     .ok_or_else(|| de::Error::custom(format!("Theme '{}' not found.", theme)))?;
 
+                let parse_time = |time: &str| {
+                    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+                        de::Error::custom(format!("'{}' is not a time like \"19:30\"", time))
+                    })
+                };
+                let mode = match (light_from, dark_from) {
+                    (None, None) => DarkMode::ColorScheme,
+                    (Some(light_from), Some(dark_from)) => DarkMode::Schedule {
+                        light_from: parse_time(&light_from)?,
+                        dark_from: parse_time(&dark_from)?,
+                    },
+                    _ => {
+                        return Err(de::Error::custom(
+                            "light_from and dark_from have to be set together",
+                        ))
+                    }
+                };
+                if let Some(dark) = dark {
+                    let dark_theme = Theme::from_file(&dark)
+                        .ok_or_else(|| de::Error::custom(format!("Theme '{}' not found.", dark)))?;
+                    theme.dark = Some((dark_theme.light, mode));
+                } else if mode != DarkMode::ColorScheme {
+                    return Err(de::Error::custom(
+                        "light_from and dark_from require a dark theme",
+                    ));
+                }
+
                 if let Some(ref overrides) = overrides {
                     theme.apply_overrides(overrides).serde_error()?;
                 }
@@ -281,3 +549,43 @@ impl<'de> Deserialize<'de> for Theme {
         deserializer.deserialize_any(ThemeVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient() {
+        let theme = Theme::from(InternalTheme {
+            good_bg: Color::Rgba(0, 200, 0, 255),
+            warning_bg: Color::Rgba(200, 200, 0, 255),
+            critical_bg: Color::Rgba(200, 0, 0, 255),
+            ..InternalTheme::default()
+        });
+
+        let gradient = Gradient { min: 0., max: 100. };
+        assert_eq!(gradient.colors(&theme, -10.).0, theme.good_bg);
+        assert_eq!(
+            gradient.colors(&theme, 25.).0,
+            Color::Rgba(100, 200, 0, 255)
+        );
+        assert_eq!(gradient.colors(&theme, 50.).0, theme.warning_bg);
+        assert_eq!(gradient.colors(&theme, 100.).0, theme.critical_bg);
+        assert_eq!(gradient.colors(&theme, 75.).1, Color::None);
+
+        let reversed = Gradient { min: 100., max: 0. };
+        assert_eq!(reversed.colors(&theme, 100.).0, theme.good_bg);
+        assert_eq!(reversed.colors(&theme, 0.).0, theme.critical_bg);
+    }
+
+    #[test]
+    fn test_clicked_colors() {
+        let (bg, fg) = (Color::Rgba(0, 0, 0, 255), Color::Rgba(255, 255, 255, 255));
+        let mut theme = InternalTheme::default();
+        assert_eq!(theme.clicked_colors(bg, fg), (fg, bg));
+
+        theme.clicked_bg = Color::Rgba(0, 0, 200, 255);
+        theme.clicked_fg = Color::Auto;
+        assert_eq!(theme.clicked_colors(bg, fg), (theme.clicked_bg, fg));
+    }
+}
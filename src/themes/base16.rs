@@ -0,0 +1,129 @@
+//! Support for [Base16](https://github.com/chriskempson/base16) color schemes.
+//!
+//! A Base16 scheme defines the 16 slots `base00` to `base0F`, which are mapped onto the theme's
+//! colors following the semantics of the Base16 styling guidelines (e.g. `base08` is red and
+//! thus used for the critical state). Theme files can override single entries of this mapping by
+//! setting a theme key to either a color or the name of a slot.
+
+use std::collections::HashMap;
+
+use crate::errors::*;
+
+/// The default mapping from theme keys to Base16 slots
+const DEFAULT_SLOTS: &[(&str, &str)] = &[
+    ("idle_bg", "base00"),
+    ("idle_fg", "base05"),
+    ("info_bg", "base0d"),
+    ("info_fg", "base00"),
+    ("good_bg", "base0b"),
+    ("good_fg", "base00"),
+    ("warning_bg", "base0a"),
+    ("warning_fg", "base00"),
+    ("critical_bg", "base08"),
+    ("critical_fg", "base00"),
+    ("separator_bg", "auto"),
+    ("separator_fg", "auto"),
+];
+
+const DEFAULT_SEPARATOR: &str = "\u{e0b2}";
+
+/// Whether the given theme file contents look like a Base16 scheme
+pub fn is_scheme(values: &HashMap<String, String>) -> bool {
+    values.keys().any(|key| key.eq_ignore_ascii_case("base00"))
+}
+
+/// Parses a Base16 scheme in its native YAML format, which only consists of `key: "value"` lines.
+pub fn parse_yaml(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            // Strip trailing comments and quotes
+            let value = value.split(" #").next().unwrap_or_default();
+            (
+                key.trim().to_string(),
+                value.trim().trim_matches(&['"', '\''][..]).to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Turns a Base16 scheme into theme overrides: every theme key is set according to the default
+/// mapping, unless the scheme itself sets the key (to a color or another slot).
+pub fn to_overrides(values: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+    let slots: HashMap<String, &str> = values
+        .iter()
+        .filter(|(key, _)| key.to_lowercase().starts_with("base"))
+        .map(|(key, value)| (key.to_lowercase(), value.trim_start_matches('#')))
+        .collect();
+
+    let resolve = |value: &str| -> Result<String> {
+        if value.to_lowercase().starts_with("base") {
+            let color = slots.get(&value.to_lowercase()).ok_or_else(|| {
+                ConfigurationError(
+                    "base16".to_string(),
+                    format!("slot '{}' is not defined by the scheme", value),
+                )
+            })?;
+            Ok(format!("#{}", color))
+        } else {
+            Ok(value.to_string())
+        }
+    };
+
+    let mut overrides = HashMap::new();
+    overrides.insert("separator".to_string(), DEFAULT_SEPARATOR.to_string());
+    for (key, slot) in DEFAULT_SLOTS {
+        overrides.insert(key.to_string(), resolve(slot)?);
+    }
+    for (key, value) in values {
+        if !key.to_lowercase().starts_with("base") && !is_metadata(key) {
+            overrides.insert(
+                key.clone(),
+                if key == "separator" {
+                    value.clone()
+                } else {
+                    resolve(value)?
+                },
+            );
+        }
+    }
+    Ok(overrides)
+}
+
+/// Keys of Base16 scheme files that carry no colors
+fn is_metadata(key: &str) -> bool {
+    matches!(
+        key,
+        "scheme" | "author" | "name" | "slug" | "system" | "variant"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheme_to_overrides() {
+        let mut scheme = parse_yaml(
+            "scheme: \"Test\" # a comment\nauthor: \"me\"\nbase00: \"282828\"\nbase05: \"d5c4a1\"\nbase08: \"fb4934\"\nbase0A: \"fabd2f\"\nbase0B: \"b8bb26\"\nbase0D: \"83a598\"\n",
+        );
+        assert!(is_scheme(&scheme));
+        assert_eq!(scheme.get("base0A").unwrap(), "fabd2f");
+
+        scheme.insert("warning_bg".to_string(), "base08".to_string());
+        scheme.insert("good_fg".to_string(), "#ffffff".to_string());
+        let overrides = to_overrides(&scheme).unwrap();
+        assert_eq!(overrides.get("idle_bg").unwrap(), "#282828");
+        assert_eq!(overrides.get("critical_bg").unwrap(), "#fb4934");
+        assert_eq!(overrides.get("warning_bg").unwrap(), "#fb4934");
+        assert_eq!(overrides.get("good_fg").unwrap(), "#ffffff");
+        assert_eq!(overrides.get("separator_fg").unwrap(), "auto");
+        assert!(!overrides.contains_key("scheme"));
+
+        scheme.insert("info_fg".to_string(), "base0F".to_string());
+        assert!(to_overrides(&scheme).is_err());
+    }
+}
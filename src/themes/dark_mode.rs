@@ -0,0 +1,154 @@
+//! Switching themes between their light and dark variants
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveTime};
+use crossbeam_channel::Sender;
+#[cfg(feature = "dbus")]
+use dbus::arg::{RefArg, Variant};
+#[cfg(feature = "dbus")]
+use dbus::blocking::Connection;
+#[cfg(feature = "dbus")]
+use dbus::message::MatchRule;
+
+use crate::errors::*;
+
+/// Namespace, key and value of the `SettingChanged` signal of the settings portal
+#[cfg(feature = "dbus")]
+type SettingChanged = (String, String, Variant<Box<dyn RefArg>>);
+
+/// Whether the dark variants of themes are in use
+static DARK: AtomicBool = AtomicBool::new(false);
+
+/// How the bar tells light from dark mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DarkMode {
+    /// Follow the `color-scheme` setting of the desktop (freedesktop settings portal)
+    ColorScheme,
+    /// Switch at fixed times of the day
+    Schedule {
+        light_from: NaiveTime,
+        dark_from: NaiveTime,
+    },
+}
+
+pub fn is_dark() -> bool {
+    DARK.load(Ordering::Relaxed)
+}
+
+/// Switches the theme variant, notifying `tx` if it changed
+fn set_dark(dark: bool, tx: &Sender<()>) {
+    if DARK.swap(dark, Ordering::Relaxed) != dark {
+        let _ = tx.send(());
+    }
+}
+
+fn is_dark_at(light_from: NaiveTime, dark_from: NaiveTime, time: NaiveTime) -> bool {
+    if light_from <= dark_from {
+        !(light_from <= time && time < dark_from)
+    } else {
+        dark_from <= time && time < light_from
+    }
+}
+
+/// Unwraps the (possibly nested) variants the settings portal returns down to the number
+#[cfg(feature = "dbus")]
+fn variant_u64(arg: &dyn RefArg) -> Option<u64> {
+    arg.as_u64().or_else(|| variant_u64(arg.as_iter()?.next()?))
+}
+
+/// `color-scheme` is 1 if the user prefers dark mode, 0 without preference and 2 for light mode
+#[cfg(feature = "dbus")]
+fn read_color_scheme(connection: &Connection) -> Option<bool> {
+    let proxy = connection.with_proxy(
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        Duration::from_secs(1),
+    );
+    let (value,): (Variant<Box<dyn RefArg>>,) = proxy
+        .method_call(
+            "org.freedesktop.portal.Settings",
+            "Read",
+            ("org.freedesktop.appearance", "color-scheme"),
+        )
+        .ok()?;
+    Some(variant_u64(&value.0)? == 1)
+}
+
+/// Sets the initial theme variant and keeps it up to date in a separate thread. `tx` is notified
+/// whenever the variant changes, so that the bar can be redrawn.
+pub fn watch(mode: DarkMode, tx: Sender<()>) -> Result<()> {
+    match mode {
+        DarkMode::Schedule {
+            light_from,
+            dark_from,
+        } => {
+            set_dark(is_dark_at(light_from, dark_from, Local::now().time()), &tx);
+            thread::Builder::new()
+                .name("dark_mode".into())
+                .spawn(move || loop {
+                    thread::sleep(Duration::from_secs(30));
+                    set_dark(is_dark_at(light_from, dark_from, Local::now().time()), &tx);
+                })
+                .internal_error("dark mode", "failed to start watching thread")?;
+        }
+        #[cfg(feature = "dbus")]
+        DarkMode::ColorScheme => {
+            let connection = Connection::new_session()
+                .internal_error("dark mode", "failed to connect to the session bus")?;
+            set_dark(read_color_scheme(&connection).unwrap_or(false), &tx);
+            thread::Builder::new()
+                .name("dark_mode".into())
+                .spawn(move || {
+                    let rule =
+                        MatchRule::new_signal("org.freedesktop.portal.Settings", "SettingChanged");
+                    let added = connection.add_match(
+                        rule,
+                        move |(namespace, key, value): SettingChanged,
+                              _: &Connection,
+                              _: &dbus::Message| {
+                            if namespace == "org.freedesktop.appearance" && key == "color-scheme" {
+                                if let Some(scheme) = variant_u64(&value.0) {
+                                    set_dark(scheme == 1, &tx);
+                                }
+                            }
+                            true
+                        },
+                    );
+                    if added.is_ok() {
+                        while connection.process(Duration::from_secs(60)).is_ok() {}
+                    }
+                })
+                .internal_error("dark mode", "failed to start watching thread")?;
+        }
+        #[cfg(not(feature = "dbus"))]
+        DarkMode::ColorScheme => {
+            return Err(ConfigurationError(
+                "following the color scheme of the desktop requires the dbus feature".to_string(),
+                "set light_from and dark_from instead".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_dark_at() {
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let (light_from, dark_from) = (time("07:00"), time("19:30"));
+        assert!(is_dark_at(light_from, dark_from, time("03:00")));
+        assert!(!is_dark_at(light_from, dark_from, time("07:00")));
+        assert!(!is_dark_at(light_from, dark_from, time("12:00")));
+        assert!(is_dark_at(light_from, dark_from, time("19:30")));
+
+        // Night owls may have light mode across midnight
+        assert!(!is_dark_at(time("20:00"), time("04:00"), time("01:00")));
+        assert!(is_dark_at(time("20:00"), time("04:00"), time("12:00")));
+    }
+}
@@ -0,0 +1,91 @@
+//! Colors imported from the X resource database, so that the bar can follow colorschemes that
+//! are managed through `~/.Xresources`.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref XRESOURCES: HashMap<String, String> = load();
+}
+
+/// Looks up the value of the resource `name` (e.g. `color4` or `URxvt.background`).
+///
+/// An exact match is preferred; otherwise any resource whose last component is `name` is used,
+/// so that both `*.color4` and `*color4` style definitions are found.
+pub fn get(name: &str) -> Option<&'static str> {
+    XRESOURCES.get(name).map(String::as_str).or_else(|| {
+        XRESOURCES
+            .iter()
+            .find(|(key, _)| {
+                matches!(key.strip_suffix(name), Some(rest) if rest.ends_with('.') || rest.ends_with('*'))
+            })
+            .map(|(_, value)| value.as_str())
+    })
+}
+
+/// Converts X color specifications of the form `rgb:rr/gg/bb` into `#rrggbb`. Other values are
+/// returned as is.
+pub fn normalize_color(value: &str) -> String {
+    match value.strip_prefix("rgb:") {
+        Some(components) => {
+            let components: Vec<&str> = components.split('/').collect();
+            if components.len() == 3 && components.iter().all(|c| c.len() == 2) {
+                format!("#{}", components.concat())
+            } else {
+                value.to_string()
+            }
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Reads the resources from the running X server and falls back to parsing `~/.Xresources` (without
+/// running it through the C preprocessor) if `xrdb` is not available.
+fn load() -> HashMap<String, String> {
+    if let Ok(output) = Command::new("xrdb").arg("-query").output() {
+        if output.status.success() {
+            return parse(&String::from_utf8_lossy(&output.stdout));
+        }
+    }
+
+    std::env::var("HOME")
+        .ok()
+        .and_then(|home| std::fs::read_to_string(format!("{}/.Xresources", home)).ok())
+        .map(|contents| parse(&contents))
+        .unwrap_or_default()
+}
+
+fn parse(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('!') && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            (
+                key.trim().trim_start_matches(&['*', '.'][..]).to_string(),
+                value.trim().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let resources = parse(
+            "! comment\n#define FOO bar\n*.background:\t#282828\n*color4: rgb:45/85/88\nURxvt.font: xft:Mono:size=10\n",
+        );
+        assert_eq!(resources.get("background").unwrap(), "#282828");
+        assert_eq!(resources.get("color4").unwrap(), "rgb:45/85/88");
+        assert_eq!(resources.get("URxvt.font").unwrap(), "xft:Mono:size=10");
+        assert_eq!(resources.len(), 3);
+        assert_eq!(normalize_color("rgb:45/85/88"), "#458588");
+        assert_eq!(normalize_color("#458588"), "#458588");
+    }
+}
@@ -0,0 +1,297 @@
+//! Time tracking that blocks can start and stop, so that e.g. pomodoros end up in the same place
+//! as the rest of the tracked time.
+//!
+//! The tracking services are talked to in a thread of their own, in the order in which intervals
+//! are started and stopped, so that slow commands or requests don't hold up the bar. Failures are
+//! logged, since there is nobody to show them to by then.
+
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, unbounded, Sender};
+use serde_derive::Deserialize;
+
+use crate::errors::*;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "name", rename_all = "lowercase", deny_unknown_fields)]
+pub enum TrackingConfig {
+    /// Intervals are tracked with `timew start` and `timew stop`
+    Timewarrior {
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// Intervals are time entries of Toggl Track
+    #[cfg(feature = "http")]
+    Toggl {
+        #[serde(default = "TrackingConfig::getenv_toggl_api_token")]
+        api_token: Option<String>,
+        /// By default the default workspace of the user
+        workspace_id: Option<u64>,
+        #[serde(default)]
+        description: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+impl TrackingConfig {
+    #[cfg(feature = "http")]
+    fn getenv_toggl_api_token() -> Option<String> {
+        std::env::var("TOGGL_API_TOKEN").ok()
+    }
+}
+
+enum Request {
+    Start {
+        description: String,
+        tags: Vec<String>,
+    },
+    Stop,
+    /// Answers once the requests before it are done
+    Done(Sender<()>),
+}
+
+/// Replaces placeholders like `{count}` in `text`
+fn expand(text: &str, placeholders: &[(&str, String)]) -> String {
+    placeholders
+        .iter()
+        .fold(text.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// Starts and stops intervals with a tracking service
+pub struct Tracker {
+    description: String,
+    tags: Vec<String>,
+    requests: Sender<Request>,
+}
+
+impl Tracker {
+    pub fn new(config: TrackingConfig) -> Result<Self> {
+        let (requests, received) = unbounded();
+        let (description, tags) = match config.clone() {
+            TrackingConfig::Timewarrior { tags } => (String::new(), tags),
+            #[cfg(feature = "http")]
+            TrackingConfig::Toggl {
+                description, tags, ..
+            } => (description, tags),
+        };
+        let mut service: Box<dyn TrackingService + Send> = match config {
+            TrackingConfig::Timewarrior { .. } => Box::new(Timewarrior),
+            #[cfg(feature = "http")]
+            TrackingConfig::Toggl {
+                api_token,
+                workspace_id,
+                ..
+            } => Box::new(toggl::Toggl::new(
+                api_token.ok_or_else(|| {
+                    ConfigurationError(
+                        "tracking".to_string(),
+                        "toggl needs an api_token or TOGGL_API_TOKEN".to_string(),
+                    )
+                })?,
+                workspace_id,
+            )),
+        };
+        thread::Builder::new()
+            .name("tracking".into())
+            .spawn(move || {
+                // Ends when the tracker is dropped
+                for request in received {
+                    let result = match request {
+                        Request::Start { description, tags } => service.start(&description, &tags),
+                        Request::Stop => service.stop(),
+                        Request::Done(done) => done.send(()).map_err(Error::from),
+                    };
+                    if let Err(error) = result {
+                        log::warn!("time tracking failed: {}", error);
+                    }
+                }
+            })
+            .internal_error("tracking", "failed to start a thread")?;
+        Ok(Tracker {
+            description,
+            tags,
+            requests,
+        })
+    }
+
+    /// Starts an interval, with placeholders like `{count}` in the configured tags and
+    /// description replaced by their values
+    pub fn start(&self, placeholders: &[(&str, String)]) {
+        let _ = self.requests.send(Request::Start {
+            description: expand(&self.description, placeholders),
+            tags: self
+                .tags
+                .iter()
+                .map(|tag| expand(tag, placeholders))
+                .collect(),
+        });
+    }
+
+    pub fn stop(&self) {
+        let _ = self.requests.send(Request::Stop);
+    }
+
+    /// Waits up to `timeout` for the intervals to be started and stopped, e.g. before the bar exits
+    pub fn wait(&self, timeout: Duration) {
+        let (done, finished) = bounded(1);
+        if self.requests.send(Request::Done(done)).is_ok() {
+            let _ = finished.recv_timeout(timeout);
+        }
+    }
+}
+
+trait TrackingService {
+    fn start(&mut self, description: &str, tags: &[String]) -> Result<()>;
+    fn stop(&mut self) -> Result<()>;
+}
+
+struct Timewarrior;
+
+impl Timewarrior {
+    fn run(args: &[&str]) -> Result<()> {
+        let output = Command::new("timew")
+            .args(args)
+            .output()
+            .block_error("tracking", "failed to run timew")?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(BlockError(
+                "tracking".to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+}
+
+impl TrackingService for Timewarrior {
+    fn start(&mut self, _description: &str, tags: &[String]) -> Result<()> {
+        let mut args = vec!["start"];
+        args.extend(tags.iter().map(String::as_str));
+        Timewarrior::run(&args)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Timewarrior::run(&["stop"])
+    }
+}
+
+#[cfg(feature = "http")]
+mod toggl {
+    use std::time::Duration;
+
+    use chrono::{SecondsFormat, Utc};
+    use serde_json::json;
+
+    use super::TrackingService;
+    use crate::errors::*;
+    use crate::http::http_send_json;
+
+    const API: &str = "https://api.track.toggl.com/api/v9";
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub struct Toggl {
+        api_token: String,
+        workspace_id: Option<u64>,
+        /// The workspace and ID of the running time entry
+        running: Option<(u64, u64)>,
+    }
+
+    impl Toggl {
+        pub fn new(api_token: String, workspace_id: Option<u64>) -> Self {
+            Toggl {
+                api_token,
+                workspace_id,
+                running: None,
+            }
+        }
+
+        fn request(
+            &self,
+            method: &str,
+            path: &str,
+            body: Option<&serde_json::Value>,
+        ) -> Result<serde_json::Value> {
+            // Toggl takes the API token as the user name
+            let response = http_send_json(
+                method,
+                &format!("{}{}", API, path),
+                Some(TIMEOUT),
+                &self.api_token,
+                "api_token",
+                body,
+            )?;
+            match response.code {
+                200..=299 => Ok(response.content),
+                code => Err(BlockError(
+                    "tracking".to_string(),
+                    format!("Toggl answered {} to {} {}", code, method, path),
+                )),
+            }
+        }
+
+        fn workspace_id(&mut self) -> Result<u64> {
+            if let Some(id) = self.workspace_id {
+                return Ok(id);
+            }
+            let id = self.request("GET", "/me", None)?["default_workspace_id"]
+                .as_u64()
+                .block_error("tracking", "Toggl didn't tell the default workspace")?;
+            self.workspace_id = Some(id);
+            Ok(id)
+        }
+    }
+
+    impl TrackingService for Toggl {
+        fn start(&mut self, description: &str, tags: &[String]) -> Result<()> {
+            self.stop()?;
+            let workspace_id = self.workspace_id()?;
+            let entry = self.request(
+                "POST",
+                &format!("/workspaces/{}/time_entries", workspace_id),
+                Some(&json!({
+                    "created_with": "i3status-rust",
+                    "description": description,
+                    "tags": tags,
+                    "workspace_id": workspace_id,
+                    "start": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    // A negative duration marks the entry as running
+                    "duration": -1,
+                })),
+            )?;
+            let id = entry["id"]
+                .as_u64()
+                .block_error("tracking", "Toggl didn't return the time entry")?;
+            self.running = Some((workspace_id, id));
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            if let Some((workspace_id, id)) = self.running.take() {
+                self.request(
+                    "PATCH",
+                    &format!("/workspaces/{}/time_entries/{}/stop", workspace_id, id),
+                    None,
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let placeholders = [("count", "3".to_string())];
+        assert_eq!(expand("pomodoro-{count}", &placeholders), "pomodoro-3");
+        assert_eq!(expand("writing", &placeholders), "writing");
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::io::BufReader;
@@ -9,6 +10,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use serde::de::DeserializeOwned;
 
 use crate::errors::*;
+use crate::widgets::State;
 
 pub const USR_SHARE_PATH: &str = "/usr/share/i3status-rust";
 
@@ -118,6 +120,21 @@ pub fn xdg_config_home() -> PathBuf {
     )))
 }
 
+pub fn xdg_data_home() -> PathBuf {
+    PathBuf::from(std::env::var("XDG_DATA_HOME").unwrap_or(format!(
+        "{}/.local/share",
+        std::env::var("HOME").unwrap_or_default()
+    )))
+}
+
+/// Where blocks keep data that has to survive restarts
+pub fn xdg_state_home() -> PathBuf {
+    PathBuf::from(std::env::var("XDG_STATE_HOME").unwrap_or(format!(
+        "{}/.local/state",
+        std::env::var("HOME").unwrap_or_default()
+    )))
+}
+
 pub fn deserialize_file<T>(path: &Path) -> Result<T>
 where
     T: DeserializeOwned,
@@ -147,6 +164,23 @@ pub fn read_file(blockname: &str, path: &Path) -> Result<String> {
     Ok(content)
 }
 
+/// The size of a file or of a directory with everything in it, without following links
+pub fn disk_usage(path: &Path) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| disk_usage(&entry.path()))
+        .sum()
+}
+
 pub fn has_command(block_name: &str, command: &str) -> Result<bool> {
     let exit_status = Command::new("sh")
         .args(&[
@@ -161,6 +195,202 @@ pub fn has_command(block_name: &str, command: &str) -> Result<bool> {
     Ok(exit_status.success())
 }
 
+/// Substitutes `${VAR}` with the value of the environment variable `VAR` and `$(command)` with
+/// the output of `command` run by `sh`, without its trailing newlines. `$$` is a literal `$`.
+pub fn interpolate(text: &str) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                result.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let value = std::env::var(&name).configuration_error(&format!(
+                    "environment variable '{}' used in \"{}\" is not set",
+                    name, text
+                ))?;
+                result.push_str(&value);
+            }
+            Some('(') => {
+                chars.next();
+                let mut command = String::new();
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    command.push(c);
+                }
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .output()
+                    .configuration_error(&format!("failed to run '{}'", command))?;
+                if !output.status.success() {
+                    return Err(ConfigurationError(
+                        format!("command '{}' failed", command),
+                        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    ));
+                }
+                result.push_str(String::from_utf8_lossy(&output.stdout).trim_end_matches('\n'));
+            }
+            _ => result.push('$'),
+        }
+    }
+    Ok(result)
+}
+
+/// Keeps a threshold-based state from flapping while a value hovers around a threshold. A state is
+/// only left for a less alarming one after the value moved more than `amount` past the threshold.
+#[derive(Debug, Clone, Default)]
+pub struct Hysteresis {
+    amount: f64,
+    last: Option<State>,
+}
+
+impl Hysteresis {
+    pub fn new(amount: f64) -> Self {
+        Self { amount, last: None }
+    }
+
+    /// Computes the state for `value` using `state_for`, which maps values to states by their
+    /// thresholds, and applies the hysteresis relative to the previous state.
+    pub fn apply<F: Fn(f64) -> State>(&mut self, value: f64, state_for: F) -> State {
+        let mut state = state_for(value);
+        if let Some(last) = self.last {
+            if state.severity() < last.severity() {
+                // Thresholds may be rising or falling, so look in both directions
+                let nearby = state_for(value + self.amount)
+                    .severity()
+                    .max(state_for(value - self.amount).severity());
+                if nearby >= last.severity() {
+                    state = last;
+                }
+            }
+        }
+        self.last = Some(state);
+        state
+    }
+}
+
+/// How to smooth a noisy series of values, configured as `"ema(<weight>)"` for an exponential
+/// moving average or `"avg(<count>)"` for the mean of the most recent values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Exponential moving average, the weight of the newest value is between 0 and 1
+    Ema(f64),
+    /// Mean of the given number of most recent values
+    Avg(usize),
+}
+
+impl std::str::FromStr for Smoothing {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ConfigurationError(
+                format!("invalid smoothing '{}'", s),
+                "expected \"ema(<weight>)\" or \"avg(<count>)\"".to_string(),
+            )
+        };
+        let (function, argument) = s
+            .trim()
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(invalid)?;
+        match function.trim() {
+            "ema" => match argument.trim().parse::<f64>() {
+                Ok(weight) if weight > 0. && weight <= 1. => Ok(Smoothing::Ema(weight)),
+                _ => Err(invalid()),
+            },
+            "avg" => match argument.trim().parse::<usize>() {
+                Ok(count) if count > 0 => Ok(Smoothing::Avg(count)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Applies a `Smoothing` to consecutive values. Without a smoothing, values are passed through.
+#[derive(Debug, Clone, Default)]
+pub struct Smoother {
+    smoothing: Option<Smoothing>,
+    history: VecDeque<f64>,
+}
+
+impl Smoother {
+    pub fn new(smoothing: Option<Smoothing>) -> Self {
+        Self {
+            smoothing,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Adds a new value and returns the smoothed value
+    pub fn push(&mut self, value: f64) -> f64 {
+        match self.smoothing {
+            None => value,
+            Some(Smoothing::Ema(weight)) => {
+                let smoothed = match self.history.pop_back() {
+                    Some(last) => weight * value + (1. - weight) * last,
+                    None => value,
+                };
+                self.history.push_back(smoothed);
+                smoothed
+            }
+            Some(Smoothing::Avg(count)) => {
+                self.history.push_back(value);
+                while self.history.len() > count {
+                    self.history.pop_front();
+                }
+                self.history.iter().sum::<f64>() / self.history.len() as f64
+            }
+        }
+    }
+}
+
+/// Matches `text` against a shell-style wildcard `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 macro_rules! map {
     ($($key:expr => $value:expr),+ $(,)*) => {{
         let mut m = ::std::collections::HashMap::new();
@@ -210,9 +440,22 @@ pub fn format_vec_to_bar_graph(content: &[f64], min: Option<f64>, max: Option<f6
     }
 }
 
+/// "2d 3h" while a day or more is left, "3:05" after that
+pub fn format_countdown(remaining: chrono::Duration) -> String {
+    let minutes = remaining.num_minutes().max(0);
+    if minutes >= 24 * 60 {
+        format!("{}d {}h", minutes / (24 * 60), minutes / 60 % 24)
+    } else {
+        format!("{}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::util::has_command;
+    use crate::util::{
+        format_countdown, has_command, interpolate, wildcard_match, Hysteresis, Smoother, Smoothing,
+    };
+    use crate::widgets::State;
 
     #[test]
     // we assume sh is always available
@@ -231,4 +474,115 @@ mod tests {
         let has_command = has_command.unwrap();
         assert!(!has_command)
     }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.toml", "blocks.toml"));
+        assert!(wildcard_match("*.toml", ".toml"));
+        assert!(wildcard_match("config.toml", "config.toml"));
+        assert!(wildcard_match("b?o*s.t*", "blocks.toml"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("*.toml", "blocks.toml.bak"));
+        assert!(!wildcard_match("config.toml", "config.tom"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        std::env::set_var("I3RS_TEST_INTERPOLATE", "secret");
+        assert_eq!(
+            interpolate("key=${I3RS_TEST_INTERPOLATE}&x=$(printf 'a(b)\\n\\n')").unwrap(),
+            "key=secret&x=a(b)"
+        );
+        assert_eq!(interpolate("$$5 and $ alone").unwrap(), "$5 and $ alone");
+        assert!(interpolate("${I3RS_TEST_UNSET_VARIABLE}").is_err());
+        assert!(interpolate("$(exit 1)").is_err());
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let state_for = |value: f64| {
+            if value > 80. {
+                State::Warning
+            } else {
+                State::Idle
+            }
+        };
+        let mut hysteresis = Hysteresis::new(5.);
+        assert_eq!(
+            hysteresis.apply(81., state_for).severity(),
+            State::Warning.severity()
+        );
+        assert_eq!(
+            hysteresis.apply(78., state_for).severity(),
+            State::Warning.severity()
+        );
+        assert_eq!(
+            hysteresis.apply(74., state_for).severity(),
+            State::Idle.severity()
+        );
+        assert_eq!(
+            hysteresis.apply(78., state_for).severity(),
+            State::Idle.severity()
+        );
+
+        // Falling thresholds, e.g. free disk space
+        let state_for = |value: f64| {
+            if value < 10. {
+                State::Critical
+            } else {
+                State::Idle
+            }
+        };
+        let mut hysteresis = Hysteresis::new(2.);
+        assert_eq!(
+            hysteresis.apply(9., state_for).severity(),
+            State::Critical.severity()
+        );
+        assert_eq!(
+            hysteresis.apply(11., state_for).severity(),
+            State::Critical.severity()
+        );
+        assert_eq!(
+            hysteresis.apply(12.5, state_for).severity(),
+            State::Idle.severity()
+        );
+    }
+
+    #[test]
+    fn test_smoothing() {
+        assert_eq!(
+            "ema(0.5)".parse::<Smoothing>().unwrap(),
+            Smoothing::Ema(0.5)
+        );
+        assert_eq!("avg(3)".parse::<Smoothing>().unwrap(), Smoothing::Avg(3));
+        assert!("ema(2)".parse::<Smoothing>().is_err());
+        assert!("avg(0)".parse::<Smoothing>().is_err());
+        assert!("median(3)".parse::<Smoothing>().is_err());
+
+        let mut ema = Smoother::new(Some(Smoothing::Ema(0.5)));
+        assert_eq!(ema.push(10.), 10.);
+        assert_eq!(ema.push(20.), 15.);
+        assert_eq!(ema.push(15.), 15.);
+
+        let mut avg = Smoother::new(Some(Smoothing::Avg(2)));
+        assert_eq!(avg.push(10.), 10.);
+        assert_eq!(avg.push(20.), 15.);
+        assert_eq!(avg.push(40.), 30.);
+
+        let mut none = Smoother::new(None);
+        assert_eq!(none.push(10.), 10.);
+    }
+
+    #[test]
+    fn test_format_countdown() {
+        let minutes = chrono::Duration::minutes;
+        assert_eq!(
+            format_countdown(minutes(2 * 24 * 60 + 3 * 60 + 59)),
+            "2d 3h"
+        );
+        assert_eq!(format_countdown(minutes(24 * 60)), "1d 0h");
+        assert_eq!(format_countdown(minutes(3 * 60 + 5)), "3:05");
+        assert_eq!(format_countdown(minutes(-10)), "0:00");
+    }
 }
@@ -1,3 +1,8 @@
+// Only used by blocks that require D-Bus so far
+#[cfg_attr(not(feature = "dbus"), allow(dead_code))]
+pub mod button_row;
+pub mod graph;
+pub mod progress;
 pub mod rotatingtext;
 pub mod text;
 
@@ -44,16 +49,79 @@ impl Spacing {
     }
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+/// What a widget shows when it has no text
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyPolicy {
+    /// Show the widget as is
+    Show,
+    /// Hide the widget
+    Hide,
+    /// Show only the icon, or hide the widget if it has none
+    IconOnly,
+    /// Show a placeholder text instead
+    Placeholder,
+}
+
+/// How a widget shows text that is longer than its maximum width
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// Scroll through the text
+    Marquee,
+    /// Cut the text off
+    Truncate,
+    /// Show only the icon
+    Hide,
+}
+
+/// How i3bar interprets the text of a widget
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Markup {
+    /// Pango markup such as `<span color='red'>` styles the text
+    Pango,
+    /// The text is shown as is
+    #[serde(rename = "none")]
+    Plain,
+}
+
+impl Markup {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Markup::Pango => "pango",
+            Markup::Plain => "none",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
 pub enum State {
+    #[serde(alias = "idle")]
     Idle,
+    #[serde(alias = "info")]
     Info,
+    #[serde(alias = "good")]
     Good,
+    #[serde(alias = "warning")]
     Warning,
+    #[serde(alias = "critical")]
     Critical,
 }
 
 impl State {
+    /// Orders states from the least to the most alarming
+    pub fn severity(self) -> u8 {
+        use self::State::*;
+        match self {
+            Good => 0,
+            Idle => 1,
+            Info => 2,
+            Warning => 3,
+            Critical => 4,
+        }
+    }
+
     pub fn theme_keys(self, theme: &Theme) -> (Color, Color) {
         use self::State::*;
         match self {
@@ -77,4 +145,14 @@ impl FromStr for State {
 
 pub trait I3BarWidget {
     fn get_data(&self) -> I3BarBlock;
+
+    /// The state that the widget is shown in
+    fn state(&self) -> State {
+        State::Idle
+    }
+
+    /// Whether the widget changes by itself, i.e. has to be redrawn regularly
+    fn animated(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,74 @@
+use super::text::TextWidget;
+use super::{I3BarWidget, Spacing, State};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::protocol::i3bar_event::I3BarEvent;
+use crate::util::pseudo_uuid;
+
+/// A row of buttons, e.g. ⏮ ⏯ ⏭, each of which is clicked separately.
+///
+/// Every segment is a widget of its own with a unique instance id, through which clicks are
+/// routed back to the segment by `clicked`.
+#[derive(Clone, Debug)]
+pub struct ButtonRowWidget {
+    id: usize,
+    shared_config: SharedConfig,
+    state: State,
+    segments: Vec<(String, TextWidget)>,
+}
+
+impl ButtonRowWidget {
+    pub fn new(id: usize, shared_config: SharedConfig) -> Self {
+        ButtonRowWidget {
+            id,
+            shared_config,
+            state: State::Idle,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Appends a segment showing `icon`, which is known as `name` to `segment` and `clicked`
+    pub fn with_segment(mut self, name: &str, icon: &str) -> Result<Self> {
+        let button = TextWidget::new(self.id, pseudo_uuid(), self.shared_config.clone())
+            .with_icon(icon)?
+            .with_state(self.state)
+            .with_spacing(Spacing::Hidden);
+        self.segments.push((name.to_string(), button));
+        Ok(self)
+    }
+
+    pub fn with_state(mut self, state: State) -> Self {
+        self.set_state(state);
+        self
+    }
+
+    pub fn segment(&mut self, name: &str) -> Option<&mut TextWidget> {
+        self.segments
+            .iter_mut()
+            .find(|(segment, _)| segment == name)
+            .map(|(_, button)| button)
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+        for (_, button) in &mut self.segments {
+            button.set_state(state);
+        }
+    }
+
+    /// The name of the segment that `event` is a click on, if any
+    pub fn clicked(&self, event: &I3BarEvent) -> Option<&str> {
+        self.segments
+            .iter()
+            .find(|(_, button)| Some(button.instance) == event.instance)
+            .map(|(segment, _)| segment.as_str())
+    }
+
+    /// The segments in order, to be included in the view of the block
+    pub fn widgets(&self) -> Vec<&dyn I3BarWidget> {
+        self.segments
+            .iter()
+            .map(|(_, button)| button as &dyn I3BarWidget)
+            .collect()
+    }
+}
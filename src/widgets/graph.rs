@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use serde_derive::Deserialize;
+
+use super::text::TextWidget;
+use super::{I3BarWidget, State};
+use crate::config::SharedConfig;
+use crate::protocol::i3bar_block::I3BarBlock;
+use crate::util::format_vec_to_bar_graph;
+
+/// Characters a graph is drawn with
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphStyle {
+    /// One block element of eight heights per sample
+    Bars,
+    /// One braille pattern of five heights per two samples
+    Braille,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct GraphConfig {
+    /// Number of samples to keep
+    pub samples: usize,
+
+    pub style: GraphStyle,
+
+    /// Value drawn as the lowest height, the smallest sample if unset
+    pub min: Option<f64>,
+
+    /// Value drawn as the full height, the largest sample if unset
+    pub max: Option<f64>,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            samples: 10,
+            style: GraphStyle::Bars,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+/// The last samples of a value, drawn as a graph
+#[derive(Clone, Debug)]
+pub struct History {
+    samples: VecDeque<f64>,
+    config: GraphConfig,
+}
+
+impl History {
+    pub fn new(config: GraphConfig) -> Self {
+        History {
+            samples: VecDeque::with_capacity(config.samples),
+            config,
+        }
+    }
+
+    /// Adds a sample, dropping the oldest one if the history is full
+    pub fn push(&mut self, sample: f64) {
+        if self.samples.len() >= self.config.samples {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn draw(&self) -> String {
+        let samples: Vec<f64> = self.samples.iter().copied().collect();
+        draw(
+            &samples,
+            self.config.samples,
+            self.config.style,
+            self.config.min,
+            self.config.max,
+        )
+    }
+}
+
+/// Shows the history of a value, fed by the block with a sample on every update
+#[derive(Clone, Debug)]
+pub struct GraphWidget {
+    text: TextWidget,
+    history: History,
+}
+
+impl GraphWidget {
+    pub fn new(
+        id: usize,
+        instance: usize,
+        shared_config: SharedConfig,
+        config: GraphConfig,
+    ) -> Self {
+        GraphWidget {
+            text: TextWidget::new(id, instance, shared_config),
+            history: History::new(config),
+        }
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.text.set_state(state);
+    }
+
+    /// Adds a sample, dropping the oldest one if the history is full
+    pub fn push(&mut self, sample: f64) {
+        self.history.push(sample);
+        self.text.set_text(self.history.draw());
+    }
+}
+
+impl I3BarWidget for GraphWidget {
+    fn get_data(&self) -> I3BarBlock {
+        self.text.get_data()
+    }
+
+    fn state(&self) -> State {
+        self.text.state()
+    }
+}
+
+/// Draws `samples` right-aligned in the space of `capacity` samples, so that the graph keeps its
+/// width while the history fills up
+fn draw(
+    samples: &[f64],
+    capacity: usize,
+    style: GraphStyle,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> String {
+    let missing = capacity.saturating_sub(samples.len());
+    match style {
+        GraphStyle::Bars => {
+            let mut graph = "\u{2581}".repeat(missing);
+            graph.push_str(&format_vec_to_bar_graph(samples, min, max));
+            graph
+        }
+        GraphStyle::Braille => {
+            let min = min.unwrap_or_else(|| samples.iter().copied().fold(f64::INFINITY, f64::min));
+            let max =
+                max.unwrap_or_else(|| samples.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+            let extent = max - min;
+            let mut heights = vec![0; missing];
+            heights.extend(samples.iter().map(|sample| {
+                if extent.is_normal() {
+                    ((sample.clamp(min, max) - min) / extent * 4.).round() as usize
+                } else {
+                    0
+                }
+            }));
+            if heights.len() % 2 == 1 {
+                heights.insert(0, 0);
+            }
+            heights
+                .chunks(2)
+                .map(|pair| braille(pair[0], pair[1]))
+                .collect()
+        }
+    }
+}
+
+/// A braille pattern whose columns are filled from the bottom up to the given number of dots
+fn braille(left: usize, right: usize) -> char {
+    const LEFT: [u32; 4] = [0x40, 0x04, 0x02, 0x01];
+    const RIGHT: [u32; 4] = [0x80, 0x20, 0x10, 0x08];
+    let dots: u32 = LEFT[..left].iter().chain(&RIGHT[..right]).sum();
+    std::char::from_u32(0x2800 + dots).unwrap_or(' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let samples = [0., 50., 100.];
+        assert_eq!(
+            draw(&samples, 5, GraphStyle::Bars, Some(0.), Some(100.)),
+            "▁▁▁▄█"
+        );
+        assert_eq!(
+            draw(&samples, 4, GraphStyle::Braille, Some(0.), Some(100.)),
+            "\u{2800}\u{28fc}"
+        );
+        assert_eq!(braille(4, 4), '\u{28ff}');
+    }
+}
@@ -0,0 +1,133 @@
+use serde_derive::Deserialize;
+
+use super::text::TextWidget;
+use super::{I3BarWidget, State};
+use crate::config::SharedConfig;
+use crate::formatting::value::format_bar;
+use crate::protocol::i3bar_block::I3BarBlock;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ProgressConfig {
+    /// Width of the bar in characters
+    pub width: usize,
+
+    /// Percentage from which the bar is shown as info
+    pub info: Option<f64>,
+
+    /// Percentage from which the bar is shown as warning
+    pub warning: Option<f64>,
+
+    /// Percentage from which the bar is shown as critical
+    pub critical: Option<f64>,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            info: None,
+            warning: None,
+            critical: None,
+        }
+    }
+}
+
+impl ProgressConfig {
+    /// The state of `value`, if any thresholds are set. They count downwards if the worse
+    /// thresholds are the lower ones, e.g. for the charge of a battery.
+    fn state(&self, value: f64) -> Option<State> {
+        let thresholds = [
+            (self.critical, State::Critical),
+            (self.warning, State::Warning),
+            (self.info, State::Info),
+        ];
+        let set: Vec<(f64, State)> = thresholds
+            .iter()
+            .filter_map(|(threshold, state)| threshold.map(|threshold| (threshold, *state)))
+            .collect();
+        if set.is_empty() {
+            return None;
+        }
+        let falling = set.len() > 1 && set[0].0 < set[1].0;
+        Some(
+            set.iter()
+                .find(|(threshold, _)| {
+                    if falling {
+                        value <= *threshold
+                    } else {
+                        value >= *threshold
+                    }
+                })
+                .map(|(_, state)| *state)
+                .unwrap_or(State::Idle),
+        )
+    }
+}
+
+/// A bar of fixed width that fills up with a percentage, e.g. of a download
+#[derive(Clone, Debug)]
+pub struct ProgressWidget {
+    text: TextWidget,
+    config: ProgressConfig,
+}
+
+impl ProgressWidget {
+    pub fn new(
+        id: usize,
+        instance: usize,
+        shared_config: SharedConfig,
+        config: ProgressConfig,
+    ) -> Self {
+        ProgressWidget {
+            text: TextWidget::new(id, instance, shared_config),
+            config,
+        }
+    }
+
+    /// Shows `value` (0 to 100), colored by the thresholds if any are configured and otherwise by
+    /// `state`
+    pub fn set_value(&mut self, value: f64, state: State) {
+        self.text
+            .set_text(format_bar(value / 100., self.config.width));
+        self.text
+            .set_state(self.config.state(value).unwrap_or(state));
+    }
+}
+
+impl I3BarWidget for ProgressWidget {
+    fn get_data(&self) -> I3BarBlock {
+        self.text.get_data()
+    }
+
+    fn state(&self) -> State {
+        self.text.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state() {
+        let disk = ProgressConfig {
+            warning: Some(80.),
+            critical: Some(95.),
+            ..ProgressConfig::default()
+        };
+        assert_eq!(disk.state(50.), Some(State::Idle));
+        assert_eq!(disk.state(90.), Some(State::Warning));
+        assert_eq!(disk.state(99.), Some(State::Critical));
+
+        let battery = ProgressConfig {
+            warning: Some(30.),
+            critical: Some(15.),
+            ..ProgressConfig::default()
+        };
+        assert_eq!(battery.state(20.), Some(State::Warning));
+        assert_eq!(battery.state(10.), Some(State::Critical));
+
+        assert_eq!(ProgressConfig::default().state(50.), None);
+    }
+}
@@ -146,8 +146,6 @@ impl RotatingTextWidget {
     }
 
     fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme);
-
         let mut icon = self.icon.clone().unwrap_or_else(|| match self.spacing {
             Spacing::Normal => String::from(" "),
             _ => String::from(""),
@@ -170,8 +168,6 @@ impl RotatingTextWidget {
                 Some(I3BarBlockMinWidth::Text(icon))
             }
         };
-        self.inner.background = key_bg;
-        self.inner.color = key_fg;
     }
 
     pub fn next(&mut self) -> Result<(bool, Option<Duration>)> {
@@ -203,7 +199,17 @@ impl RotatingTextWidget {
 }
 
 impl I3BarWidget for RotatingTextWidget {
+    fn state(&self) -> State {
+        self.state
+    }
+
     fn get_data(&self) -> I3BarBlock {
-        self.inner.clone()
+        // The colors are picked when drawing, as the theme may switch to its dark variant
+        let (background, color) = self.state.theme_keys(&self.shared_config.theme);
+        I3BarBlock {
+            background,
+            color,
+            ..self.inner.clone()
+        }
     }
 }
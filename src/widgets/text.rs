@@ -1,7 +1,16 @@
-use super::{I3BarWidget, Spacing, State};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{EmptyPolicy, I3BarWidget, Markup, Overflow, Spacing, State};
 use crate::config::SharedConfig;
 use crate::errors::*;
-use crate::protocol::i3bar_block::I3BarBlock;
+use crate::protocol::i3bar_block::{I3BarBlock, I3BarBlockMinWidth};
+use crate::themes::Color;
+use crate::util::escape_pango_text;
+
+/// How long a marquee shows the text before moving it by one character
+pub const MARQUEE_STEP: Duration = Duration::from_millis(500);
+/// Space between the end of a scrolling text and its start
+const MARQUEE_GAP: &str = "   ";
 
 #[derive(Clone, Debug)]
 pub struct TextWidget {
@@ -12,6 +21,7 @@ pub struct TextWidget {
     state: State,
     spacing: Spacing,
     spacing_short: Spacing,
+    gradient_value: Option<f64>,
     shared_config: SharedConfig,
     inner: I3BarBlock,
 }
@@ -19,13 +29,28 @@ pub struct TextWidget {
 impl TextWidget {
     pub fn new(id: usize, instance: usize, shared_config: SharedConfig) -> Self {
         let (key_bg, key_fg) = State::Idle.theme_keys(&shared_config.theme); // Initial colors
-        let inner = I3BarBlock {
+        let mut inner = I3BarBlock {
             name: Some(id.to_string()),
             instance: Some(instance.to_string()),
             color: key_fg,
             background: key_bg,
+            markup: Some(
+                shared_config
+                    .markup
+                    .unwrap_or(Markup::Pango)
+                    .as_str()
+                    .to_string(),
+            ),
+            align: shared_config.align,
             ..I3BarBlock::default()
         };
+        // i3bar measures a sample text in the font of the bar, unless it is styled as well
+        inner.min_width = match shared_config.min_width.clone() {
+            Some(I3BarBlockMinWidth::Text(sample)) => {
+                Some(I3BarBlockMinWidth::Text(with_font(&shared_config, sample)))
+            }
+            min_width => min_width,
+        };
 
         TextWidget {
             instance,
@@ -35,6 +60,7 @@ impl TextWidget {
             state: State::Idle,
             spacing: Spacing::Normal,
             spacing_short: Spacing::Normal,
+            gradient_value: None,
             shared_config,
             inner,
         }
@@ -71,6 +97,13 @@ impl TextWidget {
         Ok(())
     }
 
+    /// Shows several icons as one, e.g. an icon with a badge
+    pub fn set_icons(&mut self, names: &[&str]) -> Result<()> {
+        self.icon = Some(self.shared_config.get_icons(names)?);
+        self.update();
+        Ok(())
+    }
+
     pub fn unset_icon(&mut self) {
         self.icon = None;
         self.update();
@@ -97,6 +130,16 @@ impl TextWidget {
         self.update();
     }
 
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Sets the value that picks the colors if the block is configured with a `gradient`
+    pub fn set_gradient_value(&mut self, value: f64) {
+        self.gradient_value = Some(value);
+        self.update();
+    }
+
     pub fn set_spacing(&mut self, spacing: Spacing) {
         self.spacing = spacing;
         self.update();
@@ -113,22 +156,175 @@ impl TextWidget {
         )
     }
 
-    fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme);
+    /// The content if it is longer than the maximum width
+    fn overflowing_content(&self) -> Option<&str> {
+        let max_width = self.shared_config.max_text_width?;
+        self.content
+            .as_deref()
+            .filter(|content| content.chars().count() > max_width)
+    }
 
-        self.inner.full_text =
-            self.format_text(self.content.clone().unwrap_or_default(), self.spacing);
-        self.inner.short_text = match &self.content_short {
-            Some(text) => Some(self.format_text(text.clone(), self.spacing_short)),
-            _ => None,
-        };
-        self.inner.background = key_bg;
-        self.inner.color = key_fg;
+    /// Picked when drawing, as the theme may switch to its dark variant at any time
+    fn colors(&self) -> (Color, Color) {
+        match (self.shared_config.gradient, self.gradient_value) {
+            (Some(gradient), Some(value)) => gradient.colors(&self.shared_config.theme, value),
+            _ => self.state.theme_keys(&self.shared_config.theme),
+        }
+    }
+
+    fn update(&mut self) {
+        let content = self.content.clone().unwrap_or_default();
+        let policy = self.shared_config.if_empty;
+        if content.trim().is_empty() && matches!(policy, Some(p) if p != EmptyPolicy::Show) {
+            self.inner.short_text = None;
+            self.inner.full_text = match policy {
+                Some(EmptyPolicy::IconOnly) => self.icon.clone().unwrap_or_default(),
+                Some(EmptyPolicy::Placeholder) => {
+                    let placeholder = self.shared_config.if_empty_text.clone();
+                    self.format_text(placeholder, Spacing::Normal)
+                }
+                _ => String::new(),
+            };
+        } else {
+            self.inner.full_text = self.format_text(content, self.spacing);
+            self.inner.short_text = match &self.content_short {
+                Some(text) => Some(self.format_text(text.clone(), self.spacing_short)),
+                _ => None,
+            };
+        }
     }
 }
 
 impl I3BarWidget for TextWidget {
+    fn state(&self) -> State {
+        self.state
+    }
+
     fn get_data(&self) -> I3BarBlock {
-        self.inner.clone()
+        let mut data = self.inner.clone();
+        let (background, color) = self.colors();
+        data.background = background;
+        data.color = color;
+        if let Some(content) = self.overflowing_content() {
+            let max_width = self.shared_config.max_text_width.unwrap_or_default();
+            data.full_text = match self.shared_config.overflow {
+                Some(Overflow::Hide) => self.icon.clone().unwrap_or_default(),
+                Some(Overflow::Truncate) => {
+                    self.format_text(truncate(content, max_width), self.spacing)
+                }
+                None | Some(Overflow::Marquee) => {
+                    // Derived from the clock, so that redrawing is all it takes to move on
+                    let step = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                        / MARQUEE_STEP.as_millis();
+                    self.format_text(marquee(content, max_width, step as usize), self.spacing)
+                }
+            };
+        }
+        if let Some(monochrome) = &self.shared_config.monochrome {
+            let marker = monochrome.marker(self.state);
+            if !marker.is_empty() && !data.full_text.is_empty() {
+                data.full_text = format!("{}{}", marker, data.full_text);
+                data.short_text = data.short_text.map(|text| format!("{}{}", marker, text));
+            }
+        }
+        data.full_text = with_font(&self.shared_config, data.full_text);
+        data.short_text = data
+            .short_text
+            .map(|text| with_font(&self.shared_config, text));
+        data
+    }
+
+    fn animated(&self) -> bool {
+        matches!(self.shared_config.overflow, None | Some(Overflow::Marquee))
+            && self.overflowing_content().is_some()
+    }
+}
+
+/// Wraps `text` in a span with the font of the block, if it has one and its text is pango markup.
+/// Empty texts stay empty, so that hidden widgets stay hidden.
+fn with_font(shared_config: &SharedConfig, text: String) -> String {
+    match &shared_config.font {
+        Some(font) if !text.is_empty() && shared_config.markup != Some(Markup::Plain) => format!(
+            "<span font_desc='{}'>{}</span>",
+            escape_pango_text(font),
+            text
+        ),
+        _ => text,
+    }
+}
+
+/// Cuts `text` to `max_width` characters, ending it with an ellipsis
+fn truncate(text: &str, max_width: usize) -> String {
+    let mut text: String = text.chars().take(max_width.saturating_sub(1)).collect();
+    text.push('…');
+    text
+}
+
+/// The `max_width` characters of `text` that are visible after scrolling it by `step` characters
+fn marquee(text: &str, max_width: usize, step: usize) -> String {
+    let cycle: Vec<char> = text.chars().chain(MARQUEE_GAP.chars()).collect();
+    cycle
+        .iter()
+        .cycle()
+        .skip(step % cycle.len())
+        .take(max_width)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(truncate("Bohemian Rhapsody", 8), "Bohemia…");
+        assert_eq!(marquee("Bohemian Rhapsody", 8, 0), "Bohemian");
+        assert_eq!(marquee("Bohemian Rhapsody", 8, 13), "sody   B");
+        assert_eq!(marquee("Bohemian Rhapsody", 8, 20), "Bohemian");
+    }
+
+    #[test]
+    fn test_font() {
+        let mut shared_config = SharedConfig::default();
+        shared_config.layout_override(
+            Some("Symbols Nerd Font 13".to_string()),
+            Some(I3BarBlockMinWidth::Text("100%".to_string())),
+            None,
+        );
+        let widget = TextWidget::new(0, 0, shared_config.clone()).with_text("42%");
+        let data = widget.get_data();
+        assert_eq!(
+            data.full_text,
+            "<span font_desc='Symbols Nerd Font 13'> 42% </span>"
+        );
+        assert!(matches!(
+            data.min_width,
+            Some(I3BarBlockMinWidth::Text(sample)) if sample.ends_with(">100%</span>")
+        ));
+
+        shared_config.markup_override(Some(Markup::Plain));
+        let widget = TextWidget::new(0, 0, shared_config).with_text("42%");
+        assert_eq!(widget.get_data().full_text, " 42% ");
+    }
+
+    #[test]
+    fn test_monochrome() {
+        let config = crate::config::Config {
+            monochrome: Some(Default::default()),
+            ..Default::default()
+        };
+        let shared_config = SharedConfig::new(&config);
+        let widget = TextWidget::new(0, 0, shared_config.clone()).with_text("5%");
+        let idle = widget.get_data();
+        assert_eq!(idle.full_text, " 5% ");
+
+        let critical = widget.with_state(State::Critical).get_data();
+        assert_eq!(critical.full_text, "!! 5% ");
+        // Inverted
+        assert_eq!(critical.background, idle.color);
+        assert_eq!(critical.color, idle.background);
     }
 }
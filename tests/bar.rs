@@ -0,0 +1,231 @@
+//! End-to-end tests of the bar, see `support` for how it is run.
+
+mod support;
+
+use support::{texts, theme, widgets, Bar};
+
+#[test]
+fn test_clicks_reach_their_block() {
+    let mut bar = Bar::start(&format!(
+        r#"
+theme = "{}"
+icons = "none"
+
+[[block]]
+block = "custom"
+cycle = ["echo left 1", "echo left 2"]
+
+[[block]]
+block = "custom"
+cycle = ["echo right 1", "echo right 2"]
+"#,
+        theme("plain")
+    ));
+    bar.wait_for(|status| texts(status) == ["left 1", "right 1"]);
+
+    bar.click(1, 1);
+    bar.wait_for(|status| texts(status) == ["left 1", "right 2"]);
+    bar.click(0, 1);
+    bar.wait_for(|status| texts(status) == ["left 2", "right 2"]);
+}
+
+#[test]
+fn test_modifier_clicks_run_their_command() {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join("shift");
+    let mut bar = Bar::start(&format!(
+        r#"
+icons = "none"
+
+[[block]]
+block = "custom"
+cycle = ["echo 1", "echo 2"]
+on_click_shift = "touch {}"
+"#,
+        path.display()
+    ));
+    bar.wait_for(|status| texts(status) == ["1"]);
+
+    // Runs the command instead of cycling
+    bar.click_with_modifiers(0, 1, &["Shift"]);
+    bar.click(0, 1);
+    bar.wait_for(|status| texts(status) == ["2"]);
+    let start = std::time::Instant::now();
+    while !path.exists() {
+        assert!(start.elapsed().as_secs() < 5, "the command didn't run");
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn test_theme_colors_follow_the_state() {
+    let mut bar = Bar::start(&format!(
+        r##"
+icons = "none"
+
+[theme]
+file = "{}"
+[theme.overrides]
+idle_bg = "#101010"
+critical_bg = "#ff0000"
+
+[[block]]
+block = "custom"
+json = true
+command = "echo '{{\"text\": \"ok\"}}'"
+
+[[block]]
+block = "custom"
+json = true
+command = "echo '{{\"text\": \"fail\", \"state\": \"Critical\"}}'"
+"##,
+        theme("plain")
+    ));
+    let status = bar.wait_for(|status| texts(status) == ["ok", "fail"]);
+
+    assert_eq!(widgets(&status, 0)[0]["background"], "#101010FF");
+    assert_eq!(widgets(&status, 1)[0]["background"], "#FF0000FF");
+}
+
+#[test]
+fn test_custom_json_output() {
+    let mut bar = Bar::start(&format!(
+        r##"
+icons = "none"
+
+[theme]
+file = "{}"
+[theme.overrides]
+warning_bg = "#ffaa00"
+
+[[block]]
+block = "custom"
+json = true
+command = "echo '{{\"text\": \"disk 91%\", \"short_text\": \"91%\", \"state\": \"warning\"}}'"
+"##,
+        theme("plain")
+    ));
+    let status = bar.wait_for(|status| texts(status) == ["disk 91%"]);
+
+    let widget = &widgets(&status, 0)[0];
+    assert_eq!(widget["short_text"].as_str().unwrap().trim(), "91%");
+    assert_eq!(widget["background"], "#FFAA00FF");
+}
+
+#[test]
+fn test_custom_persistent() {
+    let mut bar = Bar::start(
+        r#"
+icons = "none"
+
+[[block]]
+block = "custom"
+persistent = true
+command = "echo starting; sleep 0.5; echo playing; sleep 30"
+"#,
+    );
+    // The second line is shown while the command still runs
+    bar.wait_for(|status| texts(status) == ["starting"]);
+    bar.wait_for(|status| texts(status) == ["playing"]);
+}
+
+#[test]
+fn test_watch_files() {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join("status");
+    std::fs::write(&path, "before").unwrap();
+    let mut bar = Bar::start(&format!(
+        r#"
+icons = "none"
+
+[[block]]
+block = "custom"
+command = "cat {path}"
+interval = "once"
+watch_files = ["{path}"]
+"#,
+        path = path.display()
+    ));
+    bar.wait_for(|status| texts(status) == ["before"]);
+
+    std::fs::write(&path, "after").unwrap();
+    bar.wait_for(|status| texts(status) == ["after"]);
+}
+
+#[test]
+fn test_clicks_are_acknowledged_while_handled() {
+    let mut bar = Bar::start(&format!(
+        r##"
+icons = "none"
+
+[theme]
+file = "{}"
+[theme.overrides]
+idle_bg = "#101010"
+idle_fg = "#eeeeee"
+
+[[block]]
+block = "custom"
+cycle = ["echo ready", "sleep 1; echo done"]
+"##,
+        theme("plain")
+    ));
+    bar.wait_for(|status| texts(status) == ["ready"]);
+
+    bar.click(0, 1);
+    let status = bar.wait_for(|status| widgets(status, 0)[0]["background"] == "#EEEEEEFF");
+    assert_eq!(texts(&status), ["ready"]);
+    bar.wait_for(|status| {
+        texts(status) == ["done"] && widgets(status, 0)[0]["background"] == "#101010FF"
+    });
+}
+
+#[test]
+fn test_config_reload_replaces_blocks() {
+    let config = |text: &str| {
+        format!(
+            r#"
+theme = "{}"
+icons = "none"
+
+[[block]]
+block = "custom"
+command = "echo {}"
+"#,
+            theme("plain"),
+            text
+        )
+    };
+    let mut bar = Bar::start(&config("before"));
+    bar.wait_for(|status| texts(status) == ["before"]);
+
+    bar.write_config(&config("after"));
+    bar.wait_for(|status| texts(status) == ["after"]);
+}
+
+#[test]
+fn test_termination_lets_blocks_clean_up() {
+    let mut bar = Bar::start(&format!(
+        r#"
+theme = "{}"
+icons = "none"
+
+[[block]]
+block = "supertoggle"
+command_current_state = "echo off"
+command_on = "true"
+command_off = "true"
+command_on_exit = "touch exited"
+format_on = "on"
+format_off = "off"
+command_status_on_regex = "on"
+command_status_off_regex = "off"
+"#,
+        theme("plain")
+    ));
+    bar.wait_for(|status| texts(status) == ["OFF off"]);
+
+    let lines = bar.terminate();
+    assert!(bar.dir.path().join("exited").exists());
+    assert!(lines.last().unwrap().contains("i3status-rs stopped"));
+}
@@ -0,0 +1,176 @@
+//! Runs the bar like i3bar does: it starts the binary with a config, sends it click events on
+//! stdin and reads the status lines it prints, so that tests can check what the bar shows.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde_json::{json, Value};
+
+/// How long to wait for the bar to show something
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The path of a theme of the repository, for the `theme` of a config
+pub fn theme(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("files/themes")
+        .join(format!("{}.toml", name))
+        .display()
+        .to_string()
+}
+
+pub struct Bar {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// Holds the config and whatever else the test puts there
+    pub dir: TempDir,
+}
+
+impl Bar {
+    /// Starts the bar with `config` and reads the header of the protocol
+    pub fn start(config: &str) -> Bar {
+        let dir = TempDir::new().unwrap();
+        dir.child("config.toml").write_str(config).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_i3status-rs"))
+            .arg(dir.child("config.toml").path())
+            .arg("--exit-on-error")
+            .current_dir(dir.path())
+            // Keeps what blocks save for the next start away from the user's
+            .env("XDG_STATE_HOME", dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Lines are read in a thread, so that waiting for them can time out
+        let (tx, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if line.map(|line| tx.send(line)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut bar = Bar {
+            child,
+            stdin,
+            lines,
+            dir,
+        };
+        let header: Value = serde_json::from_str(&bar.line()).unwrap();
+        assert_eq!(header["version"], 1);
+        assert_eq!(header["click_events"], true);
+        assert_eq!(bar.line(), "[");
+        // The bar starts its list of clicks like i3bar does
+        writeln!(bar.stdin, "[").unwrap();
+        bar
+    }
+
+    /// Replaces the config, which the bar reloads
+    pub fn write_config(&self, config: &str) {
+        self.dir.child("config.toml").write_str(config).unwrap();
+    }
+
+    fn line(&mut self) -> String {
+        match self.lines.recv_timeout(TIMEOUT) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => panic!("the bar printed nothing for {:?}", TIMEOUT),
+            Err(RecvTimeoutError::Disconnected) => panic!("the bar exited"),
+        }
+    }
+
+    /// The next status line, i.e. the widgets and separators that the bar shows
+    pub fn status(&mut self) -> Vec<Value> {
+        let line = self.line();
+        let json = line.strip_suffix(',').unwrap_or(&line);
+        match serde_json::from_str(json) {
+            Ok(Value::Array(widgets)) => widgets,
+            _ => panic!("not a status line: {}", line),
+        }
+    }
+
+    /// Waits for a status line for which `check` holds and returns it
+    pub fn wait_for(&mut self, check: impl Fn(&[Value]) -> bool) -> Vec<Value> {
+        let start = Instant::now();
+        loop {
+            let status = self.status();
+            if check(&status) {
+                return status;
+            }
+            if start.elapsed() > TIMEOUT {
+                panic!("the bar didn't show what was expected, but {:?}", status);
+            }
+        }
+    }
+
+    /// Clicks the first widget of the block at `block` in the config with `button`, where 1 is
+    /// the left button
+    pub fn click(&mut self, block: usize, button: u8) {
+        self.click_with_modifiers(block, button, &[]);
+    }
+
+    /// Clicks like `click` while holding down `modifiers`, like "Shift"
+    pub fn click_with_modifiers(&mut self, block: usize, button: u8, modifiers: &[&str]) {
+        let event = json!({
+            "name": block.to_string(),
+            "instance": "0",
+            "button": button,
+            "modifiers": modifiers,
+            "x": 1,
+            "y": 1,
+        });
+        writeln!(self.stdin, "{},", event).unwrap();
+    }
+
+    /// Terminates the bar like a session that ends and returns the lines it printed until it exited
+    pub fn terminate(&mut self) -> Vec<String> {
+        kill(Pid::from_raw(self.child.id() as i32), Signal::SIGTERM).unwrap();
+        let mut lines = Vec::new();
+        loop {
+            match self.lines.recv_timeout(TIMEOUT) {
+                Ok(line) => lines.push(line),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => panic!("the bar didn't exit"),
+            }
+        }
+        let status = self.child.wait().unwrap();
+        assert!(status.success(), "the bar exited with {}", status);
+        lines
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The texts of the widgets of the blocks, leaving out the separators, which have no name
+pub fn texts(status: &[Value]) -> Vec<String> {
+    status
+        .iter()
+        .filter(|widget| widget["name"].is_string())
+        .filter_map(|widget| Some(widget["full_text"].as_str()?.trim().to_string()))
+        .collect()
+}
+
+/// The widgets of the block at `block` in the config
+pub fn widgets(status: &[Value], block: usize) -> Vec<&Value> {
+    let name = block.to_string();
+    status
+        .iter()
+        .filter(|widget| widget["name"] == name.as_str())
+        .collect()
+}